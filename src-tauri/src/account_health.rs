@@ -0,0 +1,167 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::DATE;
+use reqwest::{Response, StatusCode};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult};
+use crate::sync_provider;
+
+/// Clocks further apart than this break sign-ins and put sync times in
+/// the wrong order.
+const MAX_SKEW_SECONDS: i64 = 300;
+
+/// How one account's connection looks, for the account settings page.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccountHealth {
+    pub account_id: String,
+    pub name: String,
+    /// Whether the service answered at all.
+    pub reachable: bool,
+    /// Whether it took the account's credentials.
+    pub authorized: bool,
+    /// Scopes the app needs that the credentials weren't granted.
+    pub missing_scopes: Vec<String>,
+    /// How far the service's clock is ahead of this device's, when it
+    /// said.
+    pub clock_skew_seconds: Option<i64>,
+    /// What to do about each problem found; empty when all is well.
+    pub diagnostics: Vec<String>,
+}
+
+impl AccountHealth {
+    pub fn new(account_id: &str, name: &str) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// The saved credentials couldn't be loaded.
+    pub fn no_credentials(&mut self, error: &CommandError) {
+        self.diagnostics.push(format!("Re-authorize: {error}"));
+    }
+
+    /// Note how a request to `service` went: whether it answered, its
+    /// clock, and whether it took the credentials. Returns the response
+    /// when it succeeded.
+    pub fn answer(
+        &mut self,
+        service: &str,
+        result: Result<Response, reqwest::Error>,
+    ) -> Option<Response> {
+        self.read(
+            service,
+            result,
+            &[StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN],
+        )
+    }
+
+    /// `answer` for an OAuth token endpoint, which turns a revoked grant
+    /// away with 400.
+    pub fn answer_sign_in(
+        &mut self,
+        service: &str,
+        result: Result<Response, reqwest::Error>,
+    ) -> Option<Response> {
+        self.read(
+            service,
+            result,
+            &[StatusCode::BAD_REQUEST, StatusCode::UNAUTHORIZED],
+        )
+    }
+
+    fn read(
+        &mut self,
+        service: &str,
+        result: Result<Response, reqwest::Error>,
+        refusals: &[StatusCode],
+    ) -> Option<Response> {
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.diagnostics.push(format!(
+                    "Can't reach {service}: {e}; check the connection and the server address"
+                ));
+                return None;
+            }
+        };
+        self.reachable = true;
+        if self.clock_skew_seconds.is_none() {
+            self.clock_skew_seconds = skew(&response, Utc::now());
+        }
+        let status = response.status();
+        if refusals.contains(&status) {
+            self.authorized = false;
+            self.diagnostics.push(format!(
+                "Re-authorize: {service} refused the account's credentials"
+            ));
+            return None;
+        }
+        if !status.is_success() {
+            self.diagnostics
+                .push(format!("{service} answered HTTP {}", status.as_u16()));
+            return None;
+        }
+        self.authorized = true;
+        Some(response)
+    }
+
+    /// Note each of `needed`, as (scope, what it's called in messages),
+    /// missing from `granted`, a space- or comma-separated list.
+    pub fn scopes(&mut self, granted: &str, needed: &[(&str, &str)]) {
+        let granted: Vec<String> = granted
+            .split([' ', ','])
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+        for (scope, label) in needed {
+            if !granted.contains(&scope.to_lowercase()) {
+                self.missing_scope(label);
+            }
+        }
+    }
+
+    pub fn missing_scope(&mut self, label: &str) {
+        self.missing_scopes.push(label.to_string());
+        self.diagnostics
+            .push(format!("Re-authorize: missing {label} scope"));
+    }
+
+    /// Add the clock's diagnostic, once every request is in.
+    pub fn finish(mut self) -> Self {
+        if let Some(seconds) = self.clock_skew_seconds {
+            if seconds.abs() > MAX_SKEW_SECONDS {
+                let way = if seconds > 0 { "behind" } else { "ahead" };
+                self.diagnostics.push(format!(
+                    "This device's clock is {} minutes {way}; set it to the right time",
+                    seconds.abs() / 60
+                ));
+            }
+        }
+        self
+    }
+}
+
+/// Seconds the server's `Date` is ahead of `now`.
+fn skew(response: &Response, now: DateTime<Utc>) -> Option<i64> {
+    let date = response.headers().get(DATE)?.to_str().ok()?;
+    let at = DateTime::parse_from_rfc2822(date).ok()?;
+    Some((at.with_timezone(&Utc) - now).num_seconds())
+}
+
+/// Check `account_id`, or every account of `provider`: that its
+/// credentials load and are taken, with the scopes syncs need, that the
+/// service answers, and that the clocks agree.
+#[tauri::command]
+pub async fn check_account(
+    db: State<'_, Db>,
+    provider: String,
+    account_id: Option<String>,
+) -> CommandResult<Vec<AccountHealth>> {
+    sync_provider::find(&provider)?
+        .check(&db, account_id.as_deref())
+        .await
+}
@@ -0,0 +1,414 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewWindow};
+
+use crate::db::Db;
+use crate::error::CommandResult;
+use crate::time_entries;
+
+/// Maximum number of recent projects offered in the jump list / dock menu.
+const MAX_RECENT_PROJECTS: usize = 5;
+
+/// Actions that can be triggered from outside the webview: keyboard shortcut
+/// bridge, taskbar jump list entries, desktop file actions, or a second
+/// launch of the binary with `--action=...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuickAction {
+    NewTask,
+    LogTime,
+    StartLastTimer,
+    OpenProject(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenProjectPayload {
+    project: String,
+}
+
+pub struct QuickActionState {
+    pub recent_projects: Mutex<Vec<String>>,
+}
+
+impl QuickActionState {
+    pub fn new() -> Self {
+        Self {
+            recent_projects: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl QuickAction {
+    /// Parse `--action=<name>` (and `--project=<name>` for open-project)
+    /// out of a process argument list. Unknown actions are ignored.
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let mut action = None;
+        let mut project = None;
+        for arg in args {
+            if let Some(value) = arg.strip_prefix("--action=") {
+                action = Some(value.to_string());
+            } else if let Some(value) = arg.strip_prefix("--project=") {
+                project = Some(value.to_string());
+            }
+        }
+
+        match action.as_deref()? {
+            "new-task" => Some(Self::NewTask),
+            "log-time" => Some(Self::LogTime),
+            "start-last-timer" => Some(Self::StartLastTimer),
+            "open-project" => project
+                .filter(|p| !p.trim().is_empty())
+                .map(Self::OpenProject),
+            _ => None,
+        }
+    }
+
+    /// Frontend event name, or `None` for actions the backend carries out
+    /// itself. New-task and log-time reuse the existing shortcut events so
+    /// the layout's listeners handle them unchanged.
+    fn event_name(&self) -> Option<&'static str> {
+        match self {
+            Self::NewTask => Some("daylight:shortcut:add-task"),
+            Self::LogTime => Some("daylight:shortcut:log-time"),
+            Self::StartLastTimer => None,
+            Self::OpenProject(_) => Some("daylight:action:open-project"),
+        }
+    }
+}
+
+/// Deliver an action to the main window both as a Tauri event and as a DOM
+/// CustomEvent, since the Tauri listener is only attached once invoke is ready.
+pub fn dispatch_to_window(window: &WebviewWindow, action: &QuickAction) {
+    let Some(event) = action.event_name() else {
+        run_in_backend(window.app_handle(), action);
+        return;
+    };
+    let (emit_result, script) = match action {
+        QuickAction::OpenProject(project) => {
+            let payload = OpenProjectPayload {
+                project: project.clone(),
+            };
+            let detail = serde_json::to_string(&payload).unwrap_or_else(|_| "null".to_string());
+            (
+                window.emit(event, payload),
+                format!("window.dispatchEvent(new CustomEvent('{event}', {{ detail: {detail} }}));"),
+            )
+        }
+        _ => (
+            window.emit(event, ()),
+            format!("window.dispatchEvent(new CustomEvent('{event}'));"),
+        ),
+    };
+
+    if let Err(error) = emit_result {
+        tracing::warn!("emit {event} failed: {error}");
+    }
+    if let Err(error) = window.eval(&script) {
+        tracing::warn!("eval {event} failed: {error}");
+    }
+}
+
+/// Carry out an action that needs no window. The frontend follows along
+/// through the usual change events.
+fn run_in_backend(app: &AppHandle, action: &QuickAction) {
+    if *action == QuickAction::StartLastTimer {
+        let db = app.state::<Db>();
+        match time_entries::restart_last(app, &db) {
+            Ok(Some(_)) => {}
+            Ok(None) => tracing::info!("no timer to restart"),
+            Err(error) => tracing::warn!("restarting last timer failed: {error}"),
+        }
+    }
+}
+
+/// Show, unminimize and focus the main window, returning it if it exists.
+pub fn focus_main_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let window = app.get_webview_window("main")?;
+    #[cfg(desktop)]
+    {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    Some(window)
+}
+
+/// Bring the main window forward and route the action to it.
+pub fn dispatch(app: &AppHandle, action: &QuickAction) {
+    if action.event_name().is_none() {
+        run_in_backend(app, action);
+        return;
+    }
+    match focus_main_window(app) {
+        Some(window) => dispatch_to_window(&window, action),
+        None => tracing::warn!("main window missing for {action:?}"),
+    }
+}
+
+/// Handle the arguments of a launch (first instance or forwarded from a
+/// second instance). Returns true if an action was dispatched.
+pub fn handle_launch_args(app: &AppHandle, args: &[String]) -> bool {
+    match QuickAction::from_args(args) {
+        Some(action) => {
+            dispatch(app, &action);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Called by the frontend whenever the set of recently used projects changes,
+/// so the OS-level quick action menus stay current.
+#[tauri::command]
+#[cfg_attr(not(target_os = "macos"), allow(unused_variables))]
+pub fn set_recent_projects(
+    app: AppHandle,
+    state: State<'_, QuickActionState>,
+    projects: Vec<String>,
+) -> CommandResult<()> {
+    let mut recent: Vec<String> = Vec::new();
+    for project in projects {
+        let trimmed = project.trim();
+        if trimmed.is_empty() || recent.iter().any(|p| p == trimmed) {
+            continue;
+        }
+        recent.push(trimmed.to_string());
+        if recent.len() == MAX_RECENT_PROJECTS {
+            break;
+        }
+    }
+
+    let mut guard = state.recent_projects.lock().map_err(|_| "Lock poisoned")?;
+    *guard = recent;
+
+    #[cfg(target_os = "windows")]
+    windows_jump_list::update(&guard)?;
+
+    #[cfg(target_os = "macos")]
+    macos_dock_menu::update(&app, &guard)?;
+
+    Ok(())
+}
+
+/// Give the dock icon its menu before the frontend reports any projects.
+#[cfg(target_os = "macos")]
+pub fn setup_dock_menu(app: &AppHandle) {
+    if let Err(error) = macos_dock_menu::update(app, &[]) {
+        tracing::warn!("{error}");
+    }
+}
+
+/// Populate the taskbar jump list. Entries relaunch the executable with
+/// `--action=...`; the single-instance plugin forwards those args back into
+/// `handle_launch_args` in the running process.
+#[cfg(target_os = "windows")]
+mod windows_jump_list {
+    use windows::core::{Interface, HSTRING};
+    use windows::Win32::Storage::EnhancedStorage::PKEY_Title;
+    use windows::Win32::System::Com::StructuredStorage::PROPVARIANT;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::Common::{IObjectArray, IObjectCollection};
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::UI::Shell::{
+        DestinationList, EnumerableObjectCollection, ICustomDestinationList, IShellLinkW,
+        ShellLink,
+    };
+
+    use crate::error::CommandResult;
+
+    fn shell_link(exe: &HSTRING, title: &str, args: &str) -> windows::core::Result<IShellLinkW> {
+        unsafe {
+            let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+            link.SetPath(exe)?;
+            link.SetArguments(&HSTRING::from(args))?;
+            link.SetDescription(&HSTRING::from(title))?;
+            let store: IPropertyStore = link.cast()?;
+            store.SetValue(&PKEY_Title, &PROPVARIANT::from(title))?;
+            store.Commit()?;
+            Ok(link)
+        }
+    }
+
+    pub fn update(projects: &[String]) -> CommandResult<()> {
+        let exe = std::env::current_exe()?;
+        let exe = HSTRING::from(exe.as_os_str());
+
+        let result: windows::core::Result<()> = unsafe {
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            (|| {
+                let list: ICustomDestinationList =
+                    CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+                let mut slots = 0u32;
+                let _removed: IObjectArray = list.BeginList(&mut slots)?;
+
+                let tasks: IObjectCollection =
+                    CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+                tasks.AddObject(&shell_link(&exe, "New task", "--action=new-task")?)?;
+                tasks.AddObject(&shell_link(
+                    &exe,
+                    "Start last timer",
+                    "--action=start-last-timer",
+                )?)?;
+                list.AddUserTasks(&tasks.cast::<IObjectArray>()?)?;
+
+                if !projects.is_empty() {
+                    let recent: IObjectCollection =
+                        CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+                    for project in projects {
+                        let args = format!("--action=open-project \"--project={project}\"");
+                        recent.AddObject(&shell_link(&exe, project, &args)?)?;
+                    }
+                    list.AppendCategory(
+                        &HSTRING::from("Recent projects"),
+                        &recent.cast::<IObjectArray>()?,
+                    )?;
+                }
+
+                list.CommitList()
+            })()
+        };
+
+        result.map_err(|e| format!("Failed to update jump list: {e}").into())
+    }
+}
+
+/// The dock icon's menu: the jump list's tasks, then recent projects. AppKit
+/// asks the app delegate for it through `applicationDockMenu:`, which tao's
+/// delegate doesn't implement, so the method is added to its class.
+#[cfg(target_os = "macos")]
+mod macos_dock_menu {
+    use std::cell::RefCell;
+    use std::sync::{Once, OnceLock};
+
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject, Imp, Sel};
+    use objc2::{define_class, msg_send, sel, MainThreadMarker, MainThreadOnly};
+    use objc2_app_kit::{NSApplication, NSMenu, NSMenuItem};
+    use objc2_foundation::{NSObject, NSString};
+    use tauri::AppHandle;
+
+    use super::QuickAction;
+    use crate::error::CommandResult;
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+    static INSTALL: Once = Once::new();
+
+    thread_local! {
+        static MENU: RefCell<Option<Retained<NSMenu>>> = const { RefCell::new(None) };
+        /// What each item does, indexed by its tag.
+        static ITEMS: RefCell<Vec<QuickAction>> = const { RefCell::new(Vec::new()) };
+        static TARGET: RefCell<Option<Retained<MenuTarget>>> = const { RefCell::new(None) };
+    }
+
+    define_class!(
+        // SAFETY: NSObject has no subclassing requirements and MenuTarget
+        // doesn't implement Drop.
+        #[unsafe(super(NSObject))]
+        #[thread_kind = MainThreadOnly]
+        #[name = "DayLightDockMenuTarget"]
+        struct MenuTarget;
+
+        impl MenuTarget {
+            #[unsafe(method(runAction:))]
+            fn run_action(&self, item: &NSMenuItem) {
+                let action = ITEMS.with(|items| items.borrow().get(item.tag() as usize).cloned());
+                if let (Some(app), Some(action)) = (APP.get(), action) {
+                    super::dispatch(app, &action);
+                }
+            }
+        }
+    );
+
+    unsafe extern "C-unwind" fn application_dock_menu(
+        _this: *mut AnyObject,
+        _cmd: Sel,
+        _sender: *mut AnyObject,
+    ) -> *mut NSMenu {
+        MENU.with(|menu| {
+            menu.borrow()
+                .as_ref()
+                .map_or(std::ptr::null_mut(), |menu| Retained::as_ptr(menu).cast_mut())
+        })
+    }
+
+    fn install(mtm: MainThreadMarker) {
+        INSTALL.call_once(|| {
+            let app = NSApplication::sharedApplication(mtm);
+            let Some(delegate) = app.delegate() else {
+                tracing::warn!("no app delegate to give a dock menu");
+                return;
+            };
+            let delegate: &AnyObject = (*delegate).as_ref();
+            let class: *const AnyClass = delegate.class();
+            // SAFETY: the signature matches the `@@:@` type encoding of
+            // `-(NSMenu *)applicationDockMenu:(NSApplication *)sender`.
+            unsafe {
+                let imp: Imp = std::mem::transmute(
+                    application_dock_menu
+                        as unsafe extern "C-unwind" fn(
+                            *mut AnyObject,
+                            Sel,
+                            *mut AnyObject,
+                        ) -> *mut NSMenu,
+                );
+                objc2::ffi::class_addMethod(
+                    class.cast_mut(),
+                    sel!(applicationDockMenu:),
+                    imp,
+                    c"@@:@".as_ptr(),
+                );
+            }
+        });
+    }
+
+    fn build(mtm: MainThreadMarker, actions: &[(String, QuickAction)]) {
+        let target = TARGET.with(|target| {
+            target
+                .borrow_mut()
+                .get_or_insert_with(|| unsafe { msg_send![MenuTarget::alloc(mtm), init] })
+                .clone()
+        });
+        let target: &AnyObject = &target;
+        let menu = NSMenu::new(mtm);
+        for (tag, (title, _)) in actions.iter().enumerate() {
+            // SAFETY: `runAction:` is implemented by the item's target.
+            let item = unsafe {
+                let item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                    NSMenuItem::alloc(mtm),
+                    &NSString::from_str(title),
+                    Some(sel!(runAction:)),
+                    &NSString::from_str(""),
+                );
+                item.setTarget(Some(target));
+                item
+            };
+            item.setTag(tag as isize);
+            menu.addItem(&item);
+        }
+        ITEMS.with(|items| *items.borrow_mut() = actions.iter().map(|(_, a)| a.clone()).collect());
+        MENU.with(|slot| *slot.borrow_mut() = Some(menu));
+    }
+
+    pub fn update(app: &AppHandle, projects: &[String]) -> CommandResult<()> {
+        let _ = APP.set(app.clone());
+        let mut actions = vec![
+            ("New task".to_string(), QuickAction::NewTask),
+            ("Start last timer".to_string(), QuickAction::StartLastTimer),
+        ];
+        actions.extend(
+            projects
+                .iter()
+                .map(|project| (project.clone(), QuickAction::OpenProject(project.clone()))),
+        );
+        app.run_on_main_thread(move || {
+            let Some(mtm) = MainThreadMarker::new() else {
+                return;
+            };
+            install(mtm);
+            build(mtm, &actions);
+        })
+        .map_err(|e| format!("Failed to update dock menu: {e}").into())
+    }
+}
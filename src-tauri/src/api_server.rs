@@ -0,0 +1,398 @@
+use std::fs;
+use std::io::{Cursor, Read};
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as Json};
+use tauri::{AppHandle, Manager};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::credentials::{self, CredentialMigration};
+use crate::data_dir;
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::ics_feed::{new_token, token_matches};
+use crate::reports;
+use crate::session::write_atomic;
+use crate::task_store;
+use crate::time_entries;
+
+const CONFIG_FILE: &str = "api_server.json";
+const DEFAULT_PORT: u16 = 47616;
+/// Every route lives under this prefix, so later versions can sit beside it.
+const PREFIX: &str = "/v1";
+const MAX_BODY: u64 = 1024 * 1024;
+
+/// The running server and its thread, kept so it can be stopped when the
+/// config changes.
+static SERVER: Mutex<Option<(Arc<Server>, JoinHandle<()>)>> = Mutex::new(None);
+/// Why the server couldn't start, for the settings screen.
+static START_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiServerConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Sent as `Authorization: Bearer <token>` with every request.
+    /// Generated on first use.
+    pub token: String,
+}
+
+impl Default for ApiServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiServerStatus {
+    pub config: ApiServerConfig,
+    pub running: bool,
+    /// Where the API is reached, when it's running.
+    pub url: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StartTimer {
+    task_id: String,
+    note: Option<String>,
+    billable: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntryRange {
+    from: String,
+    to: String,
+    task_id: Option<String>,
+}
+
+type Reply = Result<(u16, Json), (u16, String)>;
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+/// The config as saved, with the token only there on platforms without a
+/// keyring or from before it moved to one.
+fn load_file(app: &AppHandle) -> ApiServerConfig {
+    let Ok(path) = config_path(app) else {
+        return ApiServerConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            ApiServerConfig::default()
+        }),
+        Err(_) => ApiServerConfig::default(),
+    }
+}
+
+pub fn load_config(app: &AppHandle) -> ApiServerConfig {
+    let mut config = load_file(app);
+    if config.token.is_empty() {
+        config.token = credentials::load(credentials::API_TOKEN).unwrap_or_default();
+    }
+    config
+}
+
+/// Save the config, keeping the token in the keyring where there is one.
+fn save_config(app: &AppHandle, config: &ApiServerConfig) -> CommandResult<()> {
+    let path = config_path(app)?;
+    let mut file = config.clone();
+    if !config.token.is_empty()
+        && credentials::save(credentials::API_TOKEN, Some(&config.token)).is_ok()
+    {
+        file.token.clear();
+    }
+    let body = serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)
+}
+
+/// Move a token still in the config file into the keyring, and out of the
+/// file.
+pub fn migrate_token(app: &AppHandle, run: &mut CredentialMigration) {
+    let file = load_file(app);
+    if file.token.is_empty() || !run.move_secret(credentials::API_TOKEN, CONFIG_FILE, &file.token) {
+        return;
+    }
+    if let Err(e) = save_config(app, &file) {
+        tracing::warn!("failed to remove the token from {CONFIG_FILE}: {e}");
+    }
+}
+
+fn status(app: &AppHandle) -> ApiServerStatus {
+    let config = load_config(app);
+    let running = SERVER.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    ApiServerStatus {
+        url: running.then(|| format!("http://{}:{}{PREFIX}", Ipv4Addr::LOCALHOST, config.port)),
+        running,
+        error: START_ERROR
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+        config,
+    }
+}
+
+/// A query string as a JSON object to deserialize a filter or report query
+/// from. Keys in `lists` collect every value given for them; `true` and
+/// `false` become booleans for keys in `bools`.
+fn query_object(query: &str, lists: &[&str], bools: &[&str]) -> Json {
+    let mut object = Map::new();
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        let key = key.into_owned();
+        let value = match value.as_ref() {
+            "true" if bools.contains(&key.as_str()) => Json::Bool(true),
+            "false" if bools.contains(&key.as_str()) => Json::Bool(false),
+            _ => Json::String(value.into_owned()),
+        };
+        if lists.contains(&key.as_str()) {
+            let list = object.entry(key).or_insert_with(|| json!([]));
+            if let Json::Array(items) = list {
+                items.push(value);
+            }
+        } else {
+            object.insert(key, value);
+        }
+    }
+    Json::Object(object)
+}
+
+fn from_query<T: DeserializeOwned>(
+    query: &str,
+    lists: &[&str],
+    bools: &[&str],
+) -> Result<T, (u16, String)> {
+    serde_json::from_value(query_object(query, lists, bools))
+        .map_err(|e| (400, format!("Invalid query: {e}")))
+}
+
+fn from_body<T: DeserializeOwned>(body: &str) -> Result<T, (u16, String)> {
+    serde_json::from_str(body).map_err(|e| (400, format!("Invalid JSON body: {e}")))
+}
+
+/// The HTTP status for a failed command.
+fn http_status(code: ErrorCode) -> u16 {
+    match code {
+        ErrorCode::NotFound => 404,
+        ErrorCode::InvalidInput => 400,
+        ErrorCode::Unauthorized => 401,
+        ErrorCode::Conflict => 409,
+        ErrorCode::RateLimited => 429,
+        ErrorCode::Network | ErrorCode::Unavailable => 502,
+        ErrorCode::Locked | ErrorCode::Busy => 503,
+        ErrorCode::Storage | ErrorCode::Internal => 500,
+    }
+}
+
+/// A command's result as a reply, with the status its error code maps to.
+fn reply<T: Serialize>(success: u16, result: CommandResult<T>) -> Reply {
+    match result {
+        Ok(value) => Ok((success, serde_json::to_value(value).unwrap_or_default())),
+        Err(e) => Err((http_status(e.code), e.message)),
+    }
+}
+
+fn route(app: &AppHandle, method: &Method, path: &str, query: &str, body: &str) -> Reply {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let db = || app.state::<Db>();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["tasks"]) => {
+            let filter = from_query(query, &["tags_all", "tags_any", "tags_none"], &["blocked"])?;
+            reply(200, task_store::list_tasks(db(), Some(filter)))
+        }
+        (Method::Post, ["tasks"]) => reply(201, task_store::create_task(db(), from_body(body)?)),
+        (Method::Get, ["tasks", id]) => match task_store::get_task(db(), id.to_string()) {
+            Ok(None) => Err((404, format!("Task not found: {id}"))),
+            result => reply(200, result),
+        },
+        (Method::Patch, ["tasks", id]) => reply(
+            200,
+            task_store::update_task(app.clone(), db(), id.to_string(), from_body(body)?),
+        ),
+        (Method::Delete, ["tasks", id]) => reply(
+            200,
+            task_store::delete_task(app.clone(), db(), id.to_string()),
+        ),
+        (Method::Get, ["timer"]) => reply(200, time_entries::get_running_entry(db())),
+        (Method::Post, ["timer", "start"]) => {
+            let input: StartTimer = from_body(body)?;
+            reply(
+                201,
+                time_entries::start_entry(
+                    app.clone(),
+                    db(),
+                    input.task_id,
+                    input.note,
+                    input.billable,
+                ),
+            )
+        }
+        (Method::Post, ["timer", "stop"]) => {
+            reply(200, time_entries::stop_entry(app.clone(), db()))
+        }
+        (Method::Get, ["entries"]) => {
+            let range: EntryRange = from_query(query, &[], &[])?;
+            reply(
+                200,
+                time_entries::list_entries(db(), range.from, range.to, range.task_id),
+            )
+        }
+        (Method::Get, ["reports", "time"]) => reply(
+            200,
+            reports::report_time(db(), from_query(query, &[], &[])?),
+        ),
+        (Method::Get, ["reports", "completions"]) => reply(
+            200,
+            reports::report_completions(db(), from_query(query, &[], &[])?),
+        ),
+        (_, ["tasks"] | ["tasks", _] | ["timer"] | ["timer", _] | ["entries"] | ["reports", _]) => {
+            Err((405, format!("{method} isn't supported on {PREFIX}{path}")))
+        }
+        _ => Err((404, format!("No such endpoint: {method} {PREFIX}{path}"))),
+    }
+}
+
+fn json_response(status: u16, body: &Json) -> Response<Cursor<Vec<u8>>> {
+    let mut response = Response::from_string(body.to_string()).with_status_code(status);
+    if let Ok(header) = Header::from_bytes("Content-Type", "application/json") {
+        response.add_header(header);
+    }
+    response
+}
+
+fn respond(app: &AppHandle, token: &str, request: &mut Request) -> Response<Cursor<Vec<u8>>> {
+    let authorized = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given.trim(), token));
+    if !authorized {
+        return json_response(401, &json!({ "error": "Missing or wrong API token" }));
+    }
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let Some(path) = path.strip_prefix(PREFIX) else {
+        return json_response(
+            404,
+            &json!({ "error": format!("No such endpoint: {path}") }),
+        );
+    };
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().take(MAX_BODY).read_to_string(&mut body) {
+        return json_response(400, &json!({ "error": format!("Unreadable body: {e}") }));
+    }
+    match route(app, request.method(), path, query, &body) {
+        Ok((status, value)) => json_response(status, &value),
+        Err((status, error)) => json_response(status, &json!({ "error": error })),
+    }
+}
+
+/// Stop the server and wait for its thread, so the port is free again.
+fn stop() {
+    let running = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some((server, thread)) = running {
+        server.unblock();
+        drop(server);
+        let _ = thread.join();
+    }
+}
+
+/// Listen on `port`, retrying briefly: a server just stopped may not have
+/// released it yet.
+fn bind(port: u16) -> CommandResult<Server> {
+    let mut attempt = 0;
+    loop {
+        match Server::http((Ipv4Addr::LOCALHOST, port)) {
+            Ok(server) => return Ok(server),
+            Err(_) if attempt < 5 => {
+                attempt += 1;
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(format!("Can't listen on port {port}: {e}").into()),
+        }
+    }
+}
+
+/// Start the API if it's enabled, replacing a server already running so a
+/// new port or token applies. It only listens on this machine, and sends
+/// no CORS headers, so web pages can't call it.
+pub fn start(app: &AppHandle) {
+    stop();
+    *START_ERROR.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    let mut config = load_config(app);
+    if !config.enabled {
+        return;
+    }
+    if config.token.is_empty() {
+        config.token = new_token();
+        if let Err(e) = save_config(app, &config) {
+            tracing::warn!("{e}");
+        }
+    }
+    let server = match bind(config.port) {
+        Ok(server) => Arc::new(server),
+        Err(message) => {
+            tracing::warn!("{message}");
+            *START_ERROR.lock().unwrap_or_else(|e| e.into_inner()) = Some(message.to_string());
+            return;
+        }
+    };
+    let handle = app.clone();
+    let listener = server.clone();
+    let thread = std::thread::spawn(move || {
+        // Ends once `stop` unblocks the server.
+        for mut request in listener.incoming_requests() {
+            let response = respond(&handle, &config.token, &mut request);
+            let _ = request.respond(response);
+        }
+    });
+    *SERVER.lock().unwrap_or_else(|e| e.into_inner()) = Some((server, thread));
+}
+
+#[tauri::command]
+pub fn get_api_server_config(app: AppHandle) -> ApiServerStatus {
+    status(&app)
+}
+
+#[tauri::command]
+pub fn set_api_server_config(
+    app: AppHandle,
+    config: ApiServerConfig,
+) -> CommandResult<ApiServerStatus> {
+    if config.port < 1024 {
+        return Err(CommandError::invalid("Choose a port from 1024 up"));
+    }
+    // The token is only changed by regenerating it.
+    let config = ApiServerConfig {
+        token: load_config(&app).token,
+        ..config
+    };
+    save_config(&app, &config)?;
+    start(&app);
+    Ok(status(&app))
+}
+
+/// Replace the token, locking out every client using the old one.
+#[tauri::command]
+pub fn regenerate_api_token(app: AppHandle) -> CommandResult<ApiServerStatus> {
+    let config = ApiServerConfig {
+        token: new_token(),
+        ..load_config(&app)
+    };
+    save_config(&app, &config)?;
+    start(&app);
+    Ok(status(&app))
+}
@@ -0,0 +1,465 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::{self, format_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::migrations;
+use crate::reports;
+use crate::search::{self, SearchFilters, SearchHit};
+use crate::task_store::{self, Task, TaskFilter};
+use crate::timezone;
+
+/// Emitted with an `ArchiveReport` after rows move into or out of the
+/// archive; task and time views should reload.
+pub const ARCHIVE_CHANGED_EVENT: &str = "archive-changed";
+
+const ARCHIVE_FILE: &str = "daylight-archive.db";
+
+/// Per-task tables moved along with their task. `task_tags` is handled
+/// separately because tag ids can differ between the two databases.
+const TASK_CHILDREN: &[&str] = &[
+    "time_entries",
+    "solar_reminders",
+    "reminders",
+    "external_refs",
+    "attachments",
+];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArchiveReport {
+    pub tasks: usize,
+    pub time_entries: usize,
+}
+
+/// The archive lives next to the database and shares its key.
+pub fn archive_path(db_path: &Path) -> PathBuf {
+    db_path.with_file_name(ARCHIVE_FILE)
+}
+
+/// Open (creating if needed) the archive with the same schema as the main
+/// database. History triggers are dropped: rows arrive with their history
+/// already written, and nothing edits archived tasks in place.
+pub fn open_archive(path: &Path, key: Option<&str>) -> CommandResult<Connection> {
+    let mut conn = Connection::open(path).context("Failed to open archive")?;
+    db::apply_key(&conn, key)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")
+        .context("Failed to configure archive")?;
+    migrations::run_migrations(&mut conn)?;
+
+    let triggers: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM sqlite_master
+             WHERE type = 'trigger' AND name LIKE 'history\\_%' ESCAPE '\\'",
+        )
+        .and_then(|mut stmt| {
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect()
+        })
+        .context("Failed to prepare archive")?;
+    for name in triggers {
+        conn.execute_batch(&format!("DROP TRIGGER \"{name}\""))
+            .context("Failed to prepare archive")?;
+    }
+    Ok(conn)
+}
+
+/// Open the archive belonging to `db`, if one has been created.
+fn existing_archive(db: &Db) -> CommandResult<Option<Connection>> {
+    let path = archive_path(db.path());
+    if !path.exists() {
+        return Ok(None);
+    }
+    open_archive(&path, db.key().as_deref()).map(Some)
+}
+
+/// Run `f` with the archive attached to `conn` as `archive`.
+fn with_attached<T>(
+    conn: &mut Connection,
+    path: &Path,
+    key: Option<&str>,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    conn.execute(
+        "ATTACH DATABASE ?1 AS archive KEY ?2",
+        params![path.display().to_string(), key.unwrap_or("")],
+    )?;
+    let result = f(conn);
+    let detached = conn.execute_batch("DETACH DATABASE archive");
+    let result = result?;
+    detached?;
+    Ok(result)
+}
+
+fn columns(conn: &Connection, schema: &str, table: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA {schema}.table_info({table})"))?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    rows.collect()
+}
+
+/// Copy the rows of `table` matching `filter` from one schema to the other.
+/// With `overwrite`, rows already in `to` are updated by primary key
+/// (`id`); otherwise they are left alone.
+fn copy_rows(
+    conn: &Connection,
+    table: &str,
+    from: &str,
+    to: &str,
+    filter: &str,
+    overwrite: bool,
+) -> rusqlite::Result<usize> {
+    let cols = columns(conn, to, table)?;
+    let list = cols
+        .iter()
+        .map(|c| format!("\"{c}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let on_conflict = if overwrite {
+        let sets = cols
+            .iter()
+            .filter(|c| *c != "id")
+            .map(|c| format!("\"{c}\" = excluded.\"{c}\""))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("ON CONFLICT(id) DO UPDATE SET {sets}")
+    } else {
+        "ON CONFLICT DO NOTHING".to_string()
+    };
+    conn.execute(
+        &format!(
+            "INSERT INTO {to}.{table} ({list})
+             SELECT {list} FROM {from}.{table} WHERE {filter} {on_conflict}"
+        ),
+        [],
+    )
+}
+
+/// Move the tasks in `temp.moving` and the entries in `temp.moving_entries`
+/// from schema `from` to `to`. Tasks in `temp.copied` but not `moving` are
+/// copied only, so entries moved without their task still resolve. The
+/// main database's copy of a task always wins over the archive's.
+fn transfer(conn: &Connection, from: &str, to: &str) -> rusqlite::Result<ArchiveReport> {
+    const MOVING: &str = "SELECT id FROM temp.moving";
+    const COPIED: &str = "SELECT id FROM temp.copied";
+
+    // Tasks new to `to` bring their history; ones already there keep theirs.
+    conn.execute_batch(&format!(
+        "CREATE TEMP TABLE fresh (id TEXT PRIMARY KEY);
+         INSERT INTO temp.fresh SELECT id FROM temp.moving
+             WHERE id NOT IN (SELECT id FROM {to}.tasks);"
+    ))?;
+
+    let tasks = count(conn, "temp.moving")?;
+    copy_rows(
+        conn,
+        "tasks",
+        from,
+        to,
+        &format!("id IN ({COPIED})"),
+        to == "archive",
+    )?;
+    // A subtask that arrives without its parent stands on its own there.
+    conn.execute(
+        &format!(
+            "UPDATE {to}.tasks SET parent_id = NULL
+             WHERE id IN ({COPIED}) AND parent_id NOT IN (SELECT id FROM {to}.tasks)"
+        ),
+        [],
+    )?;
+
+    copy_rows(
+        conn,
+        "tags",
+        from,
+        to,
+        &format!("id IN (SELECT tag_id FROM {from}.task_tags WHERE task_id IN ({COPIED}))"),
+        false,
+    )?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {to}.task_tags (task_id, tag_id)
+             SELECT tt.task_id, (SELECT d.id FROM {to}.tags d WHERE d.name = g.name)
+             FROM {from}.task_tags tt JOIN {from}.tags g ON g.id = tt.tag_id
+             WHERE tt.task_id IN ({COPIED})
+             ON CONFLICT DO NOTHING"
+        ),
+        [],
+    )?;
+
+    let mut time_entries = 0;
+    for table in TASK_CHILDREN {
+        let copied = copy_rows(
+            conn,
+            table,
+            from,
+            to,
+            &format!("task_id IN ({MOVING})"),
+            false,
+        )?;
+        if *table == "time_entries" {
+            time_entries += copied;
+        }
+    }
+    time_entries += copy_rows(
+        conn,
+        "time_entries",
+        from,
+        to,
+        "id IN (SELECT id FROM temp.moving_entries)",
+        false,
+    )?;
+
+    let history = "task_id, field, old_value, new_value, changed_at, source";
+    conn.execute_batch(&format!(
+        "DELETE FROM {to}.task_history WHERE task_id IN (SELECT id FROM temp.fresh);
+         INSERT INTO {to}.task_history ({history})
+             SELECT {history} FROM {from}.task_history
+             WHERE task_id IN (SELECT id FROM temp.fresh) ORDER BY id;
+
+         DELETE FROM {from}.time_entries WHERE id IN (SELECT id FROM temp.moving_entries);
+         DELETE FROM {from}.tasks WHERE id IN ({MOVING});
+         DELETE FROM {from}.task_history WHERE task_id IN ({MOVING});
+         DROP TABLE temp.fresh;"
+    ))?;
+
+    Ok(ArchiveReport {
+        tasks,
+        time_entries,
+    })
+}
+
+fn count(conn: &Connection, table: &str) -> rusqlite::Result<usize> {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .map(|n| n as usize)
+}
+
+/// Run `f` inside a transaction with the scratch id tables in place and
+/// journaling paused. Moving rows between databases isn't undoable: undo
+/// only knows about the main database and would duplicate them.
+fn in_move<T>(
+    conn: &mut Connection,
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+) -> rusqlite::Result<T> {
+    let tx = conn.transaction()?;
+    // A subtask can arrive ahead of its parent, or without it.
+    tx.execute_batch(
+        "PRAGMA defer_foreign_keys = ON;
+         CREATE TEMP TABLE moving (id TEXT PRIMARY KEY);
+         CREATE TEMP TABLE copied (id TEXT PRIMARY KEY);
+         CREATE TEMP TABLE moving_entries (id TEXT PRIMARY KEY);
+         UPDATE main.journal_control SET paused = 1;",
+    )?;
+    let result = f(&tx)?;
+    tx.execute_batch(
+        "UPDATE main.journal_control SET paused = 0;
+         DROP TABLE temp.moving;
+         DROP TABLE temp.copied;
+         DROP TABLE temp.moving_entries;",
+    )?;
+    tx.commit()?;
+    Ok(result)
+}
+
+/// Move everything finished before `cutoff` into the attached archive:
+/// completed tasks with all their rows, and finished time entries on tasks
+/// that stay behind. The latest instance of a recurring series stays, since
+/// the next one is generated from it.
+///
+/// With the main database in WAL mode the two files don't commit
+/// atomically; rows are written to the archive before being deleted, so a
+/// crash in between leaves duplicates rather than losing anything.
+pub fn archive_before(conn: &mut Connection, cutoff: &str) -> rusqlite::Result<ArchiveReport> {
+    in_move(conn, |conn| {
+        conn.execute(
+            "INSERT INTO temp.moving
+             SELECT id FROM main.tasks t
+             WHERE status = ?2 AND completed_at IS NOT NULL AND completed_at < ?1
+               AND deleted_at IS NULL
+               AND NOT EXISTS (
+                   SELECT 1 FROM main.time_entries e
+                   WHERE e.task_id = t.id AND e.ended_at IS NULL
+               )
+               AND (series_id IS NULL OR EXISTS (
+                   SELECT 1 FROM main.tasks later
+                   WHERE later.series_id = t.series_id AND later.deleted_at IS NULL
+                     AND COALESCE(later.scheduled, later.due) > COALESCE(t.scheduled, t.due)
+               ))",
+            params![cutoff, task_store::STATUS_DONE],
+        )?;
+        // Deleting a task deletes its subtasks, so a task only moves when
+        // all of them do.
+        conn.execute_batch(
+            "WITH RECURSIVE staying(id) AS (
+                 SELECT parent_id FROM main.tasks
+                 WHERE parent_id IS NOT NULL AND id NOT IN (SELECT id FROM temp.moving)
+                 UNION SELECT t.parent_id FROM main.tasks t
+                 JOIN staying s ON t.id = s.id WHERE t.parent_id IS NOT NULL
+             )
+             DELETE FROM temp.moving WHERE id IN (SELECT id FROM staying);
+             INSERT INTO temp.copied SELECT id FROM temp.moving;",
+        )?;
+        conn.execute(
+            "INSERT INTO temp.moving_entries
+             SELECT id FROM main.time_entries
+             WHERE ended_at IS NOT NULL AND ended_at < ?1
+               AND task_id NOT IN (SELECT id FROM temp.moving)
+               AND task_id IN (SELECT id FROM main.tasks WHERE deleted_at IS NULL)",
+            params![cutoff],
+        )?;
+        conn.execute_batch(
+            "INSERT OR IGNORE INTO temp.copied
+             SELECT task_id FROM main.time_entries
+             WHERE id IN (SELECT id FROM temp.moving_entries);",
+        )?;
+        transfer(conn, "main", "archive")
+    })
+}
+
+/// Move archived tasks back into the attached main database with all their
+/// rows, including entries archived without their task.
+pub fn restore_from_archive(
+    conn: &mut Connection,
+    task_ids: &[String],
+) -> rusqlite::Result<ArchiveReport> {
+    in_move(conn, |conn| {
+        for id in task_ids {
+            conn.execute(
+                "INSERT OR IGNORE INTO temp.moving
+                 SELECT id FROM archive.tasks WHERE id = ?1",
+                params![id],
+            )?;
+        }
+        // Subtasks come back with their parent.
+        conn.execute_batch(
+            "WITH RECURSIVE subtree(id) AS (
+                 SELECT id FROM temp.moving
+                 UNION SELECT t.id FROM archive.tasks t JOIN subtree s ON t.parent_id = s.id
+             )
+             INSERT OR IGNORE INTO temp.moving SELECT id FROM subtree;
+             INSERT INTO temp.copied SELECT id FROM temp.moving;",
+        )?;
+        transfer(conn, "archive", "main")
+    })
+}
+
+/// Hashes of files attached to archived tasks, which must survive garbage
+/// collection of the attachment store.
+pub fn attachment_hashes(db: &Db) -> CommandResult<HashSet<String>> {
+    let Some(conn) = existing_archive(db)? else {
+        return Ok(HashSet::new());
+    };
+    let mut stmt = conn.prepare("SELECT DISTINCT hash FROM attachments")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Re-key the archive alongside the database. A missing archive is fine.
+pub fn rekey(db_path: &Path, key: Option<&str>, new_key: Option<&str>) -> CommandResult<()> {
+    let path = archive_path(db_path);
+    if !path.exists() {
+        return Ok(());
+    }
+    db::rekey_file(&path, key, new_key).context("Failed to re-key archive")
+}
+
+/// Open `db` with the archive attached, creating it on first use.
+fn with_archive<T>(
+    db: &Db,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> CommandResult<T> {
+    let key = db.key();
+    let path = archive_path(db.path());
+    // Make sure the archive exists and its schema is current before attaching.
+    drop(open_archive(&path, key.as_deref())?);
+    db.with_conn(|conn| with_attached(conn, &path, key.as_deref(), f))
+}
+
+fn announce(app: &AppHandle, report: &ArchiveReport) {
+    if report.tasks == 0 && report.time_entries == 0 {
+        return;
+    }
+    if report.time_entries > 0 {
+        crate::time_entries::notify(app);
+    }
+    let _ = app.emit(ARCHIVE_CHANGED_EVENT, report);
+}
+
+/// Archive tasks completed, and time entries ended, before `before`: a date
+/// (`YYYY-MM-DD`, local midnight in the system zone) or an RFC 3339 time.
+#[tauri::command]
+pub fn archive_items(
+    app: AppHandle,
+    db: State<'_, Db>,
+    before: String,
+) -> CommandResult<ArchiveReport> {
+    let tz = timezone::parse_zone(&timezone::system_zone())?;
+    let cutoff = reports::parse_bound(&before, &tz, false)?;
+    if cutoff > Utc::now() {
+        return Err(CommandError::invalid(
+            "Archive cutoff can't be in the future",
+        ));
+    }
+    let cutoff = format_utc(cutoff);
+    let report =
+        with_archive(&db, |conn| archive_before(conn, &cutoff)).context("Failed to archive")?;
+    announce(&app, &report);
+    Ok(report)
+}
+
+/// Move archived tasks back into the main database.
+#[tauri::command]
+pub fn unarchive_tasks(
+    app: AppHandle,
+    db: State<'_, Db>,
+    ids: Vec<String>,
+) -> CommandResult<ArchiveReport> {
+    let report = with_archive(&db, |conn| restore_from_archive(conn, &ids))
+        .context("Failed to restore from archive")?;
+    announce(&app, &report);
+    Ok(report)
+}
+
+/// Full-text search over the archive only; regular search never sees it.
+#[tauri::command]
+pub fn search_archive(
+    db: State<'_, Db>,
+    query: String,
+    filters: Option<SearchFilters>,
+) -> CommandResult<Vec<SearchHit>> {
+    let Some(query) = search::fts_query(&query) else {
+        return Ok(Vec::new());
+    };
+    if db.is_locked() {
+        return Err(CommandError::locked());
+    }
+    let Some(conn) = existing_archive(&db)? else {
+        return Ok(Vec::new());
+    };
+    Ok(search::search_index(
+        &conn,
+        &query,
+        &filters.unwrap_or_default(),
+    )?)
+}
+
+/// Archived tasks matching `filter`. Tasks that are still open only appear
+/// here as the owners of archived time entries.
+#[tauri::command]
+pub fn list_archived_tasks(
+    db: State<'_, Db>,
+    filter: Option<TaskFilter>,
+) -> CommandResult<Vec<Task>> {
+    if db.is_locked() {
+        return Err(CommandError::locked());
+    }
+    let Some(conn) = existing_archive(&db)? else {
+        return Ok(Vec::new());
+    };
+    Ok(task_store::query_tasks(&conn, &filter.unwrap_or_default())?)
+}
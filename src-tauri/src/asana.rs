@@ -0,0 +1,1206 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, State};
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::crdt;
+use crate::credentials;
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::http;
+use crate::outbox::{self, Replay};
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{self, Capabilities, SyncFuture, SyncMode, SyncProvider};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::tags;
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+
+/// `external_refs.source` for tasks imported from Asana; the external id
+/// is the task's gid.
+const SOURCE: &str = "asana";
+
+const API_URL: &str = "https://app.asana.com/api/1.0";
+const AUTH_URL: &str = "https://app.asana.com/-/oauth_authorize";
+const TOKEN_URL: &str = "https://app.asana.com/-/oauth_token";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const PAGE_SIZE: &str = "100";
+const MAX_PAGES: usize = 50;
+/// What the sync reads of each task.
+const TASK_FIELDS: &str = "name,notes,completed,due_on,due_at,modified_at,assignee,\
+                           assignee_section.name,projects.name";
+/// Sections every My Tasks list starts with; tasks in them get no tag.
+const DEFAULT_SECTIONS: &[&str] = &["Recently assigned", "Untitled section"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AsanaAccount {
+    pub id: String,
+    pub name: String,
+    /// The Asana user whose My Tasks are imported.
+    pub user_id: String,
+    pub workspace_id: String,
+    pub workspace_name: String,
+    /// The user's My Tasks list in the workspace.
+    pub task_list_id: String,
+    /// The OAuth client the account signed in with; `None` for a personal
+    /// access token.
+    pub client_id: Option<String>,
+    /// Where imported tasks go; `None` files each under a project named
+    /// after its first Asana project, or the inbox when it has none.
+    pub project: Option<String>,
+    pub last_synced_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Either a personal access token, or the result of the OAuth consent
+/// screen: the code from `await_oauth_code` with the redirect URI it was
+/// requested for.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewAsanaAccount {
+    /// Defaults to the Asana user's name.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+    /// The workspace to import from; the user's first when missing.
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// goes back to a project per Asana project.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AsanaAccountPatch {
+    pub name: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AsanaSyncReport {
+    pub account_id: String,
+    pub name: String,
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks completed because they were completed in Asana.
+    pub completed: usize,
+    /// Tasks trashed because they were deleted in Asana.
+    pub removed: usize,
+    /// Completions sent to Asana.
+    pub uploaded: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for AsanaSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.completed + self.removed + self.uploaded
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+/// Every answer of the API is wrapped in `data`; lists add where the next
+/// page starts.
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    data: T,
+    #[serde(default)]
+    next_page: Option<NextPage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NextPage {
+    offset: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Named {
+    gid: String,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteUser {
+    gid: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    workspaces: Vec<Named>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteTask {
+    gid: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    notes: String,
+    #[serde(default)]
+    completed: bool,
+    /// A date, for tasks due on a day.
+    #[serde(default)]
+    due_on: Option<String>,
+    /// An instant, for tasks due at a time.
+    #[serde(default)]
+    due_at: Option<String>,
+    modified_at: String,
+    #[serde(default)]
+    assignee: Option<Named>,
+    #[serde(default)]
+    assignee_section: Option<Named>,
+    #[serde(default)]
+    projects: Vec<Named>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+impl RemoteTask {
+    /// The My Tasks section it's in, unless it's one every list has.
+    fn section(&self) -> Option<String> {
+        let name = self.assignee_section.as_ref()?.name.trim();
+        if name.is_empty()
+            || DEFAULT_SECTIONS
+                .iter()
+                .any(|d| d.eq_ignore_ascii_case(name))
+        {
+            return None;
+        }
+        Some(name.to_string())
+    }
+
+    /// When Asana last changed the task, in milliseconds, as the clocks of
+    /// local edits are.
+    fn modified_ms(&self) -> i64 {
+        parse_utc(&self.modified_at).map_or(0, |at| at.timestamp_millis())
+    }
+
+    /// The due date as tasks keep it: a wall-clock time in `local` when
+    /// it's due at a time, else the date.
+    fn due(&self, local: &Tz) -> Option<String> {
+        if let Some(at) = self
+            .due_at
+            .as_deref()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        {
+            return Some(at.with_timezone(local).format("%Y-%m-%dT%H:%M").to_string());
+        }
+        self.due_on.clone()
+    }
+
+    fn assigned_to(&self, user_id: &str) -> bool {
+        self.assignee.as_ref().is_some_and(|a| a.gid == user_id)
+    }
+}
+
+fn row_to_account(row: &Row) -> rusqlite::Result<AsanaAccount> {
+    Ok(AsanaAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        user_id: row.get(2)?,
+        workspace_id: row.get(3)?,
+        workspace_name: row.get(4)?,
+        task_list_id: row.get(5)?,
+        client_id: row.get(6)?,
+        project: row.get(7)?,
+        last_synced_at: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+const ACCOUNT_COLUMNS: &str = "id, name, user_id, workspace_id, workspace_name, task_list_id, \
+                               client_id, project, last_synced_at, created_at, updated_at";
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<AsanaAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM asana_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<AsanaAccount>> {
+    conn.query_row(
+        &format!("SELECT {ACCOUNT_COLUMNS} FROM asana_accounts WHERE id = ?1"),
+        params![id],
+        row_to_account,
+    )
+    .optional()
+}
+
+fn client_secret(conn: &Connection, account_id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT client_secret FROM asana_accounts WHERE id = ?1",
+        params![account_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+}
+
+/// The entry holding the personal access token, or the OAuth refresh token
+/// when the account signed in with a client.
+fn keyring_name(account_id: &str) -> String {
+    format!("asana:{account_id}")
+}
+
+fn client() -> CommandResult<Client> {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(CommandError::from)
+}
+
+/// The token endpoint's form for `grant`, with the client's fields.
+fn token_form<'a>(
+    client_id: &'a str,
+    client_secret: &'a str,
+    grant: &[(&'a str, &'a str)],
+) -> Vec<(&'a str, &'a str)> {
+    let mut form = vec![("client_id", client_id), ("client_secret", client_secret)];
+    form.extend_from_slice(grant);
+    form
+}
+
+async fn request_token(
+    client: &Client,
+    client_id: &str,
+    client_secret: &str,
+    grant: &[(&str, &str)],
+) -> CommandResult<TokenResponse> {
+    let response = client
+        .post(TOKEN_URL)
+        .form(&token_form(client_id, client_secret, grant))
+        .send()
+        .await
+        .context("Asana sign-in")?;
+    match response.status() {
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(CommandError::unauthorized(
+            "Asana refused the sign-in; connect the account again",
+        )),
+        status if !status.is_success() => Err(CommandError::status(
+            status,
+            format!("Asana sign-in: HTTP {}", status.as_u16()),
+        )),
+        _ => http::read_json(response).await.context("Asana sign-in"),
+    }
+}
+
+/// A connection to the Asana API as one user.
+struct Api {
+    client: Client,
+    access_token: String,
+}
+
+impl Api {
+    fn new(access_token: String) -> CommandResult<Self> {
+        Ok(Self {
+            client: client()?,
+            access_token,
+        })
+    }
+
+    /// Use the account's personal access token, or trade its refresh token
+    /// for an access token.
+    async fn connect(db: &Db, account: &AsanaAccount) -> CommandResult<Self> {
+        let token = credentials::require(&keyring_name(&account.id), "sign-in")?;
+        let Some(client_id) = &account.client_id else {
+            return Self::new(token);
+        };
+        let secret = db
+            .with_conn(|conn| client_secret(conn, &account.id))?
+            .unwrap_or_default();
+        let client = client()?;
+        let grant = [("grant_type", "refresh_token"), ("refresh_token", &token)];
+        let token = request_token(&client, client_id, &secret, &grant).await?;
+        Ok(Self {
+            client,
+            access_token: token.access_token,
+        })
+    }
+
+    /// Connect as for `connect`, noting in `health` how the sign-in went.
+    /// `None` when it failed.
+    async fn check(
+        db: &Db,
+        account: &AsanaAccount,
+        health: &mut AccountHealth,
+    ) -> CommandResult<Option<Self>> {
+        let token = match credentials::require(&keyring_name(&account.id), "sign-in") {
+            Ok(token) => token,
+            Err(e) => {
+                health.no_credentials(&e);
+                return Ok(None);
+            }
+        };
+        let Some(client_id) = &account.client_id else {
+            return Self::new(token).map(Some);
+        };
+        let secret = db
+            .with_conn(|conn| client_secret(conn, &account.id))?
+            .unwrap_or_default();
+        let client = client()?;
+        let grant = [("grant_type", "refresh_token"), ("refresh_token", &token)];
+        let response = client
+            .post(TOKEN_URL)
+            .form(&token_form(client_id, &secret, &grant))
+            .send()
+            .await;
+        let Some(response) = health.answer_sign_in("Asana", response) else {
+            return Ok(None);
+        };
+        let token: TokenResponse = http::read_json(response).await.context("Asana sign-in")?;
+        Ok(Some(Self {
+            client,
+            access_token: token.access_token,
+        }))
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{API_URL}/{path}"))
+            .bearer_auth(&self.access_token)
+    }
+
+    /// Read the user, for the account health check.
+    async fn probe(&self) -> Result<Response, reqwest::Error> {
+        self.request(Method::GET, "users/me").send().await
+    }
+
+    /// Send a request to `path` and unwrap its answer. Returns `None` for a
+    /// task that was deleted or can't be seen any more.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<Envelope<T>>> {
+        rate_limit::check(SOURCE, "Asana")?;
+        let mut request = self.request(method.clone(), path).query(query);
+        if let Some(body) = body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await.context("Asana")?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED => Err(CommandError::unauthorized(
+                "Asana refused the sign-in; connect the account again",
+            )
+            .with_provider(SOURCE)),
+            StatusCode::TOO_MANY_REQUESTS => {
+                Err(rate_limit::limited(SOURCE, "Asana", response.headers()))
+            }
+            StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => Ok(None),
+            status if !status.is_success() => Err(CommandError::status(
+                status,
+                format!("Asana: {method} {path}: HTTP {}", status.as_u16()),
+            )
+            .with_provider(SOURCE)),
+            _ => http::read_json(response).await.map(Some).context("Asana"),
+        }
+    }
+
+    async fn me(&self) -> CommandResult<RemoteUser> {
+        let query = [("opt_fields", "name,workspaces.name")];
+        self.send(Method::GET, "users/me", &query, None)
+            .await?
+            .map(|found| found.data)
+            .ok_or_else(|| CommandError::unauthorized("Asana: no user for this sign-in"))
+    }
+
+    /// The user's My Tasks list in `workspace`.
+    async fn task_list(&self, workspace: &str) -> CommandResult<String> {
+        let query = [("workspace", workspace), ("opt_fields", "name")];
+        self.send::<Named>(Method::GET, "users/me/user_task_list", &query, None)
+            .await?
+            .map(|found| found.data.gid)
+            .ok_or_else(|| CommandError::not_found("Asana: no My Tasks list in this workspace"))
+    }
+
+    /// The incomplete tasks of My Tasks list `list`.
+    async fn my_tasks(&self, list: &str) -> CommandResult<Vec<RemoteTask>> {
+        let path = format!("user_task_lists/{list}/tasks");
+        let mut tasks = Vec::new();
+        let mut offset: Option<String> = None;
+        for _ in 0..MAX_PAGES {
+            let mut query = vec![
+                ("completed_since", "now"),
+                ("opt_fields", TASK_FIELDS),
+                ("limit", PAGE_SIZE),
+            ];
+            if let Some(offset) = &offset {
+                query.push(("offset", offset));
+            }
+            let page: Envelope<Vec<RemoteTask>> = self
+                .send(Method::GET, &path, &query, None)
+                .await?
+                .ok_or_else(|| CommandError::not_found("Asana: My Tasks list not found"))?;
+            tasks.extend(page.data);
+            match page.next_page {
+                Some(next) => offset = Some(next.offset),
+                None => break,
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// Task `gid`, or `None` once it was deleted.
+    async fn task(&self, gid: &str) -> CommandResult<Option<RemoteTask>> {
+        let query = [("opt_fields", TASK_FIELDS)];
+        let found = self
+            .send(Method::GET, &format!("tasks/{gid}"), &query, None)
+            .await?;
+        Ok(found.map(|found| found.data))
+    }
+
+    /// Complete or reopen task `gid`. `false` when it's gone.
+    async fn set_completed(&self, gid: &str, completed: bool) -> CommandResult<bool> {
+        let body = json!({ "data": { "completed": completed } });
+        let query = [("opt_fields", "completed")];
+        let updated = self
+            .send::<serde_json::Value>(Method::PUT, &format!("tasks/{gid}"), &query, Some(&body))
+            .await?;
+        Ok(updated.is_some())
+    }
+}
+
+/// A task imported before, as of the last sync.
+#[derive(Debug, Clone)]
+struct TaskRow {
+    task_id: String,
+    /// Whether it was complete in Asana.
+    completed: bool,
+    /// The section whose tag the task was given.
+    section: Option<String>,
+    modified_at: String,
+}
+
+fn load_rows(conn: &Connection, account_id: &str) -> rusqlite::Result<HashMap<String, TaskRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, task_id, completed, section, modified_at FROM asana_tasks
+         WHERE account_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            TaskRow {
+                task_id: row.get(1)?,
+                completed: row.get(2)?,
+                section: row.get(3)?,
+                modified_at: row.get(4)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_row(
+    conn: &Connection,
+    account_id: &str,
+    remote_id: &str,
+    row: &TaskRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO asana_tasks (account_id, remote_id, task_id, completed, section, modified_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(account_id, remote_id) DO UPDATE SET
+             task_id = excluded.task_id,
+             completed = excluded.completed,
+             section = excluded.section,
+             modified_at = excluded.modified_at",
+        params![
+            account_id,
+            remote_id,
+            row.task_id,
+            row.completed,
+            row.section,
+            row.modified_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_row(conn: &Connection, account_id: &str, remote_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM asana_tasks WHERE account_id = ?1 AND remote_id = ?2",
+        params![account_id, remote_id],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = Some(now_utc());
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// Create or update the task for `remote`. A field edited here after Asana
+/// last changed the task keeps the local value. Completion is only taken
+/// when it changed in Asana since `row`, and the section's tag replaces
+/// the one of the section it was in before. Returns the task and whether
+/// it was created, or `None` when nothing changed.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    row: Option<&TaskRow>,
+    remote: &RemoteTask,
+    project: Option<&str>,
+    local: &Tz,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let title = remote.name.trim();
+    let title = if title.is_empty() { "Untitled" } else { title }.to_string();
+    let description = Some(remote.notes.trim().to_string()).filter(|n| !n.is_empty());
+    let due = remote.due(local);
+    let section = remote.section();
+
+    let Some(mut task) = existing else {
+        let project = project.map(str::to_string).or_else(|| {
+            remote
+                .projects
+                .first()
+                .and_then(|p| projects::normalize_name(&p.name).ok())
+        });
+        let input = NewTask {
+            title: title.clone(),
+            description,
+            project,
+            priority: None,
+            due,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if remote.completed {
+            set_done(&mut task, true);
+            task_store::write_task(conn, &task)?;
+        }
+        if let Some(section) = &section {
+            let names = tags::normalize_names(std::slice::from_ref(section)).unwrap_or_default();
+            tags::set_task_tags(conn, &task.id, &names)?;
+            task.tags = names;
+        }
+        return Ok(Some((task, true)));
+    };
+
+    let modified = remote.modified_ms();
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| stamps.get(field).is_none_or(|s| s.clock <= modified);
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("description") {
+        task.description = description;
+    }
+    if take("due") {
+        task.due = due;
+    }
+    let completion_changed = row.is_none_or(|r| r.completed != remote.completed);
+    if completion_changed && take("status") && remote.completed != (task.status == STATUS_DONE) {
+        set_done(&mut task, remote.completed);
+    }
+    let old_section = row.and_then(|r| r.section.clone());
+    let take_tags = crdt::newest_tag_clock(conn, &task.id)?.is_none_or(|c| c <= modified);
+    let mut labels = task.tags.clone();
+    if take_tags && section != old_section {
+        if let Some(old) = &old_section {
+            labels.retain(|t| !t.eq_ignore_ascii_case(old));
+        }
+        if let Some(new) = &section {
+            if !labels.iter().any(|t| t.eq_ignore_ascii_case(new)) {
+                labels.push(new.clone());
+            }
+        }
+    }
+    let labels = tags::normalize_names(&labels).unwrap_or_default();
+    let tags_changed = labels != task.tags;
+
+    let changed = task.title != before.title
+        || task.description != before.description
+        || task.due != before.due
+        || task.status != before.status;
+    if !changed && !tags_changed {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    if tags_changed {
+        tags::set_task_tags(conn, &task.id, &labels)?;
+        task.tags = labels;
+    }
+    Ok(Some((task, false)))
+}
+
+/// A completion to send to Asana.
+#[derive(Debug)]
+struct Upload {
+    remote_id: String,
+    task_id: String,
+    done: bool,
+}
+
+impl Upload {
+    fn task_id(&self) -> &str {
+        &self.task_id
+    }
+}
+
+/// Apply the incomplete tasks My Tasks listed, and `refreshed`, the current
+/// state of tasks imported before that aren't listed any more (`None` once
+/// deleted), unless only exporting. Then work out which tasks were
+/// completed or reopened here. Tasks reassigned to someone else stop being
+/// tracked, as do ones complete on both sides.
+fn merge_remote(
+    conn: &mut Connection,
+    account: &AsanaAccount,
+    listed: &[RemoteTask],
+    refreshed: &HashMap<String, Option<RemoteTask>>,
+    local: &Tz,
+    imports: bool,
+    report: &mut AsanaSyncReport,
+) -> rusqlite::Result<Vec<Upload>> {
+    let tx = conn.transaction()?;
+    let known = load_rows(&tx, &account.id)?;
+    let project = account.project.as_deref();
+
+    let mut current: Vec<&RemoteTask> = listed.iter().collect();
+    for (remote_id, found) in refreshed {
+        match found {
+            Some(remote) if remote.assigned_to(&account.user_id) => current.push(remote),
+            Some(_) => forget_row(&tx, &account.id, remote_id)?,
+            None => {
+                if let Some(row) = known.get(remote_id) {
+                    if imports && trash::trash_task(&tx, &row.task_id)? {
+                        report.removed += 1;
+                    }
+                }
+                forget_row(&tx, &account.id, remote_id)?;
+            }
+        }
+    }
+
+    for remote in &current {
+        let row = known.get(&remote.gid);
+        if row.is_some_and(|r| r.modified_at == remote.modified_at) {
+            continue;
+        }
+        let task_id = match row {
+            Some(row) => Some(row.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, remote.gid],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if task_id.is_some() && existing.is_none() {
+            // Deleted here: it stays deleted.
+            continue;
+        }
+        if !imports {
+            // Only what Asana has is noted, so local changes go up over it.
+            if let Some(row) = row {
+                let row = TaskRow {
+                    completed: remote.completed,
+                    modified_at: remote.modified_at.clone(),
+                    ..row.clone()
+                };
+                save_row(&tx, &account.id, &remote.gid, &row)?;
+            }
+            continue;
+        }
+        let was_done = existing.as_ref().is_some_and(|t| t.status == STATUS_DONE);
+        let task_id = match write_remote(&tx, existing, row, remote, project, local)? {
+            Some((task, true)) => {
+                report.created += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, remote.gid, task.id, now_utc()],
+                )?;
+                task.id
+            }
+            Some((task, false)) => {
+                if !was_done && task.status == STATUS_DONE {
+                    report.completed += 1;
+                } else {
+                    report.updated += 1;
+                }
+                task.id
+            }
+            None => task_id.unwrap_or_default(),
+        };
+        let row = TaskRow {
+            task_id,
+            completed: remote.completed,
+            section: remote.section(),
+            modified_at: remote.modified_at.clone(),
+        };
+        save_row(&tx, &account.id, &remote.gid, &row)?;
+    }
+
+    let mut uploads = Vec::new();
+    let current: HashSet<&str> = current.iter().map(|r| r.gid.as_str()).collect();
+    for (remote_id, row) in load_rows(&tx, &account.id)? {
+        if !current.contains(remote_id.as_str()) {
+            continue;
+        }
+        let Some(task) = task_store::find_task(&tx, &row.task_id)? else {
+            continue;
+        };
+        let done = task.status == STATUS_DONE;
+        if done != row.completed {
+            uploads.push(Upload {
+                remote_id,
+                task_id: task.id,
+                done,
+            });
+        } else if done {
+            forget_row(&tx, &account.id, &remote_id)?;
+        }
+    }
+    tx.commit()?;
+    Ok(uploads)
+}
+
+/// Note the completions Asana took.
+fn save_results(
+    conn: &mut Connection,
+    account: &AsanaAccount,
+    results: &[(Upload, bool)],
+    report: &mut AsanaSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for (upload, found) in results {
+        if !found {
+            forget_row(&tx, &account.id, &upload.remote_id)?;
+            continue;
+        }
+        report.uploaded += 1;
+        tx.execute(
+            "UPDATE asana_tasks SET completed = ?3 WHERE account_id = ?1 AND remote_id = ?2",
+            params![account.id, upload.remote_id, upload.done],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Sync one account's My Tasks: import the incomplete tasks, look up the
+/// ones that left the list, and when pushing send completions made here.
+async fn sync_account(
+    db: &Db,
+    api: &Api,
+    account: &AsanaAccount,
+    mode: SyncMode,
+) -> CommandResult<AsanaSyncReport> {
+    let mut report = AsanaSyncReport {
+        account_id: account.id.clone(),
+        name: account.name.clone(),
+        ..AsanaSyncReport::default()
+    };
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let listed = api.my_tasks(&account.task_list_id).await?;
+
+    let known = db.with_conn(|conn| load_rows(conn, &account.id))?;
+    let found: HashSet<&str> = listed.iter().map(|t| t.gid.as_str()).collect();
+    let mut refreshed = HashMap::new();
+    for remote_id in known.keys() {
+        if found.contains(remote_id.as_str()) {
+            continue;
+        }
+        match api.task(remote_id).await {
+            Ok(task) => {
+                refreshed.insert(remote_id.clone(), task);
+            }
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    let uploads = db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(
+                conn,
+                account,
+                &listed,
+                &refreshed,
+                &local,
+                mode.imports(),
+                &mut report,
+            )
+        })?
+    })?;
+
+    // Importing only leaves local changes here.
+    let uploads = if mode.exports() { uploads } else { Vec::new() };
+    let uploads = db.with_conn(|conn| outbox::in_order(conn, uploads, Upload::task_id))?;
+    let mut results = Vec::new();
+    let mut replay = Replay::default();
+    for upload in uploads {
+        match api.set_completed(&upload.remote_id, upload.done).await {
+            Ok(found) => {
+                replay.delivered(upload.task_id.clone());
+                results.push((upload, found));
+            }
+            Err(e) => {
+                replay.failed(upload.task_id.clone(), &e);
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    db.with_conn(|conn| {
+        replay.save(conn)?;
+        save_results(conn, account, &results, &mut report)?;
+        conn.execute(
+            "UPDATE asana_accounts SET last_synced_at = ?2 WHERE id = ?1",
+            params![account.id, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+/// The consent screen URL for the OAuth loopback flow: start the listener
+/// with `start_oauth_listener`, open this, then pass the code from
+/// `await_oauth_code` to `connect_asana_account`.
+#[tauri::command]
+pub fn asana_auth_url(client_id: String, redirect_uri: String) -> CommandResult<String> {
+    let mut url = Url::parse(AUTH_URL).map_err(|e| e.to_string())?;
+    url.query_pairs_mut()
+        .append_pair("client_id", &client_id)
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("response_type", "code");
+    Ok(url.to_string())
+}
+
+/// Trim `value`, `None` when that leaves nothing.
+fn trimmed(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Sign in with a personal access token or an OAuth code, find the My
+/// Tasks list of the workspace and save the account. Nothing is imported
+/// until `sync_asana` runs.
+#[tauri::command]
+pub async fn connect_asana_account(
+    db: State<'_, Db>,
+    input: NewAsanaAccount,
+) -> CommandResult<AsanaAccount> {
+    let project = match input.project.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(name) => Some(projects::normalize_name(name)?),
+    };
+    let client_secret = trimmed(input.client_secret);
+    // A personal access token needs no client.
+    let (api, saved, client_credentials) = match (trimmed(input.token), trimmed(input.client_id)) {
+        (Some(token), _) => (Api::new(token.clone())?, token, None),
+        (None, Some(client_id)) => {
+            let secret = client_secret
+                .ok_or_else(|| CommandError::invalid("Enter the OAuth client secret"))?;
+            let code = trimmed(input.code)
+                .ok_or_else(|| CommandError::unauthorized("Sign in to Asana first"))?;
+            let redirect_uri = input.redirect_uri.unwrap_or_default();
+            let http = client()?;
+            let grant = [
+                ("grant_type", "authorization_code"),
+                ("code", code.as_str()),
+                ("redirect_uri", redirect_uri.as_str()),
+            ];
+            let token = request_token(&http, &client_id, &secret, &grant).await?;
+            let refresh_token = token.refresh_token.ok_or_else(|| {
+                CommandError::unauthorized(
+                    "Asana didn't hand out a refresh token; try connecting again",
+                )
+            })?;
+            let api = Api {
+                client: http,
+                access_token: token.access_token,
+            };
+            (api, refresh_token, Some((client_id, secret)))
+        }
+        (None, None) => {
+            return Err(CommandError::invalid(
+                "Enter a personal access token or sign in",
+            ))
+        }
+    };
+    let (client_id, client_secret) = client_credentials.unzip();
+
+    let user = api.me().await?;
+    let workspace = match trimmed(input.workspace_id) {
+        Some(id) => user
+            .workspaces
+            .iter()
+            .find(|w| w.gid == id)
+            .ok_or_else(|| CommandError::not_found(format!("Asana workspace not found: {id}")))?,
+        None => user
+            .workspaces
+            .first()
+            .ok_or_else(|| CommandError::invalid("This Asana user has no workspace"))?,
+    };
+    let task_list = api.task_list(&workspace.gid).await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = trimmed(input.name).unwrap_or_else(|| user.name.clone());
+    credentials::save(&keyring_name(&id), Some(&saved))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO asana_accounts (id, name, user_id, workspace_id, workspace_name,
+                 task_list_id, client_id, client_secret, project, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+            params![
+                id,
+                name,
+                user.gid,
+                workspace.gid,
+                workspace.name,
+                task_list,
+                client_id,
+                client_secret,
+                project,
+                now
+            ],
+        )?;
+        find_account(conn, &id)
+    });
+    match stored {
+        Ok(Some(account)) => Ok(account),
+        Ok(None) => Err("Failed to save account".into()),
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_asana_accounts(db: State<'_, Db>) -> CommandResult<Vec<AsanaAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Rename an account or change where its tasks go. Tasks imported before
+/// stay where they are.
+#[tauri::command]
+pub fn update_asana_account(
+    db: State<'_, Db>,
+    id: String,
+    patch: AsanaAccountPatch,
+) -> CommandResult<AsanaAccount> {
+    let name = match patch.name.map(|n| n.trim().to_string()) {
+        Some(name) if name.is_empty() => return Err(CommandError::invalid("Enter a name")),
+        name => name,
+    };
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut account) = find_account(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Asana account not found: {id}"
+            ))));
+        };
+        if let Some(name) = name {
+            account.name = name;
+        }
+        if let Some(project) = project {
+            account.project = project;
+        }
+        account.updated_at = now_utc();
+        conn.execute(
+            "UPDATE asana_accounts SET name = ?2, project = ?3, updated_at = ?4 WHERE id = ?1",
+            params![
+                account.id,
+                account.name,
+                account.project,
+                account.updated_at
+            ],
+        )?;
+        Ok(Ok(account))
+    })?
+}
+
+/// Forget an account and which tasks it imported. Its tasks stay,
+/// unlinked.
+#[tauri::command]
+pub fn remove_asana_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM asana_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Asana account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+pub struct Asana;
+
+impl SyncProvider for Asana {
+    type Report = Vec<AsanaSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Asana"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: true,
+            conflicts: false,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(db, &account).await?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                if let Some(api) = Api::check(db, &account, &mut health).await? {
+                    health.answer("Asana", api.probe().await);
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Import))
+    }
+
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::TwoWay))
+    }
+
+    fn export<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Export))
+    }
+}
+
+/// Import My Tasks of `account_id` or every account, and send completions
+/// made here. Completing a task in Asana completes it here, and deleting
+/// it trashes it. One account failing doesn't stop the others; its error
+/// is in its report.
+#[tauri::command]
+pub async fn sync_asana(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<AsanaSyncReport>> {
+    sync_provider::run_command(&app, &Asana, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+    mode: SyncMode,
+) -> CommandResult<Vec<AsanaSyncReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let result = match Api::connect(db, &account).await {
+            Ok(api) => sync_account(db, &api, &account, mode).await,
+            Err(e) => Err(e),
+        };
+        let report = result.unwrap_or_else(|e| {
+            tracing::warn!("sync of {} failed: {e}", account.name);
+            AsanaSyncReport {
+                account_id: account.id.clone(),
+                name: account.name.clone(),
+                errors: vec![e.to_string()],
+                ..AsanaSyncReport::default()
+            }
+        });
+        progress.record(&report.name, &report);
+        reports.push(report);
+    }
+    Ok(reports)
+}
@@ -0,0 +1,358 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::archive;
+use crate::data_dir;
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::task_store;
+
+const STORE_DIR: &str = "attachments";
+const TMP_DIR: &str = "tmp";
+/// Files this fresh are never collected: a copy may have landed in the store
+/// but its row not be written yet.
+const GC_GRACE: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    pub id: String,
+    pub task_id: String,
+    pub file_name: String,
+    /// SHA-256 of the contents, which is also the file's name in the store.
+    pub hash: String,
+    pub size_bytes: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GcReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+fn row_to_attachment(row: &Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        file_name: row.get(2)?,
+        hash: row.get(3)?,
+        size_bytes: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+pub fn store_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    data_dir::app_data_dir(app).map(|dir| dir.join(STORE_DIR))
+}
+
+/// Where a file with `hash` lives, fanned out by its first two characters.
+/// Hashes come back from the database, so anything but a SHA-256 hex digest
+/// is refused rather than turned into a path.
+pub fn blob_path(store: &Path, hash: &str) -> CommandResult<PathBuf> {
+    if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(CommandError::invalid(format!(
+            "Invalid attachment hash: {hash}"
+        )));
+    }
+    Ok(store.join(&hash[..2]).join(hash))
+}
+
+/// Copy `source` into the store, hashing as it goes. A file that's already
+/// stored isn't written twice. Returns the hash and size.
+pub fn store_file(store: &Path, source: &Path) -> CommandResult<(String, u64)> {
+    let input = BufReader::new(
+        File::open(source).with_context(|| format!("Failed to open {}", source.display()))?,
+    );
+    store_from(store, input, &source.display().to_string())
+}
+
+/// Put `bytes`, e.g. a mail attachment, into the store like `store_file`.
+pub fn store_bytes(store: &Path, bytes: &[u8]) -> CommandResult<(String, u64)> {
+    store_from(store, bytes, "attachment")
+}
+
+fn store_from(store: &Path, mut input: impl Read, what: &str) -> CommandResult<(String, u64)> {
+    let tmp_dir = store.join(TMP_DIR);
+    fs::create_dir_all(&tmp_dir).context("Failed to create attachment store")?;
+    let tmp = tmp_dir.join(uuid::Uuid::new_v4().to_string());
+
+    let copied = (|| {
+        let mut output = File::create(&tmp).context("Failed to write attachment")?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let n = input
+                .read(&mut buf)
+                .with_context(|| format!("Failed to read {what}"))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            output
+                .write_all(&buf[..n])
+                .context("Failed to write attachment")?;
+            size += n as u64;
+        }
+        output.sync_all().context("Failed to write attachment")?;
+        Ok::<_, CommandError>((format!("{:x}", hasher.finalize()), size))
+    })();
+    let (hash, size) = match copied {
+        Ok(done) => done,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    };
+
+    let dest = blob_path(store, &hash)?;
+    if dest.exists() {
+        let _ = fs::remove_file(&tmp);
+        // Restart the GC grace period for a file that may be unreferenced.
+        let _ = File::options()
+            .append(true)
+            .open(&dest)
+            .and_then(|f| f.set_modified(SystemTime::now()));
+    } else {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).context("Failed to create attachment store")?;
+        }
+        fs::rename(&tmp, &dest).context("Failed to store attachment")?;
+    }
+    Ok((hash, size))
+}
+
+pub fn find_attachment(conn: &Connection, id: &str) -> rusqlite::Result<Option<Attachment>> {
+    conn.query_row(
+        "SELECT id, task_id, file_name, hash, size_bytes, created_at
+         FROM attachments WHERE id = ?1",
+        params![id],
+        row_to_attachment,
+    )
+    .optional()
+}
+
+pub fn list_for_task(conn: &Connection, task_id: &str) -> rusqlite::Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, task_id, file_name, hash, size_bytes, created_at
+         FROM attachments WHERE task_id = ?1 ORDER BY created_at, id",
+    )?;
+    let rows = stmt.query_map(params![task_id], row_to_attachment)?;
+    rows.collect()
+}
+
+/// Link an already stored file to a task.
+pub fn insert_attachment(
+    conn: &Connection,
+    task_id: &str,
+    file_name: &str,
+    hash: &str,
+    size_bytes: u64,
+) -> rusqlite::Result<Attachment> {
+    let attachment = Attachment {
+        id: uuid::Uuid::new_v4().to_string(),
+        task_id: task_id.to_string(),
+        file_name: file_name.to_string(),
+        hash: hash.to_string(),
+        size_bytes: size_bytes as i64,
+        created_at: now_utc(),
+    };
+    conn.execute(
+        "INSERT INTO attachments (id, task_id, file_name, hash, size_bytes, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            attachment.id,
+            attachment.task_id,
+            attachment.file_name,
+            attachment.hash,
+            attachment.size_bytes,
+            attachment.created_at,
+        ],
+    )?;
+    Ok(attachment)
+}
+
+/// Hashes still in use: linked to a task, or needed to undo a removal.
+fn referenced_hashes(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT hash FROM attachments
+         UNION SELECT json_extract(before, '$.hash') FROM journal
+             WHERE tbl = 'attachments' AND before IS NOT NULL
+         UNION SELECT json_extract(after, '$.hash') FROM journal
+             WHERE tbl = 'attachments' AND after IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    rows.collect()
+}
+
+/// Delete stored files nothing refers to any more, plus abandoned copies.
+/// `archived` holds the hashes still used by archived tasks.
+pub fn collect_garbage(
+    conn: &Connection,
+    store: &Path,
+    archived: &HashSet<String>,
+) -> CommandResult<GcReport> {
+    let mut referenced = referenced_hashes(conn)?;
+    referenced.extend(archived.iter().cloned());
+    let cutoff = SystemTime::now() - GC_GRACE;
+    let mut report = GcReport::default();
+
+    let Ok(shards) = fs::read_dir(store) else {
+        return Ok(report);
+    };
+    for shard in shards.filter_map(|e| e.ok()) {
+        let is_tmp = shard.file_name() == TMP_DIR;
+        let Ok(files) = fs::read_dir(shard.path()) else {
+            continue;
+        };
+        for file in files.filter_map(|e| e.ok()) {
+            let Ok(meta) = file.metadata() else {
+                continue;
+            };
+            let name = file.file_name().to_string_lossy().to_string();
+            let recent = meta.modified().map(|m| m > cutoff).unwrap_or(true);
+            if recent || (!is_tmp && referenced.contains(&name)) {
+                continue;
+            }
+            if fs::remove_file(file.path()).is_ok() {
+                report.removed += 1;
+                report.freed_bytes += meta.len();
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Open `path` with the desktop's default application.
+fn open_with_system(path: &Path) -> CommandResult<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = Command::new("explorer");
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+
+    command
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Run garbage collection once in the background, e.g. at startup.
+pub fn spawn_gc(app: &AppHandle) {
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let Ok(store) = store_dir(&handle) else {
+            return;
+        };
+        let db = handle.state::<Db>();
+        if db.is_locked() {
+            return;
+        }
+        let archived = match archive::attachment_hashes(&db) {
+            Ok(archived) => archived,
+            Err(e) => {
+                tracing::warn!("cleanup skipped, archive unreadable: {e}");
+                return;
+            }
+        };
+        match db.with_conn(|conn| Ok(collect_garbage(conn, &store, &archived))) {
+            Ok(Ok(report)) if report.removed > 0 => tracing::info!(
+                "removed {} unused files ({} bytes)",
+                report.removed,
+                report.freed_bytes
+            ),
+            Ok(Err(e)) => tracing::warn!("cleanup failed: {e}"),
+            Err(e) => tracing::warn!("cleanup failed: {e}"),
+            _ => {}
+        }
+    });
+}
+
+/// Copy the file at `path` into the store and attach it to a task.
+#[tauri::command]
+pub fn add_attachment(
+    app: AppHandle,
+    db: State<'_, Db>,
+    task_id: String,
+    path: String,
+) -> CommandResult<Attachment> {
+    let source = Path::new(&path);
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| CommandError::invalid(format!("Not a file: {path}")))?;
+    if db
+        .with_conn(|conn| task_store::find_task(conn, &task_id))?
+        .is_none()
+    {
+        return Err(CommandError::not_found(format!(
+            "Task not found: {task_id}"
+        )));
+    }
+    // Copy outside the lock; large files shouldn't stall the database.
+    let (hash, size) = store_file(&store_dir(&app)?, source)?;
+    db.with_conn(|conn| insert_attachment(conn, &task_id, &file_name, &hash, size))
+}
+
+#[tauri::command]
+pub fn list_attachments(db: State<'_, Db>, task_id: String) -> CommandResult<Vec<Attachment>> {
+    db.with_conn(|conn| list_for_task(conn, &task_id))
+}
+
+/// Open an attachment in its default application. The stored file has no
+/// extension, so a copy under its original name is made in the cache dir.
+#[tauri::command]
+pub fn open_attachment(app: AppHandle, db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let attachment = db
+        .with_conn(|conn| find_attachment(conn, &id))?
+        .ok_or_else(|| CommandError::not_found(format!("Attachment not found: {id}")))?;
+    let blob = blob_path(&store_dir(&app)?, &attachment.hash)?;
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to resolve cache dir: {e}"))?
+        .join(STORE_DIR)
+        .join(&attachment.id);
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    // Names from mail were chosen by the sender; keep only the last part.
+    let name = Path::new(&attachment.file_name)
+        .file_name()
+        .ok_or_else(|| {
+            CommandError::invalid(format!("Invalid attachment name: {}", attachment.file_name))
+        })?;
+    let copy = dir.join(name);
+    fs::copy(&blob, &copy).context("Failed to open attachment")?;
+    open_with_system(&copy)
+}
+
+/// Unlink an attachment. The stored file stays until garbage collection
+/// finds nothing (including the undo history) refers to it.
+#[tauri::command]
+pub fn remove_attachment(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM attachments WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Attachment not found: {id}"
+        )));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn gc_attachments(app: AppHandle, db: State<'_, Db>) -> CommandResult<GcReport> {
+    let store = store_dir(&app)?;
+    let archived = archive::attachment_hashes(&db)?;
+    db.with_conn(|conn| Ok(collect_garbage(conn, &store, &archived)))?
+}
@@ -0,0 +1,460 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::data_dir;
+use crate::db::{self, format_utc, Db};
+use crate::error::{CommandError, CommandResult, ErrorCode, ResultExt};
+use crate::migrations;
+use crate::session::write_atomic;
+
+/// Emitted with the new `BackupInfo` after a snapshot is written and verified.
+pub const BACKUP_COMPLETED_EVENT: &str = "backup-completed";
+/// Emitted with the error message when a scheduled or manual backup fails.
+pub const BACKUP_FAILED_EVENT: &str = "backup-failed";
+/// Emitted with the `RestoreReport` after a backup replaces the live
+/// database; every view should reload.
+pub const BACKUP_RESTORED_EVENT: &str = "backup-restored";
+
+const CONFIG_FILE: &str = "backup.json";
+const DEFAULT_DIR: &str = "backups";
+const FILE_PREFIX: &str = "daylight-";
+const FILE_SUFFIX: &str = ".db";
+const STAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const SCHEDULE_POLL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub enabled: bool,
+    /// Where snapshots go. Defaults to `backups/` under the app data dir.
+    pub directory: Option<String>,
+    pub interval_hours: u32,
+    /// Keep the newest snapshot of each of the last N days...
+    pub keep_daily: u32,
+    /// ...and of each of the last N weeks.
+    pub keep_weekly: u32,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: None,
+            interval_hours: 24,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+/// What a backup contains, shown before the user confirms a restore.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupPreview {
+    pub path: String,
+    pub schema_version: i64,
+    /// The schema version this build runs; older backups are migrated up.
+    pub latest_version: i64,
+    pub needs_migration: bool,
+    pub tasks: i64,
+    pub time_entries: i64,
+    pub tags: i64,
+    pub reminders: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub restored: BackupPreview,
+    /// Where the database that was replaced now lives.
+    pub safety_copy: String,
+}
+
+fn app_data_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    data_dir::app_data_dir(app)
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+pub fn load_config(app: &AppHandle) -> BackupConfig {
+    let Ok(path) = config_path(app) else {
+        return BackupConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            BackupConfig::default()
+        }),
+        Err(_) => BackupConfig::default(),
+    }
+}
+
+pub fn backup_dir(app: &AppHandle, config: &BackupConfig) -> CommandResult<PathBuf> {
+    match &config.directory {
+        Some(dir) if !dir.trim().is_empty() => Ok(PathBuf::from(dir)),
+        _ => Ok(app_data_dir(app)?.join(DEFAULT_DIR)),
+    }
+}
+
+/// When a snapshot was taken, from its file name.
+fn snapshot_time(path: &Path) -> Option<DateTime<Utc>> {
+    let name = path.file_name()?.to_str()?;
+    let stamp = name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_SUFFIX)?;
+    NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Snapshots in `dir`, newest first. Other files are ignored.
+pub fn list_snapshots(dir: &Path) -> CommandResult<Vec<BackupInfo>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(CommandError::from(e).context(format!("Failed to read {}", dir.display())))
+        }
+    };
+    let mut snapshots: Vec<(DateTime<Utc>, BackupInfo)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let time = snapshot_time(&path)?;
+            let size_bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            Some((
+                time,
+                BackupInfo {
+                    path: path.display().to_string(),
+                    created_at: format_utc(time),
+                    size_bytes,
+                },
+            ))
+        })
+        .collect();
+    snapshots.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+    Ok(snapshots.into_iter().map(|(_, info)| info).collect())
+}
+
+fn open_read_only(path: &Path, key: Option<&str>) -> CommandResult<Connection> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    db::apply_key(&conn, key)?;
+    Ok(conn)
+}
+
+/// Open a snapshot read-only, run an integrity check and return its schema
+/// version. `key` is needed for snapshots of an encrypted database.
+pub fn verify_snapshot(path: &Path, key: Option<&str>) -> CommandResult<i64> {
+    let conn = open_read_only(path, key)?;
+    let check: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .context("Integrity check failed")?;
+    if check != "ok" {
+        return Err(CommandError::new(
+            ErrorCode::Storage,
+            format!("Integrity check failed: {check}"),
+        ));
+    }
+    migrations::current_version(&conn)
+        .map_err(|e| CommandError::invalid(format!("Not a DayLight database: {e}")))
+}
+
+/// Write a consistent copy of the live database into `dir` and verify it.
+/// Attachment metadata lives in the database, so it's included. The copy is
+/// encrypted with the live database's `key`, if any.
+pub fn snapshot(conn: &Connection, dir: &Path, key: Option<&str>) -> CommandResult<BackupInfo> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create backup dir {}", dir.display()))?;
+    let now = Utc::now();
+    let name = format!("{FILE_PREFIX}{}{FILE_SUFFIX}", now.format(STAMP_FORMAT));
+    let path = dir.join(&name);
+    let tmp = dir.join(format!("{name}.tmp"));
+    let _ = fs::remove_file(&tmp);
+
+    conn.execute("VACUUM INTO ?1", params![tmp.display().to_string()])
+        .context("Failed to write snapshot")?;
+    if let Err(e) = verify_snapshot(&tmp, key) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::rename(&tmp, &path).with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    let size_bytes = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    Ok(BackupInfo {
+        path: path.display().to_string(),
+        created_at: format_utc(now),
+        size_bytes,
+    })
+}
+
+/// Rows in `table`, or 0 when an older schema doesn't have it yet.
+fn count_rows(conn: &Connection, table: &str) -> CommandResult<i64> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+            params![table],
+            |row| row.get(0),
+        )
+        .context("Failed to read backup")?;
+    if !exists {
+        return Ok(0);
+    }
+    conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+        row.get(0)
+    })
+    .context("Failed to read backup")
+}
+
+/// Validate a backup and count what's in it without changing anything.
+pub fn inspect(path: &Path, key: Option<&str>) -> CommandResult<BackupPreview> {
+    let schema_version = verify_snapshot(path, key)?;
+    let latest_version = migrations::latest_version();
+    if schema_version > latest_version {
+        return Err(CommandError::invalid(format!(
+            "Backup schema version {schema_version} is newer than this build supports ({latest_version})"
+        )));
+    }
+    let conn = open_read_only(path, key)?;
+    Ok(BackupPreview {
+        path: path.display().to_string(),
+        schema_version,
+        latest_version,
+        needs_migration: schema_version < latest_version,
+        tasks: count_rows(&conn, "tasks")?,
+        time_entries: count_rows(&conn, "time_entries")?,
+        tags: count_rows(&conn, "tags")?,
+        reminders: count_rows(&conn, "reminders")?,
+    })
+}
+
+/// The key to open a backup with: the passphrase given for it, else the
+/// live database's key. Unencrypted backups need none.
+fn backup_key(db: &Db, path: &Path, passphrase: Option<String>) -> Option<String> {
+    if db::is_encrypted_file(path) {
+        passphrase.or_else(|| db.key())
+    } else {
+        None
+    }
+}
+
+/// Copy `source` next to the live database, re-keyed to `live_key` and
+/// migrated, so the swap itself is a rename.
+fn stage(
+    source: &Path,
+    source_key: Option<&str>,
+    live: &Path,
+    live_key: Option<&str>,
+) -> CommandResult<PathBuf> {
+    let mut staged = live.as_os_str().to_owned();
+    staged.push(".restore");
+    let staged = PathBuf::from(staged);
+    let _ = fs::remove_file(&staged);
+    if source_key == live_key {
+        fs::copy(source, &staged).context("Failed to copy backup")?;
+    } else {
+        // Not read-only: the export attaches and creates the staged file.
+        let conn = Connection::open(source)
+            .with_context(|| format!("Failed to open {}", source.display()))?;
+        db::apply_key(&conn, source_key)?;
+        db::export_copy(&conn, &staged, live_key)?;
+    }
+
+    let migrated = Connection::open(&staged)
+        .context("Failed to open backup")
+        .and_then(|mut conn| {
+            db::apply_key(&conn, live_key)?;
+            conn.execute_batch("PRAGMA foreign_keys = ON;")
+                .context("Failed to configure backup")?;
+            migrations::run_migrations(&mut conn)
+        })
+        .and_then(|_| verify_snapshot(&staged, live_key));
+    if let Err(e) = migrated {
+        let _ = fs::remove_file(&staged);
+        return Err(e);
+    }
+    Ok(staged)
+}
+
+/// Replace the live database with the backup at `source`. The backup is
+/// validated and migrated on a copy first; the replaced database is kept
+/// beside the live one as `daylight.db.pre-restore-<stamp>`. `passphrase`
+/// is only needed for a backup taken under a different passphrase.
+pub fn restore(db: &Db, source: &Path, passphrase: Option<String>) -> CommandResult<RestoreReport> {
+    let source_key = backup_key(db, source, passphrase);
+    let live_key = db.key();
+    let restored = inspect(source, source_key.as_deref())?;
+    let staged = stage(
+        source,
+        source_key.as_deref(),
+        db.path(),
+        live_key.as_deref(),
+    )?;
+
+    let mut keep_as = db.path().as_os_str().to_owned();
+    keep_as.push(format!(".pre-restore-{}", Utc::now().format(STAMP_FORMAT)));
+    let keep_as = PathBuf::from(keep_as);
+    if let Err(e) = db.replace_file(&staged, &keep_as) {
+        let _ = fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    Ok(RestoreReport {
+        restored,
+        safety_copy: keep_as.display().to_string(),
+    })
+}
+
+/// Delete snapshots that aren't the newest of one of the last `keep_daily`
+/// days or `keep_weekly` weeks (local time). Returns the deleted paths.
+pub fn rotate(dir: &Path, keep_daily: u32, keep_weekly: u32) -> CommandResult<Vec<String>> {
+    let snapshots = list_snapshots(dir)?;
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    let mut deleted = Vec::new();
+
+    // Newest first, so the first snapshot seen for a day or week is the one kept.
+    for snapshot in snapshots {
+        let Some(time) = snapshot_time(Path::new(&snapshot.path)) else {
+            continue;
+        };
+        let local = time.with_timezone(&Local).date_naive();
+        let week = local.iso_week();
+        let mut keep = false;
+        if !days.contains(&local) && days.len() < keep_daily as usize {
+            days.insert(local);
+            keep = true;
+        }
+        if !weeks.contains(&week) && weeks.len() < keep_weekly as usize {
+            weeks.insert(week);
+            keep = true;
+        }
+        if !keep {
+            fs::remove_file(&snapshot.path)
+                .with_context(|| format!("Failed to delete {}", snapshot.path))?;
+            deleted.push(snapshot.path);
+        }
+    }
+    Ok(deleted)
+}
+
+/// Snapshot, verify and rotate, emitting `BACKUP_COMPLETED_EVENT` or
+/// `BACKUP_FAILED_EVENT`.
+pub fn run_backup(app: &AppHandle) -> CommandResult<BackupInfo> {
+    let config = load_config(app);
+    let result = backup_dir(app, &config).and_then(|dir| {
+        let db = app.state::<Db>();
+        let key = db.key();
+        let info = db.with_conn(|conn| Ok(snapshot(conn, &dir, key.as_deref())))??;
+        rotate(&dir, config.keep_daily.max(1), config.keep_weekly)?;
+        Ok(info)
+    });
+    match &result {
+        Ok(info) => {
+            let _ = app.emit(BACKUP_COMPLETED_EVENT, info);
+        }
+        Err(e) => {
+            tracing::warn!("{e}");
+            let _ = app.emit(BACKUP_FAILED_EVENT, e);
+        }
+    }
+    result
+}
+
+fn backup_due(app: &AppHandle, config: &BackupConfig) -> bool {
+    if !config.enabled || app.state::<Db>().is_locked() {
+        return false;
+    }
+    let Ok(dir) = backup_dir(app, config) else {
+        return false;
+    };
+    let latest = list_snapshots(&dir)
+        .ok()
+        .and_then(|s| s.into_iter().next())
+        .and_then(|s| snapshot_time(Path::new(&s.path)));
+    match latest {
+        Some(latest) => {
+            Utc::now() - latest >= chrono::Duration::hours(config.interval_hours.max(1) as i64)
+        }
+        None => true,
+    }
+}
+
+/// Take a backup whenever the configured interval has passed since the
+/// newest snapshot. The config is re-read on every check.
+pub fn spawn_backup_scheduler(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || loop {
+        if backup_due(&handle, &load_config(&handle)) {
+            let _ = run_backup(&handle);
+        }
+        std::thread::sleep(SCHEDULE_POLL);
+    });
+}
+
+#[tauri::command]
+pub fn get_backup_config(app: AppHandle) -> BackupConfig {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn set_backup_config(app: AppHandle, config: BackupConfig) -> CommandResult<BackupConfig> {
+    let dir = backup_dir(&app, &config)?;
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create backup dir {}", dir.display()))?;
+    let path = config_path(&app)?;
+    let body = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn run_backup_now(app: AppHandle) -> CommandResult<BackupInfo> {
+    run_backup(&app)
+}
+
+#[tauri::command]
+pub fn list_backups(app: AppHandle) -> CommandResult<Vec<BackupInfo>> {
+    let config = load_config(&app);
+    list_snapshots(&backup_dir(&app, &config)?)
+}
+
+#[tauri::command]
+pub fn preview_backup(
+    db: State<'_, Db>,
+    path: String,
+    passphrase: Option<String>,
+) -> CommandResult<BackupPreview> {
+    let path = Path::new(&path);
+    inspect(path, backup_key(&db, path, passphrase).as_deref())
+}
+
+#[tauri::command]
+pub fn restore_backup(
+    app: AppHandle,
+    db: State<'_, Db>,
+    path: String,
+    passphrase: Option<String>,
+) -> CommandResult<RestoreReport> {
+    let report = restore(&db, Path::new(&path), passphrase)?;
+    tracing::info!(
+        "restored {} (previous database kept at {})",
+        report.restored.path,
+        report.safety_copy
+    );
+    let _ = app.emit(BACKUP_RESTORED_EVENT, &report);
+    Ok(report)
+}
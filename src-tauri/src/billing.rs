@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::Utc;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::csv;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::projects;
+use crate::reports::{self, GroupBy, Range, ReportQuery, TAG_FILTER_SQL};
+use crate::session::write_atomic;
+
+const RULE_COLUMNS: &str = "project, round_minutes, rounding, hourly_rate, updated_at";
+
+/// Columns of `export_invoice`, in order. Hours and amounts have two
+/// decimals; `date` is the local day the entry started.
+pub const INVOICE_FIELDS: &[&str] = &[
+    "date",
+    "project",
+    "task",
+    "note",
+    "started_at",
+    "ended_at",
+    "tracked_hours",
+    "billed_hours",
+    "hourly_rate",
+    "amount",
+];
+
+const NO_PROJECT: &str = "No project";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    #[default]
+    Nearest,
+    Up,
+    Down,
+}
+
+impl Rounding {
+    fn as_str(self) -> &'static str {
+        match self {
+            Rounding::Nearest => "nearest",
+            Rounding::Up => "up",
+            Rounding::Down => "down",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "up" => Rounding::Up,
+            "down" => Rounding::Down,
+            _ => Rounding::Nearest,
+        }
+    }
+}
+
+/// How billable time in a project is rounded and charged. Rounding applies
+/// to each entry, as it would on a timesheet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BillingRule {
+    /// `None` for the default rule, used by projects without their own and
+    /// by tasks with no project.
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Round each entry to a multiple of this many minutes; 0 keeps the
+    /// exact time.
+    #[serde(default)]
+    pub round_minutes: i64,
+    #[serde(default)]
+    pub rounding: Rounding,
+    /// Per hour of billed time. No rate means hours only, no amounts.
+    #[serde(default)]
+    pub hourly_rate: Option<f64>,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+/// One billable time entry, clipped to the report range, after rounding.
+#[derive(Debug, Clone, Serialize)]
+pub struct BilledEntry {
+    pub entry_id: String,
+    pub task_id: String,
+    pub title: String,
+    pub project: Option<String>,
+    pub note: Option<String>,
+    pub started_at: String,
+    /// `None` while the timer runs; it's counted up to now.
+    pub ended_at: Option<String>,
+    pub tracked_seconds: i64,
+    pub billed_seconds: i64,
+    pub hourly_rate: Option<f64>,
+    /// Rounded to cents.
+    pub amount: Option<f64>,
+    /// Local day the entry started, `YYYY-MM-DD`.
+    pub date: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BillingLine {
+    /// Date, week, month, project or task id, as for time reports.
+    pub key: String,
+    pub label: String,
+    pub tracked_seconds: i64,
+    pub billed_seconds: i64,
+    /// Sum of the entries' amounts; entries without a rate add nothing.
+    pub amount: f64,
+    pub entry_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BillingReport {
+    pub group_by: GroupBy,
+    pub from: String,
+    pub to: String,
+    pub lines: Vec<BillingLine>,
+    pub tracked_seconds: i64,
+    pub billed_seconds: i64,
+    pub amount: f64,
+    /// Billable seconds whose project has no hourly rate.
+    pub unrated_seconds: i64,
+}
+
+fn row_to_rule(row: &Row) -> rusqlite::Result<BillingRule> {
+    let project: String = row.get(0)?;
+    let rounding: String = row.get(2)?;
+    Ok(BillingRule {
+        project: (!project.is_empty()).then_some(project),
+        round_minutes: row.get(1)?,
+        rounding: Rounding::parse(&rounding),
+        hourly_rate: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+pub fn list_rules(conn: &Connection) -> rusqlite::Result<Vec<BillingRule>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {RULE_COLUMNS} FROM billing_rules ORDER BY project COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_rule)?;
+    rows.collect()
+}
+
+/// The rule for `project`, falling back to the default rule. With neither,
+/// time is billed exactly and without a rate.
+fn rule_for<'a>(rules: &'a [BillingRule], project: Option<&str>) -> Option<&'a BillingRule> {
+    project
+        .and_then(|name| {
+            rules.iter().find(|r| {
+                r.project
+                    .as_deref()
+                    .is_some_and(|p| p.eq_ignore_ascii_case(name))
+            })
+        })
+        .or_else(|| rules.iter().find(|r| r.project.is_none()))
+}
+
+/// Round `seconds` to a multiple of `minutes`. Exact halves round up.
+pub fn round_seconds(seconds: i64, minutes: i64, rounding: Rounding) -> i64 {
+    if minutes <= 0 {
+        return seconds;
+    }
+    let step = minutes * 60;
+    let steps = match rounding {
+        Rounding::Nearest => (seconds + step / 2) / step,
+        Rounding::Up => (seconds + step - 1) / step,
+        Rounding::Down => seconds / step,
+    };
+    steps * step
+}
+
+fn amount_for(seconds: i64, rate: f64) -> f64 {
+    (seconds as f64 / 3600.0 * rate * 100.0).round() / 100.0
+}
+
+/// Billable entries overlapping the range, oldest first, with each
+/// project's rule applied.
+pub fn billed_entries(
+    conn: &Connection,
+    query: &ReportQuery,
+    range: &Range,
+) -> rusqlite::Result<Vec<BilledEntry>> {
+    let rules = list_rules(conn)?;
+    let now = Utc::now();
+    let sql = format!(
+        "SELECT e.id, e.task_id, t.title, t.project, e.note, e.started_at, e.ended_at
+         FROM time_entries e JOIN tasks t ON t.id = e.task_id
+         WHERE t.deleted_at IS NULL AND e.billable = 1
+           AND e.started_at < ?2 AND (e.ended_at IS NULL OR e.ended_at > ?1)
+           AND (?3 IS NULL OR t.project = ?3)
+           AND {TAG_FILTER_SQL}
+         ORDER BY e.started_at, e.id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params![
+        format_utc(range.from),
+        format_utc(range.to),
+        query.project,
+        query.tag
+    ])?;
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next()? {
+        let started_at: String = row.get(5)?;
+        let ended_at: Option<String> = row.get(6)?;
+        let Ok(start) = parse_utc(&started_at) else {
+            continue;
+        };
+        let end = match ended_at.as_deref().map(parse_utc) {
+            Some(Ok(end)) => end,
+            Some(Err(_)) => continue,
+            None => now,
+        };
+        let (start, end) = (start.max(range.from), end.min(range.to));
+        if end <= start {
+            continue;
+        }
+
+        let project: Option<String> = row.get(3)?;
+        let rule = rule_for(&rules, project.as_deref());
+        let tracked = (end - start).num_seconds();
+        let billed = rule.map_or(tracked, |r| {
+            round_seconds(tracked, r.round_minutes, r.rounding)
+        });
+        let hourly_rate = rule.and_then(|r| r.hourly_rate);
+        entries.push(BilledEntry {
+            entry_id: row.get(0)?,
+            task_id: row.get(1)?,
+            title: row.get(2)?,
+            project,
+            note: row.get(4)?,
+            started_at: format_utc(start),
+            ended_at: ended_at.map(|_| format_utc(end)),
+            tracked_seconds: tracked,
+            billed_seconds: billed,
+            hourly_rate,
+            amount: hourly_rate.map(|rate| amount_for(billed, rate)),
+            date: range.local_date(start).format("%Y-%m-%d").to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Total `entries` from `billed_entries`. Each entry counts whole towards
+/// the period it started in, so rounding is never split across days.
+pub fn billing_report(
+    query: &ReportQuery,
+    range: &Range,
+    entries: &[BilledEntry],
+) -> BillingReport {
+    let mut lines: BTreeMap<String, BillingLine> = BTreeMap::new();
+    let (mut tracked, mut billed, mut amount, mut unrated) = (0i64, 0i64, 0.0, 0i64);
+    for entry in entries {
+        let (key, label) = match query.group_by {
+            GroupBy::Project => (
+                entry.project.clone().unwrap_or_default(),
+                entry
+                    .project
+                    .clone()
+                    .unwrap_or_else(|| NO_PROJECT.to_string()),
+            ),
+            GroupBy::Task => (entry.task_id.clone(), entry.title.clone()),
+            _ => {
+                let date =
+                    chrono::NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d").unwrap_or_default();
+                let key = reports::period_key(date, query.group_by);
+                (key.clone(), key)
+            }
+        };
+        let line = lines.entry(key.clone()).or_insert_with(|| BillingLine {
+            key,
+            label,
+            tracked_seconds: 0,
+            billed_seconds: 0,
+            amount: 0.0,
+            entry_count: 0,
+        });
+        line.tracked_seconds += entry.tracked_seconds;
+        line.billed_seconds += entry.billed_seconds;
+        line.amount += entry.amount.unwrap_or(0.0);
+        line.entry_count += 1;
+
+        tracked += entry.tracked_seconds;
+        billed += entry.billed_seconds;
+        match entry.amount {
+            Some(value) => amount += value,
+            None => unrated += entry.billed_seconds,
+        }
+    }
+
+    let mut lines: Vec<BillingLine> = lines.into_values().collect();
+    for line in &mut lines {
+        line.amount = (line.amount * 100.0).round() / 100.0;
+    }
+    if !query.group_by.is_calendar() {
+        lines.sort_by(|a, b| {
+            b.billed_seconds
+                .cmp(&a.billed_seconds)
+                .then_with(|| a.label.to_lowercase().cmp(&b.label.to_lowercase()))
+        });
+    }
+    BillingReport {
+        group_by: query.group_by,
+        from: format_utc(range.from),
+        to: format_utc(range.to),
+        lines,
+        tracked_seconds: tracked,
+        billed_seconds: billed,
+        amount: (amount * 100.0).round() / 100.0,
+        unrated_seconds: unrated,
+    }
+}
+
+/// Decimal hours with two places, as invoices show them.
+pub fn hours(seconds: i64) -> String {
+    format!("{:.2}", seconds as f64 / 3600.0)
+}
+
+fn invoice_record(entry: &BilledEntry) -> Vec<String> {
+    vec![
+        entry.date.clone(),
+        entry.project.clone().unwrap_or_default(),
+        entry.title.clone(),
+        entry.note.clone().unwrap_or_default(),
+        entry.started_at.clone(),
+        entry.ended_at.clone().unwrap_or_default(),
+        hours(entry.tracked_seconds),
+        hours(entry.billed_seconds),
+        entry
+            .hourly_rate
+            .map(|rate| format!("{rate:.2}"))
+            .unwrap_or_default(),
+        entry
+            .amount
+            .map(|amount| format!("{amount:.2}"))
+            .unwrap_or_default(),
+    ]
+}
+
+fn check_query(query: &ReportQuery) -> CommandResult<Range> {
+    if query.group_by == GroupBy::Tag {
+        return Err(CommandError::invalid("Billing can't be grouped by tag"));
+    }
+    Range::from_query(query)
+}
+
+#[tauri::command]
+pub fn list_billing_rules(db: State<'_, Db>) -> CommandResult<Vec<BillingRule>> {
+    db.with_conn(|conn| list_rules(conn))
+}
+
+/// Create or replace the rule for `rule.project`, or the default rule.
+#[tauri::command]
+pub fn set_billing_rule(db: State<'_, Db>, rule: BillingRule) -> CommandResult<BillingRule> {
+    let mut rule = rule;
+    rule.project = rule
+        .project
+        .as_deref()
+        .map(projects::normalize_name)
+        .transpose()?;
+    if !(0..=1440).contains(&rule.round_minutes) {
+        return Err(CommandError::invalid(
+            "Rounding must be between 0 and 1440 minutes",
+        ));
+    }
+    if rule
+        .hourly_rate
+        .is_some_and(|rate| !rate.is_finite() || rate < 0.0)
+    {
+        return Err(CommandError::invalid("Hourly rate can't be negative"));
+    }
+    rule.updated_at = now_utc();
+    db.with_conn(|conn| {
+        conn.execute(
+            &format!(
+                "INSERT INTO billing_rules ({RULE_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(project) DO UPDATE SET
+                     round_minutes = excluded.round_minutes,
+                     rounding = excluded.rounding,
+                     hourly_rate = excluded.hourly_rate,
+                     updated_at = excluded.updated_at"
+            ),
+            params![
+                rule.project.clone().unwrap_or_default(),
+                rule.round_minutes,
+                rule.rounding.as_str(),
+                rule.hourly_rate,
+                rule.updated_at,
+            ],
+        )
+    })?;
+    Ok(rule)
+}
+
+/// Remove the rule for `project`, or the default rule when it's `None`.
+#[tauri::command]
+pub fn delete_billing_rule(db: State<'_, Db>, project: Option<String>) -> CommandResult<()> {
+    let project = project.unwrap_or_default();
+    let deleted = db.with_conn(|conn| {
+        conn.execute(
+            "DELETE FROM billing_rules WHERE project = ?1",
+            params![project.trim()],
+        )
+    })?;
+    if deleted == 0 {
+        return Err(CommandError::not_found("No billing rule for that project"));
+    }
+    Ok(())
+}
+
+/// Billable time in the range after rounding, with amounts where a rate is
+/// set.
+#[tauri::command]
+pub fn report_billing(db: State<'_, Db>, query: ReportQuery) -> CommandResult<BillingReport> {
+    let range = check_query(&query)?;
+    let entries = db.with_conn(|conn| billed_entries(conn, &query, &range))?;
+    Ok(billing_report(&query, &range, &entries))
+}
+
+/// Write the range's billable entries to a CSV at `path`, one line per
+/// entry (see `INVOICE_FIELDS`), and return the totals.
+#[tauri::command]
+pub fn export_invoice(
+    db: State<'_, Db>,
+    query: ReportQuery,
+    path: String,
+) -> CommandResult<BillingReport> {
+    let range = check_query(&query)?;
+    let entries = db.with_conn(|conn| billed_entries(conn, &query, &range))?;
+
+    let mut out = Vec::new();
+    let write = |out: &mut Vec<u8>, fields: &[&str]| {
+        csv::write_record(out, fields).map_err(|e| e.to_string())
+    };
+    write(&mut out, INVOICE_FIELDS)?;
+    for entry in &entries {
+        let record = invoice_record(entry);
+        let fields: Vec<&str> = record.iter().map(String::as_str).collect();
+        write(&mut out, &fields)?;
+    }
+    write_atomic(Path::new(&path), &out)?;
+    Ok(billing_report(&query, &range, &entries))
+}
@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::Db;
+use crate::dependencies;
+use crate::error::{CommandError, CommandResult};
+use crate::journal;
+use crate::projects;
+use crate::recurrence;
+use crate::tags;
+use crate::task_store::{self, double_option, Task, TaskPatch, STATUS_DONE};
+
+/// New dates for `reschedule_tasks`. A missing key leaves the date alone
+/// while an explicit `null` clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Reschedule {
+    #[serde(deserialize_with = "double_option")]
+    pub due: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub scheduled: Option<Option<String>>,
+}
+
+/// Patch every task in `ids` in one transaction, so the batch is a single
+/// undo step and a single `data-changed` event. An unknown id fails the
+/// whole batch.
+fn run(
+    app: &AppHandle,
+    db: &Db,
+    ids: Vec<String>,
+    patch_for: impl Fn(&Task) -> TaskPatch,
+) -> CommandResult<Vec<Task>> {
+    let mut seen = HashSet::new();
+    let ids: Vec<String> = ids
+        .into_iter()
+        .filter(|id| seen.insert(id.clone()))
+        .collect();
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (tasks, next, unblocked) = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let mut tasks = Vec::with_capacity(ids.len());
+        let mut next = Vec::new();
+        let mut unblocked: Vec<Task> = Vec::new();
+        for id in &ids {
+            let Some(task) = task_store::find_task(&tx, id)? else {
+                return Ok(Err(CommandError::not_found(format!(
+                    "Task not found: {id}"
+                ))));
+            };
+            let patch = patch_for(&task);
+            let (task, instance, freed) = task_store::save_patch(&tx, task, &patch)?;
+            next.extend(instance);
+            for task in freed {
+                if !seen.contains(&task.id) && !unblocked.iter().any(|t| t.id == task.id) {
+                    unblocked.push(task);
+                }
+            }
+            tasks.push(task);
+        }
+        tx.commit()?;
+        Ok(Ok((tasks, next, unblocked)))
+    })??;
+
+    if !next.is_empty() {
+        let _ = app.emit(recurrence::RECURRENCE_EVENT, next);
+    }
+    if !unblocked.is_empty() {
+        let _ = app.emit(dependencies::UNBLOCKED_EVENT, unblocked);
+    }
+    journal::announce(app, db, "bulk");
+    Ok(tasks)
+}
+
+/// Mark tasks done, creating the next instance of any recurring ones.
+#[tauri::command]
+pub fn complete_tasks(
+    app: AppHandle,
+    db: State<'_, Db>,
+    ids: Vec<String>,
+) -> CommandResult<Vec<Task>> {
+    run(&app, &db, ids, |_| TaskPatch {
+        status: Some(STATUS_DONE.to_string()),
+        ..TaskPatch::default()
+    })
+}
+
+/// Move tasks to `project`, or out of any project when it's `None`.
+#[tauri::command]
+pub fn move_tasks(
+    app: AppHandle,
+    db: State<'_, Db>,
+    ids: Vec<String>,
+    project: Option<String>,
+) -> CommandResult<Vec<Task>> {
+    let project = project
+        .as_deref()
+        .map(projects::normalize_name)
+        .transpose()?;
+    run(&app, &db, ids, |_| TaskPatch {
+        project: Some(project.clone()),
+        ..TaskPatch::default()
+    })
+}
+
+/// Add and remove tags on each task, keeping its other tags.
+#[tauri::command]
+pub fn retag_tasks(
+    app: AppHandle,
+    db: State<'_, Db>,
+    ids: Vec<String>,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> CommandResult<Vec<Task>> {
+    let add = tags::normalize_names(&add)?;
+    let remove = tags::normalize_names(&remove)?;
+    run(&app, &db, ids, |task| {
+        let mut names: Vec<String> = task
+            .tags
+            .iter()
+            .filter(|name| !remove.iter().any(|r| r.eq_ignore_ascii_case(name)))
+            .cloned()
+            .collect();
+        for name in &add {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name.clone());
+            }
+        }
+        TaskPatch {
+            tags: Some(names),
+            ..TaskPatch::default()
+        }
+    })
+}
+
+#[tauri::command]
+pub fn reschedule_tasks(
+    app: AppHandle,
+    db: State<'_, Db>,
+    ids: Vec<String>,
+    dates: Reschedule,
+) -> CommandResult<Vec<Task>> {
+    if dates.due.is_none() && dates.scheduled.is_none() {
+        return Err(CommandError::invalid("Nothing to reschedule"));
+    }
+    run(&app, &db, ids, |_| TaskPatch {
+        due: dates.due.clone(),
+        scheduled: dates.scheduled.clone(),
+        ..TaskPatch::default()
+    })
+}
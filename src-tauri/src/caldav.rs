@@ -0,0 +1,1517 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{CONTENT_TYPE, ETAG, LOCATION};
+use reqwest::{redirect, Certificate, Client, ClientBuilder, Method, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::calendars;
+use crate::conflicts::{self, ConflictPolicy};
+use crate::credentials;
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::ics;
+use crate::outbox::{self, Replay};
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{
+    self, Capabilities, RemoteCollection, SyncFuture, SyncMode, SyncProvider,
+};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::task_store::{self, double_option};
+use crate::timezone;
+use crate::trash;
+use crate::xml::{self, Element};
+
+/// `external_refs.source` for tasks that came from a CalDAV server.
+const SOURCE: &str = "caldav";
+/// The provider paused when a server throttles calendar fetches.
+const CALENDAR_SOURCE: &str = "caldav_calendar";
+
+/// `caldav_accounts.provider` for accounts set up as Nextcloud.
+pub const PROVIDER_NEXTCLOUD: &str = "nextcloud";
+/// Nextcloud offers each Deck board as a calendar of read-only tasks.
+const NEXTCLOUD_DECK_PREFIX: &str = "app-generated--deck--";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: usize = 5;
+/// Calendar objects fetched per `calendar-multiget` request.
+const MULTIGET_BATCH: usize = 50;
+
+const PROPFIND_START: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:cs="http://calendarserver.org/ns/">
+  <d:prop><d:current-user-principal/><d:resourcetype/><d:displayname/><c:supported-calendar-component-set/><cs:getctag/></d:prop>
+</d:propfind>"#;
+
+const PROPFIND_HOME: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><c:calendar-home-set/></d:prop>
+</d:propfind>"#;
+
+const PROPFIND_COLLECTIONS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:cs="http://calendarserver.org/ns/">
+  <d:prop><d:resourcetype/><d:displayname/><c:supported-calendar-component-set/><cs:getctag/></d:prop>
+</d:propfind>"#;
+
+const PROPFIND_CTAG: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:cs="http://calendarserver.org/ns/">
+  <d:prop><cs:getctag/></d:prop>
+</d:propfind>"#;
+
+const REPORT_ETAGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+/// Events overlapping a time range, for the day view. `{start}` and `{end}`
+/// are UTC date-times.
+const REPORT_EVENTS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT">
+    <c:time-range start="{start}" end="{end}"/>
+  </c:comp-filter></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaldavCollection {
+    pub id: String,
+    pub account_id: String,
+    pub url: String,
+    pub name: String,
+    /// Local project the collection's tasks are filed under. New tasks in
+    /// this project are uploaded to the collection.
+    pub project: Option<String>,
+    pub enabled: bool,
+    pub last_synced_at: Option<String>,
+}
+
+/// Trust for servers whose certificate the system doesn't accept, such as
+/// a self-hosted one with a self-signed certificate.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsOptions {
+    /// PEM certificates to trust: the CA that signed the server's, or the
+    /// server's own.
+    pub certificate: Option<String>,
+    /// Accept any certificate. The connection can then be intercepted, so
+    /// this is for servers on a trusted network only.
+    pub accept_invalid_certs: bool,
+}
+
+impl TlsOptions {
+    pub fn normalized(mut self) -> Self {
+        self.certificate = self
+            .certificate
+            .map(|pem| pem.trim().to_string())
+            .filter(|pem| !pem.is_empty());
+        self
+    }
+
+    pub fn apply(&self, builder: ClientBuilder) -> CommandResult<ClientBuilder> {
+        let mut builder = builder.danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(pem) = &self.certificate {
+            let certificates = Certificate::from_pem_bundle(pem.as_bytes())
+                .map_err(|e| CommandError::invalid(format!("Invalid certificate: {e}")))?;
+            if certificates.is_empty() {
+                return Err(CommandError::invalid("The certificate isn't in PEM format"));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaldavAccount {
+    pub id: String,
+    pub name: String,
+    pub server_url: String,
+    pub username: String,
+    /// Set for accounts added through a preset, such as `nextcloud`.
+    pub provider: Option<String>,
+    pub tls: TlsOptions,
+    pub created_at: String,
+    pub updated_at: String,
+    pub collections: Vec<CaldavCollection>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewCaldavAccount {
+    /// Defaults to the server's host name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The server's base URL, a principal or a single collection. Nextcloud
+    /// is `https://host/remote.php/dav`; Fastmail is
+    /// `https://caldav.fastmail.com/`.
+    pub server_url: String,
+    pub username: String,
+    /// Kept in the OS keyring. Use an app password where the server has
+    /// them.
+    pub password: String,
+    #[serde(default)]
+    pub tls: TlsOptions,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CollectionPatch {
+    pub enabled: Option<bool>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CaldavSyncReport {
+    pub collection_id: String,
+    pub name: String,
+    /// Tasks created or updated from the server.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because they were deleted on the server.
+    pub removed: usize,
+    /// Tasks uploaded, new or changed.
+    pub uploaded: usize,
+    /// Tasks deleted on the server because they were deleted here.
+    pub deleted: usize,
+    /// Uploads refused because the server copy changed meanwhile. They're
+    /// merged and retried on the next sync.
+    pub conflicts: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for CaldavSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.uploaded + self.deleted
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+fn row_to_collection(row: &Row) -> rusqlite::Result<CaldavCollection> {
+    Ok(CaldavCollection {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        url: row.get(2)?,
+        name: row.get(3)?,
+        project: row.get(4)?,
+        enabled: row.get(5)?,
+        last_synced_at: row.get(6)?,
+    })
+}
+
+const COLLECTION_COLUMNS: &str = "id, account_id, url, name, project, enabled, last_synced_at";
+
+fn list_collections(
+    conn: &Connection,
+    account_id: &str,
+) -> rusqlite::Result<Vec<CaldavCollection>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLLECTION_COLUMNS} FROM caldav_collections
+         WHERE account_id = ?1 ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map(params![account_id], row_to_collection)?;
+    rows.collect()
+}
+
+fn find_collection(conn: &Connection, id: &str) -> rusqlite::Result<Option<CaldavCollection>> {
+    conn.query_row(
+        &format!("SELECT {COLLECTION_COLUMNS} FROM caldav_collections WHERE id = ?1"),
+        params![id],
+        row_to_collection,
+    )
+    .optional()
+}
+
+const ACCOUNT_COLUMNS: &str = "id, name, server_url, username, provider, tls_certificate, \
+                               accept_invalid_certs, created_at, updated_at";
+
+fn row_to_account(row: &Row) -> rusqlite::Result<CaldavAccount> {
+    Ok(CaldavAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        server_url: row.get(2)?,
+        username: row.get(3)?,
+        provider: row.get(4)?,
+        tls: TlsOptions {
+            certificate: row.get(5)?,
+            accept_invalid_certs: row.get(6)?,
+        },
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+        collections: Vec::new(),
+    })
+}
+
+fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<CaldavAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM caldav_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    let mut accounts = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    for account in &mut accounts {
+        account.collections = list_collections(conn, &account.id)?;
+    }
+    Ok(accounts)
+}
+
+pub fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<CaldavAccount>> {
+    let account = conn
+        .query_row(
+            &format!("SELECT {ACCOUNT_COLUMNS} FROM caldav_accounts WHERE id = ?1"),
+            params![id],
+            row_to_account,
+        )
+        .optional()?;
+    let Some(mut account) = account else {
+        return Ok(None);
+    };
+    account.collections = list_collections(conn, &account.id)?;
+    Ok(Some(account))
+}
+
+pub fn keyring_name(account_id: &str) -> String {
+    format!("caldav:{account_id}")
+}
+
+/// One `<response>` of a multistatus reply, with the properties the server
+/// found.
+pub struct DavResponse {
+    pub href: String,
+    props: Vec<Element>,
+}
+
+impl DavResponse {
+    fn prop(&self, name: &str) -> Option<&Element> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.prop(name)
+            .map(|p| p.text.trim().to_string())
+            .filter(|t| !t.is_empty())
+    }
+
+    fn is_calendar(&self) -> bool {
+        self.prop("resourcetype")
+            .is_some_and(|t| t.child("calendar").is_some())
+    }
+
+    /// Whether the collection takes `component`s such as `VTODO`. Servers
+    /// that don't list the component types accept everything.
+    fn holds(&self, component: &str) -> bool {
+        match self.prop("supported-calendar-component-set") {
+            Some(set) => set.children("comp").any(|c| {
+                c.attr("name")
+                    .is_some_and(|n| n.eq_ignore_ascii_case(component))
+            }),
+            None => true,
+        }
+    }
+
+    fn discovered(&self, fallback_name: impl FnOnce() -> String) -> Discovered {
+        Discovered {
+            url: self.href.clone(),
+            name: self.text("displayname").unwrap_or_else(fallback_name),
+            todos: self.holds("VTODO"),
+            events: self.holds("VEVENT"),
+        }
+    }
+}
+
+/// A calendar collection found on the server.
+struct Discovered {
+    url: String,
+    name: String,
+    /// Holds tasks, and can be synced as a task list.
+    todos: bool,
+    /// Holds events, and can be shown in the day view.
+    events: bool,
+}
+
+fn is_ok_status(status: Option<&Element>) -> bool {
+    status.is_none_or(|s| s.text.split_whitespace().nth(1) == Some("200"))
+}
+
+fn parse_multistatus(body: &str) -> CommandResult<Vec<DavResponse>> {
+    let root = xml::parse(body).context("Unreadable server response")?;
+    let mut responses = Vec::new();
+    for response in root.children("response") {
+        let Some(href) = response.find_text("href") else {
+            continue;
+        };
+        if !is_ok_status(response.child("status")) {
+            continue;
+        }
+        let props = response
+            .children("propstat")
+            .filter(|p| is_ok_status(p.child("status")))
+            .filter_map(|p| p.child("prop"))
+            .flat_map(|p| p.children.iter().cloned())
+            .collect();
+        responses.push(DavResponse { href, props });
+    }
+    Ok(responses)
+}
+
+/// An authenticated connection to one account's server.
+pub struct Session {
+    client: Client,
+    username: String,
+    password: String,
+    /// The provider paused when the server throttles the requests.
+    provider: &'static str,
+}
+
+impl Session {
+    pub fn new(
+        provider: &'static str,
+        username: &str,
+        password: &str,
+        tls: &TlsOptions,
+    ) -> CommandResult<Self> {
+        // Redirects are followed by hand: the client would turn a PROPFIND
+        // into a GET on a 301 or 302.
+        let builder = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(REQUEST_TIMEOUT);
+        let client = tls.apply(builder)?.build()?;
+        Ok(Self {
+            client,
+            username: username.to_string(),
+            password: password.to_string(),
+            provider,
+        })
+    }
+
+    /// Send a request, following redirects with the same method and body.
+    /// Returns the final URL along with the response.
+    pub async fn send(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &[(&str, &str)],
+        body: Option<&str>,
+    ) -> CommandResult<(Url, Response)> {
+        let method = Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+        let server = url.host_str().unwrap_or("The server").to_string();
+        rate_limit::check(self.provider, &server)?;
+        let mut url = url.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            let mut request = self
+                .client
+                .request(method.clone(), url.clone())
+                .basic_auth(&self.username, Some(&self.password));
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            if let Some(body) = body {
+                request = request.body(body.to_string());
+            }
+            let response = request
+                .send()
+                .await
+                .with_context(|| url.host_str().unwrap_or("server").to_string())?;
+            if !response.status().is_redirection() {
+                match response.status() {
+                    StatusCode::UNAUTHORIZED => {
+                        return Err(CommandError::unauthorized(
+                            "The server rejected the username or password",
+                        )
+                        .with_provider(self.provider))
+                    }
+                    StatusCode::TOO_MANY_REQUESTS => {
+                        return Err(rate_limit::limited(
+                            self.provider,
+                            &server,
+                            response.headers(),
+                        ))
+                    }
+                    _ => {}
+                }
+                return Ok((url, response));
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or("Redirect without a location")?;
+            url = url
+                .join(location)
+                .map_err(|e| format!("Bad redirect: {e}"))?;
+        }
+        Err("Too many redirects".into())
+    }
+
+    /// PROPFIND `url` for the account health check, following redirects,
+    /// with the raw result so an unreachable server can be told from one
+    /// that refuses the password.
+    pub async fn probe(&self, url: &Url) -> Result<Response, reqwest::Error> {
+        let method = Method::from_bytes(b"PROPFIND").expect("PROPFIND is a valid method");
+        let mut url = url.clone();
+        let mut redirects = 0;
+        loop {
+            let response = self
+                .client
+                .request(method.clone(), url.clone())
+                .basic_auth(&self.username, Some(&self.password))
+                .header("Depth", "0")
+                .send()
+                .await?;
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .and_then(|l| url.join(l).ok());
+            match location {
+                Some(next) if response.status().is_redirection() && redirects < MAX_REDIRECTS => {
+                    url = next;
+                    redirects += 1;
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+
+    /// PROPFIND or REPORT, expecting a 207 multistatus. Hrefs are resolved
+    /// against the final URL.
+    pub async fn multistatus(
+        &self,
+        method: &str,
+        url: &Url,
+        depth: &str,
+        body: &str,
+    ) -> CommandResult<(Url, Vec<DavResponse>)> {
+        let headers = [
+            ("Depth", depth),
+            ("Content-Type", "application/xml; charset=utf-8"),
+        ];
+        let (url, response) = self.send(method, url, &headers, Some(body)).await?;
+        let status = response.status();
+        if status != StatusCode::MULTI_STATUS {
+            return Err(CommandError::status(
+                status,
+                format!("{method} {}: HTTP {}", url.path(), status.as_u16()),
+            )
+            .with_provider(self.provider));
+        }
+        let text = response.text().await?;
+        let mut responses = parse_multistatus(&text)?;
+        for response in &mut responses {
+            if let Ok(resolved) = url.join(&response.href) {
+                response.href = resolved.to_string();
+            }
+        }
+        Ok((url, responses))
+    }
+}
+
+fn parse_url(value: &str) -> CommandResult<Url> {
+    let url = Url::parse(value.trim())
+        .map_err(|e| CommandError::invalid(format!("Invalid server URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(CommandError::invalid(
+            "The server URL must start with http:// or https://",
+        ));
+    }
+    Ok(url)
+}
+
+/// A collection's URL always ends in a slash, so member names join onto it.
+pub fn collection_url(href: &str) -> CommandResult<Url> {
+    let mut url = parse_url(href)?;
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+/// Every calendar collection under `server` that can hold tasks or events.
+/// `server` may itself be such a collection.
+async fn discover(session: &Session, server: &Url) -> CommandResult<Vec<Discovered>> {
+    let (start, found) = session
+        .multistatus("PROPFIND", server, "0", PROPFIND_START)
+        .await?;
+    if let Some(own) = found.first().filter(|r| r.is_calendar()) {
+        return Ok(vec![own.discovered(|| start.to_string())]);
+    }
+
+    let mut principal = found
+        .iter()
+        .find_map(|r| r.prop("current-user-principal"))
+        .and_then(|p| p.find_text("href"));
+    if principal.is_none() {
+        let well_known = start
+            .join("/.well-known/caldav")
+            .map_err(|e| e.to_string())?;
+        if let Ok((url, found)) = session
+            .multistatus("PROPFIND", &well_known, "0", PROPFIND_START)
+            .await
+        {
+            principal = found
+                .iter()
+                .find_map(|r| r.prop("current-user-principal"))
+                .and_then(|p| p.find_text("href"))
+                .and_then(|href| url.join(&href).ok())
+                .map(String::from);
+        }
+    }
+    // Without a principal, the URL given may be one already.
+    let principal = match principal {
+        Some(href) => start.join(&href).map_err(|e| e.to_string())?,
+        None => start.clone(),
+    };
+
+    let (principal, found) = session
+        .multistatus("PROPFIND", &principal, "0", PROPFIND_HOME)
+        .await?;
+    let home = found
+        .iter()
+        .find_map(|r| r.prop("calendar-home-set"))
+        .and_then(|p| p.find_text("href"))
+        .ok_or("The server didn't say where the calendars are")?;
+    let home = principal.join(&home).map_err(|e| e.to_string())?;
+
+    let (_, found) = session
+        .multistatus("PROPFIND", &home, "1", PROPFIND_COLLECTIONS)
+        .await?;
+    Ok(found
+        .iter()
+        .filter(|r| r.is_calendar())
+        .map(|r| {
+            r.discovered(|| {
+                let trimmed = r.href.trim_end_matches('/');
+                trimmed.rsplit('/').next().unwrap_or(trimmed).to_string()
+            })
+        })
+        .filter(|d| d.todos || d.events)
+        .collect())
+}
+
+/// Adjust what `discover` found for the account's preset. Nextcloud lists
+/// and calendars are labelled with the account, so those of two accounts
+/// can be told apart, and Deck boards, which can't be written to, are left
+/// out.
+fn for_provider(
+    mut found: Vec<Discovered>,
+    provider: Option<&str>,
+    label: &str,
+) -> Vec<Discovered> {
+    if provider == Some(PROVIDER_NEXTCLOUD) {
+        found.retain(|d| !d.url.contains(NEXTCLOUD_DECK_PREFIX));
+        for discovered in &mut found {
+            discovered.name = format!("{} ({label})", discovered.name);
+        }
+    }
+    found
+}
+
+/// Save what `discover` found: task lists as collections, and event
+/// calendars for the day view. Collections the server no longer lists are
+/// dropped along with their sync state; their tasks stay.
+fn store_collections(
+    conn: &Connection,
+    account_id: &str,
+    found: &[Discovered],
+) -> rusqlite::Result<()> {
+    let calendars: Vec<(&str, &str)> = found
+        .iter()
+        .filter(|d| d.events)
+        .map(|d| (d.url.as_str(), d.name.as_str()))
+        .collect();
+    calendars::store_discovered(conn, account_id, &calendars)?;
+
+    let found: Vec<(&str, &str)> = found
+        .iter()
+        .filter(|d| d.todos)
+        .map(|d| (d.url.as_str(), d.name.as_str()))
+        .collect();
+    for (url, name) in &found {
+        conn.execute(
+            "INSERT INTO caldav_collections (id, account_id, url, name)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(account_id, url) DO UPDATE SET name = excluded.name",
+            params![uuid::Uuid::new_v4().to_string(), account_id, url, name],
+        )?;
+    }
+    let urls: Vec<&str> = found.iter().map(|(url, _)| *url).collect();
+    let urls = serde_json::to_string(&urls).unwrap_or_default();
+    conn.execute(
+        "DELETE FROM caldav_collections
+         WHERE account_id = ?1 AND url NOT IN (SELECT value FROM json_each(?2))",
+        params![account_id, urls],
+    )?;
+    Ok(())
+}
+
+/// What the last sync knew about one calendar object.
+#[derive(Debug, Clone)]
+struct ItemRow {
+    uid: String,
+    etag: Option<String>,
+    task_id: String,
+    /// The task's `updated_at` when it last matched the server.
+    synced_at: String,
+}
+
+fn load_items(
+    conn: &Connection,
+    collection_id: &str,
+) -> rusqlite::Result<HashMap<String, ItemRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT href, uid, etag, task_id, synced_at FROM caldav_items WHERE collection_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![collection_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ItemRow {
+                uid: row.get(1)?,
+                etag: row.get(2)?,
+                task_id: row.get(3)?,
+                synced_at: row.get(4)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_item(
+    conn: &Connection,
+    collection_id: &str,
+    href: &str,
+    item: &ItemRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO caldav_items (collection_id, href, uid, etag, task_id, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(collection_id, href) DO UPDATE SET
+             uid = excluded.uid,
+             etag = excluded.etag,
+             task_id = excluded.task_id,
+             synced_at = excluded.synced_at",
+        params![
+            collection_id,
+            href,
+            item.uid,
+            item.etag,
+            item.task_id,
+            item.synced_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// A change to send to the server.
+enum Upload {
+    /// PUT a new object; `href` must not exist yet.
+    Create {
+        href: String,
+        item: ItemRow,
+        body: String,
+    },
+    /// PUT over the object as last seen.
+    Update {
+        href: String,
+        item: ItemRow,
+        body: String,
+    },
+    Delete {
+        href: String,
+        etag: Option<String>,
+        task_id: String,
+    },
+}
+
+impl Upload {
+    fn task_id(&self) -> &str {
+        match self {
+            Upload::Create { item, .. } | Upload::Update { item, .. } => &item.task_id,
+            Upload::Delete { task_id, .. } => task_id,
+        }
+    }
+}
+
+/// Objects fetched from the server: href, etag and calendar data.
+type Fetched = Vec<(String, Option<String>, String)>;
+
+/// Apply the server's changes, unless only exporting, then work out what to
+/// upload. `listing` is every object on the server with its etag, or
+/// `None` when the ctag says nothing changed there.
+fn merge_remote(
+    conn: &mut Connection,
+    collection: &CaldavCollection,
+    listing: Option<&HashMap<String, Option<String>>>,
+    fetched: &Fetched,
+    imports: bool,
+    report: &mut CaldavSyncReport,
+) -> rusqlite::Result<Vec<Upload>> {
+    let tx = conn.transaction()?;
+    let zone = timezone::system_zone();
+    let items = load_items(&tx, &collection.id)?;
+    let policy = conflicts::policy(&tx, SOURCE)?;
+
+    for (href, etag, data) in fetched {
+        let mut parsed = match ics::parse_todo(data) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                report.errors.push(format!("{href}: {e}"));
+                continue;
+            }
+        };
+        let known = items.get(href);
+        let task_id = match known {
+            Some(item) => Some(item.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, parsed.uid],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let local = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if known.is_some() && local.is_none() {
+            // Deleted here; the delete is uploaded below.
+            continue;
+        }
+        if !imports {
+            // Only the etag is taken, so local edits go up over the object.
+            if let Some(item) = known {
+                let item = ItemRow {
+                    etag: etag.clone(),
+                    ..item.clone()
+                };
+                save_item(&tx, &collection.id, href, &item)?;
+            }
+            continue;
+        }
+        let dirty = match (known, &local) {
+            (Some(item), Some(task)) => task.updated_at > item.synced_at,
+            _ => false,
+        };
+        parsed.existing_task_id = local.as_ref().map(|t| t.id.clone());
+
+        let remote = |tx: &Connection, policy| ics::write_item(tx, &parsed, &zone, SOURCE, policy);
+        let (mut task, created) = match local {
+            Some(local)
+                if dirty
+                    && conflicts::keep_local(&tx, SOURCE, policy, &local, |tx| {
+                        remote(tx, ConflictPolicy::Remote).map(|(task, _)| task)
+                    })? =>
+            {
+                (local, false)
+            }
+            _ => remote(&tx, policy)?,
+        };
+        if created && collection.project.is_some() {
+            task.project = collection.project.clone();
+            task_store::write_task(&tx, &task)?;
+        }
+        if created {
+            report.created += 1;
+        } else {
+            report.updated += 1;
+        }
+        let synced_at = match known {
+            // Local edits the server doesn't have yet are kept dirty.
+            Some(item) if dirty && policy != ConflictPolicy::Remote => item.synced_at.clone(),
+            _ => task.updated_at.clone(),
+        };
+        let item = ItemRow {
+            uid: parsed.uid.clone(),
+            etag: etag.clone(),
+            task_id: task.id.clone(),
+            synced_at,
+        };
+        save_item(&tx, &collection.id, href, &item)?;
+    }
+
+    if let Some(listing) = listing {
+        for (href, item) in &items {
+            if listing.contains_key(href) {
+                continue;
+            }
+            if imports && trash::trash_task(&tx, &item.task_id)? {
+                report.removed += 1;
+            }
+            tx.execute(
+                "DELETE FROM caldav_items WHERE collection_id = ?1 AND href = ?2",
+                params![collection.id, href],
+            )?;
+        }
+    }
+
+    let held = conflicts::held(&tx, SOURCE)?;
+    let mut uploads = Vec::new();
+    for (href, item) in load_items(&tx, &collection.id)? {
+        match task_store::find_task(&tx, &item.task_id)? {
+            None => uploads.push(Upload::Delete {
+                etag: item.etag.clone(),
+                href,
+                task_id: item.task_id,
+            }),
+            Some(task) if task.updated_at > item.synced_at && !held.contains(&task.id) => {
+                let body = ics::todo_calendar(&task, &item.uid);
+                let item = ItemRow {
+                    synced_at: task.updated_at,
+                    ..item
+                };
+                uploads.push(Upload::Update { href, item, body });
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(project) = &collection.project {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM tasks
+             WHERE project = ?1 COLLATE NOCASE AND deleted_at IS NULL
+               AND id NOT IN (SELECT task_id FROM caldav_items)
+             ORDER BY created_at",
+        )?;
+        let ids = stmt
+            .query_map(params![project], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        let base = Url::parse(&collection.url).ok();
+        for id in ids {
+            let (Some(task), Some(base)) = (task_store::find_task(&tx, &id)?, &base) else {
+                continue;
+            };
+            let Ok(href) = base.join(&format!("{}.ics", task.id)) else {
+                continue;
+            };
+            let uid = ics::uid(&task, "todo");
+            uploads.push(Upload::Create {
+                href: href.to_string(),
+                body: ics::todo_calendar(&task, &uid),
+                item: ItemRow {
+                    uid,
+                    etag: None,
+                    task_id: task.id.clone(),
+                    synced_at: task.updated_at.clone(),
+                },
+            });
+        }
+    }
+
+    tx.commit()?;
+    Ok(uploads)
+}
+
+/// What came of one upload.
+enum Uploaded {
+    Saved { href: String, item: ItemRow },
+    Deleted { href: String },
+    Conflict,
+}
+
+fn etag_of(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(ETAG)
+        .and_then(|e| e.to_str().ok())
+        .map(str::to_string)
+}
+
+async fn upload(session: &Session, change: Upload) -> CommandResult<Uploaded> {
+    let calendar = [(CONTENT_TYPE.as_str(), "text/calendar; charset=utf-8")];
+    match change {
+        Upload::Create { href, item, body } => {
+            let url = parse_url(&href)?;
+            let headers = [calendar[0], ("If-None-Match", "*")];
+            let (_, response) = session.send("PUT", &url, &headers, Some(&body)).await?;
+            match response.status() {
+                StatusCode::PRECONDITION_FAILED => Ok(Uploaded::Conflict),
+                status if status.is_success() => {
+                    let etag = etag_of(&response);
+                    Ok(Uploaded::Saved {
+                        href,
+                        item: ItemRow { etag, ..item },
+                    })
+                }
+                status => Err(CommandError::status(
+                    status,
+                    format!("PUT {}: HTTP {}", url.path(), status.as_u16()),
+                )
+                .with_provider(session.provider)),
+            }
+        }
+        Upload::Update { href, item, body } => {
+            let url = parse_url(&href)?;
+            let mut headers = vec![calendar[0]];
+            if let Some(etag) = &item.etag {
+                headers.push(("If-Match", etag.as_str()));
+            }
+            let (_, response) = session.send("PUT", &url, &headers, Some(&body)).await?;
+            match response.status() {
+                StatusCode::PRECONDITION_FAILED => Ok(Uploaded::Conflict),
+                status if status.is_success() => {
+                    let etag = etag_of(&response);
+                    Ok(Uploaded::Saved {
+                        href,
+                        item: ItemRow { etag, ..item },
+                    })
+                }
+                status => Err(CommandError::status(
+                    status,
+                    format!("PUT {}: HTTP {}", url.path(), status.as_u16()),
+                )
+                .with_provider(session.provider)),
+            }
+        }
+        Upload::Delete { href, etag, .. } => {
+            let url = parse_url(&href)?;
+            let headers: Vec<(&str, &str)> = etag
+                .as_deref()
+                .map(|etag| ("If-Match", etag))
+                .into_iter()
+                .collect();
+            let (_, response) = session.send("DELETE", &url, &headers, None).await?;
+            match response.status() {
+                // Changed on the server since: forget it here, and the next
+                // sync brings it back as a new task.
+                StatusCode::PRECONDITION_FAILED | StatusCode::NOT_FOUND => {
+                    Ok(Uploaded::Deleted { href })
+                }
+                status if status.is_success() => Ok(Uploaded::Deleted { href }),
+                status => Err(CommandError::status(
+                    status,
+                    format!("DELETE {}: HTTP {}", url.path(), status.as_u16()),
+                )
+                .with_provider(session.provider)),
+            }
+        }
+    }
+}
+
+async fn fetch_ctag(session: &Session, url: &Url) -> CommandResult<Option<String>> {
+    let (_, found) = session
+        .multistatus("PROPFIND", url, "0", PROPFIND_CTAG)
+        .await?;
+    Ok(found.first().and_then(|r| r.text("getctag")))
+}
+
+async fn fetch_objects(session: &Session, url: &Url, hrefs: &[String]) -> CommandResult<Fetched> {
+    let mut fetched = Vec::new();
+    for batch in hrefs.chunks(MULTIGET_BATCH) {
+        let mut body = String::from(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>"#,
+        );
+        for href in batch {
+            let path = Url::parse(href).map_or_else(|_| href.clone(), |u| u.path().to_string());
+            body.push_str(&format!("\n  <d:href>{}</d:href>", xml::escape(&path)));
+        }
+        body.push_str("\n</c:calendar-multiget>");
+        let (_, found) = session.multistatus("REPORT", url, "1", &body).await?;
+        for response in found {
+            if let Some(data) = response.prop("calendar-data") {
+                fetched.push((
+                    response.href.clone(),
+                    response.text("getetag"),
+                    data.text.clone(),
+                ));
+            }
+        }
+    }
+    Ok(fetched)
+}
+
+/// Calendar data of the objects in the calendar at `url` with an event in
+/// `window`. Recurring events come whole, for the caller to expand.
+pub async fn fetch_events(
+    account: &CaldavAccount,
+    url: &str,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Vec<String>> {
+    let session = Session::new(
+        CALENDAR_SOURCE,
+        &account.username,
+        &credentials::require(&keyring_name(&account.id), "password")?,
+        &account.tls,
+    )?;
+    let url = collection_url(url)?;
+    let stamp = |at: DateTime<Utc>| at.format("%Y%m%dT%H%M%SZ").to_string();
+    let body = REPORT_EVENTS
+        .replace("{start}", &stamp(window.0))
+        .replace("{end}", &stamp(window.1));
+    let (_, found) = session.multistatus("REPORT", &url, "1", &body).await?;
+    Ok(found
+        .into_iter()
+        .filter_map(|r| r.prop("calendar-data").map(|d| d.text.clone()))
+        .collect())
+}
+
+/// Two-way sync of one collection: pull what changed on the server (by
+/// ctag, then etag), merge it field by field, and when pushing upload
+/// local changes.
+async fn sync_collection(
+    db: &Db,
+    session: &Session,
+    collection: &CaldavCollection,
+    mode: SyncMode,
+) -> CommandResult<CaldavSyncReport> {
+    let mut report = CaldavSyncReport {
+        collection_id: collection.id.clone(),
+        name: collection.name.clone(),
+        ..CaldavSyncReport::default()
+    };
+    let url = collection_url(&collection.url)?;
+    let (known, stored_ctag) = db.with_conn(|conn| {
+        let ctag: Option<String> = conn.query_row(
+            "SELECT ctag FROM caldav_collections WHERE id = ?1",
+            params![collection.id],
+            |row| row.get(0),
+        )?;
+        Ok((load_items(conn, &collection.id)?, ctag))
+    })?;
+
+    let ctag = fetch_ctag(session, &url).await?;
+    let unchanged = ctag.is_some() && ctag == stored_ctag;
+    let (listing, fetched) = if unchanged {
+        (None, Vec::new())
+    } else {
+        let (_, found) = session
+            .multistatus("REPORT", &url, "1", REPORT_ETAGS)
+            .await?;
+        let listing: HashMap<String, Option<String>> = found
+            .into_iter()
+            .map(|r| (r.href.clone(), r.text("getetag")))
+            .collect();
+        let wanted: Vec<String> = listing
+            .iter()
+            .filter(|(href, etag)| {
+                etag.is_none() || known.get(*href).map(|item| &item.etag) != Some(*etag)
+            })
+            .map(|(href, _)| href.clone())
+            .collect();
+        let fetched = fetch_objects(session, &url, &wanted).await?;
+        (Some(listing), fetched)
+    };
+
+    let uploads = db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(
+                conn,
+                collection,
+                listing.as_ref(),
+                &fetched,
+                mode.imports(),
+                &mut report,
+            )
+        })?
+    })?;
+
+    // Importing only leaves local changes here.
+    let uploads = if mode.exports() { uploads } else { Vec::new() };
+    let uploads = db.with_conn(|conn| outbox::in_order(conn, uploads, Upload::task_id))?;
+    let mut results = Vec::new();
+    let mut replay = Replay::default();
+    for change in uploads {
+        let task_id = change.task_id().to_string();
+        match upload(session, change).await {
+            Ok(result) => {
+                // A conflict is merged and tried again next time.
+                if !matches!(result, Uploaded::Conflict) {
+                    replay.delivered(task_id);
+                }
+                results.push(result);
+            }
+            Err(e) => {
+                replay.failed(task_id, &e);
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    let changed_server = !results.is_empty();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        replay.save(&tx)?;
+        for result in &results {
+            match result {
+                Uploaded::Saved { href, item } => {
+                    report.uploaded += 1;
+                    tx.execute(
+                        "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        params![SOURCE, item.uid, item.task_id, now_utc()],
+                    )?;
+                    save_item(&tx, &collection.id, href, item)?;
+                }
+                Uploaded::Deleted { href } => {
+                    report.deleted += 1;
+                    tx.execute(
+                        "DELETE FROM caldav_items WHERE collection_id = ?1 AND href = ?2",
+                        params![collection.id, href],
+                    )?;
+                }
+                Uploaded::Conflict => report.conflicts += 1,
+            }
+        }
+        // Our own uploads change the ctag; list everything next time rather
+        // than risk missing someone else's change made meanwhile.
+        let ctag = if changed_server { None } else { ctag.clone() };
+        tx.execute(
+            "UPDATE caldav_collections SET ctag = ?2, last_synced_at = ?3 WHERE id = ?1",
+            params![collection.id, ctag, now_utc()],
+        )?;
+        tx.commit()
+    })?;
+    Ok(report)
+}
+
+/// Find the task lists and calendars on a CalDAV server and save the
+/// account, set up as `provider` when added through a preset.
+pub async fn add_account(
+    db: &Db,
+    input: NewCaldavAccount,
+    provider: Option<&str>,
+) -> CommandResult<CaldavAccount> {
+    let server = parse_url(&input.server_url)?;
+    let username = input.username.trim().to_string();
+    if username.is_empty() {
+        return Err(CommandError::invalid("Enter the account's username"));
+    }
+    let tls = input.tls.normalized();
+    let session = Session::new(SOURCE, &username, &input.password, &tls)?;
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or_else(|| server.host_str().map(str::to_string))
+        .unwrap_or_else(|| server.to_string());
+    let found = for_provider(discover(&session, &server).await?, provider, &name);
+    if found.is_empty() {
+        return Err(CommandError::not_found(
+            "No task lists or calendars found on this server",
+        ));
+    }
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    credentials::save(&keyring_name(&id), Some(&input.password))?;
+    let stored = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO caldav_accounts
+                 (id, name, server_url, username, provider, tls_certificate,
+                  accept_invalid_certs, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+            params![
+                id,
+                name,
+                server.to_string(),
+                username,
+                provider,
+                tls.certificate,
+                tls.accept_invalid_certs,
+                now
+            ],
+        )?;
+        store_collections(&tx, &id, &found)?;
+        tx.commit()?;
+        list_accounts(conn)
+    });
+    let accounts = match stored {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            return Err(e);
+        }
+    };
+    accounts
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| "Failed to save account".into())
+}
+
+/// Find the task lists and calendars on a CalDAV server and save the
+/// account. Nothing syncs or shows until it's enabled.
+#[tauri::command]
+pub async fn add_caldav_account(
+    db: State<'_, Db>,
+    input: NewCaldavAccount,
+) -> CommandResult<CaldavAccount> {
+    add_account(&db, input, None).await
+}
+
+#[tauri::command]
+pub fn list_caldav_accounts(db: State<'_, Db>) -> CommandResult<Vec<CaldavAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+async fn refresh_collections(db: &Db, account_id: &str) -> CommandResult<Vec<CaldavCollection>> {
+    let account = db
+        .with_conn(|conn| find_account(conn, account_id))?
+        .ok_or_else(|| {
+            CommandError::not_found(format!("CalDAV account not found: {account_id}"))
+        })?;
+    let session = Session::new(
+        SOURCE,
+        &account.username,
+        &credentials::require(&keyring_name(&account.id), "password")?,
+        &account.tls,
+    )?;
+    let found = discover(&session, &parse_url(&account.server_url)?).await?;
+    let found = for_provider(found, account.provider.as_deref(), &account.name);
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_collections(&tx, &account.id, &found)?;
+        tx.commit()?;
+        list_collections(conn, &account.id)
+    })
+}
+
+/// Look for collections and calendars added or removed on the server since.
+#[tauri::command]
+pub async fn refresh_caldav_collections(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<CaldavCollection>> {
+    refresh_collections(&db, &account_id).await
+}
+
+fn update_collection(db: &Db, id: &str, patch: CollectionPatch) -> CommandResult<CaldavCollection> {
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut collection) = find_collection(conn, id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Collection not found: {id}"
+            ))));
+        };
+        if let Some(project) = project {
+            collection.project = project;
+        }
+        if let Some(enabled) = patch.enabled {
+            collection.enabled = enabled;
+        }
+        if collection.enabled && collection.project.is_none() {
+            collection.project = Some(collection.name.clone());
+        }
+        conn.execute(
+            "UPDATE caldav_collections SET project = ?2, enabled = ?3 WHERE id = ?1",
+            params![collection.id, collection.project, collection.enabled],
+        )?;
+        Ok(Ok(collection))
+    })?
+}
+
+/// Turn syncing of a collection on or off, or change its project. A
+/// collection is enabled with a project named after it unless one is given.
+#[tauri::command]
+pub fn update_caldav_collection(
+    db: State<'_, Db>,
+    id: String,
+    patch: CollectionPatch,
+) -> CommandResult<CaldavCollection> {
+    update_collection(&db, &id, patch)
+}
+
+/// Forget an account and its sync state. Its tasks stay, unlinked.
+#[tauri::command]
+pub fn remove_caldav_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db
+        .with_conn(|conn| conn.execute("DELETE FROM caldav_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "CalDAV account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+pub struct Caldav;
+
+impl SyncProvider for Caldav {
+    type Report = Vec<CaldavSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "CalDAV"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: true,
+            conflicts: true,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    credentials::require(&keyring_name(&account.id), "password")
+                        .and_then(|p| Session::new(SOURCE, &account.username, &p, &account.tls))?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                let session = credentials::require(&keyring_name(&account.id), "password")
+                    .and_then(|p| Session::new(SOURCE, &account.username, &p, &account.tls));
+                match (session, parse_url(&account.server_url)) {
+                    (Ok(session), Ok(url)) => {
+                        health.answer("The server", session.probe(&url).await);
+                    }
+                    (Err(e), _) => health.no_credentials(&e),
+                    (_, Err(e)) => health.diagnostics.push(e.to_string()),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Import))
+    }
+
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::TwoWay))
+    }
+
+    fn export<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Export))
+    }
+
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let collections = match refresh_collections(db, &account.id).await {
+                    Ok(collections) => collections,
+                    Err(e) => {
+                        tracing::warn!("refresh of {} failed: {e}", account.name);
+                        account.collections
+                    }
+                };
+                found.extend(collections.into_iter().map(|c| RemoteCollection {
+                    id: c.id,
+                    account_id: c.account_id,
+                    name: c.name,
+                    selected: c.enabled,
+                }));
+            }
+            Ok(found)
+        })
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        let patch = CollectionPatch {
+            enabled: Some(selected),
+            ..CollectionPatch::default()
+        };
+        update_collection(db, id, patch).map(drop)
+    }
+}
+
+/// Sync every enabled collection of `account_id`, or of all accounts. One
+/// collection failing doesn't stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_caldav(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<CaldavSyncReport>> {
+    sync_provider::run_command(&app, &Caldav, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+    mode: SyncMode,
+) -> CommandResult<Vec<CaldavSyncReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let collections: Vec<&CaldavCollection> =
+            account.collections.iter().filter(|c| c.enabled).collect();
+        if collections.is_empty() {
+            continue;
+        }
+        let session = credentials::require(&keyring_name(&account.id), "password")
+            .and_then(|p| Session::new(SOURCE, &account.username, &p, &account.tls));
+        for collection in collections {
+            let result = match &session {
+                Ok(session) => sync_collection(db, session, collection, mode).await,
+                Err(e) => Err(e.clone()),
+            };
+            let report = result.unwrap_or_else(|e| {
+                tracing::warn!("sync of {} failed: {e}", collection.name);
+                CaldavSyncReport {
+                    collection_id: collection.id.clone(),
+                    name: collection.name.clone(),
+                    errors: vec![e.to_string()],
+                    ..CaldavSyncReport::default()
+                }
+            });
+            progress.record(&report.name, &report);
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
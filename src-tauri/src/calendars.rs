@@ -0,0 +1,822 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Days, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::caldav::{self, CaldavAccount};
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::ews;
+use crate::ews_calendar;
+use crate::google;
+use crate::google_calendar;
+use crate::http;
+use crate::ics::{self, IcsEvent};
+use crate::microsoft;
+use crate::microsoft_calendar;
+use crate::reports;
+use crate::timezone;
+
+/// Emitted after calendars are fetched, so the day view reloads its events.
+pub const CALENDAR_EVENTS_EVENT: &str = "calendar-events-changed";
+
+/// Events are cached from this many days back to this many ahead.
+const FETCH_PAST_DAYS: u64 = 7;
+const FETCH_AHEAD_DAYS: u64 = 60;
+const REFRESH_EVERY: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+/// How often a subscribed .ics URL is polled unless set on the calendar.
+const DEFAULT_FEED_MINUTES: i64 = 60;
+/// Feeds can be polled no more often than the refresh loop runs.
+const MIN_FEED_MINUTES: i64 = 15;
+
+/// Set while calendars are fetched, so two refreshes don't interleave.
+static REFRESHING: AtomicBool = AtomicBool::new(false);
+
+/// A calendar whose events are shown in the day view and planned around.
+#[derive(Debug, Clone, Serialize)]
+pub struct Calendar {
+    pub id: String,
+    /// The CalDAV account it was found on. All accounts are `None` for a
+    /// subscribed .ics URL.
+    pub account_id: Option<String>,
+    pub google_account_id: Option<String>,
+    pub microsoft_account_id: Option<String>,
+    pub ews_account_id: Option<String>,
+    pub url: String,
+    pub name: String,
+    pub enabled: bool,
+    /// Whether time blocks are pushed to it as events. Only one Google or
+    /// Outlook calendar takes them.
+    pub push_blocks: bool,
+    pub fetched_at: Option<String>,
+    /// Why the last fetch failed. Events fetched before are kept.
+    pub last_error: Option<String>,
+    pub color: Option<String>,
+    /// How often a subscribed .ics URL is polled; `None` for the default.
+    pub refresh_minutes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarEvent {
+    pub calendar_id: String,
+    pub calendar_name: String,
+    pub calendar_color: Option<String>,
+    pub uid: String,
+    pub title: String,
+    pub location: Option<String>,
+    pub starts_at: String,
+    pub ends_at: String,
+    pub all_day: bool,
+    /// Whether the planner keeps clear of it.
+    pub busy: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CalendarPatch {
+    pub name: Option<String>,
+    pub enabled: Option<bool>,
+    pub push_blocks: Option<bool>,
+    /// `Some("")` clears the color.
+    pub color: Option<String>,
+    /// `Some(0)` goes back to the default.
+    pub refresh_minutes: Option<i64>,
+}
+
+/// What a fetch found. A full fetch replaces the cached events; otherwise
+/// `events` replace those with the same uid and `removed` uids are dropped.
+#[derive(Debug, Default)]
+pub struct Fetched {
+    pub events: Vec<IcsEvent>,
+    pub removed: Vec<String>,
+    pub full: bool,
+    /// Where the next fetch picks up, for calendars that hand one out.
+    pub sync_token: Option<String>,
+    /// The first day the token covers, for tokens tied to a date range.
+    pub sync_from: Option<String>,
+}
+
+impl Calendar {
+    /// Whether it's a subscribed .ics URL rather than on an account.
+    fn is_feed(&self) -> bool {
+        self.account_id.is_none()
+            && self.google_account_id.is_none()
+            && self.microsoft_account_id.is_none()
+            && self.ews_account_id.is_none()
+    }
+
+    /// Whether a feed's poll interval has passed since it was last fetched.
+    fn poll_due(&self, now: DateTime<Utc>) -> bool {
+        let minutes = self.refresh_minutes.unwrap_or(DEFAULT_FEED_MINUTES);
+        self.fetched_at
+            .as_deref()
+            .and_then(|at| parse_utc(at).ok())
+            .is_none_or(|at| now - at >= Duration::minutes(minutes.max(MIN_FEED_MINUTES)))
+    }
+}
+
+const CALENDAR_COLUMNS: &str = "id, account_id, google_account_id, microsoft_account_id, url, name,
+     enabled, push_blocks, fetched_at, last_error, color, refresh_minutes, ews_account_id";
+
+fn row_to_calendar(row: &Row) -> rusqlite::Result<Calendar> {
+    Ok(Calendar {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        google_account_id: row.get(2)?,
+        microsoft_account_id: row.get(3)?,
+        url: row.get(4)?,
+        name: row.get(5)?,
+        enabled: row.get(6)?,
+        push_blocks: row.get(7)?,
+        fetched_at: row.get(8)?,
+        last_error: row.get(9)?,
+        color: row.get(10)?,
+        refresh_minutes: row.get(11)?,
+        ews_account_id: row.get(12)?,
+    })
+}
+
+fn list(conn: &Connection) -> rusqlite::Result<Vec<Calendar>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {CALENDAR_COLUMNS} FROM calendars ORDER BY name COLLATE NOCASE, id"
+    ))?;
+    let rows = stmt.query_map([], row_to_calendar)?;
+    rows.collect()
+}
+
+pub fn list_google(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<Calendar>> {
+    list_for(conn, "google_account_id", account_id)
+}
+
+pub fn list_microsoft(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<Calendar>> {
+    list_for(conn, "microsoft_account_id", account_id)
+}
+
+pub fn list_ews(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<Calendar>> {
+    list_for(conn, "ews_account_id", account_id)
+}
+
+fn list_for(conn: &Connection, column: &str, account_id: &str) -> rusqlite::Result<Vec<Calendar>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {CALENDAR_COLUMNS} FROM calendars
+         WHERE {column} = ?1 ORDER BY name COLLATE NOCASE, id"
+    ))?;
+    let rows = stmt.query_map(params![account_id], row_to_calendar)?;
+    rows.collect()
+}
+
+fn find(conn: &Connection, id: &str) -> rusqlite::Result<Option<Calendar>> {
+    conn.query_row(
+        &format!("SELECT {CALENDAR_COLUMNS} FROM calendars WHERE id = ?1"),
+        params![id],
+        row_to_calendar,
+    )
+    .optional()
+}
+
+/// Save the event calendars found on a CalDAV account, as (url, name).
+/// New ones start out hidden; ones the server no longer lists are dropped.
+pub fn store_discovered(
+    conn: &Connection,
+    account_id: &str,
+    found: &[(&str, &str)],
+) -> rusqlite::Result<()> {
+    store_found(conn, "account_id", account_id, found)
+}
+
+/// Save the calendars in a Google account's list, like `store_discovered`.
+pub fn store_google(
+    conn: &Connection,
+    account_id: &str,
+    found: &[(&str, &str)],
+) -> rusqlite::Result<()> {
+    store_found(conn, "google_account_id", account_id, found)
+}
+
+/// Save the calendars of a Microsoft account, like `store_discovered`.
+pub fn store_microsoft(
+    conn: &Connection,
+    account_id: &str,
+    found: &[(&str, &str)],
+) -> rusqlite::Result<()> {
+    store_found(conn, "microsoft_account_id", account_id, found)
+}
+
+/// Save the calendar folders of an Exchange account, like
+/// `store_discovered`.
+pub fn store_ews(
+    conn: &Connection,
+    account_id: &str,
+    found: &[(&str, &str)],
+) -> rusqlite::Result<()> {
+    store_found(conn, "ews_account_id", account_id, found)
+}
+
+fn store_found(
+    conn: &Connection,
+    column: &str,
+    account_id: &str,
+    found: &[(&str, &str)],
+) -> rusqlite::Result<()> {
+    for (url, name) in found {
+        conn.execute(
+            &format!(
+                "INSERT INTO calendars (id, {column}, url, name, enabled)
+                 VALUES (?1, ?2, ?3, ?4, 0)
+                 ON CONFLICT({column}, url) DO UPDATE SET name = excluded.name"
+            ),
+            params![uuid::Uuid::new_v4().to_string(), account_id, url, name],
+        )?;
+    }
+    let urls: Vec<&str> = found.iter().map(|(url, _)| *url).collect();
+    let urls = serde_json::to_string(&urls).unwrap_or_default();
+    conn.execute(
+        &format!(
+            "DELETE FROM calendars
+             WHERE {column} = ?1 AND url NOT IN (SELECT value FROM json_each(?2))"
+        ),
+        params![account_id, urls],
+    )?;
+    Ok(())
+}
+
+/// Busy time from enabled calendars overlapping `from`..`to`, in start
+/// order.
+pub fn busy_between(
+    conn: &Connection,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> rusqlite::Result<Vec<(DateTime<Utc>, DateTime<Utc>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT starts_at, ends_at FROM calendar_events
+         WHERE busy = 1 AND starts_at < ?2 AND ends_at > ?1
+           AND calendar_id IN (SELECT id FROM calendars WHERE enabled = 1)
+         ORDER BY starts_at",
+    )?;
+    let rows = stmt.query_map(params![format_utc(from), format_utc(to)], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    let mut busy = Vec::new();
+    for row in rows {
+        let (start, end) = row?;
+        if let (Ok(start), Ok(end)) = (parse_utc(&start), parse_utc(&end)) {
+            busy.push((start, end));
+        }
+    }
+    Ok(busy)
+}
+
+fn events_between(
+    conn: &Connection,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> rusqlite::Result<Vec<CalendarEvent>> {
+    // Zero-length events count when they start inside the range.
+    let mut stmt = conn.prepare(
+        "SELECT e.calendar_id, c.name, c.color, e.uid, e.title, e.location, e.starts_at,
+                e.ends_at, e.all_day, e.busy
+         FROM calendar_events e JOIN calendars c ON c.id = e.calendar_id
+         WHERE c.enabled = 1 AND e.starts_at < ?2
+           AND (e.ends_at > ?1 OR e.starts_at >= ?1)
+         ORDER BY e.all_day DESC, e.starts_at, e.title COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map(params![format_utc(from), format_utc(to)], |row| {
+        Ok(CalendarEvent {
+            calendar_id: row.get(0)?,
+            calendar_name: row.get(1)?,
+            calendar_color: row.get(2)?,
+            uid: row.get(3)?,
+            title: row.get(4)?,
+            location: row.get(5)?,
+            starts_at: row.get(6)?,
+            ends_at: row.get(7)?,
+            all_day: row.get(8)?,
+            busy: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// A time block to mirror on a calendar, with the event it was last
+/// pushed as.
+#[derive(Debug, Clone)]
+pub struct BlockRow {
+    pub id: String,
+    pub title: String,
+    pub starts_at: String,
+    pub duration_minutes: i64,
+    /// The later of the block's and its task's `updated_at`.
+    pub updated_at: String,
+    pub event_id: Option<String>,
+    pub synced_at: Option<String>,
+}
+
+impl BlockRow {
+    pub fn span(&self) -> CommandResult<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = parse_utc(&self.starts_at)?;
+        Ok((start, start + Duration::minutes(self.duration_minutes)))
+    }
+
+    /// Whether the event already shows the block as it is.
+    pub fn is_synced(&self) -> bool {
+        self.synced_at
+            .as_deref()
+            .is_some_and(|synced| synced >= self.updated_at.as_str())
+    }
+}
+
+/// Blocks from `since` (YYYY-MM-DD) on to mirror on `calendar`, leaving
+/// out those whose tasks are in the trash. None once pushing is off.
+pub fn blocks_to_push(
+    conn: &Connection,
+    calendar: &Calendar,
+    since: &str,
+) -> rusqlite::Result<Vec<BlockRow>> {
+    if !calendar.push_blocks {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT b.id, t.title, b.starts_at, b.duration_minutes,
+                max(b.updated_at, t.updated_at), e.event_id, e.synced_at
+         FROM time_blocks b JOIN tasks t ON t.id = b.task_id
+         LEFT JOIN calendar_block_events e ON e.block_id = b.id AND e.calendar_id = ?1
+         WHERE t.deleted_at IS NULL AND b.day >= ?2
+         ORDER BY b.starts_at",
+    )?;
+    let rows = stmt.query_map(params![calendar.id, since], |row| {
+        Ok(BlockRow {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            starts_at: row.get(2)?,
+            duration_minutes: row.get(3)?,
+            updated_at: row.get(4)?,
+            event_id: row.get(5)?,
+            synced_at: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Events pushed to `calendar` to take down, as (block id, event id):
+/// those whose block is gone, or all of them once pushing is off.
+pub fn stale_block_events(
+    conn: &Connection,
+    calendar: &Calendar,
+) -> rusqlite::Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT block_id, event_id FROM calendar_block_events
+         WHERE calendar_id = ?1 AND (?2 = 0 OR block_id NOT IN (
+             SELECT b.id FROM time_blocks b JOIN tasks t ON t.id = b.task_id
+             WHERE t.deleted_at IS NULL))",
+    )?;
+    let rows = stmt.query_map(params![calendar.id, calendar.push_blocks], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?;
+    rows.collect()
+}
+
+pub fn save_block_event(
+    conn: &Connection,
+    block: &BlockRow,
+    calendar_id: &str,
+    event_id: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO calendar_block_events (block_id, calendar_id, event_id, synced_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(block_id, calendar_id) DO UPDATE SET
+             event_id = excluded.event_id,
+             synced_at = excluded.synced_at",
+        params![block.id, calendar_id, event_id, block.updated_at],
+    )?;
+    Ok(())
+}
+
+pub fn forget_block_event(
+    conn: &Connection,
+    block_id: &str,
+    calendar_id: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM calendar_block_events WHERE block_id = ?1 AND calendar_id = ?2",
+        params![block_id, calendar_id],
+    )?;
+    Ok(())
+}
+
+/// Ids of the events pushed to a calendar from time blocks, which aren't
+/// shown as meetings.
+pub fn pushed_event_ids(conn: &Connection, calendar_id: &str) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt =
+        conn.prepare("SELECT event_id FROM calendar_block_events WHERE calendar_id = ?1")?;
+    let rows = stmt.query_map(params![calendar_id], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Apply a fetch to a calendar's cached events, or record why the fetch
+/// failed.
+fn store_events(
+    conn: &mut Connection,
+    calendar_id: &str,
+    fetched: &CommandResult<Fetched>,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    match fetched {
+        Ok(fetched) => {
+            if fetched.full {
+                tx.execute(
+                    "DELETE FROM calendar_events WHERE calendar_id = ?1",
+                    params![calendar_id],
+                )?;
+            } else {
+                let uids = fetched
+                    .removed
+                    .iter()
+                    .chain(fetched.events.iter().map(|e| &e.uid));
+                let mut stmt =
+                    tx.prepare("DELETE FROM calendar_events WHERE calendar_id = ?1 AND uid = ?2")?;
+                for uid in uids {
+                    stmt.execute(params![calendar_id, uid])?;
+                }
+            }
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO calendar_events
+                     (calendar_id, uid, title, location, starts_at, ends_at, all_day, busy)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for event in &fetched.events {
+                stmt.execute(params![
+                    calendar_id,
+                    event.uid,
+                    event.title,
+                    event.location,
+                    format_utc(event.starts_at),
+                    format_utc(event.ends_at),
+                    event.all_day,
+                    event.busy,
+                ])?;
+            }
+            drop(stmt);
+            tx.execute(
+                "UPDATE calendars SET fetched_at = ?2, last_error = NULL, sync_token = ?3,
+                     sync_from = ?4
+                 WHERE id = ?1",
+                params![
+                    calendar_id,
+                    now_utc(),
+                    fetched.sync_token,
+                    fetched.sync_from
+                ],
+            )?;
+        }
+        Err(e) => {
+            tx.execute(
+                "UPDATE calendars SET last_error = ?2 WHERE id = ?1",
+                params![calendar_id, e.to_string()],
+            )?;
+        }
+    }
+    tx.commit()
+}
+
+/// The events of one calendar in `window`, over CalDAV or from its .ics URL.
+/// A feed goes through the HTTP cache: when it hasn't changed, the cached
+/// copy is read again for the window.
+async fn fetch(
+    db: &Db,
+    calendar: &Calendar,
+    account: Option<&CaldavAccount>,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Vec<IcsEvent>> {
+    let Some(account) = account else {
+        let cached = db.with_conn(|conn| http::load_cached(conn, &calendar.url))?;
+        let response = http::get_text_cached(&calendar.url, cached).await?;
+        let events = ics::parse_events(&response.body, window)?;
+        db.with_conn(|conn| http::store_cached(conn, &response))?;
+        return Ok(events);
+    };
+    let objects = caldav::fetch_events(account, &calendar.url, window).await?;
+    let mut events = Vec::new();
+    for object in objects {
+        match ics::parse_events(&object, window) {
+            Ok(found) => events.extend(found),
+            Err(e) => tracing::warn!("skipped an event in {}: {e}", calendar.name),
+        }
+    }
+    Ok(events)
+}
+
+/// Fetch every enabled calendar, or only `only`. Subscribed feeds wait for
+/// their poll interval unless `force`d. One calendar failing doesn't stop
+/// the others; its error is kept on it.
+async fn refresh(db: &Db, only: Option<&str>, force: bool) -> CommandResult<Vec<Calendar>> {
+    if REFRESHING.swap(true, Ordering::SeqCst) {
+        return Err("Calendars are already being fetched".into());
+    }
+    let result = fetch_calendars(db, only, force).await;
+    REFRESHING.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn fetch_calendars(db: &Db, only: Option<&str>, force: bool) -> CommandResult<Vec<Calendar>> {
+    let now = Utc::now();
+    let sources = db.with_conn(|conn| {
+        let mut sources = Vec::new();
+        for calendar in list(conn)? {
+            if only.is_some_and(|id| id != calendar.id) {
+                continue;
+            }
+            if !force && calendar.is_feed() && !calendar.poll_due(now) {
+                continue;
+            }
+            // Hidden Google and Outlook calendars are still visited to take
+            // down events pushed to them.
+            let pushed: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM calendar_block_events WHERE calendar_id = ?1)",
+                params![calendar.id],
+                |row| row.get(0),
+            )?;
+            if !(calendar.enabled || calendar.push_blocks || pushed) {
+                continue;
+            }
+            let account = match &calendar.account_id {
+                Some(id) => caldav::find_account(conn, id)?,
+                None => None,
+            };
+            sources.push((calendar, account));
+        }
+        Ok(sources)
+    })?;
+
+    let window = (
+        now - Days::new(FETCH_PAST_DAYS),
+        now + Days::new(FETCH_AHEAD_DAYS),
+    );
+    let mut google_apis: HashMap<String, CommandResult<google::Api>> = HashMap::new();
+    let mut microsoft_apis: HashMap<String, CommandResult<microsoft::Api>> = HashMap::new();
+    let mut ews_apis: HashMap<String, CommandResult<ews::Api>> = HashMap::new();
+    for (calendar, account) in sources {
+        let fetched = if let Some(account_id) = &calendar.google_account_id {
+            if !google_apis.contains_key(account_id) {
+                let api = google::Api::connect(db, account_id, google_calendar::SOURCE).await;
+                google_apis.insert(account_id.clone(), api);
+            }
+            match &google_apis[account_id] {
+                Ok(api) => google_calendar::refresh(db, api, &calendar, window).await,
+                Err(e) => Err(e.clone()),
+            }
+        } else if let Some(account_id) = &calendar.microsoft_account_id {
+            if !microsoft_apis.contains_key(account_id) {
+                let api = microsoft::Api::connect(db, account_id, microsoft_calendar::SOURCE).await;
+                microsoft_apis.insert(account_id.clone(), api);
+            }
+            match &microsoft_apis[account_id] {
+                Ok(api) => microsoft_calendar::refresh(db, api, &calendar, window).await,
+                Err(e) => Err(e.clone()),
+            }
+        } else if let Some(account_id) = &calendar.ews_account_id {
+            if !ews_apis.contains_key(account_id) {
+                let api = match db.with_conn(|conn| ews::find_account(conn, account_id)) {
+                    Ok(Some(account)) => ews::Api::connect(&account),
+                    Ok(None) => Err(CommandError::not_found(format!(
+                        "Exchange account not found: {account_id}"
+                    ))),
+                    Err(e) => Err(e),
+                };
+                ews_apis.insert(account_id.clone(), api);
+            }
+            match &ews_apis[account_id] {
+                Ok(api) => ews_calendar::refresh(api, &calendar, window).await,
+                Err(e) => Err(e.clone()),
+            }
+        } else if calendar.enabled {
+            fetch(db, &calendar, account.as_ref(), window)
+                .await
+                .map(|events| {
+                    Some(Fetched {
+                        events,
+                        full: true,
+                        ..Fetched::default()
+                    })
+                })
+        } else {
+            Ok(None)
+        };
+        let fetched = match fetched {
+            Ok(None) => continue,
+            Ok(Some(fetched)) => Ok(fetched),
+            Err(e) => {
+                tracing::warn!("fetch of {} failed: {e}", calendar.name);
+                Err(e)
+            }
+        };
+        db.with_conn(|conn| store_events(conn, &calendar.id, &fetched))?;
+    }
+    db.with_conn(|conn| list(conn))
+}
+
+/// Fetch calendars now and then, so the day view and planner stay current.
+pub fn spawn_calendar_refresh(app: &AppHandle) {
+    let handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let db = handle.state::<Db>();
+            if refresh(&db, None, false).await.is_ok() {
+                let _ = handle.emit(CALENDAR_EVENTS_EVENT, ());
+            }
+            tokio::time::sleep(REFRESH_EVERY).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_calendars(db: State<'_, Db>) -> CommandResult<Vec<Calendar>> {
+    db.with_conn(|conn| list(conn))
+}
+
+fn validate_refresh_minutes(minutes: i64) -> CommandResult<Option<i64>> {
+    match minutes {
+        0 => Ok(None),
+        m if m < MIN_FEED_MINUTES => Err(CommandError::invalid(format!(
+            "Calendars can be refreshed at most every {MIN_FEED_MINUTES} minutes"
+        ))),
+        m => Ok(Some(m)),
+    }
+}
+
+/// Subscribe to a calendar published as an .ics URL, such as a team,
+/// holiday or class calendar, and fetch it. It's read-only and polled every
+/// `refresh_minutes`.
+#[tauri::command]
+pub async fn subscribe_calendar(
+    app: AppHandle,
+    db: State<'_, Db>,
+    url: String,
+    name: Option<String>,
+    color: Option<String>,
+    refresh_minutes: Option<i64>,
+) -> CommandResult<Calendar> {
+    let url = url.trim().to_string();
+    if !http::is_url(&url) {
+        return Err(CommandError::invalid(
+            "The calendar URL must start with http://, https:// or webcal://",
+        ));
+    }
+    let refresh_minutes = match refresh_minutes {
+        Some(minutes) => validate_refresh_minutes(minutes)?,
+        None => None,
+    };
+    let color = color.filter(|c| !c.trim().is_empty());
+    let name = name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or_else(|| {
+            url::Url::parse(&url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string))
+        })
+        .unwrap_or_else(|| url.clone());
+    let id = uuid::Uuid::new_v4().to_string();
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO calendars (id, url, name, color, refresh_minutes)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, url, name, color, refresh_minutes],
+        )
+    })?;
+    refresh(&db, Some(&id), true).await?;
+    let _ = app.emit(CALENDAR_EVENTS_EVENT, ());
+    Ok(db
+        .with_conn(|conn| find(conn, &id))?
+        .ok_or_else(|| "Failed to save calendar".to_string())?)
+}
+
+/// Rename, recolor, show or hide a calendar, choose it for time blocks, or
+/// set how often a subscribed one is polled. Hidden calendars aren't
+/// fetched or planned around.
+#[tauri::command]
+pub fn update_calendar(
+    db: State<'_, Db>,
+    id: String,
+    patch: CalendarPatch,
+) -> CommandResult<Calendar> {
+    let name = match patch.name.as_deref().map(str::trim) {
+        Some("") => return Err(CommandError::invalid("Calendar name can't be empty")),
+        other => other.map(str::to_string),
+    };
+    let refresh_minutes = match patch.refresh_minutes {
+        Some(minutes) => Some(validate_refresh_minutes(minutes)?),
+        None => None,
+    };
+    db.with_conn(|conn| {
+        let Some(mut calendar) = find(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Calendar not found: {id}"
+            ))));
+        };
+        if let Some(name) = name {
+            calendar.name = name;
+        }
+        if let Some(enabled) = patch.enabled {
+            calendar.enabled = enabled;
+        }
+        if let Some(push_blocks) = patch.push_blocks {
+            if push_blocks
+                && calendar.google_account_id.is_none()
+                && calendar.microsoft_account_id.is_none()
+            {
+                return Ok(Err(CommandError::invalid(
+                    "Time blocks can only be added to a Google or Outlook calendar".to_string(),
+                )));
+            }
+            calendar.push_blocks = push_blocks;
+        }
+        if let Some(color) = &patch.color {
+            calendar.color = (!color.trim().is_empty()).then(|| color.clone());
+        }
+        if let Some(minutes) = refresh_minutes {
+            if !calendar.is_feed() {
+                return Ok(Err(CommandError::invalid(
+                    "Only subscribed calendars have their own refresh interval".to_string(),
+                )));
+            }
+            calendar.refresh_minutes = minutes;
+        }
+        let tx = conn.transaction()?;
+        if calendar.push_blocks {
+            tx.execute(
+                "UPDATE calendars SET push_blocks = 0 WHERE id != ?1",
+                params![calendar.id],
+            )?;
+        }
+        tx.execute(
+            "UPDATE calendars SET name = ?2, enabled = ?3, push_blocks = ?4, color = ?5,
+                 refresh_minutes = ?6
+             WHERE id = ?1",
+            params![
+                calendar.id,
+                calendar.name,
+                calendar.enabled,
+                calendar.push_blocks,
+                calendar.color,
+                calendar.refresh_minutes
+            ],
+        )?;
+        tx.commit()?;
+        Ok(Ok(calendar))
+    })?
+}
+
+/// Unsubscribe from an .ics calendar. Calendars on a CalDAV, Google,
+/// Microsoft or Exchange account go with the account, and can only be
+/// hidden.
+#[tauri::command]
+pub fn remove_calendar(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    db.with_conn(|conn| {
+        let Some(calendar) = find(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Calendar not found: {id}"
+            ))));
+        };
+        if !calendar.is_feed() {
+            return Ok(Err(CommandError::invalid(
+                "This calendar belongs to an account; hide it instead",
+            )));
+        }
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM calendars WHERE id = ?1", params![id])?;
+        tx.execute(
+            "DELETE FROM http_cache
+             WHERE url = ?1 AND NOT EXISTS (SELECT 1 FROM calendars WHERE url = ?1)",
+            params![calendar.url],
+        )?;
+        tx.commit()?;
+        Ok(Ok(()))
+    })?
+}
+
+/// Fetch every enabled calendar now.
+#[tauri::command]
+pub async fn refresh_calendars(app: AppHandle, db: State<'_, Db>) -> CommandResult<Vec<Calendar>> {
+    let calendars = refresh(&db, None, true).await?;
+    let _ = app.emit(CALENDAR_EVENTS_EVENT, ());
+    Ok(calendars)
+}
+
+/// Cached events of enabled calendars between `from` and `to`, dates
+/// (inclusive) or RFC 3339 times. All-day events come first.
+#[tauri::command]
+pub fn list_calendar_events(
+    db: State<'_, Db>,
+    from: String,
+    to: String,
+) -> CommandResult<Vec<CalendarEvent>> {
+    let tz = timezone::parse_zone(&timezone::system_zone())?;
+    let from = reports::parse_bound(&from, &tz, false)?;
+    let to = reports::parse_bound(&to, &tz, true)?;
+    db.with_conn(|conn| events_between(conn, from, to))
+}
@@ -0,0 +1,354 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::crdt::{self, ChangeSet, MergeReport, TASKS_MERGED_EVENT};
+use crate::data_dir;
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::session::write_atomic;
+
+const CONFIG_FILE: &str = "change_log.json";
+/// Each device appends to `<node>.jsonl` and reads the others'.
+const LOG_SUFFIX: &str = ".jsonl";
+/// File sync tools write in several steps; wait for them to settle.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Held while the folder is read and written, so two passes don't
+/// interleave.
+static SYNCING: Mutex<()> = Mutex::new(());
+/// The watcher on the folder, and the way to ask for a pass.
+static WATCH: Mutex<Option<Watch>> = Mutex::new(None);
+
+struct Watch {
+    _watcher: RecommendedWatcher,
+    poke: Sender<()>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChangeLogConfig {
+    pub enabled: bool,
+    /// A folder kept in sync between devices by something else, such as
+    /// Syncthing.
+    pub directory: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChangeLogReport {
+    created: usize,
+    updated: usize,
+    kept_local: usize,
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+pub fn load_config(app: &AppHandle) -> ChangeLogConfig {
+    let Ok(path) = config_path(app) else {
+        return ChangeLogConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            ChangeLogConfig::default()
+        }),
+        Err(_) => ChangeLogConfig::default(),
+    }
+}
+
+/// The folder to use, when the log is on.
+fn folder(config: &ChangeLogConfig) -> Option<PathBuf> {
+    config
+        .directory
+        .as_deref()
+        .filter(|_| config.enabled)
+        .map(PathBuf::from)
+}
+
+/// How far `name` was read, in bytes, and for this device's own log the
+/// clock it was last appended at.
+fn file_state(conn: &Connection, name: &str) -> rusqlite::Result<(u64, i64)> {
+    let state = conn
+        .query_row(
+            "SELECT offset, clock FROM change_log_files WHERE name = ?1",
+            params![name],
+            |row| Ok((row.get::<_, i64>(0)? as u64, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(state.unwrap_or((0, 0)))
+}
+
+fn save_file_state(conn: &Connection, name: &str, offset: u64, clock: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO change_log_files (name, offset, clock) VALUES (?1, ?2, ?3)
+         ON CONFLICT(name) DO UPDATE SET offset = excluded.offset, clock = excluded.clock",
+        params![name, offset as i64, clock],
+    )?;
+    Ok(())
+}
+
+/// Tasks edited on this device after `since`. Values merged in from other
+/// devices keep their stamps, so they aren't written back out.
+fn own_changes(conn: &Connection, since: i64) -> rusqlite::Result<ChangeSet> {
+    let mut changes = crdt::changes_since(conn, since)?;
+    if since > 0 {
+        let node = changes.node.clone();
+        let ours = |clock: i64, by: &str| clock > since && by == node;
+        changes.tasks.retain(|task| {
+            task.fields
+                .values()
+                .any(|f| ours(f.stamp.clock, &f.stamp.node))
+                || task.tags.iter().any(|t| ours(t.stamp.clock, &t.stamp.node))
+        });
+    }
+    Ok(changes)
+}
+
+/// Complete lines of `text`, leaving out one still being written.
+fn complete_lines(text: &str) -> (&str, usize) {
+    match text.rfind('\n') {
+        Some(end) => (&text[..=end], end + 1),
+        None => ("", 0),
+    }
+}
+
+/// What was added to another device's log since it was last read. Starts
+/// over when the file got shorter, as when it was replaced.
+fn read_new(path: &Path, offset: u64) -> CommandResult<(String, u64)> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let offset = if len < offset { 0 } else { offset };
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let (lines, read) = complete_lines(&text);
+    Ok((lines.to_string(), offset + read as u64))
+}
+
+/// Merge what other devices appended to their logs.
+fn read_logs(db: &Db, dir: &Path, own: &str, report: &mut ChangeLogReport) -> CommandResult<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Can't read {}", dir.display()))?;
+    for entry in entries.flatten() {
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if name == own || !name.ends_with(LOG_SUFFIX) {
+            continue;
+        }
+        let (offset, mut clock) = db.with_conn(|conn| file_state(conn, &name))?;
+        let (lines, read_to) = match read_new(&entry.path(), offset) {
+            Ok(new) => new,
+            Err(e) => {
+                tracing::warn!("can't read {name}: {e}");
+                continue;
+            }
+        };
+        if read_to == offset {
+            continue;
+        }
+        for line in lines.lines().filter(|l| !l.trim().is_empty()) {
+            let changes: ChangeSet = match serde_json::from_str(line) {
+                Ok(changes) => changes,
+                Err(e) => {
+                    tracing::warn!("skipping unreadable line in {name}: {e}");
+                    continue;
+                }
+            };
+            let merged = db.with_conn(|conn| {
+                history::with_source(conn, ChangeSource::Sync, |conn| crdt::merge(conn, &changes))?
+            })?;
+            for rejected in &merged.rejected {
+                tracing::warn!("{name}: {rejected}");
+            }
+            report.created += merged.created;
+            report.updated += merged.updated;
+            report.kept_local += merged.kept_local;
+            clock = clock.max(changes.clock);
+        }
+        db.with_conn(|conn| save_file_state(conn, &name, read_to, clock))?;
+    }
+    Ok(())
+}
+
+/// Append what changed here since the last line. The first line of a new
+/// log holds every task, so a device that joins later still sees them all.
+fn append_own(db: &Db, dir: &Path, own: &str) -> CommandResult<()> {
+    let path = dir.join(own);
+    let (_, written) = db.with_conn(|conn| file_state(conn, own))?;
+    let fresh = !path.exists();
+    let since = if fresh { 0 } else { written };
+    let changes = db.with_conn(|conn| own_changes(conn, since))?;
+    if !fresh && (changes.tasks.is_empty() || changes.clock <= written) {
+        return Ok(());
+    }
+    let mut line = serde_json::to_string(&changes).map_err(|e| e.to_string())?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Can't open {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Can't write {}", path.display()))?;
+    db.with_conn(|conn| save_file_state(conn, own, 0, changes.clock))?;
+    Ok(())
+}
+
+fn sync_folder(db: &Db, dir: &Path) -> CommandResult<ChangeLogReport> {
+    let _syncing = SYNCING.lock().unwrap_or_else(|e| e.into_inner());
+    if !dir.is_dir() {
+        return Err(CommandError::not_found(format!(
+            "Folder not found: {}",
+            dir.display()
+        )));
+    }
+    let (node, _) = db.with_conn(|conn| crdt::local_clock(conn))?;
+    let own = format!("{node}{LOG_SUFFIX}");
+    let mut report = ChangeLogReport::default();
+    read_logs(db, dir, &own, &mut report)?;
+    append_own(db, dir, &own)?;
+    Ok(report)
+}
+
+/// Read the other devices' logs and append to this one's, telling the UI
+/// about merged tasks.
+fn sync_now(app: &AppHandle, dir: &Path) {
+    let db = app.state::<Db>().unjournaled();
+    if db.is_locked() {
+        return;
+    }
+    match sync_folder(&db, dir) {
+        Ok(report) if report.created + report.updated > 0 => {
+            let _ = app.emit(
+                TASKS_MERGED_EVENT,
+                MergeReport {
+                    created: report.created,
+                    updated: report.updated,
+                    kept_local: report.kept_local,
+                    rejected: Vec::new(),
+                },
+            );
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("{e}"),
+    }
+}
+
+/// Ask for a pass because tasks changed. Merging another device's log
+/// changes tasks too, but appends nothing, so that ends there.
+pub fn tasks_changed() {
+    let watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(watch) = watch.as_ref() {
+        let _ = watch.poke.send(());
+    }
+}
+
+/// Merge the folder's logs, then again whenever one of them changes or
+/// tasks change here. Replaces any earlier watch, or just stops it when the
+/// log is off.
+pub fn watch(app: &AppHandle) {
+    let dir = folder(&load_config(app)).filter(|_| !app.state::<Db>().is_locked());
+    let mut slot = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    *slot = None;
+    let Some(dir) = dir else {
+        return;
+    };
+
+    let (tx, rx) = mpsc::channel::<()>();
+    let events = tx.clone();
+    let mut watcher = match RecommendedWatcher::new(
+        move |res: Result<notify::Event, notify::Error>| {
+            let Ok(event) = res else {
+                return;
+            };
+            let logs = event.paths.iter().any(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.ends_with(LOG_SUFFIX))
+            });
+            if logs && !event.kind.is_access() {
+                let _ = events.send(());
+            }
+        },
+        Config::default(),
+    ) {
+        Ok(w) => w,
+        Err(e) => {
+            tracing::warn!("failed to start watcher: {e}");
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+        tracing::warn!("failed to watch {}: {e}", dir.display());
+    }
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        while rx.recv().is_ok() {
+            let deadline = Instant::now() + DEBOUNCE;
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                if rx.recv_timeout(remaining).is_err() {
+                    break;
+                }
+            }
+            sync_now(&handle, &dir);
+        }
+    });
+    let _ = tx.send(());
+    *slot = Some(Watch {
+        _watcher: watcher,
+        poke: tx,
+    });
+}
+
+#[tauri::command]
+pub fn get_change_log_config(app: AppHandle) -> ChangeLogConfig {
+    load_config(&app)
+}
+
+/// Save the config and start or stop the watch. Choosing another folder
+/// starts its logs over from the beginning.
+#[tauri::command]
+pub fn set_change_log_config(
+    app: AppHandle,
+    config: ChangeLogConfig,
+) -> CommandResult<ChangeLogConfig> {
+    let config = ChangeLogConfig {
+        directory: config
+            .directory
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty()),
+        ..config
+    };
+    if config.enabled {
+        let dir = config
+            .directory
+            .as_deref()
+            .ok_or_else(|| CommandError::invalid("Choose a folder"))?;
+        if !Path::new(dir).is_dir() {
+            return Err(CommandError::not_found(format!("Folder not found: {dir}")));
+        }
+    }
+    if config.directory != load_config(&app).directory {
+        app.state::<Db>()
+            .with_conn(|conn| conn.execute("DELETE FROM change_log_files", []))?;
+    }
+    let path = config_path(&app)?;
+    let body = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+    watch(&app);
+    Ok(config)
+}
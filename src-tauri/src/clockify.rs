@@ -0,0 +1,671 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::SecondsFormat;
+use reqwest::{Client, Method, StatusCode};
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::State;
+
+use crate::credentials;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::sync_provider::SyncFuture;
+use crate::task_store::Task;
+use crate::time_entries::TimeEntry;
+use crate::time_push::{self, EntryStatus, PushReport, Pushing, Service};
+
+const API_URL: &str = "https://api.clockify.me/api/v1";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const PAGE_SIZE: usize = 200;
+/// Pushed entries, by account.
+const ENTRIES_TABLE: &str = "clockify_entries";
+
+const SERVICE: Service = Service {
+    id: "clockify",
+    name: "Clockify",
+    refused: "Clockify refused the API key; connect the account again",
+    refused_statuses: &[StatusCode::UNAUTHORIZED],
+    empty_statuses: &[StatusCode::NOT_FOUND],
+    message: |text| {
+        serde_json::from_str::<RemoteError>(text)
+            .map(|e| e.message)
+            .unwrap_or_else(|_| text.to_string())
+    },
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockifyAccount {
+    pub id: String,
+    pub name: String,
+    /// The workspace entries are pushed to.
+    pub workspace_id: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewClockifyAccount {
+    /// Defaults to the Clockify user's name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The API key from Clockify's profile settings.
+    pub api_key: String,
+    /// Defaults to the user's active workspace.
+    #[serde(default)]
+    pub workspace_id: Option<String>,
+}
+
+pub type ClockifyPushReport = PushReport;
+
+/// Where one time entry stands with a Clockify account.
+pub type ClockifyEntryStatus = EntryStatus<String>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockifyWorkspace {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockifyProject {
+    pub id: String,
+    pub name: String,
+    pub client_name: Option<String>,
+    pub archived: bool,
+}
+
+/// A local project sent to a chosen Clockify project instead of the one of
+/// the same name.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockifyMapping {
+    pub project: String,
+    pub remote_id: String,
+}
+
+/// What's needed to choose a workspace and map projects to Clockify's.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockifyProjects {
+    pub workspace_id: String,
+    pub workspaces: Vec<ClockifyWorkspace>,
+    /// The projects of the account's workspace, archived ones too.
+    pub projects: Vec<ClockifyProject>,
+    pub mappings: Vec<ClockifyMapping>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteUser {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    active_workspace: Option<String>,
+    #[serde(default)]
+    default_workspace: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteWorkspace {
+    id: String,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteProject {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    client_name: Option<String>,
+    #[serde(default)]
+    archived: bool,
+}
+
+/// A tag, or a project by the fields needed to find it by name.
+#[derive(Debug, Deserialize)]
+struct RemoteNamed {
+    id: String,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteEntry {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteError {
+    message: String,
+}
+
+/// What a stopped entry sends to Clockify, with its project and tags by
+/// name. Its hash is kept with the Clockify id to tell when it needs
+/// pushing again.
+#[derive(Debug, Clone, Serialize)]
+struct Export {
+    description: String,
+    start: String,
+    end: String,
+    project: Option<String>,
+    /// The Clockify project `project` is mapped to.
+    mapped_project: Option<String>,
+    tags: Vec<String>,
+    billable: bool,
+}
+
+impl Export {
+    fn new(
+        entry: &TimeEntry,
+        ended_at: &str,
+        task: Option<&Task>,
+        mappings: &HashMap<String, String>,
+    ) -> CommandResult<Self> {
+        let project = time_push::project(task);
+        let mapped_project = project
+            .as_ref()
+            .and_then(|p| mappings.get(&p.to_lowercase()).cloned());
+        Ok(Self {
+            description: time_push::description(entry, task),
+            start: parse_utc(&entry.started_at)?.to_rfc3339_opts(SecondsFormat::Secs, true),
+            end: parse_utc(ended_at)?.to_rfc3339_opts(SecondsFormat::Secs, true),
+            project,
+            mapped_project,
+            tags: task.map(|t| t.tags.clone()).unwrap_or_default(),
+            billable: entry.billable,
+        })
+    }
+
+    /// The request body, with the project and tags resolved to Clockify ids.
+    fn body(&self, project_id: Option<&str>, tag_ids: &[String]) -> serde_json::Value {
+        json!({
+            "start": self.start,
+            "end": self.end,
+            "description": self.description,
+            "projectId": project_id,
+            "tagIds": tag_ids,
+            "billable": self.billable,
+        })
+    }
+}
+
+type Outgoing = time_push::Outgoing<Export, String>;
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<ClockifyAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, workspace_id, created_at, updated_at
+         FROM clockify_accounts ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ClockifyAccount {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            workspace_id: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> CommandResult<ClockifyAccount> {
+    list_accounts(conn)?
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| CommandError::not_found(format!("Clockify account not found: {id}")))
+}
+
+fn list_mappings(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<ClockifyMapping>> {
+    let mut stmt = conn.prepare(
+        "SELECT project, remote_id FROM clockify_projects
+         WHERE account_id = ?1 ORDER BY project",
+    )?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok(ClockifyMapping {
+            project: row.get(0)?,
+            remote_id: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("clockify:{account_id}")
+}
+
+/// A connection to the Clockify API with one account's key.
+struct Api {
+    client: Client,
+    key: String,
+}
+
+impl Api {
+    fn new(key: String) -> CommandResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self { client, key })
+    }
+
+    fn connect(account_id: &str) -> CommandResult<Self> {
+        Self::new(credentials::require(&keyring_name(account_id), "API key")?)
+    }
+
+    /// Send a request to `path` under the API. `None` when Clockify has no
+    /// such thing.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        let url = format!("{API_URL}{path}");
+        let request = || {
+            self.client
+                .request(method.clone(), &url)
+                .header("X-Api-Key", &self.key)
+        };
+        time_push::send(&SERVICE, request, body).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> CommandResult<T> {
+        self.send(Method::GET, path, None)
+            .await?
+            .ok_or_else(|| CommandError::not_found(format!("Clockify: {path} not found")))
+    }
+
+    /// Every page of a workspace collection such as `projects` or `tags`.
+    async fn all<T: DeserializeOwned>(
+        &self,
+        workspace_id: &str,
+        collection: &str,
+    ) -> CommandResult<Vec<T>> {
+        let mut items = Vec::new();
+        for page in 1.. {
+            let found: Vec<T> = self
+                .get(&format!(
+                    "/workspaces/{workspace_id}/{collection}?page={page}&page-size={PAGE_SIZE}"
+                ))
+                .await?;
+            let done = found.len() < PAGE_SIZE;
+            items.extend(found);
+            if done {
+                break;
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// The time entries between `from` and `to` (UTC), each with what it sends
+/// to Clockify and what `account_id` was last sent for it.
+fn outgoing(
+    conn: &Connection,
+    account_id: &str,
+    from: &str,
+    to: &str,
+) -> CommandResult<Vec<Outgoing>> {
+    let mappings: HashMap<String, String> = list_mappings(conn, account_id)?
+        .into_iter()
+        .map(|m| (m.project.to_lowercase(), m.remote_id))
+        .collect();
+    time_push::outgoing(
+        conn,
+        ENTRIES_TABLE,
+        account_id,
+        from,
+        to,
+        |entry, ended_at, task, _| Export::new(entry, ended_at, task, &mappings),
+    )
+}
+
+/// Pushes entries to one workspace, creating projects and tags as they
+/// come up.
+struct Pusher<'a> {
+    api: &'a Api,
+    workspace_id: &'a str,
+    /// Fetched when the first entry that needs them is pushed, by
+    /// lowercased name.
+    projects: Option<HashMap<String, String>>,
+    tags: Option<HashMap<String, String>>,
+}
+
+impl Pusher<'_> {
+    /// The id of the Clockify project or tag named `name` in `collection`,
+    /// ignoring case; made when there's none.
+    async fn named(&mut self, collection: &str, name: &str) -> CommandResult<String> {
+        let cache = if collection == "projects" {
+            &mut self.projects
+        } else {
+            &mut self.tags
+        };
+        let known = match cache {
+            Some(known) => known,
+            None => {
+                let found: Vec<RemoteNamed> = self.api.all(self.workspace_id, collection).await?;
+                let mut known = HashMap::new();
+                for item in found {
+                    known.entry(item.name.to_lowercase()).or_insert(item.id);
+                }
+                cache.insert(known)
+            }
+        };
+        let key = name.to_lowercase();
+        if let Some(id) = known.get(&key) {
+            return Ok(id.clone());
+        }
+        let created: RemoteNamed = self
+            .api
+            .send(
+                Method::POST,
+                &format!("/workspaces/{}/{collection}", self.workspace_id),
+                Some(&json!({ "name": name })),
+            )
+            .await?
+            .ok_or_else(|| CommandError::not_found("Clockify: the workspace is gone"))?;
+        known.insert(key, created.id.clone());
+        Ok(created.id)
+    }
+
+    async fn push_entry(
+        &mut self,
+        export: &Export,
+        remote_id: Option<&str>,
+    ) -> CommandResult<(String, bool)> {
+        let project_id = match (&export.mapped_project, &export.project) {
+            (Some(mapped), _) => Some(mapped.clone()),
+            (None, Some(name)) => Some(self.named("projects", name).await?),
+            (None, None) => None,
+        };
+        let mut tag_ids = Vec::new();
+        for tag in &export.tags {
+            tag_ids.push(self.named("tags", tag).await?);
+        }
+        let body = export.body(project_id.as_deref(), &tag_ids);
+        let base = format!("/workspaces/{}/time-entries", self.workspace_id);
+        if let Some(remote_id) = remote_id {
+            let updated: Option<RemoteEntry> = self
+                .api
+                .send(Method::PUT, &format!("{base}/{remote_id}"), Some(&body))
+                .await?;
+            if let Some(updated) = updated {
+                return Ok((updated.id, false));
+            }
+        }
+        let created: RemoteEntry = self
+            .api
+            .send(Method::POST, &base, Some(&body))
+            .await?
+            .ok_or_else(|| CommandError::not_found("Clockify: the workspace is gone"))?;
+        Ok((created.id, true))
+    }
+}
+
+impl time_push::Target<Export, String> for Pusher<'_> {
+    fn push<'a>(
+        &'a mut self,
+        export: &'a Export,
+        remote_id: Option<String>,
+    ) -> SyncFuture<'a, (String, bool)> {
+        Box::pin(async move { self.push_entry(export, remote_id.as_deref()).await })
+    }
+}
+
+async fn push_account(
+    db: &Db,
+    account_id: &str,
+    from: &str,
+    to: &str,
+) -> CommandResult<ClockifyPushReport> {
+    let account = db.with_conn(|conn| Ok(find_account(conn, account_id)))??;
+    let api = Api::connect(&account.id)?;
+    let outgoing = db.with_conn(|conn| Ok(outgoing(conn, &account.id, from, to)))??;
+    let mut pusher = Pusher {
+        api: &api,
+        workspace_id: &account.workspace_id,
+        projects: None,
+        tags: None,
+    };
+    time_push::push_entries(
+        db,
+        ENTRIES_TABLE,
+        &account.id,
+        outgoing,
+        &mut pusher,
+        |export| &export.description,
+    )
+    .await
+}
+
+/// Check an API key and save the account, pushing to `workspace_id` or the
+/// user's active workspace.
+#[tauri::command]
+pub async fn connect_clockify_account(
+    db: State<'_, Db>,
+    input: NewClockifyAccount,
+) -> CommandResult<ClockifyAccount> {
+    let key = input.api_key.trim().to_string();
+    if key.is_empty() {
+        return Err(CommandError::invalid("Enter the Clockify API key"));
+    }
+    let api = Api::new(key.clone())?;
+    let user: RemoteUser = api.get("/user").await?;
+    let workspace_id = match input.workspace_id {
+        Some(id) => {
+            let workspaces: Vec<RemoteWorkspace> = api.get("/workspaces").await?;
+            if !workspaces.iter().any(|w| w.id == id) {
+                return Err(CommandError::not_found(format!(
+                    "No Clockify workspace {id} for this account"
+                )));
+            }
+            id
+        }
+        None => user
+            .active_workspace
+            .or(user.default_workspace)
+            .ok_or_else(|| CommandError::invalid("This Clockify account has no workspace"))?,
+    };
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .or(user.name)
+        .or(user.email)
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Clockify".to_string());
+    credentials::save(&keyring_name(&id), Some(&key))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO clockify_accounts (id, name, workspace_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![id, name, workspace_id, now],
+        )?;
+        list_accounts(conn)
+    });
+    let accounts = match stored {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            return Err(e);
+        }
+    };
+    Ok(accounts
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| "Failed to save account".to_string())?)
+}
+
+#[tauri::command]
+pub fn list_clockify_accounts(db: State<'_, Db>) -> CommandResult<Vec<ClockifyAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Push to another workspace. Project mappings and the record of what was
+/// pushed belong to the old one and are dropped, so every entry is pushed
+/// again.
+#[tauri::command]
+pub async fn update_clockify_account(
+    db: State<'_, Db>,
+    id: String,
+    workspace_id: String,
+) -> CommandResult<ClockifyAccount> {
+    let account = db.with_conn(|conn| Ok(find_account(conn, &id)))??;
+    if account.workspace_id == workspace_id {
+        return Ok(account);
+    }
+    let api = Api::connect(&id)?;
+    let workspaces: Vec<RemoteWorkspace> = api.get("/workspaces").await?;
+    if !workspaces.iter().any(|w| w.id == workspace_id) {
+        return Err(CommandError::not_found(format!(
+            "No Clockify workspace {workspace_id} for this account"
+        )));
+    }
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE clockify_accounts SET workspace_id = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, workspace_id, now_utc()],
+        )?;
+        conn.execute(
+            "DELETE FROM clockify_projects WHERE account_id = ?1",
+            params![id],
+        )?;
+        conn.execute(
+            "DELETE FROM clockify_entries WHERE account_id = ?1",
+            params![id],
+        )?;
+        Ok(find_account(conn, &id))
+    })?
+}
+
+/// Forget an account and which entries went to it. Entries already in
+/// Clockify stay there.
+#[tauri::command]
+pub fn remove_clockify_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| {
+        conn.execute("DELETE FROM clockify_accounts WHERE id = ?1", params![id])
+    })?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Clockify account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+/// The account's workspaces, the projects of the one it pushes to, and
+/// which local projects are mapped to which, for choosing where time goes.
+#[tauri::command]
+pub async fn list_clockify_projects(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<ClockifyProjects> {
+    let account = db.with_conn(|conn| Ok(find_account(conn, &account_id)))??;
+    let api = Api::connect(&account.id)?;
+    let workspaces: Vec<RemoteWorkspace> = api.get("/workspaces").await?;
+    let found: Vec<RemoteProject> = api.all(&account.workspace_id, "projects").await?;
+    let mut projects: Vec<ClockifyProject> = found
+        .into_iter()
+        .map(|p| ClockifyProject {
+            id: p.id,
+            name: p.name,
+            client_name: p.client_name.filter(|c| !c.is_empty()),
+            archived: p.archived,
+        })
+        .collect();
+    projects.sort_by_key(|p| (p.archived, p.name.to_lowercase()));
+    let mappings = db.with_conn(|conn| list_mappings(conn, &account.id))?;
+    Ok(ClockifyProjects {
+        workspace_id: account.workspace_id,
+        workspaces: workspaces
+            .into_iter()
+            .map(|w| ClockifyWorkspace {
+                id: w.id,
+                name: w.name,
+            })
+            .collect(),
+        projects,
+        mappings,
+    })
+}
+
+/// Send time on tasks in `project` to the Clockify project `remote_id`, or
+/// back to the one of the same name when `None`. Entries already pushed
+/// move with the next push.
+#[tauri::command]
+pub fn map_clockify_project(
+    db: State<'_, Db>,
+    account_id: String,
+    project: String,
+    remote_id: Option<String>,
+) -> CommandResult<Vec<ClockifyMapping>> {
+    let project = project.trim().to_string();
+    if project.is_empty() {
+        return Err(CommandError::invalid("Choose a project to map"));
+    }
+    db.with_conn(|conn| {
+        if let Err(e) = find_account(conn, &account_id) {
+            return Ok(Err(e));
+        }
+        match remote_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+        {
+            Some(remote_id) => conn.execute(
+                "INSERT INTO clockify_projects (account_id, project, remote_id)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT(account_id, project) DO UPDATE SET
+                     project = excluded.project,
+                     remote_id = excluded.remote_id",
+                params![account_id, project, remote_id],
+            )?,
+            None => conn.execute(
+                "DELETE FROM clockify_projects WHERE account_id = ?1 AND project = ?2",
+                params![account_id, project],
+            )?,
+        };
+        list_mappings(conn, &account_id).map(Ok)
+    })?
+}
+
+/// Push the stopped time entries between `from` and `to` to Clockify: the
+/// task's title (and the entry's note) as the description, its project as
+/// the mapped Clockify project or the one of the same name, made when
+/// missing, and its tags. Entries pushed before are updated when they or
+/// their task changed, and skipped otherwise. One entry failing doesn't
+/// stop the others; its error is kept as its status.
+#[tauri::command]
+pub async fn push_to_clockify(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+) -> CommandResult<ClockifyPushReport> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    let _pushing = Pushing::start("Clockify")?;
+    push_account(&db, &account_id, &from, &to).await
+}
+
+/// Where each time entry between `from` and `to` stands with `account_id`.
+#[tauri::command]
+pub fn list_clockify_entry_status(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+) -> CommandResult<Vec<ClockifyEntryStatus>> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    db.with_conn(|conn| {
+        Ok(find_account(conn, &account_id)
+            .and_then(|_| outgoing(conn, &account_id, &from, &to))
+            .map(|items| items.iter().map(Outgoing::entry_status).collect()))
+    })?
+}
@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::crdt;
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::sync_provider;
+use crate::tags;
+use crate::task_store::{self, Task};
+
+/// What a sync does with an item changed both here and remotely since it
+/// last synced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Field by field, whichever side changed it last.
+    #[default]
+    Newest,
+    /// The remote version replaces the local edits.
+    Remote,
+    /// The local version is kept and uploaded over the remote one.
+    Local,
+    /// The local version is kept, and not uploaded, until the user picks
+    /// one in `resolve_conflict`.
+    Manual,
+}
+
+impl ConflictPolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConflictPolicy::Newest => "newest",
+            ConflictPolicy::Remote => "remote",
+            ConflictPolicy::Local => "local",
+            ConflictPolicy::Manual => "manual",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "remote" => ConflictPolicy::Remote,
+            "local" => ConflictPolicy::Local,
+            "manual" => ConflictPolicy::Manual,
+            _ => ConflictPolicy::Newest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictChoice {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderPolicy {
+    pub provider: String,
+    pub policy: ConflictPolicy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncConflict {
+    pub id: String,
+    pub provider: String,
+    /// The task as it is here.
+    pub local: Task,
+    /// The task as the remote version would leave it.
+    pub remote: Task,
+    pub created_at: String,
+    /// When the remote version last came in.
+    pub updated_at: String,
+}
+
+pub fn policy(conn: &Connection, provider: &str) -> rusqlite::Result<ConflictPolicy> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT policy FROM sync_policies WHERE provider = ?1",
+            params![provider],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(stored
+        .as_deref()
+        .map_or_else(ConflictPolicy::default, ConflictPolicy::parse))
+}
+
+/// Which of a task's fields an incoming remote version overwrites: every
+/// one under `Remote`, otherwise those not edited here after the remote
+/// changed at `modified` (ms). `"tags"` stands for the whole tag set.
+pub fn remote_fields(
+    conn: &Connection,
+    task_id: &str,
+    modified: Option<i64>,
+    policy: ConflictPolicy,
+) -> rusqlite::Result<impl Fn(&str) -> bool> {
+    let (stamps, tag_clock) = if policy == ConflictPolicy::Remote {
+        (Default::default(), None)
+    } else {
+        (
+            crdt::field_stamps(conn, task_id)?,
+            crdt::newest_tag_clock(conn, task_id)?,
+        )
+    };
+    Ok(move |field: &str| {
+        let clock = match field {
+            "tags" => tag_clock,
+            _ => stamps.get(field).map(|stamp| stamp.clock),
+        };
+        match (modified, clock) {
+            (Some(modified), Some(clock)) => clock <= modified,
+            _ => true,
+        }
+    })
+}
+
+/// Whether `local`, changed both here and remotely since it last synced,
+/// keeps its local version rather than taking the remote one. Always under
+/// `Local`; under `Manual` when `remote`, which writes the remote version
+/// over the task, would change it, and the conflict is queued.
+pub fn keep_local(
+    conn: &Connection,
+    provider: &str,
+    policy: ConflictPolicy,
+    local: &Task,
+    remote: impl FnOnce(&Connection) -> rusqlite::Result<Task>,
+) -> rusqlite::Result<bool> {
+    match policy {
+        ConflictPolicy::Local => return Ok(true),
+        ConflictPolicy::Newest | ConflictPolicy::Remote => return Ok(false),
+        ConflictPolicy::Manual => {}
+    }
+    // Write the remote version only to see it.
+    conn.execute_batch("SAVEPOINT conflict_preview")?;
+    let written = remote(conn);
+    conn.execute_batch("ROLLBACK TO conflict_preview; RELEASE conflict_preview")?;
+    let remote = written?;
+    if same(local, &remote) {
+        conn.execute(
+            "DELETE FROM sync_conflicts WHERE provider = ?1 AND task_id = ?2",
+            params![provider, local.id],
+        )?;
+        return Ok(false);
+    }
+    conn.execute(
+        "INSERT INTO sync_conflicts (id, provider, task_id, remote, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+         ON CONFLICT(provider, task_id) DO UPDATE SET
+             remote = excluded.remote,
+             updated_at = excluded.updated_at",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            provider,
+            local.id,
+            serde_json::to_string(&remote).unwrap_or_default(),
+            now_utc(),
+        ],
+    )?;
+    Ok(true)
+}
+
+/// Ids of tasks with a conflict waiting on the user, whose local changes
+/// aren't uploaded meanwhile.
+pub fn held(conn: &Connection, provider: &str) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT task_id FROM sync_conflicts WHERE provider = ?1")?;
+    let rows = stmt.query_map(params![provider], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Whether two versions of a task differ in nothing a sync carries.
+fn same(a: &Task, b: &Task) -> bool {
+    a.title == b.title
+        && a.description == b.description
+        && a.status == b.status
+        && a.project == b.project
+        && a.priority == b.priority
+        && a.due == b.due
+        && a.scheduled == b.scheduled
+        && a.recurrence == b.recurrence
+        && a.estimate_minutes == b.estimate_minutes
+        && a.tags == b.tags
+}
+
+pub fn list(conn: &Connection, provider: Option<&str>) -> rusqlite::Result<Vec<SyncConflict>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.provider, c.task_id, c.remote, c.created_at, c.updated_at
+         FROM sync_conflicts c JOIN tasks t ON t.id = c.task_id
+         WHERE t.deleted_at IS NULL AND (?1 IS NULL OR c.provider = ?1)
+         ORDER BY c.updated_at DESC",
+    )?;
+    let rows = stmt
+        .query_map(params![provider], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut conflicts = Vec::new();
+    for (id, provider, task_id, remote, created_at, updated_at) in rows {
+        let (Some(local), Ok(remote)) = (
+            task_store::find_task(conn, &task_id)?,
+            serde_json::from_str::<Task>(&remote),
+        ) else {
+            continue;
+        };
+        conflicts.push(SyncConflict {
+            id,
+            provider,
+            local,
+            remote,
+            created_at,
+            updated_at,
+        });
+    }
+    Ok(conflicts)
+}
+
+/// Settle a conflict with `choice`. Either way the task is uploaded on the
+/// provider's next sync.
+pub fn resolve(conn: &mut Connection, id: &str, choice: ConflictChoice) -> CommandResult<Task> {
+    let conflict = list(conn, None)?
+        .into_iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| CommandError::not_found(format!("Conflict not found: {id}")))?;
+    let tx = conn.transaction()?;
+    let task = match choice {
+        ConflictChoice::Local => conflict.local,
+        ConflictChoice::Remote => {
+            let mut task = Task {
+                id: conflict.local.id.clone(),
+                updated_at: now_utc(),
+                ..conflict.remote
+            };
+            task_store::write_task(&tx, &task)?;
+            if task.tags != conflict.local.tags {
+                tags::set_task_tags(&tx, &task.id, &task.tags)?;
+            }
+            task.parent_id = conflict.local.parent_id;
+            task.is_blocked = conflict.local.is_blocked;
+            task
+        }
+    };
+    tx.execute("DELETE FROM sync_conflicts WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(task)
+}
+
+#[tauri::command]
+pub fn get_conflict_policies(db: State<'_, Db>) -> CommandResult<Vec<ProviderPolicy>> {
+    db.with_conn(|conn| {
+        sync_provider::two_way()
+            .map(|provider| {
+                Ok(ProviderPolicy {
+                    provider: provider.id().to_string(),
+                    policy: policy(conn, provider.id())?,
+                })
+            })
+            .collect()
+    })
+}
+
+/// Takes effect on the provider's next sync. Conflicts already queued stay
+/// until resolved.
+#[tauri::command]
+pub fn set_conflict_policy(
+    db: State<'_, Db>,
+    provider: String,
+    policy: ConflictPolicy,
+) -> CommandResult<ProviderPolicy> {
+    if !sync_provider::two_way().any(|p| p.id() == provider) {
+        return Err(CommandError::invalid(format!(
+            "Not a two-way sync provider: {provider}"
+        )));
+    }
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO sync_policies (provider, policy) VALUES (?1, ?2)
+             ON CONFLICT(provider) DO UPDATE SET policy = excluded.policy",
+            params![provider, policy.as_str()],
+        )
+    })?;
+    Ok(ProviderPolicy { provider, policy })
+}
+
+/// Conflicts waiting on the user, newest first, with both versions of each
+/// task.
+#[tauri::command]
+pub fn list_conflicts(
+    db: State<'_, Db>,
+    provider: Option<String>,
+) -> CommandResult<Vec<SyncConflict>> {
+    db.with_conn(|conn| list(conn, provider.as_deref()))
+}
+
+#[tauri::command]
+pub fn resolve_conflict(
+    db: State<'_, Db>,
+    id: String,
+    choice: ConflictChoice,
+) -> CommandResult<Task> {
+    db.with_conn(|conn| Ok(resolve(conn, &id, choice)))?
+}
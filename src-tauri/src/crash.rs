@@ -0,0 +1,276 @@
+use std::backtrace::Backtrace;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::db::now_utc;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::logging;
+use crate::session::write_atomic;
+
+/// Crash reports live beside the logs, one JSON file each.
+const CRASH_DIR: &str = "crashes";
+/// Reports kept; older ones are deleted as new ones are written.
+const MAX_REPORTS: usize = 20;
+/// Log lines appended to an exported report.
+const EXPORT_LOG_LINES: usize = 1000;
+
+pub const KIND_PANIC: &str = "panic";
+#[cfg(target_os = "linux")]
+pub const KIND_WEBVIEW: &str = "webview";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// The file name without `.json`, e.g. `crash-20260101T120000123Z`.
+    pub id: String,
+    /// `panic` for the backend, `webview` when the web process died.
+    pub kind: String,
+    pub created_at: String,
+    pub app_version: String,
+    /// OS, architecture and, where known, the distribution or release.
+    pub os: String,
+    pub message: String,
+    /// Where the backend panicked, `file:line:column`.
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: Option<String>,
+    /// Whether the user has been shown the report.
+    #[serde(default)]
+    pub seen: bool,
+}
+
+fn crash_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(logging::log_dir(app)?.join(CRASH_DIR))
+}
+
+/// The OS as a bug report wants it, e.g. `linux x86_64 (Fedora Linux 41)`.
+fn os_info() -> String {
+    let base = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+    let release = fs::read_to_string("/etc/os-release").ok().and_then(|text| {
+        text.lines().find_map(|line| {
+            line.strip_prefix("PRETTY_NAME=")
+                .map(|name| name.trim_matches('"').to_string())
+        })
+    });
+    match release {
+        Some(release) => format!("{base} ({release})"),
+        None => base,
+    }
+}
+
+fn new_report(kind: &str, message: String) -> CrashReport {
+    CrashReport {
+        id: format!("crash-{}", Utc::now().format("%Y%m%dT%H%M%S%3fZ")),
+        kind: kind.to_string(),
+        created_at: now_utc(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: os_info(),
+        message,
+        location: None,
+        thread: None,
+        backtrace: None,
+        seen: false,
+    }
+}
+
+fn report_path(dir: &Path, id: &str) -> CommandResult<PathBuf> {
+    if !id.starts_with("crash-") || id.contains(['/', '\\', '.']) {
+        return Err(CommandError::invalid(format!(
+            "Invalid crash report id: {id}"
+        )));
+    }
+    Ok(dir.join(format!("{id}.json")))
+}
+
+fn write_report(dir: &Path, report: &CrashReport) -> CommandResult<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let body = serde_json::to_vec_pretty(report).map_err(|e| e.to_string())?;
+    write_atomic(&report_path(dir, &report.id)?, &body)
+}
+
+/// Every report in `dir`, newest first.
+fn load_reports(dir: &Path) -> Vec<CrashReport> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<CrashReport> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let text = fs::read_to_string(&path).ok()?;
+            serde_json::from_str(&text)
+                .map_err(|e| tracing::warn!("skipping unreadable {}: {e}", path.display()))
+                .ok()
+        })
+        .collect();
+    reports.sort_by(|a, b| b.id.cmp(&a.id));
+    reports
+}
+
+/// Drop all but the newest `MAX_REPORTS`.
+fn prune(dir: &Path) {
+    for old in load_reports(dir).iter().skip(MAX_REPORTS) {
+        if let Ok(path) = report_path(dir, &old.id) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Save a report for a crash outside the backend, such as the web process
+/// dying.
+#[cfg(target_os = "linux")]
+fn record(app: &AppHandle, kind: &str, message: String) {
+    tracing::error!("{kind} crash: {message}");
+    let saved = crash_dir(app).and_then(|dir| {
+        write_report(&dir, &new_report(kind, message))?;
+        prune(&dir);
+        Ok(())
+    });
+    if let Err(e) = saved {
+        tracing::error!("failed to save crash report: {e}");
+    }
+}
+
+/// Report the main window's web process dying, which otherwise leaves a
+/// blank window and nothing in the logs. WebKitGTK says why it stopped.
+#[cfg(target_os = "linux")]
+pub fn watch_webview(app: &AppHandle, window: &tauri::WebviewWindow) {
+    use gtk::glib;
+    use gtk::prelude::*;
+
+    fn find_webview(widget: &gtk::Widget) -> Option<gtk::Widget> {
+        if widget.type_().name() == "WebKitWebView" {
+            return Some(widget.clone());
+        }
+        widget
+            .downcast_ref::<gtk::Container>()?
+            .children()
+            .iter()
+            .find_map(find_webview)
+    }
+
+    let Ok(gtk_window) = window.gtk_window() else {
+        return;
+    };
+    let Some(webview) = find_webview(gtk_window.upcast_ref()) else {
+        tracing::warn!("webview not found; its crashes won't be reported");
+        return;
+    };
+    let app = app.clone();
+    webview.connect_local("web-process-terminated", false, move |args| {
+        let reason = args
+            .get(1)
+            .and_then(glib::EnumValue::from_value)
+            .map(|(_, value)| value.nick().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        record(
+            &app,
+            KIND_WEBVIEW,
+            format!("the web process stopped: {reason}"),
+        );
+        None
+    });
+}
+
+/// Write a crash report, with a backtrace, whenever the backend panics,
+/// then carry on to the default hook.
+pub fn install_panic_hook(app: &AppHandle) {
+    let dir = match crash_dir(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            tracing::warn!("crash reports disabled: {e}");
+            return;
+        }
+    };
+    prune(&dir);
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic with a non-string payload".to_string());
+        let mut report = new_report(KIND_PANIC, message);
+        report.location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+        report.thread = std::thread::current().name().map(str::to_string);
+        report.backtrace = Some(Backtrace::force_capture().to_string());
+        tracing::error!(
+            "panic at {}: {}",
+            report.location.as_deref().unwrap_or("unknown location"),
+            report.message
+        );
+        if let Err(e) = write_report(&dir, &report) {
+            tracing::error!("failed to save crash report: {e}");
+        }
+        previous(info);
+    }));
+}
+
+/// Crash reports, newest first. With `unseen_only`, just those the user
+/// hasn't been shown yet, for the prompt on launch.
+#[tauri::command]
+pub fn list_crash_reports(
+    app: AppHandle,
+    unseen_only: Option<bool>,
+) -> CommandResult<Vec<CrashReport>> {
+    let reports = load_reports(&crash_dir(&app)?);
+    Ok(if unseen_only.unwrap_or(false) {
+        reports.into_iter().filter(|r| !r.seen).collect()
+    } else {
+        reports
+    })
+}
+
+/// Stop offering a report on launch; it stays in the list.
+#[tauri::command]
+pub fn mark_crash_report_seen(app: AppHandle, id: String) -> CommandResult<()> {
+    let dir = crash_dir(&app)?;
+    let path = report_path(&dir, &id)?;
+    let text = fs::read_to_string(&path)
+        .map_err(|_| CommandError::not_found(format!("Crash report not found: {id}")))?;
+    let mut report: CrashReport = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    report.seen = true;
+    write_report(&dir, &report)
+}
+
+#[tauri::command]
+pub fn delete_crash_report(app: AppHandle, id: String) -> CommandResult<()> {
+    let path = report_path(&crash_dir(&app)?, &id)?;
+    fs::remove_file(&path)
+        .map_err(|_| CommandError::not_found(format!("Crash report not found: {id}")))
+}
+
+/// Write a report, followed by the latest log lines, as plain text to
+/// `path`, ready to attach to a bug report.
+#[tauri::command]
+pub fn export_crash_report(app: AppHandle, id: String, path: String) -> CommandResult<()> {
+    let report = load_reports(&crash_dir(&app)?)
+        .into_iter()
+        .find(|r| r.id == id)
+        .ok_or_else(|| CommandError::not_found(format!("Crash report not found: {id}")))?;
+    let mut text = format!(
+        "DayLight {} crash report\n\nKind: {}\nWhen: {}\nOS: {}\nThread: {}\nLocation: {}\n\n{}\n",
+        report.app_version,
+        report.kind,
+        report.created_at,
+        report.os,
+        report.thread.as_deref().unwrap_or("-"),
+        report.location.as_deref().unwrap_or("-"),
+        report.message
+    );
+    if let Some(backtrace) = &report.backtrace {
+        text.push_str(&format!("\nBacktrace:\n{backtrace}\n"));
+    }
+    match logging::recent_logs(&app, Some(EXPORT_LOG_LINES)) {
+        Ok(logs) => text.push_str(&format!("\nRecent log:\n{}\n", logs.text)),
+        Err(e) => text.push_str(&format!("\nRecent log unavailable: {e}\n")),
+    }
+    fs::write(&path, text).with_context(|| format!("Failed to write {path}"))
+}
@@ -0,0 +1,674 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::projects;
+use crate::tags;
+use crate::task_store;
+
+/// Emitted with the `MergeReport` after changes from elsewhere were merged
+/// in; task views should reload.
+pub const TASKS_MERGED_EVENT: &str = "tasks-merged";
+
+/// Task columns merged field by field, each a last-writer-wins register.
+/// `id`, `created_at` and `updated_at` aren't edited, so aren't merged.
+pub const MERGED_FIELDS: &[&str] = &[
+    "title",
+    "description",
+    "status",
+    "project",
+    "priority",
+    "due",
+    "scheduled",
+    "completed_at",
+    "recurrence",
+    "series_id",
+    "tz",
+    "parent_id",
+    "sort_key",
+    "deleted_at",
+    "estimate_minutes",
+];
+
+/// SQL for the next local clock: one past the last clock seen, or the
+/// wall clock in milliseconds if that's later. Clocks from other devices
+/// and sources push the last clock forward, so a later edit always wins.
+const NEXT_CLOCK_SQL: &str = "UPDATE crdt_state SET clock = max(clock + 1, \
+     CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)) WHERE id = 1;";
+
+/// When a value was written and by whom. Compared by clock, then node, so
+/// every device picks the same winner.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Stamp {
+    pub clock: i64,
+    pub node: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldValue {
+    pub value: Json,
+    #[serde(flatten)]
+    pub stamp: Stamp,
+}
+
+/// One element of a task's tag set: added when `present`, removed if not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagState {
+    pub name: String,
+    pub present: bool,
+    #[serde(flatten)]
+    pub stamp: Stamp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskState {
+    pub id: String,
+    pub created_at: String,
+    pub fields: BTreeMap<String, FieldValue>,
+    pub tags: Vec<TagState>,
+}
+
+/// Tasks as one replica knows them, for another to merge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeSet {
+    pub node: String,
+    /// The sender's clock; pass it back as `since` to get only newer changes.
+    pub clock: i64,
+    pub tasks: Vec<TaskState>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeReport {
+    pub created: usize,
+    pub updated: usize,
+    /// Incoming values that lost to a newer local edit.
+    pub kept_local: usize,
+    /// Incoming values that weren't valid for their field.
+    pub rejected: Vec<String>,
+}
+
+/// (Re)create the triggers that stamp local edits, like the journal's.
+/// Inserting a task stamps every field; updates stamp the fields that
+/// changed; tag links stamp their tag.
+pub fn install(conn: &Connection) -> rusqlite::Result<()> {
+    let stamp = |field: &str, task: &str| {
+        format!(
+            "INSERT INTO task_clocks (task_id, field, clock, node)
+             SELECT {task}, '{field}', clock, node FROM crdt_state WHERE id = 1
+             ON CONFLICT (task_id, field) DO UPDATE SET
+                 clock = excluded.clock, node = excluded.node;"
+        )
+    };
+    let tag_stamp = |task: &str, tag: &str, present: i64| {
+        format!(
+            "INSERT INTO task_tag_clocks (task_id, tag, present, clock, node)
+             SELECT {task}, g.name, {present}, s.clock, s.node
+             FROM tags g, crdt_state s WHERE g.id = {tag} AND s.id = 1
+             ON CONFLICT (task_id, tag) DO UPDATE SET
+                 present = excluded.present, clock = excluded.clock, node = excluded.node;"
+        )
+    };
+
+    let mut sql = String::from("DROP TRIGGER IF EXISTS crdt_tasks_insert;");
+    let all: String = MERGED_FIELDS.iter().map(|f| stamp(f, "new.id")).collect();
+    sql.push_str(&format!(
+        "CREATE TRIGGER crdt_tasks_insert AFTER INSERT ON tasks BEGIN
+             {NEXT_CLOCK_SQL}
+             {all}
+         END;"
+    ));
+    for field in MERGED_FIELDS {
+        sql.push_str(&format!(
+            "DROP TRIGGER IF EXISTS crdt_tasks_{field};
+             CREATE TRIGGER crdt_tasks_{field} AFTER UPDATE OF \"{field}\" ON tasks
+             WHEN old.\"{field}\" IS NOT new.\"{field}\" BEGIN
+                 {NEXT_CLOCK_SQL}
+                 {}
+             END;",
+            stamp(field, "new.id")
+        ));
+    }
+    sql.push_str(&format!(
+        "DROP TRIGGER IF EXISTS crdt_task_tags_insert;
+         CREATE TRIGGER crdt_task_tags_insert AFTER INSERT ON task_tags BEGIN
+             {NEXT_CLOCK_SQL}
+             {}
+         END;
+         DROP TRIGGER IF EXISTS crdt_task_tags_delete;
+         CREATE TRIGGER crdt_task_tags_delete AFTER DELETE ON task_tags
+         WHEN EXISTS (SELECT 1 FROM tasks WHERE id = old.task_id) BEGIN
+             {NEXT_CLOCK_SQL}
+             {}
+         END;",
+        tag_stamp("new.task_id", "new.tag_id", 1),
+        tag_stamp("old.task_id", "old.tag_id", 0),
+    ));
+    conn.execute_batch(&sql)
+}
+
+pub fn local_clock(conn: &Connection) -> rusqlite::Result<(String, i64)> {
+    conn.query_row(
+        "SELECT node, clock FROM crdt_state WHERE id = 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Move the local clock past one seen elsewhere.
+fn observe(conn: &Connection, clock: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE crdt_state SET clock = max(clock, ?1) WHERE id = 1",
+        params![clock],
+    )?;
+    Ok(())
+}
+
+pub fn field_stamps(conn: &Connection, task_id: &str) -> rusqlite::Result<HashMap<String, Stamp>> {
+    let mut stmt = conn.prepare("SELECT field, clock, node FROM task_clocks WHERE task_id = ?1")?;
+    let rows = stmt.query_map(params![task_id], |row| {
+        Ok((
+            row.get(0)?,
+            Stamp {
+                clock: row.get(1)?,
+                node: row.get(2)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+/// The task's tag set, including removals, keyed by lowercase name. Links
+/// made before stamping began count as present with a zero stamp.
+fn tag_states(conn: &Connection, task_id: &str) -> rusqlite::Result<HashMap<String, TagState>> {
+    let mut states = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT g.name, 1, c.clock, c.node FROM task_tags tt
+         JOIN tags g ON g.id = tt.tag_id
+         LEFT JOIN task_tag_clocks c ON c.task_id = tt.task_id AND c.tag = g.name
+         WHERE tt.task_id = ?1
+         UNION ALL
+         SELECT tag, present, clock, node FROM task_tag_clocks
+         WHERE task_id = ?1 AND present = 0",
+    )?;
+    let rows = stmt.query_map(params![task_id], |row| {
+        Ok(TagState {
+            name: row.get(0)?,
+            present: row.get(1)?,
+            stamp: Stamp {
+                clock: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                node: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            },
+        })
+    })?;
+    for state in rows {
+        let state = state?;
+        states.entry(state.name.to_lowercase()).or_insert(state);
+    }
+    Ok(states)
+}
+
+/// When the task's tags were last changed, if the change was stamped.
+pub fn newest_tag_clock(conn: &Connection, task_id: &str) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT MAX(clock) FROM task_tag_clocks WHERE task_id = ?1",
+        params![task_id],
+        |row| row.get(0),
+    )
+}
+
+fn to_json(value: ValueRef) -> Json {
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => Json::Null,
+        ValueRef::Integer(n) => Json::from(n),
+        ValueRef::Real(n) => Json::from(n),
+        ValueRef::Text(text) => Json::from(String::from_utf8_lossy(text).into_owned()),
+    }
+}
+
+/// Everything this replica knows about a task, trashed or not.
+pub fn task_state(conn: &Connection, id: &str) -> rusqlite::Result<Option<TaskState>> {
+    let columns = MERGED_FIELDS
+        .iter()
+        .map(|f| format!("\"{f}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let row = conn
+        .query_row(
+            &format!("SELECT created_at, {columns} FROM tasks WHERE id = ?1"),
+            params![id],
+            |row| {
+                let created_at: String = row.get(0)?;
+                let values = (0..MERGED_FIELDS.len())
+                    .map(|i| row.get_ref(i + 1).map(to_json))
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok((created_at, values))
+            },
+        )
+        .optional()?;
+    let Some((created_at, values)) = row else {
+        return Ok(None);
+    };
+    let mut stamps = field_stamps(conn, id)?;
+    let fields = MERGED_FIELDS
+        .iter()
+        .zip(values)
+        .map(|(field, value)| {
+            let stamp = stamps.remove(*field).unwrap_or_default();
+            (field.to_string(), FieldValue { value, stamp })
+        })
+        .collect();
+    let mut tags: Vec<TagState> = tag_states(conn, id)?.into_values().collect();
+    tags.sort_by_key(|t| t.name.to_lowercase());
+    Ok(Some(TaskState {
+        id: id.to_string(),
+        created_at,
+        fields,
+        tags,
+    }))
+}
+
+/// Tasks with a field or tag stamped after `since`; every task when it's 0.
+pub fn changes_since(conn: &Connection, since: i64) -> rusqlite::Result<ChangeSet> {
+    let (node, clock) = local_clock(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT id FROM tasks
+         WHERE ?1 = 0
+            OR id IN (SELECT task_id FROM task_clocks WHERE clock > ?1)
+            OR id IN (SELECT task_id FROM task_tag_clocks WHERE clock > ?1)
+         ORDER BY created_at, id",
+    )?;
+    let ids = stmt
+        .query_map(params![since], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut tasks = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(state) = task_state(conn, &id)? {
+            tasks.push(state);
+        }
+    }
+    Ok(ChangeSet { node, clock, tasks })
+}
+
+/// Check an incoming value fits its column.
+fn to_sql(field: &str, value: &Json) -> CommandResult<Value> {
+    let invalid = || CommandError::invalid(format!("Invalid value for {field}: {value}"));
+    let value = match value {
+        Json::Null => Value::Null,
+        Json::Number(n) => Value::Integer(n.as_i64().ok_or_else(invalid)?),
+        Json::String(s) => Value::Text(s.clone()),
+        _ => return Err(invalid()),
+    };
+    match (field, &value) {
+        ("title", Value::Text(title)) => {
+            task_store::validate_title(title)?;
+        }
+        ("status", Value::Text(status)) => task_store::validate_status(status)?,
+        ("title" | "status", _) => return Err(invalid()),
+        ("priority" | "estimate_minutes", Value::Text(_)) => return Err(invalid()),
+        ("estimate_minutes", Value::Integer(minutes)) => {
+            task_store::validate_estimate(*minutes)?;
+        }
+        (_, Value::Integer(_)) if field != "priority" => return Err(invalid()),
+        _ => {}
+    }
+    Ok(value)
+}
+
+fn set_stamp(conn: &Connection, task_id: &str, field: &str, stamp: &Stamp) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO task_clocks (task_id, field, clock, node) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (task_id, field) DO UPDATE SET clock = excluded.clock, node = excluded.node",
+        params![task_id, field, stamp.clock, stamp.node],
+    )?;
+    Ok(())
+}
+
+fn set_tag_stamp(conn: &Connection, task_id: &str, tag: &TagState) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO task_tag_clocks (task_id, tag, present, clock, node)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (task_id, tag) DO UPDATE SET
+             present = excluded.present, clock = excluded.clock, node = excluded.node",
+        params![
+            task_id,
+            tag.name,
+            tag.present,
+            tag.stamp.clock,
+            tag.stamp.node
+        ],
+    )?;
+    Ok(())
+}
+
+/// Merge one task into this replica. Each field and tag keeps whichever
+/// side stamped it last, so merging in any order gives the same result.
+/// Returns whether anything changed; a task not seen before is created.
+pub fn merge_task(
+    conn: &Connection,
+    remote: &TaskState,
+    report: &mut MergeReport,
+) -> rusqlite::Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM tasks WHERE id = ?1)",
+        params![remote.id],
+        |row| row.get(0),
+    )?;
+    let mut local = field_stamps(conn, &remote.id)?;
+    if !exists {
+        let title = remote
+            .fields
+            .get("title")
+            .map(|f| to_sql("title", &f.value));
+        if !matches!(title, Some(Ok(_))) {
+            report
+                .rejected
+                .push(format!("{}: a new task needs a title", remote.id));
+            return Ok(false);
+        }
+        conn.execute(
+            "INSERT INTO tasks (id, title, created_at, updated_at) VALUES (?1, '', ?2, ?2)",
+            params![remote.id, remote.created_at],
+        )?;
+        // The insert stamped every field as a local edit; anything the
+        // other side sent should win over that.
+        local.clear();
+    }
+
+    let mut changed = !exists;
+    let mut newest = 0;
+    for (field, incoming) in &remote.fields {
+        if !MERGED_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        newest = newest.max(incoming.stamp.clock);
+        if local
+            .get(field)
+            .is_some_and(|stamp| *stamp >= incoming.stamp)
+        {
+            if exists {
+                report.kept_local += 1;
+            }
+            continue;
+        }
+        let value = match to_sql(field, &incoming.value) {
+            Ok(value) => value,
+            Err(e) => {
+                report.rejected.push(format!("{}: {e}", remote.id));
+                continue;
+            }
+        };
+        if let Value::Text(project) = &value {
+            if field == "project" {
+                projects::ensure_project(conn, project)?;
+            }
+        }
+        changed |= conn.execute(
+            &format!("UPDATE tasks SET \"{field}\" = ?2 WHERE id = ?1 AND \"{field}\" IS NOT ?2"),
+            params![remote.id, value],
+        )? > 0;
+        set_stamp(conn, &remote.id, field, &incoming.stamp)?;
+    }
+
+    let local_tags = tag_states(conn, &remote.id)?;
+    for incoming in &remote.tags {
+        newest = newest.max(incoming.stamp.clock);
+        let key = incoming.name.to_lowercase();
+        if local_tags
+            .get(&key)
+            .is_some_and(|local| local.stamp >= incoming.stamp)
+        {
+            report.kept_local += 1;
+            continue;
+        }
+        let Ok(name) = tags::normalize_name(&incoming.name) else {
+            report
+                .rejected
+                .push(format!("{}: invalid tag {}", remote.id, incoming.name));
+            continue;
+        };
+        let was_present = local_tags.get(&key).is_some_and(|t| t.present);
+        if incoming.present && !was_present {
+            let tag = tags::ensure_tag(conn, &name)?;
+            conn.execute(
+                "INSERT OR IGNORE INTO task_tags (task_id, tag_id) VALUES (?1, ?2)",
+                params![remote.id, tag.id],
+            )?;
+            changed = true;
+        } else if !incoming.present && was_present {
+            conn.execute(
+                "DELETE FROM task_tags WHERE task_id = ?1
+                 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+                params![remote.id, name],
+            )?;
+            changed = true;
+        }
+        set_tag_stamp(conn, &remote.id, incoming)?;
+    }
+
+    if changed {
+        conn.execute(
+            "UPDATE tasks SET updated_at = ?2 WHERE id = ?1",
+            params![remote.id, now_utc()],
+        )?;
+    }
+    observe(conn, newest)?;
+    Ok(changed)
+}
+
+/// Merge another replica's tasks in one transaction.
+pub fn merge(conn: &mut Connection, changes: &ChangeSet) -> rusqlite::Result<MergeReport> {
+    let tx = conn.transaction()?;
+    // A subtask can arrive ahead of its parent.
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON;")?;
+    let mut report = MergeReport::default();
+    for task in &changes.tasks {
+        let existed: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM tasks WHERE id = ?1)",
+            params![task.id],
+            |row| row.get(0),
+        )?;
+        if merge_task(&tx, task, &mut report)? {
+            if existed {
+                report.updated += 1;
+            } else {
+                report.created += 1;
+            }
+        }
+    }
+    observe(&tx, changes.clock)?;
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Tasks changed after `since` (a clock from an earlier export), or all of
+/// them, for another device or sync provider to merge.
+#[tauri::command]
+pub fn export_task_changes(db: State<'_, Db>, since: Option<i64>) -> CommandResult<ChangeSet> {
+    db.with_conn(|conn| changes_since(conn, since.unwrap_or(0)))
+}
+
+#[tauri::command]
+pub fn merge_task_changes(
+    app: AppHandle,
+    db: State<'_, Db>,
+    changes: ChangeSet,
+) -> CommandResult<MergeReport> {
+    let report = db
+        .with_conn(|conn| {
+            history::with_source(conn, ChangeSource::Sync, |conn| merge(conn, &changes))
+        })?
+        .context("Failed to merge changes")?;
+    if report.created + report.updated > 0 {
+        let _ = app.emit(TASKS_MERGED_EVENT, &report);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+    use serde_json::json;
+
+    fn replica() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        migrations::run_migrations(&mut conn).unwrap();
+        install(&conn).unwrap();
+        conn
+    }
+
+    fn add_task(conn: &Connection, id: &str, title: &str) {
+        conn.execute(
+            "INSERT INTO tasks (id, title, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+            params![id, title, "2025-01-15T10:00:00.000Z"],
+        )
+        .unwrap();
+    }
+
+    fn set(conn: &Connection, id: &str, field: &str, value: impl rusqlite::ToSql) {
+        conn.execute(
+            &format!("UPDATE tasks SET \"{field}\" = ?2 WHERE id = ?1"),
+            params![id, value],
+        )
+        .unwrap();
+    }
+
+    fn set_tags(conn: &Connection, id: &str, names: &[&str]) {
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        tags::set_task_tags(conn, id, &names).unwrap();
+    }
+
+    /// Field values and stamps, plus the tag set, as a comparable value.
+    fn snapshot(conn: &Connection, id: &str) -> Json {
+        let state = task_state(conn, id).unwrap().unwrap();
+        json!({ "fields": state.fields, "tags": state.tags })
+    }
+
+    fn present_tags(conn: &Connection, id: &str) -> Vec<String> {
+        let state = task_state(conn, id).unwrap().unwrap();
+        state
+            .tags
+            .into_iter()
+            .filter(|t| t.present)
+            .map(|t| t.name)
+            .collect()
+    }
+
+    /// Send each side everything the other knows.
+    fn sync(a: &mut Connection, b: &mut Connection) {
+        let from_a = changes_since(a, 0).unwrap();
+        let from_b = changes_since(b, 0).unwrap();
+        merge(a, &from_b).unwrap();
+        merge(b, &from_a).unwrap();
+    }
+
+    #[test]
+    fn concurrent_edits_converge() {
+        let (mut a, mut b) = (replica(), replica());
+        add_task(&a, "t1", "Plan trip");
+        set_tags(&a, "t1", &["travel"]);
+        let report = merge(&mut b, &changes_since(&a, 0).unwrap()).unwrap();
+        assert_eq!((report.created, report.updated), (1, 0));
+        assert_eq!(snapshot(&a, "t1"), snapshot(&b, "t1"));
+
+        // Both edit the title; each edits a field of its own and the tags.
+        set(&a, "t1", "title", "Plan the trip");
+        set(&a, "t1", "priority", 2);
+        set_tags(&a, "t1", &["travel", "summer"]);
+        set(&b, "t1", "title", "Book the trip");
+        set(&b, "t1", "due", "2025-02-01");
+        set_tags(&b, "t1", &[]);
+        add_task(&b, "t2", "Pack");
+
+        sync(&mut a, &mut b);
+        assert_eq!(snapshot(&a, "t1"), snapshot(&b, "t1"));
+        assert_eq!(snapshot(&a, "t2"), snapshot(&b, "t2"));
+        let merged = task_state(&a, "t1").unwrap().unwrap();
+        assert_eq!(merged.fields["priority"].value, json!(2));
+        assert_eq!(merged.fields["due"].value, json!("2025-02-01"));
+        // B removed "travel" after seeing A add it; A's "summer" is newer.
+        assert_eq!(present_tags(&a, "t1"), ["summer"]);
+
+        // Nothing left to exchange.
+        let again = merge(&mut a, &changes_since(&b, 0).unwrap()).unwrap();
+        assert_eq!((again.created, again.updated), (0, 0));
+    }
+
+    #[test]
+    fn merge_order_does_not_matter() {
+        let (a, mut b, mut c) = (replica(), replica(), replica());
+        add_task(&a, "t1", "Write report");
+        let base = changes_since(&a, 0).unwrap();
+        merge(&mut b, &base).unwrap();
+        merge(&mut c, &base).unwrap();
+
+        set(&b, "t1", "status", "done");
+        set(&b, "t1", "title", "Write the report");
+        set(&c, "t1", "title", "Write a report");
+        set(&c, "t1", "project", "Work");
+        let (from_b, from_c) = (changes_since(&b, 0).unwrap(), changes_since(&c, 0).unwrap());
+
+        let (mut first, mut second) = (replica(), replica());
+        for changes in [&base, &from_b, &from_c] {
+            merge(&mut first, changes).unwrap();
+        }
+        for changes in [&from_c, &from_b, &base] {
+            merge(&mut second, changes).unwrap();
+        }
+        assert_eq!(snapshot(&first, "t1"), snapshot(&second, "t1"));
+        let state = task_state(&first, "t1").unwrap().unwrap();
+        assert_eq!(state.fields["status"].value, json!("done"));
+        assert_eq!(state.fields["project"].value, json!("Work"));
+    }
+
+    #[test]
+    fn newer_local_edit_is_kept() {
+        let (a, mut b) = (replica(), replica());
+        add_task(&a, "t1", "Call plumber");
+        let stale = changes_since(&a, 0).unwrap();
+        merge(&mut b, &stale).unwrap();
+        set(&b, "t1", "title", "Call the plumber");
+
+        let report = merge(&mut b, &stale).unwrap();
+        assert_eq!(report.updated, 0);
+        assert_eq!(report.kept_local, MERGED_FIELDS.len());
+        let state = task_state(&b, "t1").unwrap().unwrap();
+        assert_eq!(state.fields["title"].value, json!("Call the plumber"));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let mut a = replica();
+        add_task(&a, "t1", "Water plants");
+        let mut changes = changes_since(&a, 0).unwrap();
+        let future = Stamp {
+            clock: changes.clock + 1000,
+            node: "other".into(),
+        };
+        let task = &mut changes.tasks[0];
+        for (field, value) in [("status", json!("someday")), ("priority", json!("high"))] {
+            let field = task.fields.get_mut(field).unwrap();
+            field.value = value;
+            field.stamp = future.clone();
+        }
+        let mut untitled = task.clone();
+        untitled.id = "t2".into();
+        untitled.fields.remove("title");
+        changes.tasks.push(untitled);
+
+        let report = merge(&mut a, &changes).unwrap();
+        assert_eq!(report.rejected.len(), 3);
+        assert_eq!(report.created, 0);
+        let state = task_state(&a, "t1").unwrap().unwrap();
+        assert_eq!(state.fields["status"].value, json!("open"));
+        assert!(task_state(&a, "t2").unwrap().is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::data_dir;
+use crate::db::now_utc;
+use crate::error::{CommandError, CommandResult};
+use crate::session::write_atomic;
+
+#[cfg(desktop)]
+const KEYRING_SERVICE: &str = "daylight";
+/// Every credential moved into the keyring so far, for the settings page.
+const REPORT_FILE: &str = "credential_migration.json";
+
+/// The local API's bearer token, formerly in `api_server.json`.
+pub const API_TOKEN: &str = "api-server:token";
+/// The calendar feed's secret path segment, formerly in `ics_feed.json`.
+pub const ICS_FEED_TOKEN: &str = "ics-feed:token";
+/// Secrets the frontend keeps in the keyring, by entry name; it can't read
+/// or write any other entry.
+const FRONTEND_SECRETS: &[&str] = &[
+    "google-calendar:client-secret",
+    "google-calendar:access-token",
+    "google-calendar:refresh-token",
+    "ics:secret-url",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedCredential {
+    /// The keyring entry it now lives in, e.g. `api-server:token`.
+    pub name: String,
+    /// The file it was in, e.g. `meta.json`.
+    pub source: String,
+    pub moved_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedCredential {
+    pub name: String,
+    pub source: String,
+    /// Why it's still in `source`.
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CredentialMigration {
+    pub moved: Vec<MovedCredential>,
+    pub failed: Vec<FailedCredential>,
+}
+
+#[cfg(desktop)]
+fn keyring_entry(name: &str) -> CommandResult<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .map_err(|e| format!("Failed to open keyring: {e}").into())
+}
+
+#[cfg(desktop)]
+pub fn load(name: &str) -> Option<String> {
+    keyring_entry(name).ok()?.get_password().ok()
+}
+
+#[cfg(not(desktop))]
+pub fn load(_name: &str) -> Option<String> {
+    None
+}
+
+/// `name`'s secret, for a sync that can't go on without it. `what` names
+/// it in the error, e.g. `API key`.
+pub fn require(name: &str, what: &str) -> CommandResult<String> {
+    load(name)
+        .ok_or_else(|| CommandError::unauthorized(format!("No saved {what} for this account")))
+}
+
+/// Save a secret to the keyring under `name`, or forget it when `None`.
+#[cfg(desktop)]
+pub fn save(name: &str, secret: Option<&str>) -> CommandResult<()> {
+    let entry = keyring_entry(name)?;
+    match secret {
+        Some(secret) => entry
+            .set_password(secret)
+            .map_err(|e| format!("Failed to save {name} to keyring: {e}").into()),
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to remove {name} from keyring: {e}").into()),
+        },
+    }
+}
+
+#[cfg(not(desktop))]
+pub fn save(_name: &str, secret: Option<&str>) -> CommandResult<()> {
+    match secret {
+        Some(_) => Err("No keyring on this platform".into()),
+        None => Ok(()),
+    }
+}
+
+fn report_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(REPORT_FILE))
+}
+
+fn load_report(app: &AppHandle) -> CredentialMigration {
+    let Ok(path) = report_path(app) else {
+        return CredentialMigration::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable report: {e}");
+            CredentialMigration::default()
+        }),
+        Err(_) => CredentialMigration::default(),
+    }
+}
+
+/// Add `run` to the saved report. A credential moved now is no longer
+/// listed as failed.
+fn record(app: &AppHandle, run: &CredentialMigration) {
+    if run.moved.is_empty() && run.failed.is_empty() {
+        return;
+    }
+    let mut report = load_report(app);
+    report.failed.retain(|old| {
+        !run.moved.iter().any(|moved| moved.name == old.name)
+            && !run.failed.iter().any(|failed| failed.name == old.name)
+    });
+    report.moved.extend(run.moved.iter().cloned());
+    report.failed.extend(run.failed.iter().cloned());
+    let saved = report_path(app).and_then(|path| {
+        let body = serde_json::to_vec_pretty(&report).map_err(|e| e.to_string())?;
+        write_atomic(&path, &body)
+    });
+    if let Err(e) = saved {
+        tracing::warn!("failed to save credential report: {e}");
+    }
+}
+
+impl CredentialMigration {
+    /// Move `secret` into the keyring as `name`, noting how it went.
+    /// Returns whether it's now safe to scrub from `source`.
+    pub fn move_secret(&mut self, name: &str, source: &str, secret: &str) -> bool {
+        match save(name, Some(secret)) {
+            Ok(()) => {
+                tracing::info!("moved {name} from {source} to the keyring");
+                self.moved.push(MovedCredential {
+                    name: name.to_string(),
+                    source: source.to_string(),
+                    moved_at: now_utc(),
+                });
+                true
+            }
+            Err(error) => {
+                tracing::warn!("couldn't move {name} from {source}: {error}");
+                self.failed.push(FailedCredential {
+                    name: name.to_string(),
+                    source: source.to_string(),
+                    error: error.to_string(),
+                });
+                false
+            }
+        }
+    }
+}
+
+/// Move credentials the backend kept in plain files into the keyring.
+/// Does nothing once they're gone from the files, so it runs on every
+/// launch.
+pub fn migrate_app_data(app: &AppHandle) {
+    if cfg!(not(desktop)) {
+        return;
+    }
+    let mut run = CredentialMigration::default();
+    crate::api_server::migrate_token(app, &mut run);
+    crate::ics_feed::migrate_token(app, &mut run);
+    record(app, &run);
+}
+
+fn check_frontend_name(name: &str) -> CommandResult<()> {
+    if FRONTEND_SECRETS.contains(&name) {
+        Ok(())
+    } else {
+        Err(CommandError::invalid(format!("Unknown credential: {name}")))
+    }
+}
+
+/// The frontend's secrets that are in the keyring, by name.
+#[tauri::command]
+pub fn get_credentials(names: Vec<String>) -> CommandResult<HashMap<String, String>> {
+    let mut found = HashMap::new();
+    for name in names {
+        check_frontend_name(&name)?;
+        if let Some(secret) = load(&name) {
+            found.insert(name, secret);
+        }
+    }
+    Ok(found)
+}
+
+/// Save a frontend secret to the keyring, or forget it when `value` is
+/// empty or omitted.
+#[tauri::command]
+pub fn set_credential(name: String, value: Option<String>) -> CommandResult<()> {
+    check_frontend_name(&name)?;
+    let value = value.filter(|value| !value.is_empty());
+    save(&name, value.as_deref())
+}
+
+/// Move secrets the frontend found in `source` (such as `meta.json`) into
+/// the keyring. The frontend scrubs those listed as moved; failed ones are
+/// left where they were.
+#[tauri::command]
+pub fn migrate_credentials(
+    app: AppHandle,
+    source: String,
+    values: HashMap<String, String>,
+) -> CommandResult<CredentialMigration> {
+    let mut run = CredentialMigration::default();
+    for (name, secret) in &values {
+        check_frontend_name(name)?;
+        if !secret.is_empty() {
+            run.move_secret(name, &source, secret);
+        }
+    }
+    record(&app, &run);
+    Ok(run)
+}
+
+/// Every credential moved into the keyring so far, and any that couldn't
+/// be.
+#[tauri::command]
+pub fn get_credential_migration(app: AppHandle) -> CredentialMigration {
+    load_report(&app)
+}
@@ -0,0 +1,144 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Quote a field if it contains a delimiter, quote or line break (RFC 4180).
+fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Write one CSV record terminated by CRLF.
+pub fn write_record<W: Write>(out: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        out.write_all(escape(field).as_bytes())?;
+    }
+    out.write_all(b"\r\n")
+}
+
+/// Guess the delimiter from the header line: whichever of comma, semicolon
+/// or tab appears most. Spreadsheets in many locales export with `;`.
+pub fn detect_delimiter(text: &str) -> char {
+    let header = text.lines().next().unwrap_or("");
+    [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|d| header.matches(*d).count())
+        .filter(|d| header.contains(*d))
+        .unwrap_or(',')
+}
+
+/// Parse CSV text into records. Handles quoted fields with embedded
+/// delimiters, quotes and line breaks, CRLF or LF line endings, and a
+/// leading byte-order mark. Blank lines are skipped.
+pub fn parse(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c != '"' {
+                field.push(c);
+            } else if chars.peek() == Some(&'"') {
+                field.push('"');
+                chars.next();
+            } else {
+                quoted = false;
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            record.push(std::mem::take(&mut field));
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|r: &Vec<String>| !(r.len() == 1 && r[0].trim().is_empty()));
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(records: &[&[&str]]) -> Vec<Vec<String>> {
+        records
+            .iter()
+            .map(|r| r.iter().map(|f| f.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn write_quotes_only_when_needed() {
+        let mut out = Vec::new();
+        write_record(
+            &mut out,
+            &["id", "Buy milk, eggs", "say \"hi\"", "two\nlines", ""],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,\"Buy milk, eggs\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let records: &[&[&str]] = &[
+            &["title", "notes", "due"],
+            &["Call \"Mum\"", "ring,\r\nthen text", ""],
+            &["", "", "2025-01-15"],
+        ];
+        let mut out = Vec::new();
+        for record in records {
+            write_record(&mut out, record).unwrap();
+        }
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(parse(&text, detect_delimiter(&text)), rows(records));
+    }
+
+    #[test]
+    fn parse_spreadsheet_exports() {
+        // A BOM, semicolons, LF endings, a blank line and no final newline.
+        let text = "\u{feff}Title;Project;Tags\nPay rent;Home;\"bills;monthly\"\n\n  \nTaxes;;";
+        assert_eq!(detect_delimiter(text), ';');
+        assert_eq!(
+            parse(text, ';'),
+            rows(&[
+                &["Title", "Project", "Tags"],
+                &["Pay rent", "Home", "bills;monthly"],
+                &["Taxes", "", ""],
+            ])
+        );
+        assert_eq!(detect_delimiter("a\tb\tc,d"), '\t');
+        assert_eq!(detect_delimiter("title"), ',');
+    }
+
+    #[test]
+    fn quotes_inside_unquoted_fields_are_literal() {
+        assert_eq!(
+            parse("5\" screen,\"ok\"\r\n", ','),
+            rows(&[&["5\" screen", "ok"]])
+        );
+        // An unterminated quote runs to the end of the text.
+        assert_eq!(parse("a,\"b\nc", ','), rows(&[&["a", "b\nc"]]));
+    }
+}
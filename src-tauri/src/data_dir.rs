@@ -0,0 +1,319 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{self, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::session::write_atomic;
+
+/// `--data-dir <path>` (or `--data-dir=<path>`) keeps everything in `path`.
+pub const DATA_DIR_FLAG: &str = "--data-dir";
+/// A file next to the executable that turns on portable mode. It may name
+/// the data folder, relative to the executable; otherwise `data/` is used.
+const PORTABLE_MARKER: &str = "portable.txt";
+const PORTABLE_DIR: &str = "data";
+/// Kept in the default data dir after `migrate_data_dir`, pointing at the
+/// new one.
+const LOCATION_FILE: &str = "location.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDirSource {
+    Default,
+    Flag,
+    Portable,
+    /// Moved with `migrate_data_dir`.
+    Moved,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataDirInfo {
+    pub path: String,
+    pub source: DataDirSource,
+    pub default_path: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LocationFile {
+    data_dir: PathBuf,
+    /// Where the data was moved from; cleared once the app has started from
+    /// the new place.
+    previous: Option<PathBuf>,
+}
+
+/// Where the database, attachments and settings live. The webview's own
+/// storage stays in the platform default.
+pub struct DataDir {
+    fixed: Option<(PathBuf, DataDirSource)>,
+    resolved: OnceLock<(PathBuf, DataDirSource)>,
+}
+
+impl DataDir {
+    /// Read `--data-dir` and the portable marker. Both are fixed for the
+    /// life of the process.
+    pub fn from_env() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let fixed = flag_dir(&args)
+            .map(|dir| (dir, DataDirSource::Flag))
+            .or_else(|| portable_dir().map(|dir| (dir, DataDirSource::Portable)));
+        Self {
+            fixed,
+            resolved: OnceLock::new(),
+        }
+    }
+}
+
+fn flag_dir(args: &[String]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(value) = arg
+            .strip_prefix(DATA_DIR_FLAG)
+            .and_then(|v| v.strip_prefix('='))
+        {
+            return Some(PathBuf::from(value));
+        }
+    }
+    None
+}
+
+/// The folder the executable was launched from. For an AppImage that's
+/// where the image file is, not its mount point.
+fn exe_dir() -> Option<PathBuf> {
+    let exe = match std::env::var_os("APPIMAGE") {
+        Some(image) => PathBuf::from(image),
+        None => std::env::current_exe().ok()?,
+    };
+    exe.parent().map(Path::to_path_buf)
+}
+
+fn portable_dir() -> Option<PathBuf> {
+    let exe_dir = exe_dir()?;
+    let marker = fs::read_to_string(exe_dir.join(PORTABLE_MARKER)).ok()?;
+    let named = marker.lines().next().unwrap_or("").trim();
+    Some(if named.is_empty() {
+        exe_dir.join(PORTABLE_DIR)
+    } else {
+        exe_dir.join(named)
+    })
+}
+
+fn default_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data dir: {e}").into())
+}
+
+fn read_location(default: &Path) -> Option<LocationFile> {
+    let content = fs::read_to_string(default.join(LOCATION_FILE)).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(location) => Some(location),
+        Err(e) => {
+            tracing::warn!("ignoring unreadable {LOCATION_FILE}: {e}");
+            None
+        }
+    }
+}
+
+fn resolve(app: &AppHandle) -> CommandResult<(PathBuf, DataDirSource)> {
+    let state = app.state::<DataDir>();
+    if let Some(resolved) = state.resolved.get() {
+        return Ok(resolved.clone());
+    }
+    let resolved = match &state.fixed {
+        Some(fixed) => fixed.clone(),
+        None => {
+            let default = default_dir(app)?;
+            match read_location(&default) {
+                Some(location) if location.data_dir != default => {
+                    (location.data_dir, DataDirSource::Moved)
+                }
+                _ => (default, DataDirSource::Default),
+            }
+        }
+    };
+    Ok(state.resolved.get_or_init(|| resolved).clone())
+}
+
+/// The app's data dir: the database, attachments, notes and the backend's
+/// own settings files.
+pub fn app_data_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    resolve(app).map(|(dir, _)| dir)
+}
+
+/// Per-machine settings such as zoom. In portable mode and with
+/// `--data-dir` these travel with the data.
+pub fn app_config_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    match resolve(app)? {
+        (dir, DataDirSource::Flag | DataDirSource::Portable) => Ok(dir),
+        _ => app
+            .path()
+            .app_config_dir()
+            .map_err(|e| format!("Failed to resolve app config dir: {e}").into()),
+    }
+}
+
+/// Whether a file in the data dir belongs to the live database, which is
+/// copied separately.
+fn is_database_file(name: &str) -> bool {
+    name.starts_with(db::DB_FILE)
+}
+
+fn copy_dir(from: &Path, to: &Path, top: bool) -> CommandResult<()> {
+    fs::create_dir_all(to).with_context(|| format!("Failed to create {}", to.display()))?;
+    let entries =
+        fs::read_dir(from).with_context(|| format!("Failed to read {}", from.display()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        if top && (is_database_file(&name_str) || name_str == LOCATION_FILE) {
+            continue;
+        }
+        let (source, dest) = (entry.path(), to.join(&name));
+        if source.is_dir() {
+            copy_dir(&source, &dest, false)?;
+        } else {
+            fs::copy(&source, &dest)
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove what was left behind by a move, once the app has started from
+/// the new place. Call before the database is opened.
+pub fn finish_move(app: &AppHandle) {
+    let Ok(default) = default_dir(app) else {
+        return;
+    };
+    let Some(mut location) = read_location(&default) else {
+        return;
+    };
+    let Some(previous) = location.previous.take() else {
+        return;
+    };
+    if !location.data_dir.join(db::DB_FILE).exists() {
+        tracing::warn!(
+            "{} has no database; keeping {}",
+            location.data_dir.display(),
+            previous.display()
+        );
+        return;
+    }
+    if let Ok(entries) = fs::read_dir(&previous) {
+        for entry in entries.flatten() {
+            if previous == default && entry.file_name() == LOCATION_FILE {
+                continue;
+            }
+            let path = entry.path();
+            let removed = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            if let Err(e) = removed {
+                tracing::warn!("failed to remove {}: {e}", path.display());
+            }
+        }
+    }
+    if previous != default {
+        let _ = fs::remove_dir(&previous);
+    }
+    if location.data_dir == default {
+        let _ = fs::remove_file(default.join(LOCATION_FILE));
+        return;
+    }
+    match serde_json::to_vec_pretty(&location) {
+        Ok(body) => {
+            if let Err(e) = write_atomic(&default.join(LOCATION_FILE), &body) {
+                tracing::warn!("{e}");
+            }
+        }
+        Err(e) => tracing::warn!("{e}"),
+    }
+}
+
+#[tauri::command]
+pub fn get_data_dir(app: AppHandle) -> CommandResult<DataDirInfo> {
+    let (dir, source) = resolve(&app)?;
+    Ok(DataDirInfo {
+        path: dir.to_string_lossy().into_owned(),
+        source,
+        default_path: default_dir(&app)?.to_string_lossy().into_owned(),
+    })
+}
+
+/// Copy everything to `target` and restart from there. The old copy is
+/// only deleted once the app has started from the new place.
+#[tauri::command]
+pub fn migrate_data_dir(app: AppHandle, db: State<'_, Db>, target: String) -> CommandResult<()> {
+    let (current, source) = resolve(&app)?;
+    match source {
+        DataDirSource::Flag => {
+            return Err(CommandError::invalid(format!(
+                "The data folder is set with {DATA_DIR_FLAG}"
+            )));
+        }
+        DataDirSource::Portable => {
+            return Err(CommandError::invalid(format!(
+                "The data folder is set by {PORTABLE_MARKER}"
+            )));
+        }
+        DataDirSource::Default | DataDirSource::Moved => {}
+    }
+    let target = PathBuf::from(target.trim());
+    if !target.is_absolute() {
+        return Err(CommandError::invalid(
+            "Choose a full path for the data folder",
+        ));
+    }
+    if target.starts_with(&current) || current.starts_with(&target) {
+        return Err(CommandError::invalid(
+            "The new data folder can't be inside the current one, or hold it",
+        ));
+    }
+    // Moving back to the default dir finds only the location file there.
+    let occupied = fs::read_dir(&target).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| entry.file_name() != LOCATION_FILE)
+    });
+    if occupied {
+        return Err(CommandError::invalid(format!(
+            "{} isn't empty",
+            target.display()
+        )));
+    }
+
+    fs::create_dir_all(&target)
+        .with_context(|| format!("Failed to create {}", target.display()))?;
+    let copied = copy_dir(&current, &target, true).and_then(|()| {
+        let key = db.key();
+        db.with_conn(|conn| {
+            Ok(db::export_copy(
+                conn,
+                &target.join(db::DB_FILE),
+                key.as_deref(),
+            ))
+        })?
+    });
+    if let Err(e) = copied {
+        let _ = fs::remove_dir_all(&target);
+        return Err(e);
+    }
+
+    let default = default_dir(&app)?;
+    fs::create_dir_all(&default).context("Failed to create app data dir")?;
+    let location = LocationFile {
+        data_dir: target,
+        previous: Some(current),
+    };
+    let body = serde_json::to_vec_pretty(&location).map_err(|e| e.to_string())?;
+    write_atomic(&default.join(LOCATION_FILE), &body)?;
+    app.restart()
+}
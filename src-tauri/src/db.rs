@@ -0,0 +1,336 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+use tauri::AppHandle;
+
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::{crdt, data_dir, journal, migrations};
+
+pub const DB_FILE: &str = "daylight.db";
+
+/// Backend-owned SQLite database. A single connection behind a mutex is
+/// plenty for a personal task list and keeps every write serialized.
+pub struct Db {
+    shared: Arc<Shared>,
+    /// Whether each `with_conn` call's changes become an undo step.
+    journaled: bool,
+}
+
+struct Shared {
+    slot: Mutex<Slot>,
+    path: PathBuf,
+    /// Told about each call's changes once the connection is free again.
+    listener: OnceLock<Listener>,
+}
+
+type Listener = Box<dyn Fn(&[journal::Change]) + Send + Sync>;
+
+/// The open connection and the key it was opened with. `conn` is `None`
+/// while an encrypted database waits for its passphrase.
+struct Slot {
+    conn: Option<Connection>,
+    key: Option<String>,
+}
+
+impl Slot {
+    /// Close the connection so its WAL is checkpointed into the main file.
+    /// On failure the connection is put back untouched.
+    fn close(&mut self, path: &Path) -> CommandResult<()> {
+        let Some(conn) = self.conn.take() else {
+            return Err(CommandError::locked());
+        };
+        if let Err((conn, e)) = conn.close() {
+            self.conn = Some(conn);
+            return Err(CommandError::from(e).context("Failed to close database"));
+        }
+        for suffix in ["-wal", "-shm"] {
+            let mut side = path.to_path_buf().into_os_string();
+            side.push(suffix);
+            let _ = fs::remove_file(side);
+        }
+        Ok(())
+    }
+}
+
+/// Whether `path` holds an encrypted database. Plain SQLite files start with
+/// a fixed header; SQLCipher files are indistinguishable from random bytes.
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => &header != b"SQLite format 3\0",
+        Err(_) => false,
+    }
+}
+
+/// Key a freshly opened connection and check the key actually decrypts it.
+/// A no-op for unencrypted databases.
+pub fn apply_key(conn: &Connection, key: Option<&str>) -> CommandResult<()> {
+    let Some(key) = key else {
+        return Ok(());
+    };
+    conn.pragma_update(None, "key", key)
+        .context("Failed to set database key")?;
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .map(|_| ())
+    .map_err(|_| CommandError::invalid("Wrong passphrase, or not a DayLight database"))
+}
+
+/// Write a full copy of `conn` to `dest`, encrypted with `key` or plain when
+/// `key` is `None`. Unlike `VACUUM INTO`, the copy can use a different key.
+pub fn export_copy(conn: &Connection, dest: &Path, key: Option<&str>) -> CommandResult<()> {
+    let _ = fs::remove_file(dest);
+    conn.execute(
+        "ATTACH DATABASE ?1 AS export KEY ?2",
+        params![dest.display().to_string(), key.unwrap_or("")],
+    )
+    .with_context(|| format!("Failed to create {}", dest.display()))?;
+    let exported = conn
+        .query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()))
+        .context("Failed to copy database");
+    let _ = conn.execute_batch("DETACH DATABASE export");
+    if exported.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    exported
+}
+
+/// Change the key of a database file that isn't open elsewhere, the way
+/// `Db::rekey` does for the live one.
+pub fn rekey_file(path: &Path, key: Option<&str>, new_key: Option<&str>) -> CommandResult<()> {
+    let conn = Connection::open(path).context("Failed to open database")?;
+    apply_key(&conn, key)?;
+    match (key, new_key) {
+        (None, None) => return Ok(()),
+        (Some(_), Some(new)) => {
+            return conn
+                .pragma_update(None, "rekey", new)
+                .context("Failed to change passphrase");
+        }
+        _ => {}
+    }
+
+    let mut staged = path.to_path_buf().into_os_string();
+    staged.push(".rekey");
+    let staged = PathBuf::from(staged);
+    export_copy(&conn, &staged, new_key)?;
+    drop(conn);
+    fs::rename(&staged, path).map_err(|e| {
+        let _ = fs::remove_file(&staged);
+        CommandError::from(e).context("Failed to replace database")
+    })
+}
+
+/// Open `path` with the app's pragmas and bring its schema up to date.
+fn connect(path: &Path, key: Option<&str>) -> CommandResult<Connection> {
+    let mut conn = Connection::open(path).context("Failed to open database")?;
+    apply_key(&conn, key)?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA foreign_keys = ON;
+         PRAGMA busy_timeout = 5000;",
+    )
+    .context("Failed to configure database")?;
+    migrations::run_migrations(&mut conn)?;
+    journal::install(&conn).context("Failed to set up undo journal")?;
+    crdt::install(&conn).context("Failed to set up merge clocks")?;
+    Ok(conn)
+}
+
+impl Db {
+    /// Open the database at `path`. An encrypted file is left locked until
+    /// `unlock` is called with its passphrase.
+    pub fn open(path: &Path) -> CommandResult<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create database dir")?;
+        }
+
+        let conn = if is_encrypted_file(path) {
+            None
+        } else {
+            Some(connect(path, None)?)
+        };
+
+        Ok(Self {
+            shared: Arc::new(Shared {
+                slot: Mutex::new(Slot { conn, key: None }),
+                path: path.to_path_buf(),
+                listener: OnceLock::new(),
+            }),
+            journaled: true,
+        })
+    }
+
+    /// The same database for background jobs such as syncs, imports and
+    /// purges: what they change isn't an undo step, so Ctrl+Z only ever
+    /// reverts the user's own edits. Listeners still hear about it.
+    pub fn unjournaled(&self) -> Db {
+        Db {
+            shared: Arc::clone(&self.shared),
+            journaled: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.shared.path
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.shared
+            .slot
+            .lock()
+            .map(|s| s.conn.is_none())
+            .unwrap_or(true)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.shared
+            .slot
+            .lock()
+            .map(|s| s.key.is_some())
+            .unwrap_or(false)
+            || is_encrypted_file(&self.shared.path)
+    }
+
+    /// The key the database is open with, if it's encrypted.
+    pub fn key(&self) -> Option<String> {
+        self.shared.slot.lock().ok().and_then(|s| s.key.clone())
+    }
+
+    /// Open a locked database with `key`. Fails without changing anything if
+    /// the key is wrong.
+    pub fn unlock(&self, key: &str) -> CommandResult<()> {
+        let mut slot = self.shared.slot.lock().map_err(|_| "Lock poisoned")?;
+        if slot.conn.is_some() {
+            return Ok(());
+        }
+        slot.conn = Some(connect(&self.shared.path, Some(key))?);
+        slot.key = Some(key.to_string());
+        Ok(())
+    }
+
+    /// Change the encryption key. `None` decrypts the database; encrypting or
+    /// decrypting rewrites the file, changing an existing key is done in place.
+    pub fn rekey(&self, new_key: Option<&str>) -> CommandResult<()> {
+        let mut slot = self.shared.slot.lock().map_err(|_| "Lock poisoned")?;
+        let slot = &mut *slot;
+        let Some(conn) = slot.conn.as_ref() else {
+            return Err(CommandError::locked());
+        };
+        match (slot.key.as_deref(), new_key) {
+            (None, None) => return Ok(()),
+            (Some(_), Some(new)) => {
+                conn.pragma_update(None, "rekey", new)
+                    .context("Failed to change passphrase")?;
+                slot.key = Some(new.to_string());
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let mut staged = self.shared.path.clone().into_os_string();
+        staged.push(".rekey");
+        let staged = PathBuf::from(staged);
+        export_copy(conn, &staged, new_key)?;
+        if let Err(e) = slot.close(&self.shared.path) {
+            let _ = fs::remove_file(&staged);
+            return Err(e);
+        }
+        let swapped = fs::rename(&staged, &self.shared.path).context("Failed to replace database");
+        if swapped.is_ok() {
+            slot.key = new_key.map(str::to_string);
+        } else {
+            let _ = fs::remove_file(&staged);
+        }
+        slot.conn = Some(connect(&self.shared.path, slot.key.as_deref())?);
+        swapped
+    }
+
+    /// Close the connection, checkpointing its WAL into the main file, before
+    /// the process goes away. From then on the database reads as locked.
+    pub fn close(&self) -> CommandResult<()> {
+        let mut slot = self.shared.slot.lock().map_err(|_| "Lock poisoned")?;
+        if slot.conn.is_none() {
+            return Ok(());
+        }
+        slot.close(&self.shared.path)
+    }
+
+    /// Swap the database file for `staged` while holding the lock, moving
+    /// the current file to `keep_as`. `staged` must use the current key.
+    pub fn replace_file(&self, staged: &Path, keep_as: &Path) -> CommandResult<()> {
+        let mut slot = self.shared.slot.lock().map_err(|_| "Lock poisoned")?;
+        slot.close(&self.shared.path)?;
+
+        let swapped = fs::rename(&self.shared.path, keep_as)
+            .context("Failed to move the current database aside")
+            .and_then(|_| {
+                fs::rename(staged, &self.shared.path).map_err(|e| {
+                    let _ = fs::rename(keep_as, &self.shared.path);
+                    CommandError::from(e).context("Failed to replace database")
+                })
+            });
+        slot.conn = Some(connect(&self.shared.path, slot.key.as_deref())?);
+        swapped
+    }
+
+    /// Run `f` with exclusive access to the connection. Whatever `f` changed
+    /// becomes one undo step, unless this handle is `unjournaled`.
+    pub fn with_conn<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+    ) -> CommandResult<T> {
+        let mut slot = self.shared.slot.lock().map_err(|_| "Lock poisoned")?;
+        let conn = slot.conn.as_mut().ok_or_else(CommandError::locked)?;
+        let result = f(conn).map_err(CommandError::from);
+        let changes = if self.journaled {
+            journal::seal(conn)
+        } else {
+            journal::discard(conn)
+        }
+        .unwrap_or_else(|e| {
+            tracing::warn!("failed to record undo step: {e}");
+            Vec::new()
+        });
+        drop(slot);
+        if let Some(listener) = self.shared.listener.get().filter(|_| !changes.is_empty()) {
+            listener(&changes);
+        }
+        result
+    }
+
+    /// Call `listener` with the rows each `with_conn` call changed. It may
+    /// use the database itself. Only the first listener set is kept.
+    pub fn set_listener(&self, listener: impl Fn(&[journal::Change]) + Send + Sync + 'static) {
+        let _ = self.shared.listener.set(Box::new(listener));
+    }
+}
+
+/// Location of the database file under the app data dir.
+pub fn db_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(DB_FILE))
+}
+
+/// Current time as an RFC 3339 UTC timestamp, the format used for every
+/// `*_at` column.
+pub fn now_utc() -> String {
+    format_utc(Utc::now())
+}
+
+/// Canonical timestamp format: UTC, millisecond precision, `Z` suffix. Keeping
+/// one format means timestamp columns sort and compare correctly as text.
+pub fn format_utc(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Parse any RFC 3339 timestamp (with offset) into UTC.
+pub fn parse_utc(value: &str) -> CommandResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| CommandError::invalid(format!("Invalid timestamp '{value}': {e}")))
+}
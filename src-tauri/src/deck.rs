@@ -0,0 +1,1371 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client, Method, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, State};
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::caldav::{self, CaldavAccount, PROVIDER_NEXTCLOUD};
+use crate::conflicts::{self, ConflictPolicy};
+use crate::credentials;
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::nextcloud;
+use crate::outbox::{self, Replay};
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{
+    self, Capabilities, RemoteCollection, SyncFuture, SyncMode, SyncProvider,
+};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::tags;
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+
+/// `external_refs.source` for tasks that came from Deck.
+const SOURCE: &str = "deck";
+
+/// Where the Deck API lives, under the Nextcloud base URL.
+const API_PATH: &str = "index.php/apps/deck/api/v1.0/";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Color of labels made for tags Deck doesn't have yet.
+const LABEL_COLOR: &str = "31CC7C";
+/// Puts new cards at the bottom of their stack.
+const NEW_CARD_ORDER: i64 = 999;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeckStack {
+    pub id: i64,
+    pub title: String,
+    pub position: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeckBoard {
+    pub id: String,
+    pub account_id: String,
+    pub title: String,
+    /// Local project the board's cards are filed under. New tasks in this
+    /// project are added to the board.
+    pub project: Option<String>,
+    pub enabled: bool,
+    /// The stack whose cards are done: completing a task moves its card
+    /// there. Defaults to the board's last stack.
+    pub done_stack: Option<i64>,
+    pub stacks: Vec<DeckStack>,
+    pub last_synced_at: Option<String>,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DeckBoardPatch {
+    pub enabled: Option<bool>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    pub done_stack: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeckSyncReport {
+    pub board_id: String,
+    pub title: String,
+    /// Tasks created or updated from Deck.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because their card was deleted in Deck.
+    pub removed: usize,
+    /// Tasks uploaded, new or changed.
+    pub uploaded: usize,
+    /// Cards deleted in Deck because their task was deleted here.
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for DeckSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.uploaded + self.deleted
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteLabel {
+    id: i64,
+    #[serde(default)]
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteBoard {
+    id: i64,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    archived: bool,
+    /// When it was deleted, or 0.
+    #[serde(default)]
+    deleted_at: i64,
+    #[serde(default)]
+    labels: Option<Vec<RemoteLabel>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteStack {
+    id: i64,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    order: i64,
+    #[serde(default)]
+    cards: Option<Vec<RemoteCard>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteCard {
+    id: i64,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    stack_id: i64,
+    #[serde(default)]
+    order: i64,
+    /// Unix seconds.
+    #[serde(default)]
+    last_modified: i64,
+    /// An ISO 8601 time with offset.
+    #[serde(default)]
+    duedate: Option<String>,
+    #[serde(default)]
+    labels: Option<Vec<RemoteLabel>>,
+    /// The user id, or an object holding it, depending on the Deck version.
+    #[serde(default)]
+    owner: serde_json::Value,
+}
+
+impl RemoteCard {
+    fn label_titles(&self) -> Vec<String> {
+        self.labels
+            .iter()
+            .flatten()
+            .map(|l| l.title.clone())
+            .filter(|t| !t.trim().is_empty())
+            .collect()
+    }
+
+    fn owner(&self) -> Option<String> {
+        match &self.owner {
+            serde_json::Value::String(uid) => Some(uid.clone()),
+            serde_json::Value::Object(owner) => owner
+                .get("uid")
+                .or_else(|| owner.get("primaryKey"))
+                .and_then(|uid| uid.as_str())
+                .map(str::to_string),
+            _ => None,
+        }
+    }
+}
+
+/// A card's due time as tasks keep it: a date when it falls at local
+/// midnight, a wall-clock time in `local` otherwise.
+fn from_deck_due(due: Option<&str>, local: &Tz) -> Option<String> {
+    let at = DateTime::parse_from_rfc3339(due?)
+        .ok()?
+        .with_timezone(local);
+    if at.time() == NaiveTime::MIN {
+        Some(at.date_naive().to_string())
+    } else {
+        Some(at.format("%Y-%m-%dT%H:%M").to_string())
+    }
+}
+
+/// The due time Deck takes for a task's, a date being local midnight.
+fn to_deck_due(due: Option<&str>, local: &Tz) -> Option<String> {
+    let due = due?;
+    let at = match NaiveDate::parse_from_str(due, "%Y-%m-%d") {
+        Ok(date) => date.and_time(NaiveTime::MIN),
+        Err(_) => NaiveDateTime::parse_from_str(due, "%Y-%m-%dT%H:%M").ok()?,
+    };
+    timezone::resolve_local(at, local).map(|at| at.with_timezone(&Utc).to_rfc3339())
+}
+
+fn row_to_board(row: &Row) -> rusqlite::Result<DeckBoard> {
+    Ok(DeckBoard {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        title: row.get(2)?,
+        project: row.get(3)?,
+        enabled: row.get(4)?,
+        done_stack: row.get(5)?,
+        last_synced_at: row.get(6)?,
+        stacks: Vec::new(),
+    })
+}
+
+const BOARD_COLUMNS: &str = "id, account_id, title, project, enabled, done_stack, last_synced_at";
+
+fn load_stacks(conn: &Connection, board_id: &str) -> rusqlite::Result<Vec<DeckStack>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, title, position FROM deck_stacks
+         WHERE board_id = ?1 ORDER BY position, remote_id",
+    )?;
+    let rows = stmt.query_map(params![board_id], |row| {
+        Ok(DeckStack {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            position: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn list_boards(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<DeckBoard>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {BOARD_COLUMNS} FROM deck_boards
+         WHERE account_id = ?1 ORDER BY title COLLATE NOCASE"
+    ))?;
+    let mut boards = stmt
+        .query_map(params![account_id], row_to_board)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for board in &mut boards {
+        board.stacks = load_stacks(conn, &board.id)?;
+    }
+    Ok(boards)
+}
+
+fn find_board(conn: &Connection, id: &str) -> rusqlite::Result<Option<DeckBoard>> {
+    let board = conn
+        .query_row(
+            &format!("SELECT {BOARD_COLUMNS} FROM deck_boards WHERE id = ?1"),
+            params![id],
+            row_to_board,
+        )
+        .optional()?;
+    let Some(mut board) = board else {
+        return Ok(None);
+    };
+    board.stacks = load_stacks(conn, &board.id)?;
+    Ok(Some(board))
+}
+
+/// A connection to the Deck API of a Nextcloud account.
+struct Api {
+    client: Client,
+    base: Url,
+    username: String,
+    password: String,
+}
+
+impl Api {
+    fn connect(account: &CaldavAccount) -> CommandResult<Self> {
+        if account.provider.as_deref() != Some(PROVIDER_NEXTCLOUD) {
+            return Err(CommandError::invalid(
+                "Deck boards need an account set up as Nextcloud",
+            ));
+        }
+        let base = nextcloud::base_url(&account.server_url)?
+            .join(API_PATH)
+            .map_err(|e| e.to_string())?;
+        let builder = Client::builder().timeout(REQUEST_TIMEOUT);
+        let client = account.tls.apply(builder)?.build()?;
+        Ok(Self {
+            client,
+            base,
+            username: account.username.clone(),
+            password: credentials::require(&caldav::keyring_name(&account.id), "password")?,
+        })
+    }
+
+    /// List the boards, for the account health check.
+    async fn probe(&self) -> Result<Response, reqwest::Error> {
+        let url = self
+            .base
+            .join("boards")
+            .unwrap_or_else(|_| self.base.clone());
+        self.client
+            .get(url)
+            .basic_auth(&self.username, Some(&self.password))
+            .header("OCS-APIRequest", "true")
+            .header(ACCEPT, "application/json")
+            .send()
+            .await
+    }
+
+    /// Send a request to `path` under the API. Returns `None` on 404, for
+    /// a board or card that's gone.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        rate_limit::check(SOURCE, "Deck")?;
+        let url = self.base.join(path).map_err(|e| e.to_string())?;
+        let mut request = self
+            .client
+            .request(method.clone(), url)
+            .basic_auth(&self.username, Some(&self.password))
+            .header("OCS-APIRequest", "true")
+            .header(ACCEPT, "application/json");
+        if let Some(body) = body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await.context("Deck")?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::UNAUTHORIZED => Err(CommandError::unauthorized(
+                "Nextcloud rejected the username or password",
+            )
+            .with_provider(SOURCE)),
+            StatusCode::TOO_MANY_REQUESTS => {
+                Err(rate_limit::limited(SOURCE, "Deck", response.headers()))
+            }
+            status if !status.is_success() => Err(CommandError::status(
+                status,
+                format!("Deck: {method} {path}: HTTP {}", status.as_u16()),
+            )
+            .with_provider(SOURCE)),
+            _ => {
+                let text = response.text().await?;
+                serde_json::from_str(&text)
+                    .map(Some)
+                    .map_err(|e| format!("Deck: {e}").into())
+            }
+        }
+    }
+}
+
+/// Save the boards found on an account. Boards Deck no longer has, or that
+/// were archived, are dropped along with their sync state; their tasks
+/// stay.
+fn store_boards(
+    conn: &Connection,
+    account_id: &str,
+    found: &[RemoteBoard],
+) -> rusqlite::Result<()> {
+    let found: Vec<&RemoteBoard> = found
+        .iter()
+        .filter(|b| !b.archived && b.deleted_at == 0)
+        .collect();
+    for board in &found {
+        conn.execute(
+            "INSERT INTO deck_boards (id, account_id, remote_id, title)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(account_id, remote_id) DO UPDATE SET title = excluded.title",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                account_id,
+                board.id,
+                board.title
+            ],
+        )?;
+    }
+    let ids: Vec<i64> = found.iter().map(|b| b.id).collect();
+    let ids = serde_json::to_string(&ids).unwrap_or_default();
+    conn.execute(
+        "DELETE FROM deck_boards
+         WHERE account_id = ?1 AND remote_id NOT IN (SELECT value FROM json_each(?2))",
+        params![account_id, ids],
+    )?;
+    Ok(())
+}
+
+/// Save the board's stacks, and pick its last stack as the done one when
+/// none is set or the one set is gone.
+fn store_stacks(
+    conn: &Connection,
+    board: &mut DeckBoard,
+    found: &[RemoteStack],
+) -> rusqlite::Result<()> {
+    for stack in found {
+        conn.execute(
+            "INSERT INTO deck_stacks (board_id, remote_id, title, position)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(board_id, remote_id) DO UPDATE SET
+                 title = excluded.title,
+                 position = excluded.position",
+            params![board.id, stack.id, stack.title, stack.order],
+        )?;
+    }
+    let ids: Vec<i64> = found.iter().map(|s| s.id).collect();
+    conn.execute(
+        "DELETE FROM deck_stacks
+         WHERE board_id = ?1 AND remote_id NOT IN (SELECT value FROM json_each(?2))",
+        params![board.id, serde_json::to_string(&ids).unwrap_or_default()],
+    )?;
+    board.stacks = load_stacks(conn, &board.id)?;
+    if !board.stacks.iter().any(|s| Some(s.id) == board.done_stack) {
+        board.done_stack = board.stacks.last().map(|s| s.id);
+        conn.execute(
+            "UPDATE deck_boards SET done_stack = ?2 WHERE id = ?1",
+            params![board.id, board.done_stack],
+        )?;
+    }
+    Ok(())
+}
+
+/// The first stack that isn't the done one, where reopened and new tasks
+/// go.
+fn open_stack(board: &DeckBoard) -> Option<i64> {
+    board
+        .stacks
+        .iter()
+        .find(|s| Some(s.id) != board.done_stack)
+        .map(|s| s.id)
+}
+
+/// What the last sync knew about one card.
+#[derive(Debug, Clone)]
+struct CardRow {
+    task_id: String,
+    stack_id: i64,
+    last_modified: i64,
+    /// The task's `updated_at` when it last matched Deck.
+    synced_at: String,
+}
+
+fn load_cards(conn: &Connection, board_id: &str) -> rusqlite::Result<HashMap<i64, CardRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, task_id, stack_id, last_modified, synced_at FROM deck_cards
+         WHERE board_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![board_id], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            CardRow {
+                task_id: row.get(1)?,
+                stack_id: row.get(2)?,
+                last_modified: row.get(3)?,
+                synced_at: row.get(4)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_card(
+    conn: &Connection,
+    board_id: &str,
+    remote_id: i64,
+    card: &CardRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO deck_cards (board_id, remote_id, task_id, stack_id, last_modified, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(board_id, remote_id) DO UPDATE SET
+             task_id = excluded.task_id,
+             stack_id = excluded.stack_id,
+             last_modified = excluded.last_modified,
+             synced_at = excluded.synced_at",
+        params![
+            board_id,
+            remote_id,
+            card.task_id,
+            card.stack_id,
+            card.last_modified,
+            card.synced_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_card(conn: &Connection, board_id: &str, remote_id: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM deck_cards WHERE board_id = ?1 AND remote_id = ?2",
+        params![board_id, remote_id],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = Some(now_utc());
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// Create or update the task for `card`, done when it's in `done_stack`.
+/// Unless `policy` says remote wins, a field edited here after Deck last
+/// changed the card keeps the local value. Returns the task and whether it
+/// was created, or `None` when nothing changed.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    card: &RemoteCard,
+    done: bool,
+    project: Option<&str>,
+    local: &Tz,
+    policy: ConflictPolicy,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let title = card.title.trim();
+    let title = if title.is_empty() { "Untitled" } else { title }.to_string();
+    let description = card.description.clone().filter(|d| !d.trim().is_empty());
+    let due = from_deck_due(card.duedate.as_deref(), local);
+    let labels = tags::normalize_names(&card.label_titles()).unwrap_or_default();
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description,
+            project: project.map(str::to_string),
+            priority: None,
+            due,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if done {
+            set_done(&mut task, true);
+            task_store::write_task(conn, &task)?;
+        }
+        tags::set_task_tags(conn, &task.id, &labels)?;
+        task.tags = labels;
+        return Ok(Some((task, true)));
+    };
+
+    let modified = card.last_modified * 1000;
+    let take = conflicts::remote_fields(conn, &task.id, Some(modified), policy)?;
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("description") {
+        task.description = description;
+    }
+    if take("due") {
+        task.due = due;
+    }
+    if take("status") && done != (task.status == STATUS_DONE) {
+        set_done(&mut task, done);
+    }
+    let mut have: Vec<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+    let mut want: Vec<String> = labels.iter().map(|t| t.to_lowercase()).collect();
+    have.sort();
+    want.sort();
+    let tags_changed = take("tags") && have != want;
+
+    let changed = task.title != before.title
+        || task.description != before.description
+        || task.due != before.due
+        || task.status != before.status;
+    if !changed && !tags_changed {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    if tags_changed {
+        tags::set_task_tags(conn, &task.id, &labels)?;
+        task.tags = labels;
+    }
+    Ok(Some((task, false)))
+}
+
+/// A change to send to Deck.
+#[derive(Debug)]
+enum Upload {
+    Insert {
+        stack_id: i64,
+        task: Task,
+    },
+    Update {
+        remote_id: i64,
+        card: RemoteCard,
+        task: Task,
+        /// The stack to move the card to, when its status changed here.
+        move_to: Option<i64>,
+    },
+    Delete {
+        remote_id: i64,
+        stack_id: i64,
+        task_id: String,
+    },
+}
+
+impl Upload {
+    fn task_id(&self) -> &str {
+        match self {
+            Upload::Insert { task, .. } | Upload::Update { task, .. } => &task.id,
+            Upload::Delete { task_id, .. } => task_id,
+        }
+    }
+}
+
+/// Apply the cards on the board, unless only exporting, then work out which
+/// tasks to upload. `archived` are the ids of archived cards, whose tasks
+/// are marked done and unlinked; linked cards in neither list were deleted
+/// in Deck.
+fn merge_remote(
+    conn: &mut Connection,
+    board: &DeckBoard,
+    cards: &[RemoteCard],
+    archived: &HashSet<i64>,
+    local: &Tz,
+    imports: bool,
+    report: &mut DeckSyncReport,
+) -> rusqlite::Result<Vec<Upload>> {
+    let tx = conn.transaction()?;
+    let known = load_cards(&tx, &board.id)?;
+    let policy = conflicts::policy(&tx, SOURCE)?;
+
+    for card in cards {
+        let row = known.get(&card.id);
+        if row.is_some_and(|r| r.last_modified == card.last_modified && r.stack_id == card.stack_id)
+        {
+            continue;
+        }
+        let task_id = match row {
+            Some(row) => Some(row.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, card.id.to_string()],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if row.is_some() && existing.is_none() {
+            // Deleted here; the delete is uploaded below.
+            continue;
+        }
+        if !imports {
+            // Only where the card is and when it changed are taken, so
+            // local edits go up over it.
+            if let Some(row) = row {
+                let row = CardRow {
+                    stack_id: card.stack_id,
+                    last_modified: card.last_modified,
+                    ..row.clone()
+                };
+                save_card(&tx, &board.id, card.id, &row)?;
+            }
+            continue;
+        }
+        let dirty = match (row, &existing) {
+            (Some(row), Some(task)) if task.updated_at > row.synced_at => {
+                Some(row.synced_at.clone())
+            }
+            _ => None,
+        };
+        let done = Some(card.stack_id) == board.done_stack;
+        let project = board.project.as_deref();
+        let written = match existing {
+            Some(existing)
+                if dirty.is_some()
+                    && conflicts::keep_local(&tx, SOURCE, policy, &existing, |tx| {
+                        let forced = ConflictPolicy::Remote;
+                        let written = write_remote(
+                            tx,
+                            Some(existing.clone()),
+                            card,
+                            done,
+                            project,
+                            local,
+                            forced,
+                        )?;
+                        Ok(written.map_or_else(|| existing.clone(), |(task, _)| task))
+                    })? =>
+            {
+                None
+            }
+            existing => write_remote(&tx, existing, card, done, project, local, policy)?,
+        };
+        let task_id = match written {
+            Some((task, true)) => {
+                report.created += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, card.id.to_string(), task.id, now_utc()],
+                )?;
+                task.id
+            }
+            Some((task, false)) => {
+                report.updated += 1;
+                task.id
+            }
+            None => task_id.unwrap_or_default(),
+        };
+        let Some(task) = task_store::find_task(&tx, &task_id)? else {
+            continue;
+        };
+        let row = CardRow {
+            task_id: task.id,
+            stack_id: card.stack_id,
+            last_modified: card.last_modified,
+            // Local edits Deck doesn't have yet are kept dirty.
+            synced_at: dirty
+                .filter(|_| policy != ConflictPolicy::Remote)
+                .unwrap_or(task.updated_at),
+        };
+        save_card(&tx, &board.id, card.id, &row)?;
+    }
+
+    let listed: HashSet<i64> = cards.iter().map(|c| c.id).collect();
+    for (remote_id, row) in &known {
+        if listed.contains(remote_id) {
+            continue;
+        }
+        if imports && archived.contains(remote_id) {
+            if let Some(mut task) = task_store::find_task(&tx, &row.task_id)? {
+                if task.status != STATUS_DONE {
+                    set_done(&mut task, true);
+                    task.updated_at = now_utc();
+                    task_store::write_task(&tx, &task)?;
+                    report.updated += 1;
+                }
+            }
+        } else if imports && trash::trash_task(&tx, &row.task_id)? {
+            report.removed += 1;
+        }
+        forget_card(&tx, &board.id, *remote_id)?;
+    }
+
+    let by_id: HashMap<i64, &RemoteCard> = cards.iter().map(|c| (c.id, c)).collect();
+    let held = conflicts::held(&tx, SOURCE)?;
+    let mut uploads = Vec::new();
+    for (remote_id, row) in load_cards(&tx, &board.id)? {
+        let Some(card) = by_id.get(&remote_id) else {
+            continue;
+        };
+        match task_store::find_task(&tx, &row.task_id)? {
+            None => uploads.push(Upload::Delete {
+                remote_id,
+                stack_id: card.stack_id,
+                task_id: row.task_id,
+            }),
+            Some(task) if task.updated_at > row.synced_at && !held.contains(&task.id) => {
+                let done = task.status == STATUS_DONE;
+                let move_to = if done == (Some(card.stack_id) == board.done_stack) {
+                    None
+                } else if done {
+                    board.done_stack
+                } else {
+                    open_stack(board)
+                };
+                uploads.push(Upload::Update {
+                    remote_id,
+                    card: (*card).clone(),
+                    task,
+                    move_to,
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Deck has no subtasks, so only top-level tasks go up.
+    if let (Some(project), Some(stack_id)) = (&board.project, open_stack(board)) {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM tasks
+             WHERE project = ?1 COLLATE NOCASE AND deleted_at IS NULL AND parent_id IS NULL
+               AND status = ?2 AND id NOT IN (SELECT task_id FROM deck_cards)
+             ORDER BY created_at",
+        )?;
+        let ids = stmt
+            .query_map(params![project, STATUS_OPEN], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        for id in ids {
+            if let Some(task) = task_store::find_task(&tx, &id)? {
+                uploads.push(Upload::Insert { stack_id, task });
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(uploads)
+}
+
+/// What came of one upload.
+enum Uploaded {
+    Saved { remote_id: i64, card: CardRow },
+    Deleted { remote_id: i64 },
+}
+
+/// The card fields for a task. Deck wants the owner on every update.
+fn card_body(task: &Task, owner: &str, order: i64, local: &Tz) -> serde_json::Value {
+    json!({
+        "title": task.title,
+        "type": "plain",
+        "owner": owner,
+        "order": order,
+        "description": task.description.clone().unwrap_or_default(),
+        "duedate": to_deck_due(task.due.as_deref(), local),
+    })
+}
+
+/// Uploads for one board, with its labels as they're added to.
+struct Uploader<'a> {
+    api: &'a Api,
+    board: i64,
+    labels: Vec<RemoteLabel>,
+    owner: String,
+    local: Tz,
+}
+
+impl Uploader<'_> {
+    fn card_path(&self, stack_id: i64, card_id: i64) -> String {
+        format!("boards/{}/stacks/{stack_id}/cards/{card_id}", self.board)
+    }
+
+    /// The board's label titled `name`, made when it has none.
+    async fn label(&mut self, name: &str) -> CommandResult<i64> {
+        if let Some(label) = self
+            .labels
+            .iter()
+            .find(|l| l.title.eq_ignore_ascii_case(name))
+        {
+            return Ok(label.id);
+        }
+        let body = json!({ "title": name, "color": LABEL_COLOR });
+        let path = format!("boards/{}/labels", self.board);
+        let created: Option<RemoteLabel> = self.api.send(Method::POST, &path, Some(&body)).await?;
+        let created = created.ok_or_else(|| CommandError::not_found("Deck board not found"))?;
+        let id = created.id;
+        self.labels.push(created);
+        Ok(id)
+    }
+
+    /// Bring the card's labels in line with the task's tags.
+    async fn set_labels(
+        &mut self,
+        stack_id: i64,
+        card_id: i64,
+        have: &[String],
+        want: &[String],
+    ) -> CommandResult<()> {
+        let path = self.card_path(stack_id, card_id);
+        for name in want {
+            if !have.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+                let body = json!({ "labelId": self.label(name).await? });
+                self.api
+                    .send::<serde_json::Value>(
+                        Method::PUT,
+                        &format!("{path}/assignLabel"),
+                        Some(&body),
+                    )
+                    .await?;
+            }
+        }
+        for name in have {
+            if want.iter().any(|w| w.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            let Some(label) = self
+                .labels
+                .iter()
+                .find(|l| l.title.eq_ignore_ascii_case(name))
+            else {
+                continue;
+            };
+            let body = json!({ "labelId": label.id });
+            self.api
+                .send::<serde_json::Value>(Method::PUT, &format!("{path}/removeLabel"), Some(&body))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn upload(&mut self, change: Upload) -> CommandResult<Uploaded> {
+        match change {
+            Upload::Insert { stack_id, task } => {
+                let body = card_body(&task, &self.owner, NEW_CARD_ORDER, &self.local);
+                let path = format!("boards/{}/stacks/{stack_id}/cards", self.board);
+                let created: Option<RemoteCard> =
+                    self.api.send(Method::POST, &path, Some(&body)).await?;
+                let created =
+                    created.ok_or_else(|| CommandError::not_found("Deck stack not found"))?;
+                self.set_labels(stack_id, created.id, &[], &task.tags)
+                    .await?;
+                Ok(Uploaded::Saved {
+                    remote_id: created.id,
+                    card: CardRow {
+                        task_id: task.id,
+                        stack_id,
+                        last_modified: created.last_modified,
+                        synced_at: task.updated_at,
+                    },
+                })
+            }
+            Upload::Update {
+                remote_id,
+                card,
+                task,
+                move_to,
+            } => {
+                let owner = card.owner().unwrap_or_else(|| self.owner.clone());
+                let body = card_body(&task, &owner, card.order, &self.local);
+                let path = self.card_path(card.stack_id, remote_id);
+                let updated: Option<RemoteCard> =
+                    self.api.send(Method::PUT, &path, Some(&body)).await?;
+                // Deleted in Deck meanwhile. The link is dropped; the task
+                // is added again as new while it's in the board's project.
+                let Some(updated) = updated else {
+                    return Ok(Uploaded::Deleted { remote_id });
+                };
+                self.set_labels(card.stack_id, remote_id, &card.label_titles(), &task.tags)
+                    .await?;
+                let mut stack_id = card.stack_id;
+                if let Some(to) = move_to {
+                    let body = json!({ "order": card.order, "stackId": to });
+                    self.api
+                        .send::<serde_json::Value>(
+                            Method::PUT,
+                            &format!("{path}/reorder"),
+                            Some(&body),
+                        )
+                        .await?;
+                    stack_id = to;
+                }
+                Ok(Uploaded::Saved {
+                    remote_id,
+                    card: CardRow {
+                        task_id: task.id,
+                        stack_id,
+                        last_modified: updated.last_modified,
+                        synced_at: task.updated_at,
+                    },
+                })
+            }
+            Upload::Delete {
+                remote_id,
+                stack_id,
+                ..
+            } => {
+                let path = self.card_path(stack_id, remote_id);
+                self.api
+                    .send::<serde_json::Value>(Method::DELETE, &path, None)
+                    .await?;
+                Ok(Uploaded::Deleted { remote_id })
+            }
+        }
+    }
+}
+
+fn save_results(
+    conn: &mut Connection,
+    board: &DeckBoard,
+    results: &[Uploaded],
+    report: &mut DeckSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for result in results {
+        match result {
+            Uploaded::Saved { remote_id, card } => {
+                report.uploaded += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, remote_id.to_string(), card.task_id, now_utc()],
+                )?;
+                save_card(&tx, &board.id, *remote_id, card)?;
+            }
+            Uploaded::Deleted { remote_id } => {
+                report.deleted += 1;
+                forget_card(&tx, &board.id, *remote_id)?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+/// Two-way sync of one board: fetch its stacks and cards, merge them field
+/// by field, and when pushing upload local changes. Cards that changed
+/// since are the ones whose `lastModified` moved.
+async fn sync_board(
+    db: &Db,
+    api: &Api,
+    account: &CaldavAccount,
+    board: &DeckBoard,
+    mode: SyncMode,
+) -> CommandResult<DeckSyncReport> {
+    let mut report = DeckSyncReport {
+        board_id: board.id.clone(),
+        title: board.title.clone(),
+        ..DeckSyncReport::default()
+    };
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let remote_id: i64 = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT remote_id FROM deck_boards WHERE id = ?1",
+            params![board.id],
+            |row| row.get(0),
+        )
+    })?;
+    let found: Option<RemoteBoard> = api
+        .send(Method::GET, &format!("boards/{remote_id}"), None)
+        .await?;
+    let found = found.ok_or_else(|| CommandError::not_found("Board not found in Deck"))?;
+    let stacks: Vec<RemoteStack> = api
+        .send(Method::GET, &format!("boards/{remote_id}/stacks"), None)
+        .await?
+        .unwrap_or_default();
+    let archived: Vec<RemoteStack> = api
+        .send(
+            Method::GET,
+            &format!("boards/{remote_id}/stacks/archived"),
+            None,
+        )
+        .await?
+        .unwrap_or_default();
+    let cards: Vec<RemoteCard> = stacks
+        .iter()
+        .flat_map(|s| s.cards.iter().flatten().cloned())
+        .collect();
+    let archived: HashSet<i64> = archived
+        .iter()
+        .flat_map(|s| s.cards.iter().flatten().map(|c| c.id))
+        .collect();
+
+    let mut board = board.clone();
+    let uploads = db.with_conn(|conn| {
+        store_stacks(conn, &mut board, &stacks)?;
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(
+                conn,
+                &board,
+                &cards,
+                &archived,
+                &local,
+                mode.imports(),
+                &mut report,
+            )
+        })?
+    })?;
+
+    let mut uploader = Uploader {
+        api,
+        board: remote_id,
+        labels: found.labels.unwrap_or_default(),
+        owner: account.username.clone(),
+        local,
+    };
+    // Importing only leaves local changes here.
+    let uploads = if mode.exports() { uploads } else { Vec::new() };
+    let uploads = db.with_conn(|conn| outbox::in_order(conn, uploads, Upload::task_id))?;
+    let mut results = Vec::new();
+    let mut replay = Replay::default();
+    for change in uploads {
+        let task_id = change.task_id().to_string();
+        match uploader.upload(change).await {
+            Ok(result) => {
+                replay.delivered(task_id);
+                results.push(result);
+            }
+            Err(e) => {
+                replay.failed(task_id, &e);
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    db.with_conn(|conn| {
+        replay.save(conn)?;
+        save_results(conn, &board, &results, &mut report)?;
+        conn.execute(
+            "UPDATE deck_boards SET last_synced_at = ?2 WHERE id = ?1",
+            params![board.id, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+fn find_nextcloud_account(db: &Db, account_id: &str) -> CommandResult<CaldavAccount> {
+    db.with_conn(|conn| caldav::find_account(conn, account_id))?
+        .ok_or_else(|| {
+            CommandError::not_found(format!("Nextcloud account not found: {account_id}"))
+        })
+}
+
+#[tauri::command]
+pub fn list_deck_boards(db: State<'_, Db>, account_id: String) -> CommandResult<Vec<DeckBoard>> {
+    db.with_conn(|conn| list_boards(conn, &account_id))
+}
+
+async fn refresh_boards(db: &Db, account_id: &str) -> CommandResult<Vec<DeckBoard>> {
+    let account = find_nextcloud_account(db, account_id)?;
+    let api = Api::connect(&account)?;
+    let found: Vec<RemoteBoard> = api
+        .send(Method::GET, "boards", None)
+        .await?
+        .ok_or_else(|| CommandError::not_found("Deck isn't installed on this Nextcloud"))?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_boards(&tx, &account.id, &found)?;
+        tx.commit()?;
+        list_boards(conn, &account.id)
+    })
+}
+
+/// Look for boards added to or removed from Deck since.
+#[tauri::command]
+pub async fn refresh_deck_boards(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<DeckBoard>> {
+    refresh_boards(&db, &account_id).await
+}
+
+fn update_board(db: &Db, id: &str, patch: DeckBoardPatch) -> CommandResult<DeckBoard> {
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut board) = find_board(conn, id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Deck board not found: {id}"
+            ))));
+        };
+        if let Some(project) = project {
+            board.project = project;
+        }
+        if let Some(enabled) = patch.enabled {
+            board.enabled = enabled;
+        }
+        if let Some(stack) = patch.done_stack {
+            if !board.stacks.iter().any(|s| s.id == stack) {
+                return Ok(Err(CommandError::not_found(format!(
+                    "Stack not found on this board: {stack}"
+                ))));
+            }
+            board.done_stack = Some(stack);
+        }
+        if board.enabled && board.project.is_none() {
+            board.project = Some(board.title.clone());
+        }
+        conn.execute(
+            "UPDATE deck_boards SET project = ?2, enabled = ?3, done_stack = ?4 WHERE id = ?1",
+            params![board.id, board.project, board.enabled, board.done_stack],
+        )?;
+        Ok(Ok(board))
+    })?
+}
+
+/// Turn syncing of a board on or off, or change its project or done
+/// stack. A board is enabled with a project named after it unless one is
+/// given.
+#[tauri::command]
+pub fn update_deck_board(
+    db: State<'_, Db>,
+    id: String,
+    patch: DeckBoardPatch,
+) -> CommandResult<DeckBoard> {
+    update_board(&db, &id, patch)
+}
+
+pub struct Deck;
+
+impl SyncProvider for Deck {
+    type Report = Vec<DeckSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Deck"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: true,
+            conflicts: true,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for id in db.with_conn(|conn| synced_accounts(conn))? {
+                if account_id.is_none_or(|only| only == id) {
+                    Api::connect(&find_nextcloud_account(db, &id)?)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let ids = match account_id {
+                Some(id) => vec![id.to_string()],
+                None => db.with_conn(|conn| board_accounts(conn))?,
+            };
+            let mut found = Vec::new();
+            for id in ids {
+                let account = find_nextcloud_account(db, &id)?;
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                match Api::connect(&account) {
+                    Ok(api) => {
+                        health.answer("Deck", api.probe().await);
+                    }
+                    Err(e) => health.no_credentials(&e),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Import))
+    }
+
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::TwoWay))
+    }
+
+    fn export<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Export))
+    }
+
+    /// Boards of `account_id`, or of every Nextcloud account Deck was
+    /// looked up on.
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move {
+            let accounts = match account_id {
+                Some(id) => vec![id.to_string()],
+                None => db.with_conn(|conn| board_accounts(conn))?,
+            };
+            let mut found = Vec::new();
+            for id in accounts {
+                let boards = match refresh_boards(db, &id).await {
+                    Ok(boards) => boards,
+                    Err(e) => {
+                        tracing::warn!("refresh of boards failed: {e}");
+                        db.with_conn(|conn| list_boards(conn, &id))?
+                    }
+                };
+                found.extend(boards.into_iter().map(|b| RemoteCollection {
+                    id: b.id,
+                    account_id: b.account_id,
+                    name: b.title,
+                    selected: b.enabled,
+                }));
+            }
+            Ok(found)
+        })
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        let patch = DeckBoardPatch {
+            enabled: Some(selected),
+            ..DeckBoardPatch::default()
+        };
+        update_board(db, id, patch).map(drop)
+    }
+}
+
+/// Sync every enabled board of `account_id`, or of all Nextcloud accounts.
+/// One board failing doesn't stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_deck(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<DeckSyncReport>> {
+    sync_provider::run_command(&app, &Deck, account_id.as_deref()).await
+}
+
+/// Nextcloud accounts with a board to sync.
+fn synced_accounts(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT account_id FROM deck_boards WHERE enabled = 1 ORDER BY account_id",
+    )?;
+    let ids = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    ids.collect()
+}
+
+/// Nextcloud accounts with any board, synced or not.
+fn board_accounts(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt =
+        conn.prepare("SELECT DISTINCT account_id FROM deck_boards ORDER BY account_id")?;
+    let ids = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    ids.collect()
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+    mode: SyncMode,
+) -> CommandResult<Vec<DeckSyncReport>> {
+    let accounts = db.with_conn(|conn| synced_accounts(conn))?;
+    let mut reports = Vec::new();
+    for id in accounts {
+        if account_id.is_some_and(|only| only != id) {
+            continue;
+        }
+        let account = find_nextcloud_account(db, &id)?;
+        let boards = db.with_conn(|conn| list_boards(conn, &id))?;
+        let api = Api::connect(&account);
+        for board in boards.iter().filter(|b| b.enabled) {
+            let result = match &api {
+                Ok(api) => sync_board(db, api, &account, board, mode).await,
+                Err(e) => Err(e.clone()),
+            };
+            let report = result.unwrap_or_else(|e| {
+                tracing::warn!("sync of {} failed: {e}", board.title);
+                DeckSyncReport {
+                    board_id: board.id.clone(),
+                    title: board.title.clone(),
+                    errors: vec![e.to_string()],
+                    ..DeckSyncReport::default()
+                }
+            });
+            progress.record(&report.title, &report);
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::tags;
+use crate::task_store::{self, row_to_task, Task, STATUS_DONE, TASK_COLUMNS};
+
+/// Emitted with the tasks whose last open blocker was just completed.
+pub const UNBLOCKED_EVENT: &str = "tasks-unblocked";
+
+/// SQL condition: task `tasks.id` has a blocker that is neither done nor in
+/// the trash.
+pub const BLOCKED_SQL: &str = "EXISTS (SELECT 1 FROM task_dependencies d \
+     JOIN tasks b ON b.id = d.blocker_id \
+     WHERE d.task_id = tasks.id AND b.status != 'done' AND b.deleted_at IS NULL)";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskDependencies {
+    /// Tasks that have to be finished first.
+    pub blocked_by: Vec<Task>,
+    /// Tasks waiting on this one.
+    pub blocking: Vec<Task>,
+}
+
+/// Fill in `is_blocked` for each task with one query.
+pub fn load_blocked(conn: &Connection, tasks: &mut [Task]) -> rusqlite::Result<()> {
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id FROM tasks
+         WHERE id IN (SELECT value FROM json_each(?1)) AND {BLOCKED_SQL}"
+    ))?;
+    let blocked = stmt
+        .query_map(
+            params![serde_json::to_string(&ids).unwrap_or_default()],
+            |row| row.get::<_, String>(0),
+        )?
+        .collect::<rusqlite::Result<HashSet<_>>>()?;
+    for task in tasks {
+        task.is_blocked = blocked.contains(&task.id);
+    }
+    Ok(())
+}
+
+fn linked_tasks(conn: &Connection, sql: &str, id: &str) -> rusqlite::Result<Vec<Task>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TASK_COLUMNS} FROM tasks
+         WHERE id IN ({sql}) AND deleted_at IS NULL
+         ORDER BY created_at, id"
+    ))?;
+    let mut tasks = stmt
+        .query_map(params![id], row_to_task)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tags::load_task_tags(conn, &mut tasks)?;
+    load_blocked(conn, &mut tasks)?;
+    Ok(tasks)
+}
+
+pub fn dependencies(conn: &Connection, id: &str) -> rusqlite::Result<TaskDependencies> {
+    Ok(TaskDependencies {
+        blocked_by: linked_tasks(
+            conn,
+            "SELECT blocker_id FROM task_dependencies WHERE task_id = ?1",
+            id,
+        )?,
+        blocking: linked_tasks(
+            conn,
+            "SELECT task_id FROM task_dependencies WHERE blocker_id = ?1",
+            id,
+        )?,
+    })
+}
+
+/// Open tasks that were waiting on `blocker_id` and aren't blocked by
+/// anything else. Call after completing it.
+pub fn unblocked_by(conn: &Connection, blocker_id: &str) -> rusqlite::Result<Vec<Task>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TASK_COLUMNS} FROM tasks
+         WHERE id IN (SELECT task_id FROM task_dependencies WHERE blocker_id = ?1)
+           AND status != ?2 AND deleted_at IS NULL AND NOT {BLOCKED_SQL}
+         ORDER BY created_at, id"
+    ))?;
+    let mut tasks = stmt
+        .query_map(params![blocker_id, STATUS_DONE], row_to_task)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tags::load_task_tags(conn, &mut tasks)?;
+    Ok(tasks)
+}
+
+/// Check `blocker_id` can block `task_id`: both must exist outside the
+/// trash, and the blocker mustn't already be waiting on the task.
+fn check_blocker(conn: &Connection, task_id: &str, blocker_id: &str) -> CommandResult<()> {
+    let db_err = |e: rusqlite::Error| e.to_string();
+    for id in [task_id, blocker_id] {
+        if task_store::find_task(conn, id).map_err(db_err)?.is_none() {
+            return Err(CommandError::not_found(format!("Task not found: {id}")));
+        }
+    }
+    if task_id == blocker_id {
+        return Err(CommandError::invalid("A task can't block itself"));
+    }
+    let cycle: bool = conn
+        .query_row(
+            "WITH RECURSIVE upstream(id) AS (
+                 SELECT ?1
+                 UNION SELECT d.blocker_id FROM task_dependencies d
+                 JOIN upstream u ON d.task_id = u.id
+             )
+             SELECT EXISTS (SELECT 1 FROM upstream WHERE id = ?2)",
+            params![blocker_id, task_id],
+            |row| row.get(0),
+        )
+        .map_err(db_err)?;
+    if cycle {
+        return Err(CommandError::invalid(
+            "That would make the tasks block each other",
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_task_dependencies(db: State<'_, Db>, id: String) -> CommandResult<TaskDependencies> {
+    db.with_conn(|conn| dependencies(conn, &id))
+}
+
+/// Mark `task_id` as blocked by `blocker_id`. Returns the blocked task.
+#[tauri::command]
+pub fn add_task_blocker(
+    db: State<'_, Db>,
+    task_id: String,
+    blocker_id: String,
+) -> CommandResult<Task> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        if let Err(e) = check_blocker(&tx, &task_id, &blocker_id) {
+            return Ok(Err(e));
+        }
+        tx.execute(
+            "INSERT INTO task_dependencies (task_id, blocker_id, created_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT DO NOTHING",
+            params![task_id, blocker_id, now_utc()],
+        )?;
+        let task = task_store::find_task(&tx, &task_id)?;
+        tx.commit()?;
+        Ok(task.ok_or_else(|| CommandError::not_found(format!("Task not found: {task_id}"))))
+    })?
+}
+
+/// Returns the task that was blocked.
+#[tauri::command]
+pub fn remove_task_blocker(
+    db: State<'_, Db>,
+    task_id: String,
+    blocker_id: String,
+) -> CommandResult<Task> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "DELETE FROM task_dependencies WHERE task_id = ?1 AND blocker_id = ?2",
+            params![task_id, blocker_id],
+        )?;
+        task_store::find_task(conn, &task_id)
+    })?
+    .ok_or_else(|| CommandError::not_found(format!("Task not found: {task_id}")))
+}
@@ -0,0 +1,110 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::archive;
+use crate::change_log;
+use crate::credentials;
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::notes;
+use crate::recurrence;
+use crate::timer;
+use crate::todotxt;
+
+/// Emitted once a locked database has been opened; views should load.
+pub const DATABASE_UNLOCKED_EVENT: &str = "database-unlocked";
+
+const KEYRING_USER: &str = "database-key";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseStatus {
+    pub encrypted: bool,
+    pub locked: bool,
+    /// Whether the key is saved in the OS keyring so the app unlocks itself.
+    pub remembered: bool,
+}
+
+/// Unlock an encrypted database at startup with the key saved in the
+/// keyring. Without one the database stays locked until the user enters the
+/// passphrase.
+pub fn unlock_from_keyring(db: &Db) {
+    if !db.is_locked() {
+        return;
+    }
+    if let Some(key) = credentials::load(KEYRING_USER) {
+        if let Err(e) = db.unlock(&key) {
+            tracing::warn!("saved key didn't unlock the database: {e}");
+        }
+    }
+}
+
+fn status(db: &Db) -> DatabaseStatus {
+    DatabaseStatus {
+        encrypted: db.is_encrypted(),
+        locked: db.is_locked(),
+        remembered: credentials::load(KEYRING_USER).is_some(),
+    }
+}
+
+/// Treat an empty passphrase as "no encryption".
+fn non_empty(passphrase: Option<String>) -> Option<String> {
+    passphrase.filter(|p| !p.is_empty())
+}
+
+#[tauri::command]
+pub fn database_status(db: State<'_, Db>) -> DatabaseStatus {
+    status(&db)
+}
+
+#[tauri::command]
+pub fn unlock_database(
+    app: AppHandle,
+    db: State<'_, Db>,
+    passphrase: String,
+    remember: bool,
+) -> CommandResult<DatabaseStatus> {
+    db.unlock(&passphrase)?;
+    if remember {
+        credentials::save(KEYRING_USER, Some(&passphrase))?;
+    }
+    // Rollover, the crashed-timer check, note indexing and the todo.txt and
+    // change log watches were skipped while locked.
+    recurrence::run_roll_over(&app);
+    timer::check_recovery(&app);
+    notes::sync_index(&app);
+    todotxt::watch(&app);
+    change_log::watch(&app);
+    let _ = app.emit(DATABASE_UNLOCKED_EVENT, ());
+    Ok(status(&db))
+}
+
+/// Encrypt, re-key or decrypt the database. `current` must match when the
+/// database is already encrypted; a missing or empty `new` decrypts it.
+/// The archive is re-keyed with it; existing backups keep the passphrase they
+/// were taken with.
+#[tauri::command]
+pub fn change_passphrase(
+    db: State<'_, Db>,
+    current: Option<String>,
+    new: Option<String>,
+    remember: bool,
+) -> CommandResult<DatabaseStatus> {
+    if db.is_locked() {
+        return Err(CommandError::locked());
+    }
+    if non_empty(current) != db.key() {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            "Current passphrase is incorrect",
+        ));
+    }
+    let new = non_empty(new);
+    let current = db.key();
+    db.rekey(new.as_deref())?;
+    archive::rekey(db.path(), current.as_deref(), new.as_deref())?;
+    match new.as_deref() {
+        Some(key) if remember => credentials::save(KEYRING_USER, Some(key))?,
+        _ => credentials::save(KEYRING_USER, None)?,
+    }
+    Ok(status(&db))
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+
+use reqwest::StatusCode;
+use serde::Serialize;
+
+/// What kind of failure a command hit, for the frontend to pick a
+/// recovery action by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The task, project, account, … asked for doesn't exist.
+    NotFound,
+    /// The arguments were rejected; fix them and try again.
+    InvalidInput,
+    /// The service turned the credentials away, or there are none: sign
+    /// in again.
+    Unauthorized,
+    /// The service asked to slow down; retry later.
+    RateLimited,
+    /// The service couldn't be reached.
+    Network,
+    /// The service answered with a server error.
+    Unavailable,
+    /// Something changed underneath, or already exists.
+    Conflict,
+    /// The database or a file couldn't be read or written.
+    Storage,
+    /// The database is encrypted and hasn't been unlocked.
+    Locked,
+    /// Another connection held the database; retry in a moment.
+    Busy,
+    Internal,
+}
+
+impl ErrorCode {
+    /// Whether the same call may succeed if simply made again later.
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::RateLimited | ErrorCode::Network | ErrorCode::Unavailable | ErrorCode::Busy
+        )
+    }
+
+    /// The code for a service's error status.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorCode::Unauthorized,
+            StatusCode::NOT_FOUND | StatusCode::GONE => ErrorCode::NotFound,
+            StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => ErrorCode::Conflict,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+            status if status.is_server_error() => ErrorCode::Unavailable,
+            status if status.is_client_error() => ErrorCode::InvalidInput,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// The error every command returns: a `code` to branch on, a message to
+/// show, whether retrying may help and, for sync errors, which provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub retryable: bool,
+    /// The provider id, e.g. `todoist`, when a service failed.
+    pub provider: Option<String>,
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retryable: code.retryable(),
+            provider: None,
+        }
+    }
+
+    /// The database is encrypted and hasn't been unlocked yet.
+    pub fn locked() -> Self {
+        Self::new(ErrorCode::Locked, "Database is locked")
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidInput, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+
+    /// The service turned the credentials away, or there are none.
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unauthorized, message)
+    }
+
+    /// A connection to a server failed or dropped. Socket errors go through
+    /// here rather than `From<io::Error>`, which is about files.
+    pub fn network(message: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Network, message.to_string())
+    }
+
+    /// A service answered with the error `status`.
+    pub fn status(status: StatusCode, message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::from_status(status), message)
+    }
+
+    pub fn with_provider(mut self, provider: &str) -> Self {
+        self.provider = Some(provider.to_string());
+        self
+    }
+
+    /// The same failure, its message prefixed with what was being done
+    /// (`Todoist: …`, `Failed to read notes.md: …`).
+    pub fn context(mut self, what: impl fmt::Display) -> Self {
+        self.message = format!("{what}: {}", self.message);
+        self
+    }
+}
+
+/// `context` for results, keeping the code of whatever failed.
+pub trait ResultExt<T> {
+    fn context(self, what: &str) -> CommandResult<T>;
+    fn with_context(self, what: impl FnOnce() -> String) -> CommandResult<T>;
+}
+
+impl<T, E: Into<CommandError>> ResultExt<T> for Result<T, E> {
+    fn context(self, what: &str) -> CommandResult<T> {
+        self.map_err(|e| e.into().context(what))
+    }
+
+    fn with_context(self, what: impl FnOnce() -> String) -> CommandResult<T> {
+        self.map_err(|e| e.into().context(what()))
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Text alone says nothing about what went wrong; failures the frontend
+/// can act on are built with their code where they happen.
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<rusqlite::Error> for CommandError {
+    fn from(e: rusqlite::Error) -> Self {
+        let code = match &e {
+            rusqlite::Error::QueryReturnedNoRows => ErrorCode::NotFound,
+            rusqlite::Error::SqliteFailure(failure, _)
+                if matches!(
+                    failure.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                ) =>
+            {
+                ErrorCode::Busy
+            }
+            _ => ErrorCode::Storage,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        let code = match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            _ => ErrorCode::Storage,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for CommandError {
+    fn from(e: reqwest::Error) -> Self {
+        let code = match e.status() {
+            Some(status) => ErrorCode::from_status(status),
+            None if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() => {
+                ErrorCode::Network
+            }
+            None => ErrorCode::Internal,
+        };
+        Self::new(code, e.to_string())
+    }
+}
@@ -0,0 +1,221 @@
+use serde::Serialize;
+use serde_json::{Map, Value as Json};
+use tauri::{AppHandle, Emitter};
+
+use crate::db::Db;
+use crate::journal::Change;
+use crate::task_store::STATUS_DONE;
+
+/// Each event below carries a list, one item per row, and is emitted once
+/// per backend call that touched such rows. Undo and redo aren't reported
+/// here; they send `journal::DATA_CHANGED_EVENT` instead.
+pub const TASK_CREATED_EVENT: &str = "task-created";
+pub const TASK_UPDATED_EVENT: &str = "task-updated";
+/// Also sent when a task goes to the trash or the archive.
+pub const TASK_DELETED_EVENT: &str = "task-deleted";
+pub const ENTRY_STARTED_EVENT: &str = "entry-started";
+pub const ENTRY_STOPPED_EVENT: &str = "entry-stopped";
+pub const ENTRY_CREATED_EVENT: &str = "entry-created";
+pub const ENTRY_UPDATED_EVENT: &str = "entry-updated";
+pub const ENTRY_DELETED_EVENT: &str = "entry-deleted";
+
+/// Columns every write touches, left out of `changed_fields`.
+const IGNORED_FIELDS: &[&str] = &["updated_at"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskChanged {
+    pub id: String,
+    /// Columns whose value changed, plus `tags`. Empty for created and
+    /// deleted tasks.
+    pub changed_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryChanged {
+    pub id: String,
+    pub task_id: String,
+    pub changed_fields: Vec<String>,
+}
+
+/// Everything that happened to one row within a step.
+struct RowHistory {
+    tbl: String,
+    id: String,
+    /// The row before the step; `None` if the step created it.
+    first: Option<Map<String, Json>>,
+    /// The row after the step; `None` if the step deleted it.
+    last: Option<Map<String, Json>>,
+    tags_changed: bool,
+}
+
+fn text(image: &Map<String, Json>, key: &str) -> Option<String> {
+    image.get(key).and_then(Json::as_str).map(str::to_string)
+}
+
+fn is_null(image: &Map<String, Json>, key: &str) -> bool {
+    image.get(key).is_none_or(Json::is_null)
+}
+
+/// A trashed task counts as gone, so moving it to and from the trash reads
+/// as a delete and a create.
+fn live(tbl: &str, image: &Option<Map<String, Json>>) -> Option<Map<String, Json>> {
+    image
+        .as_ref()
+        .filter(|row| tbl != "tasks" || is_null(row, "deleted_at"))
+        .cloned()
+}
+
+fn changed_fields(first: &Map<String, Json>, last: &Map<String, Json>) -> Vec<String> {
+    last.iter()
+        .filter(|(key, value)| {
+            !IGNORED_FIELDS.contains(&key.as_str()) && first.get(*key) != Some(*value)
+        })
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+fn collect(changes: &[Change]) -> Vec<RowHistory> {
+    let mut rows: Vec<RowHistory> = Vec::new();
+    for change in changes {
+        let image = change.after.as_ref().or(change.before.as_ref());
+        let (tbl, id) = match change.tbl.as_str() {
+            "tasks" | "time_entries" => (change.tbl.as_str(), image.and_then(|m| text(m, "id"))),
+            "task_tags" => ("tasks", image.and_then(|m| text(m, "task_id"))),
+            _ => continue,
+        };
+        let Some(id) = id else {
+            continue;
+        };
+        let index = match rows.iter().position(|r| r.tbl == tbl && r.id == id) {
+            Some(index) => index,
+            None => {
+                let first = if change.tbl == "task_tags" {
+                    // The task itself may not change; seed it as unchanged.
+                    Some(Map::new())
+                } else {
+                    live(tbl, &change.before)
+                };
+                rows.push(RowHistory {
+                    tbl: tbl.to_string(),
+                    id,
+                    last: first.clone(),
+                    first,
+                    tags_changed: false,
+                });
+                rows.len() - 1
+            }
+        };
+        let row = &mut rows[index];
+        if change.tbl == "task_tags" {
+            row.tags_changed = true;
+        } else {
+            if row.first.as_ref().is_some_and(Map::is_empty) {
+                row.first = live(tbl, &change.before);
+            }
+            row.last = live(tbl, &change.after);
+        }
+    }
+    rows
+}
+
+fn emit_list<T: Serialize + Clone>(app: &AppHandle, event: &str, items: &[T]) {
+    if !items.is_empty() {
+        let _ = app.emit(event, items.to_vec());
+    }
+}
+
+/// Turn one step's journaled changes into the events above.
+fn announce(app: &AppHandle, changes: &[Change]) {
+    let (mut created, mut updated, mut deleted) = (Vec::new(), Vec::new(), Vec::new());
+    let mut completed = Vec::new();
+    let (mut started, mut stopped) = (Vec::new(), Vec::new());
+    let (mut entries_created, mut entries_updated, mut entries_deleted) =
+        (Vec::new(), Vec::new(), Vec::new());
+
+    for row in collect(changes) {
+        if row.tbl == "tasks" {
+            let mut fields = match (&row.first, &row.last) {
+                (Some(first), Some(last)) if !first.is_empty() => changed_fields(first, last),
+                _ => Vec::new(),
+            };
+            let task = |changed_fields| TaskChanged {
+                id: row.id.clone(),
+                changed_fields,
+            };
+            match (&row.first, &row.last) {
+                (None, Some(_)) => created.push(task(Vec::new())),
+                (Some(_), None) => deleted.push(task(Vec::new())),
+                (Some(_), Some(_)) => {
+                    if row.tags_changed {
+                        fields.push("tags".to_string());
+                    }
+                    let done = row
+                        .last
+                        .as_ref()
+                        .and_then(|last| text(last, "status"))
+                        .is_some_and(|status| status == STATUS_DONE);
+                    if done && fields.iter().any(|f| f == "status") {
+                        completed.push(row.id.clone());
+                    }
+                    if !fields.is_empty() {
+                        updated.push(task(fields));
+                    }
+                }
+                (None, None) => {}
+            }
+            continue;
+        }
+
+        let image = row.last.as_ref().or(row.first.as_ref());
+        let entry = |changed_fields| EntryChanged {
+            id: row.id.clone(),
+            task_id: image.and_then(|m| text(m, "task_id")).unwrap_or_default(),
+            changed_fields,
+        };
+        let running = |image: &Map<String, Json>| is_null(image, "ended_at");
+        match (&row.first, &row.last) {
+            (None, Some(last)) if running(last) => started.push(entry(Vec::new())),
+            (None, Some(_)) => entries_created.push(entry(Vec::new())),
+            (Some(_), None) => entries_deleted.push(entry(Vec::new())),
+            (Some(first), Some(last)) => {
+                let fields = changed_fields(first, last);
+                match (running(first), running(last)) {
+                    (true, false) => stopped.push(entry(fields)),
+                    (false, true) => started.push(entry(fields)),
+                    _ if !fields.is_empty() => entries_updated.push(entry(fields)),
+                    _ => {}
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    emit_list(app, TASK_CREATED_EVENT, &created);
+    emit_list(app, TASK_UPDATED_EVENT, &updated);
+    emit_list(app, TASK_DELETED_EVENT, &deleted);
+    emit_list(app, ENTRY_CREATED_EVENT, &entries_created);
+    emit_list(app, ENTRY_UPDATED_EVENT, &entries_updated);
+    emit_list(app, ENTRY_DELETED_EVENT, &entries_deleted);
+    emit_list(app, ENTRY_STOPPED_EVENT, &stopped);
+    emit_list(app, ENTRY_STARTED_EVENT, &started);
+
+    if !created.is_empty() || !updated.is_empty() || !deleted.is_empty() {
+        crate::todotxt::tasks_changed();
+        crate::change_log::tasks_changed();
+    }
+    crate::webhooks::tasks_completed(app, &completed);
+    crate::webhooks::timers_started(app, &started);
+    crate::scripts::tasks_completed(app, &completed);
+    crate::scripts::timers_stopped(app, &stopped);
+
+    #[cfg(desktop)]
+    if !started.is_empty() || !stopped.is_empty() {
+        crate::tray::refresh_timer(app);
+    }
+}
+
+/// Report every change made through `db` to the frontend.
+pub fn attach(app: &AppHandle, db: &Db) {
+    let app = app.clone();
+    db.set_listener(move |changes| announce(&app, changes));
+}
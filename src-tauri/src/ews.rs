@@ -0,0 +1,852 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::caldav::TlsOptions;
+use crate::calendars::{self, Calendar};
+use crate::crdt;
+use crate::credentials;
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::ews_calendar;
+use crate::history::{self, ChangeSource};
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{self, Capabilities, SyncFuture, SyncProvider};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+use crate::xml::{self, Element};
+
+/// `external_refs.source` for tasks that came from Exchange; the external
+/// id is the item id.
+const SOURCE: &str = "ews";
+
+/// Where Exchange serves EWS when the server URL has no path.
+const EWS_PATH: &str = "/EWS/Exchange.asmx";
+/// The oldest schema with everything asked for here, so Exchange 2007 SP1
+/// servers answer too.
+const SERVER_VERSION: &str = "Exchange2007_SP1";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const PAGE_SIZE: usize = 100;
+const MAX_PAGES: usize = 50;
+
+const TASK_COMPLETED: &str = "Completed";
+
+/// An on-premises Exchange account, reached over Exchange Web Services
+/// for servers without Graph. Its tasks are imported and its calendars
+/// read; nothing is written back.
+#[derive(Debug, Clone, Serialize)]
+pub struct EwsAccount {
+    pub id: String,
+    pub name: String,
+    /// The EWS endpoint, usually `https://host/EWS/Exchange.asmx`.
+    pub server_url: String,
+    /// `DOMAIN\user` or `user@domain`, as the server takes it.
+    pub username: String,
+    /// Where imported tasks go; `None` for the inbox.
+    pub project: Option<String>,
+    pub tls: TlsOptions,
+    pub last_synced_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub calendars: Vec<Calendar>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewEwsAccount {
+    /// Defaults to the server's host name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The server's name or address, or the full EWS endpoint URL.
+    pub server_url: String,
+    pub username: String,
+    /// Kept in the OS keyring. The server has to accept basic
+    /// authentication; NTLM-only servers can't be used.
+    pub password: String,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tls: TlsOptions,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// goes back to the inbox.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EwsAccountPatch {
+    pub name: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EwsSyncReport {
+    pub account_id: String,
+    pub name: String,
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks completed because they were completed in Exchange.
+    pub completed: usize,
+    /// Tasks moved to the trash because they were deleted in Exchange.
+    pub removed: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for EwsSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.completed + self.removed
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+/// A task in the Exchange tasks folder.
+#[derive(Debug, Clone)]
+struct RemoteTask {
+    id: String,
+    /// Changes whenever the item does.
+    change_key: String,
+    title: String,
+    due: Option<String>,
+    done: bool,
+    priority: Option<i64>,
+    /// When Exchange last changed it, in milliseconds, as the clocks of
+    /// local edits are.
+    modified: Option<i64>,
+}
+
+impl RemoteTask {
+    fn parse(element: &Element, local: &Tz) -> Option<Self> {
+        let item_id = element.child("ItemId")?;
+        let title = element
+            .find_text("Subject")
+            .unwrap_or_else(|| "Untitled".to_string());
+        let priority = match element.find_text("Importance").as_deref() {
+            Some("High") => Some(3),
+            Some("Low") => Some(1),
+            _ => Some(2),
+        };
+        Some(RemoteTask {
+            id: item_id.attr("Id")?.to_string(),
+            change_key: item_id.attr("ChangeKey").unwrap_or_default().to_string(),
+            title,
+            due: element
+                .find_text("DueDate")
+                .and_then(|d| local_date(&d, local)),
+            done: element.find_text("Status").as_deref() == Some(TASK_COMPLETED),
+            priority,
+            modified: element
+                .find_text("LastModifiedTime")
+                .and_then(|at| parse_utc(&at).ok())
+                .map(|at| at.timestamp_millis()),
+        })
+    }
+}
+
+/// The day `value`, an EWS time, falls on here. Exchange keeps a task's
+/// dates as midnight where it was set, in UTC.
+fn local_date(value: &str, local: &Tz) -> Option<String> {
+    let at = DateTime::parse_from_rfc3339(value).ok()?;
+    Some(at.with_timezone(local).date_naive().to_string())
+}
+
+/// The Exchange importance for a priority (0-3, none to high).
+fn importance(priority: Option<i64>) -> &'static str {
+    match priority {
+        Some(p) if p >= 3 => "High",
+        Some(1) => "Low",
+        _ => "Normal",
+    }
+}
+
+const ACCOUNT_COLUMNS: &str = "id, name, server_url, username, project, tls_certificate, \
+                               accept_invalid_certs, last_synced_at, created_at, updated_at";
+
+fn row_to_account(row: &Row) -> rusqlite::Result<EwsAccount> {
+    Ok(EwsAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        server_url: row.get(2)?,
+        username: row.get(3)?,
+        project: row.get(4)?,
+        tls: TlsOptions {
+            certificate: row.get(5)?,
+            accept_invalid_certs: row.get(6)?,
+        },
+        last_synced_at: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        calendars: Vec::new(),
+    })
+}
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<EwsAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM ews_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    let mut accounts = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    for account in &mut accounts {
+        account.calendars = calendars::list_ews(conn, &account.id)?;
+    }
+    Ok(accounts)
+}
+
+pub fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<EwsAccount>> {
+    let account = conn
+        .query_row(
+            &format!("SELECT {ACCOUNT_COLUMNS} FROM ews_accounts WHERE id = ?1"),
+            params![id],
+            row_to_account,
+        )
+        .optional()?;
+    let Some(mut account) = account else {
+        return Ok(None);
+    };
+    account.calendars = calendars::list_ews(conn, &account.id)?;
+    Ok(Some(account))
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("ews:{account_id}")
+}
+
+/// The EWS endpoint for `value`: a server's name or address, which gets
+/// the usual path, or the endpoint's full URL.
+fn endpoint(value: &str) -> CommandResult<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(CommandError::invalid("Enter the Exchange server address"));
+    }
+    let value = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("https://{value}")
+    };
+    let mut url = Url::parse(&value)
+        .map_err(|e| CommandError::invalid(format!("Invalid server URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(CommandError::invalid(
+            "The server URL must start with http:// or https://",
+        ));
+    }
+    if url.path().trim_end_matches('/').is_empty() {
+        url.set_path(EWS_PATH);
+    }
+    Ok(url.to_string())
+}
+
+/// `body` in a SOAP envelope asking for `SERVER_VERSION`.
+fn envelope(body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"
+    xmlns:t="http://schemas.microsoft.com/exchange/services/2006/types"
+    xmlns:m="http://schemas.microsoft.com/exchange/services/2006/messages">
+  <soap:Header><t:RequestServerVersion Version="{SERVER_VERSION}"/></soap:Header>
+  <soap:Body>{body}</soap:Body>
+</soap:Envelope>"#
+    )
+}
+
+/// The tasks folder itself, which every mailbox has; cheap enough to
+/// check a connection with.
+const GET_TASKS_FOLDER: &str = r#"<m:GetFolder>
+  <m:FolderShape><t:BaseShape>IdOnly</t:BaseShape></m:FolderShape>
+  <m:FolderIds><t:DistinguishedFolderId Id="tasks"/></m:FolderIds>
+</m:GetFolder>"#;
+
+/// A connection to one account's EWS endpoint.
+pub struct Api {
+    client: Client,
+    url: String,
+    username: String,
+    password: String,
+}
+
+impl Api {
+    fn new(url: &str, username: &str, password: &str, tls: &TlsOptions) -> CommandResult<Self> {
+        let builder = Client::builder().timeout(REQUEST_TIMEOUT);
+        let client = tls.apply(builder)?.build()?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    pub fn connect(account: &EwsAccount) -> CommandResult<Self> {
+        let password = credentials::require(&keyring_name(&account.id), "password")?;
+        Self::new(
+            &account.server_url,
+            &account.username,
+            &password,
+            &account.tls,
+        )
+    }
+
+    async fn post(&self, body: &str) -> Result<Response, reqwest::Error> {
+        self.client
+            .post(&self.url)
+            .basic_auth(&self.username, Some(&self.password))
+            .header(CONTENT_TYPE, "text/xml; charset=utf-8")
+            .body(envelope(body))
+            .send()
+            .await
+    }
+
+    /// Ask for the tasks folder, for the account health check.
+    async fn probe(&self) -> Result<Response, reqwest::Error> {
+        self.post(GET_TASKS_FOLDER).await
+    }
+
+    /// Send one operation and return its response message, failing when
+    /// Exchange says it failed.
+    pub async fn call(&self, body: &str) -> CommandResult<Element> {
+        rate_limit::check(SOURCE, "Exchange")?;
+        let response = self.post(body).await.context("Exchange")?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        match status {
+            StatusCode::UNAUTHORIZED => {
+                return Err(CommandError::unauthorized(
+                    "Exchange rejected the username or password",
+                )
+                .with_provider(SOURCE))
+            }
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                return Err(rate_limit::limited(SOURCE, "Exchange", &headers))
+            }
+            _ => {}
+        }
+        let text = response.text().await?;
+        // Faults come back as 500 with a SOAP body saying why.
+        let document = xml::parse(&text).map_err(|e| {
+            if status.is_success() {
+                format!("Exchange: {e}").into()
+            } else {
+                CommandError::status(status, format!("Exchange: HTTP {}", status.as_u16()))
+                    .with_provider(SOURCE)
+            }
+        })?;
+        if let Some(fault) = document.find_text("faultstring") {
+            return Err(format!("Exchange: {fault}").into());
+        }
+        let message = document
+            .find("ResponseMessages")
+            .and_then(|m| m.children.first())
+            .ok_or_else(|| {
+                CommandError::status(status, format!("Exchange: HTTP {}", status.as_u16()))
+                    .with_provider(SOURCE)
+            })?;
+        if message.attr("ResponseClass") == Some("Error") {
+            if message.find_text("ResponseCode").as_deref() == Some("ErrorServerBusy") {
+                return Err(rate_limit::limited(SOURCE, "Exchange", &headers));
+            }
+            let text = message
+                .find_text("MessageText")
+                .or_else(|| message.find_text("ResponseCode"))
+                .unwrap_or_else(|| "the request failed".to_string());
+            return Err(format!("Exchange: {text}").into());
+        }
+        Ok(message.clone())
+    }
+
+    /// Every task in the tasks folder, finished or not.
+    async fn tasks(&self, local: &Tz) -> CommandResult<Vec<RemoteTask>> {
+        let mut found = Vec::new();
+        let mut offset = 0;
+        for _ in 0..MAX_PAGES {
+            let body = format!(
+                r#"<m:FindItem Traversal="Shallow">
+  <m:ItemShape>
+    <t:BaseShape>IdOnly</t:BaseShape>
+    <t:AdditionalProperties>
+      <t:FieldURI FieldURI="item:Subject"/>
+      <t:FieldURI FieldURI="item:Importance"/>
+      <t:FieldURI FieldURI="item:LastModifiedTime"/>
+      <t:FieldURI FieldURI="task:DueDate"/>
+      <t:FieldURI FieldURI="task:Status"/>
+    </t:AdditionalProperties>
+  </m:ItemShape>
+  <m:IndexedPageItemView MaxEntriesReturned="{PAGE_SIZE}" Offset="{offset}" BasePoint="Beginning"/>
+  <m:ParentFolderIds><t:DistinguishedFolderId Id="tasks"/></m:ParentFolderIds>
+</m:FindItem>"#
+            );
+            let message = self.call(&body).await?;
+            let Some(root) = message.child("RootFolder") else {
+                break;
+            };
+            let Some(items) = root.child("Items") else {
+                break;
+            };
+            let page: Vec<RemoteTask> = items
+                .children("Task")
+                .filter_map(|task| RemoteTask::parse(task, local))
+                .collect();
+            offset += items.children.len();
+            found.extend(page);
+            if root.attr("IncludesLastItemInRange") != Some("false") || items.children.is_empty() {
+                break;
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// A task imported before, as of the last sync.
+#[derive(Debug, Clone)]
+struct ItemRow {
+    task_id: String,
+    change_key: String,
+}
+
+fn load_items(conn: &Connection, account_id: &str) -> rusqlite::Result<HashMap<String, ItemRow>> {
+    let mut stmt =
+        conn.prepare("SELECT item_id, task_id, change_key FROM ews_items WHERE account_id = ?1")?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ItemRow {
+                task_id: row.get(1)?,
+                change_key: row.get(2)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_item(
+    conn: &Connection,
+    account_id: &str,
+    remote: &RemoteTask,
+    task_id: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO ews_items (account_id, item_id, task_id, change_key)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(account_id, item_id) DO UPDATE SET
+             task_id = excluded.task_id,
+             change_key = excluded.change_key",
+        params![account_id, remote.id, task_id, remote.change_key],
+    )?;
+    Ok(())
+}
+
+fn forget_item(conn: &Connection, account_id: &str, item_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM ews_items WHERE account_id = ?1 AND item_id = ?2",
+        params![account_id, item_id],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = Some(now_utc());
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// Create or update the task for `remote`. A field edited here after
+/// Exchange last changed the task keeps the local value. Returns the task
+/// and whether it was created, or `None` when nothing changed.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    remote: &RemoteTask,
+    project: Option<&str>,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: remote.title.clone(),
+            description: None,
+            project: project.map(String::from),
+            priority: remote.priority.filter(|p| *p != 2),
+            due: remote.due.clone(),
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let task = task_store::insert_task(conn, &input, remote.title.clone())?;
+        return Ok(Some((task, true)));
+    };
+
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| {
+        stamps
+            .get(field)
+            .is_none_or(|s| remote.modified.is_none_or(|at| s.clock <= at))
+    };
+    let before = task.clone();
+    if take("title") {
+        task.title = remote.title.clone();
+    }
+    if take("priority") && importance(task.priority) != importance(remote.priority) {
+        task.priority = remote.priority;
+    }
+    // Exchange keeps only the date; a time of day set here survives.
+    if take("due") && task.due.as_deref().and_then(|d| d.get(..10)) != remote.due.as_deref() {
+        task.due = remote.due.clone();
+    }
+    if take("status") && remote.done != (task.status == STATUS_DONE) {
+        set_done(&mut task, remote.done);
+    }
+    if task.title == before.title
+        && task.priority == before.priority
+        && task.due == before.due
+        && task.status == before.status
+    {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    Ok(Some((task, false)))
+}
+
+/// Apply the tasks Exchange listed. Tasks already finished there aren't
+/// imported; ones deleted there go to the trash here.
+fn merge_remote(
+    conn: &mut Connection,
+    account: &EwsAccount,
+    listed: &[RemoteTask],
+    report: &mut EwsSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let known = load_items(&tx, &account.id)?;
+    let project = account.project.as_deref();
+
+    for remote in listed {
+        let row = known.get(&remote.id);
+        if row.is_some_and(|r| r.change_key == remote.change_key) {
+            continue;
+        }
+        let task_id = match row {
+            Some(row) => Some(row.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, remote.id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if task_id.is_some() && existing.is_none() {
+            // Deleted here: it stays deleted.
+            continue;
+        }
+        if existing.is_none() && remote.done {
+            continue;
+        }
+        let was_done = existing.as_ref().is_some_and(|t| t.status == STATUS_DONE);
+        let task = match write_remote(&tx, existing, remote, project)? {
+            Some((task, true)) => {
+                report.created += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, remote.id, task.id, now_utc()],
+                )?;
+                task.id
+            }
+            Some((task, false)) => {
+                if !was_done && task.status == STATUS_DONE {
+                    report.completed += 1;
+                } else {
+                    report.updated += 1;
+                }
+                task.id
+            }
+            None => task_id.unwrap_or_default(),
+        };
+        save_item(&tx, &account.id, remote, &task)?;
+    }
+
+    let listed: HashSet<&str> = listed.iter().map(|remote| remote.id.as_str()).collect();
+    for (item_id, row) in &known {
+        if listed.contains(item_id.as_str()) {
+            continue;
+        }
+        if trash::trash_task(&tx, &row.task_id)? {
+            report.removed += 1;
+        }
+        forget_item(&tx, &account.id, item_id)?;
+    }
+    tx.commit()
+}
+
+async fn sync_account(db: &Db, api: &Api, account: &EwsAccount) -> CommandResult<EwsSyncReport> {
+    let mut report = EwsSyncReport {
+        account_id: account.id.clone(),
+        name: account.name.clone(),
+        ..EwsSyncReport::default()
+    };
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let listed = api.tasks(&local).await?;
+    db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(conn, account, &listed, &mut report)
+        })??;
+        conn.execute(
+            "UPDATE ews_accounts SET last_synced_at = ?2 WHERE id = ?1",
+            params![account.id, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+/// Check the server, username and password, then save the account with
+/// its calendars, all hidden. Tasks aren't imported until `sync_ews`
+/// runs.
+#[tauri::command]
+pub async fn connect_ews_account(
+    db: State<'_, Db>,
+    input: NewEwsAccount,
+) -> CommandResult<EwsAccount> {
+    let url = endpoint(&input.server_url)?;
+    let username = input.username.trim().to_string();
+    if username.is_empty() {
+        return Err(CommandError::invalid("Enter the username"));
+    }
+    let project = match input.project.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(name) => Some(projects::normalize_name(name)?),
+    };
+    let tls = input.tls.normalized();
+    let api = Api::new(&url, &username, &input.password, &tls)?;
+    api.call(GET_TASKS_FOLDER).await?;
+    let found = ews_calendar::calendars(&api).await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or_else(|| {
+            Url::parse(&url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_string))
+        })
+        .unwrap_or_else(|| "Exchange".to_string());
+    credentials::save(&keyring_name(&id), Some(&input.password))?;
+    let stored = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO ews_accounts (id, name, server_url, username, project, tls_certificate,
+                 accept_invalid_certs, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+            params![
+                id,
+                name,
+                url,
+                username,
+                project,
+                tls.certificate,
+                tls.accept_invalid_certs,
+                now
+            ],
+        )?;
+        ews_calendar::store_calendars(&tx, &id, &found)?;
+        tx.commit()?;
+        find_account(conn, &id)
+    });
+    match stored {
+        Ok(Some(account)) => Ok(account),
+        Ok(None) => Err("Failed to save account".into()),
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_ews_accounts(db: State<'_, Db>) -> CommandResult<Vec<EwsAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Rename an account or change where its tasks go. Tasks imported before
+/// stay where they are.
+#[tauri::command]
+pub fn update_ews_account(
+    db: State<'_, Db>,
+    id: String,
+    patch: EwsAccountPatch,
+) -> CommandResult<EwsAccount> {
+    let name = match patch.name.map(|n| n.trim().to_string()) {
+        Some(name) if name.is_empty() => return Err(CommandError::invalid("Enter a name")),
+        name => name,
+    };
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut account) = find_account(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Exchange account not found: {id}"
+            ))));
+        };
+        if let Some(name) = name {
+            account.name = name;
+        }
+        if let Some(project) = project {
+            account.project = project;
+        }
+        account.updated_at = now_utc();
+        conn.execute(
+            "UPDATE ews_accounts SET name = ?2, project = ?3, updated_at = ?4 WHERE id = ?1",
+            params![
+                account.id,
+                account.name,
+                account.project,
+                account.updated_at
+            ],
+        )?;
+        Ok(Ok(account))
+    })?
+}
+
+/// Forget an account, its calendars and which tasks it imported. Its
+/// tasks stay, unlinked.
+#[tauri::command]
+pub fn remove_ews_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM ews_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Exchange account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+pub struct Ews;
+
+impl SyncProvider for Ews {
+    type Report = Vec<EwsSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Exchange"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: false,
+            conflicts: false,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(&account)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                match Api::connect(&account) {
+                    Ok(api) => {
+                        health.answer("Exchange", api.probe().await);
+                    }
+                    Err(e) => health.no_credentials(&e),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress))
+    }
+}
+
+/// Import the tasks of `account_id` or every account. Completing or
+/// deleting a task in Exchange does the same here. One account failing
+/// doesn't stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_ews(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<EwsSyncReport>> {
+    sync_provider::run_command(&app, &Ews, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+) -> CommandResult<Vec<EwsSyncReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let result = match Api::connect(&account) {
+            Ok(api) => sync_account(db, &api, &account).await,
+            Err(e) => Err(e),
+        };
+        let report = result.unwrap_or_else(|e| {
+            tracing::warn!("sync of {} failed: {e}", account.name);
+            EwsSyncReport {
+                account_id: account.id.clone(),
+                name: account.name.clone(),
+                errors: vec![e.to_string()],
+                ..EwsSyncReport::default()
+            }
+        });
+        progress.record(&report.name, &report);
+        reports.push(report);
+    }
+    Ok(reports)
+}
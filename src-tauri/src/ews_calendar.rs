@@ -0,0 +1,184 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use tauri::State;
+
+use crate::calendars::{self, Calendar, Fetched};
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult};
+use crate::ews::{self, Api};
+use crate::ics::IcsEvent;
+use crate::timezone;
+use crate::xml::{self, Element};
+
+/// How Exchange wants the ends of a calendar view.
+const VIEW_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+/// A calendar view fails rather than be cut short, so this is set well
+/// above what the fetch window holds.
+const MAX_EVENTS: usize = 2000;
+
+/// Every calendar folder of the mailbox, as (folder id, name). The id is
+/// also the calendar's `calendars.url`.
+pub async fn calendars(api: &Api) -> CommandResult<Vec<(String, String)>> {
+    let message = api
+        .call(
+            r#"<m:FindFolder Traversal="Deep">
+  <m:FolderShape>
+    <t:BaseShape>IdOnly</t:BaseShape>
+    <t:AdditionalProperties><t:FieldURI FieldURI="folder:DisplayName"/></t:AdditionalProperties>
+  </m:FolderShape>
+  <m:Restriction>
+    <t:IsEqualTo>
+      <t:FieldURI FieldURI="folder:FolderClass"/>
+      <t:FieldURIOrConstant><t:Constant Value="IPF.Appointment"/></t:FieldURIOrConstant>
+    </t:IsEqualTo>
+  </m:Restriction>
+  <m:ParentFolderIds><t:DistinguishedFolderId Id="msgfolderroot"/></m:ParentFolderIds>
+</m:FindFolder>"#,
+        )
+        .await?;
+    let Some(folders) = message.find("Folders") else {
+        return Ok(Vec::new());
+    };
+    Ok(folders
+        .children
+        .iter()
+        .filter_map(|folder| {
+            let id = folder.child("FolderId")?.attr("Id")?.to_string();
+            let name = folder
+                .find_text("DisplayName")
+                .unwrap_or_else(|| "Calendar".to_string());
+            Some((id, name))
+        })
+        .collect())
+}
+
+pub fn store_calendars(
+    conn: &Connection,
+    account_id: &str,
+    found: &[(String, String)],
+) -> rusqlite::Result<()> {
+    let found: Vec<(&str, &str)> = found
+        .iter()
+        .map(|(url, name)| (url.as_str(), name.as_str()))
+        .collect();
+    calendars::store_ews(conn, account_id, &found)
+}
+
+/// The event to cache for a calendar item, or `None` when it was
+/// cancelled.
+fn to_event(item: &Element, local: &Tz) -> Option<IcsEvent> {
+    if item.find_text("IsCancelled").as_deref() == Some("true") {
+        return None;
+    }
+    let all_day = item.find_text("IsAllDayEvent").as_deref() == Some("true");
+    let instant = |name: &str| {
+        let at = DateTime::parse_from_rfc3339(&item.find_text(name)?).ok()?;
+        if all_day {
+            // All-day events run midnight to midnight on their dates,
+            // wherever the user is.
+            let date = at.with_timezone(local).date_naive();
+            timezone::resolve_local(date.and_time(NaiveTime::MIN), local)
+                .map(|dt| dt.with_timezone(&Utc))
+        } else {
+            Some(at.with_timezone(&Utc))
+        }
+    };
+    let starts_at = instant("Start")?;
+    let ends_at = instant("End")
+        .filter(|end| *end >= starts_at)
+        .unwrap_or(starts_at);
+    let title = item
+        .find_text("Subject")
+        .unwrap_or_else(|| "Busy".to_string());
+    let busy = !all_day && item.find_text("LegacyFreeBusyStatus").as_deref() != Some("Free");
+    Some(IcsEvent {
+        uid: item.child("ItemId")?.attr("Id")?.to_string(),
+        title,
+        location: item.find_text("Location"),
+        starts_at,
+        ends_at,
+        all_day,
+        busy,
+    })
+}
+
+/// Every event of the calendar in `window`, with recurring ones expanded
+/// into their occurrences, each with its own id. EWS hands out no sync
+/// state for calendar views, so each fetch is a full one.
+async fn fetch_events(
+    api: &Api,
+    calendar: &Calendar,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Fetched> {
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let body = format!(
+        r#"<m:FindItem Traversal="Shallow">
+  <m:ItemShape>
+    <t:BaseShape>IdOnly</t:BaseShape>
+    <t:AdditionalProperties>
+      <t:FieldURI FieldURI="item:Subject"/>
+      <t:FieldURI FieldURI="calendar:Start"/>
+      <t:FieldURI FieldURI="calendar:End"/>
+      <t:FieldURI FieldURI="calendar:IsAllDayEvent"/>
+      <t:FieldURI FieldURI="calendar:IsCancelled"/>
+      <t:FieldURI FieldURI="calendar:Location"/>
+      <t:FieldURI FieldURI="calendar:LegacyFreeBusyStatus"/>
+    </t:AdditionalProperties>
+  </m:ItemShape>
+  <m:CalendarView MaxEntriesReturned="{MAX_EVENTS}" StartDate="{}" EndDate="{}"/>
+  <m:ParentFolderIds><t:FolderId Id="{}"/></m:ParentFolderIds>
+</m:FindItem>"#,
+        window.0.format(VIEW_TIME_FORMAT),
+        window.1.format(VIEW_TIME_FORMAT),
+        xml::escape(&calendar.url)
+    );
+    let message = api.call(&body).await?;
+    let events = message
+        .find("Items")
+        .map(|items| {
+            items
+                .children("CalendarItem")
+                .filter_map(|item| to_event(item, &local))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Fetched {
+        events,
+        full: true,
+        ..Fetched::default()
+    })
+}
+
+/// Fetch the calendar's events. Returns `None` for a hidden calendar.
+pub async fn refresh(
+    api: &Api,
+    calendar: &Calendar,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Option<Fetched>> {
+    if !calendar.enabled {
+        return Ok(None);
+    }
+    fetch_events(api, calendar, window).await.map(Some)
+}
+
+/// Look for calendars added to or removed from the account.
+#[tauri::command]
+pub async fn refresh_ews_calendars(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<Calendar>> {
+    let account = db
+        .with_conn(|conn| ews::find_account(conn, &account_id))?
+        .ok_or_else(|| {
+            CommandError::not_found(format!("Exchange account not found: {account_id}"))
+        })?;
+    let api = Api::connect(&account)?;
+    let found = calendars(&api).await?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_calendars(&tx, &account_id, &found)?;
+        tx.commit()?;
+        calendars::list_ews(conn, &account_id)
+    })
+}
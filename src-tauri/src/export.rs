@@ -0,0 +1,399 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::csv;
+use crate::db::{format_utc, now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::reports;
+use crate::task_store::{row_to_task, Task, TASK_COLUMNS, TASK_COLUMN_COUNT};
+use crate::time_entries::{row_to_entry, TimeEntry, ENTRY_COLUMNS};
+use crate::timezone;
+use crate::usage;
+
+/// Bumped whenever a field is renamed or removed, so importers can tell
+/// which layout they are reading.
+pub const EXPORT_SCHEMA_VERSION: i64 = 1;
+
+/// Task columns, in CSV order. JSON exports use the same names as keys.
+/// Timestamps are RFC 3339 UTC; `due`/`scheduled` are `YYYY-MM-DD` or
+/// `YYYY-MM-DDTHH:MM` wall-clock in `tz`; `tags` is a JSON array in JSON
+/// exports and a ", "-separated list in CSV.
+pub const TASK_FIELDS: &[&str] = &[
+    "id",
+    "title",
+    "description",
+    "status",
+    "project",
+    "priority",
+    "due",
+    "scheduled",
+    "created_at",
+    "updated_at",
+    "completed_at",
+    "recurrence",
+    "series_id",
+    "tz",
+    "tags",
+];
+
+/// Time entry columns, in CSV order. `ended_at` is empty for a running
+/// entry; `billable` is `true`/`false`.
+pub const ENTRY_FIELDS: &[&str] = &[
+    "id",
+    "task_id",
+    "started_at",
+    "ended_at",
+    "note",
+    "created_at",
+    "updated_at",
+    "tz",
+    "billable",
+];
+
+const TAG_LIST_SEPARATOR: &str = ", ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// One object: `{schema_version, exported_at, tasks, time_entries}`.
+    Json,
+    /// One file per table, with a header row.
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportScope {
+    Tasks,
+    TimeEntries,
+    All,
+}
+
+impl ExportScope {
+    fn tasks(self) -> bool {
+        matches!(self, ExportScope::Tasks | ExportScope::All)
+    }
+
+    fn time_entries(self) -> bool {
+        matches!(self, ExportScope::TimeEntries | ExportScope::All)
+    }
+}
+
+/// Either bound may be an RFC 3339 instant or a local `YYYY-MM-DD` date
+/// (`to` inclusive). Tasks are included if they existed and changed within
+/// the range; time entries if they overlap it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DateRange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSummary {
+    pub files: Vec<String>,
+    pub tasks: u64,
+    pub time_entries: u64,
+}
+
+struct Bounds {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+impl Bounds {
+    fn from_range(range: &DateRange) -> CommandResult<Self> {
+        let tz = timezone::parse_zone(&timezone::system_zone())?;
+        let bound = |value: &Option<String>, inclusive: bool| {
+            value
+                .as_deref()
+                .map(|v| reports::parse_bound(v, &tz, inclusive).map(format_utc))
+                .transpose()
+        };
+        Ok(Self {
+            from: bound(&range.from, false)?,
+            to: bound(&range.to, true)?,
+        })
+    }
+}
+
+fn db_err(e: rusqlite::Error) -> String {
+    format!("Failed to read data for export: {e}")
+}
+
+fn write_err(path: &Path) -> impl Fn(std::io::Error) -> CommandError + '_ {
+    move |e| CommandError::from(e).context(format!("Failed to write {}", path.display()))
+}
+
+/// Call `f` for every task in range, oldest first, one row at a time.
+fn each_task(
+    conn: &Connection,
+    bounds: &Bounds,
+    mut f: impl FnMut(&Task) -> CommandResult<()>,
+) -> CommandResult<u64> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TASK_COLUMNS},
+                    (SELECT json_group_array(g.name) FROM task_tags tt
+                     JOIN tags g ON g.id = tt.tag_id WHERE tt.task_id = tasks.id)
+             FROM tasks
+             WHERE deleted_at IS NULL
+               AND (?1 IS NULL OR updated_at >= ?1) AND (?2 IS NULL OR created_at < ?2)
+             ORDER BY created_at"
+        ))
+        .map_err(db_err)?;
+    let mut rows = stmt
+        .query(params![bounds.from, bounds.to])
+        .map_err(db_err)?;
+    let mut count = 0;
+    while let Some(row) = rows.next().map_err(db_err)? {
+        let mut task = row_to_task(row).map_err(db_err)?;
+        let tags: Option<String> = row.get(TASK_COLUMN_COUNT).map_err(db_err)?;
+        task.tags = tags
+            .and_then(|t| serde_json::from_str(&t).ok())
+            .unwrap_or_default();
+        task.tags.sort_by_key(|t| t.to_lowercase());
+        f(&task)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Call `f` for every time entry overlapping the range, oldest first.
+fn each_entry(
+    conn: &Connection,
+    bounds: &Bounds,
+    mut f: impl FnMut(&TimeEntry) -> CommandResult<()>,
+) -> CommandResult<u64> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM time_entries
+             WHERE (?2 IS NULL OR started_at < ?2)
+               AND (?1 IS NULL OR ended_at IS NULL OR ended_at > ?1)
+               AND task_id IN (SELECT id FROM tasks WHERE deleted_at IS NULL)
+             ORDER BY started_at"
+        ))
+        .map_err(db_err)?;
+    let mut rows = stmt
+        .query(params![bounds.from, bounds.to])
+        .map_err(db_err)?;
+    let mut count = 0;
+    while let Some(row) = rows.next().map_err(db_err)? {
+        f(&row_to_entry(row).map_err(db_err)?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn opt(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("")
+}
+
+fn task_record(task: &Task) -> Vec<String> {
+    vec![
+        task.id.clone(),
+        task.title.clone(),
+        opt(&task.description).to_string(),
+        task.status.clone(),
+        opt(&task.project).to_string(),
+        task.priority.map(|p| p.to_string()).unwrap_or_default(),
+        opt(&task.due).to_string(),
+        opt(&task.scheduled).to_string(),
+        task.created_at.clone(),
+        task.updated_at.clone(),
+        opt(&task.completed_at).to_string(),
+        opt(&task.recurrence).to_string(),
+        opt(&task.series_id).to_string(),
+        opt(&task.tz).to_string(),
+        task.tags.join(TAG_LIST_SEPARATOR),
+    ]
+}
+
+fn entry_record(entry: &TimeEntry) -> Vec<String> {
+    vec![
+        entry.id.clone(),
+        entry.task_id.clone(),
+        entry.started_at.clone(),
+        opt(&entry.ended_at).to_string(),
+        opt(&entry.note).to_string(),
+        entry.created_at.clone(),
+        entry.updated_at.clone(),
+        opt(&entry.tz).to_string(),
+        entry.billable.to_string(),
+    ]
+}
+
+/// Stream into a temp file next to `path` and rename it into place once
+/// complete, so a failed export never leaves a truncated file behind.
+pub fn write_streamed(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> CommandResult<()>,
+) -> CommandResult<()> {
+    let tmp = path.with_extension("tmp");
+    let file = File::create(&tmp).map_err(write_err(&tmp))?;
+    let mut out = BufWriter::new(file);
+    let result = write(&mut out).and_then(|()| out.flush().map_err(write_err(&tmp)));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+fn write_csv<T>(
+    path: &Path,
+    header: &[&str],
+    each: impl FnOnce(&mut dyn FnMut(&T) -> CommandResult<()>) -> CommandResult<u64>,
+    record: impl Fn(&T) -> Vec<String>,
+) -> CommandResult<u64> {
+    let mut count = 0;
+    write_streamed(path, |out| {
+        csv::write_record(out, header).map_err(write_err(path))?;
+        count = each(&mut |item| {
+            let fields = record(item);
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            csv::write_record(out, &fields).map_err(write_err(path))
+        })?;
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+/// `export.csv` -> `export.tasks.csv`, for the one-file-per-table CSV layout.
+fn table_path(path: &Path, table: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("export");
+    path.with_file_name(format!("{stem}.{table}.csv"))
+}
+
+fn export_csv(
+    conn: &Connection,
+    path: &Path,
+    scope: ExportScope,
+    bounds: &Bounds,
+) -> CommandResult<ExportSummary> {
+    let mut summary = ExportSummary {
+        files: Vec::new(),
+        tasks: 0,
+        time_entries: 0,
+    };
+    let split = scope == ExportScope::All;
+
+    if scope.tasks() {
+        let file = if split {
+            table_path(path, "tasks")
+        } else {
+            path.to_path_buf()
+        };
+        summary.tasks = write_csv(
+            &file,
+            TASK_FIELDS,
+            |f| each_task(conn, bounds, f),
+            task_record,
+        )?;
+        summary.files.push(file.display().to_string());
+    }
+    if scope.time_entries() {
+        let file = if split {
+            table_path(path, "time_entries")
+        } else {
+            path.to_path_buf()
+        };
+        summary.time_entries = write_csv(
+            &file,
+            ENTRY_FIELDS,
+            |f| each_entry(conn, bounds, f),
+            entry_record,
+        )?;
+        summary.files.push(file.display().to_string());
+    }
+    Ok(summary)
+}
+
+/// Write a JSON array one element at a time.
+fn write_json_array<T: Serialize>(
+    out: &mut BufWriter<File>,
+    path: &Path,
+    key: &str,
+    each: impl FnOnce(&mut dyn FnMut(&T) -> CommandResult<()>) -> CommandResult<u64>,
+) -> CommandResult<u64> {
+    write!(out, ",\n\"{key}\":[").map_err(write_err(path))?;
+    let mut first = true;
+    let count = each(&mut |item| {
+        out.write_all(if first { b"\n" } else { b",\n" })
+            .map_err(write_err(path))?;
+        first = false;
+        serde_json::to_writer(&mut *out, item).map_err(|e| e.to_string().into())
+    })?;
+    out.write_all(b"\n]").map_err(write_err(path))?;
+    Ok(count)
+}
+
+fn export_json(
+    conn: &Connection,
+    path: &Path,
+    scope: ExportScope,
+    bounds: &Bounds,
+) -> CommandResult<ExportSummary> {
+    let mut summary = ExportSummary {
+        files: vec![path.display().to_string()],
+        tasks: 0,
+        time_entries: 0,
+    };
+    write_streamed(path, |out| {
+        write!(
+            out,
+            "{{\"schema_version\":{EXPORT_SCHEMA_VERSION},\"exported_at\":\"{}\"",
+            now_utc()
+        )
+        .map_err(write_err(path))?;
+        if scope.tasks() {
+            summary.tasks = write_json_array(out, path, "tasks", |f| each_task(conn, bounds, f))?;
+        }
+        if scope.time_entries() {
+            summary.time_entries =
+                write_json_array(out, path, "time_entries", |f| each_entry(conn, bounds, f))?;
+        }
+        out.write_all(b"}\n").map_err(write_err(path))
+    })?;
+    Ok(summary)
+}
+
+/// Export tasks and/or time entries to `path`. JSON writes a single file;
+/// CSV with scope `all` writes `<stem>.tasks.csv` and `<stem>.time_entries.csv`
+/// beside `path`. See `TASK_FIELDS` and `ENTRY_FIELDS` for the columns.
+pub fn export(
+    conn: &Connection,
+    path: &Path,
+    format: ExportFormat,
+    scope: ExportScope,
+    range: &DateRange,
+) -> CommandResult<ExportSummary> {
+    let bounds = Bounds::from_range(range)?;
+    match format {
+        ExportFormat::Json => export_json(conn, path, scope, &bounds),
+        ExportFormat::Csv => export_csv(conn, path, scope, &bounds),
+    }
+}
+
+#[tauri::command]
+pub fn export_data(
+    db: State<'_, Db>,
+    path: String,
+    format: ExportFormat,
+    scope: ExportScope,
+    date_range: Option<DateRange>,
+) -> CommandResult<ExportSummary> {
+    let range = date_range.unwrap_or_default();
+    let summary =
+        db.with_conn(|conn| Ok(export(conn, Path::new(&path), format, scope, &range)))??;
+    usage::record(&db, "export", None);
+    Ok(summary)
+}
@@ -0,0 +1,220 @@
+use std::process::{Child, Command};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::pomodoro::{self, PomodoroEngine, PomodoroStatus};
+use crate::usage;
+
+pub const FOCUS_MODE_EVENT: &str = "focus-mode-changed";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FocusModeOptions {
+    pub do_not_disturb: bool,
+    pub inhibit_sleep: bool,
+    pub start_pomodoro: bool,
+    /// Task the pomodoro started with focus mode counts towards.
+    pub task_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FocusModeStatus {
+    pub active: bool,
+    pub do_not_disturb: bool,
+    pub inhibit_sleep: bool,
+    /// The pomodoro engine's state while focus mode is on.
+    pub pomodoro: Option<PomodoroStatus>,
+}
+
+#[derive(Default)]
+struct FocusModeInner {
+    status: FocusModeStatus,
+    /// `systemd-inhibit` child holding the sleep/idle lock while focus mode runs.
+    inhibitor: Option<Child>,
+    /// GNOME `show-banners` value to restore when DND is turned back off.
+    previous_banners: Option<String>,
+    /// Whether entering focus mode started the pomodoro, so leaving stops it.
+    started_pomodoro: bool,
+}
+
+pub struct FocusModeState {
+    inner: Mutex<FocusModeInner>,
+}
+
+impl FocusModeState {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(FocusModeInner::default()),
+        }
+    }
+}
+
+fn start_sleep_inhibitor() -> CommandResult<Child> {
+    if !cfg!(target_os = "linux") {
+        return Err("Sleep inhibition is only supported on Linux".into());
+    }
+    Command::new("systemd-inhibit")
+        .args([
+            "--what=idle:sleep",
+            "--who=DayLight",
+            "--why=Focus mode",
+            "--mode=block",
+            "sleep",
+            "infinity",
+        ])
+        .spawn()
+        .context("Failed to start systemd-inhibit")
+}
+
+fn gsettings(args: &[&str]) -> CommandResult<String> {
+    let output = Command::new("gsettings")
+        .args(args)
+        .output()
+        .context("Failed to run gsettings")?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Enable GNOME's notification banner suppression and return the prior value.
+fn enable_do_not_disturb() -> CommandResult<String> {
+    if !cfg!(target_os = "linux") {
+        return Err("Do Not Disturb is only supported on Linux".into());
+    }
+    let previous = gsettings(&["get", "org.gnome.desktop.notifications", "show-banners"])?;
+    gsettings(&[
+        "set",
+        "org.gnome.desktop.notifications",
+        "show-banners",
+        "false",
+    ])?;
+    Ok(previous)
+}
+
+fn restore_do_not_disturb(previous: &str) {
+    if let Err(error) = gsettings(&[
+        "set",
+        "org.gnome.desktop.notifications",
+        "show-banners",
+        previous,
+    ]) {
+        tracing::warn!("failed to restore notification banners: {error}");
+    }
+}
+
+/// `status` with the pomodoro as the engine has it now.
+fn with_pomodoro(app: &AppHandle, status: &FocusModeStatus) -> FocusModeStatus {
+    let mut status = status.clone();
+    if status.active {
+        status.pomodoro = pomodoro::get_pomodoro(app.state::<PomodoroEngine>()).ok();
+    }
+    status
+}
+
+/// Switch the main window into fullscreen, always-on-top "deep work" mode.
+/// DND and sleep inhibition are best effort: failures are reported in the
+/// returned status rather than aborting focus mode.
+#[tauri::command]
+pub fn enter_focus_mode(
+    app: AppHandle,
+    state: State<'_, FocusModeState>,
+    options: FocusModeOptions,
+) -> CommandResult<FocusModeStatus> {
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| CommandError::not_found("Main window not found"))?;
+
+    let mut inner = state.inner.lock().map_err(|_| "Lock poisoned")?;
+    if inner.status.active {
+        return Ok(with_pomodoro(&app, &inner.status));
+    }
+
+    window
+        .set_fullscreen(true)
+        .map_err(|e| format!("Failed to enter fullscreen: {e}"))?;
+    window
+        .set_always_on_top(true)
+        .map_err(|e| format!("Failed to set always on top: {e}"))?;
+
+    if options.inhibit_sleep {
+        match start_sleep_inhibitor() {
+            Ok(child) => inner.inhibitor = Some(child),
+            Err(error) => tracing::warn!("{error}"),
+        }
+    }
+
+    if options.do_not_disturb {
+        match enable_do_not_disturb() {
+            Ok(previous) => inner.previous_banners = Some(previous),
+            Err(error) => tracing::warn!("{error}"),
+        }
+    }
+
+    if options.start_pomodoro {
+        match pomodoro::start_pomodoro(app.clone(), app.state::<Db>(), options.task_id) {
+            Ok(_) => inner.started_pomodoro = true,
+            Err(error) => tracing::warn!("failed to start pomodoro: {error}"),
+        }
+    }
+
+    inner.status = FocusModeStatus {
+        active: true,
+        do_not_disturb: inner.previous_banners.is_some(),
+        inhibit_sleep: inner.inhibitor.is_some(),
+        pomodoro: None,
+    };
+    usage::record(&app.state::<Db>(), "focus_mode", None);
+
+    let status = with_pomodoro(&app, &inner.status);
+    let _ = app.emit(FOCUS_MODE_EVENT, status.clone());
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn exit_focus_mode(
+    app: AppHandle,
+    state: State<'_, FocusModeState>,
+) -> CommandResult<FocusModeStatus> {
+    let mut inner = state.inner.lock().map_err(|_| "Lock poisoned")?;
+    if !inner.status.active {
+        return Ok(inner.status.clone());
+    }
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.set_always_on_top(false);
+        let _ = window.set_fullscreen(false);
+    }
+
+    if let Some(mut child) = inner.inhibitor.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    if let Some(previous) = inner.previous_banners.take() {
+        restore_do_not_disturb(&previous);
+    }
+
+    if std::mem::take(&mut inner.started_pomodoro) {
+        if let Err(error) = pomodoro::stop_pomodoro(app.clone()) {
+            tracing::warn!("failed to stop pomodoro: {error}");
+        }
+    }
+
+    inner.status = FocusModeStatus::default();
+    let _ = app.emit(FOCUS_MODE_EVENT, inner.status.clone());
+    Ok(inner.status.clone())
+}
+
+#[tauri::command]
+pub fn get_focus_mode(
+    app: AppHandle,
+    state: State<'_, FocusModeState>,
+) -> CommandResult<FocusModeStatus> {
+    let inner = state.inner.lock().map_err(|_| "Lock poisoned")?;
+    Ok(with_pomodoro(&app, &inner.status))
+}
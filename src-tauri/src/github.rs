@@ -0,0 +1,900 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, State};
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::crdt;
+use crate::credentials;
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::http;
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{self, Capabilities, SyncFuture, SyncProvider};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+
+/// `external_refs.source` for tasks imported from GitHub; the external id
+/// is the issue or pull request URL.
+const SOURCE: &str = "github";
+
+const GITHUB_API_URL: &str = "https://api.github.com/graphql";
+/// GitHub turns away requests without one.
+const USER_AGENT: &str = "DayLight";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const PAGE_SIZE: usize = 50;
+/// GitHub's search stops at 1000 results.
+const MAX_PAGES: usize = 20;
+/// Most node ids GitHub looks up in one query.
+const MAX_NODES: usize = 100;
+
+pub const KIND_ISSUE: &str = "issue";
+/// A pull request the user was asked to review.
+pub const KIND_REVIEW: &str = "review";
+
+/// Open issues assigned to the user.
+const ISSUE_SEARCH: &str = "is:open is:issue archived:false assignee:@me";
+/// Open pull requests waiting on the user's review.
+const REVIEW_SEARCH: &str = "is:open is:pr archived:false review-requested:@me";
+const STATE_OPEN: &str = "OPEN";
+
+const ITEM_FIELDS: &str = "__typename
+    ... on Issue { id number title url state updatedAt repository { name nameWithOwner } }
+    ... on PullRequest { id number title url state updatedAt repository { name nameWithOwner } }";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GithubAccount {
+    pub id: String,
+    pub name: String,
+    /// The GraphQL endpoint: GitHub's, or a GitHub Enterprise server's.
+    pub api_url: String,
+    /// Where imported tasks go; `None` files them under a project named
+    /// after their repository.
+    pub project: Option<String>,
+    /// Whether pull requests waiting on the user's review are imported
+    /// too.
+    pub include_reviews: bool,
+    pub last_synced_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewGithubAccount {
+    /// Defaults to the GitHub login.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// A personal access token that can read issues and pull requests.
+    pub token: String,
+    /// The address of a GitHub Enterprise server; GitHub itself when
+    /// missing.
+    #[serde(default)]
+    pub server_url: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub include_reviews: Option<bool>,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// goes back to a project per repository.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GithubAccountPatch {
+    pub name: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    pub include_reviews: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GithubSyncReport {
+    pub account_id: String,
+    pub name: String,
+    /// Tasks made for new issues and review requests.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks completed because their issue was closed or their review
+    /// request was dealt with.
+    pub completed: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for GithubSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.completed
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteRepository {
+    name: String,
+    name_with_owner: String,
+}
+
+/// An issue or pull request.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteItem {
+    id: String,
+    number: i64,
+    #[serde(default)]
+    title: String,
+    url: String,
+    /// `OPEN` or `CLOSED`, or `MERGED` for pull requests.
+    state: String,
+    updated_at: String,
+    repository: RemoteRepository,
+}
+
+impl RemoteItem {
+    fn is_open(&self) -> bool {
+        self.state == STATE_OPEN
+    }
+
+    /// When GitHub last changed the item, in milliseconds, as the clocks
+    /// of local edits are.
+    fn modified_ms(&self) -> i64 {
+        parse_utc(&self.updated_at).map_or(0, |at| at.timestamp_millis())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Viewer {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ViewerData {
+    viewer: Viewer,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    #[serde(default)]
+    end_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResults {
+    page_info: PageInfo,
+    #[serde(default)]
+    nodes: Vec<Option<RemoteItem>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchData {
+    search: SearchResults,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodesData {
+    #[serde(default)]
+    nodes: Vec<Option<RemoteItem>>,
+}
+
+fn row_to_account(row: &Row) -> rusqlite::Result<GithubAccount> {
+    Ok(GithubAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        api_url: row.get(2)?,
+        project: row.get(3)?,
+        include_reviews: row.get(4)?,
+        last_synced_at: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+const ACCOUNT_COLUMNS: &str =
+    "id, name, api_url, project, include_reviews, last_synced_at, created_at, updated_at";
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<GithubAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM github_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<GithubAccount>> {
+    conn.query_row(
+        &format!("SELECT {ACCOUNT_COLUMNS} FROM github_accounts WHERE id = ?1"),
+        params![id],
+        row_to_account,
+    )
+    .optional()
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("github:{account_id}")
+}
+
+/// The GraphQL endpoint of the GitHub Enterprise server at `value`, or
+/// GitHub's when `None`.
+fn api_url(value: Option<&str>) -> CommandResult<String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(GITHUB_API_URL.to_string());
+    };
+    let value = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("https://{value}")
+    };
+    let url = Url::parse(&value)
+        .map_err(|e| CommandError::invalid(format!("Invalid server URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(CommandError::invalid(
+            "The server URL must start with http:// or https://",
+        ));
+    }
+    if url.host_str() == Some("github.com") {
+        return Ok(GITHUB_API_URL.to_string());
+    }
+    let mut url = url.join("/api/graphql").map_err(|e| e.to_string())?;
+    url.set_query(None);
+    Ok(url.to_string())
+}
+
+/// A connection to the GraphQL API with one account's token.
+struct Api {
+    client: Client,
+    url: String,
+    token: String,
+}
+
+impl Api {
+    fn new(url: String, token: String) -> CommandResult<Self> {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(USER_AGENT)
+            .build()?;
+        Ok(Self { client, url, token })
+    }
+
+    fn connect(account: &GithubAccount) -> CommandResult<Self> {
+        Self::new(
+            account.api_url.clone(),
+            credentials::require(&keyring_name(&account.id), "access token")?,
+        )
+    }
+
+    async fn query<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> CommandResult<T> {
+        rate_limit::check(SOURCE, "GitHub")?;
+        let body = json!({ "query": query, "variables": variables });
+        let response = self
+            .client
+            .post(&self.url)
+            .bearer_auth(&self.token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .context("GitHub")?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED => {
+                return Err(CommandError::unauthorized(
+                    "GitHub refused the access token; connect the account again",
+                )
+                .with_provider(SOURCE))
+            }
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS => {
+                return Err(rate_limit::limited(SOURCE, "GitHub", response.headers()))
+            }
+            status if !status.is_success() => {
+                return Err(CommandError::status(
+                    status,
+                    format!("GitHub: HTTP {}", status.as_u16()),
+                )
+                .with_provider(SOURCE))
+            }
+            _ => {}
+        }
+        let result: GraphqlResponse<T> = http::read_json(response).await.context("GitHub")?;
+        match (result.data, result.errors.first()) {
+            (Some(data), None) => Ok(data),
+            (_, Some(error)) => Err(format!("GitHub: {}", error.message).into()),
+            (None, None) => Err("GitHub sent back nothing".into()),
+        }
+    }
+
+    /// Ask who the token belongs to, for the account health check; the
+    /// response says which scopes a classic token was granted.
+    async fn probe(&self) -> Result<Response, reqwest::Error> {
+        self.client
+            .post(&self.url)
+            .bearer_auth(&self.token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(json!({ "query": "{ viewer { login } }" }).to_string())
+            .send()
+            .await
+    }
+
+    /// Every open issue or pull request matching `search`.
+    async fn search(&self, search: &str) -> CommandResult<Vec<RemoteItem>> {
+        let query = format!(
+            "query($q: String!, $after: String) {{
+                search(query: $q, type: ISSUE, first: {PAGE_SIZE}, after: $after) {{
+                    pageInfo {{ hasNextPage endCursor }}
+                    nodes {{ {ITEM_FIELDS} }}
+                }}
+            }}"
+        );
+        let mut items = Vec::new();
+        let mut after: Option<String> = None;
+        for _ in 0..MAX_PAGES {
+            let page: SearchData = self
+                .query(&query, json!({ "q": search, "after": after }))
+                .await?;
+            items.extend(page.search.nodes.into_iter().flatten());
+            match page.search.page_info.end_cursor {
+                Some(cursor) if page.search.page_info.has_next_page => after = Some(cursor),
+                _ => break,
+            }
+        }
+        Ok(items)
+    }
+
+    /// The issues and pull requests with these node ids, by id. Ones that
+    /// were deleted or can't be seen any more are missing.
+    async fn nodes(&self, ids: &[String]) -> CommandResult<HashMap<String, RemoteItem>> {
+        let query = format!("query($ids: [ID!]!) {{ nodes(ids: $ids) {{ {ITEM_FIELDS} }} }}");
+        let mut found = HashMap::new();
+        for chunk in ids.chunks(MAX_NODES) {
+            let data: NodesData = self.query(&query, json!({ "ids": chunk })).await?;
+            for item in data.nodes.into_iter().flatten() {
+                found.insert(item.id.clone(), item);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// An item imported before, as of the last sync.
+#[derive(Debug, Clone)]
+struct ItemRow {
+    task_id: String,
+    kind: String,
+    state: String,
+    updated_at: String,
+}
+
+fn load_items(conn: &Connection, account_id: &str) -> rusqlite::Result<HashMap<String, ItemRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT node_id, task_id, kind, state, updated_at FROM github_items
+         WHERE account_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ItemRow {
+                task_id: row.get(1)?,
+                kind: row.get(2)?,
+                state: row.get(3)?,
+                updated_at: row.get(4)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_item(
+    conn: &Connection,
+    account_id: &str,
+    item: &RemoteItem,
+    row: &ItemRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO github_items (account_id, node_id, task_id, kind, url, state, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(account_id, node_id) DO UPDATE SET
+             task_id = excluded.task_id,
+             kind = excluded.kind,
+             url = excluded.url,
+             state = excluded.state,
+             updated_at = excluded.updated_at",
+        params![
+            account_id,
+            item.id,
+            row.task_id,
+            row.kind,
+            item.url,
+            row.state,
+            row.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_item(conn: &Connection, account_id: &str, node_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM github_items WHERE account_id = ?1 AND node_id = ?2",
+        params![account_id, node_id],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = Some(now_utc());
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// The task title for `item`: a review request says so.
+fn item_title(item: &RemoteItem, kind: &str) -> String {
+    let title = item.title.trim();
+    let title = if title.is_empty() {
+        format!("{}#{}", item.repository.name_with_owner, item.number)
+    } else {
+        title.to_string()
+    };
+    if kind == KIND_REVIEW {
+        format!("Review: {title}")
+    } else {
+        title
+    }
+}
+
+/// Create or update the task for `item`, done when `done`. The title and
+/// status edited here after GitHub last changed the item keep the local
+/// value. New tasks link back to the item in their description. Returns
+/// the task and whether it was created, or `None` when nothing changed.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    item: &RemoteItem,
+    kind: &str,
+    done: bool,
+    project: Option<&str>,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let title = item_title(item, kind);
+    let Some(mut task) = existing else {
+        let project = project.unwrap_or(&item.repository.name);
+        let input = NewTask {
+            title: title.clone(),
+            description: Some(item.url.clone()),
+            project: Some(project.to_string()),
+            priority: None,
+            due: None,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let task = task_store::insert_task(conn, &input, title)?;
+        return Ok(Some((task, true)));
+    };
+
+    let modified = item.modified_ms();
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| stamps.get(field).is_none_or(|s| s.clock <= modified);
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("status") && done != (task.status == STATUS_DONE) {
+        set_done(&mut task, done);
+    }
+    if task.title == before.title && task.status == before.status {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    Ok(Some((task, false)))
+}
+
+/// Apply the open items the searches found, `listed` with their kind, and
+/// `refreshed`, the current state of items imported before that the
+/// searches no longer find. Closed items complete their task, as do
+/// review requests that were dealt with; either stops being tracked.
+fn merge_remote(
+    conn: &mut Connection,
+    account: &GithubAccount,
+    listed: &[(RemoteItem, &str)],
+    refreshed: &HashMap<String, RemoteItem>,
+    report: &mut GithubSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let known = load_items(&tx, &account.id)?;
+    let project = account.project.as_deref();
+
+    for (item, kind) in listed {
+        let row = known.get(&item.id);
+        if row.is_some_and(|r| r.updated_at == item.updated_at && r.state == item.state) {
+            continue;
+        }
+        let task_id = match row {
+            Some(row) => Some(row.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, item.url],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if task_id.is_some() && existing.is_none() {
+            // Deleted here: it stays deleted.
+            continue;
+        }
+        let task = match write_remote(&tx, existing, item, kind, false, project)? {
+            Some((task, true)) => {
+                report.created += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, item.url, task.id, now_utc()],
+                )?;
+                task.id
+            }
+            Some((task, false)) => {
+                report.updated += 1;
+                task.id
+            }
+            None => task_id.unwrap_or_default(),
+        };
+        let row = ItemRow {
+            task_id: task,
+            kind: kind.to_string(),
+            state: item.state.clone(),
+            updated_at: item.updated_at.clone(),
+        };
+        save_item(&tx, &account.id, item, &row)?;
+    }
+
+    let listed: HashSet<&str> = listed.iter().map(|(item, _)| item.id.as_str()).collect();
+    for (node_id, row) in &known {
+        if listed.contains(node_id.as_str()) {
+            continue;
+        }
+        // An issue no longer assigned stays as it is; a closed one, or a
+        // review no longer asked for, is done.
+        let item = refreshed.get(node_id);
+        let done = match item {
+            Some(item) => !item.is_open() || row.kind == KIND_REVIEW,
+            None => false,
+        };
+        if let (Some(item), true) = (item, done) {
+            if let Some(task) = task_store::find_task(&tx, &row.task_id)? {
+                let was_done = task.status == STATUS_DONE;
+                if let Some((task, _)) =
+                    write_remote(&tx, Some(task), item, &row.kind, true, project)?
+                {
+                    if !was_done && task.status == STATUS_DONE {
+                        report.completed += 1;
+                    } else {
+                        report.updated += 1;
+                    }
+                }
+            }
+        }
+        forget_item(&tx, &account.id, node_id)?;
+    }
+    tx.commit()
+}
+
+async fn sync_account(
+    db: &Db,
+    api: &Api,
+    account: &GithubAccount,
+) -> CommandResult<GithubSyncReport> {
+    let mut report = GithubSyncReport {
+        account_id: account.id.clone(),
+        name: account.name.clone(),
+        ..GithubSyncReport::default()
+    };
+    let mut listed: Vec<(RemoteItem, &str)> = api
+        .search(ISSUE_SEARCH)
+        .await?
+        .into_iter()
+        .map(|item| (item, KIND_ISSUE))
+        .collect();
+    if account.include_reviews {
+        listed.extend(
+            api.search(REVIEW_SEARCH)
+                .await?
+                .into_iter()
+                .map(|item| (item, KIND_REVIEW)),
+        );
+    }
+    let known = db.with_conn(|conn| load_items(conn, &account.id))?;
+    let found: HashSet<&str> = listed.iter().map(|(item, _)| item.id.as_str()).collect();
+    let missing: Vec<String> = known
+        .keys()
+        .filter(|id| !found.contains(id.as_str()))
+        .cloned()
+        .collect();
+    let refreshed = if missing.is_empty() {
+        HashMap::new()
+    } else {
+        api.nodes(&missing).await?
+    };
+    db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(conn, account, &listed, &refreshed, &mut report)
+        })??;
+        conn.execute(
+            "UPDATE github_accounts SET last_synced_at = ?2 WHERE id = ?1",
+            params![account.id, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+/// Check an access token and save the account. Nothing is imported until
+/// `sync_github` runs.
+#[tauri::command]
+pub async fn connect_github_account(
+    db: State<'_, Db>,
+    input: NewGithubAccount,
+) -> CommandResult<GithubAccount> {
+    let token = input.token.trim().to_string();
+    if token.is_empty() {
+        return Err(CommandError::invalid("Enter the GitHub access token"));
+    }
+    let project = match input.project.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(name) => Some(projects::normalize_name(name)?),
+    };
+    let url = api_url(input.server_url.as_deref())?;
+    let api = Api::new(url.clone(), token.clone())?;
+    let data: ViewerData = api.query("query { viewer { login } }", json!({})).await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(data.viewer.login);
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO github_accounts (id, name, api_url, project, include_reviews,
+                 created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+            params![
+                id,
+                name,
+                url,
+                project,
+                input.include_reviews.unwrap_or(true),
+                now
+            ],
+        )?;
+        find_account(conn, &id)
+    });
+    match stored {
+        Ok(Some(account)) => Ok(account),
+        Ok(None) => Err("Failed to save account".into()),
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_github_accounts(db: State<'_, Db>) -> CommandResult<Vec<GithubAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Rename an account, change where its tasks go, or turn importing review
+/// requests on or off. Tasks imported before stay where they are.
+#[tauri::command]
+pub fn update_github_account(
+    db: State<'_, Db>,
+    id: String,
+    patch: GithubAccountPatch,
+) -> CommandResult<GithubAccount> {
+    let name = match patch.name.map(|n| n.trim().to_string()) {
+        Some(name) if name.is_empty() => return Err(CommandError::invalid("Enter a name")),
+        name => name,
+    };
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut account) = find_account(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "GitHub account not found: {id}"
+            ))));
+        };
+        if let Some(name) = name {
+            account.name = name;
+        }
+        if let Some(project) = project {
+            account.project = project;
+        }
+        if let Some(include) = patch.include_reviews {
+            account.include_reviews = include;
+        }
+        account.updated_at = now_utc();
+        conn.execute(
+            "UPDATE github_accounts
+             SET name = ?2, project = ?3, include_reviews = ?4, updated_at = ?5
+             WHERE id = ?1",
+            params![
+                account.id,
+                account.name,
+                account.project,
+                account.include_reviews,
+                account.updated_at
+            ],
+        )?;
+        Ok(Ok(account))
+    })?
+}
+
+/// Forget an account and which items it imported. Its tasks stay,
+/// unlinked.
+#[tauri::command]
+pub fn remove_github_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db
+        .with_conn(|conn| conn.execute("DELETE FROM github_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "GitHub account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+pub struct Github;
+
+impl SyncProvider for Github {
+    type Report = Vec<GithubSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "GitHub"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: false,
+            conflicts: false,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(&account)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                match Api::connect(&account) {
+                    Ok(api) => {
+                        if let Some(response) = health.answer("GitHub", api.probe().await) {
+                            // Fine-grained tokens don't list their scopes.
+                            if let Some(scopes) = response
+                                .headers()
+                                .get("x-oauth-scopes")
+                                .and_then(|v| v.to_str().ok())
+                            {
+                                health.scopes(scopes, &[("repo", "repo")]);
+                            }
+                        }
+                    }
+                    Err(e) => health.no_credentials(&e),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress))
+    }
+}
+
+/// Import the issues assigned to the user, and the pull requests waiting
+/// on their review, of `account_id` or every account. Closing an issue on
+/// GitHub completes its task. One account failing doesn't stop the
+/// others; its error is in its report.
+#[tauri::command]
+pub async fn sync_github(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<GithubSyncReport>> {
+    sync_provider::run_command(&app, &Github, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+) -> CommandResult<Vec<GithubSyncReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let result = match Api::connect(&account) {
+            Ok(api) => sync_account(db, &api, &account).await,
+            Err(e) => Err(e),
+        };
+        let report = result.unwrap_or_else(|e| {
+            tracing::warn!("sync of {} failed: {e}", account.name);
+            GithubSyncReport {
+                account_id: account.id.clone(),
+                name: account.name.clone(),
+                errors: vec![e.to_string()],
+                ..GithubSyncReport::default()
+            }
+        });
+        progress.record(&report.name, &report);
+        reports.push(report);
+    }
+    Ok(reports)
+}
@@ -0,0 +1,966 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::crdt;
+use crate::credentials;
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::http;
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{self, Capabilities, SyncFuture, SyncProvider};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+
+/// `external_refs.source` for issues imported from GitLab; the external id
+/// is the issue URL. Todos aren't linked there, since theirs point at the
+/// issue or merge request they're about.
+const SOURCE: &str = "gitlab";
+
+const GITLAB_URL: &str = "https://gitlab.com";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const PAGE_SIZE: usize = 100;
+const MAX_PAGES: usize = 20;
+
+pub const KIND_ISSUE: &str = "issue";
+/// A pending GitLab todo. It lands in the inbox, outside any project.
+pub const KIND_TODO: &str = "todo";
+
+const STATE_OPENED: &str = "opened";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GitlabAccount {
+    pub id: String,
+    pub name: String,
+    /// GitLab's address, or a self-hosted server's.
+    pub base_url: String,
+    /// Where imported issues go; `None` files them under a project named
+    /// after their GitLab project. Todos always go to the inbox.
+    pub project: Option<String>,
+    /// Whether pending todos are imported too.
+    pub include_todos: bool,
+    pub last_synced_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewGitlabAccount {
+    /// Defaults to the GitLab username.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// A personal access token with the `read_api` scope.
+    pub token: String,
+    /// The address of a self-hosted server; gitlab.com when missing.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub include_todos: Option<bool>,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// goes back to a project per GitLab project.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GitlabAccountPatch {
+    pub name: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    pub include_todos: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GitlabSyncReport {
+    pub account_id: String,
+    pub name: String,
+    /// Tasks made for new issues and todos.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks completed because their issue was closed or their todo was
+    /// marked done.
+    pub completed: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for GitlabSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.completed
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct References {
+    /// `group/project#12`.
+    #[serde(default)]
+    full: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteIssue {
+    id: i64,
+    iid: i64,
+    project_id: i64,
+    #[serde(default)]
+    title: String,
+    web_url: String,
+    /// `opened` or `closed`.
+    state: String,
+    updated_at: String,
+    #[serde(default)]
+    references: References,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct TodoTarget {
+    #[serde(default)]
+    title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteTodo {
+    id: i64,
+    /// Why the todo was made: `assigned`, `mentioned`, `review_requested`
+    /// and so on.
+    action_name: String,
+    #[serde(default)]
+    target: Option<TodoTarget>,
+    #[serde(default)]
+    target_url: String,
+    #[serde(default)]
+    body: String,
+    created_at: String,
+    #[serde(default)]
+    updated_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteUser {
+    username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteToken {
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+/// An issue or todo, as the sync handles both.
+#[derive(Debug, Clone)]
+struct Item {
+    /// `issue:<id>` or `todo:<id>`.
+    key: String,
+    kind: &'static str,
+    title: String,
+    url: String,
+    /// The GitLab project an issue is in; `None` for todos.
+    project_name: Option<String>,
+    project_id: Option<i64>,
+    iid: Option<i64>,
+    open: bool,
+    updated_at: String,
+}
+
+impl Item {
+    fn from_issue(issue: RemoteIssue) -> Self {
+        let path = issue
+            .references
+            .full
+            .split('#')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let title = match issue.title.trim() {
+            "" => issue.references.full.clone(),
+            title => title.to_string(),
+        };
+        Item {
+            key: format!("issue:{}", issue.id),
+            kind: KIND_ISSUE,
+            title,
+            url: issue.web_url,
+            project_name: path
+                .rsplit('/')
+                .next()
+                .filter(|n| !n.is_empty())
+                .map(String::from),
+            project_id: Some(issue.project_id),
+            iid: Some(issue.iid),
+            open: issue.state == STATE_OPENED,
+            updated_at: issue.updated_at,
+        }
+    }
+
+    fn from_todo(todo: RemoteTodo) -> Self {
+        let subject = todo
+            .target
+            .and_then(|t| t.title)
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| todo.body.trim().to_string());
+        Item {
+            key: format!("todo:{}", todo.id),
+            kind: KIND_TODO,
+            title: format!("{}: {subject}", action_label(&todo.action_name)),
+            url: todo.target_url,
+            project_name: None,
+            project_id: None,
+            iid: None,
+            open: true,
+            updated_at: todo.updated_at.unwrap_or(todo.created_at),
+        }
+    }
+
+    /// When GitLab last changed the item, in milliseconds, as the clocks of
+    /// local edits are.
+    fn modified_ms(&self) -> i64 {
+        parse_utc(&self.updated_at).map_or(0, |at| at.timestamp_millis())
+    }
+}
+
+/// What a todo asks for, to start its task's title with.
+fn action_label(action: &str) -> String {
+    match action {
+        "assigned" => "Assigned".to_string(),
+        "mentioned" | "directly_addressed" => "Mentioned".to_string(),
+        "review_requested" => "Review".to_string(),
+        "approval_required" => "Approve".to_string(),
+        "build_failed" => "Pipeline failed".to_string(),
+        "unmergeable" => "Can't merge".to_string(),
+        "marked" => "To do".to_string(),
+        other => {
+            let words = other.replace('_', " ");
+            let mut chars = words.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => "To do".to_string(),
+            }
+        }
+    }
+}
+
+fn row_to_account(row: &Row) -> rusqlite::Result<GitlabAccount> {
+    Ok(GitlabAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        base_url: row.get(2)?,
+        project: row.get(3)?,
+        include_todos: row.get(4)?,
+        last_synced_at: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+const ACCOUNT_COLUMNS: &str =
+    "id, name, base_url, project, include_todos, last_synced_at, created_at, updated_at";
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<GitlabAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM gitlab_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<GitlabAccount>> {
+    conn.query_row(
+        &format!("SELECT {ACCOUNT_COLUMNS} FROM gitlab_accounts WHERE id = ?1"),
+        params![id],
+        row_to_account,
+    )
+    .optional()
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("gitlab:{account_id}")
+}
+
+/// The server address in `value`, without a trailing slash or `/api/v4`,
+/// or gitlab.com's when `None`. A server under a path keeps it.
+fn base_url(value: Option<&str>) -> CommandResult<String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(GITLAB_URL.to_string());
+    };
+    let value = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("https://{value}")
+    };
+    let mut url = Url::parse(&value)
+        .map_err(|e| CommandError::invalid(format!("Invalid GitLab URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(CommandError::invalid(
+            "The GitLab URL must start with http:// or https://",
+        ));
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    let path = url.path().trim_end_matches('/');
+    let path = path.strip_suffix("/api/v4").unwrap_or(path).to_string();
+    url.set_path(&path);
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// A connection to the REST API with one account's token.
+struct Api {
+    client: Client,
+    base: String,
+    token: String,
+}
+
+impl Api {
+    fn new(base_url: &str, token: String) -> CommandResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self {
+            client,
+            base: format!("{base_url}/api/v4"),
+            token,
+        })
+    }
+
+    fn connect(account: &GitlabAccount) -> CommandResult<Self> {
+        Self::new(
+            &account.base_url,
+            credentials::require(&keyring_name(&account.id), "access token")?,
+        )
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.client
+            .get(format!("{}/{path}", self.base))
+            .header("PRIVATE-TOKEN", &self.token)
+    }
+
+    /// Send `request`; `None` when what it asked for isn't there.
+    async fn send(&self, request: RequestBuilder) -> CommandResult<Option<Response>> {
+        rate_limit::check(SOURCE, "GitLab")?;
+        let response = request.send().await.context("GitLab")?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED => Err(CommandError::unauthorized(
+                "GitLab refused the access token; connect the account again",
+            )
+            .with_provider(SOURCE)),
+            StatusCode::TOO_MANY_REQUESTS => {
+                Err(rate_limit::limited(SOURCE, "GitLab", response.headers()))
+            }
+            StatusCode::NOT_FOUND | StatusCode::FORBIDDEN => Ok(None),
+            status if !status.is_success() => Err(CommandError::status(
+                status,
+                format!("GitLab: HTTP {}", status.as_u16()),
+            )
+            .with_provider(SOURCE)),
+            _ => Ok(Some(response)),
+        }
+    }
+
+    async fn user(&self) -> CommandResult<RemoteUser> {
+        let Some(response) = self.send(self.get("user")).await? else {
+            return Err(CommandError::unauthorized(
+                "GitLab: no user for this access token",
+            ));
+        };
+        http::read_json(response).await.context("GitLab")
+    }
+
+    /// The scopes the access token was granted; `None` when the server
+    /// won't say.
+    async fn token_scopes(&self) -> Option<Vec<String>> {
+        let response = self
+            .send(self.get("personal_access_tokens/self"))
+            .await
+            .ok()??;
+        http::read_json::<RemoteToken>(response)
+            .await
+            .ok()
+            .map(|t| t.scopes)
+    }
+
+    /// Every page of the list at `path`.
+    async fn list<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> CommandResult<Vec<T>> {
+        let mut items = Vec::new();
+        let mut page = "1".to_string();
+        for _ in 0..MAX_PAGES {
+            let request = self
+                .get(path)
+                .query(query)
+                .query(&[("per_page", PAGE_SIZE.to_string()), ("page", page.clone())]);
+            let Some(response) = self.send(request).await? else {
+                return Err(format!("GitLab: can't read {path}").into());
+            };
+            let next = response
+                .headers()
+                .get("x-next-page")
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from);
+            let batch: Vec<T> = http::read_json(response).await.context("GitLab")?;
+            items.extend(batch);
+            match next {
+                Some(next) => page = next,
+                None => break,
+            }
+        }
+        Ok(items)
+    }
+
+    /// The open issues assigned to the user.
+    async fn assigned_issues(&self) -> CommandResult<Vec<RemoteIssue>> {
+        self.list(
+            "issues",
+            &[("scope", "assigned_to_me"), ("state", STATE_OPENED)],
+        )
+        .await
+    }
+
+    async fn pending_todos(&self) -> CommandResult<Vec<RemoteTodo>> {
+        self.list("todos", &[("state", "pending")]).await
+    }
+
+    /// The issue `iid` of project `project_id`, or `None` once it was
+    /// deleted or can't be seen any more.
+    async fn issue(&self, project_id: i64, iid: i64) -> CommandResult<Option<RemoteIssue>> {
+        let request = self.get(&format!("projects/{project_id}/issues/{iid}"));
+        match self.send(request).await? {
+            Some(response) => http::read_json(response).await.map(Some).context("GitLab"),
+            None => Ok(None),
+        }
+    }
+}
+
+/// An item imported before, as of the last sync.
+#[derive(Debug, Clone)]
+struct ItemRow {
+    task_id: String,
+    kind: String,
+    project_id: Option<i64>,
+    iid: Option<i64>,
+    updated_at: String,
+}
+
+fn load_items(conn: &Connection, account_id: &str) -> rusqlite::Result<HashMap<String, ItemRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT item_key, task_id, kind, project_id, iid, updated_at FROM gitlab_items
+         WHERE account_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ItemRow {
+                task_id: row.get(1)?,
+                kind: row.get(2)?,
+                project_id: row.get(3)?,
+                iid: row.get(4)?,
+                updated_at: row.get(5)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_item(
+    conn: &Connection,
+    account_id: &str,
+    item: &Item,
+    task_id: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO gitlab_items (account_id, item_key, task_id, kind, url, project_id, iid,
+             updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(account_id, item_key) DO UPDATE SET
+             task_id = excluded.task_id,
+             url = excluded.url,
+             project_id = excluded.project_id,
+             iid = excluded.iid,
+             updated_at = excluded.updated_at",
+        params![
+            account_id,
+            item.key,
+            task_id,
+            item.kind,
+            item.url,
+            item.project_id,
+            item.iid,
+            item.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_item(conn: &Connection, account_id: &str, key: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM gitlab_items WHERE account_id = ?1 AND item_key = ?2",
+        params![account_id, key],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = Some(now_utc());
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// Create or update the task for `item`, done unless the item is open.
+/// The title and status edited here after GitLab last changed the item
+/// keep the local value. New tasks link back to the item in their
+/// description. Returns the task and whether it was created, or `None`
+/// when nothing changed.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    item: &Item,
+    project: Option<&str>,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let Some(mut task) = existing else {
+        // Todos land in the inbox.
+        let project = match item.kind {
+            KIND_ISSUE => project.or(item.project_name.as_deref()),
+            _ => None,
+        };
+        let input = NewTask {
+            title: item.title.clone(),
+            description: Some(item.url.clone()).filter(|u| !u.is_empty()),
+            project: project.map(String::from),
+            priority: None,
+            due: None,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let task = task_store::insert_task(conn, &input, item.title.clone())?;
+        return Ok(Some((task, true)));
+    };
+
+    let modified = item.modified_ms();
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| stamps.get(field).is_none_or(|s| s.clock <= modified);
+    let before = task.clone();
+    if take("title") {
+        task.title = item.title.clone();
+    }
+    if take("status") && item.open == (task.status == STATUS_DONE) {
+        set_done(&mut task, !item.open);
+    }
+    if task.title == before.title && task.status == before.status {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    Ok(Some((task, false)))
+}
+
+/// Apply the open issues and pending todos GitLab listed, and `refreshed`,
+/// the current state of issues imported before that aren't listed any
+/// more. A closed issue completes its task, as does a todo that stopped
+/// being pending; either stops being tracked, as does an issue that was
+/// only unassigned.
+fn merge_remote(
+    conn: &mut Connection,
+    account: &GitlabAccount,
+    listed: &[Item],
+    refreshed: &HashMap<String, Item>,
+    report: &mut GitlabSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let known = load_items(&tx, &account.id)?;
+    let project = account.project.as_deref();
+
+    for item in listed {
+        let row = known.get(&item.key);
+        if row.is_some_and(|r| r.updated_at == item.updated_at) {
+            continue;
+        }
+        let task_id = match row {
+            Some(row) => Some(row.task_id.clone()),
+            None if item.kind == KIND_ISSUE => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, item.url],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+            None => None,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if task_id.is_some() && existing.is_none() {
+            // Deleted here: it stays deleted.
+            continue;
+        }
+        let task = match write_remote(&tx, existing, item, project)? {
+            Some((task, true)) => {
+                report.created += 1;
+                if item.kind == KIND_ISSUE {
+                    tx.execute(
+                        "INSERT OR IGNORE INTO external_refs (source, external_id, task_id,
+                             created_at)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        params![SOURCE, item.url, task.id, now_utc()],
+                    )?;
+                }
+                task.id
+            }
+            Some((task, false)) => {
+                report.updated += 1;
+                task.id
+            }
+            None => task_id.unwrap_or_default(),
+        };
+        save_item(&tx, &account.id, item, &task)?;
+    }
+
+    let listed: HashSet<&str> = listed.iter().map(|item| item.key.as_str()).collect();
+    for (key, row) in &known {
+        if listed.contains(key.as_str()) {
+            continue;
+        }
+        let Some(mut task) = task_store::find_task(&tx, &row.task_id)? else {
+            forget_item(&tx, &account.id, key)?;
+            continue;
+        };
+        let was_done = task.status == STATUS_DONE;
+        if row.kind == KIND_TODO {
+            // Done on GitLab, or its issue or merge request was closed.
+            if !was_done {
+                set_done(&mut task, true);
+                task.updated_at = now_utc();
+                task_store::write_task(&tx, &task)?;
+                report.completed += 1;
+            }
+        } else if let Some(item) = refreshed.get(key).filter(|item| !item.open) {
+            if let Some((task, _)) = write_remote(&tx, Some(task), item, project)? {
+                if !was_done && task.status == STATUS_DONE {
+                    report.completed += 1;
+                } else {
+                    report.updated += 1;
+                }
+            }
+        }
+        forget_item(&tx, &account.id, key)?;
+    }
+    tx.commit()
+}
+
+async fn sync_account(
+    db: &Db,
+    api: &Api,
+    account: &GitlabAccount,
+) -> CommandResult<GitlabSyncReport> {
+    let mut report = GitlabSyncReport {
+        account_id: account.id.clone(),
+        name: account.name.clone(),
+        ..GitlabSyncReport::default()
+    };
+    let mut listed: Vec<Item> = api
+        .assigned_issues()
+        .await?
+        .into_iter()
+        .map(Item::from_issue)
+        .collect();
+    if account.include_todos {
+        // A todo about an issue already imported would be a second task
+        // for the same thing.
+        let issues: HashSet<String> = listed.iter().map(|item| item.url.clone()).collect();
+        listed.extend(
+            api.pending_todos()
+                .await?
+                .into_iter()
+                .map(Item::from_todo)
+                .filter(|item| !issues.contains(&item.url)),
+        );
+    }
+
+    let known = db.with_conn(|conn| load_items(conn, &account.id))?;
+    let found: HashSet<&str> = listed.iter().map(|item| item.key.as_str()).collect();
+    let mut refreshed = HashMap::new();
+    for (key, row) in &known {
+        if found.contains(key.as_str()) || row.kind != KIND_ISSUE {
+            continue;
+        }
+        let (Some(project_id), Some(iid)) = (row.project_id, row.iid) else {
+            continue;
+        };
+        match api.issue(project_id, iid).await {
+            Ok(Some(issue)) => {
+                refreshed.insert(key.clone(), Item::from_issue(issue));
+            }
+            Ok(None) => {}
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(conn, account, &listed, &refreshed, &mut report)
+        })??;
+        conn.execute(
+            "UPDATE gitlab_accounts SET last_synced_at = ?2 WHERE id = ?1",
+            params![account.id, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+/// Check an access token and save the account. Nothing is imported until
+/// `sync_gitlab` runs.
+#[tauri::command]
+pub async fn connect_gitlab_account(
+    db: State<'_, Db>,
+    input: NewGitlabAccount,
+) -> CommandResult<GitlabAccount> {
+    let token = input.token.trim().to_string();
+    if token.is_empty() {
+        return Err(CommandError::invalid("Enter the GitLab access token"));
+    }
+    let project = match input.project.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(name) => Some(projects::normalize_name(name)?),
+    };
+    let base = base_url(input.base_url.as_deref())?;
+    let user = Api::new(&base, token.clone())?.user().await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(user.username);
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO gitlab_accounts (id, name, base_url, project, include_todos,
+                 created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+            params![
+                id,
+                name,
+                base,
+                project,
+                input.include_todos.unwrap_or(true),
+                now
+            ],
+        )?;
+        find_account(conn, &id)
+    });
+    match stored {
+        Ok(Some(account)) => Ok(account),
+        Ok(None) => Err("Failed to save account".into()),
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_gitlab_accounts(db: State<'_, Db>) -> CommandResult<Vec<GitlabAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Rename an account, change where its issues go, or turn importing todos
+/// on or off. Tasks imported before stay where they are.
+#[tauri::command]
+pub fn update_gitlab_account(
+    db: State<'_, Db>,
+    id: String,
+    patch: GitlabAccountPatch,
+) -> CommandResult<GitlabAccount> {
+    let name = match patch.name.map(|n| n.trim().to_string()) {
+        Some(name) if name.is_empty() => return Err(CommandError::invalid("Enter a name")),
+        name => name,
+    };
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut account) = find_account(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "GitLab account not found: {id}"
+            ))));
+        };
+        if let Some(name) = name {
+            account.name = name;
+        }
+        if let Some(project) = project {
+            account.project = project;
+        }
+        if let Some(include) = patch.include_todos {
+            account.include_todos = include;
+        }
+        account.updated_at = now_utc();
+        conn.execute(
+            "UPDATE gitlab_accounts
+             SET name = ?2, project = ?3, include_todos = ?4, updated_at = ?5
+             WHERE id = ?1",
+            params![
+                account.id,
+                account.name,
+                account.project,
+                account.include_todos,
+                account.updated_at
+            ],
+        )?;
+        Ok(Ok(account))
+    })?
+}
+
+/// Forget an account and which items it imported. Its tasks stay,
+/// unlinked.
+#[tauri::command]
+pub fn remove_gitlab_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db
+        .with_conn(|conn| conn.execute("DELETE FROM gitlab_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "GitLab account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+pub struct Gitlab;
+
+impl SyncProvider for Gitlab {
+    type Report = Vec<GitlabSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "GitLab"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: false,
+            conflicts: false,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(&account)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                match Api::connect(&account) {
+                    Ok(api) => {
+                        if health
+                            .answer("GitLab", api.get("user").send().await)
+                            .is_some()
+                        {
+                            if let Some(scopes) = api.token_scopes().await {
+                                if !scopes.iter().any(|s| s == "api" || s == "read_api") {
+                                    health.missing_scope("read_api");
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => health.no_credentials(&e),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress))
+    }
+}
+
+/// Import the open issues assigned to the user, and their pending todos,
+/// of `account_id` or every account. Closing an issue on GitLab completes
+/// its task, as does marking a todo done. One account failing doesn't
+/// stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_gitlab(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<GitlabSyncReport>> {
+    sync_provider::run_command(&app, &Gitlab, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+) -> CommandResult<Vec<GitlabSyncReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let result = match Api::connect(&account) {
+            Ok(api) => sync_account(db, &api, &account).await,
+            Err(e) => Err(e),
+        };
+        let report = result.unwrap_or_else(|e| {
+            tracing::warn!("sync of {} failed: {e}", account.name);
+            GitlabSyncReport {
+                account_id: account.id.clone(),
+                name: account.name.clone(),
+                errors: vec![e.to_string()],
+                ..GitlabSyncReport::default()
+            }
+        });
+        progress.record(&report.name, &report);
+        reports.push(report);
+    }
+    Ok(reports)
+}
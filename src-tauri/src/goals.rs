@@ -0,0 +1,448 @@
+use std::time::Duration;
+
+use chrono::{Datelike, Days, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::projects;
+use crate::recurrence;
+use crate::reports::{self, GroupBy, Range, ReportQuery};
+use crate::tags;
+use crate::task_store::double_option;
+
+/// Sent with a `GoalProgress` the first time in a period that a goal is
+/// met, or falls behind.
+pub const GOAL_STATUS_EVENT: &str = "goal-status";
+
+const GOAL_POLL: Duration = Duration::from_secs(60);
+/// A goal can only be at risk once this share of its period has passed.
+const AT_RISK_AFTER: f64 = 0.5;
+/// ...and progress is below this share of the even pace to the target.
+const AT_RISK_PACE: f64 = 0.75;
+
+const GOAL_COLUMNS: &str = "id, name, metric, target, period, project, tag, created_at, updated_at";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalMetric {
+    /// Minutes of time entries in the period.
+    TrackedMinutes,
+    /// Tasks completed in the period.
+    CompletedTasks,
+}
+
+impl GoalMetric {
+    fn as_str(self) -> &'static str {
+        match self {
+            GoalMetric::TrackedMinutes => "tracked_minutes",
+            GoalMetric::CompletedTasks => "completed_tasks",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "completed_tasks" => GoalMetric::CompletedTasks,
+            _ => GoalMetric::TrackedMinutes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalPeriod {
+    Day,
+    /// Weeks start on Monday.
+    Week,
+}
+
+impl GoalPeriod {
+    fn as_str(self) -> &'static str {
+        match self {
+            GoalPeriod::Day => "day",
+            GoalPeriod::Week => "week",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "week" => GoalPeriod::Week,
+            _ => GoalPeriod::Day,
+        }
+    }
+
+    /// First and last day of the period holding `date`.
+    fn bounds(self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            GoalPeriod::Day => (date, date),
+            GoalPeriod::Week => {
+                let monday = date
+                    .checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+                    .unwrap_or(date);
+                (
+                    monday,
+                    monday.checked_add_days(Days::new(6)).unwrap_or(monday),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Goal {
+    pub id: String,
+    pub name: String,
+    pub metric: GoalMetric,
+    pub target: i64,
+    pub period: GoalPeriod,
+    /// Only count time and tasks in this project.
+    pub project: Option<String>,
+    /// Only count time and tasks with this tag.
+    pub tag: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewGoal {
+    pub name: String,
+    pub metric: GoalMetric,
+    pub target: i64,
+    pub period: GoalPeriod,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+/// Partial update. For `project` and `tag`, a missing key leaves the filter
+/// alone while an explicit `null` clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GoalPatch {
+    pub name: Option<String>,
+    pub metric: Option<GoalMetric>,
+    pub target: Option<i64>,
+    pub period: Option<GoalPeriod>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub tag: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalStatus {
+    OnTrack,
+    /// Well behind an even pace with most of the period gone.
+    AtRisk,
+    Met,
+    /// The period is over and the target wasn't reached.
+    Missed,
+}
+
+impl GoalStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            GoalStatus::OnTrack => "on_track",
+            GoalStatus::AtRisk => "at_risk",
+            GoalStatus::Met => "met",
+            GoalStatus::Missed => "missed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    /// First and last day of the period, `YYYY-MM-DD`.
+    pub period_start: String,
+    pub period_end: String,
+    /// Minutes or completed tasks so far, depending on the metric.
+    pub value: i64,
+    /// `value / target`, which can go past 1.
+    pub fraction: f64,
+    /// Share of the period that has passed, from 0 to 1.
+    pub elapsed: f64,
+    pub status: GoalStatus,
+}
+
+fn row_to_goal(row: &Row) -> rusqlite::Result<Goal> {
+    let metric: String = row.get(2)?;
+    let period: String = row.get(4)?;
+    Ok(Goal {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        metric: GoalMetric::parse(&metric),
+        target: row.get(3)?,
+        period: GoalPeriod::parse(&period),
+        project: row.get(5)?,
+        tag: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+/// Check a goal's fields and put them in canonical form.
+fn validate(goal: &mut Goal) -> CommandResult<()> {
+    goal.name = goal.name.trim().to_string();
+    if goal.name.is_empty() {
+        return Err(CommandError::invalid("Goal name cannot be empty"));
+    }
+    if goal.target <= 0 {
+        return Err(CommandError::invalid(
+            "Goal target must be greater than zero",
+        ));
+    }
+    goal.project = goal
+        .project
+        .as_deref()
+        .map(projects::normalize_name)
+        .transpose()?;
+    goal.tag = goal.tag.as_deref().map(tags::normalize_name).transpose()?;
+    Ok(())
+}
+
+pub fn list(conn: &Connection) -> rusqlite::Result<Vec<Goal>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {GOAL_COLUMNS} FROM goals ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_goal)?;
+    rows.collect()
+}
+
+fn find_goal(conn: &Connection, id: &str) -> rusqlite::Result<Option<Goal>> {
+    conn.query_row(
+        &format!("SELECT {GOAL_COLUMNS} FROM goals WHERE id = ?1"),
+        params![id],
+        row_to_goal,
+    )
+    .optional()
+}
+
+fn write_goal(conn: &Connection, goal: &Goal) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO goals ({GOAL_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 metric = excluded.metric,
+                 target = excluded.target,
+                 period = excluded.period,
+                 project = excluded.project,
+                 tag = excluded.tag,
+                 updated_at = excluded.updated_at"
+        ),
+        params![
+            goal.id,
+            goal.name,
+            goal.metric.as_str(),
+            goal.target,
+            goal.period.as_str(),
+            goal.project,
+            goal.tag,
+            goal.created_at,
+            goal.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Where `goal` stands in the period holding `date`, as of now.
+pub fn progress(
+    conn: &Connection,
+    goal: &Goal,
+    date: NaiveDate,
+) -> rusqlite::Result<CommandResult<GoalProgress>> {
+    let (start, end) = goal.period.bounds(date);
+    let query = ReportQuery {
+        from: start.format("%Y-%m-%d").to_string(),
+        to: end.format("%Y-%m-%d").to_string(),
+        group_by: GroupBy::Day,
+        zone: None,
+        project: goal.project.clone(),
+        tag: goal.tag.clone(),
+    };
+    let range = match Range::from_query(&query) {
+        Ok(range) => range,
+        Err(e) => return Ok(Err(e)),
+    };
+    let value = match goal.metric {
+        GoalMetric::TrackedMinutes => {
+            reports::time_report(conn, &query, &range)?.total_seconds / 60
+        }
+        GoalMetric::CompletedTasks => reports::completion_report(conn, &query, &range)?.total,
+    };
+
+    let span = (range.to - range.from).num_seconds().max(1) as f64;
+    let elapsed = ((Utc::now() - range.from).num_seconds() as f64 / span).clamp(0.0, 1.0);
+    let target = goal.target as f64;
+    let status = if value >= goal.target {
+        GoalStatus::Met
+    } else if elapsed >= 1.0 {
+        GoalStatus::Missed
+    } else if elapsed >= AT_RISK_AFTER && (value as f64) < target * elapsed * AT_RISK_PACE {
+        GoalStatus::AtRisk
+    } else {
+        GoalStatus::OnTrack
+    };
+
+    Ok(Ok(GoalProgress {
+        goal: goal.clone(),
+        period_start: query.from,
+        period_end: query.to,
+        value,
+        fraction: value as f64 / target,
+        elapsed,
+        status,
+    }))
+}
+
+/// Progress of every goal in its current period, keeping only the
+/// notifications not sent yet.
+fn unannounced(conn: &Connection) -> rusqlite::Result<Vec<GoalProgress>> {
+    let today = recurrence::today();
+    let mut fresh = Vec::new();
+    for goal in list(conn)? {
+        let progress = match progress(conn, &goal, today)? {
+            Ok(progress) => progress,
+            Err(e) => {
+                tracing::warn!("can't evaluate '{}': {e}", goal.name);
+                continue;
+            }
+        };
+        if !matches!(progress.status, GoalStatus::Met | GoalStatus::AtRisk) {
+            continue;
+        }
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO goal_notices (goal_id, period_start, status)
+             VALUES (?1, ?2, ?3)",
+            params![goal.id, progress.period_start, progress.status.as_str()],
+        )?;
+        if inserted > 0 {
+            fresh.push(progress);
+        }
+    }
+    Ok(fresh)
+}
+
+/// Re-evaluate goals every minute and announce the ones that were just
+/// met or fell behind.
+pub fn spawn_goal_watcher(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || loop {
+        std::thread::sleep(GOAL_POLL);
+        let db = handle.state::<Db>().unjournaled();
+        match db.with_conn(|conn| unannounced(conn)) {
+            Ok(fresh) => {
+                for progress in fresh {
+                    let _ = handle.emit(GOAL_STATUS_EVENT, progress);
+                }
+            }
+            Err(e) => tracing::warn!("evaluation failed: {e}"),
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_goals(db: State<'_, Db>) -> CommandResult<Vec<Goal>> {
+    db.with_conn(|conn| list(conn))
+}
+
+#[tauri::command]
+pub fn create_goal(db: State<'_, Db>, input: NewGoal) -> CommandResult<Goal> {
+    let now = now_utc();
+    let mut goal = Goal {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: input.name,
+        metric: input.metric,
+        target: input.target,
+        period: input.period,
+        project: input.project,
+        tag: input.tag,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    validate(&mut goal)?;
+    db.with_conn(|conn| write_goal(conn, &goal))?;
+    Ok(goal)
+}
+
+/// Changing a goal lets it be announced again in the current period.
+#[tauri::command]
+pub fn update_goal(db: State<'_, Db>, id: String, patch: GoalPatch) -> CommandResult<Goal> {
+    db.with_conn(|conn| {
+        let Some(mut goal) = find_goal(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Goal not found: {id}"
+            ))));
+        };
+        if let Some(name) = &patch.name {
+            goal.name = name.clone();
+        }
+        if let Some(metric) = patch.metric {
+            goal.metric = metric;
+        }
+        if let Some(target) = patch.target {
+            goal.target = target;
+        }
+        if let Some(period) = patch.period {
+            goal.period = period;
+        }
+        if let Some(project) = &patch.project {
+            goal.project = project.clone();
+        }
+        if let Some(tag) = &patch.tag {
+            goal.tag = tag.clone();
+        }
+        if let Err(e) = validate(&mut goal) {
+            return Ok(Err(e));
+        }
+        goal.updated_at = now_utc();
+        let tx = conn.transaction()?;
+        write_goal(&tx, &goal)?;
+        tx.execute(
+            "DELETE FROM goal_notices WHERE goal_id = ?1",
+            params![goal.id],
+        )?;
+        tx.commit()?;
+        Ok(Ok(goal))
+    })?
+}
+
+#[tauri::command]
+pub fn delete_goal(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM goals WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!("Goal not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Progress of every goal in the period holding `date` (default today).
+#[tauri::command]
+pub fn get_goal_progress(
+    db: State<'_, Db>,
+    date: Option<String>,
+) -> CommandResult<Vec<GoalProgress>> {
+    let date = match date {
+        Some(date) => NaiveDate::parse_from_str(date.get(..10).unwrap_or(&date), "%Y-%m-%d")
+            .map_err(|_| CommandError::invalid(format!("Invalid date: {date}")))?,
+        None => recurrence::today(),
+    };
+    db.with_conn(|conn| {
+        let mut all = Vec::new();
+        for goal in list(conn)? {
+            match progress(conn, &goal, date)? {
+                Ok(progress) => all.push(progress),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+        Ok(Ok(all))
+    })?
+}
@@ -0,0 +1,433 @@
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Method, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::calendars::{self, Calendar};
+use crate::credentials;
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::google_calendar;
+use crate::google_tasks::{self, GoogleTaskList};
+use crate::http;
+use crate::rate_limit;
+
+const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+/// Tasks read and write, the calendar list, and events on the calendars.
+const SCOPES: &str = "https://www.googleapis.com/auth/tasks \
+                      https://www.googleapis.com/auth/calendar.readonly \
+                      https://www.googleapis.com/auth/calendar.events";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A Google sign-in, shared by Tasks and Calendar.
+#[derive(Debug, Clone, Serialize)]
+pub struct GoogleAccount {
+    pub id: String,
+    pub name: String,
+    pub client_id: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub lists: Vec<GoogleTaskList>,
+    pub calendars: Vec<Calendar>,
+}
+
+/// The result of the OAuth consent screen: pass the code from
+/// `await_oauth_code` with the redirect URI it was requested for.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewGoogleAccount {
+    /// Defaults to "Google".
+    #[serde(default)]
+    pub name: Option<String>,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    pub code: String,
+    pub redirect_uri: String,
+}
+
+/// One page of a list endpoint. Only Calendar hands out sync tokens.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    #[serde(default = "Vec::new")]
+    pub items: Vec<T>,
+    #[serde(default)]
+    pub next_page_token: Option<String>,
+    #[serde(default)]
+    pub next_sync_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    /// The scopes granted, space-separated.
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<GoogleAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, client_id, created_at, updated_at
+         FROM google_accounts ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(GoogleAccount {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            client_id: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+            lists: Vec::new(),
+            calendars: Vec::new(),
+        })
+    })?;
+    let mut accounts = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    for account in &mut accounts {
+        account.lists = google_tasks::list_task_lists(conn, &account.id)?;
+        account.calendars = calendars::list_google(conn, &account.id)?;
+    }
+    Ok(accounts)
+}
+
+/// The OAuth client an account was connected with, as (id, secret).
+fn client_credentials(
+    conn: &Connection,
+    account_id: &str,
+) -> rusqlite::Result<Option<(String, Option<String>)>> {
+    conn.query_row(
+        "SELECT client_id, client_secret FROM google_accounts WHERE id = ?1",
+        params![account_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Named for Tasks, which had accounts first; kept so saved sign-ins load.
+fn keyring_name(account_id: &str) -> String {
+    format!("google-tasks:{account_id}")
+}
+
+fn client() -> CommandResult<Client> {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(CommandError::from)
+}
+
+/// POST to the token endpoint with `grant`'s fields plus the client's.
+async fn request_token(
+    client: &Client,
+    client_id: &str,
+    client_secret: Option<&str>,
+    grant: &[(&str, &str)],
+) -> CommandResult<TokenResponse> {
+    let mut form: Vec<(&str, &str)> = vec![("client_id", client_id)];
+    if let Some(secret) = client_secret {
+        form.push(("client_secret", secret));
+    }
+    form.extend_from_slice(grant);
+    let response = client
+        .post(TOKEN_URL)
+        .form(&form)
+        .send()
+        .await
+        .context("Google sign-in")?;
+    match response.status() {
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(CommandError::unauthorized(
+            "Google refused the sign-in; connect the account again",
+        )),
+        status if !status.is_success() => Err(CommandError::status(
+            status,
+            format!("Google sign-in: HTTP {}", status.as_u16()),
+        )),
+        _ => http::read_json(response).await.context("Google sign-in"),
+    }
+}
+
+/// An authorized connection to Google's APIs for one account.
+pub struct Api {
+    client: Client,
+    access_token: String,
+    /// The provider paused when Google throttles the requests.
+    provider: &'static str,
+}
+
+impl Api {
+    /// Trade the account's saved refresh token for an access token, for
+    /// requests made by `provider`.
+    pub async fn connect(db: &Db, account_id: &str, provider: &'static str) -> CommandResult<Self> {
+        let (client_id, client_secret) = db
+            .with_conn(|conn| client_credentials(conn, account_id))?
+            .ok_or_else(|| {
+                CommandError::not_found(format!("Google account not found: {account_id}"))
+            })?;
+        let refresh_token = credentials::require(&keyring_name(account_id), "sign-in")?;
+        let client = client()?;
+        let token = request_token(
+            &client,
+            &client_id,
+            client_secret.as_deref(),
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &refresh_token),
+            ],
+        )
+        .await?;
+        Ok(Self {
+            client,
+            access_token: token.access_token,
+            provider,
+        })
+    }
+
+    /// Sign in as for `connect`, noting in `health` how it went and which
+    /// scopes are missing. `None` when the sign-in failed.
+    pub async fn check(
+        db: &Db,
+        account_id: &str,
+        provider: &'static str,
+        health: &mut AccountHealth,
+    ) -> CommandResult<Option<Self>> {
+        let (client_id, client_secret) = db
+            .with_conn(|conn| client_credentials(conn, account_id))?
+            .ok_or_else(|| {
+                CommandError::not_found(format!("Google account not found: {account_id}"))
+            })?;
+        let refresh_token = match credentials::require(&keyring_name(account_id), "sign-in") {
+            Ok(token) => token,
+            Err(e) => {
+                health.no_credentials(&e);
+                return Ok(None);
+            }
+        };
+        let client = client()?;
+        let mut form: Vec<(&str, &str)> = vec![("client_id", &client_id)];
+        if let Some(secret) = &client_secret {
+            form.push(("client_secret", secret));
+        }
+        form.push(("grant_type", "refresh_token"));
+        form.push(("refresh_token", &refresh_token));
+        let response = client.post(TOKEN_URL).form(&form).send().await;
+        let Some(response) = health.answer_sign_in("Google", response) else {
+            return Ok(None);
+        };
+        let token: TokenResponse = http::read_json(response).await.context("Google sign-in")?;
+        if let Some(granted) = &token.scope {
+            health.scopes(
+                granted,
+                &[
+                    ("https://www.googleapis.com/auth/tasks", "tasks"),
+                    (
+                        "https://www.googleapis.com/auth/calendar.readonly",
+                        "calendar",
+                    ),
+                    (
+                        "https://www.googleapis.com/auth/calendar.events",
+                        "calendar events",
+                    ),
+                ],
+            );
+        }
+        Ok(Some(Self {
+            client,
+            access_token: token.access_token,
+            provider,
+        }))
+    }
+
+    /// GET `url` with the raw result, for the account health check.
+    pub async fn probe(&self, url: &str) -> Result<Response, reqwest::Error> {
+        self.client
+            .get(url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+    }
+
+    /// Send a request to `url`. Returns the response body, or `None` for
+    /// 204, and for 404 and 410 (gone already, or an expired sync token).
+    pub async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        rate_limit::check(self.provider, "Google")?;
+        let mut parsed = Url::parse(url).map_err(|e| e.to_string())?;
+        if !query.is_empty() {
+            parsed.query_pairs_mut().extend_pairs(query);
+        }
+        let mut request = self
+            .client
+            .request(method.clone(), parsed)
+            .bearer_auth(&self.access_token);
+        if let Some(body) = body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await.context("Google")?;
+        match response.status() {
+            StatusCode::NO_CONTENT | StatusCode::NOT_FOUND | StatusCode::GONE => Ok(None),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(CommandError::unauthorized(
+                "Google refused access; connect the account again",
+            )
+            .with_provider(self.provider)),
+            StatusCode::TOO_MANY_REQUESTS => Err(rate_limit::limited(
+                self.provider,
+                "Google",
+                response.headers(),
+            )),
+            status if !status.is_success() => Err(CommandError::status(
+                status,
+                format!("{method} {url}: HTTP {}", status.as_u16()),
+            )
+            .with_provider(self.provider)),
+            _ => http::read_json(response).await.map(Some).context("Google"),
+        }
+    }
+
+    /// Every page of a list endpoint, `page_size` items at a time.
+    pub async fn all_pages<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        page_size: &str,
+    ) -> CommandResult<Vec<T>> {
+        let mut items = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = query.to_vec();
+            query.push(("maxResults", page_size));
+            if let Some(token) = &token {
+                query.push(("pageToken", token));
+            }
+            let page: Option<Page<T>> = self.send(Method::GET, url, &query, None).await?;
+            let Some(page) = page else {
+                return Err(CommandError::not_found(format!("GET {url}: not found")));
+            };
+            items.extend(page.items);
+            match page.next_page_token {
+                Some(next) => token = Some(next),
+                None => return Ok(items),
+            }
+        }
+    }
+}
+
+/// The consent screen URL for the OAuth loopback flow: start the listener
+/// with `start_oauth_listener`, open this, then pass the code from
+/// `await_oauth_code` to `connect_google_account`.
+#[tauri::command]
+pub fn google_auth_url(client_id: String, redirect_uri: String) -> CommandResult<String> {
+    let mut url = Url::parse(AUTH_URL).map_err(|e| e.to_string())?;
+    url.query_pairs_mut()
+        .append_pair("client_id", &client_id)
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("response_type", "code")
+        .append_pair("access_type", "offline")
+        .append_pair("prompt", "consent")
+        .append_pair("scope", SCOPES);
+    Ok(url.to_string())
+}
+
+/// Finish signing in to Google and save the account with its task lists
+/// and calendars. Nothing syncs until it's enabled.
+#[tauri::command]
+pub async fn connect_google_account(
+    db: State<'_, Db>,
+    input: NewGoogleAccount,
+) -> CommandResult<GoogleAccount> {
+    let client_id = input.client_id.trim().to_string();
+    if client_id.is_empty() {
+        return Err(CommandError::invalid("Enter the OAuth client ID"));
+    }
+    let client_secret = input
+        .client_secret
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    let http = client()?;
+    let token = request_token(
+        &http,
+        &client_id,
+        client_secret.as_deref(),
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &input.code),
+            ("redirect_uri", &input.redirect_uri),
+        ],
+    )
+    .await?;
+    let refresh_token = token.refresh_token.ok_or_else(|| {
+        CommandError::unauthorized("Google didn't grant offline access; try connecting again")
+    })?;
+    let api = Api {
+        client: http,
+        access_token: token.access_token,
+        provider: google_tasks::SOURCE,
+    };
+    let lists = google_tasks::task_lists(&api).await?;
+    let found = google_calendar::calendars(&api).await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Google".to_string());
+    credentials::save(&keyring_name(&id), Some(&refresh_token))?;
+    let stored = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO google_accounts (id, name, client_id, client_secret, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![id, name, client_id, client_secret, now],
+        )?;
+        google_tasks::store_task_lists(&tx, &id, &lists)?;
+        google_calendar::store_calendars(&tx, &id, &found)?;
+        tx.commit()?;
+        list_accounts(conn)
+    });
+    let accounts = match stored {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            return Err(e);
+        }
+    };
+    Ok(accounts
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| "Failed to save account".to_string())?)
+}
+
+#[tauri::command]
+pub fn list_google_accounts(db: State<'_, Db>) -> CommandResult<Vec<GoogleAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Forget an account, its calendars and its sync state. Its tasks stay,
+/// unlinked; events pushed to its calendars stay on Google.
+#[tauri::command]
+pub fn remove_google_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db
+        .with_conn(|conn| conn.execute("DELETE FROM google_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Google account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::json;
+use tauri::State;
+use url::Url;
+
+use crate::calendars::{self, BlockRow, Calendar, Fetched};
+use crate::db::{format_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::google::{Api, Page};
+use crate::ics::IcsEvent;
+use crate::timezone;
+
+/// The provider paused when Google throttles calendar requests.
+pub const SOURCE: &str = "google_calendar";
+const API_URL: &str = "https://www.googleapis.com/calendar/v3";
+/// Largest page the API hands out.
+const PAGE_SIZE: &str = "250";
+/// Private extended property holding the id of the time block an event
+/// was pushed from.
+const BLOCK_PROPERTY: &str = "daylightBlock";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteCalendar {
+    id: String,
+    #[serde(default)]
+    summary: Option<String>,
+    /// The name the user gave the calendar in their list.
+    #[serde(default)]
+    summary_override: Option<String>,
+}
+
+/// A start or end: `date` for all-day events, `dateTime` otherwise.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventTime {
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    date_time: Option<String>,
+}
+
+impl EventTime {
+    fn instant(&self, local: &Tz) -> Option<DateTime<Utc>> {
+        if let Some(at) = &self.date_time {
+            return parse_utc(at).ok();
+        }
+        let date = NaiveDate::parse_from_str(self.date.as_deref()?, "%Y-%m-%d").ok()?;
+        timezone::resolve_local(date.and_time(NaiveTime::MIN), local)
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ExtendedProperties {
+    #[serde(default)]
+    private: HashMap<String, String>,
+}
+
+/// An event as the API returns it. Cancelled events carry only their id.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteEvent {
+    id: String,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    start: Option<EventTime>,
+    #[serde(default)]
+    end: Option<EventTime>,
+    #[serde(default)]
+    transparency: Option<String>,
+    #[serde(default)]
+    extended_properties: Option<ExtendedProperties>,
+}
+
+impl RemoteEvent {
+    /// The event to cache, or `None` when it was cancelled or is one of
+    /// ours, pushed from a time block.
+    fn to_event(&self, local: &Tz) -> Option<IcsEvent> {
+        let pushed = self
+            .extended_properties
+            .as_ref()
+            .is_some_and(|p| p.private.contains_key(BLOCK_PROPERTY));
+        if pushed || self.status.as_deref() == Some("cancelled") {
+            return None;
+        }
+        let start = self.start.as_ref()?;
+        let all_day = start.date_time.is_none();
+        let starts_at = start.instant(local)?;
+        let ends_at = self
+            .end
+            .as_ref()
+            .and_then(|end| end.instant(local))
+            .filter(|end| *end >= starts_at)
+            .unwrap_or(starts_at);
+        let title = self
+            .summary
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .unwrap_or("Busy");
+        Some(IcsEvent {
+            uid: self.id.clone(),
+            title: title.to_string(),
+            location: self.location.clone().filter(|l| !l.trim().is_empty()),
+            starts_at,
+            ends_at,
+            all_day,
+            busy: !all_day && self.transparency.as_deref() != Some("transparent"),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SavedEvent {
+    id: String,
+}
+
+/// The API URL of a calendar, which is also its `calendars.url`.
+fn calendar_url(id: &str) -> CommandResult<String> {
+    let mut url = Url::parse(API_URL).map_err(|e| e.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| "Invalid API URL".to_string())?
+        .push("calendars")
+        .push(id);
+    Ok(url.to_string())
+}
+
+/// Every calendar in the account's list, as (url, name).
+pub async fn calendars(api: &Api) -> CommandResult<Vec<(String, String)>> {
+    let found: Vec<RemoteCalendar> = api
+        .all_pages(&format!("{API_URL}/users/me/calendarList"), &[], PAGE_SIZE)
+        .await?;
+    found
+        .into_iter()
+        .map(|c| {
+            let name = c
+                .summary_override
+                .or(c.summary)
+                .unwrap_or_else(|| c.id.clone());
+            Ok((calendar_url(&c.id)?, name))
+        })
+        .collect()
+}
+
+pub fn store_calendars(
+    conn: &Connection,
+    account_id: &str,
+    found: &[(String, String)],
+) -> rusqlite::Result<()> {
+    let found: Vec<(&str, &str)> = found
+        .iter()
+        .map(|(url, name)| (url.as_str(), name.as_str()))
+        .collect();
+    calendars::store_google(conn, account_id, &found)
+}
+
+/// Events of the calendar changed since `sync_token`, or all of them from
+/// the start of `window` when there's no token. A token Google has expired
+/// (410 Gone) starts over with a full fetch.
+async fn fetch_events(
+    api: &Api,
+    calendar: &Calendar,
+    sync_token: Option<&str>,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Fetched> {
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let url = format!("{}/events", calendar.url);
+    let time_min = format_utc(window.0);
+    let mut sync_token = sync_token.map(str::to_string);
+    let mut page_token: Option<String> = None;
+    let mut fetched = Fetched {
+        full: sync_token.is_none(),
+        ..Fetched::default()
+    };
+    loop {
+        // Recurring events come as their instances, each with its own id.
+        let mut query = vec![("singleEvents", "true"), ("maxResults", PAGE_SIZE)];
+        match &sync_token {
+            Some(token) => query.push(("syncToken", token)),
+            None => query.push(("timeMin", &time_min)),
+        }
+        if let Some(token) = &page_token {
+            query.push(("pageToken", token));
+        }
+        let page: Option<Page<RemoteEvent>> = api.send(Method::GET, &url, &query, None).await?;
+        let Some(page) = page else {
+            if sync_token.is_none() {
+                return Err(CommandError::not_found("Calendar not found on Google"));
+            }
+            sync_token = None;
+            page_token = None;
+            fetched = Fetched {
+                full: true,
+                ..Fetched::default()
+            };
+            continue;
+        };
+        for event in page.items {
+            match event.to_event(&local) {
+                Some(found) => fetched.events.push(found),
+                None => fetched.removed.push(event.id),
+            }
+        }
+        match page.next_page_token {
+            Some(next) => page_token = Some(next),
+            None => {
+                fetched.sync_token = page.next_sync_token;
+                return Ok(fetched);
+            }
+        }
+    }
+}
+
+/// The event fields for a time block.
+fn block_body(block: &BlockRow) -> CommandResult<serde_json::Value> {
+    let (start, end) = block.span()?;
+    Ok(json!({
+        "summary": block.title,
+        "start": { "dateTime": format_utc(start) },
+        "end": { "dateTime": format_utc(end) },
+        "transparency": "opaque",
+        "extendedProperties": { "private": { BLOCK_PROPERTY: block.id } },
+    }))
+}
+
+/// Mirror time blocks from `since` on as events on the calendar, so the
+/// time shows as taken to others, and remove events whose block is gone.
+async fn push_blocks(db: &Db, api: &Api, calendar: &Calendar, since: &str) -> CommandResult<()> {
+    let (blocks, stale) = db.with_conn(|conn| {
+        Ok((
+            calendars::blocks_to_push(conn, calendar, since)?,
+            calendars::stale_block_events(conn, calendar)?,
+        ))
+    })?;
+
+    for (block_id, event_id) in stale {
+        let url = format!("{}/events/{event_id}", calendar.url);
+        api.send::<serde_json::Value>(Method::DELETE, &url, &[], None)
+            .await?;
+        db.with_conn(|conn| calendars::forget_block_event(conn, &block_id, &calendar.id))?;
+    }
+
+    for block in blocks {
+        if block.is_synced() {
+            continue;
+        }
+        let body = block_body(&block)?;
+        let updated: Option<SavedEvent> = match &block.event_id {
+            Some(event_id) => {
+                let url = format!("{}/events/{event_id}", calendar.url);
+                api.send(Method::PATCH, &url, &[], Some(&body)).await?
+            }
+            None => None,
+        };
+        // New, or deleted on Google meanwhile: the block still exists, so
+        // it's pushed again.
+        let saved = match updated {
+            Some(saved) => saved,
+            None => {
+                let url = format!("{}/events", calendar.url);
+                let created: Option<SavedEvent> =
+                    api.send(Method::POST, &url, &[], Some(&body)).await?;
+                created.ok_or_else(|| CommandError::not_found("Calendar not found on Google"))?
+            }
+        };
+        db.with_conn(|conn| calendars::save_block_event(conn, &block, &calendar.id, &saved.id))?;
+    }
+    Ok(())
+}
+
+/// Push time blocks to the calendar if it takes them, then fetch what
+/// changed on it. Returns `None` for a hidden calendar, which is only
+/// visited to clean up events pushed to it before.
+pub async fn refresh(
+    db: &Db,
+    api: &Api,
+    calendar: &Calendar,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Option<Fetched>> {
+    let since = window.0.date_naive().to_string();
+    push_blocks(db, api, calendar, &since).await?;
+    if !calendar.enabled {
+        return Ok(None);
+    }
+    let sync_token = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT sync_token FROM calendars WHERE id = ?1",
+            params![calendar.id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+    })?;
+    fetch_events(api, calendar, sync_token.as_deref(), window)
+        .await
+        .map(Some)
+}
+
+/// Look for calendars added to or removed from the account's list.
+#[tauri::command]
+pub async fn refresh_google_calendars(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<Calendar>> {
+    let api = Api::connect(&db, &account_id, SOURCE).await?;
+    let found = calendars(&api).await?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_calendars(&tx, &account_id, &found)?;
+        tx.commit()?;
+        calendars::list_google(conn, &account_id)
+    })
+}
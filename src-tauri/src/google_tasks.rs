@@ -0,0 +1,925 @@
+use std::collections::{HashMap, HashSet};
+
+use reqwest::Method;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::account_health::AccountHealth;
+use crate::conflicts::{self, ConflictPolicy};
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::google::{self, Api};
+use crate::history::{self, ChangeSource};
+use crate::outbox::{self, Replay};
+use crate::projects;
+use crate::sync_provider::{
+    self, Capabilities, RemoteCollection, SyncFuture, SyncMode, SyncProvider,
+};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::trash;
+
+/// `external_refs.source` for tasks that came from Google Tasks.
+pub const SOURCE: &str = "google_tasks";
+
+const API_URL: &str = "https://tasks.googleapis.com/tasks/v1";
+/// Largest page the API hands out.
+const PAGE_SIZE: &str = "100";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GoogleTaskList {
+    pub id: String,
+    pub account_id: String,
+    pub title: String,
+    /// Local project the list's tasks are filed under. New tasks in this
+    /// project are uploaded to the list.
+    pub project: Option<String>,
+    pub enabled: bool,
+    pub last_synced_at: Option<String>,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TaskListPatch {
+    pub enabled: Option<bool>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GoogleSyncReport {
+    pub task_list_id: String,
+    pub title: String,
+    /// Tasks created or updated from Google.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because they were deleted on Google.
+    pub removed: usize,
+    /// Tasks uploaded, new or changed.
+    pub uploaded: usize,
+    /// Tasks deleted on Google because they were deleted here.
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for GoogleSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.uploaded + self.deleted
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+/// A task as the API returns it. Dates are RFC 3339; `due` carries only a
+/// date, at midnight UTC.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteTask {
+    id: String,
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    due: Option<String>,
+    #[serde(default)]
+    completed: Option<String>,
+    updated: String,
+    #[serde(default)]
+    deleted: bool,
+}
+
+impl RemoteTask {
+    fn is_done(&self) -> bool {
+        self.status.as_deref() == Some("completed")
+    }
+
+    fn due_date(&self) -> Option<String> {
+        self.due
+            .as_deref()
+            .and_then(|d| d.get(..10))
+            .map(str::to_string)
+    }
+}
+
+/// The fields uploaded for a task. `None` is sent as `null`, which clears
+/// the field on Google.
+#[derive(Debug, Clone, Serialize)]
+struct TaskBody {
+    title: String,
+    notes: Option<String>,
+    status: &'static str,
+    due: Option<String>,
+    completed: Option<String>,
+}
+
+impl TaskBody {
+    fn from_task(task: &Task) -> Self {
+        let done = task.status == STATUS_DONE;
+        Self {
+            title: task.title.clone(),
+            notes: task.description.clone(),
+            status: if done { "completed" } else { "needsAction" },
+            due: task
+                .due
+                .as_deref()
+                .and_then(|d| d.get(..10))
+                .map(|d| format!("{d}T00:00:00.000Z")),
+            completed: task.completed_at.clone().filter(|_| done),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoteList {
+    id: String,
+    title: String,
+}
+
+fn row_to_list(row: &Row) -> rusqlite::Result<GoogleTaskList> {
+    Ok(GoogleTaskList {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        title: row.get(2)?,
+        project: row.get(3)?,
+        enabled: row.get(4)?,
+        last_synced_at: row.get(5)?,
+    })
+}
+
+const LIST_COLUMNS: &str = "id, account_id, title, project, enabled, last_synced_at";
+
+pub fn list_task_lists(
+    conn: &Connection,
+    account_id: &str,
+) -> rusqlite::Result<Vec<GoogleTaskList>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LIST_COLUMNS} FROM google_task_lists
+         WHERE account_id = ?1 ORDER BY title COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map(params![account_id], row_to_list)?;
+    rows.collect()
+}
+
+fn find_task_list(conn: &Connection, id: &str) -> rusqlite::Result<Option<GoogleTaskList>> {
+    conn.query_row(
+        &format!("SELECT {LIST_COLUMNS} FROM google_task_lists WHERE id = ?1"),
+        params![id],
+        row_to_list,
+    )
+    .optional()
+}
+
+/// Every task list on the account.
+pub async fn task_lists(api: &Api) -> CommandResult<Vec<RemoteList>> {
+    api.all_pages(&format!("{API_URL}/users/@me/lists"), &[], PAGE_SIZE)
+        .await
+}
+
+/// Tasks changed since `updated_min`, deletions included, or every task
+/// when `None`.
+async fn tasks(api: &Api, list: &str, updated_min: Option<&str>) -> CommandResult<Vec<RemoteTask>> {
+    let url = format!("{API_URL}/lists/{list}/tasks");
+    let mut query = vec![("showCompleted", "true"), ("showHidden", "true")];
+    if let Some(updated_min) = updated_min {
+        query.push(("showDeleted", "true"));
+        query.push(("updatedMin", updated_min));
+    }
+    api.all_pages(&url, &query, PAGE_SIZE).await
+}
+
+/// Save the lists found on an account. Lists Google no longer has are
+/// dropped along with their sync state; their tasks stay.
+pub fn store_task_lists(
+    conn: &Connection,
+    account_id: &str,
+    found: &[RemoteList],
+) -> rusqlite::Result<()> {
+    for list in found {
+        conn.execute(
+            "INSERT INTO google_task_lists (id, account_id, remote_id, title)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(account_id, remote_id) DO UPDATE SET title = excluded.title",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                account_id,
+                list.id,
+                list.title
+            ],
+        )?;
+    }
+    let ids: Vec<&str> = found.iter().map(|l| l.id.as_str()).collect();
+    let ids = serde_json::to_string(&ids).unwrap_or_default();
+    conn.execute(
+        "DELETE FROM google_task_lists
+         WHERE account_id = ?1 AND remote_id NOT IN (SELECT value FROM json_each(?2))",
+        params![account_id, ids],
+    )?;
+    Ok(())
+}
+
+/// What the last sync knew about one Google task.
+#[derive(Debug, Clone)]
+struct ItemRow {
+    etag: Option<String>,
+    task_id: String,
+    /// The task's `updated_at` when it last matched Google.
+    synced_at: String,
+}
+
+fn load_items(conn: &Connection, task_list_id: &str) -> rusqlite::Result<HashMap<String, ItemRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, etag, task_id, synced_at FROM google_task_items
+         WHERE task_list_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![task_list_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ItemRow {
+                etag: row.get(1)?,
+                task_id: row.get(2)?,
+                synced_at: row.get(3)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_item(
+    conn: &Connection,
+    task_list_id: &str,
+    remote_id: &str,
+    item: &ItemRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO google_task_items (task_list_id, remote_id, etag, task_id, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(task_list_id, remote_id) DO UPDATE SET
+             etag = excluded.etag,
+             task_id = excluded.task_id,
+             synced_at = excluded.synced_at",
+        params![
+            task_list_id,
+            remote_id,
+            item.etag,
+            item.task_id,
+            item.synced_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_item(conn: &Connection, task_list_id: &str, remote_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM google_task_items WHERE task_list_id = ?1 AND remote_id = ?2",
+        params![task_list_id, remote_id],
+    )?;
+    Ok(())
+}
+
+/// Create or update the task for `remote`. Unless `policy` says remote
+/// wins, a field edited here after Google last changed the task keeps the
+/// local value. Returns the task and whether it was created.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    remote: &RemoteTask,
+    project: Option<&str>,
+    policy: ConflictPolicy,
+) -> rusqlite::Result<(Task, bool)> {
+    let title = remote
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or("Untitled")
+        .to_string();
+    let notes = remote.notes.clone().filter(|n| !n.trim().is_empty());
+    let completed_at = remote.completed.clone().or_else(|| Some(now_utc()));
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description: notes,
+            project: project.map(str::to_string),
+            priority: None,
+            due: remote.due_date(),
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if remote.is_done() {
+            task.status = STATUS_DONE.to_string();
+            task.completed_at = completed_at;
+            task_store::write_task(conn, &task)?;
+        }
+        return Ok((task, true));
+    };
+
+    let modified = parse_utc(&remote.updated)
+        .ok()
+        .map(|at| at.timestamp_millis());
+    let take = conflicts::remote_fields(conn, &task.id, modified, policy)?;
+    if take("title") {
+        task.title = title;
+    }
+    if take("description") {
+        task.description = notes;
+    }
+    // Google keeps only the date; a time of day set here survives.
+    let due = remote.due_date();
+    if take("due") && task.due.as_deref().and_then(|d| d.get(..10)) != due.as_deref() {
+        task.due = due;
+    }
+    if take("status") && remote.is_done() != (task.status == STATUS_DONE) {
+        if remote.is_done() {
+            task.status = STATUS_DONE.to_string();
+            task.completed_at = completed_at;
+        } else {
+            task.status = STATUS_OPEN.to_string();
+            task.completed_at = None;
+        }
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    Ok((task, false))
+}
+
+/// A change to send to Google.
+enum Upload {
+    Insert {
+        item: ItemRow,
+        body: TaskBody,
+    },
+    Update {
+        remote_id: String,
+        item: ItemRow,
+        body: TaskBody,
+    },
+    Delete {
+        remote_id: String,
+        task_id: String,
+    },
+}
+
+impl Upload {
+    fn task_id(&self) -> &str {
+        match self {
+            Upload::Insert { item, .. } | Upload::Update { item, .. } => &item.task_id,
+            Upload::Delete { task_id, .. } => task_id,
+        }
+    }
+}
+
+/// Apply Google's changes, unless only exporting, then work out what to
+/// upload. `full` is set when `remote` is every task in the list rather
+/// than only the changed ones, so tasks missing from it were deleted there.
+fn merge_remote(
+    conn: &mut Connection,
+    list: &GoogleTaskList,
+    remote: &[RemoteTask],
+    full: bool,
+    imports: bool,
+    report: &mut GoogleSyncReport,
+) -> rusqlite::Result<Vec<Upload>> {
+    let tx = conn.transaction()?;
+    let items = load_items(&tx, &list.id)?;
+    let policy = conflicts::policy(&tx, SOURCE)?;
+
+    for task in remote {
+        let known = items.get(&task.id);
+        if task.deleted {
+            if let Some(item) = known {
+                if imports && trash::trash_task(&tx, &item.task_id)? {
+                    report.removed += 1;
+                }
+                forget_item(&tx, &list.id, &task.id)?;
+            }
+            continue;
+        }
+        // Our own upload coming back.
+        if known.is_some_and(|item| item.etag.is_some() && item.etag == task.etag) {
+            continue;
+        }
+        let task_id = match known {
+            Some(item) => Some(item.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, task.id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let local = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if known.is_some() && local.is_none() {
+            // Deleted here; the delete is uploaded below.
+            continue;
+        }
+        if !imports {
+            // Only the etag is taken, so local edits go up over the task.
+            if let Some(item) = known {
+                let item = ItemRow {
+                    etag: task.etag.clone(),
+                    ..item.clone()
+                };
+                save_item(&tx, &list.id, &task.id, &item)?;
+            }
+            continue;
+        }
+        let dirty = match (known, &local) {
+            (Some(item), Some(local)) => local.updated_at > item.synced_at,
+            _ => false,
+        };
+
+        let project = list.project.as_deref();
+        let (written, created) = match local {
+            Some(local)
+                if dirty
+                    && conflicts::keep_local(&tx, SOURCE, policy, &local, |tx| {
+                        write_remote(
+                            tx,
+                            Some(local.clone()),
+                            task,
+                            project,
+                            ConflictPolicy::Remote,
+                        )
+                        .map(|(task, _)| task)
+                    })? =>
+            {
+                (local, false)
+            }
+            local => write_remote(&tx, local, task, project, policy)?,
+        };
+        if created {
+            report.created += 1;
+            tx.execute(
+                "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![SOURCE, task.id, written.id, now_utc()],
+            )?;
+        } else {
+            report.updated += 1;
+        }
+        let synced_at = match known {
+            // Local edits Google doesn't have yet are kept dirty.
+            Some(item) if dirty && policy != ConflictPolicy::Remote => item.synced_at.clone(),
+            _ => written.updated_at.clone(),
+        };
+        let item = ItemRow {
+            etag: task.etag.clone(),
+            task_id: written.id,
+            synced_at,
+        };
+        save_item(&tx, &list.id, &task.id, &item)?;
+    }
+
+    if full {
+        let listed: HashSet<&str> = remote.iter().map(|t| t.id.as_str()).collect();
+        for (remote_id, item) in &items {
+            if listed.contains(remote_id.as_str()) {
+                continue;
+            }
+            if imports && trash::trash_task(&tx, &item.task_id)? {
+                report.removed += 1;
+            }
+            forget_item(&tx, &list.id, remote_id)?;
+        }
+    }
+
+    let held = conflicts::held(&tx, SOURCE)?;
+    let mut uploads = Vec::new();
+    for (remote_id, item) in load_items(&tx, &list.id)? {
+        match task_store::find_task(&tx, &item.task_id)? {
+            None => uploads.push(Upload::Delete {
+                remote_id,
+                task_id: item.task_id,
+            }),
+            Some(task) if task.updated_at > item.synced_at && !held.contains(&task.id) => {
+                let body = TaskBody::from_task(&task);
+                let item = ItemRow {
+                    synced_at: task.updated_at,
+                    ..item
+                };
+                uploads.push(Upload::Update {
+                    remote_id,
+                    item,
+                    body,
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(project) = &list.project {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM tasks
+             WHERE project = ?1 COLLATE NOCASE AND deleted_at IS NULL
+               AND id NOT IN (SELECT task_id FROM google_task_items)
+             ORDER BY created_at",
+        )?;
+        let ids = stmt
+            .query_map(params![project], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        for id in ids {
+            let Some(task) = task_store::find_task(&tx, &id)? else {
+                continue;
+            };
+            uploads.push(Upload::Insert {
+                body: TaskBody::from_task(&task),
+                item: ItemRow {
+                    etag: None,
+                    task_id: task.id,
+                    synced_at: task.updated_at,
+                },
+            });
+        }
+    }
+
+    tx.commit()?;
+    Ok(uploads)
+}
+
+/// What came of one upload.
+enum Uploaded {
+    Saved { remote_id: String, item: ItemRow },
+    Deleted { remote_id: String },
+}
+
+fn to_json(body: &TaskBody) -> CommandResult<serde_json::Value> {
+    serde_json::to_value(body).map_err(|e| e.to_string().into())
+}
+
+async fn upload(api: &Api, list: &str, change: Upload) -> CommandResult<Uploaded> {
+    match change {
+        Upload::Insert { item, body } => {
+            let url = format!("{API_URL}/lists/{list}/tasks");
+            let created: Option<RemoteTask> = api
+                .send(Method::POST, &url, &[], Some(&to_json(&body)?))
+                .await?;
+            let created =
+                created.ok_or_else(|| CommandError::not_found(format!("POST {url}: not found")))?;
+            Ok(Uploaded::Saved {
+                remote_id: created.id,
+                item: ItemRow {
+                    etag: created.etag,
+                    ..item
+                },
+            })
+        }
+        Upload::Update {
+            remote_id,
+            item,
+            body,
+        } => {
+            let url = format!("{API_URL}/lists/{list}/tasks/{remote_id}");
+            let updated: Option<RemoteTask> = api
+                .send(Method::PATCH, &url, &[], Some(&to_json(&body)?))
+                .await?;
+            match updated {
+                Some(updated) => Ok(Uploaded::Saved {
+                    remote_id,
+                    item: ItemRow {
+                        etag: updated.etag,
+                        ..item
+                    },
+                }),
+                // Deleted on Google meanwhile. The link is dropped; the task
+                // is uploaded again as new while it's in the list's project.
+                None => Ok(Uploaded::Deleted { remote_id }),
+            }
+        }
+        Upload::Delete { remote_id, .. } => {
+            let url = format!("{API_URL}/lists/{list}/tasks/{remote_id}");
+            api.send::<serde_json::Value>(Method::DELETE, &url, &[], None)
+                .await?;
+            Ok(Uploaded::Deleted { remote_id })
+        }
+    }
+}
+
+/// Two-way sync of one list: pull what changed on Google since the last
+/// sync, merge it field by field, and when pushing upload local changes.
+async fn sync_task_list(
+    db: &Db,
+    api: &Api,
+    list: &GoogleTaskList,
+    mode: SyncMode,
+) -> CommandResult<GoogleSyncReport> {
+    let mut report = GoogleSyncReport {
+        task_list_id: list.id.clone(),
+        title: list.title.clone(),
+        ..GoogleSyncReport::default()
+    };
+    let (remote_list, updated_min) = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT remote_id, updated_min FROM google_task_lists WHERE id = ?1",
+            params![list.id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+    })?;
+
+    let remote = tasks(api, &remote_list, updated_min.as_deref()).await?;
+    // The newest change seen; the next sync asks for what came after.
+    let next_updated_min = remote
+        .iter()
+        .map(|t| t.updated.clone())
+        .max()
+        .or(updated_min.clone());
+    let full = updated_min.is_none();
+
+    let uploads = db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(conn, list, &remote, full, mode.imports(), &mut report)
+        })?
+    })?;
+
+    // Importing only leaves local changes here.
+    let uploads = if mode.exports() { uploads } else { Vec::new() };
+    let uploads = db.with_conn(|conn| outbox::in_order(conn, uploads, Upload::task_id))?;
+    let mut results = Vec::new();
+    let mut replay = Replay::default();
+    for change in uploads {
+        let task_id = change.task_id().to_string();
+        match upload(api, &remote_list, change).await {
+            Ok(result) => {
+                replay.delivered(task_id);
+                results.push(result);
+            }
+            Err(e) => {
+                replay.failed(task_id, &e);
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        replay.save(&tx)?;
+        for result in &results {
+            match result {
+                Uploaded::Saved { remote_id, item } => {
+                    report.uploaded += 1;
+                    tx.execute(
+                        "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        params![SOURCE, remote_id, item.task_id, now_utc()],
+                    )?;
+                    save_item(&tx, &list.id, remote_id, item)?;
+                }
+                Uploaded::Deleted { remote_id } => {
+                    report.deleted += 1;
+                    forget_item(&tx, &list.id, remote_id)?;
+                }
+            }
+        }
+        tx.execute(
+            "UPDATE google_task_lists SET updated_min = ?2, last_synced_at = ?3 WHERE id = ?1",
+            params![list.id, next_updated_min, now_utc()],
+        )?;
+        tx.commit()
+    })?;
+    Ok(report)
+}
+
+async fn refresh_task_lists(db: &Db, account_id: &str) -> CommandResult<Vec<GoogleTaskList>> {
+    let api = Api::connect(db, account_id, SOURCE).await?;
+    let found = task_lists(&api).await?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_task_lists(&tx, account_id, &found)?;
+        tx.commit()?;
+        list_task_lists(conn, account_id)
+    })
+}
+
+/// Look for lists added or removed on Google since.
+#[tauri::command]
+pub async fn refresh_google_task_lists(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<GoogleTaskList>> {
+    refresh_task_lists(&db, &account_id).await
+}
+
+fn update_task_list(db: &Db, id: &str, patch: TaskListPatch) -> CommandResult<GoogleTaskList> {
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut list) = find_task_list(conn, id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Task list not found: {id}"
+            ))));
+        };
+        if let Some(project) = project {
+            list.project = project;
+        }
+        if let Some(enabled) = patch.enabled {
+            list.enabled = enabled;
+        }
+        if list.enabled && list.project.is_none() {
+            list.project = Some(list.title.clone());
+        }
+        conn.execute(
+            "UPDATE google_task_lists SET project = ?2, enabled = ?3 WHERE id = ?1",
+            params![list.id, list.project, list.enabled],
+        )?;
+        Ok(Ok(list))
+    })?
+}
+
+/// Turn syncing of a list on or off, or change its project. A list is
+/// enabled with a project named after it unless one is given.
+#[tauri::command]
+pub fn update_google_task_list(
+    db: State<'_, Db>,
+    id: String,
+    patch: TaskListPatch,
+) -> CommandResult<GoogleTaskList> {
+    update_task_list(&db, &id, patch)
+}
+
+pub struct GoogleTasks;
+
+impl SyncProvider for GoogleTasks {
+    type Report = Vec<GoogleSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Google Tasks"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: true,
+            conflicts: true,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| google::list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(db, &account.id, SOURCE).await?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| google::list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                if let Some(api) = Api::check(db, &account.id, SOURCE, &mut health).await? {
+                    let lists = api
+                        .probe(&format!("{API_URL}/users/@me/lists?maxResults=1"))
+                        .await;
+                    health.answer("Google Tasks", lists);
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Import))
+    }
+
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::TwoWay))
+    }
+
+    fn export<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Export))
+    }
+
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| google::list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let lists = match refresh_task_lists(db, &account.id).await {
+                    Ok(lists) => lists,
+                    Err(e) => {
+                        tracing::warn!("refresh of {} failed: {e}", account.name);
+                        account.lists
+                    }
+                };
+                found.extend(lists.into_iter().map(|l| RemoteCollection {
+                    id: l.id,
+                    account_id: l.account_id,
+                    name: l.title,
+                    selected: l.enabled,
+                }));
+            }
+            Ok(found)
+        })
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        let patch = TaskListPatch {
+            enabled: Some(selected),
+            ..TaskListPatch::default()
+        };
+        update_task_list(db, id, patch).map(drop)
+    }
+}
+
+/// Sync every enabled list of `account_id`, or of all accounts. One list
+/// failing doesn't stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_google_tasks(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<GoogleSyncReport>> {
+    sync_provider::run_command(&app, &GoogleTasks, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+    mode: SyncMode,
+) -> CommandResult<Vec<GoogleSyncReport>> {
+    let accounts = db.with_conn(|conn| google::list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let lists: Vec<&GoogleTaskList> = account.lists.iter().filter(|l| l.enabled).collect();
+        if lists.is_empty() {
+            continue;
+        }
+        let api = Api::connect(db, &account.id, SOURCE).await;
+        for list in lists {
+            let result = match &api {
+                Ok(api) => sync_task_list(db, api, list, mode).await,
+                Err(e) => Err(e.clone()),
+            };
+            let report = result.unwrap_or_else(|e| {
+                tracing::warn!("sync of {} failed: {e}", list.title);
+                GoogleSyncReport {
+                    task_list_id: list.id.clone(),
+                    title: list.title.clone(),
+                    errors: vec![e.to_string()],
+                    ..GoogleSyncReport::default()
+                }
+            });
+            progress.record(&report.title, &report);
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
@@ -0,0 +1,928 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono_tz::Tz;
+use reqwest::{Client, Method, StatusCode};
+use rusqlite::{params, Connection, ToSql};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::State;
+
+use crate::credentials;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::task_store::Task;
+use crate::time_entries::TimeEntry;
+use crate::time_push::{self, Pushing, Service, STATUS_FAILED, STATUS_SYNCED};
+use crate::timezone;
+
+const API_URL: &str = "https://api.harvestapp.com/v2";
+/// Lists the Harvest and Forecast accounts a token can reach.
+const ACCOUNTS_URL: &str = "https://id.getharvest.com/api/v2/accounts";
+/// Harvest turns away requests without one.
+const USER_AGENT: &str = "DayLight";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Pushed entries, by account.
+const ENTRIES_TABLE: &str = "harvest_entries";
+
+/// In a Harvest entry that's approved, invoiced or in a locked timesheet,
+/// so changes since the last push can't be sent.
+pub const STATUS_LOCKED: &str = "locked";
+
+const SERVICE: Service = Service {
+    id: "harvest",
+    name: "Harvest",
+    refused: "Harvest refused the access token; connect the account again",
+    refused_statuses: &[StatusCode::UNAUTHORIZED],
+    empty_statuses: &[StatusCode::NOT_FOUND],
+    message: |text| {
+        serde_json::from_str::<RemoteError>(text)
+            .ok()
+            .and_then(|e| e.message.or(e.error_description))
+            .unwrap_or_else(|| text.to_string())
+    },
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HarvestAccount {
+    pub id: String,
+    pub name: String,
+    /// The Harvest account entries are pushed to; a token can reach several.
+    pub harvest_account_id: i64,
+    /// Whether the Harvest account tracks start and end times rather than
+    /// durations.
+    pub timestamps: bool,
+    /// Where time on projects with no mapping, and no Harvest project of
+    /// the same name, goes.
+    pub default_project_id: Option<i64>,
+    pub default_task_id: Option<i64>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewHarvestAccount {
+    /// Defaults to the Harvest account's name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// A personal access token from Harvest's developer settings.
+    pub token: String,
+    /// Defaults to the first Harvest account the token can reach.
+    #[serde(default)]
+    pub harvest_account_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HarvestPushReport {
+    pub account_id: String,
+    pub created: usize,
+    pub updated: usize,
+    /// Entries already in Harvest as they are here.
+    pub unchanged: usize,
+    /// Running entries, left until they're stopped.
+    pub running: usize,
+    /// Entries changed here after Harvest locked them.
+    pub locked: usize,
+    pub failed: usize,
+    /// Every entry that's locked or failed, and why.
+    pub not_exported: Vec<HarvestProblem>,
+}
+
+/// An entry that couldn't be exported.
+#[derive(Debug, Clone, Serialize)]
+pub struct HarvestProblem {
+    pub time_entry_id: String,
+    pub notes: String,
+    pub reason: String,
+}
+
+/// Where one time entry stands with a Harvest account.
+#[derive(Debug, Clone, Serialize)]
+pub struct HarvestEntryStatus {
+    pub time_entry_id: String,
+    /// `synced`, `pending`, `locked`, `failed` or `running`.
+    pub status: String,
+    /// The Harvest time entry's id, once it was created.
+    pub remote_id: Option<i64>,
+    /// Why the last push of the entry failed, or why Harvest locked it.
+    pub error: Option<String>,
+    /// Whether Harvest bills the entry, going by its task and project.
+    pub billable: Option<bool>,
+    /// Whether the entry is on an invoice in Harvest.
+    pub invoiced: bool,
+    pub pushed_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HarvestTask {
+    pub id: i64,
+    pub name: String,
+    pub billable: bool,
+}
+
+/// A project the user can log time to, with its client and tasks.
+#[derive(Debug, Clone, Serialize)]
+pub struct HarvestProject {
+    pub id: i64,
+    pub name: String,
+    pub code: Option<String>,
+    pub client_id: Option<i64>,
+    pub client_name: Option<String>,
+    pub tasks: Vec<HarvestTask>,
+}
+
+/// A local project sent to a chosen Harvest project and task.
+#[derive(Debug, Clone, Serialize)]
+pub struct HarvestMapping {
+    pub project: String,
+    pub remote_project_id: i64,
+    pub remote_task_id: i64,
+}
+
+/// What's needed to map projects to Harvest's.
+#[derive(Debug, Clone, Serialize)]
+pub struct HarvestProjects {
+    pub projects: Vec<HarvestProject>,
+    pub mappings: Vec<HarvestMapping>,
+    pub default_project_id: Option<i64>,
+    pub default_task_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteAccounts {
+    #[serde(default)]
+    accounts: Vec<RemoteAccount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteAccount {
+    id: i64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    product: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteCompany {
+    #[serde(default)]
+    wants_timestamp_timers: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteRef {
+    id: i64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    code: Option<String>,
+}
+
+fn active() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteTaskAssignment {
+    task: RemoteRef,
+    #[serde(default = "active")]
+    is_active: bool,
+    #[serde(default)]
+    billable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteAssignment {
+    project: RemoteRef,
+    #[serde(default)]
+    client: Option<RemoteRef>,
+    #[serde(default = "active")]
+    is_active: bool,
+    #[serde(default)]
+    task_assignments: Vec<RemoteTaskAssignment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssignmentPage {
+    #[serde(default)]
+    project_assignments: Vec<RemoteAssignment>,
+    #[serde(default)]
+    next_page: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteEntry {
+    id: i64,
+    #[serde(default)]
+    is_locked: bool,
+    #[serde(default)]
+    locked_reason: Option<String>,
+    #[serde(default)]
+    is_billed: bool,
+    #[serde(default)]
+    billable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteError {
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// What a stopped entry sends to Harvest, with its project by name. Its
+/// hash is kept with the Harvest id to tell when it needs pushing again.
+#[derive(Debug, Clone, Serialize)]
+struct Export {
+    /// The day the entry started, where it was recorded.
+    spent_date: String,
+    /// Clock times like `9:30am`, for accounts tracking them. Entries past
+    /// midnight go as hours.
+    started_time: Option<String>,
+    ended_time: Option<String>,
+    hours: f64,
+    notes: String,
+    project: Option<String>,
+    /// The Harvest project and task `project` is mapped to.
+    mapped: Option<(i64, i64)>,
+    billable: bool,
+}
+
+impl Export {
+    fn new(
+        entry: &TimeEntry,
+        ended_at: &str,
+        task: Option<&Task>,
+        mappings: &HashMap<String, (i64, i64)>,
+        timestamps: bool,
+    ) -> CommandResult<Self> {
+        let zone = entry.tz.clone().unwrap_or_else(timezone::system_zone);
+        let tz: Tz = timezone::parse_zone(&zone)?;
+        let started = parse_utc(&entry.started_at)?;
+        let ended = parse_utc(ended_at)?;
+        let seconds = (ended - started).num_seconds().max(0);
+        let start = started.with_timezone(&tz);
+        let end = ended.with_timezone(&tz);
+        let (started_time, ended_time) = if timestamps && start.date_naive() == end.date_naive() {
+            (
+                Some(start.format("%-I:%M%P").to_string()),
+                Some(end.format("%-I:%M%P").to_string()),
+            )
+        } else {
+            (None, None)
+        };
+        let project = time_push::project(task);
+        let mapped = project
+            .as_ref()
+            .and_then(|p| mappings.get(&p.to_lowercase()).copied());
+        Ok(Self {
+            spent_date: start.format("%Y-%m-%d").to_string(),
+            started_time,
+            ended_time,
+            hours: (seconds as f64 / 36.0).round() / 100.0,
+            notes: time_push::description(entry, task),
+            project,
+            mapped,
+            billable: entry.billable,
+        })
+    }
+
+    /// The request body for the entry under `project_id` and `task_id`.
+    fn body(&self, project_id: i64, task_id: i64) -> serde_json::Value {
+        let mut body = json!({
+            "project_id": project_id,
+            "task_id": task_id,
+            "spent_date": self.spent_date,
+            "notes": self.notes,
+        });
+        match (&self.started_time, &self.ended_time) {
+            (Some(started), Some(ended)) => {
+                body["started_time"] = json!(started);
+                body["ended_time"] = json!(ended);
+            }
+            _ => body["hours"] = json!(self.hours),
+        }
+        body
+    }
+}
+
+/// How Harvest bills a pushed entry.
+#[derive(Debug, Clone, Default)]
+struct Billing {
+    /// Going by the entry's task and project.
+    billable: Option<bool>,
+    invoiced: bool,
+}
+
+impl time_push::Extra for Billing {
+    const COLUMNS: &'static [&'static str] = &["billable", "invoiced"];
+
+    fn read(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Self> {
+        Ok(Self {
+            billable: row.get(first)?,
+            invoiced: row.get(first + 1)?,
+        })
+    }
+
+    fn values(&self) -> Vec<&dyn ToSql> {
+        vec![&self.billable, &self.invoiced]
+    }
+}
+
+type PushedRow = time_push::PushedRow<i64, Billing>;
+
+/// A locked entry that didn't change since it was pushed counts as
+/// current, so it isn't sent again.
+type Outgoing = time_push::Outgoing<Export, i64, Billing>;
+
+fn entry_status(item: &Outgoing) -> HarvestEntryStatus {
+    let row = item.row.as_ref();
+    HarvestEntryStatus {
+        time_entry_id: item.entry.id.clone(),
+        status: item.status().to_string(),
+        remote_id: row.and_then(|r| r.remote_id),
+        error: row.and_then(|r| r.error.clone()),
+        billable: row.and_then(|r| r.extra.billable),
+        invoiced: row.is_some_and(|r| r.extra.invoiced),
+        pushed_at: row.and_then(|r| r.pushed_at.clone()),
+    }
+}
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<HarvestAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, harvest_account_id, timestamps, default_project_id,
+                default_task_id, created_at, updated_at
+         FROM harvest_accounts ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(HarvestAccount {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            harvest_account_id: row.get(2)?,
+            timestamps: row.get(3)?,
+            default_project_id: row.get(4)?,
+            default_task_id: row.get(5)?,
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> CommandResult<HarvestAccount> {
+    list_accounts(conn)?
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| CommandError::not_found(format!("Harvest account not found: {id}")))
+}
+
+fn list_mappings(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<HarvestMapping>> {
+    let mut stmt = conn.prepare(
+        "SELECT project, remote_project_id, remote_task_id FROM harvest_projects
+         WHERE account_id = ?1 ORDER BY project",
+    )?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok(HarvestMapping {
+            project: row.get(0)?,
+            remote_project_id: row.get(1)?,
+            remote_task_id: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("harvest:{account_id}")
+}
+
+/// Whether Harvest refused an entry for being in a locked timesheet.
+fn is_lock_error(error: &str) -> bool {
+    error.to_lowercase().contains("lock")
+}
+
+/// A connection to the Harvest API with one account's token.
+struct Api {
+    client: Client,
+    token: String,
+    /// The Harvest account requests go to.
+    harvest_account_id: i64,
+}
+
+impl Api {
+    fn new(token: String, harvest_account_id: i64) -> CommandResult<Self> {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(USER_AGENT)
+            .build()?;
+        Ok(Self {
+            client,
+            token,
+            harvest_account_id,
+        })
+    }
+
+    fn connect(account: &HarvestAccount) -> CommandResult<Self> {
+        Self::new(
+            credentials::require(&keyring_name(&account.id), "access token")?,
+            account.harvest_account_id,
+        )
+    }
+
+    /// Send a request to `url`. `None` when Harvest has no such thing.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        let request = || {
+            self.client
+                .request(method.clone(), url)
+                .bearer_auth(&self.token)
+                .header("Harvest-Account-Id", self.harvest_account_id.to_string())
+        };
+        time_push::send(&SERVICE, request, body).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> CommandResult<T> {
+        self.send(Method::GET, &format!("{API_URL}{path}"), None)
+            .await?
+            .ok_or_else(|| CommandError::not_found(format!("Harvest: {path} not found")))
+    }
+
+    /// The projects the user can log time to, with their tasks.
+    async fn assignments(&self) -> CommandResult<Vec<RemoteAssignment>> {
+        let mut assignments = Vec::new();
+        let mut page = Some(1);
+        while let Some(number) = page {
+            let found: AssignmentPage = self
+                .get(&format!("/users/me/project_assignments?page={number}"))
+                .await?;
+            assignments.extend(
+                found
+                    .project_assignments
+                    .into_iter()
+                    .filter(|a| a.is_active),
+            );
+            page = found.next_page;
+        }
+        Ok(assignments)
+    }
+}
+
+/// The time entries between `from` and `to` (UTC), each with what it sends
+/// to Harvest and what `account` was last sent for it.
+fn outgoing(
+    conn: &Connection,
+    account: &HarvestAccount,
+    from: &str,
+    to: &str,
+) -> CommandResult<Vec<Outgoing>> {
+    let mappings: HashMap<String, (i64, i64)> = list_mappings(conn, &account.id)?
+        .into_iter()
+        .map(|m| {
+            (
+                m.project.to_lowercase(),
+                (m.remote_project_id, m.remote_task_id),
+            )
+        })
+        .collect();
+    time_push::outgoing(
+        conn,
+        ENTRIES_TABLE,
+        &account.id,
+        from,
+        to,
+        |entry, ended_at, task, _| {
+            Export::new(entry, ended_at, task, &mappings, account.timestamps)
+        },
+    )
+}
+
+/// The task of `assignment` time goes to: the first one billed as the
+/// entry is, or the first one at all.
+fn pick_task(assignment: &RemoteAssignment, billable: bool) -> Option<i64> {
+    let tasks: Vec<&RemoteTaskAssignment> = assignment
+        .task_assignments
+        .iter()
+        .filter(|t| t.is_active)
+        .collect();
+    tasks
+        .iter()
+        .find(|t| t.billable.unwrap_or(false) == billable)
+        .or_else(|| tasks.first())
+        .map(|t| t.task.id)
+}
+
+/// How pushing an entry went.
+enum Outcome {
+    Created(RemoteEntry),
+    Updated(RemoteEntry),
+    /// Harvest locked the entry, so it was left as it is there.
+    Locked(RemoteEntry),
+}
+
+/// Pushes entries to one Harvest account.
+struct Pusher<'a> {
+    api: &'a Api,
+    account: &'a HarvestAccount,
+    /// Fetched when the first entry without a mapping is pushed.
+    assignments: Option<Vec<RemoteAssignment>>,
+}
+
+impl Pusher<'_> {
+    /// The Harvest project and task for `export`: its mapping, else the
+    /// project of the same name, else the account's default.
+    async fn target(&mut self, export: &Export) -> CommandResult<(i64, i64)> {
+        if let Some(mapped) = export.mapped {
+            return Ok(mapped);
+        }
+        if let Some(name) = &export.project {
+            let assignments = match &self.assignments {
+                Some(assignments) => assignments,
+                None => self.assignments.insert(self.api.assignments().await?),
+            };
+            let named = assignments
+                .iter()
+                .find(|a| a.project.name.eq_ignore_ascii_case(name.trim()));
+            if let Some(assignment) = named {
+                return pick_task(assignment, export.billable)
+                    .map(|task| (assignment.project.id, task))
+                    .ok_or_else(|| {
+                        CommandError::invalid(format!(
+                            "The Harvest project {} has no tasks",
+                            assignment.project.name
+                        ))
+                    });
+            }
+        }
+        match (
+            self.account.default_project_id,
+            self.account.default_task_id,
+        ) {
+            (Some(project), Some(task)) => Ok((project, task)),
+            _ => Err(CommandError::invalid(match &export.project {
+                Some(name) => format!("No Harvest project for {name}; map it to one"),
+                None => "No default Harvest project for time outside projects".to_string(),
+            })),
+        }
+    }
+
+    /// Create the entry in Harvest, or update it when it has a Harvest id
+    /// and Harvest hasn't locked it. An entry deleted in Harvest is made
+    /// again.
+    async fn push(&mut self, export: &Export, remote_id: Option<i64>) -> CommandResult<Outcome> {
+        let (project_id, task_id) = self.target(export).await?;
+        let body = export.body(project_id, task_id);
+        let base = format!("{API_URL}/time_entries");
+        if let Some(remote_id) = remote_id {
+            let url = format!("{base}/{remote_id}");
+            let existing: Option<RemoteEntry> = self.api.send(Method::GET, &url, None).await?;
+            if let Some(existing) = existing {
+                if existing.is_locked {
+                    return Ok(Outcome::Locked(existing));
+                }
+                let updated: Option<RemoteEntry> =
+                    self.api.send(Method::PATCH, &url, Some(&body)).await?;
+                if let Some(updated) = updated {
+                    return Ok(Outcome::Updated(updated));
+                }
+            }
+        }
+        self.api
+            .send(Method::POST, &base, Some(&body))
+            .await?
+            .map(Outcome::Created)
+            .ok_or_else(|| CommandError::not_found("Harvest: the account is gone"))
+    }
+}
+
+async fn push_account(
+    db: &Db,
+    account_id: &str,
+    from: &str,
+    to: &str,
+) -> CommandResult<HarvestPushReport> {
+    let mut account = db.with_conn(|conn| Ok(find_account(conn, account_id)))??;
+    let api = Api::connect(&account)?;
+    // The account may have switched between durations and clock times.
+    let company: RemoteCompany = api.get("/company").await?;
+    if company.wants_timestamp_timers != account.timestamps {
+        account.timestamps = company.wants_timestamp_timers;
+        db.with_conn(|conn| {
+            conn.execute(
+                "UPDATE harvest_accounts SET timestamps = ?2, updated_at = ?3 WHERE id = ?1",
+                params![account.id, account.timestamps, now_utc()],
+            )
+        })?;
+    }
+    let outgoing = db.with_conn(|conn| Ok(outgoing(conn, &account, from, to)))??;
+    let mut pusher = Pusher {
+        api: &api,
+        account: &account,
+        assignments: None,
+    };
+    let mut report = HarvestPushReport {
+        account_id: account.id.clone(),
+        ..HarvestPushReport::default()
+    };
+    for item in outgoing {
+        let Some(export) = &item.export else {
+            report.running += 1;
+            continue;
+        };
+        if item.is_current() {
+            report.unchanged += 1;
+            continue;
+        }
+        let previous = item.row.as_ref();
+        let mut row = PushedRow {
+            remote_id: previous.and_then(|r| r.remote_id),
+            status: STATUS_SYNCED.to_string(),
+            error: None,
+            digest: Some(time_push::digest(export)),
+            pushed_at: Some(now_utc()),
+            extra: previous.map(|r| r.extra.clone()).unwrap_or_default(),
+        };
+        let problem = match pusher.push(export, row.remote_id).await {
+            Ok(Outcome::Created(remote)) => {
+                report.created += 1;
+                row.remote_id = Some(remote.id);
+                row.extra.billable = Some(remote.billable);
+                row.extra.invoiced = remote.is_billed;
+                None
+            }
+            Ok(Outcome::Updated(remote)) => {
+                report.updated += 1;
+                row.extra.billable = Some(remote.billable);
+                row.extra.invoiced = remote.is_billed;
+                None
+            }
+            Ok(Outcome::Locked(remote)) => {
+                report.locked += 1;
+                let reason = remote
+                    .locked_reason
+                    .filter(|r| !r.is_empty())
+                    .unwrap_or_else(|| "Locked in Harvest".to_string());
+                row.status = STATUS_LOCKED.to_string();
+                row.error = Some(reason.clone());
+                row.digest = previous.and_then(|r| r.digest.clone());
+                row.extra.invoiced = remote.is_billed;
+                Some(reason)
+            }
+            Err(e) => {
+                tracing::warn!("push of entry {} failed: {e}", item.entry.id);
+                row.status = if is_lock_error(&e.message) {
+                    report.locked += 1;
+                    STATUS_LOCKED
+                } else {
+                    report.failed += 1;
+                    STATUS_FAILED
+                }
+                .to_string();
+                row.error = Some(e.to_string());
+                row.digest = previous.and_then(|r| r.digest.clone());
+                row.error.clone()
+            }
+        };
+        if let Some(reason) = problem {
+            report.not_exported.push(HarvestProblem {
+                time_entry_id: item.entry.id.clone(),
+                notes: export.notes.clone(),
+                reason,
+            });
+        }
+        // Saved as each goes, so an interrupted push can't create an entry
+        // twice.
+        db.with_conn(|conn| {
+            time_push::save_row(conn, ENTRIES_TABLE, &account.id, &item.entry.id, &row)
+        })?;
+    }
+    Ok(report)
+}
+
+/// Check an access token and save the account, pushing to
+/// `harvest_account_id` or the first Harvest account the token reaches.
+#[tauri::command]
+pub async fn connect_harvest_account(
+    db: State<'_, Db>,
+    input: NewHarvestAccount,
+) -> CommandResult<HarvestAccount> {
+    let token = input.token.trim().to_string();
+    if token.is_empty() {
+        return Err(CommandError::invalid("Enter the Harvest access token"));
+    }
+    let lookup = Api::new(token.clone(), 0)?;
+    let found: RemoteAccounts = lookup
+        .send(Method::GET, ACCOUNTS_URL, None)
+        .await?
+        .unwrap_or(RemoteAccounts {
+            accounts: Vec::new(),
+        });
+    let remote = found
+        .accounts
+        .into_iter()
+        .filter(|a| a.product.is_empty() || a.product == "harvest")
+        .find(|a| input.harvest_account_id.is_none_or(|id| id == a.id))
+        .ok_or_else(|| match input.harvest_account_id {
+            Some(id) => format!("No Harvest account {id} for this token"),
+            None => "This token reaches no Harvest account".to_string(),
+        })?;
+    let api = Api::new(token.clone(), remote.id)?;
+    let company: RemoteCompany = api.get("/company").await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(remote.name);
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO harvest_accounts
+                 (id, name, harvest_account_id, timestamps, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![id, name, remote.id, company.wants_timestamp_timers, now],
+        )?;
+        list_accounts(conn)
+    });
+    let accounts = match stored {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            return Err(e);
+        }
+    };
+    Ok(accounts
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| "Failed to save account".to_string())?)
+}
+
+#[tauri::command]
+pub fn list_harvest_accounts(db: State<'_, Db>) -> CommandResult<Vec<HarvestAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Forget an account and which entries went to it. Entries already in
+/// Harvest stay there.
+#[tauri::command]
+pub fn remove_harvest_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| {
+        conn.execute("DELETE FROM harvest_accounts WHERE id = ?1", params![id])
+    })?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Harvest account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+/// The projects the user can log time to in Harvest, by client, with their
+/// tasks and which local projects are mapped to which.
+#[tauri::command]
+pub async fn list_harvest_projects(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<HarvestProjects> {
+    let account = db.with_conn(|conn| Ok(find_account(conn, &account_id)))??;
+    let api = Api::connect(&account)?;
+    let mut projects: Vec<HarvestProject> = api
+        .assignments()
+        .await?
+        .into_iter()
+        .map(|a| HarvestProject {
+            id: a.project.id,
+            name: a.project.name,
+            code: a.project.code.filter(|c| !c.is_empty()),
+            client_id: a.client.as_ref().map(|c| c.id),
+            client_name: a.client.map(|c| c.name),
+            tasks: a
+                .task_assignments
+                .into_iter()
+                .filter(|t| t.is_active)
+                .map(|t| HarvestTask {
+                    id: t.task.id,
+                    name: t.task.name,
+                    billable: t.billable.unwrap_or(false),
+                })
+                .collect(),
+        })
+        .collect();
+    projects.sort_by_key(|p| {
+        (
+            p.client_name.as_deref().unwrap_or("").to_lowercase(),
+            p.name.to_lowercase(),
+        )
+    });
+    let mappings = db.with_conn(|conn| list_mappings(conn, &account.id))?;
+    Ok(HarvestProjects {
+        projects,
+        mappings,
+        default_project_id: account.default_project_id,
+        default_task_id: account.default_task_id,
+    })
+}
+
+/// Send time on tasks in `project` to a Harvest project and task, or set
+/// the account's default for time that has nowhere else to go when
+/// `project` is `None`. Without ids, the mapping or default is dropped.
+/// Entries already pushed move with the next push.
+#[tauri::command]
+pub fn map_harvest_project(
+    db: State<'_, Db>,
+    account_id: String,
+    project: Option<String>,
+    remote_project_id: Option<i64>,
+    remote_task_id: Option<i64>,
+) -> CommandResult<Vec<HarvestMapping>> {
+    let target = match (remote_project_id, remote_task_id) {
+        (Some(project_id), Some(task_id)) => Some((project_id, task_id)),
+        (None, None) => None,
+        _ => {
+            return Err(CommandError::invalid(
+                "Choose both a Harvest project and a task",
+            ))
+        }
+    };
+    let project = project.map(|p| p.trim().to_string());
+    if project.as_deref() == Some("") {
+        return Err(CommandError::invalid("Choose a project to map"));
+    }
+    db.with_conn(|conn| {
+        let account = match find_account(conn, &account_id) {
+            Ok(account) => account,
+            Err(e) => return Ok(Err(e)),
+        };
+        match (&project, target) {
+            (Some(project), Some((project_id, task_id))) => conn.execute(
+                "INSERT INTO harvest_projects
+                     (account_id, project, remote_project_id, remote_task_id)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(account_id, project) DO UPDATE SET
+                     project = excluded.project,
+                     remote_project_id = excluded.remote_project_id,
+                     remote_task_id = excluded.remote_task_id",
+                params![account.id, project, project_id, task_id],
+            )?,
+            (Some(project), None) => conn.execute(
+                "DELETE FROM harvest_projects WHERE account_id = ?1 AND project = ?2",
+                params![account.id, project],
+            )?,
+            (None, target) => conn.execute(
+                "UPDATE harvest_accounts
+                 SET default_project_id = ?2, default_task_id = ?3, updated_at = ?4
+                 WHERE id = ?1",
+                params![
+                    account.id,
+                    target.map(|t| t.0),
+                    target.map(|t| t.1),
+                    now_utc()
+                ],
+            )?,
+        };
+        list_mappings(conn, &account.id).map(Ok)
+    })?
+}
+
+/// Push the stopped time entries between `from` and `to` to Harvest: the
+/// task's title (and the entry's note) as the notes, filed under the
+/// mapped Harvest project and task, the project of the same name, or the
+/// account's default. Harvest bills and invoices entries by their task
+/// and project. Entries pushed before are updated when they or their task
+/// changed, unless Harvest locked them; the report lists every entry that
+/// couldn't be exported and why.
+#[tauri::command]
+pub async fn push_to_harvest(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+) -> CommandResult<HarvestPushReport> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    let _pushing = Pushing::start("Harvest")?;
+    push_account(&db, &account_id, &from, &to).await
+}
+
+/// Where each time entry between `from` and `to` stands with `account_id`.
+#[tauri::command]
+pub fn list_harvest_entry_status(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+) -> CommandResult<Vec<HarvestEntryStatus>> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    db.with_conn(|conn| {
+        Ok(find_account(conn, &account_id)
+            .and_then(|account| outgoing(conn, &account, &from, &to))
+            .map(|items| items.iter().map(entry_status).collect()))
+    })?
+}
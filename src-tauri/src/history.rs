@@ -0,0 +1,89 @@
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::CommandResult;
+
+/// Who made a change. Triggers stamp every history row with the current
+/// source, which is `User` unless a caller wraps its writes in `with_source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSource {
+    User,
+    Import,
+    Sync,
+    Cli,
+}
+
+impl ChangeSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeSource::User => "user",
+            ChangeSource::Import => "import",
+            ChangeSource::Sync => "sync",
+            ChangeSource::Cli => "cli",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskChange {
+    pub id: i64,
+    pub task_id: String,
+    /// A task column, `tags` (one row per tag added or removed), or
+    /// `created`/`deleted`.
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_at: String,
+    pub source: String,
+}
+
+fn row_to_change(row: &Row) -> rusqlite::Result<TaskChange> {
+    Ok(TaskChange {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        field: row.get(2)?,
+        old_value: row.get(3)?,
+        new_value: row.get(4)?,
+        changed_at: row.get(5)?,
+        source: row.get(6)?,
+    })
+}
+
+fn set_source(conn: &Connection, source: ChangeSource) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE change_source SET source = ?1 WHERE id = 1",
+        params![source.as_str()],
+    )?;
+    Ok(())
+}
+
+/// Run `f` with history attributed to `source`, then switch back to `User`
+/// whether or not `f` succeeded.
+pub fn with_source<T>(
+    conn: &mut Connection,
+    source: ChangeSource,
+    f: impl FnOnce(&mut Connection) -> T,
+) -> rusqlite::Result<T> {
+    set_source(conn, source)?;
+    let result = f(conn);
+    set_source(conn, ChangeSource::User)?;
+    Ok(result)
+}
+
+pub fn task_history(conn: &Connection, task_id: &str) -> rusqlite::Result<Vec<TaskChange>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, task_id, field, old_value, new_value, changed_at, source
+         FROM task_history WHERE task_id = ?1 ORDER BY id DESC",
+    )?;
+    let rows = stmt.query_map(params![task_id], row_to_change)?;
+    rows.collect()
+}
+
+/// Every recorded change to a task, newest first.
+#[tauri::command]
+pub fn get_task_history(db: State<'_, Db>, task_id: String) -> CommandResult<Vec<TaskChange>> {
+    db.with_conn(|conn| task_history(conn, &task_id))
+}
@@ -0,0 +1,182 @@
+use chrono::{Duration, Utc};
+use reqwest::header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+
+use crate::db::{format_utc, parse_utc};
+use crate::error::{CommandError, CommandResult};
+
+/// A response kept in `http_cache`: its body, the validators to check it
+/// with and how long the server said it stays fresh.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+    pub fetched_at: String,
+    pub expires_at: Option<String>,
+}
+
+/// `webcal://` links (common for calendar subscriptions) are fetched over
+/// HTTPS.
+fn fetch_url(url: &str) -> String {
+    match url.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{rest}"),
+        None => url.to_string(),
+    }
+}
+
+/// GET `url` and return the body as text.
+pub async fn get_text(url: &str) -> CommandResult<String> {
+    let response = reqwest::get(&fetch_url(url)).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CommandError::status(
+            status,
+            format!("HTTP {}", status.as_u16()),
+        ));
+    }
+    Ok(response.text().await?)
+}
+
+/// The body of `response` as JSON.
+pub async fn read_json<T: DeserializeOwned>(response: Response) -> CommandResult<T> {
+    let text = response.text().await?;
+    serde_json::from_str(&text).map_err(|e| e.to_string().into())
+}
+
+pub fn load_cached(conn: &Connection, url: &str) -> rusqlite::Result<Option<CachedResponse>> {
+    conn.query_row(
+        "SELECT url, etag, last_modified, body, fetched_at, expires_at
+         FROM http_cache WHERE url = ?1",
+        params![url],
+        |row| {
+            Ok(CachedResponse {
+                url: row.get(0)?,
+                etag: row.get(1)?,
+                last_modified: row.get(2)?,
+                body: row.get(3)?,
+                fetched_at: row.get(4)?,
+                expires_at: row.get(5)?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub fn store_cached(conn: &Connection, cached: &CachedResponse) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO http_cache
+             (url, etag, last_modified, body, fetched_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            cached.url,
+            cached.etag,
+            cached.last_modified,
+            cached.body,
+            cached.fetched_at,
+            cached.expires_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// The longest a response is served from the cache without asking the
+/// server again, whatever its `max-age` says: a week.
+const MAX_AGE_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// When a response stops being fresh, from its `Cache-Control: max-age`.
+/// `no-cache` and a missing max-age mean it's checked every time; a
+/// negative max-age means it's stale already.
+fn expires_at(cache_control: Option<&str>) -> Option<String> {
+    let directives: Vec<String> = cache_control?
+        .split(',')
+        .map(|d| d.trim().to_ascii_lowercase())
+        .collect();
+    if directives
+        .iter()
+        .any(|d| d == "no-cache" || d == "no-store")
+    {
+        return None;
+    }
+    let seconds: i64 = directives
+        .iter()
+        .find_map(|d| d.strip_prefix("max-age="))?
+        .trim_matches('"')
+        .parse()
+        .ok()?;
+    let age = Duration::try_seconds(seconds.clamp(0, MAX_AGE_SECS))?;
+    Some(format_utc(Utc::now().checked_add_signed(age)?))
+}
+
+/// GET `url` through `cached`, its last response: served from it while
+/// fresh, otherwise revalidated with its ETag or Last-Modified so an
+/// unchanged resource isn't downloaded again. Returns the response to cache
+/// in its place.
+pub async fn get_text_cached(
+    url: &str,
+    cached: Option<CachedResponse>,
+) -> CommandResult<CachedResponse> {
+    let now = Utc::now();
+    if let Some(cached) = &cached {
+        let fresh = cached
+            .expires_at
+            .as_deref()
+            .and_then(|at| parse_utc(at).ok())
+            .is_some_and(|at| at > now);
+        if fresh {
+            return Ok(cached.clone());
+        }
+    }
+
+    let mut request = reqwest::Client::new().get(fetch_url(url));
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request.send().await?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let expires = expires_at(header(CACHE_CONTROL).as_deref());
+    let status = response.status();
+    if let (StatusCode::NOT_MODIFIED, Some(cached)) = (status, cached) {
+        return Ok(CachedResponse {
+            fetched_at: format_utc(now),
+            expires_at: expires,
+            ..cached
+        });
+    }
+    if !status.is_success() {
+        return Err(CommandError::status(
+            status,
+            format!("HTTP {}", status.as_u16()),
+        ));
+    }
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+    let body = response.text().await?;
+    Ok(CachedResponse {
+        url: url.to_string(),
+        etag,
+        last_modified,
+        body,
+        fetched_at: format_utc(now),
+        expires_at: expires,
+    })
+}
+
+pub fn is_url(source: &str) -> bool {
+    ["http://", "https://", "webcal://"]
+        .iter()
+        .any(|scheme| source.starts_with(scheme))
+}
@@ -0,0 +1,1317 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::conflicts::{self, ConflictPolicy};
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::export::write_streamed;
+use crate::history::{self, ChangeSource};
+use crate::http;
+use crate::reminders;
+use crate::rrule::Rrule;
+use crate::tags;
+use crate::task_store::{self, row_to_task, Task, STATUS_DONE, STATUS_OPEN, TASK_COLUMNS};
+use crate::timezone;
+use crate::trash;
+
+const PRODID: &str = "-//DayLight//DayLight//EN";
+const UID_DOMAIN: &str = "daylight";
+
+/// Length of the event exported for a task scheduled at a time of day.
+pub const DEFAULT_BLOCK_MINUTES: i64 = 30;
+
+/// RFC 5545 caps content lines at 75 octets; longer lines are folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IcsScope {
+    /// Every task as a VTODO.
+    Tasks,
+    /// Tasks scheduled at a time of day, as VEVENT blocks.
+    Schedule,
+    All,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IcsExportSummary {
+    pub todos: u64,
+    pub events: u64,
+}
+
+/// Escape a TEXT value (RFC 5545 3.3.11).
+fn escape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Write one content line, folding it at 75 octets without splitting a
+/// UTF-8 sequence.
+fn write_line<W: Write>(out: &mut W, line: &str) -> std::io::Result<()> {
+    let mut rest = line;
+    let mut limit = MAX_LINE_OCTETS;
+    while rest.len() > limit {
+        let mut cut = limit;
+        while !rest.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        out.write_all(&rest.as_bytes()[..cut])?;
+        out.write_all(b"\r\n ")?;
+        rest = &rest[cut..];
+        // Continuation lines spend one octet on the leading space.
+        limit = MAX_LINE_OCTETS - 1;
+    }
+    out.write_all(rest.as_bytes())?;
+    out.write_all(b"\r\n")
+}
+
+fn format_instant(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// A `due`/`scheduled` value as an iCalendar property: `;VALUE=DATE:...` for
+/// a plain date, or a UTC DATE-TIME for a wall-clock time in the task's zone.
+fn date_property(name: &str, value: &str, zone: Option<&str>) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(format!("{name};VALUE=DATE:{}", format_date(date)));
+    }
+    let instant = local_instant(value, zone)?;
+    Some(format!("{name}:{}", format_instant(instant)))
+}
+
+pub fn local_instant(value: &str, zone: Option<&str>) -> Option<DateTime<Utc>> {
+    let local = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").ok()?;
+    let zone = zone
+        .map(str::to_string)
+        .unwrap_or_else(timezone::system_zone);
+    let tz = timezone::parse_zone(&zone).ok()?;
+    timezone::resolve_local(local, &tz).map(|dt| dt.with_timezone(&Utc))
+}
+
+/// 3/2/1 (high/normal/low) onto iCalendar's 1 (highest) to 9 (lowest).
+fn ical_priority(priority: Option<i64>) -> Option<u8> {
+    match priority? {
+        p if p >= 3 => Some(1),
+        2 => Some(5),
+        1 => Some(9),
+        _ => None,
+    }
+}
+
+pub fn uid(task: &Task, kind: &str) -> String {
+    format!("{}-{kind}@{UID_DOMAIN}", task.id)
+}
+
+/// Lines shared by VTODO and VEVENT: identity, stamps, text and categories.
+fn common_lines(task: &Task, uid: &str, stamp: &str) -> Vec<String> {
+    let mut lines = vec![
+        format!("UID:{uid}"),
+        format!("DTSTAMP:{stamp}"),
+        format!("SUMMARY:{}", escape_text(&task.title)),
+    ];
+    if let Ok(created) = parse_utc(&task.created_at) {
+        lines.push(format!("CREATED:{}", format_instant(created)));
+    }
+    if let Ok(modified) = parse_utc(&task.updated_at) {
+        lines.push(format!("LAST-MODIFIED:{}", format_instant(modified)));
+    }
+    if let Some(description) = &task.description {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|t| escape_text(t)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(project) = &task.project {
+        lines.push(format!("X-DAYLIGHT-PROJECT:{}", escape_text(project)));
+    }
+    lines
+}
+
+/// RRULE and EXDATE lines, plus the DTSTART they hang off when the task has
+/// no scheduled date of its own. `start` is the task's own DTSTART line.
+fn recurrence_lines(rule: &str, start: Option<&str>) -> Vec<String> {
+    let Ok(mut rule) = Rrule::parse(rule) else {
+        return Vec::new();
+    };
+    let mut lines = Vec::new();
+    let timed_start = start.is_some_and(|s| !s.contains("VALUE=DATE"));
+    if start.is_none() {
+        lines.push(format!("DTSTART;VALUE=DATE:{}", format_date(rule.dtstart)));
+    }
+    let until = rule.until.take();
+    let mut value = rule.rrule_value();
+    // UNTIL has to match DTSTART's type: a date, or a UTC date-time.
+    if let Some(until) = until {
+        let suffix = if timed_start { "T235959Z" } else { "" };
+        value.push_str(&format!(";UNTIL={}{suffix}", format_date(until)));
+    }
+    lines.push(format!("RRULE:{value}"));
+    if !rule.exdates.is_empty() {
+        let dates: Vec<String> = rule.exdates.iter().map(|d| format_date(*d)).collect();
+        lines.push(format!("EXDATE;VALUE=DATE:{}", dates.join(",")));
+    }
+    lines
+}
+
+fn todo_lines(task: &Task, uid: &str, stamp: &str, with_rule: bool) -> Vec<String> {
+    let zone = task.tz.as_deref();
+    let mut lines = vec!["BEGIN:VTODO".to_string()];
+    lines.extend(common_lines(task, uid, stamp));
+
+    let start = task
+        .scheduled
+        .as_deref()
+        .and_then(|s| date_property("DTSTART", s, zone));
+    lines.extend(start.clone());
+    lines.extend(
+        task.due
+            .as_deref()
+            .and_then(|d| date_property("DUE", d, zone)),
+    );
+    if let Some(priority) = ical_priority(task.priority) {
+        lines.push(format!("PRIORITY:{priority}"));
+    }
+    if task.status == STATUS_DONE {
+        lines.push("STATUS:COMPLETED".to_string());
+        lines.push("PERCENT-COMPLETE:100".to_string());
+        if let Some(completed) = task.completed_at.as_deref().and_then(|c| parse_utc(c).ok()) {
+            lines.push(format!("COMPLETED:{}", format_instant(completed)));
+        }
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+    if with_rule {
+        if let Some(rule) = &task.recurrence {
+            lines.extend(recurrence_lines(rule, start.as_deref()));
+        }
+    }
+    lines.push("END:VTODO".to_string());
+    lines
+}
+
+/// A VEVENT for a task scheduled at a time of day, or `None` for date-only
+/// and unscheduled tasks.
+fn event_lines(task: &Task, stamp: &str) -> Option<Vec<String>> {
+    let start = local_instant(task.scheduled.as_deref()?, task.tz.as_deref())?;
+    let end = start + chrono::Duration::minutes(DEFAULT_BLOCK_MINUTES);
+    let mut lines = vec!["BEGIN:VEVENT".to_string()];
+    lines.extend(common_lines(task, &uid(task, "event"), stamp));
+    lines.push(format!("DTSTART:{}", format_instant(start)));
+    lines.push(format!("DTEND:{}", format_instant(end)));
+    lines.push("TRANSP:OPAQUE".to_string());
+    if task.status == STATUS_DONE {
+        lines.push("X-DAYLIGHT-COMPLETED:TRUE".to_string());
+    }
+    lines.push("END:VEVENT".to_string());
+    Some(lines)
+}
+
+/// Ids of the newest instance of each recurring series. Only these carry
+/// the RRULE, so a calendar app doesn't expand the same series once per
+/// materialized instance.
+fn series_heads(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM tasks t
+         WHERE recurrence IS NOT NULL AND series_id IS NOT NULL AND deleted_at IS NULL
+           AND NOT EXISTS (
+               SELECT 1 FROM tasks later
+               WHERE later.series_id = t.series_id AND later.deleted_at IS NULL
+                 AND (COALESCE(later.scheduled, later.due) > COALESCE(t.scheduled, t.due)
+                      OR (COALESCE(later.scheduled, later.due) = COALESCE(t.scheduled, t.due)
+                          AND later.created_at > t.created_at))
+           )",
+    )?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Write tasks (and/or time-blocked tasks as events) to an .ics file.
+/// Rows are streamed, so the whole calendar never has to fit in memory.
+pub fn export(conn: &Connection, path: &Path, scope: IcsScope) -> CommandResult<IcsExportSummary> {
+    let db_err = |e: rusqlite::Error| format!("Failed to read tasks for export: {e}");
+    let write_err = |e: std::io::Error| {
+        CommandError::from(e).context(format!("Failed to write {}", path.display()))
+    };
+    let heads = series_heads(conn).map_err(db_err)?;
+    let stamp = format_instant(Utc::now());
+    let todos = matches!(scope, IcsScope::Tasks | IcsScope::All);
+    let events = matches!(scope, IcsScope::Schedule | IcsScope::All);
+    let mut summary = IcsExportSummary {
+        todos: 0,
+        events: 0,
+    };
+
+    write_streamed(path, |out| {
+        for line in [
+            "BEGIN:VCALENDAR",
+            "VERSION:2.0",
+            &format!("PRODID:{PRODID}"),
+            "CALSCALE:GREGORIAN",
+            "X-WR-CALNAME:DayLight",
+        ] {
+            write_line(out, line).map_err(write_err)?;
+        }
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks WHERE deleted_at IS NULL ORDER BY created_at"
+            ))
+            .map_err(db_err)?;
+        let mut rows = stmt.query([]).map_err(db_err)?;
+        while let Some(row) = rows.next().map_err(db_err)? {
+            let mut task = row_to_task(row).map_err(db_err)?;
+            tags::load_task_tags(conn, std::slice::from_mut(&mut task)).map_err(db_err)?;
+
+            let mut lines = Vec::new();
+            if todos {
+                let uid = uid(&task, "todo");
+                lines.extend(todo_lines(&task, &uid, &stamp, heads.contains(&task.id)));
+                summary.todos += 1;
+            }
+            if events {
+                if let Some(event) = event_lines(&task, &stamp) {
+                    lines.extend(event);
+                    summary.events += 1;
+                }
+            }
+            for line in &lines {
+                write_line(out, line).map_err(write_err)?;
+            }
+        }
+        write_line(out, "END:VCALENDAR").map_err(write_err)
+    })?;
+    Ok(summary)
+}
+
+/// One task as a calendar object holding a single VTODO with `uid`, the way
+/// a CalDAV server stores it.
+pub fn todo_calendar(task: &Task, uid: &str) -> String {
+    let stamp = format_instant(Utc::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{PRODID}"),
+    ];
+    lines.extend(todo_lines(task, uid, &stamp, true));
+    lines.push("END:VCALENDAR".to_string());
+    let mut out = Vec::new();
+    for line in &lines {
+        // Writing to a Vec can't fail.
+        let _ = write_line(&mut out, line);
+    }
+    String::from_utf8(out).unwrap_or_default()
+}
+
+/// An all-day (or, with a time, instant) VEVENT on an open task's due
+/// date. It's marked free so it doesn't block time in the subscriber's
+/// calendar.
+fn due_lines(task: &Task, stamp: &str) -> Option<Vec<String>> {
+    let due = task.due.as_deref()?;
+    let uid = uid(task, "due");
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{uid}"),
+        format!("DTSTAMP:{stamp}"),
+        format!("SUMMARY:{}", escape_text(&format!("Due: {}", task.title))),
+    ];
+    if let Ok(date) = NaiveDate::parse_from_str(due, "%Y-%m-%d") {
+        lines.push(format!("DTSTART;VALUE=DATE:{}", format_date(date)));
+        lines.push(format!(
+            "DTEND;VALUE=DATE:{}",
+            format_date(date.succ_opt()?)
+        ));
+    } else {
+        let at = format_instant(local_instant(due, task.tz.as_deref())?);
+        lines.push(format!("DTSTART:{at}"));
+        lines.push(format!("DTEND:{at}"));
+    }
+    if let Ok(modified) = parse_utc(&task.updated_at) {
+        lines.push(format!("LAST-MODIFIED:{}", format_instant(modified)));
+    }
+    lines.push("TRANSP:TRANSPARENT".to_string());
+    lines.push("END:VEVENT".to_string());
+    Some(lines)
+}
+
+/// The schedule as a calendar to subscribe to: time blocks and tasks
+/// scheduled at a time of day as events, plus open tasks on their due
+/// dates, from `since` (YYYY-MM-DD) on. Only VEVENTs, which every calendar
+/// app shows, unlike VTODOs.
+pub fn schedule_feed(conn: &Connection, since: &str) -> rusqlite::Result<String> {
+    let stamp = format_instant(Utc::now());
+    let mut lines: Vec<String> = [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        &format!("PRODID:{PRODID}"),
+        "CALSCALE:GREGORIAN",
+        "METHOD:PUBLISH",
+        "X-WR-CALNAME:DayLight",
+        "REFRESH-INTERVAL;VALUE=DURATION:PT15M",
+        "X-PUBLISHED-TTL:PT15M",
+    ]
+    .iter()
+    .map(|line| line.to_string())
+    .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT b.id, b.starts_at, b.duration_minutes, b.updated_at, t.title, t.id
+         FROM time_blocks b JOIN tasks t ON t.id = b.task_id
+         WHERE b.day >= ?1 AND t.deleted_at IS NULL
+         ORDER BY b.starts_at",
+    )?;
+    let mut rows = stmt.query(params![since])?;
+    while let Some(row) = rows.next()? {
+        let (id, starts_at, minutes): (String, String, i64) =
+            (row.get(0)?, row.get(1)?, row.get(2)?);
+        let Ok(start) = parse_utc(&starts_at) else {
+            continue;
+        };
+        let end = start + chrono::Duration::minutes(minutes);
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{id}-block@{UID_DOMAIN}"),
+            format!("DTSTAMP:{stamp}"),
+            format!("SUMMARY:{}", escape_text(&row.get::<_, String>(4)?)),
+            format!("DTSTART:{}", format_instant(start)),
+            format!("DTEND:{}", format_instant(end)),
+        ]);
+        if let Ok(modified) = parse_utc(&row.get::<_, String>(3)?) {
+            lines.push(format!("LAST-MODIFIED:{}", format_instant(modified)));
+        }
+        lines.push(format!("X-DAYLIGHT-TASK:{}", row.get::<_, String>(5)?));
+        lines.push("TRANSP:OPAQUE".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    drop(rows);
+    drop(stmt);
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TASK_COLUMNS} FROM tasks
+         WHERE deleted_at IS NULL AND status = ?1
+           AND (substr(scheduled, 1, 10) >= ?2 OR substr(due, 1, 10) >= ?2)
+         ORDER BY created_at"
+    ))?;
+    let mut tasks = stmt
+        .query_map(params![STATUS_OPEN, since], row_to_task)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tags::load_task_tags(conn, &mut tasks)?;
+    for task in &tasks {
+        if task
+            .scheduled
+            .as_deref()
+            .is_some_and(|s| s.get(..10) >= Some(since))
+        {
+            lines.extend(event_lines(task, &stamp).unwrap_or_default());
+        }
+        if task
+            .due
+            .as_deref()
+            .is_some_and(|d| d.get(..10) >= Some(since))
+        {
+            lines.extend(due_lines(task, &stamp).unwrap_or_default());
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = Vec::new();
+    for line in &lines {
+        // Writing to a Vec can't fail.
+        let _ = write_line(&mut out, line);
+    }
+    Ok(String::from_utf8(out).unwrap_or_default())
+}
+
+/// Export tasks as VTODOs and time-blocked tasks as VEVENTs to `path`, for
+/// importing into any calendar app.
+#[tauri::command]
+pub fn export_ics(
+    db: State<'_, Db>,
+    path: String,
+    scope: IcsScope,
+) -> CommandResult<IcsExportSummary> {
+    db.with_conn(|conn| Ok(export(conn, Path::new(&path), scope)))?
+}
+
+const IMPORT_SOURCE: &str = "ics";
+const UNTITLED: &str = "Untitled";
+
+/// One parsed content line.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+struct Component {
+    name: String,
+    props: Vec<Property>,
+    children: Vec<Component>,
+}
+
+impl Component {
+    fn prop(&self, name: &str) -> Option<&Property> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    fn props<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> + 'a {
+        self.props.iter().filter(move |p| p.name == name)
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.prop(name)
+            .map(|p| unescape_text(&p.value))
+            .filter(|v| !v.trim().is_empty())
+    }
+}
+
+/// Join folded lines back together (RFC 5545 3.1).
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in text.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        if let Some(rest) = raw.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(rest);
+                continue;
+            }
+        }
+        if !raw.is_empty() {
+            lines.push(raw.to_string());
+        }
+    }
+    lines
+}
+
+/// Split `NAME;PARAM=x;PARAM="y:z":value`, honouring quoted parameters.
+fn parse_property(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let mut parts: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut value_at = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parts.push(&line[start..i]);
+                start = i + 1;
+            }
+            ':' if !quoted => {
+                parts.push(&line[start..i]);
+                value_at = Some(i + 1);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let value = &line[value_at?..];
+    let mut parts = parts.into_iter();
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| {
+            (
+                k.trim().to_ascii_uppercase(),
+                v.trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+fn parse_components(text: &str) -> Vec<Component> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<Component> = Vec::new();
+    for line in unfold(text) {
+        let Some(prop) = parse_property(&line) else {
+            continue;
+        };
+        match prop.name.as_str() {
+            "BEGIN" => stack.push(Component {
+                name: prop.value.trim().to_ascii_uppercase(),
+                props: Vec::new(),
+                children: Vec::new(),
+            }),
+            "END" => {
+                let Some(done) = stack.pop() else {
+                    continue;
+                };
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(done),
+                    None => roots.push(done),
+                }
+            }
+            _ => {
+                if let Some(current) = stack.last_mut() {
+                    current.props.push(prop);
+                }
+            }
+        }
+    }
+    roots
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split a multi-valued TEXT property (CATEGORIES) on unescaped commas.
+fn split_text_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            current.push('\\');
+            current.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ',' {
+            items.push(unescape_text(&std::mem::take(&mut current)));
+        } else {
+            current.push(c);
+        }
+    }
+    items.push(unescape_text(&current));
+    items.retain(|i| !i.trim().is_empty());
+    items
+}
+
+enum IcsTime {
+    Date(NaiveDate),
+    Instant(DateTime<Utc>),
+}
+
+/// Resolve a TZID. Besides plain IANA names, accept prefixed forms such as
+/// `/mozilla.org/20050126_1/Europe/Berlin` by trying the trailing segments.
+fn parse_tzid(tzid: &str) -> Option<Tz> {
+    let segments: Vec<&str> = tzid.split('/').filter(|s| !s.is_empty()).collect();
+    (1..=segments.len().min(3))
+        .rev()
+        .find_map(|n| timezone::parse_zone(&segments[segments.len() - n..].join("/")).ok())
+}
+
+/// Parse a DATE or DATE-TIME value. Floating times and unknown TZIDs are
+/// read in `local`.
+fn parse_time(prop: &Property, local: &Tz) -> Option<IcsTime> {
+    let value = prop.value.trim();
+    if prop.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(IcsTime::Date);
+    }
+    let (naive, utc) = match value.strip_suffix(['Z', 'z']) {
+        Some(v) => (v, true),
+        None => (value, false),
+    };
+    let naive = NaiveDateTime::parse_from_str(naive, "%Y%m%dT%H%M%S").ok()?;
+    if utc {
+        return Some(IcsTime::Instant(naive.and_utc()));
+    }
+    let named = prop.param("TZID").and_then(parse_tzid);
+    timezone::resolve_local(naive, named.as_ref().unwrap_or(local))
+        .map(|dt| IcsTime::Instant(dt.with_timezone(&Utc)))
+}
+
+impl IcsTime {
+    /// As a `due`/`scheduled` value: a date, or wall-clock time in `local`.
+    fn task_value(&self, local: &Tz) -> String {
+        match self {
+            IcsTime::Date(date) => date.format("%Y-%m-%d").to_string(),
+            IcsTime::Instant(at) => at.with_timezone(local).format("%Y-%m-%dT%H:%M").to_string(),
+        }
+    }
+
+    fn instant(&self, local: &Tz) -> Option<DateTime<Utc>> {
+        match self {
+            IcsTime::Date(date) => timezone::resolve_local(date.and_time(NaiveTime::MIN), local)
+                .map(|dt| dt.with_timezone(&Utc)),
+            IcsTime::Instant(at) => Some(*at),
+        }
+    }
+
+    fn date(&self, local: &Tz) -> NaiveDate {
+        match self {
+            IcsTime::Date(date) => *date,
+            IcsTime::Instant(at) => at.with_timezone(local).date_naive(),
+        }
+    }
+}
+
+/// Parse an RFC 5545 DURATION such as `-PT15M` or `P1DT2H`.
+fn parse_duration(value: &str) -> Option<chrono::Duration> {
+    let value = value.trim();
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let rest = rest.strip_prefix(['P', 'p'])?;
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c.to_ascii_uppercase() {
+            'T' => in_time = true,
+            d if d.is_ascii_digit() => number.push(d),
+            unit => {
+                let n: i64 = std::mem::take(&mut number).parse().ok()?;
+                seconds += n * match (unit, in_time) {
+                    ('W', false) => 7 * 86_400,
+                    ('D', false) => 86_400,
+                    ('H', true) => 3_600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(chrono::Duration::seconds(sign * seconds))
+}
+
+/// 1-4 high, 5 normal, 6-9 low; 0 means undefined.
+fn daylight_priority(value: &str) -> Option<i64> {
+    match value.trim().parse::<i64>().ok()? {
+        1..=4 => Some(3),
+        5 => Some(2),
+        6..=9 => Some(1),
+        _ => None,
+    }
+}
+
+/// A VTODO or VEVENT as it would be imported.
+#[derive(Debug, Clone, Serialize)]
+pub struct IcsItem {
+    pub uid: String,
+    /// `todo` or `event`.
+    pub kind: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub due: Option<String>,
+    pub scheduled: Option<String>,
+    pub priority: Option<i64>,
+    pub done: bool,
+    pub completed_at: Option<String>,
+    /// Canonical RRULE string, see `rrule::Rrule`.
+    pub recurrence: Option<String>,
+    pub tags: Vec<String>,
+    /// Alarm times (RFC 3339 UTC), imported as reminders.
+    pub reminders: Vec<String>,
+    /// LAST-MODIFIED (or DTSTAMP) as RFC 3339 UTC. Fields edited in the app
+    /// since then are kept when the item is imported again.
+    pub modified_at: Option<String>,
+    /// The task this item was imported into previously, if any; importing
+    /// again updates it instead of creating a duplicate.
+    pub existing_task_id: Option<String>,
+    /// Parts of the item that couldn't be carried over.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IcsPreview {
+    pub items: Vec<IcsItem>,
+    /// Components left out entirely, with the reason.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IcsImportReport {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// Alarm times for `component`, anchored on its start (or end/due for
+/// `RELATED=END`). Non-display actions are still imported as reminders.
+fn alarm_times(
+    component: &Component,
+    start: Option<&IcsTime>,
+    end: Option<&IcsTime>,
+    local: &Tz,
+    warnings: &mut Vec<String>,
+) -> Vec<String> {
+    let mut times = Vec::new();
+    for alarm in component.children.iter().filter(|c| c.name == "VALARM") {
+        let Some(trigger) = alarm.prop("TRIGGER") else {
+            continue;
+        };
+        let at = if trigger.param("VALUE") == Some("DATE-TIME") {
+            parse_time(trigger, local).and_then(|t| t.instant(local))
+        } else {
+            let anchor = match trigger.param("RELATED") {
+                Some("END") => end.or(start),
+                _ => start.or(end),
+            };
+            match (
+                anchor.and_then(|a| a.instant(local)),
+                parse_duration(&trigger.value),
+            ) {
+                (Some(anchor), Some(offset)) => Some(anchor + offset),
+                _ => None,
+            }
+        };
+        match at {
+            Some(at) => times.push(format_utc(at)),
+            None => warnings.push(format!("Skipped alarm with trigger {}", trigger.value)),
+        }
+    }
+    times.sort();
+    times.dedup();
+    times
+}
+
+/// Convert RRULE/EXDATE properties to DayLight's rule format, anchored on
+/// `start`.
+fn item_recurrence(
+    component: &Component,
+    start: NaiveDate,
+    local: &Tz,
+    warnings: &mut Vec<String>,
+) -> Option<String> {
+    let rule = component.prop("RRULE")?;
+    let mut text = format!("DTSTART:{};{}", format_date(start), rule.value.trim());
+    let exdates: Vec<String> = component
+        .props("EXDATE")
+        .flat_map(|p| {
+            p.value
+                .split(',')
+                .filter_map(|v| {
+                    let single = Property {
+                        name: p.name.clone(),
+                        params: p.params.clone(),
+                        value: v.to_string(),
+                    };
+                    parse_time(&single, local).map(|t| format_date(t.date(local)))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if !exdates.is_empty() {
+        text.push_str(&format!(";EXDATE:{}", exdates.join(",")));
+    }
+    match Rrule::parse(&text) {
+        Ok(rule) => Some(rule.to_string()),
+        Err(e) => {
+            warnings.push(format!("Recurrence not imported: {e}"));
+            None
+        }
+    }
+}
+
+fn existing_task(conn: &Connection, uid: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+        params![IMPORT_SOURCE, uid],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn to_item(component: &Component, local: &Tz) -> CommandResult<IcsItem> {
+    let kind = if component.name == "VTODO" {
+        "todo"
+    } else {
+        "event"
+    };
+    if component.prop("RECURRENCE-ID").is_some() {
+        return Err(CommandError::invalid(
+            "changes to single occurrences of a series aren't supported",
+        ));
+    }
+    let status = component
+        .prop("STATUS")
+        .map(|p| p.value.trim().to_ascii_uppercase());
+    if status.as_deref() == Some("CANCELLED") {
+        return Err("cancelled".into());
+    }
+
+    let mut warnings = Vec::new();
+    let start = component.prop("DTSTART").and_then(|p| parse_time(p, local));
+    let end_prop = if kind == "todo" { "DUE" } else { "DTEND" };
+    let end = component.prop(end_prop).and_then(|p| parse_time(p, local));
+    let title = component
+        .text("SUMMARY")
+        .unwrap_or_else(|| UNTITLED.to_string());
+    let uid = component.text("UID").unwrap_or_else(|| {
+        format!(
+            "{title}|{}",
+            component.prop("DTSTART").map_or("", |p| &p.value)
+        )
+    });
+
+    let completed_at = component
+        .prop("COMPLETED")
+        .and_then(|p| parse_time(p, local))
+        .and_then(|t| t.instant(local))
+        .map(format_utc);
+    let modified_at = component
+        .prop("LAST-MODIFIED")
+        .or_else(|| component.prop("DTSTAMP"))
+        .and_then(|p| parse_time(p, local))
+        .and_then(|t| t.instant(local))
+        .map(format_utc);
+    let done = kind == "todo" && (status.as_deref() == Some("COMPLETED") || completed_at.is_some());
+
+    let recurrence = start
+        .as_ref()
+        .or(end.as_ref())
+        .map(|anchor| anchor.date(local))
+        .and_then(|anchor| item_recurrence(component, anchor, local, &mut warnings));
+    if component.prop("RRULE").is_some() && start.is_none() && end.is_none() {
+        warnings.push("Recurrence not imported: the item has no start or due date".to_string());
+    }
+
+    let tags: Vec<String> = component
+        .props("CATEGORIES")
+        .flat_map(|p| split_text_list(&p.value))
+        .collect();
+    let tags = tags::normalize_names(&tags).unwrap_or_default();
+    let reminders = alarm_times(
+        component,
+        start.as_ref(),
+        end.as_ref(),
+        local,
+        &mut warnings,
+    );
+
+    Ok(IcsItem {
+        uid,
+        kind: kind.to_string(),
+        title,
+        description: component.text("DESCRIPTION"),
+        due: match kind {
+            "todo" => end.as_ref().map(|t| t.task_value(local)),
+            _ => None,
+        },
+        scheduled: start.as_ref().map(|t| t.task_value(local)),
+        priority: component
+            .prop("PRIORITY")
+            .and_then(|p| daylight_priority(&p.value)),
+        done,
+        completed_at,
+        recurrence,
+        tags,
+        reminders,
+        modified_at,
+        existing_task_id: None,
+        warnings,
+    })
+}
+
+/// Parse an iCalendar document into importable items.
+pub fn preview(conn: &Connection, text: &str) -> CommandResult<IcsPreview> {
+    let roots = parse_components(text);
+    if !roots.iter().any(|c| c.name == "VCALENDAR") {
+        return Err(CommandError::invalid("Not an iCalendar file"));
+    }
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+
+    let mut preview = IcsPreview {
+        items: Vec::new(),
+        skipped: Vec::new(),
+    };
+    let components = roots
+        .iter()
+        .filter(|c| c.name == "VCALENDAR")
+        .flat_map(|c| &c.children)
+        .filter(|c| c.name == "VTODO" || c.name == "VEVENT");
+    for component in components {
+        match to_item(component, &local) {
+            Ok(mut item) => {
+                item.existing_task_id =
+                    existing_task(conn, &item.uid).context("Failed to check existing tasks")?;
+                // A deleted task stays deleted rather than being re-imported.
+                if let Some(id) = &item.existing_task_id {
+                    if trash::is_trashed(conn, id).context("Failed to check existing tasks")? {
+                        preview
+                            .skipped
+                            .push(format!("{}: in the trash", item.title));
+                        continue;
+                    }
+                }
+                preview.items.push(item);
+            }
+            Err(reason) => {
+                let title = component
+                    .text("SUMMARY")
+                    .unwrap_or_else(|| UNTITLED.to_string());
+                preview.skipped.push(format!("{title}: {reason}"));
+            }
+        }
+    }
+    Ok(preview)
+}
+
+/// Parse a calendar object from a CalDAV server: its VTODO, leaving out
+/// overrides of single occurrences.
+pub fn parse_todo(text: &str) -> CommandResult<IcsItem> {
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let roots = parse_components(text);
+    let todo = roots
+        .iter()
+        .filter(|c| c.name == "VCALENDAR")
+        .flat_map(|c| &c.children)
+        .find(|c| c.name == "VTODO" && c.prop("RECURRENCE-ID").is_none())
+        .ok_or_else(|| CommandError::not_found("No VTODO in calendar object"))?;
+    to_item(todo, &local)
+}
+
+/// One occurrence of a VEVENT, shown alongside the day's plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct IcsEvent {
+    pub uid: String,
+    pub title: String,
+    pub location: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub all_day: bool,
+    /// Whether the time is taken. All-day events and those marked free
+    /// (TRANSP:TRANSPARENT) aren't.
+    pub busy: bool,
+}
+
+/// Start times of `component` from its RRULE, in the zone of its DTSTART,
+/// up to `until`.
+fn event_starts(
+    component: &Component,
+    start: &IcsTime,
+    local: &Tz,
+    until: DateTime<Utc>,
+) -> Option<Vec<DateTime<Utc>>> {
+    let dtstart = component.prop("DTSTART")?;
+    let named = match dtstart.param("TZID") {
+        Some(tzid) => parse_tzid(tzid),
+        None if dtstart.value.trim().ends_with(['Z', 'z']) => timezone::parse_zone("UTC").ok(),
+        None => None,
+    };
+    let zone = named.as_ref().unwrap_or(local);
+    let rule = item_recurrence(component, start.date(zone), zone, &mut Vec::new())?;
+    let rule = Rrule::parse(&rule).ok()?;
+    let time = match start {
+        IcsTime::Date(_) => NaiveTime::MIN,
+        IcsTime::Instant(at) => at.with_timezone(zone).time(),
+    };
+    let mut starts = Vec::new();
+    for date in rule.occurrences() {
+        let Some(at) = timezone::resolve_local(date.and_time(time), zone) else {
+            continue;
+        };
+        let at = at.with_timezone(&Utc);
+        if at >= until {
+            break;
+        }
+        starts.push(at);
+    }
+    Some(starts)
+}
+
+/// The occurrences of the VEVENTs in `text` that overlap `window`.
+/// Recurring events are expanded, with moved and cancelled occurrences
+/// applied.
+pub fn parse_events(
+    text: &str,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Vec<IcsEvent>> {
+    let roots = parse_components(text);
+    if !roots.iter().any(|c| c.name == "VCALENDAR") {
+        return Err(CommandError::invalid("Not an iCalendar file"));
+    }
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let components: Vec<&Component> = roots
+        .iter()
+        .filter(|c| c.name == "VCALENDAR")
+        .flat_map(|c| &c.children)
+        .filter(|c| c.name == "VEVENT")
+        .collect();
+    // Occurrences of a series replaced by an override, by UID and the
+    // start they replace.
+    let overridden: HashSet<(String, DateTime<Utc>)> = components
+        .iter()
+        .filter_map(|c| {
+            let at = parse_time(c.prop("RECURRENCE-ID")?, &local)?.instant(&local)?;
+            Some((c.text("UID")?, at))
+        })
+        .collect();
+
+    let mut events = Vec::new();
+    for component in components {
+        let cancelled = component
+            .prop("STATUS")
+            .is_some_and(|p| p.value.trim().eq_ignore_ascii_case("CANCELLED"));
+        let start = component
+            .prop("DTSTART")
+            .and_then(|p| parse_time(p, &local));
+        let (false, Some(start)) = (cancelled, start) else {
+            continue;
+        };
+        let Some(first) = start.instant(&local) else {
+            continue;
+        };
+        let all_day = matches!(start, IcsTime::Date(_));
+        let length = match component.prop("DTEND").and_then(|p| parse_time(p, &local)) {
+            Some(end) => end.instant(&local).map(|end| end - first),
+            None => component
+                .prop("DURATION")
+                .and_then(|p| parse_duration(&p.value)),
+        }
+        .unwrap_or_else(|| match all_day {
+            true => chrono::Duration::days(1),
+            false => chrono::Duration::zero(),
+        })
+        .max(chrono::Duration::zero());
+
+        let title = component
+            .text("SUMMARY")
+            .unwrap_or_else(|| UNTITLED.to_string());
+        let uid = component.text("UID").unwrap_or_else(|| {
+            format!(
+                "{title}|{}",
+                component.prop("DTSTART").map_or("", |p| &p.value)
+            )
+        });
+        let free = component
+            .prop("TRANSP")
+            .is_some_and(|p| p.value.trim().eq_ignore_ascii_case("TRANSPARENT"));
+        let series = component.prop("RECURRENCE-ID").is_none() && component.prop("RRULE").is_some();
+        let starts = match series {
+            true => event_starts(component, &start, &local, window.1),
+            false => None,
+        }
+        .unwrap_or_else(|| vec![first]);
+
+        for at in starts {
+            let end = at + length;
+            let overlaps = at < window.1 && (end > window.0 || at >= window.0);
+            if !overlaps || (series && overridden.contains(&(uid.clone(), at))) {
+                continue;
+            }
+            events.push(IcsEvent {
+                uid: uid.clone(),
+                title: title.clone(),
+                location: component.text("LOCATION"),
+                starts_at: at,
+                ends_at: end,
+                all_day,
+                busy: !all_day && !free,
+            });
+        }
+    }
+    events.sort_by(|a, b| a.starts_at.cmp(&b.starts_at).then(a.uid.cmp(&b.uid)));
+    Ok(events)
+}
+
+/// Create or update the task for `item`, remembering its UID under
+/// `source`. `policy` decides which local edits survive. Returns the task
+/// and whether it was created.
+pub fn write_item(
+    conn: &Connection,
+    item: &IcsItem,
+    zone: &str,
+    source: &str,
+    policy: ConflictPolicy,
+) -> rusqlite::Result<(Task, bool)> {
+    let now = now_utc();
+    let existing = match &item.existing_task_id {
+        Some(id) => task_store::find_task(conn, id)?,
+        None => None,
+    };
+    let created = existing.is_none();
+    let mut task = existing.unwrap_or_else(|| Task {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: String::new(),
+        description: None,
+        status: STATUS_OPEN.to_string(),
+        project: None,
+        priority: None,
+        due: None,
+        scheduled: None,
+        created_at: now.clone(),
+        updated_at: now.clone(),
+        completed_at: None,
+        recurrence: None,
+        series_id: None,
+        tz: Some(zone.to_string()),
+        parent_id: None,
+        sort_key: None,
+        estimate_minutes: None,
+        tags: Vec::new(),
+        is_blocked: false,
+    });
+
+    // A field edited in the app after the calendar last changed the item
+    // keeps the app's value.
+    let modified = item
+        .modified_at
+        .as_deref()
+        .and_then(|at| parse_utc(at).ok())
+        .map(|at| at.timestamp_millis());
+    let take = conflicts::remote_fields(conn, &task.id, modified, policy)?;
+
+    if take("title") {
+        task.title = item.title.clone();
+    }
+    if take("description") {
+        task.description = item.description.clone();
+    }
+    if take("priority") {
+        task.priority = item.priority;
+    }
+    if take("due") {
+        task.due = item.due.clone();
+    }
+    if take("scheduled") {
+        task.scheduled = item.scheduled.clone();
+    }
+    if take("recurrence") {
+        task.series_id = match &item.recurrence {
+            Some(_) => task.series_id.or_else(|| Some(task.id.clone())),
+            None => None,
+        };
+        task.recurrence = item.recurrence.clone();
+    }
+    if take("status") {
+        if item.done {
+            task.status = STATUS_DONE.to_string();
+            task.completed_at = item
+                .completed_at
+                .clone()
+                .or(task.completed_at)
+                .or_else(|| Some(now.clone()));
+        } else {
+            task.status = STATUS_OPEN.to_string();
+            task.completed_at = None;
+        }
+    }
+    task.updated_at = now.clone();
+
+    task_store::write_task(conn, &task)?;
+    if take("tags") {
+        tags::set_task_tags(conn, &task.id, &item.tags)?;
+    }
+    reminders::set_task_reminders(conn, &task.id, &item.reminders)?;
+    conn.execute(
+        "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![source, item.uid, task.id, now],
+    )?;
+    Ok((task, created))
+}
+
+/// Import the items of `text` whose UID is in `uids` (all when `None`), in
+/// one transaction. Items imported before are updated in place.
+pub fn import(
+    conn: &mut Connection,
+    text: &str,
+    uids: Option<&[String]>,
+) -> CommandResult<IcsImportReport> {
+    let preview = preview(conn, text)?;
+    let zone = timezone::system_zone();
+    let mut report = IcsImportReport {
+        created: 0,
+        updated: 0,
+        skipped: preview.skipped.len(),
+    };
+
+    let db_err = |e: rusqlite::Error| format!("Failed to import calendar: {e}");
+    let tx = conn.transaction().map_err(db_err)?;
+    for item in &preview.items {
+        if uids.is_some_and(|uids| !uids.contains(&item.uid)) {
+            report.skipped += 1;
+            continue;
+        }
+        let (_, created) =
+            write_item(&tx, item, &zone, IMPORT_SOURCE, ConflictPolicy::Newest).map_err(db_err)?;
+        if created {
+            report.created += 1;
+        } else {
+            report.updated += 1;
+        }
+    }
+    tx.commit().map_err(db_err)?;
+    Ok(report)
+}
+
+/// Read an .ics file, or fetch it when `source` is an http(s)/webcal URL.
+async fn load_source(source: &str) -> CommandResult<String> {
+    if http::is_url(source) {
+        return http::get_text(source).await;
+    }
+    std::fs::read_to_string(source).with_context(|| format!("Failed to read {source}"))
+}
+
+/// Show what importing `source` would create or update, without writing.
+#[tauri::command]
+pub async fn preview_ics(db: State<'_, Db>, source: String) -> CommandResult<IcsPreview> {
+    let text = load_source(&source).await?;
+    db.with_conn(|conn| Ok(preview(conn, &text)))?
+}
+
+/// Import VTODOs and VEVENTs from a file or URL as tasks. Pass the UIDs
+/// picked in the preview to import only those.
+#[tauri::command]
+pub async fn import_ics(
+    db: State<'_, Db>,
+    source: String,
+    uids: Option<Vec<String>>,
+) -> CommandResult<IcsImportReport> {
+    let text = load_source(&source).await?;
+    // A calendar URL is a feed someone else maintains; a file is a one-off.
+    let change_source = if http::is_url(&source) {
+        ChangeSource::Sync
+    } else {
+        ChangeSource::Import
+    };
+    db.with_conn(|conn| {
+        history::with_source(conn, change_source, |conn| {
+            import(conn, &text, uids.as_deref())
+        })
+    })?
+}
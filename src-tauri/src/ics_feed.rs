@@ -0,0 +1,322 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use chrono::Days;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::credentials::{self, CredentialMigration};
+use crate::data_dir;
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::ics;
+use crate::recurrence;
+use crate::session::write_atomic;
+
+const CONFIG_FILE: &str = "ics_feed.json";
+const DEFAULT_PORT: u16 = 47615;
+const FEED_FILE: &str = "daylight.ics";
+
+/// The running server and its thread, kept so it can be stopped when the
+/// config changes.
+static SERVER: Mutex<Option<(Arc<Server>, JoinHandle<()>)>> = Mutex::new(None);
+/// Why the server couldn't start, for the settings screen.
+static START_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IcsFeedConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Listen on every interface so devices on the local network can
+    /// subscribe, rather than only on this machine.
+    pub lan: bool,
+    /// The secret path segment a subscriber has to know. Generated on first
+    /// use.
+    pub token: String,
+    /// How many days of past time blocks and dates the feed includes.
+    pub days_back: u32,
+}
+
+impl Default for IcsFeedConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+            lan: false,
+            token: String::new(),
+            days_back: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IcsFeedStatus {
+    pub config: IcsFeedConfig,
+    pub running: bool,
+    /// Subscription URLs: this machine's, and the LAN one when enabled.
+    pub urls: Vec<String>,
+    pub error: Option<String>,
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+/// The config as saved, with the token only there on platforms without a
+/// keyring or from before it moved to one.
+fn load_file(app: &AppHandle) -> IcsFeedConfig {
+    let Ok(path) = config_path(app) else {
+        return IcsFeedConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            IcsFeedConfig::default()
+        }),
+        Err(_) => IcsFeedConfig::default(),
+    }
+}
+
+pub fn load_config(app: &AppHandle) -> IcsFeedConfig {
+    let mut config = load_file(app);
+    if config.token.is_empty() {
+        config.token = credentials::load(credentials::ICS_FEED_TOKEN).unwrap_or_default();
+    }
+    config
+}
+
+/// Save the config, keeping the token in the keyring where there is one.
+fn save_config(app: &AppHandle, config: &IcsFeedConfig) -> CommandResult<()> {
+    let path = config_path(app)?;
+    let mut file = config.clone();
+    if !config.token.is_empty()
+        && credentials::save(credentials::ICS_FEED_TOKEN, Some(&config.token)).is_ok()
+    {
+        file.token.clear();
+    }
+    let body = serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)
+}
+
+/// Move a token still in the config file into the keyring, and out of the
+/// file.
+pub fn migrate_token(app: &AppHandle, run: &mut CredentialMigration) {
+    let file = load_file(app);
+    if file.token.is_empty()
+        || !run.move_secret(credentials::ICS_FEED_TOKEN, CONFIG_FILE, &file.token)
+    {
+        return;
+    }
+    if let Err(e) = save_config(app, &file) {
+        tracing::warn!("failed to remove the token from {CONFIG_FILE}: {e}");
+    }
+}
+
+pub fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// The address other devices on the network reach this machine at: the
+/// one the OS would send from. Connecting a UDP socket sends nothing.
+fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_loopback())
+}
+
+fn urls(config: &IcsFeedConfig) -> Vec<String> {
+    let mut hosts = vec![Ipv4Addr::LOCALHOST.to_string()];
+    if config.lan {
+        hosts.extend(lan_ip().map(|ip| ip.to_string()));
+    }
+    hosts
+        .iter()
+        .map(|host| format!("http://{host}:{}/{}/{FEED_FILE}", config.port, config.token))
+        .collect()
+}
+
+fn status(app: &AppHandle) -> IcsFeedStatus {
+    let config = load_config(app);
+    let running = SERVER.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    IcsFeedStatus {
+        urls: if config.enabled && !config.token.is_empty() {
+            urls(&config)
+        } else {
+            Vec::new()
+        },
+        running,
+        error: START_ERROR
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone(),
+        config,
+    }
+}
+
+/// Constant-time comparison, so the token can't be guessed a byte at a
+/// time from response timings.
+pub fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn text(status: u16, body: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(body).with_status_code(status)
+}
+
+fn respond(
+    app: &AppHandle,
+    config: &IcsFeedConfig,
+    method: &Method,
+    url: &str,
+) -> Response<std::io::Cursor<Vec<u8>>> {
+    if !matches!(method, Method::Get | Method::Head) {
+        return text(405, "Method not allowed");
+    }
+    let path = url.split('?').next().unwrap_or_default();
+    let given = path
+        .strip_prefix('/')
+        .and_then(|p| p.strip_suffix(&format!("/{FEED_FILE}")))
+        .unwrap_or_default();
+    if !token_matches(given, &config.token) {
+        return text(404, "Not found");
+    }
+    let since = recurrence::today()
+        .checked_sub_days(Days::new(u64::from(config.days_back)))
+        .unwrap_or_else(recurrence::today)
+        .format("%Y-%m-%d")
+        .to_string();
+    let db = app.state::<Db>();
+    match db.with_conn(|conn| ics::schedule_feed(conn, &since)) {
+        Ok(body) => {
+            let mut response = Response::from_string(body);
+            for (name, value) in [
+                ("Content-Type", "text/calendar; charset=utf-8"),
+                ("Cache-Control", "no-cache"),
+            ] {
+                if let Ok(header) = Header::from_bytes(name, value) {
+                    response.add_header(header);
+                }
+            }
+            response
+        }
+        Err(e) if e.code == ErrorCode::Locked => text(503, &e.message),
+        Err(e) => {
+            tracing::warn!("{e}");
+            text(500, "Failed to build the feed")
+        }
+    }
+}
+
+/// Stop the server and wait for its thread, so the port is free again.
+fn stop() {
+    let running = SERVER.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some((server, thread)) = running {
+        server.unblock();
+        drop(server);
+        let _ = thread.join();
+    }
+}
+
+/// Listen on `port`, retrying briefly: a server just stopped may not have
+/// released it yet.
+fn bind(host: Ipv4Addr, port: u16) -> CommandResult<Server> {
+    let mut attempt = 0;
+    loop {
+        match Server::http((host, port)) {
+            Ok(server) => return Ok(server),
+            Err(_) if attempt < 5 => {
+                attempt += 1;
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(format!("Can't listen on port {port}: {e}").into()),
+        }
+    }
+}
+
+/// Start serving the feed if it's enabled, replacing a server already
+/// running so port and interface changes apply.
+pub fn start(app: &AppHandle) {
+    stop();
+    *START_ERROR.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    let mut config = load_config(app);
+    if !config.enabled {
+        return;
+    }
+    if config.token.is_empty() {
+        config.token = new_token();
+        if let Err(e) = save_config(app, &config) {
+            tracing::warn!("{e}");
+        }
+    }
+    let host = if config.lan {
+        Ipv4Addr::UNSPECIFIED
+    } else {
+        Ipv4Addr::LOCALHOST
+    };
+    let server = match bind(host, config.port) {
+        Ok(server) => Arc::new(server),
+        Err(message) => {
+            tracing::warn!("{message}");
+            *START_ERROR.lock().unwrap_or_else(|e| e.into_inner()) = Some(message.to_string());
+            return;
+        }
+    };
+    let handle = app.clone();
+    let listener = server.clone();
+    let thread = std::thread::spawn(move || {
+        // Ends once `stop` unblocks the server.
+        for request in listener.incoming_requests() {
+            let response = respond(&handle, &config, request.method(), request.url());
+            let _ = request.respond(response);
+        }
+    });
+    *SERVER.lock().unwrap_or_else(|e| e.into_inner()) = Some((server, thread));
+}
+
+#[tauri::command]
+pub fn get_ics_feed_config(app: AppHandle) -> IcsFeedStatus {
+    status(&app)
+}
+
+#[tauri::command]
+pub fn set_ics_feed_config(app: AppHandle, config: IcsFeedConfig) -> CommandResult<IcsFeedStatus> {
+    if config.port < 1024 {
+        return Err(CommandError::invalid("Choose a port from 1024 up"));
+    }
+    let current = load_config(&app);
+    // The token is only changed by regenerating it.
+    let config = IcsFeedConfig {
+        token: current.token,
+        ..config
+    };
+    save_config(&app, &config)?;
+    start(&app);
+    Ok(status(&app))
+}
+
+/// Replace the token, so URLs handed out before stop working.
+#[tauri::command]
+pub fn regenerate_ics_feed_token(app: AppHandle) -> CommandResult<IcsFeedStatus> {
+    let config = IcsFeedConfig {
+        token: new_token(),
+        ..load_config(&app)
+    };
+    save_config(&app, &config)?;
+    start(&app);
+    Ok(status(&app))
+}
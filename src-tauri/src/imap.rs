@@ -0,0 +1,718 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::attachments;
+use crate::credentials;
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::history::{self, ChangeSource};
+use crate::mime::{self, Message};
+use crate::task_store::{self, NewTask, Task};
+
+/// `external_refs.source` for tasks made from email; the external id is the
+/// Message-ID, so a message filed in two watched folders is one task.
+const SOURCE: &str = "email";
+
+const DEFAULT_PORT: u16 = 993;
+const DEFAULT_FOLDER: &str = "INBOX";
+/// How often every enabled account is checked.
+const CHECK_EVERY: Duration = Duration::from_secs(300);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+/// Messages turned into tasks per account per check; the rest wait for the
+/// next one.
+const MAX_PER_CHECK: usize = 50;
+/// Larger messages are left in the folder untouched.
+const MAX_MESSAGE_BYTES: usize = 50 * 1024 * 1024;
+
+/// Set while accounts are checked, so two checks can't import a message
+/// twice.
+static CHECKING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImapAccount {
+    pub id: String,
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// The folder watched for new mail.
+    pub folder: String,
+    /// Only mail sent to this address becomes a task, e.g. a plus address
+    /// like `me+tasks@example.com` delivered to the inbox.
+    pub to_filter: Option<String>,
+    /// Project given to tasks made from this account's mail.
+    pub project: Option<String>,
+    pub enabled: bool,
+    pub uid_validity: Option<u32>,
+    /// The highest UID already turned into a task.
+    pub last_uid: u32,
+    pub checked_at: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewImapAccount {
+    /// Defaults to the username.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub to_filter: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+/// Fields left `None` are unchanged; an empty `to_filter` or `project`
+/// clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ImapAccountPatch {
+    pub name: Option<String>,
+    pub folder: Option<String>,
+    pub to_filter: Option<String>,
+    pub project: Option<String>,
+    pub enabled: Option<bool>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImapCheckReport {
+    pub account_id: String,
+    pub created: usize,
+    /// Messages already a task, e.g. from another folder.
+    pub known: usize,
+    /// Messages too large to fetch, left unread in the folder.
+    pub skipped: usize,
+    pub error: Option<String>,
+}
+
+fn row_to_account(row: &rusqlite::Row) -> rusqlite::Result<ImapAccount> {
+    Ok(ImapAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        host: row.get(2)?,
+        port: row.get(3)?,
+        username: row.get(4)?,
+        folder: row.get(5)?,
+        to_filter: row.get(6)?,
+        project: row.get(7)?,
+        enabled: row.get(8)?,
+        uid_validity: row.get(9)?,
+        last_uid: row.get(10)?,
+        checked_at: row.get(11)?,
+        last_error: row.get(12)?,
+        created_at: row.get(13)?,
+        updated_at: row.get(14)?,
+    })
+}
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<ImapAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, host, port, username, folder, to_filter, project, enabled,
+                uid_validity, last_uid, checked_at, last_error, created_at, updated_at
+         FROM imap_accounts ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], row_to_account)?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> CommandResult<ImapAccount> {
+    list_accounts(conn)?
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| CommandError::not_found(format!("Mail account not found: {id}")))
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("imap:{account_id}")
+}
+
+/// An IMAP string argument.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The size of the literal announced at the end of `line`, as in
+/// `* 1 FETCH (BODY[] {2048}`.
+fn literal_size(line: &str) -> Option<usize> {
+    let rest = line.strip_suffix('}')?;
+    let open = rest.rfind('{')?;
+    rest[open + 1..].trim_end_matches('+').parse().ok()
+}
+
+/// One response from the server. Literals it carried are pulled out into
+/// `literals`, leaving their `{n}` markers in `line`; a literal over the size
+/// limit is read and dropped, leaving `None`.
+#[derive(Debug, Default)]
+struct Response {
+    line: String,
+    literals: Vec<Option<Vec<u8>>>,
+}
+
+/// An IMAP connection over any stream, TLS in practice.
+struct Session<S: Read + Write> {
+    stream: BufReader<S>,
+    tag: u32,
+}
+
+impl<S: Read + Write> Session<S> {
+    /// Read the server's greeting.
+    fn start(stream: S) -> CommandResult<Self> {
+        let mut session = Self {
+            stream: BufReader::new(stream),
+            tag: 0,
+        };
+        let greeting = session.read_response()?;
+        let status = greeting.line.strip_prefix("* ").unwrap_or_default();
+        let word = status.split(' ').next().unwrap_or_default();
+        if !word.eq_ignore_ascii_case("OK") && !word.eq_ignore_ascii_case("PREAUTH") {
+            return Err(format!("The mail server refused the connection: {status}").into());
+        }
+        Ok(session)
+    }
+
+    fn read_response(&mut self) -> CommandResult<Response> {
+        let mut response = Response::default();
+        loop {
+            let mut line = Vec::new();
+            let read = self
+                .stream
+                .read_until(b'\n', &mut line)
+                .map_err(|e| CommandError::network(format!("Mail server: {e}")))?;
+            if read == 0 {
+                return Err(CommandError::network(
+                    "The mail server closed the connection",
+                ));
+            }
+            while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                line.pop();
+            }
+            let line = String::from_utf8_lossy(&line);
+            response.line.push_str(&line);
+            let Some(size) = literal_size(&line) else {
+                return Ok(response);
+            };
+            let literal = if size > MAX_MESSAGE_BYTES {
+                io::copy(&mut (&mut self.stream).take(size as u64), &mut io::sink())
+                    .map_err(|e| CommandError::network(format!("Mail server: {e}")))?;
+                None
+            } else {
+                let mut literal = vec![0; size];
+                self.stream
+                    .read_exact(&mut literal)
+                    .map_err(|e| CommandError::network(format!("Mail server: {e}")))?;
+                Some(literal)
+            };
+            response.literals.push(literal);
+        }
+    }
+
+    /// Send `command` and read up to its tagged reply. Returns the untagged
+    /// responses that came before it.
+    fn command(&mut self, command: &str) -> CommandResult<Vec<Response>> {
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{tag} {command}\r\n").as_bytes())
+            .and_then(|()| stream.flush())
+            .map_err(|e| CommandError::network(format!("Mail server: {e}")))?;
+        // Named by its first word only: a LOGIN carries the password.
+        let verb = command.split(' ').next().unwrap_or_default();
+        let mut untagged = Vec::new();
+        loop {
+            let response = self.read_response()?;
+            if let Some(status) = response
+                .line
+                .strip_prefix(tag.as_str())
+                .and_then(|rest| rest.strip_prefix(' '))
+            {
+                let (word, text) = status.split_once(' ').unwrap_or((status, ""));
+                if word.eq_ignore_ascii_case("OK") {
+                    return Ok(untagged);
+                }
+                return Err(format!("{verb} failed: {}", text.trim()).into());
+            }
+            if response.line.starts_with('+') {
+                return Err(format!("{verb} failed: the server asked for more").into());
+            }
+            untagged.push(response);
+        }
+    }
+
+    fn login(&mut self, username: &str, password: &str) -> CommandResult<()> {
+        self.command(&format!("LOGIN {} {}", quote(username), quote(password)))
+            .map(|_| ())
+    }
+
+    /// Open `folder` and return its UIDVALIDITY.
+    fn select(&mut self, folder: &str) -> CommandResult<u32> {
+        let responses = self.command(&format!("SELECT {}", quote(folder)))?;
+        responses
+            .iter()
+            .find_map(|r| {
+                let upper = r.line.to_ascii_uppercase();
+                let start = upper.find("[UIDVALIDITY ")? + "[UIDVALIDITY ".len();
+                let end = start + upper[start..].find(']')?;
+                upper[start..end].trim().parse().ok()
+            })
+            .ok_or_else(|| format!("The mail server gave no UIDVALIDITY for {folder}").into())
+    }
+
+    /// UIDs above `last_uid`, oldest first. Before anything has been
+    /// imported, only unread mail counts, so connecting an inbox doesn't
+    /// turn years of it into tasks.
+    fn search(&mut self, last_uid: u32, to_filter: Option<&str>) -> CommandResult<Vec<u32>> {
+        let mut command = format!("UID SEARCH UID {}:*", last_uid + 1);
+        if last_uid == 0 {
+            command.push_str(" UNSEEN");
+        }
+        if let Some(to) = to_filter {
+            command.push_str(&format!(" TO {}", quote(to)));
+        }
+        let responses = self.command(&command)?;
+        let mut uids: Vec<u32> = responses
+            .iter()
+            .filter_map(|r| {
+                let rest = r.line.strip_prefix("* ")?;
+                let (word, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+                word.eq_ignore_ascii_case("SEARCH").then_some(rest)
+            })
+            .flat_map(|rest| rest.split_whitespace().filter_map(|uid| uid.parse().ok()))
+            // `n:*` always matches the newest message, even below n.
+            .filter(|uid| *uid > last_uid)
+            .collect();
+        uids.sort_unstable();
+        uids.dedup();
+        Ok(uids)
+    }
+
+    /// The whole message, without marking it read. `None` when it's over
+    /// the size limit or gone.
+    fn fetch(&mut self, uid: u32) -> CommandResult<Option<Vec<u8>>> {
+        let responses = self.command(&format!("UID FETCH {uid} (BODY.PEEK[])"))?;
+        Ok(responses
+            .into_iter()
+            .find(|r| r.line.to_ascii_uppercase().contains(" FETCH "))
+            .and_then(|r| r.literals.into_iter().next())
+            .flatten())
+    }
+
+    fn mark_seen(&mut self, uid: u32) -> CommandResult<()> {
+        self.command(&format!("UID STORE {uid} +FLAGS.SILENT (\\Seen)"))
+            .map(|_| ())
+    }
+
+    fn logout(mut self) {
+        let _ = self.command("LOGOUT");
+    }
+}
+
+fn tls_config() -> CommandResult<Arc<ClientConfig>> {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// Connect over TLS from the first byte (port 993); STARTTLS isn't offered.
+fn connect(
+    host: &str,
+    port: u16,
+) -> CommandResult<Session<StreamOwned<ClientConnection, TcpStream>>> {
+    let addr = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| CommandError::network(format!("Can't find mail server {host}: {e}")))?
+        .next()
+        .ok_or_else(|| CommandError::network(format!("Can't find mail server {host}")))?;
+    let tcp = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(|e| {
+        CommandError::network(format!("Can't reach mail server {host}:{port}: {e}"))
+    })?;
+    tcp.set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|()| tcp.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(CommandError::network)?;
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|e| CommandError::invalid(format!("Not a mail server name: {host}: {e}")))?;
+    let tls = ClientConnection::new(tls_config()?, name).map_err(|e| e.to_string())?;
+    Session::start(StreamOwned::new(tls, tcp))
+}
+
+/// The task title for a message: its subject without forwarding prefixes.
+fn task_title(message: &Message) -> String {
+    let mut subject = message.subject.trim();
+    loop {
+        let lower = subject.to_ascii_lowercase();
+        let Some(prefix) = ["fwd:", "fw:"].into_iter().find(|p| lower.starts_with(p)) else {
+            break;
+        };
+        subject = subject[prefix.len()..].trim_start();
+    }
+    if !subject.is_empty() {
+        subject.to_string()
+    } else if !message.from.is_empty() {
+        format!("Email from {}", message.from)
+    } else {
+        "(no subject)".to_string()
+    }
+}
+
+fn task_note(message: &Message) -> Option<String> {
+    let text = message.text.trim();
+    match (message.from.is_empty(), text.is_empty()) {
+        (true, true) => None,
+        (true, false) => Some(text.to_string()),
+        (false, true) => Some(format!("From: {}", message.from)),
+        (false, false) => Some(format!("From: {}\n\n{text}", message.from)),
+    }
+}
+
+/// Turn `message` into a task with the already-stored `attachments` (name,
+/// hash, size) and move the account past `uid`. `None` when the message was
+/// already a task.
+fn import_message(
+    conn: &mut Connection,
+    account: &ImapAccount,
+    uid_validity: u32,
+    uid: u32,
+    message: &Message,
+    attachments: &[(String, String, u64)],
+) -> rusqlite::Result<Option<Task>> {
+    let tx = conn.transaction()?;
+    let external_id = message
+        .message_id
+        .clone()
+        .unwrap_or_else(|| format!("{}:{uid_validity}:{uid}", account.id));
+    let known = tx
+        .query_row(
+            "SELECT 1 FROM external_refs WHERE source = ?1 AND external_id = ?2",
+            params![SOURCE, external_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    let task = if known {
+        None
+    } else {
+        let input = NewTask {
+            title: task_title(message),
+            description: task_note(message),
+            project: account.project.clone(),
+            priority: None,
+            due: None,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let task = task_store::insert_task(&tx, &input, input.title.clone())?;
+        tx.execute(
+            "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![SOURCE, external_id, task.id, now_utc()],
+        )?;
+        for (file_name, hash, size) in attachments {
+            attachments::insert_attachment(&tx, &task.id, file_name, hash, *size)?;
+        }
+        Some(task)
+    };
+    advance(&tx, &account.id, uid_validity, uid)?;
+    tx.commit()?;
+    Ok(task)
+}
+
+fn advance(
+    conn: &Connection,
+    account_id: &str,
+    uid_validity: u32,
+    uid: u32,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE imap_accounts SET uid_validity = ?2, last_uid = ?3 WHERE id = ?1",
+        params![account_id, uid_validity, uid],
+    )?;
+    Ok(())
+}
+
+/// Import new mail from the selected session. Each message is committed,
+/// then marked read, on its own, so a dropped connection loses nothing.
+fn import_new<S: Read + Write>(
+    session: &mut Session<S>,
+    db: &Db,
+    store: &Path,
+    account: &ImapAccount,
+    report: &mut ImapCheckReport,
+) -> CommandResult<()> {
+    let uid_validity = session.select(&account.folder)?;
+    // A new UIDVALIDITY renumbers the folder; Message-IDs stop repeats.
+    let last_uid = if account.uid_validity == Some(uid_validity) {
+        account.last_uid
+    } else {
+        0
+    };
+    let mut uids = session.search(last_uid, account.to_filter.as_deref())?;
+    uids.truncate(MAX_PER_CHECK);
+    for uid in uids {
+        let Some(raw) = session.fetch(uid)? else {
+            report.skipped += 1;
+            db.with_conn(|conn| advance(conn, &account.id, uid_validity, uid))?;
+            continue;
+        };
+        let message = mime::parse(&raw);
+        let mut stored = Vec::new();
+        for attachment in &message.attachments {
+            let (hash, size) = attachments::store_bytes(store, &attachment.content)?;
+            stored.push((attachment.file_name.clone(), hash, size));
+        }
+        let task = db.with_conn(|conn| {
+            history::with_source(conn, ChangeSource::Import, |conn| {
+                import_message(conn, account, uid_validity, uid, &message, &stored)
+            })?
+        })?;
+        match task {
+            Some(_) => report.created += 1,
+            None => report.known += 1,
+        }
+        session.mark_seen(uid)?;
+    }
+    Ok(())
+}
+
+fn check_account(app: &AppHandle, account: &ImapAccount) -> ImapCheckReport {
+    let mut report = ImapCheckReport {
+        account_id: account.id.clone(),
+        ..Default::default()
+    };
+    let db = app.state::<Db>().unjournaled();
+    let result = (|| {
+        let password = credentials::require(&keyring_name(&account.id), "password")?;
+        let store = attachments::store_dir(app)?;
+        let mut session = connect(&account.host, account.port)?;
+        session.login(&account.username, &password)?;
+        let imported = import_new(&mut session, &db, &store, account, &mut report);
+        session.logout();
+        imported
+    })();
+    report.error = result.err().map(|e| e.to_string());
+    if let Some(e) = &report.error {
+        tracing::warn!("{}: {e}", account.name);
+    }
+    let _ = db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE imap_accounts SET checked_at = ?2, last_error = ?3 WHERE id = ?1",
+            params![account.id, now_utc(), report.error],
+        )
+    });
+    report
+}
+
+/// Check `account_id`, or every enabled account when `None`.
+fn check_accounts(
+    app: &AppHandle,
+    account_id: Option<&str>,
+) -> CommandResult<Vec<ImapCheckReport>> {
+    if CHECKING.swap(true, Ordering::SeqCst) {
+        return Err("Mail is already being checked".into());
+    }
+    let accounts = app.state::<Db>().with_conn(|conn| list_accounts(conn));
+    let reports = accounts.map(|accounts| {
+        accounts
+            .iter()
+            .filter(|a| match account_id {
+                Some(id) => a.id == id,
+                None => a.enabled,
+            })
+            .map(|a| check_account(app, a))
+            .collect()
+    });
+    CHECKING.store(false, Ordering::SeqCst);
+    reports
+}
+
+/// Check every enabled account for new mail every few minutes.
+pub fn spawn_imap_poller(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || loop {
+        if !handle.state::<Db>().is_locked() {
+            let _ = check_accounts(&handle, None);
+        }
+        std::thread::sleep(CHECK_EVERY);
+    });
+}
+
+/// Log in and open the folder before saving, so a typo shows up now rather
+/// than as a failed check later.
+#[tauri::command]
+pub async fn connect_imap_account(
+    app: AppHandle,
+    input: NewImapAccount,
+) -> CommandResult<ImapAccount> {
+    let host = input.host.trim().to_string();
+    let username = input.username.trim().to_string();
+    if host.is_empty() || username.is_empty() || input.password.is_empty() {
+        return Err(CommandError::invalid(
+            "Enter the mail server, username and password",
+        ));
+    }
+    let port = input.port.unwrap_or(DEFAULT_PORT);
+    let folder = non_empty(input.folder).unwrap_or_else(|| DEFAULT_FOLDER.to_string());
+    let password = input.password;
+    let uid_validity = {
+        let (host, username, folder, password) = (
+            host.clone(),
+            username.clone(),
+            folder.clone(),
+            password.clone(),
+        );
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut session = connect(&host, port)?;
+            session.login(&username, &password)?;
+            let selected = session.select(&folder);
+            session.logout();
+            selected
+        })
+        .await
+        .map_err(|e| e.to_string())??
+    };
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = non_empty(input.name).unwrap_or_else(|| username.clone());
+    credentials::save(&keyring_name(&id), Some(&password))?;
+    let db = app.state::<Db>();
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO imap_accounts (id, name, host, port, username, folder, to_filter,
+                 project, uid_validity, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+            params![
+                id,
+                name,
+                host,
+                port,
+                username,
+                folder,
+                non_empty(input.to_filter),
+                non_empty(input.project),
+                uid_validity,
+                now,
+            ],
+        )?;
+        Ok(find_account(conn, &id))
+    });
+    match stored {
+        Ok(Ok(account)) => Ok(account),
+        Ok(Err(e)) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_imap_accounts(db: State<'_, Db>) -> CommandResult<Vec<ImapAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Change what's watched and where tasks go. Watching another folder starts
+/// it afresh from its unread mail.
+#[tauri::command]
+pub fn update_imap_account(
+    db: State<'_, Db>,
+    id: String,
+    patch: ImapAccountPatch,
+) -> CommandResult<ImapAccount> {
+    let account = db.with_conn(|conn| Ok(find_account(conn, &id)))??;
+    if let Some(password) = patch.password.as_deref().filter(|p| !p.is_empty()) {
+        credentials::save(&keyring_name(&id), Some(password))?;
+    }
+    let folder = non_empty(patch.folder).unwrap_or(account.folder.clone());
+    let moved = folder != account.folder;
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE imap_accounts
+             SET name = ?2, folder = ?3, to_filter = ?4, project = ?5, enabled = ?6,
+                 uid_validity = CASE WHEN ?7 THEN NULL ELSE uid_validity END,
+                 last_uid = CASE WHEN ?7 THEN 0 ELSE last_uid END,
+                 updated_at = ?8
+             WHERE id = ?1",
+            params![
+                id,
+                non_empty(patch.name).unwrap_or(account.name),
+                folder,
+                match patch.to_filter {
+                    Some(filter) => non_empty(Some(filter)),
+                    None => account.to_filter,
+                },
+                match patch.project {
+                    Some(project) => non_empty(Some(project)),
+                    None => account.project,
+                },
+                patch.enabled.unwrap_or(account.enabled),
+                moved,
+                now_utc(),
+            ],
+        )?;
+        Ok(find_account(conn, &id))
+    })?
+}
+
+/// Stop watching an account. Tasks made from its mail stay.
+#[tauri::command]
+pub fn remove_imap_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM imap_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Mail account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+/// Check `account_id` now, or every enabled account when `None`.
+#[tauri::command]
+pub async fn check_imap_now(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<ImapCheckReport>> {
+    tauri::async_runtime::spawn_blocking(move || check_accounts(&app, account_id.as_deref()))
+        .await
+        .map_err(|e| format!("Mail check failed: {e}"))?
+}
@@ -0,0 +1,659 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::csv;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::tags;
+use crate::task_store::{self, Task, STATUS_DONE, STATUS_OPEN};
+use crate::time_entries::{self, TIME_ENTRIES_EVENT};
+use crate::timezone;
+
+/// Emitted with the `ImportReport` after a (non dry-run) import commits.
+pub const IMPORT_EVENT: &str = "data-imported";
+
+const SAMPLE_ROWS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportTarget {
+    Tasks,
+    TimeEntries,
+}
+
+/// Fields a column can be mapped onto, with the header names (lowercase,
+/// `_`/`-` read as spaces) that suggest them.
+const TASK_FIELDS: &[(&str, &[&str])] = &[
+    ("id", &["id"]),
+    (
+        "title",
+        &["title", "name", "task", "summary", "subject", "content"],
+    ),
+    (
+        "description",
+        &["description", "notes", "note", "details", "body"],
+    ),
+    (
+        "status",
+        &["status", "state", "done", "completed", "complete"],
+    ),
+    ("project", &["project", "list", "category", "folder"]),
+    ("priority", &["priority", "prio"]),
+    ("due", &["due", "due date", "deadline"]),
+    (
+        "scheduled",
+        &["scheduled", "scheduled date", "start date", "do date"],
+    ),
+    (
+        "completed_at",
+        &[
+            "completed at",
+            "completion date",
+            "completed date",
+            "done date",
+        ],
+    ),
+    (
+        "created_at",
+        &["created at", "created", "created date", "date created"],
+    ),
+    ("tags", &["tags", "tag", "labels", "label"]),
+    ("recurrence", &["recurrence", "rrule", "repeat"]),
+];
+
+const ENTRY_FIELDS: &[(&str, &[&str])] = &[
+    ("id", &["id"]),
+    ("task_id", &["task id"]),
+    (
+        "task_title",
+        &["task", "task title", "title", "description"],
+    ),
+    ("project", &["project", "client"]),
+    (
+        "started_at",
+        &["started at", "start", "start time", "from", "begin"],
+    ),
+    ("ended_at", &["ended at", "end", "end time", "to", "stop"]),
+    ("duration", &["duration", "hours", "time"]),
+    ("note", &["note", "notes", "comment"]),
+    ("billable", &["billable"]),
+];
+
+impl ImportTarget {
+    fn fields(self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            ImportTarget::Tasks => TASK_FIELDS,
+            ImportTarget::TimeEntries => ENTRY_FIELDS,
+        }
+    }
+
+    fn json_key(self) -> &'static str {
+        match self {
+            ImportTarget::Tasks => "tasks",
+            ImportTarget::TimeEntries => "time_entries",
+        }
+    }
+}
+
+/// A source file flattened to a header row and string cells.
+struct Table {
+    format: &'static str,
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportInspection {
+    pub format: String,
+    pub columns: Vec<String>,
+    pub sample: Vec<Vec<String>>,
+    pub row_count: usize,
+    /// Field -> column guesses from the header names, as a starting point
+    /// for the mapping UI.
+    pub suggested_mapping: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportMapping {
+    pub target: ImportTarget,
+    /// DayLight field -> source column. Unmapped fields are left empty.
+    pub columns: HashMap<String, String>,
+    /// Read ambiguous numeric dates like 03/04/2026 as day/month.
+    #[serde(default)]
+    pub day_first: bool,
+    /// Zone for timestamps without an offset. Defaults to the system zone.
+    #[serde(default)]
+    pub zone: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowError {
+    /// 1-based data row, not counting the header.
+    pub row: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub target: ImportTarget,
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub imported: usize,
+    /// Tasks created to hold imported time entries.
+    pub created_tasks: usize,
+    pub errors: Vec<RowError>,
+}
+
+fn json_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(json_cell).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// Accepts an array of objects, or an object holding one (such as the
+/// `tasks`/`time_entries` arrays written by `export_data`).
+fn json_table(text: &str, target: ImportTarget) -> CommandResult<Table> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| CommandError::invalid(format!("Invalid JSON: {e}")))?;
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(mut map) => match map.remove(target.json_key()) {
+            Some(Value::Array(items)) => items,
+            _ => map
+                .into_iter()
+                .find_map(|(_, v)| match v {
+                    Value::Array(items) => Some(items),
+                    _ => None,
+                })
+                .ok_or_else(|| CommandError::invalid("JSON file contains no array of records"))?,
+        },
+        _ => {
+            return Err(CommandError::invalid(
+                "JSON file must contain an array of records",
+            ))
+        }
+    };
+
+    let mut columns: Vec<String> = Vec::new();
+    for item in &items {
+        if let Value::Object(map) = item {
+            for key in map.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+    }
+    let rows = items
+        .iter()
+        .map(|item| {
+            columns
+                .iter()
+                .map(|c| item.get(c).map(json_cell).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    Ok(Table {
+        format: "json",
+        columns,
+        rows,
+    })
+}
+
+fn csv_table(text: &str) -> CommandResult<Table> {
+    let mut records = csv::parse(text, csv::detect_delimiter(text)).into_iter();
+    let columns: Vec<String> = records
+        .next()
+        .ok_or_else(|| CommandError::invalid("CSV file is empty"))?
+        .into_iter()
+        .map(|c| c.trim().to_string())
+        .collect();
+    Ok(Table {
+        format: "csv",
+        columns,
+        rows: records.collect(),
+    })
+}
+
+fn load_table(path: &Path, target: ImportTarget) -> CommandResult<Table> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        || text.trim_start().starts_with(['[', '{']);
+    if is_json {
+        json_table(&text, target)
+    } else {
+        csv_table(&text)
+    }
+}
+
+fn header_key(column: &str) -> String {
+    column.trim().to_lowercase().replace(['_', '-'], " ")
+}
+
+fn suggest_mapping(columns: &[String], target: ImportTarget) -> HashMap<String, String> {
+    let mut mapping = HashMap::new();
+    for (field, aliases) in target.fields() {
+        let found = aliases.iter().find_map(|alias| {
+            columns
+                .iter()
+                .find(|c| header_key(c) == *alias && !mapping.values().any(|v| v == *c))
+        });
+        if let Some(column) = found {
+            mapping.insert(field.to_string(), column.clone());
+        }
+    }
+    mapping
+}
+
+/// Resolved mapping: field -> column index.
+struct Columns(HashMap<&'static str, usize>);
+
+impl Columns {
+    fn resolve(mapping: &ImportMapping, table: &Table) -> CommandResult<Self> {
+        let fields = mapping.target.fields();
+        let mut resolved = HashMap::new();
+        for (field, column) in &mapping.columns {
+            let Some((name, _)) = fields.iter().find(|(f, _)| f == field) else {
+                return Err(CommandError::invalid(format!("Unknown field: {field}")));
+            };
+            let index = table
+                .columns
+                .iter()
+                .position(|c| c == column)
+                .ok_or_else(|| CommandError::not_found(format!("Column not found: {column}")))?;
+            resolved.insert(*name, index);
+        }
+
+        let has = |f: &str| resolved.contains_key(f);
+        match mapping.target {
+            ImportTarget::Tasks if !has("title") => {
+                return Err(CommandError::invalid("Map a column to the task title"));
+            }
+            ImportTarget::TimeEntries if !has("started_at") => {
+                return Err(CommandError::invalid(
+                    "Map a column to the entry start time",
+                ));
+            }
+            ImportTarget::TimeEntries if !has("task_id") && !has("task_title") => {
+                return Err(CommandError::invalid(
+                    "Map a column to the task id or task title",
+                ));
+            }
+            _ => {}
+        }
+        Ok(Self(resolved))
+    }
+
+    /// The trimmed cell for `field`, or `None` if unmapped or blank.
+    fn get<'a>(&self, row: &'a [String], field: &str) -> Option<&'a str> {
+        let index = *self.0.get(field)?;
+        row.get(index).map(|v| v.trim()).filter(|v| !v.is_empty())
+    }
+}
+
+/// Split a cell into a date and an optional time, trying ISO first and then
+/// the numeric order the user picked.
+fn parse_naive(value: &str, day_first: bool) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None => (value, None),
+    };
+    let date_formats: &[&str] = if day_first {
+        &["%Y-%m-%d", "%Y/%m/%d", "%d/%m/%Y", "%d.%m.%Y", "%d-%m-%Y"]
+    } else {
+        &["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y", "%m-%d-%Y", "%d.%m.%Y"]
+    };
+    let date = date_formats
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(date, fmt).ok())?;
+    let time = match time {
+        None | Some("") => None,
+        Some(time) => Some(
+            [
+                "%H:%M:%S",
+                "%H:%M",
+                "%I:%M %p",
+                "%I:%M:%S %p",
+                "%I%p",
+                "%I %p",
+            ]
+            .iter()
+            .find_map(|fmt| NaiveTime::parse_from_str(&time.to_uppercase(), fmt).ok())?,
+        ),
+    };
+    Some((date, time))
+}
+
+/// An instant: RFC 3339, or a local date/time in `tz`.
+fn parse_instant(value: &str, tz: &Tz, day_first: bool) -> CommandResult<String> {
+    if let Ok(dt) = parse_utc(value) {
+        return Ok(format_utc(dt));
+    }
+    let (date, time) = parse_naive(value, day_first)
+        .ok_or_else(|| CommandError::invalid(format!("Unrecognized date/time: {value}")))?;
+    let local = NaiveDateTime::new(date, time.unwrap_or(NaiveTime::MIN));
+    timezone::resolve_local(local, tz)
+        .map(|dt| format_utc(dt.with_timezone(&Utc)))
+        .ok_or_else(|| CommandError::invalid(format!("{value} does not exist in this time zone")))
+}
+
+/// A `due`/`scheduled` value: `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM` wall-clock.
+fn parse_day(value: &str, tz: &Tz, day_first: bool) -> CommandResult<String> {
+    if let Ok(dt) = parse_utc(value) {
+        return Ok(dt.with_timezone(tz).format("%Y-%m-%dT%H:%M").to_string());
+    }
+    match parse_naive(value, day_first) {
+        Some((date, None)) => Ok(date.format("%Y-%m-%d").to_string()),
+        Some((date, Some(time))) => Ok(date.and_time(time).format("%Y-%m-%dT%H:%M").to_string()),
+        None => Err(CommandError::invalid(format!("Unrecognized date: {value}"))),
+    }
+}
+
+fn parse_bool(value: &str) -> bool {
+    matches!(
+        value.to_lowercase().as_str(),
+        "true" | "yes" | "y" | "1" | "x" | "done" | "completed" | "complete"
+    )
+}
+
+/// Numbers as-is; the frontend's none/low/normal/high as 0-3.
+fn parse_priority(value: &str) -> CommandResult<i64> {
+    if let Ok(n) = value.parse() {
+        return Ok(n);
+    }
+    match value.to_lowercase().as_str() {
+        "none" => Ok(0),
+        "low" => Ok(1),
+        "normal" | "medium" => Ok(2),
+        "high" | "urgent" => Ok(3),
+        _ => Err(CommandError::invalid(format!(
+            "Unrecognized priority: {value}"
+        ))),
+    }
+}
+
+/// `H:MM[:SS]`, or a plain number of minutes.
+fn parse_duration(value: &str) -> CommandResult<i64> {
+    let invalid = || CommandError::invalid(format!("Unrecognized duration: {value}"));
+    if !value.contains(':') {
+        let minutes: f64 = value.parse().map_err(|_| invalid())?;
+        return Ok((minutes * 60.0).round() as i64);
+    }
+    let parts: Vec<i64> = value
+        .split(':')
+        .map(|p| p.trim().parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    match parts[..] {
+        [h, m] => Ok(h * 3600 + m * 60),
+        [h, m, s] => Ok(h * 3600 + m * 60 + s),
+        _ => Err(invalid()),
+    }
+}
+
+fn split_tags(value: &str) -> CommandResult<Vec<String>> {
+    let names: Vec<String> = value
+        .split([',', ';'])
+        .filter(|t| !t.trim().is_empty())
+        .map(str::to_string)
+        .collect();
+    tags::normalize_names(&names)
+}
+
+struct RowContext<'a> {
+    columns: &'a Columns,
+    zone: &'a str,
+    tz: &'a Tz,
+    day_first: bool,
+}
+
+impl RowContext<'_> {
+    fn get<'r>(&self, row: &'r [String], field: &str) -> Option<&'r str> {
+        self.columns.get(row, field)
+    }
+
+    fn instant(&self, row: &[String], field: &str) -> CommandResult<Option<String>> {
+        self.get(row, field)
+            .map(|v| parse_instant(v, self.tz, self.day_first))
+            .transpose()
+    }
+}
+
+fn import_task_row(conn: &Connection, ctx: &RowContext, row: &[String]) -> CommandResult<()> {
+    let title = task_store::validate_title(ctx.get(row, "title").unwrap_or(""))?;
+    let done = ctx.get(row, "status").is_some_and(parse_bool);
+    let completed_at = ctx.instant(row, "completed_at")?;
+    let created_at = ctx.instant(row, "created_at")?.unwrap_or_else(now_utc);
+    let recurrence = ctx
+        .get(row, "recurrence")
+        .map(task_store::validate_recurrence)
+        .transpose()?;
+    let id = ctx
+        .get(row, "id")
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let status = if done || completed_at.is_some() {
+        STATUS_DONE
+    } else {
+        STATUS_OPEN
+    };
+
+    let task = Task {
+        series_id: recurrence.as_ref().map(|_| id.clone()),
+        id,
+        title,
+        description: ctx.get(row, "description").map(str::to_string),
+        status: status.to_string(),
+        project: ctx.get(row, "project").map(str::to_string),
+        priority: ctx.get(row, "priority").map(parse_priority).transpose()?,
+        due: ctx
+            .get(row, "due")
+            .map(|v| parse_day(v, ctx.tz, ctx.day_first))
+            .transpose()?,
+        scheduled: ctx
+            .get(row, "scheduled")
+            .map(|v| parse_day(v, ctx.tz, ctx.day_first))
+            .transpose()?,
+        completed_at: match status {
+            STATUS_DONE => Some(completed_at.unwrap_or_else(now_utc)),
+            _ => None,
+        },
+        updated_at: now_utc(),
+        created_at,
+        recurrence,
+        tz: Some(ctx.zone.to_string()),
+        parent_id: None,
+        sort_key: None,
+        estimate_minutes: None,
+        tags: Vec::new(),
+        is_blocked: false,
+    };
+    let tag_names = ctx.get(row, "tags").map(split_tags).transpose()?;
+
+    task_store::write_task(conn, &task)?;
+    Ok(tags::set_task_tags(
+        conn,
+        &task.id,
+        &tag_names.unwrap_or_default(),
+    )?)
+}
+
+/// The task an imported entry belongs to: by id, else the most recent task
+/// with the same title, else a new task. Returns whether one was created.
+fn entry_task(
+    conn: &Connection,
+    ctx: &RowContext,
+    row: &[String],
+) -> CommandResult<(String, bool)> {
+    if let Some(id) = ctx.get(row, "task_id") {
+        return match task_store::find_task(conn, id)? {
+            Some(task) => Ok((task.id, false)),
+            None => Err(CommandError::not_found(format!("Task not found: {id}"))),
+        };
+    }
+    let title = task_store::validate_title(ctx.get(row, "task_title").unwrap_or(""))?;
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT id FROM tasks WHERE title = ?1 COLLATE NOCASE AND deleted_at IS NULL
+             ORDER BY created_at DESC LIMIT 1",
+            params![title],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(id) = existing {
+        return Ok((id, false));
+    }
+
+    let input = task_store::NewTask {
+        title: title.clone(),
+        description: None,
+        project: ctx.get(row, "project").map(str::to_string),
+        priority: None,
+        due: None,
+        scheduled: None,
+        recurrence: None,
+        tags: Vec::new(),
+        parent_id: None,
+        estimate_minutes: None,
+    };
+    let task = task_store::insert_task(conn, &input, title)?;
+    Ok((task.id, true))
+}
+
+fn import_entry_row(conn: &Connection, ctx: &RowContext, row: &[String]) -> CommandResult<bool> {
+    let started_at = ctx
+        .instant(row, "started_at")?
+        .ok_or_else(|| CommandError::invalid("Missing start time"))?;
+    let ended_at = match (ctx.instant(row, "ended_at")?, ctx.get(row, "duration")) {
+        (Some(end), _) => Some(end),
+        (None, Some(duration)) => {
+            let end =
+                parse_utc(&started_at)? + chrono::Duration::seconds(parse_duration(duration)?);
+            Some(format_utc(end))
+        }
+        (None, None) => None,
+    };
+    if let Some(end) = &ended_at {
+        if parse_utc(end)? <= parse_utc(&started_at)? {
+            return Err(CommandError::invalid("Entry must end after it starts"));
+        }
+    }
+
+    let (task_id, created) = entry_task(conn, ctx, row)?;
+    let mut entry = time_entries::new_entry(
+        &task_id,
+        started_at,
+        ctx.get(row, "note").map(str::to_string),
+    );
+    if let Some(id) = ctx.get(row, "id") {
+        entry.id = id.to_string();
+    }
+    entry.ended_at = ended_at;
+    entry.billable = ctx.get(row, "billable").is_some_and(parse_bool);
+    entry.tz = Some(ctx.zone.to_string());
+    time_entries::write_entry(conn, &entry)?;
+    Ok(created)
+}
+
+/// Import every row of `path` in one transaction. Bad rows are reported and
+/// skipped; a dry run does all the same work and then rolls back.
+pub fn import(
+    conn: &mut Connection,
+    path: &Path,
+    mapping: &ImportMapping,
+    dry_run: bool,
+) -> CommandResult<ImportReport> {
+    let table = load_table(path, mapping.target)?;
+    let columns = Columns::resolve(mapping, &table)?;
+    let zone = mapping.zone.clone().unwrap_or_else(timezone::system_zone);
+    let tz = timezone::parse_zone(&zone)?;
+    let ctx = RowContext {
+        columns: &columns,
+        zone: &zone,
+        tz: &tz,
+        day_first: mapping.day_first,
+    };
+
+    let mut report = ImportReport {
+        target: mapping.target,
+        dry_run,
+        total_rows: table.rows.len(),
+        imported: 0,
+        created_tasks: 0,
+        errors: Vec::new(),
+    };
+    let tx = conn.transaction().context("Failed to start import")?;
+    for (i, row) in table.rows.iter().enumerate() {
+        let result = match mapping.target {
+            ImportTarget::Tasks => import_task_row(&tx, &ctx, row).map(|()| false),
+            ImportTarget::TimeEntries => import_entry_row(&tx, &ctx, row),
+        };
+        match result {
+            Ok(created) => {
+                report.imported += 1;
+                report.created_tasks += usize::from(created);
+            }
+            Err(e) => report.errors.push(RowError {
+                row: i + 1,
+                message: e.to_string(),
+            }),
+        }
+    }
+    if !dry_run {
+        tx.commit().context("Failed to commit import")?;
+    }
+    Ok(report)
+}
+
+/// Read the file's columns and a few rows so the user can build a mapping.
+#[tauri::command]
+pub fn inspect_import(
+    path: String,
+    target: Option<ImportTarget>,
+) -> CommandResult<ImportInspection> {
+    let target = target.unwrap_or(ImportTarget::Tasks);
+    let table = load_table(Path::new(&path), target)?;
+    Ok(ImportInspection {
+        format: table.format.to_string(),
+        suggested_mapping: suggest_mapping(&table.columns, target),
+        sample: table.rows.iter().take(SAMPLE_ROWS).cloned().collect(),
+        row_count: table.rows.len(),
+        columns: table.columns,
+    })
+}
+
+#[tauri::command]
+pub fn import_data(
+    app: AppHandle,
+    db: State<'_, Db>,
+    path: String,
+    mapping: ImportMapping,
+    dry_run: bool,
+) -> CommandResult<ImportReport> {
+    let report = db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Import, |conn| {
+            import(conn, Path::new(&path), &mapping, dry_run)
+        })
+    })??;
+    if !dry_run && report.imported > 0 {
+        if report.target == ImportTarget::TimeEntries {
+            let _ = app.emit(TIME_ENTRIES_EVENT, ());
+        }
+        let _ = app.emit(IMPORT_EVENT, &report);
+    }
+    Ok(report)
+}
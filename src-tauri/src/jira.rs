@@ -0,0 +1,840 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::{Client, Method, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::State;
+use url::Url;
+
+use crate::credentials;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::task_store::Task;
+use crate::time_entries::{self, TimeEntry};
+use crate::time_push::{self, Pushing, Service, STATUS_FAILED, STATUS_PENDING, STATUS_SYNCED};
+
+/// Jira Cloud sites live under this domain; anything else is taken for
+/// Jira Server or Data Center.
+const CLOUD_DOMAIN: &str = ".atlassian.net";
+/// Path segments that start the part of a Jira URL after its base.
+const ROUTE_SEGMENTS: [&str; 5] = ["browse", "secure", "projects", "rest", "plugins"];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Jira refuses worklogs shorter than a minute.
+const MIN_WORKLOG_SECONDS: i64 = 60;
+/// Pushed worklogs, by account.
+const ENTRIES_TABLE: &str = "jira_entries";
+
+/// Not pushed: its task names no issue, or it's under a minute.
+pub const STATUS_SKIPPED: &str = "skipped";
+
+pub const ACTION_CREATE: &str = "create";
+pub const ACTION_UPDATE: &str = "update";
+/// The task now names another issue: the worklog is deleted from the old
+/// one and made on the new one.
+pub const ACTION_MOVE: &str = "move";
+pub const ACTION_UNCHANGED: &str = "unchanged";
+pub const ACTION_SKIP: &str = "skip";
+
+const SERVICE: Service = Service {
+    id: "jira",
+    name: "Jira",
+    refused: "Jira refused the sign-in; connect the account again",
+    refused_statuses: &[StatusCode::UNAUTHORIZED],
+    empty_statuses: &[StatusCode::NOT_FOUND, StatusCode::NO_CONTENT],
+    message: |text| {
+        let error: RemoteError = serde_json::from_str(text).unwrap_or_default();
+        let mut messages = error.error_messages;
+        messages.extend(error.errors.into_values());
+        messages.join("; ")
+    },
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JiraAccount {
+    pub id: String,
+    pub name: String,
+    pub base_url: String,
+    /// The email address for Jira Cloud, or the user name for a Jira Server
+    /// password. `None` for a Jira Server personal access token.
+    pub username: Option<String>,
+    pub cloud: bool,
+    /// Only issue keys of these projects count; all do when empty.
+    pub project_keys: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewJiraAccount {
+    /// Defaults to the site's host name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The address Jira is opened at in a browser.
+    pub base_url: String,
+    /// Required for Jira Cloud, with an API token as `token`. For Jira
+    /// Server, leave it out to sign in with a personal access token.
+    #[serde(default)]
+    pub username: Option<String>,
+    pub token: String,
+    #[serde(default)]
+    pub project_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct JiraAccountPatch {
+    pub name: Option<String>,
+    pub project_keys: Option<Vec<String>>,
+}
+
+/// What a push did, or would do, with one time entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct JiraWorklogItem {
+    pub time_entry_id: String,
+    /// The issue the worklog goes on.
+    pub issue_key: Option<String>,
+    /// When the worklog starts, after any adjustment.
+    pub started_at: Option<String>,
+    pub time_spent_seconds: i64,
+    pub comment: Option<String>,
+    /// `create`, `update`, `move`, `unchanged` or `skip`.
+    pub action: String,
+    /// Why the entry was skipped, or how sending it failed.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JiraPushReport {
+    pub account_id: String,
+    /// Nothing was sent; the items say what would have been.
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    /// Running entries, entries under a minute and ones whose task names
+    /// no issue.
+    pub skipped: usize,
+    pub failed: usize,
+    pub items: Vec<JiraWorklogItem>,
+}
+
+/// Where one time entry stands with a Jira account.
+#[derive(Debug, Clone, Serialize)]
+pub struct JiraEntryStatus {
+    pub time_entry_id: String,
+    /// `synced`, `pending`, `failed`, `running` or `skipped`.
+    pub status: String,
+    /// The issue the entry's task names now.
+    pub issue_key: Option<String>,
+    /// The issue and worklog the entry was last pushed to.
+    pub pushed_issue_key: Option<String>,
+    pub remote_id: Option<String>,
+    /// The start time the worklog gets instead of the entry's.
+    pub started_override: Option<String>,
+    pub error: Option<String>,
+    pub pushed_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteUser {
+    #[serde(default)]
+    display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteWorklog {
+    id: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteError {
+    #[serde(default)]
+    error_messages: Vec<String>,
+    #[serde(default)]
+    errors: HashMap<String, String>,
+}
+
+/// The first Jira issue key, like `ABC-123`, in `text`. Only keys of
+/// `projects` count when it isn't empty.
+pub fn find_issue_key(text: &str, projects: &[String]) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let is_key_char = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_';
+    let mut i = 0;
+    while i < chars.len() {
+        let starts_word = i == 0 || !chars[i - 1].is_alphanumeric();
+        if !starts_word || !chars[i].is_ascii_uppercase() {
+            i += 1;
+            continue;
+        }
+        let mut dash = i + 1;
+        while dash < chars.len() && is_key_char(chars[dash]) {
+            dash += 1;
+        }
+        if dash - i >= 2 && chars.get(dash) == Some(&'-') {
+            let mut end = dash + 1;
+            while end < chars.len() && chars[end].is_ascii_digit() {
+                end += 1;
+            }
+            let ends_word = chars.get(end).is_none_or(|c| !c.is_alphanumeric());
+            if end > dash + 1 && ends_word {
+                let project: String = chars[i..dash].iter().collect();
+                if projects.is_empty() || projects.contains(&project) {
+                    return Some(chars[i..end].iter().collect());
+                }
+            }
+        }
+        i = dash;
+    }
+    None
+}
+
+/// The issue `task` is about, from its title or else its description.
+fn task_issue(task: &Task, projects: &[String]) -> Option<String> {
+    find_issue_key(&task.title, projects).or_else(|| {
+        task.description
+            .as_deref()
+            .and_then(|d| find_issue_key(d, projects))
+    })
+}
+
+fn normalize_keys(keys: &[String]) -> Vec<String> {
+    let mut keys: Vec<String> = keys
+        .iter()
+        .map(|k| k.trim().to_uppercase())
+        .filter(|k| !k.is_empty())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// The address of the Jira site at `value`, without a trailing slash. Takes
+/// the address with or without a scheme, or any page of the site. Jira
+/// Cloud sites drop any path, as they're always at the root.
+fn base_url(value: &str) -> CommandResult<(String, bool)> {
+    let value = value.trim();
+    let value = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("https://{value}")
+    };
+    let mut url =
+        Url::parse(&value).map_err(|e| CommandError::invalid(format!("Invalid Jira URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(CommandError::invalid(
+            "The Jira URL must start with http:// or https://",
+        ));
+    }
+    let cloud = url.host_str().is_some_and(|h| h.ends_with(CLOUD_DOMAIN));
+    url.set_query(None);
+    url.set_fragment(None);
+    let mut path = String::new();
+    if !cloud {
+        for segment in url.path().split('/').filter(|s| !s.is_empty()) {
+            if ROUTE_SEGMENTS.contains(&segment) {
+                break;
+            }
+            path.push('/');
+            path.push_str(segment);
+        }
+    }
+    url.set_path(&path);
+    Ok((url.as_str().trim_end_matches('/').to_string(), cloud))
+}
+
+/// A worklog's start as Jira takes it.
+fn jira_time(at: &str) -> CommandResult<String> {
+    Ok(parse_utc(at)?
+        .format("%Y-%m-%dT%H:%M:%S%.3f+0000")
+        .to_string())
+}
+
+/// What a stopped entry sends to Jira. Its hash is kept with the worklog id
+/// to tell when it needs pushing again.
+#[derive(Debug, Clone, Serialize)]
+struct Export {
+    issue_key: Option<String>,
+    /// UTC, adjusted when the entry has an override.
+    started_at: String,
+    time_spent_seconds: i64,
+    comment: Option<String>,
+}
+
+impl Export {
+    fn new(
+        entry: &TimeEntry,
+        ended_at: &str,
+        task: Option<&Task>,
+        projects: &[String],
+        started_override: Option<&str>,
+    ) -> CommandResult<Self> {
+        let seconds = (parse_utc(ended_at)? - parse_utc(&entry.started_at)?).num_seconds();
+        Ok(Self {
+            issue_key: task.and_then(|t| task_issue(t, projects)),
+            started_at: started_override.unwrap_or(&entry.started_at).to_string(),
+            time_spent_seconds: seconds.max(0),
+            comment: entry
+                .note
+                .as_deref()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    /// Why the entry can't be pushed, if it can't.
+    fn problem(&self) -> Option<String> {
+        if self.issue_key.is_none() {
+            Some("The task names no Jira issue".to_string())
+        } else if self.time_spent_seconds < MIN_WORKLOG_SECONDS {
+            Some("Jira needs at least a minute of work".to_string())
+        } else {
+            None
+        }
+    }
+
+    /// The request body. Jira Cloud takes the comment as a document, Jira
+    /// Server as plain text.
+    fn body(&self, cloud: bool) -> CommandResult<serde_json::Value> {
+        let mut body = json!({
+            "started": jira_time(&self.started_at)?,
+            "timeSpentSeconds": self.time_spent_seconds,
+        });
+        if let Some(comment) = &self.comment {
+            body["comment"] = if cloud {
+                json!({
+                    "type": "doc",
+                    "version": 1,
+                    "content": [{
+                        "type": "paragraph",
+                        "content": [{ "type": "text", "text": comment }],
+                    }],
+                })
+            } else {
+                json!(comment)
+            };
+        }
+        Ok(body)
+    }
+}
+
+/// Which issue an entry's worklog was last pushed to, and its adjusted
+/// start, which is kept from before its first push.
+#[derive(Debug, Clone, Default)]
+struct Worklog {
+    issue_key: Option<String>,
+    started_override: Option<String>,
+}
+
+impl time_push::Extra for Worklog {
+    const COLUMNS: &'static [&'static str] = &["issue_key", "started_override"];
+
+    fn read(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Self> {
+        Ok(Self {
+            issue_key: row.get(first)?,
+            started_override: row.get(first + 1)?,
+        })
+    }
+
+    fn values(&self) -> Vec<&dyn ToSql> {
+        vec![&self.issue_key, &self.started_override]
+    }
+}
+
+type PushedRow = time_push::PushedRow<String, Worklog>;
+type Outgoing = time_push::Outgoing<Export, String, Worklog>;
+
+fn entry_status(item: &Outgoing) -> JiraEntryStatus {
+    let skipped = !item.is_current() && item.export.as_ref().is_some_and(|e| e.problem().is_some());
+    let row = item.row.as_ref();
+    JiraEntryStatus {
+        time_entry_id: item.entry.id.clone(),
+        status: if skipped {
+            STATUS_SKIPPED
+        } else {
+            item.status()
+        }
+        .to_string(),
+        issue_key: item.export.as_ref().and_then(|e| e.issue_key.clone()),
+        pushed_issue_key: row
+            .filter(|r| r.remote_id.is_some())
+            .and_then(|r| r.extra.issue_key.clone()),
+        remote_id: row.and_then(|r| r.remote_id.clone()),
+        started_override: row.and_then(|r| r.extra.started_override.clone()),
+        error: row.and_then(|r| r.error.clone()),
+        pushed_at: row.and_then(|r| r.pushed_at.clone()),
+    }
+}
+
+/// What pushing the entry does, and why not when it's skipped.
+fn action(item: &Outgoing) -> (&'static str, Option<String>) {
+    let Some(export) = &item.export else {
+        return (ACTION_SKIP, Some("Still running".to_string()));
+    };
+    if let Some(problem) = export.problem() {
+        return (ACTION_SKIP, Some(problem));
+    }
+    if item.is_current() {
+        return (ACTION_UNCHANGED, None);
+    }
+    match item.row.as_ref().filter(|r| r.remote_id.is_some()) {
+        Some(row) if row.extra.issue_key != export.issue_key => (ACTION_MOVE, None),
+        Some(_) => (ACTION_UPDATE, None),
+        None => (ACTION_CREATE, None),
+    }
+}
+
+fn worklog_item(item: &Outgoing, action: &str, error: Option<String>) -> JiraWorklogItem {
+    let export = item.export.as_ref();
+    JiraWorklogItem {
+        time_entry_id: item.entry.id.clone(),
+        issue_key: export.and_then(|e| e.issue_key.clone()),
+        started_at: export.map(|e| e.started_at.clone()),
+        time_spent_seconds: export.map_or(0, |e| e.time_spent_seconds),
+        comment: export.and_then(|e| e.comment.clone()),
+        action: action.to_string(),
+        error,
+    }
+}
+
+fn row_to_account(row: &rusqlite::Row) -> rusqlite::Result<JiraAccount> {
+    let keys: Option<String> = row.get(5)?;
+    Ok(JiraAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        base_url: row.get(2)?,
+        username: row.get(3)?,
+        cloud: row.get(4)?,
+        project_keys: keys
+            .map(|k| k.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+const ACCOUNT_COLUMNS: &str =
+    "id, name, base_url, username, cloud, project_keys, created_at, updated_at";
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<JiraAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM jira_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<JiraAccount>> {
+    conn.query_row(
+        &format!("SELECT {ACCOUNT_COLUMNS} FROM jira_accounts WHERE id = ?1"),
+        params![id],
+        row_to_account,
+    )
+    .optional()
+}
+
+fn require_account(conn: &Connection, id: &str) -> CommandResult<JiraAccount> {
+    find_account(conn, id)?
+        .ok_or_else(|| CommandError::not_found(format!("Jira account not found: {id}")))
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("jira:{account_id}")
+}
+
+/// A connection to one Jira site.
+struct Api {
+    client: Client,
+    base_url: String,
+    username: Option<String>,
+    token: String,
+    cloud: bool,
+}
+
+impl Api {
+    fn new(
+        base_url: String,
+        username: Option<String>,
+        token: String,
+        cloud: bool,
+    ) -> CommandResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self {
+            client,
+            base_url,
+            username,
+            token,
+            cloud,
+        })
+    }
+
+    fn connect(account: &JiraAccount) -> CommandResult<Self> {
+        Self::new(
+            account.base_url.clone(),
+            account.username.clone(),
+            credentials::require(&keyring_name(&account.id), "token")?,
+            account.cloud,
+        )
+    }
+
+    /// Send a request to `path` under the REST API: version 3 on Jira Cloud
+    /// and 2 on Jira Server. `None` when Jira has no such thing, or sends
+    /// nothing back.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        let version = if self.cloud { 3 } else { 2 };
+        let url = format!("{}/rest/api/{version}{path}", self.base_url);
+        let request = || {
+            let request = self.client.request(method.clone(), &url);
+            match &self.username {
+                Some(username) => request.basic_auth(username, Some(&self.token)),
+                None => request.bearer_auth(&self.token),
+            }
+        };
+        time_push::send(&SERVICE, request, body).await
+    }
+}
+
+/// The time entries between `from` and `to` (UTC), each with what it sends
+/// to Jira and what `account` was last sent for it.
+fn outgoing(
+    conn: &Connection,
+    account: &JiraAccount,
+    from: &str,
+    to: &str,
+) -> CommandResult<Vec<Outgoing>> {
+    time_push::outgoing(
+        conn,
+        ENTRIES_TABLE,
+        &account.id,
+        from,
+        to,
+        |entry, ended_at, task, row: Option<&PushedRow>| {
+            let started_override = row.and_then(|r| r.extra.started_override.as_deref());
+            Export::new(
+                entry,
+                ended_at,
+                task,
+                &account.project_keys,
+                started_override,
+            )
+        },
+    )
+}
+
+/// Send one entry's worklog as `action` says, returning the worklog id.
+async fn push_entry(
+    api: &Api,
+    export: &Export,
+    row: Option<&PushedRow>,
+    action: &str,
+) -> CommandResult<String> {
+    let Some(issue_key) = &export.issue_key else {
+        return Err(CommandError::invalid("The task names no Jira issue"));
+    };
+    let body = export.body(api.cloud)?;
+    let previous = row.and_then(|r| r.remote_id.as_deref().zip(r.extra.issue_key.as_deref()));
+    match (action, previous) {
+        (ACTION_UPDATE, Some((remote_id, _))) => {
+            let updated: Option<RemoteWorklog> = api
+                .send(
+                    Method::PUT,
+                    &format!("/issue/{issue_key}/worklog/{remote_id}"),
+                    Some(&body),
+                )
+                .await?;
+            if let Some(updated) = updated {
+                return Ok(updated.id);
+            }
+        }
+        (ACTION_MOVE, Some((remote_id, old_key))) => {
+            api.send::<serde_json::Value>(
+                Method::DELETE,
+                &format!("/issue/{old_key}/worklog/{remote_id}"),
+                None,
+            )
+            .await?;
+        }
+        _ => {}
+    }
+    let created: Option<RemoteWorklog> = api
+        .send(
+            Method::POST,
+            &format!("/issue/{issue_key}/worklog"),
+            Some(&body),
+        )
+        .await?;
+    created.map(|w| w.id).ok_or_else(|| {
+        CommandError::not_found(format!(
+            "No Jira issue {issue_key}, or no permission to log work on it"
+        ))
+    })
+}
+
+async fn push_account(
+    db: &Db,
+    account_id: &str,
+    from: &str,
+    to: &str,
+    dry_run: bool,
+) -> CommandResult<JiraPushReport> {
+    let account = db.with_conn(|conn| Ok(require_account(conn, account_id)))??;
+    let outgoing = db.with_conn(|conn| Ok(outgoing(conn, &account, from, to)))??;
+    let api = if dry_run {
+        None
+    } else {
+        Some(Api::connect(&account)?)
+    };
+    let mut report = JiraPushReport {
+        account_id: account.id.clone(),
+        dry_run,
+        ..JiraPushReport::default()
+    };
+    for item in outgoing {
+        let (action, reason) = action(&item);
+        match action {
+            ACTION_SKIP => report.skipped += 1,
+            ACTION_UNCHANGED => report.unchanged += 1,
+            _ => {}
+        }
+        let (Some(api), Some(export), ACTION_CREATE | ACTION_UPDATE | ACTION_MOVE) =
+            (&api, &item.export, action)
+        else {
+            // A dry run counts what it would send.
+            match action {
+                ACTION_CREATE | ACTION_MOVE => report.created += 1,
+                ACTION_UPDATE => report.updated += 1,
+                _ => {}
+            }
+            report.items.push(worklog_item(&item, action, reason));
+            continue;
+        };
+        let mut row = item.row.clone().unwrap_or_default();
+        row.pushed_at = Some(now_utc());
+        let error = match push_entry(api, export, item.row.as_ref(), action).await {
+            Ok(remote_id) => {
+                if action == ACTION_UPDATE && row.remote_id.as_deref() == Some(remote_id.as_str()) {
+                    report.updated += 1;
+                } else {
+                    report.created += 1;
+                }
+                row.remote_id = Some(remote_id);
+                row.extra.issue_key = export.issue_key.clone();
+                row.status = STATUS_SYNCED.to_string();
+                row.error = None;
+                row.digest = Some(time_push::digest(export));
+                None
+            }
+            Err(e) => {
+                tracing::warn!("worklog for entry {} failed: {e}", item.entry.id);
+                report.failed += 1;
+                row.status = STATUS_FAILED.to_string();
+                row.error = Some(e.to_string());
+                row.error.clone()
+            }
+        };
+        // Saved as each goes, so an interrupted push can't log work twice.
+        db.with_conn(|conn| {
+            time_push::save_row(conn, ENTRIES_TABLE, &account.id, &item.entry.id, &row)
+        })?;
+        report.items.push(worklog_item(&item, action, error));
+    }
+    Ok(report)
+}
+
+/// Check the sign-in and save the account. Jira Cloud takes the email
+/// address and an API token; Jira Server a user name and password, or a
+/// personal access token alone.
+#[tauri::command]
+pub async fn connect_jira_account(
+    db: State<'_, Db>,
+    input: NewJiraAccount,
+) -> CommandResult<JiraAccount> {
+    let (base, cloud) = base_url(&input.base_url)?;
+    let token = input.token.trim().to_string();
+    if token.is_empty() {
+        return Err(CommandError::invalid("Enter the Jira API token"));
+    }
+    let username = input
+        .username
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty());
+    if cloud && username.is_none() {
+        return Err(CommandError::invalid(
+            "Enter the email address you sign in to Jira with",
+        ));
+    }
+    let api = Api::new(base.clone(), username.clone(), token.clone(), cloud)?;
+    let user: RemoteUser = api
+        .send(Method::GET, "/myself", None)
+        .await?
+        .ok_or_else(|| CommandError::not_found("No Jira found at this address"))?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let host = Url::parse(&base)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string));
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or(host)
+        .or(user.display_name)
+        .unwrap_or_else(|| "Jira".to_string());
+    let keys = normalize_keys(&input.project_keys);
+    let keys = (!keys.is_empty()).then(|| keys.join(","));
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO jira_accounts (id, name, base_url, username, cloud, project_keys,
+                 created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+            params![id, name, base, username, cloud, keys, now],
+        )?;
+        find_account(conn, &id)
+    });
+    match stored {
+        Ok(Some(account)) => Ok(account),
+        Ok(None) => Err("Failed to save account".into()),
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_jira_accounts(db: State<'_, Db>) -> CommandResult<Vec<JiraAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Rename an account or change which projects' issue keys count.
+#[tauri::command]
+pub fn update_jira_account(
+    db: State<'_, Db>,
+    id: String,
+    patch: JiraAccountPatch,
+) -> CommandResult<JiraAccount> {
+    let name = match patch.name.map(|n| n.trim().to_string()) {
+        Some(name) if name.is_empty() => return Err(CommandError::invalid("Enter a name")),
+        name => name,
+    };
+    db.with_conn(|conn| {
+        let Some(mut account) = find_account(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Jira account not found: {id}"
+            ))));
+        };
+        if let Some(name) = name {
+            account.name = name;
+        }
+        if let Some(keys) = &patch.project_keys {
+            account.project_keys = normalize_keys(keys);
+        }
+        account.updated_at = now_utc();
+        let keys = (!account.project_keys.is_empty()).then(|| account.project_keys.join(","));
+        conn.execute(
+            "UPDATE jira_accounts SET name = ?2, project_keys = ?3, updated_at = ?4
+             WHERE id = ?1",
+            params![account.id, account.name, keys, account.updated_at],
+        )?;
+        Ok(Ok(account))
+    })?
+}
+
+/// Forget an account and which entries went to it. Worklogs already in
+/// Jira stay there.
+#[tauri::command]
+pub fn remove_jira_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM jira_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Jira account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+/// Start the worklog for `time_entry_id` at `started_at` instead of when
+/// the entry started, or go back to the entry's start when `None`. The
+/// worklog keeps the entry's length, and moves with the next push.
+#[tauri::command]
+pub fn set_jira_worklog_start(
+    db: State<'_, Db>,
+    account_id: String,
+    time_entry_id: String,
+    started_at: Option<String>,
+) -> CommandResult<()> {
+    let started_at = started_at
+        .map(|at| parse_utc(&at).map(format_utc))
+        .transpose()?;
+    db.with_conn(|conn| {
+        if let Err(e) = require_account(conn, &account_id) {
+            return Ok(Err(e));
+        }
+        if time_entries::find_entry(conn, &time_entry_id)?.is_none() {
+            return Ok(Err(CommandError::not_found(format!(
+                "Time entry not found: {time_entry_id}"
+            ))));
+        }
+        let mut row: PushedRow = time_push::load_rows(conn, ENTRIES_TABLE, &account_id)?
+            .remove(&time_entry_id)
+            .unwrap_or_else(|| PushedRow {
+                status: STATUS_PENDING.to_string(),
+                ..PushedRow::default()
+            });
+        row.extra.started_override = started_at;
+        time_push::save_row(conn, ENTRIES_TABLE, &account_id, &time_entry_id, &row).map(Ok)
+    })?
+}
+
+/// Log the stopped time entries between `from` and `to` as worklogs on the
+/// Jira issues their tasks name, like `ABC-123` in the title or
+/// description. Entries pushed before are updated when they changed, and
+/// moved when their task names another issue. With `dry_run`, nothing is
+/// sent and the report lists what would be.
+#[tauri::command]
+pub async fn push_to_jira(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+    dry_run: Option<bool>,
+) -> CommandResult<JiraPushReport> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    let dry_run = dry_run.unwrap_or(false);
+    if dry_run {
+        return push_account(&db, &account_id, &from, &to, true).await;
+    }
+    let _pushing = Pushing::start("Jira")?;
+    push_account(&db, &account_id, &from, &to, false).await
+}
+
+/// Where each time entry between `from` and `to` stands with `account_id`.
+#[tauri::command]
+pub fn list_jira_entry_status(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+) -> CommandResult<Vec<JiraEntryStatus>> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    db.with_conn(|conn| {
+        Ok(require_account(conn, &account_id)
+            .and_then(|account| outgoing(conn, &account, &from, &to))
+            .map(|items| items.iter().map(entry_status).collect()))
+    })?
+}
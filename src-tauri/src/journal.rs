@@ -0,0 +1,637 @@
+use std::collections::BTreeSet;
+
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value as Json};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandResult, ResultExt};
+
+/// Emitted after an undo, a redo or a batch edit with a `DataChanged`
+/// payload. The rows changed underneath the frontend, so affected views
+/// should reload.
+pub const DATA_CHANGED_EVENT: &str = "data-changed";
+
+/// User data whose every insert, update and delete is journaled. Tables
+/// added by later migrations belong here too.
+const JOURNALED_TABLES: &[&str] = &[
+    "tasks",
+    "time_entries",
+    "tags",
+    "task_tags",
+    "task_dependencies",
+    "reminders",
+    "solar_reminders",
+    "external_refs",
+    "attachments",
+    "projects",
+    "time_blocks",
+    "task_templates",
+    "goals",
+    "billing_rules",
+];
+
+/// How many undo steps are kept; older ones are dropped.
+const MAX_STEPS: i64 = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalStep {
+    pub id: i64,
+    /// Short description such as `Delete 3 tasks`.
+    pub label: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoState {
+    pub undo: Option<JournalStep>,
+    pub redo: Option<JournalStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataChanged {
+    /// `undo`, `redo` or `bulk`.
+    pub action: String,
+    pub step: JournalStep,
+    pub tables: Vec<String>,
+}
+
+/// One journaled row change, with the row as it was and as it became.
+pub struct Change {
+    pub tbl: String,
+    /// `insert`, `update` or `delete`.
+    pub op: String,
+    pub before: Option<Map<String, Json>>,
+    pub after: Option<Map<String, Json>>,
+}
+
+fn columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<(String, bool)>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(1)?, row.get::<_, i64>(5)? > 0))
+    })?;
+    rows.collect()
+}
+
+fn json_row(alias: &str, columns: &[(String, bool)]) -> String {
+    let pairs: Vec<String> = columns
+        .iter()
+        .map(|(name, _)| format!("'{name}', {alias}.\"{name}\""))
+        .collect();
+    format!("json_object({})", pairs.join(", "))
+}
+
+/// (Re)create the journaling triggers from each table's current columns, so a
+/// column added by a migration is captured without touching the triggers by
+/// hand. Changes made by the migrations themselves are not undoable.
+pub fn install(conn: &Connection) -> rusqlite::Result<()> {
+    let mut sql = String::new();
+    for table in JOURNALED_TABLES {
+        let columns = columns(conn, table)?;
+        let old = json_row("old", &columns);
+        let new = json_row("new", &columns);
+        let when = "WHEN (SELECT paused FROM journal_control) = 0";
+        for (op, before, after) in [
+            ("insert", "NULL", new.as_str()),
+            ("update", old.as_str(), new.as_str()),
+            ("delete", old.as_str(), "NULL"),
+        ] {
+            sql.push_str(&format!(
+                "DROP TRIGGER IF EXISTS journal_{table}_{op};
+                 CREATE TRIGGER journal_{table}_{op} AFTER {} ON {table} {when} BEGIN
+                     INSERT INTO journal (tbl, op, before, after)
+                     VALUES ('{table}', '{op}', {before}, {after});
+                 END;",
+                op.to_uppercase()
+            ));
+        }
+    }
+    sql.push_str("DELETE FROM journal WHERE step_id IS NULL;");
+    conn.execute_batch(&sql)
+}
+
+fn parse_image(value: Option<String>) -> Option<Map<String, Json>> {
+    match serde_json::from_str(&value?) {
+        Ok(Json::Object(map)) => Some(map),
+        _ => None,
+    }
+}
+
+fn load_changes(conn: &Connection, step_id: Option<i64>) -> rusqlite::Result<Vec<Change>> {
+    let mut stmt = conn
+        .prepare("SELECT tbl, op, before, after FROM journal WHERE step_id IS ?1 ORDER BY seq")?;
+    let rows = stmt.query_map(params![step_id], |row| {
+        Ok(Change {
+            tbl: row.get(0)?,
+            op: row.get(1)?,
+            before: parse_image(row.get(2)?),
+            after: parse_image(row.get(3)?),
+        })
+    })?;
+    rows.collect()
+}
+
+fn noun(table: &str, count: usize) -> &'static str {
+    match (table, count) {
+        ("tasks", 1) => "task",
+        ("tasks", _) => "tasks",
+        ("time_entries", 1) => "time entry",
+        ("time_entries", _) => "time entries",
+        ("tags", 1) => "tag",
+        ("tags", _) => "tags",
+        ("reminders", 1) => "reminder",
+        ("reminders", _) => "reminders",
+        ("solar_reminders", 1) => "sun reminder",
+        ("solar_reminders", _) => "sun reminders",
+        ("task_tags", _) => "task tags",
+        ("task_dependencies", 1) => "dependency",
+        ("task_dependencies", _) => "dependencies",
+        ("attachments", 1) => "attachment",
+        ("attachments", _) => "attachments",
+        ("projects", 1) => "project",
+        ("projects", _) => "projects",
+        ("time_blocks", 1) => "time block",
+        ("time_blocks", _) => "time blocks",
+        ("task_templates", 1) => "template",
+        ("task_templates", _) => "templates",
+        ("goals", 1) => "goal",
+        ("goals", _) => "goals",
+        ("billing_rules", 1) => "billing rule",
+        ("billing_rules", _) => "billing rules",
+        _ => "items",
+    }
+}
+
+fn is_completion(change: &Change) -> bool {
+    let status = |image: &Option<Map<String, Json>>| {
+        image
+            .as_ref()
+            .and_then(|m| m.get("status"))
+            .and_then(Json::as_str)
+            .map(str::to_string)
+    };
+    status(&change.after).as_deref() == Some("done")
+        && status(&change.before).as_deref() != Some("done")
+}
+
+/// Whether an update moved a row into (`into`) or out of the trash.
+fn is_trash_move(change: &Change, into: bool) -> bool {
+    let trashed = |image: &Option<Map<String, Json>>| {
+        image
+            .as_ref()
+            .and_then(|m| m.get("deleted_at"))
+            .is_some_and(|v| !v.is_null())
+    };
+    trashed(&change.after) == into && trashed(&change.before) != into
+}
+
+/// Describe a step by its most significant table and most common operation.
+fn describe(changes: &[Change]) -> String {
+    let Some(table) = JOURNALED_TABLES
+        .iter()
+        .find(|t| changes.iter().any(|c| c.tbl == **t))
+    else {
+        return "Change".to_string();
+    };
+    let main: Vec<&Change> = changes.iter().filter(|c| c.tbl == *table).collect();
+    let (op, _) = [("insert", 2), ("delete", 1), ("update", 0)]
+        .into_iter()
+        .map(|(op, rank)| (op, (main.iter().filter(|c| c.op == op).count(), rank)))
+        .max_by_key(|(_, key)| *key)
+        .unwrap_or(("update", (0, 0)));
+    let rows: Vec<&&Change> = main.iter().filter(|c| c.op == op).collect();
+    // The same row is often written more than once in a step.
+    let ids: BTreeSet<String> = rows
+        .iter()
+        .filter_map(|c| c.after.as_ref().or(c.before.as_ref()))
+        .map(|m| m.get("id").map(Json::to_string).unwrap_or_default())
+        .collect();
+    let count = ids.len().max(1);
+
+    let verb = match op {
+        "insert" => "Create",
+        "delete" => "Delete",
+        _ if rows.iter().any(|c| is_trash_move(c, true)) => "Delete",
+        _ if rows.iter().any(|c| is_trash_move(c, false)) => "Restore",
+        _ if rows.iter().any(|c| is_completion(c)) => "Complete",
+        _ => "Edit",
+    };
+    let title = rows
+        .first()
+        .and_then(|c| c.after.as_ref().or(c.before.as_ref()))
+        .and_then(|m| {
+            ["title", "name", "file_name"]
+                .iter()
+                .find_map(|key| m.get(*key))
+        })
+        .and_then(Json::as_str);
+    match title {
+        Some(title) if count == 1 => format!("{verb} {} \"{title}\"", noun(table, 1)),
+        _ if count == 1 => format!("{verb} {}", noun(table, 1)),
+        _ => format!("{verb} {count} {}", noun(table, count)),
+    }
+}
+
+/// Group everything journaled since the last call into one undo step, and
+/// return its changes. Called by `Db::with_conn` after each closure, so a
+/// step is one backend call. Recording a new step discards anything that
+/// could have been redone.
+pub fn seal(conn: &mut Connection) -> rusqlite::Result<Vec<Change>> {
+    let pending = load_changes(conn, None)?;
+    if pending.is_empty() {
+        return Ok(pending);
+    }
+    let tx = conn.transaction()?;
+    tx.execute("DELETE FROM journal_steps WHERE undone = 1", [])?;
+    tx.execute(
+        "INSERT INTO journal_steps (label, created_at) VALUES (?1, ?2)",
+        params![describe(&pending), now_utc()],
+    )?;
+    let step_id = tx.last_insert_rowid();
+    tx.execute(
+        "UPDATE journal SET step_id = ?1 WHERE step_id IS NULL",
+        params![step_id],
+    )?;
+    tx.execute(
+        "DELETE FROM journal_steps WHERE id NOT IN
+             (SELECT id FROM journal_steps ORDER BY id DESC LIMIT ?1)",
+        params![MAX_STEPS],
+    )?;
+    tx.commit()?;
+    Ok(pending)
+}
+
+/// Take everything journaled since the last call without making it an
+/// undo step, for `Db::unjournaled` handles.
+pub fn discard(conn: &Connection) -> rusqlite::Result<Vec<Change>> {
+    let pending = load_changes(conn, None)?;
+    if !pending.is_empty() {
+        conn.execute("DELETE FROM journal WHERE step_id IS NULL", [])?;
+    }
+    Ok(pending)
+}
+
+fn to_value(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Integer(*b as i64),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        Json::String(s) => Value::Text(s.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+fn key_clause(pk: &[String], row: &Map<String, Json>, values: &mut Vec<Value>) -> String {
+    pk.iter()
+        .map(|col| {
+            values.push(to_value(row.get(col).unwrap_or(&Json::Null)));
+            format!("\"{col}\" = ?{}", values.len())
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn insert_row(conn: &Connection, table: &str, row: &Map<String, Json>) -> rusqlite::Result<()> {
+    let cols: Vec<String> = row.keys().map(|c| format!("\"{c}\"")).collect();
+    let marks: Vec<String> = (1..=row.len()).map(|i| format!("?{i}")).collect();
+    let values: Vec<Value> = row.values().map(to_value).collect();
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} ({}) VALUES ({})",
+            cols.join(", "),
+            marks.join(", ")
+        ),
+        rusqlite::params_from_iter(values),
+    )?;
+    Ok(())
+}
+
+fn delete_row(
+    conn: &Connection,
+    table: &str,
+    pk: &[String],
+    row: &Map<String, Json>,
+) -> rusqlite::Result<()> {
+    let mut values = Vec::new();
+    let clause = key_clause(pk, row, &mut values);
+    conn.execute(
+        &format!("DELETE FROM {table} WHERE {clause}"),
+        rusqlite::params_from_iter(values),
+    )?;
+    Ok(())
+}
+
+fn update_row(
+    conn: &Connection,
+    table: &str,
+    pk: &[String],
+    key: &Map<String, Json>,
+    row: &Map<String, Json>,
+) -> rusqlite::Result<()> {
+    let mut values: Vec<Value> = row.values().map(to_value).collect();
+    let sets: Vec<String> = row
+        .keys()
+        .enumerate()
+        .map(|(i, c)| format!("\"{c}\" = ?{}", i + 1))
+        .collect();
+    let clause = key_clause(pk, key, &mut values);
+    conn.execute(
+        &format!("UPDATE {table} SET {} WHERE {clause}", sets.join(", ")),
+        rusqlite::params_from_iter(values),
+    )?;
+    Ok(())
+}
+
+/// Apply one change backwards (undo) or forwards (redo).
+fn apply(conn: &Connection, change: &Change, forward: bool) -> rusqlite::Result<()> {
+    let Some(table) = JOURNALED_TABLES.iter().find(|t| **t == change.tbl) else {
+        return Ok(());
+    };
+    let pk: Vec<String> = columns(conn, table)?
+        .into_iter()
+        .filter(|(_, is_pk)| *is_pk)
+        .map(|(name, _)| name)
+        .collect();
+    let (from, to) = if forward {
+        (&change.before, &change.after)
+    } else {
+        (&change.after, &change.before)
+    };
+    match (from, to) {
+        (None, Some(row)) => insert_row(conn, table, row),
+        (Some(row), None) => delete_row(conn, table, &pk, row),
+        (Some(key), Some(row)) => update_row(conn, table, &pk, key, row),
+        (None, None) => Ok(()),
+    }
+}
+
+fn find_step(conn: &Connection, undone: bool) -> rusqlite::Result<Option<JournalStep>> {
+    // Undo takes the newest live step, redo the oldest undone one.
+    let order = if undone { "ASC" } else { "DESC" };
+    conn.query_row(
+        &format!(
+            "SELECT id, label, created_at FROM journal_steps
+             WHERE undone = ?1 ORDER BY id {order} LIMIT 1"
+        ),
+        params![undone],
+        |row| {
+            Ok(JournalStep {
+                id: row.get(0)?,
+                label: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Undo (`forward == false`) or redo the next step, with journaling paused so
+/// the replay itself isn't recorded. Returns the step and the tables touched.
+pub fn replay(
+    conn: &mut Connection,
+    forward: bool,
+) -> rusqlite::Result<Option<(JournalStep, Vec<String>)>> {
+    let Some(step) = find_step(conn, forward)? else {
+        return Ok(None);
+    };
+    let mut changes = load_changes(conn, Some(step.id))?;
+    if !forward {
+        changes.reverse();
+    }
+
+    let tx = conn.transaction()?;
+    // Rows come back parent-after-child as often as not.
+    tx.execute_batch(
+        "PRAGMA defer_foreign_keys = ON;
+         UPDATE journal_control SET paused = 1;",
+    )?;
+    for change in &changes {
+        apply(&tx, change, forward)?;
+    }
+    tx.execute(
+        "UPDATE journal_steps SET undone = ?2 WHERE id = ?1",
+        params![step.id, !forward],
+    )?;
+    tx.execute("UPDATE journal_control SET paused = 0", [])?;
+    tx.commit()?;
+
+    let tables: BTreeSet<String> = changes.into_iter().map(|c| c.tbl).collect();
+    Ok(Some((step, tables.into_iter().collect())))
+}
+
+fn undo_state(conn: &Connection) -> rusqlite::Result<UndoState> {
+    Ok(UndoState {
+        undo: find_step(conn, false)?,
+        redo: find_step(conn, true)?,
+    })
+}
+
+fn emit_changed(app: &AppHandle, action: &str, step: JournalStep, tables: Vec<String>) {
+    if tables.iter().any(|t| t == "time_entries") {
+        crate::time_entries::notify(app);
+    }
+    let _ = app.emit(
+        DATA_CHANGED_EVENT,
+        DataChanged {
+            action: action.to_string(),
+            step,
+            tables,
+        },
+    );
+}
+
+/// Announce the step just recorded, for a batch edit whose rows several
+/// views may be showing.
+pub fn announce(app: &AppHandle, db: &Db, action: &str) {
+    let latest = db.with_conn(|conn| {
+        let Some(step) = find_step(conn, false)? else {
+            return Ok(None);
+        };
+        let tables: BTreeSet<String> = load_changes(conn, Some(step.id))?
+            .into_iter()
+            .map(|c| c.tbl)
+            .collect();
+        Ok(Some((step, tables.into_iter().collect())))
+    });
+    match latest {
+        Ok(Some((step, tables))) => emit_changed(app, action, step, tables),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("{e}"),
+    }
+}
+
+fn run_replay(app: &AppHandle, db: &Db, forward: bool) -> CommandResult<UndoState> {
+    let action = if forward { "redo" } else { "undo" };
+    let (replayed, state) = db
+        .with_conn(|conn| {
+            let replayed = replay(conn, forward)?;
+            Ok((replayed, undo_state(conn)?))
+        })
+        .with_context(|| format!("Failed to {action}"))?;
+
+    if let Some((step, tables)) = replayed {
+        emit_changed(app, action, step, tables);
+    }
+    Ok(state)
+}
+
+#[tauri::command]
+pub fn get_undo_state(db: State<'_, Db>) -> CommandResult<UndoState> {
+    db.with_conn(|conn| undo_state(conn))
+}
+
+#[tauri::command]
+pub fn undo(app: AppHandle, db: State<'_, Db>) -> CommandResult<UndoState> {
+    run_replay(&app, &db, false)
+}
+
+#[tauri::command]
+pub fn redo(app: AppHandle, db: State<'_, Db>) -> CommandResult<UndoState> {
+    run_replay(&app, &db, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{migrations, tags};
+
+    fn open() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        migrations::run_migrations(&mut conn).unwrap();
+        install(&conn).unwrap();
+        conn
+    }
+
+    /// Run `sql` as one step, the way `Db::with_conn` does.
+    fn step(conn: &mut Connection, sql: &str) -> String {
+        conn.execute_batch(sql).unwrap();
+        seal(conn).unwrap();
+        undo_state(conn).unwrap().undo.unwrap().label
+    }
+
+    fn add_task(id: &str, title: &str) -> String {
+        format!(
+            "INSERT INTO tasks (id, title, created_at, updated_at)
+             VALUES ('{id}', '{title}', '2025-01-15T10:00:00.000Z', '2025-01-15T10:00:00.000Z');"
+        )
+    }
+
+    fn titles(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT title FROM tasks ORDER BY id").unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    fn replay_label(conn: &mut Connection, forward: bool) -> Option<String> {
+        replay(conn, forward).unwrap().map(|(step, _)| step.label)
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_history() {
+        let mut conn = open();
+        assert_eq!(
+            step(&mut conn, &add_task("a", "Walk")),
+            "Create task \"Walk\""
+        );
+        let edit = "UPDATE tasks SET title = 'Walk the dog' WHERE id = 'a'";
+        assert_eq!(step(&mut conn, edit), "Edit task \"Walk the dog\"");
+        let done = "UPDATE tasks SET status = 'done' WHERE id = 'a'";
+        assert_eq!(step(&mut conn, done), "Complete task \"Walk the dog\"");
+
+        assert_eq!(
+            replay_label(&mut conn, false).unwrap(),
+            "Complete task \"Walk the dog\""
+        );
+        assert_eq!(
+            replay_label(&mut conn, false).unwrap(),
+            "Edit task \"Walk the dog\""
+        );
+        assert_eq!(titles(&conn), ["Walk"]);
+        assert_eq!(
+            replay_label(&mut conn, false).unwrap(),
+            "Create task \"Walk\""
+        );
+        assert!(titles(&conn).is_empty());
+        assert_eq!(replay_label(&mut conn, false), None);
+
+        assert_eq!(
+            replay_label(&mut conn, true).unwrap(),
+            "Create task \"Walk\""
+        );
+        assert_eq!(
+            replay_label(&mut conn, true).unwrap(),
+            "Edit task \"Walk the dog\""
+        );
+        assert_eq!(titles(&conn), ["Walk the dog"]);
+        // Replaying isn't itself recorded.
+        assert!(load_changes(&conn, None).unwrap().is_empty());
+
+        // A new edit drops what could have been redone.
+        step(
+            &mut conn,
+            "UPDATE tasks SET title = 'Feed the dog' WHERE id = 'a'",
+        );
+        let state = undo_state(&conn).unwrap();
+        assert!(state.redo.is_none());
+        assert_eq!(state.undo.unwrap().label, "Edit task \"Feed the dog\"");
+    }
+
+    #[test]
+    fn undo_restores_cascaded_rows() {
+        let mut conn = open();
+        conn.execute_batch(&format!(
+            "{}{}",
+            add_task("a", "Pay"),
+            add_task("b", "File")
+        ))
+        .unwrap();
+        for id in ["a", "b"] {
+            tags::set_task_tags(&conn, id, &["bills".to_string()]).unwrap();
+        }
+        seal(&mut conn).unwrap();
+
+        let label = step(&mut conn, "DELETE FROM tasks");
+        assert_eq!(label, "Delete 2 tasks");
+        let links = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM task_tags", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(links(&conn), 0);
+
+        let (_, tables) = replay(&mut conn, false).unwrap().unwrap();
+        assert_eq!(titles(&conn), ["Pay", "File"]);
+        assert_eq!(links(&conn), 2);
+        assert!(tables.contains(&"tasks".to_string()) && tables.contains(&"task_tags".to_string()));
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut conn = open();
+        step(&mut conn, &add_task("a", "Count"));
+        for n in 0..MAX_STEPS + 5 {
+            step(&mut conn, &format!("UPDATE tasks SET priority = {n}"));
+        }
+        let steps: i64 = conn
+            .query_row("SELECT COUNT(*) FROM journal_steps", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(steps, MAX_STEPS);
+        while replay(&mut conn, false).unwrap().is_some() {}
+        // The oldest steps, creating the task included, are gone for good.
+        assert_eq!(titles(&conn), ["Count"]);
+    }
+
+    #[test]
+    fn discarded_changes_are_not_steps() {
+        let mut conn = open();
+        conn.execute_batch(&add_task("a", "Synced")).unwrap();
+        assert_eq!(discard(&conn).unwrap().len(), 1);
+        assert!(seal(&mut conn).unwrap().is_empty());
+        assert!(undo_state(&conn).unwrap().undo.is_none());
+    }
+}
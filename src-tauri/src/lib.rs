@@ -1,14 +1,120 @@
+mod account_health;
+mod actions;
+mod api_server;
+mod archive;
+mod asana;
+mod attachments;
+mod backup;
+mod billing;
+mod bulk;
+mod caldav;
+mod calendars;
+mod change_log;
+mod clockify;
+mod conflicts;
+mod crash;
+mod crdt;
+mod credentials;
+mod csv;
+mod data_dir;
+mod db;
+mod deck;
+mod dependencies;
+mod encryption;
+mod error;
+mod events;
+mod ews;
+mod ews_calendar;
+mod export;
+#[cfg(desktop)]
+mod focus_mode;
+mod github;
+mod gitlab;
+mod goals;
+mod google;
+mod google_calendar;
+mod google_tasks;
+mod harvest;
+mod history;
+mod http;
+mod ics;
+mod ics_feed;
+mod imap;
+mod import;
+mod jira;
+mod journal;
+mod locale;
+mod location;
+mod logging;
+mod maintenance;
+mod microsoft;
+mod microsoft_calendar;
+mod microsoft_todo;
+mod migrations;
+mod mime;
+mod mqtt;
+mod natural_date;
+mod nextcloud;
+mod notes;
+mod notion;
+mod obsidian;
+mod order_key;
+mod orgmode;
+mod outbox;
+mod pomodoro;
+mod projects;
+mod rate_limit;
+mod recurrence;
+mod reminders;
+mod report_pdf;
+mod reports;
+mod rrule;
+mod schedule;
+mod scripts;
+mod search;
+mod session;
+mod settings;
+mod stats;
+mod subtasks;
+mod sun;
+mod sync_provider;
+mod sync_status;
+mod tags;
+mod task_store;
 mod tasks;
+mod taskwarrior;
+mod templates;
 mod theme;
+mod time_entries;
+mod time_push;
+mod timer;
+mod timezone;
+mod todoist;
+mod todotxt;
+mod toggl;
+mod trash;
+mod trello;
+#[cfg(desktop)]
+mod tray;
+mod usage;
+mod webdav_sync;
+mod weather;
+mod webhooks;
+#[cfg(desktop)]
+mod window_effects;
+mod xml;
+mod zoom;
 
 use std::sync::Mutex;
 use std::time::Duration;
 
-use tauri::{Emitter, Manager, State};
+use tauri::{Manager, State};
 use tokio::time::timeout;
 use tokio::sync::oneshot;
 use tiny_http::{ListenAddr, Response, Server};
 
+use crate::error::CommandResult;
+
 struct OAuthListenerState {
     receiver: Mutex<Option<oneshot::Receiver<String>>>,
 }
@@ -23,10 +129,10 @@ fn extract_code(url: &str) -> Option<String> {
     None
 }
 
-fn listen_addr_port(addr: ListenAddr) -> Result<u16, String> {
+fn listen_addr_port(addr: ListenAddr) -> CommandResult<u16> {
     match addr {
         ListenAddr::IP(address) => Ok(address.port()),
-        _ => Err("Unsupported listener address".to_string()),
+        _ => Err("Unsupported listener address".into()),
     }
 }
 
@@ -36,12 +142,12 @@ fn setup_linux_shortcut_bridge(window: &tauri::WebviewWindow) {
     use gtk::prelude::*;
 
     let Ok(gtk_window) = window.gtk_window() else {
-        eprintln!("[daylight-dev] linux-shortcuts: failed to get gtk window");
+        tracing::debug!("linux-shortcuts: failed to get gtk window");
         return;
     };
 
     #[cfg(debug_assertions)]
-    eprintln!("[daylight-dev] linux-shortcuts: bridge installed");
+    tracing::debug!("linux-shortcuts: bridge installed");
 
     let window_for_handler = window.clone();
     gtk_window.connect_key_press_event(move |_widget, event| {
@@ -52,8 +158,8 @@ fn setup_linux_shortcut_bridge(window: &tauri::WebviewWindow) {
 
         #[cfg(debug_assertions)]
         if ctrl_or_meta {
-            eprintln!(
-                "[daylight-dev] linux-shortcuts: raw key={:?} unicode={:?} state={:?}",
+            tracing::debug!(
+                "linux-shortcuts: raw key={:?} unicode={:?} state={:?}",
                 event.keyval(),
                 event.keyval().to_unicode(),
                 state
@@ -68,28 +174,14 @@ fn setup_linux_shortcut_bridge(window: &tauri::WebviewWindow) {
         match key {
             Some('n') => {
                 #[cfg(debug_assertions)]
-                eprintln!("[daylight-dev] linux-shortcuts: Ctrl/Cmd+N");
-                if let Err(error) = window_for_handler.emit("daylight:shortcut:add-task", ()) {
-                    eprintln!("[daylight-dev] linux-shortcuts: emit add-task failed: {error}");
-                }
-                if let Err(error) = window_for_handler
-                    .eval("window.dispatchEvent(new CustomEvent('daylight:shortcut:add-task'));")
-                {
-                    eprintln!("[daylight-dev] linux-shortcuts: eval add-task failed: {error}");
-                }
+                tracing::debug!("linux-shortcuts: Ctrl/Cmd+N");
+                actions::dispatch_to_window(&window_for_handler, &actions::QuickAction::NewTask);
                 gtk::glib::Propagation::Stop
             }
             Some('t') => {
                 #[cfg(debug_assertions)]
-                eprintln!("[daylight-dev] linux-shortcuts: Ctrl/Cmd+T");
-                if let Err(error) = window_for_handler.emit("daylight:shortcut:log-time", ()) {
-                    eprintln!("[daylight-dev] linux-shortcuts: emit log-time failed: {error}");
-                }
-                if let Err(error) = window_for_handler
-                    .eval("window.dispatchEvent(new CustomEvent('daylight:shortcut:log-time'));")
-                {
-                    eprintln!("[daylight-dev] linux-shortcuts: eval log-time failed: {error}");
-                }
+                tracing::debug!("linux-shortcuts: Ctrl/Cmd+T");
+                actions::dispatch_to_window(&window_for_handler, &actions::QuickAction::LogTime);
                 gtk::glib::Propagation::Stop
             }
             _ => gtk::glib::Propagation::Proceed,
@@ -98,10 +190,10 @@ fn setup_linux_shortcut_bridge(window: &tauri::WebviewWindow) {
 }
 
 #[tauri::command]
-async fn start_oauth_listener(state: State<'_, OAuthListenerState>) -> Result<u16, String> {
+async fn start_oauth_listener(state: State<'_, OAuthListenerState>) -> CommandResult<u16> {
     let mut guard = state.receiver.lock().map_err(|_| "Lock poisoned")?;
     if guard.is_some() {
-        return Err("OAuth listener already running".to_string());
+        return Err("OAuth listener already running".into());
     }
 
     let server = Server::http("127.0.0.1:0").map_err(|e| e.to_string())?;
@@ -131,7 +223,7 @@ async fn start_oauth_listener(state: State<'_, OAuthListenerState>) -> Result<u1
 async fn await_oauth_code(
     state: State<'_, OAuthListenerState>,
     timeout_ms: u64,
-) -> Result<String, String> {
+) -> CommandResult<String> {
     let rx = {
         let mut guard = state.receiver.lock().map_err(|_| "Lock poisoned")?;
         guard.take().ok_or_else(|| "OAuth listener not started".to_string())?
@@ -140,21 +232,14 @@ async fn await_oauth_code(
     let duration = Duration::from_millis(timeout_ms);
     match timeout(duration, rx).await {
         Ok(Ok(code)) => Ok(code),
-        Ok(Err(_)) => Err("OAuth listener closed".to_string()),
-        Err(_) => Err("OAuth listener timed out".to_string()),
+        Ok(Err(_)) => Err("OAuth listener closed".into()),
+        Err(_) => Err("OAuth listener timed out".into()),
     }
 }
 
 #[tauri::command]
-async fn fetch_url(url: String) -> Result<String, String> {
-    let response = reqwest::get(url)
-        .await
-        .map_err(|e| e.to_string())?;
-    let status = response.status();
-    if !status.is_success() {
-        return Err(format!("HTTP {}", status.as_u16()));
-    }
-    response.text().await.map_err(|e| e.to_string())
+async fn fetch_url(url: String) -> CommandResult<String> {
+    http::get_text(&url).await
 }
 
 #[tauri::command]
@@ -164,12 +249,29 @@ fn tauri_ready() -> bool {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    // Autostart entries launch with --hidden, as does every launch with the
+    // tray.start_hidden setting: keep the main window hidden and live in the
+    // tray until the user opens it.
+    #[cfg(desktop)]
+    let start_hidden = std::env::args().any(|arg| arg == "--hidden");
+
+    let builder = tauri::Builder::default();
+
+    // Relaunches (jump list entries, desktop actions, plain double-launch)
+    // are forwarded here instead of opening a second window.
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+        if !actions::handle_launch_args(app, &argv) {
+            actions::focus_main_window(app);
+        }
+    }));
+
+    builder
         .on_page_load(|_webview, payload| {
             #[cfg(debug_assertions)]
             {
-                eprintln!(
-                    "[daylight-dev] page-load {:?} {}",
+                tracing::debug!(
+                    "page-load {:?} {}",
                     payload.event(),
                     payload.url()
                 );
@@ -178,21 +280,423 @@ pub fn run() {
         .manage(OAuthListenerState {
             receiver: Mutex::new(None),
         })
+        .manage(data_dir::DataDir::from_env())
+        .manage(actions::QuickActionState::new())
+        .manage(zoom::ZoomState::new())
+        .manage(pomodoro::PomodoroEngine::new())
+        .manage(timer::TimerState::new())
+        .manage(session::RestartState::new())
         .invoke_handler(tauri::generate_handler![
             start_oauth_listener,
             await_oauth_code,
             fetch_url,
             tauri_ready,
             theme::get_gtk_colors,
-            tasks::load_grouped_tasks
+            tasks::load_grouped_tasks,
+            actions::set_recent_projects,
+            session::restart_app,
+            session::restart_ready,
+            session::take_restart_state,
+            #[cfg(desktop)]
+            window_effects::get_window_effects_support,
+            #[cfg(desktop)]
+            window_effects::set_window_effect,
+            zoom::set_zoom,
+            zoom::step_zoom,
+            zoom::get_zoom,
+            #[cfg(desktop)]
+            focus_mode::enter_focus_mode,
+            #[cfg(desktop)]
+            focus_mode::exit_focus_mode,
+            #[cfg(desktop)]
+            focus_mode::get_focus_mode,
+            task_store::create_task,
+            task_store::get_task,
+            task_store::update_task,
+            task_store::delete_task,
+            task_store::list_tasks,
+            migrations::get_schema_info,
+            search::search,
+            recurrence::expand_rrule,
+            natural_date::parse_natural_date,
+            recurrence::expand_rrule_at,
+            timezone::get_time_zone,
+            timezone::convert_time,
+            timezone::local_to_utc,
+            timezone::zone_offset,
+            tags::list_tags,
+            tags::create_tag,
+            tags::update_tag,
+            tags::delete_tag,
+            tags::merge_tags,
+            time_entries::start_entry,
+            time_entries::stop_entry,
+            time_entries::get_running_entry,
+            time_entries::edit_entry,
+            time_entries::split_entry,
+            time_entries::resolve_idle_time,
+            time_entries::delete_entry,
+            time_entries::list_entries,
+            reports::report_time,
+            reports::report_completions,
+            export::export_data,
+            import::inspect_import,
+            import::import_data,
+            ics::export_ics,
+            ics::preview_ics,
+            ics::import_ics,
+            reminders::list_reminders,
+            backup::get_backup_config,
+            backup::set_backup_config,
+            backup::run_backup_now,
+            backup::list_backups,
+            backup::preview_backup,
+            backup::restore_backup,
+            encryption::database_status,
+            encryption::unlock_database,
+            encryption::change_passphrase,
+            journal::get_undo_state,
+            journal::undo,
+            journal::redo,
+            history::get_task_history,
+            attachments::add_attachment,
+            attachments::list_attachments,
+            attachments::open_attachment,
+            attachments::remove_attachment,
+            attachments::gc_attachments,
+            archive::archive_items,
+            archive::unarchive_tasks,
+            archive::search_archive,
+            archive::list_archived_tasks,
+            trash::get_trash_config,
+            trash::set_trash_config,
+            trash::list_trash,
+            trash::restore_from_trash,
+            trash::purge_trash,
+            projects::list_projects,
+            projects::get_project_rollups,
+            projects::create_project,
+            projects::update_project,
+            projects::reorder_projects,
+            projects::delete_project,
+            subtasks::list_subtasks,
+            subtasks::set_task_parent,
+            subtasks::move_subtask,
+            subtasks::get_task_progress,
+            dependencies::get_task_dependencies,
+            dependencies::add_task_blocker,
+            dependencies::remove_task_blocker,
+            pomodoro::get_pomodoro_config,
+            pomodoro::set_pomodoro_config,
+            pomodoro::get_pomodoro,
+            pomodoro::start_pomodoro,
+            pomodoro::pause_pomodoro,
+            pomodoro::resume_pomodoro,
+            pomodoro::skip_pomodoro_phase,
+            pomodoro::stop_pomodoro,
+            timer::get_timer_status,
+            timer::get_timer_recovery,
+            timer::resolve_timer_recovery,
+            schedule::list_time_blocks,
+            schedule::create_time_block,
+            schedule::reschedule_time_block,
+            schedule::delete_time_block,
+            schedule::propose_day_plan,
+            notes::read_task_note,
+            notes::write_task_note,
+            notes::get_task_note_path,
+            stats::get_stats,
+            maintenance::run_maintenance,
+            crdt::export_task_changes,
+            crdt::merge_task_changes,
+            data_dir::get_data_dir,
+            data_dir::migrate_data_dir,
+            bulk::complete_tasks,
+            bulk::move_tasks,
+            bulk::retag_tasks,
+            bulk::reschedule_tasks,
+            task_store::list_tasks_page,
+            templates::list_templates,
+            templates::create_template,
+            templates::update_template,
+            templates::delete_template,
+            templates::instantiate_template,
+            goals::list_goals,
+            goals::create_goal,
+            goals::update_goal,
+            goals::delete_goal,
+            goals::get_goal_progress,
+            billing::list_billing_rules,
+            billing::set_billing_rule,
+            billing::delete_billing_rule,
+            billing::report_billing,
+            billing::export_invoice,
+            caldav::add_caldav_account,
+            caldav::list_caldav_accounts,
+            caldav::refresh_caldav_collections,
+            caldav::update_caldav_collection,
+            caldav::remove_caldav_account,
+            caldav::sync_caldav,
+            calendars::list_calendars,
+            calendars::subscribe_calendar,
+            calendars::update_calendar,
+            calendars::remove_calendar,
+            calendars::refresh_calendars,
+            calendars::list_calendar_events,
+            google::google_auth_url,
+            google::connect_google_account,
+            google::list_google_accounts,
+            google::remove_google_account,
+            google_tasks::refresh_google_task_lists,
+            google_tasks::update_google_task_list,
+            google_tasks::sync_google_tasks,
+            google_calendar::refresh_google_calendars,
+            microsoft::microsoft_auth_url,
+            microsoft::connect_microsoft_account,
+            microsoft::list_microsoft_accounts,
+            microsoft::remove_microsoft_account,
+            microsoft_todo::refresh_microsoft_task_lists,
+            microsoft_todo::update_microsoft_task_list,
+            microsoft_todo::sync_microsoft_todo,
+            microsoft_calendar::refresh_microsoft_calendars,
+            todoist::connect_todoist_account,
+            todoist::list_todoist_accounts,
+            todoist::remove_todoist_account,
+            todoist::sync_todoist,
+            nextcloud::start_nextcloud_login,
+            nextcloud::add_nextcloud_account,
+            deck::list_deck_boards,
+            deck::refresh_deck_boards,
+            deck::update_deck_board,
+            deck::sync_deck,
+            toggl::connect_toggl_account,
+            toggl::list_toggl_accounts,
+            toggl::remove_toggl_account,
+            toggl::push_to_toggl,
+            toggl::list_toggl_entry_status,
+            clockify::connect_clockify_account,
+            clockify::list_clockify_accounts,
+            clockify::update_clockify_account,
+            clockify::remove_clockify_account,
+            clockify::list_clockify_projects,
+            clockify::map_clockify_project,
+            clockify::push_to_clockify,
+            clockify::list_clockify_entry_status,
+            harvest::connect_harvest_account,
+            harvest::list_harvest_accounts,
+            harvest::remove_harvest_account,
+            harvest::list_harvest_projects,
+            harvest::map_harvest_project,
+            harvest::push_to_harvest,
+            harvest::list_harvest_entry_status,
+            jira::connect_jira_account,
+            jira::list_jira_accounts,
+            jira::update_jira_account,
+            jira::remove_jira_account,
+            jira::set_jira_worklog_start,
+            jira::push_to_jira,
+            jira::list_jira_entry_status,
+            github::connect_github_account,
+            github::list_github_accounts,
+            github::update_github_account,
+            github::remove_github_account,
+            github::sync_github,
+            gitlab::connect_gitlab_account,
+            gitlab::list_gitlab_accounts,
+            gitlab::update_gitlab_account,
+            gitlab::remove_gitlab_account,
+            gitlab::sync_gitlab,
+            trello::connect_trello_account,
+            trello::list_trello_accounts,
+            trello::remove_trello_account,
+            trello::list_trello_boards,
+            trello::refresh_trello_boards,
+            trello::update_trello_board,
+            trello::import_trello_board,
+            trello::sync_trello,
+            notion::connect_notion_account,
+            notion::list_notion_accounts,
+            notion::remove_notion_account,
+            notion::list_notion_databases,
+            notion::refresh_notion_databases,
+            notion::update_notion_database,
+            notion::sync_notion,
+            taskwarrior::import_taskwarrior,
+            taskwarrior::export_taskwarrior,
+            todotxt::get_todotxt_link,
+            todotxt::link_todotxt,
+            todotxt::unlink_todotxt,
+            todotxt::sync_todotxt,
+            orgmode::list_org_files,
+            orgmode::add_org_file,
+            orgmode::remove_org_file,
+            orgmode::sync_org_files,
+            obsidian::get_obsidian_config,
+            obsidian::set_obsidian_config,
+            obsidian::export_obsidian_now,
+            ics_feed::get_ics_feed_config,
+            ics_feed::set_ics_feed_config,
+            ics_feed::regenerate_ics_feed_token,
+            schedule::finalize_day_plan,
+            webhooks::list_webhooks,
+            webhooks::create_webhook,
+            webhooks::update_webhook,
+            webhooks::delete_webhook,
+            webhooks::regenerate_webhook_secret,
+            webhooks::test_webhook,
+            api_server::get_api_server_config,
+            api_server::set_api_server_config,
+            api_server::regenerate_api_token,
+            mqtt::get_mqtt_config,
+            mqtt::set_mqtt_config,
+            imap::connect_imap_account,
+            imap::list_imap_accounts,
+            imap::update_imap_account,
+            imap::remove_imap_account,
+            imap::check_imap_now,
+            conflicts::get_conflict_policies,
+            conflicts::set_conflict_policy,
+            conflicts::list_conflicts,
+            conflicts::resolve_conflict,
+            sync_status::get_sync_status,
+            outbox::list_outbox,
+            outbox::retry_outbox_item,
+            webdav_sync::get_webdav_sync_config,
+            webdav_sync::set_webdav_sync_config,
+            webdav_sync::sync_webdav_now,
+            change_log::get_change_log_config,
+            change_log::set_change_log_config,
+            sync_provider::list_sync_providers,
+            sync_provider::authenticate_sync_provider,
+            sync_provider::run_sync_provider,
+            sync_provider::get_sync_modes,
+            sync_provider::set_sync_mode,
+            sync_provider::list_remote_collections,
+            sync_provider::select_remote_collection,
+            account_health::check_account,
+            ews::connect_ews_account,
+            ews::list_ews_accounts,
+            ews::update_ews_account,
+            ews::remove_ews_account,
+            ews::sync_ews,
+            ews_calendar::refresh_ews_calendars,
+            asana::asana_auth_url,
+            asana::connect_asana_account,
+            asana::list_asana_accounts,
+            asana::update_asana_account,
+            asana::remove_asana_account,
+            asana::sync_asana,
+            sun::get_sun_times,
+            location::get_location_settings,
+            location::set_location_consent,
+            location::set_manual_location,
+            location::get_location,
+            sun::get_sun_times_here,
+            weather::get_weather,
+            sun::get_daylight,
+            reminders::add_solar_reminder,
+            reminders::list_solar_reminders,
+            reminders::remove_solar_reminder,
+            theme::get_theme_schedule,
+            theme::set_theme_schedule,
+            logging::get_log_config,
+            logging::set_log_config,
+            logging::get_recent_logs,
+            logging::set_log_level,
+            logging::log_from_frontend,
+            crash::list_crash_reports,
+            crash::mark_crash_report_seen,
+            crash::delete_crash_report,
+            crash::export_crash_report,
+            locale::get_locale_info,
+            settings::get_settings,
+            settings::set_setting,
+            settings::reset_settings,
+            settings::import_legacy_settings,
+            credentials::get_credentials,
+            credentials::set_credential,
+            credentials::migrate_credentials,
+            credentials::get_credential_migration,
+            usage::record_usage,
+            usage::get_usage_stats,
+            usage::purge_usage_stats,
+            usage::get_year_in_review,
+            scripts::list_scripts,
+            scripts::create_script,
+            scripts::update_script,
+            scripts::delete_script,
+            scripts::run_script,
+            report_pdf::export_report_pdf
         ])
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
-        .setup(|app| {
+        .setup(move |app| {
+            logging::init(app.handle());
+            crash::install_panic_hook(app.handle());
+            data_dir::finish_move(app.handle());
+            let db = db::Db::open(&db::db_path(app.handle())?)?;
+            events::attach(app.handle(), &db);
+            encryption::unlock_from_keyring(&db);
+            app.manage(db);
+            usage::apply(&settings::load(app.handle()));
+            recurrence::spawn_rollover_watcher(app.handle());
+            goals::spawn_goal_watcher(app.handle());
+            calendars::spawn_calendar_refresh(app.handle());
+            timezone::spawn_zone_watcher(app.handle());
+            backup::spawn_backup_scheduler(app.handle());
+            obsidian::spawn_obsidian_scheduler(app.handle());
+            credentials::migrate_app_data(app.handle());
+            ics_feed::start(app.handle());
+            api_server::start(app.handle());
+            mqtt::spawn_mqtt_publisher(app.handle());
+            imap::spawn_imap_poller(app.handle());
+            outbox::spawn_outbox_replayer(app.handle());
+            reminders::spawn_solar_scheduler(app.handle());
+            theme::spawn_theme_scheduler(app.handle());
+            webdav_sync::spawn_webdav_sync_scheduler(app.handle());
+            webhooks::spawn_webhook_sender(app.handle());
+            attachments::spawn_gc(app.handle());
+            trash::spawn_trash_purger(app.handle());
+            pomodoro::spawn_pomodoro_ticker(app.handle());
+            timer::spawn_timer_heartbeat(app.handle());
+            notes::spawn_note_watcher(app.handle());
+            todotxt::watch(app.handle());
+            change_log::watch(app.handle());
+
             #[cfg(target_os = "linux")]
             theme::setup_gtk_watcher(app.handle());
 
+            #[cfg(target_os = "linux")]
+            if let Some(window) = app.get_webview_window("main") {
+                crash::watch_webview(app.handle(), &window);
+            }
+
+            #[cfg(desktop)]
+            {
+                app.manage(focus_mode::FocusModeState::new());
+                tray::setup_tray(app.handle())?;
+                #[cfg(target_os = "macos")]
+                actions::setup_dock_menu(app.handle());
+                if !start_hidden && !settings::load(app.handle()).tray.start_hidden {
+                    if let Some(window) = app.get_webview_window("main") {
+                        let _ = window.show();
+                    }
+                }
+            }
+
+            // The main window starts hidden for the tray; mobile has no tray,
+            // so it's always shown there.
+            #[cfg(mobile)]
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+            }
+
+            zoom::restore_zoom(app.handle());
+
+            let args: Vec<String> = std::env::args().collect();
+            actions::handle_launch_args(app.handle(), &args);
+
             #[cfg(debug_assertions)]
             {
                 if let Some(window) = app.get_webview_window("main") {
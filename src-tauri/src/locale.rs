@@ -0,0 +1,186 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Timelike, Weekday};
+use serde::Serialize;
+
+/// Used when the OS doesn't say, or says `C`/`POSIX`.
+const DEFAULT_TAG: &str = "en-US";
+
+/// Regions whose week starts on Sunday or Saturday; the rest start on
+/// Monday.
+const SUNDAY_FIRST: &[&str] = &[
+    "US", "CA", "JP", "BR", "MX", "PH", "IL", "KR", "TW", "ZA", "IN", "HK",
+];
+const SATURDAY_FIRST: &[&str] = &[
+    "AE", "AF", "BH", "DZ", "EG", "IQ", "IR", "JO", "KW", "LY", "OM", "QA", "SD", "SY",
+];
+/// Regions that read the clock in 12 hours with AM/PM.
+const TWELVE_HOUR: &[&str] = &[
+    "US", "CA", "AU", "NZ", "IN", "PH", "PK", "BD", "EG", "SA", "MY", "CO", "KR", "TW", "HN", "NI",
+    "SV",
+];
+/// Regions writing dates month first, and year first; the rest go day
+/// first.
+const MONTH_FIRST: &[&str] = &["US", "PH", "FM", "PW"];
+const YEAR_FIRST: &[&str] = &["CN", "JP", "KR", "TW", "HU", "LT", "MN", "IR", "SE"];
+/// Languages writing `1.234,5`, and `1 234,5`; the rest write `1,234.5`.
+const DOT_GROUPING: &[&str] = &[
+    "de", "es", "it", "nl", "pt", "da", "id", "tr", "el", "sl", "hr",
+];
+const SPACE_GROUPING: &[&str] = &[
+    "fr", "ru", "pl", "sv", "nb", "nn", "no", "fi", "cs", "sk", "uk", "hu", "lt", "lv", "et", "bg",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LocaleInfo {
+    /// BCP 47, e.g. `en-GB`.
+    pub tag: String,
+    pub language: String,
+    pub region: Option<String>,
+    /// `monday`, `sunday` or `saturday`.
+    pub first_day_of_week: String,
+    /// Whether times read `15:05` rather than `3:05 PM`.
+    pub clock_24h: bool,
+    /// `mdy`, `dmy` or `ymd`, for numeric dates.
+    pub date_order: String,
+    pub date_separator: String,
+    pub decimal_separator: String,
+    pub grouping_separator: String,
+}
+
+/// `en_GB.UTF-8@euro` and the like as a BCP 47 tag, `en-GB`.
+fn normalize(raw: &str) -> Option<String> {
+    let base = raw.split(['.', '@']).next()?.trim();
+    if base.is_empty() || base == "C" || base == "POSIX" {
+        return None;
+    }
+    Some(base.replace('_', "-"))
+}
+
+/// The user's locale as the OS reports it. On Unix the time category
+/// (`LC_ALL`, then `LC_TIME`, then `LANG`) decides, as it does for other
+/// programs.
+fn system_tag() -> String {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().as_deref().and_then(normalize))
+        .or_else(|| sys_locale::get_locale().as_deref().and_then(normalize))
+        .unwrap_or_else(|| DEFAULT_TAG.to_string())
+}
+
+impl LocaleInfo {
+    /// What a BCP 47 tag such as `de-AT` implies. English without a region
+    /// is taken to be American.
+    pub fn from_tag(tag: &str) -> Self {
+        let tag = normalize(tag).unwrap_or_else(|| DEFAULT_TAG.to_string());
+        let mut parts = tag.split('-');
+        let language = parts.next().unwrap_or("en").to_ascii_lowercase();
+        // Skip a script subtag, as in `zh-Hant-TW`.
+        let region = parts
+            .find(|p| p.len() == 2 || p.chars().all(|c| c.is_ascii_digit()))
+            .map(str::to_ascii_uppercase);
+        let r = region
+            .as_deref()
+            .unwrap_or(if language == "en" { "US" } else { "" });
+
+        let first_day_of_week = if SUNDAY_FIRST.contains(&r) {
+            "sunday"
+        } else if SATURDAY_FIRST.contains(&r) {
+            "saturday"
+        } else {
+            "monday"
+        };
+        // French Canada keeps the 24-hour clock.
+        let clock_24h = !TWELVE_HOUR.contains(&r) || (r == "CA" && language == "fr");
+        let date_order = if MONTH_FIRST.contains(&r) {
+            "mdy"
+        } else if YEAR_FIRST.contains(&r) {
+            "ymd"
+        } else {
+            "dmy"
+        };
+        let date_separator = match (r, language.as_str()) {
+            ("SE" | "LT" | "NL", _) => "-",
+            (
+                _,
+                "de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "nn" | "no" | "da" | "tr" | "uk",
+            )
+            | ("HU" | "KR", _) => ".",
+            _ => "/",
+        };
+        let (decimal_separator, grouping_separator) = if r == "CH" {
+            (".", "'")
+        } else if DOT_GROUPING.contains(&language.as_str()) {
+            (",", ".")
+        } else if SPACE_GROUPING.contains(&language.as_str()) {
+            (",", "\u{a0}")
+        } else {
+            (".", ",")
+        };
+
+        Self {
+            tag,
+            language,
+            region,
+            first_day_of_week: first_day_of_week.to_string(),
+            clock_24h,
+            date_order: date_order.to_string(),
+            date_separator: date_separator.to_string(),
+            decimal_separator: decimal_separator.to_string(),
+            grouping_separator: grouping_separator.to_string(),
+        }
+    }
+
+    pub fn week_start(&self) -> Weekday {
+        match self.first_day_of_week.as_str() {
+            "sunday" => Weekday::Sun,
+            "saturday" => Weekday::Sat,
+            _ => Weekday::Mon,
+        }
+    }
+
+    /// A numeric date, e.g. `3/4/2026`, `04.03.2026` or `2026-03-04`.
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        let sep = &self.date_separator;
+        match self.date_order.as_str() {
+            "mdy" => date.format(&format!("%-m{sep}%-d{sep}%Y")).to_string(),
+            "ymd" => date.format(&format!("%Y{sep}%m{sep}%d")).to_string(),
+            _ => date.format(&format!("%d{sep}%m{sep}%Y")).to_string(),
+        }
+    }
+
+    /// A time of day to the minute, `15:05` or `3:05 PM`.
+    pub fn format_time(&self, time: NaiveTime) -> String {
+        if self.clock_24h {
+            time.format("%H:%M").to_string()
+        } else {
+            let suffix = if time.hour() < 12 { "AM" } else { "PM" };
+            format!(
+                "{}:{:02} {suffix}",
+                (time.hour() + 11) % 12 + 1,
+                time.minute()
+            )
+        }
+    }
+
+    /// A date and time in `at`'s own zone.
+    pub fn format_datetime<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> String {
+        let local = at.naive_local();
+        format!(
+            "{} {}",
+            self.format_date(local.date()),
+            self.format_time(local.time())
+        )
+    }
+}
+
+/// The user's locale, read from the OS each time so a change applies
+/// without a restart.
+pub fn current() -> LocaleInfo {
+    LocaleInfo::from_tag(&system_tag())
+}
+
+/// The OS locale's language, region, first day of the week, clock, date
+/// and number conventions.
+#[tauri::command]
+pub fn get_locale_info() -> LocaleInfo {
+    current()
+}
@@ -0,0 +1,316 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+use chrono::{Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+
+/// A fix from the system is reused for this long before asking again.
+const MAX_FIX_AGE_MINUTES: i64 = 60;
+/// How long the system gets to come up with a fix.
+const LOCATE_TIMEOUT_SECONDS: u32 = 15;
+/// Where distributions install GeoClue's demo agent, which asks GeoClue
+/// for one fix on the app's behalf and prints it.
+const WHERE_AM_I: &[&str] = &[
+    "/usr/libexec/geoclue-2.0/demos/where-am-i",
+    "/usr/lib/geoclue-2.0/demos/where-am-i",
+    "/usr/lib64/geoclue-2.0/demos/where-am-i",
+];
+/// GeoClue's city-level accuracy: enough for sun times and weather,
+/// without asking for the user's street.
+const GEOCLUE_ACCURACY_CITY: &str = "4";
+
+pub const SOURCE_SYSTEM: &str = "system";
+pub const SOURCE_MANUAL: &str = "manual";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// What the user calls the place, e.g. "Home".
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// How far off a system fix may be; `None` for coordinates set by hand.
+    pub accuracy_meters: Option<f64>,
+    pub label: Option<String>,
+    /// `system` or `manual`.
+    pub source: String,
+    pub located_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LocationSettings {
+    /// Whether the user allowed asking the system where they are.
+    pub consent: bool,
+    /// Coordinates set by hand, used instead of the system's.
+    pub manual: Option<Coordinates>,
+    /// The last fix the system gave.
+    pub last_fix: Option<Location>,
+}
+
+fn load_settings(conn: &Connection) -> rusqlite::Result<LocationSettings> {
+    conn.query_row(
+        "SELECT consent, manual_latitude, manual_longitude, manual_label, latitude, longitude,
+                accuracy_meters, located_at
+         FROM location_settings WHERE id = 1",
+        [],
+        |row| {
+            let manual = match (row.get(1)?, row.get(2)?) {
+                (Some(latitude), Some(longitude)) => Some(Coordinates {
+                    latitude,
+                    longitude,
+                    label: row.get(3)?,
+                }),
+                _ => None,
+            };
+            let last_fix = match (row.get(4)?, row.get(5)?, row.get(7)?) {
+                (Some(latitude), Some(longitude), Some(located_at)) => Some(Location {
+                    latitude,
+                    longitude,
+                    accuracy_meters: row.get(6)?,
+                    label: None,
+                    source: SOURCE_SYSTEM.to_string(),
+                    located_at,
+                }),
+                _ => None,
+            };
+            Ok(LocationSettings {
+                consent: row.get(0)?,
+                manual,
+                last_fix,
+            })
+        },
+    )
+}
+
+fn validate(latitude: f64, longitude: f64) -> CommandResult<()> {
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(CommandError::invalid(format!(
+            "Latitude must be between -90 and 90: {latitude}"
+        )));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(CommandError::invalid(format!(
+            "Longitude must be between -180 and 180: {longitude}"
+        )));
+    }
+    Ok(())
+}
+
+/// The number after `label:` in one line of a locator's output, as in
+/// `Latitude:    51.5000°` or `Accuracy:    25000.000000 meters`.
+fn field(line: &str, label: &str) -> Option<f64> {
+    let value = line.trim().strip_prefix(label)?.strip_prefix(':')?;
+    value
+        .split_whitespace()
+        .next()?
+        .trim_end_matches('°')
+        .parse()
+        .ok()
+}
+
+/// The first fix in a locator's output, as (latitude, longitude,
+/// accuracy).
+fn read_fix(lines: impl Iterator<Item = String>) -> Option<(f64, f64, Option<f64>)> {
+    let (mut latitude, mut longitude) = (None, None);
+    for line in lines {
+        if let Some(value) = field(&line, "Latitude") {
+            latitude = Some(value);
+        } else if let Some(value) = field(&line, "Longitude") {
+            longitude = Some(value);
+        } else if let Some(accuracy) = field(&line, "Accuracy") {
+            return Some((latitude?, longitude?, Some(accuracy)));
+        }
+    }
+    Some((latitude?, longitude?, None))
+}
+
+/// Run a locator and read its first fix, stopping it once it has one.
+fn run_locator(mut command: Command) -> CommandResult<(f64, f64, Option<f64>)> {
+    let mut child = command
+        .env("LC_ALL", "C")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to ask the system for the location")?;
+    let stdout = child.stdout.take().ok_or("No output from the locator")?;
+    let fix = read_fix(BufReader::new(stdout).lines().map_while(Result::ok));
+    let _ = child.kill();
+    let _ = child.wait();
+    fix.ok_or_else(|| "The system couldn't work out where you are".into())
+}
+
+/// Ask GeoClue, through its demo agent. GeoClue checks the desktop's
+/// location permission before it answers.
+fn geoclue_fix() -> CommandResult<(f64, f64, Option<f64>)> {
+    let agent = WHERE_AM_I
+        .iter()
+        .find(|path| std::path::Path::new(path).exists())
+        .ok_or("GeoClue isn't installed; set your location by hand")?;
+    let mut command = Command::new(agent);
+    command.args([
+        "--timeout",
+        &LOCATE_TIMEOUT_SECONDS.to_string(),
+        "--accuracy-level",
+        GEOCLUE_ACCURACY_CITY,
+    ]);
+    run_locator(command)
+}
+
+/// Ask Windows' location service, through PowerShell.
+fn windows_fix() -> CommandResult<(f64, f64, Option<f64>)> {
+    let script = format!(
+        "Add-Type -AssemblyName System.Device; \
+         $w = New-Object System.Device.Location.GeoCoordinateWatcher; \
+         if (-not $w.TryStart($false, [TimeSpan]::FromSeconds({LOCATE_TIMEOUT_SECONDS}))) {{ exit 1 }}; \
+         $i = 0; \
+         while ($w.Position.Location.IsUnknown -and $i -lt {}) {{ Start-Sleep -Milliseconds 100; $i++ }}; \
+         $l = $w.Position.Location; \
+         if ($l.IsUnknown) {{ exit 1 }}; \
+         $c = [Globalization.CultureInfo]::InvariantCulture; \
+         'Latitude: ' + $l.Latitude.ToString($c); \
+         'Longitude: ' + $l.Longitude.ToString($c); \
+         'Accuracy: ' + $l.HorizontalAccuracy.ToString($c)",
+        LOCATE_TIMEOUT_SECONDS * 10
+    );
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+    run_locator(command)
+}
+
+fn system_fix() -> CommandResult<(f64, f64, Option<f64>)> {
+    if cfg!(target_os = "linux") {
+        geoclue_fix()
+    } else if cfg!(target_os = "windows") {
+        windows_fix()
+    } else {
+        Err("Finding the location isn't supported on this platform; set it by hand".into())
+    }
+}
+
+/// Where the user is: the coordinates set by hand, else a recent fix from
+/// the system, asking it again once the last is too old. The system is
+/// only asked with the user's consent; when it can't say, the last fix
+/// stands in.
+pub async fn current(db: &Db) -> CommandResult<Location> {
+    let settings = db.with_conn(|conn| load_settings(conn))?;
+    if let Some(manual) = settings.manual {
+        return Ok(Location {
+            latitude: manual.latitude,
+            longitude: manual.longitude,
+            accuracy_meters: None,
+            label: manual.label,
+            source: SOURCE_MANUAL.to_string(),
+            located_at: now_utc(),
+        });
+    }
+    if !settings.consent {
+        return Err("Allow DayLight to use your location, or set it by hand".into());
+    }
+    let fresh = settings.last_fix.as_ref().filter(|fix| {
+        parse_utc(&fix.located_at)
+            .is_ok_and(|at| Utc::now() - at < Duration::minutes(MAX_FIX_AGE_MINUTES))
+    });
+    if let Some(fix) = fresh {
+        return Ok(fix.clone());
+    }
+    let found = tauri::async_runtime::spawn_blocking(system_fix)
+        .await
+        .map_err(|e| format!("Location lookup failed: {e}"))?;
+    let (latitude, longitude, accuracy_meters) = match found {
+        Ok(fix) => fix,
+        Err(e) => {
+            tracing::warn!("{e}");
+            return settings.last_fix.ok_or(e);
+        }
+    };
+    validate(latitude, longitude)?;
+    let located_at = now_utc();
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE location_settings
+             SET latitude = ?1, longitude = ?2, accuracy_meters = ?3, located_at = ?4
+             WHERE id = 1",
+            params![latitude, longitude, accuracy_meters, located_at],
+        )
+    })?;
+    Ok(Location {
+        latitude,
+        longitude,
+        accuracy_meters,
+        label: None,
+        source: SOURCE_SYSTEM.to_string(),
+        located_at,
+    })
+}
+
+#[tauri::command]
+pub fn get_location_settings(db: State<'_, Db>) -> CommandResult<LocationSettings> {
+    db.with_conn(|conn| load_settings(conn))
+}
+
+/// Allow or stop asking the system where the user is. Taking consent back
+/// forgets the last fix.
+#[tauri::command]
+pub fn set_location_consent(db: State<'_, Db>, granted: bool) -> CommandResult<LocationSettings> {
+    db.with_conn(|conn| {
+        if granted {
+            conn.execute("UPDATE location_settings SET consent = 1 WHERE id = 1", [])?;
+        } else {
+            conn.execute(
+                "UPDATE location_settings
+                 SET consent = 0, latitude = NULL, longitude = NULL, accuracy_meters = NULL,
+                     located_at = NULL
+                 WHERE id = 1",
+                [],
+            )?;
+        }
+        load_settings(conn)
+    })
+}
+
+/// Set the coordinates to use instead of the system's, or go back to the
+/// system's with `None`.
+#[tauri::command]
+pub fn set_manual_location(
+    db: State<'_, Db>,
+    coordinates: Option<Coordinates>,
+) -> CommandResult<LocationSettings> {
+    if let Some(c) = &coordinates {
+        validate(c.latitude, c.longitude)?;
+    }
+    let label = coordinates
+        .as_ref()
+        .and_then(|c| c.label.as_deref())
+        .map(str::trim)
+        .filter(|l| !l.is_empty());
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE location_settings
+             SET manual_latitude = ?1, manual_longitude = ?2, manual_label = ?3
+             WHERE id = 1",
+            params![
+                coordinates.as_ref().map(|c| c.latitude),
+                coordinates.as_ref().map(|c| c.longitude),
+                label
+            ],
+        )?;
+        load_settings(conn)
+    })
+}
+
+/// Where the user is, as `current` works it out.
+#[tauri::command]
+pub async fn get_location(db: State<'_, Db>) -> CommandResult<Location> {
+    current(&db).await
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{Builder, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+use crate::data_dir;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::session::write_atomic;
+
+const CONFIG_FILE: &str = "logging.json";
+/// Log files are `daylight.YYYY-MM-DD.log`, one a day.
+const FILE_PREFIX: &str = "daylight";
+const FILE_SUFFIX: &str = "log";
+/// Days of logs kept; older files are deleted as new ones start.
+const MAX_LOG_FILES: usize = 7;
+/// Overrides the configured levels, in `EnvFilter` syntax, e.g.
+/// `DAYLIGHT_LOG=debug` or `DAYLIGHT_LOG=info,daylight_lib::caldav=trace`.
+const ENV_FILTER: &str = "DAYLIGHT_LOG";
+/// Target the webview's messages are logged under; set its level with the
+/// module name `frontend`.
+const FRONTEND_TARGET: &str = concat!(env!("CARGO_CRATE_NAME"), "::frontend");
+/// Longest message taken from the webview; the rest is cut off.
+const MAX_FRONTEND_MESSAGE: usize = 16 * 1024;
+const DEFAULT_RECENT_LINES: usize = 500;
+const MAX_RECENT_LINES: usize = 20_000;
+
+/// Flushes the file writer when dropped, so it lives as long as the app.
+static GUARD: OnceLock<WorkerGuard> = OnceLock::new();
+/// Swaps the level filter when the config changes.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Level for everything not listed in `modules`: `error`, `warn`,
+    /// `info`, `debug`, `trace` or `off`.
+    pub level: String,
+    /// Levels for single modules, by name (`caldav`) or full target
+    /// (`reqwest::connect`).
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentLogs {
+    pub log_dir: String,
+    /// The last lines logged, oldest first, across files.
+    pub text: String,
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+pub fn log_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    app.path()
+        .app_log_dir()
+        .map_err(|e| format!("No log directory: {e}").into())
+}
+
+pub fn load_config(app: &AppHandle) -> LogConfig {
+    let Ok(path) = config_path(app) else {
+        return LogConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            LogConfig::default()
+        }),
+        Err(_) => LogConfig::default(),
+    }
+}
+
+fn save_config(app: &AppHandle, config: &LogConfig) -> CommandResult<()> {
+    let path = config_path(app)?;
+    let body = serde_json::to_vec_pretty(config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)
+}
+
+fn level(value: &str) -> CommandResult<LevelFilter> {
+    LevelFilter::from_str(value.trim())
+        .map_err(|_| CommandError::invalid(format!("Unknown log level: {value}")))
+}
+
+/// The filter `config` describes. A bare module name is taken to be one
+/// of the app's own.
+fn filter(config: &LogConfig) -> CommandResult<EnvFilter> {
+    let mut directives = vec![level(&config.level)?.to_string()];
+    for (module, value) in &config.modules {
+        let module = module.trim();
+        if module.is_empty() || module.contains([',', '=', ' ']) {
+            return Err(CommandError::invalid(format!(
+                "Invalid module name: '{module}'"
+            )));
+        }
+        let target = if module.contains("::") {
+            module.to_string()
+        } else {
+            format!("{}::{module}", env!("CARGO_CRATE_NAME"))
+        };
+        directives.push(format!("{target}={}", level(value)?));
+    }
+    EnvFilter::try_new(directives.join(",")).map_err(|e| CommandError::invalid(e.to_string()))
+}
+
+/// Send `tracing` events to stderr and to daily log files under the app log
+/// dir, at the levels in the config or `DAYLIGHT_LOG`.
+pub fn init(app: &AppHandle) {
+    let env = std::env::var(ENV_FILTER).ok().and_then(|value| {
+        EnvFilter::try_new(&value)
+            .map_err(|e| eprintln!("[daylight] logging: ignoring {ENV_FILTER}: {e}"))
+            .ok()
+    });
+    let config = load_config(app);
+    let initial = env.unwrap_or_else(|| {
+        filter(&config).unwrap_or_else(|e| {
+            eprintln!("[daylight] logging: ignoring config: {e}");
+            EnvFilter::new(LevelFilter::INFO.to_string())
+        })
+    });
+    let (filter_layer, handle) = reload::Layer::new(initial);
+
+    let file = log_dir(app).and_then(|dir| {
+        Builder::new()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(FILE_PREFIX)
+            .filename_suffix(FILE_SUFFIX)
+            .max_log_files(MAX_LOG_FILES)
+            .build(&dir)
+            .map_err(|e| format!("Can't write logs to {}: {e}", dir.display()).into())
+    });
+    let file_layer = match file {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let _ = GUARD.set(guard);
+            Some(fmt::layer().with_writer(writer))
+        }
+        Err(e) => {
+            eprintln!("[daylight] logging: {e}");
+            None
+        }
+    };
+
+    let installed = tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(file_layer)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .try_init();
+    match installed {
+        Ok(()) => {
+            let _ = FILTER.set(handle);
+        }
+        Err(e) => eprintln!("[daylight] logging: {e}"),
+    }
+}
+
+#[tauri::command]
+pub fn get_log_config(app: AppHandle) -> LogConfig {
+    load_config(&app)
+}
+
+/// Save `config` and apply its levels straight away. `DAYLIGHT_LOG`, when
+/// set, still wins until the next start without it.
+fn apply(app: &AppHandle, config: &LogConfig) -> CommandResult<()> {
+    let new_filter = filter(config)?;
+    save_config(app, config)?;
+    if std::env::var_os(ENV_FILTER).is_none() {
+        if let Some(handle) = FILTER.get() {
+            handle.reload(new_filter).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Save new log levels and apply them straight away.
+#[tauri::command]
+pub fn set_log_config(app: AppHandle, config: LogConfig) -> CommandResult<LogConfig> {
+    apply(&app, &config)?;
+    Ok(config)
+}
+
+/// Change one module's level while the app runs, or the default level
+/// when `module` is omitted. A `level` of `None` puts the module back on
+/// the default.
+#[tauri::command]
+pub fn set_log_level(
+    app: AppHandle,
+    module: Option<String>,
+    level: Option<String>,
+) -> CommandResult<LogConfig> {
+    let mut config = load_config(&app);
+    match (module.as_deref().map(str::trim), level) {
+        (None | Some(""), Some(level)) => config.level = level,
+        (None | Some(""), None) => {
+            return Err(CommandError::invalid("The default level can't be removed"))
+        }
+        (Some(module), Some(level)) => {
+            config.modules.insert(module.to_string(), level);
+        }
+        (Some(module), None) => {
+            config.modules.remove(module);
+        }
+    }
+    apply(&app, &config)?;
+    Ok(config)
+}
+
+/// Log a message from the webview, such as an uncaught error, into the
+/// same files as the backend's, under the `frontend` module.
+#[tauri::command]
+pub fn log_from_frontend(level: String, message: String) -> CommandResult<()> {
+    let mut message = message;
+    if message.len() > MAX_FRONTEND_MESSAGE {
+        let mut end = MAX_FRONTEND_MESSAGE;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+        message.push_str(" [truncated]");
+    }
+    match level.trim().to_ascii_lowercase().as_str() {
+        "error" => tracing::error!(target: FRONTEND_TARGET, "{message}"),
+        "warn" | "warning" => tracing::warn!(target: FRONTEND_TARGET, "{message}"),
+        "info" | "log" => tracing::info!(target: FRONTEND_TARGET, "{message}"),
+        "debug" => tracing::debug!(target: FRONTEND_TARGET, "{message}"),
+        "trace" => tracing::trace!(target: FRONTEND_TARGET, "{message}"),
+        other => return Err(CommandError::invalid(format!("Unknown log level: {other}"))),
+    }
+    Ok(())
+}
+
+/// The last `lines` lines logged, across the rotated files.
+pub fn recent_logs(app: &AppHandle, lines: Option<usize>) -> CommandResult<RecentLogs> {
+    let wanted = lines
+        .unwrap_or(DEFAULT_RECENT_LINES)
+        .clamp(1, MAX_RECENT_LINES);
+    let dir = log_dir(app)?;
+    let mut files: Vec<PathBuf> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX)
+                    })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    // Dated names sort oldest first.
+    files.sort();
+
+    let mut recent: Vec<String> = Vec::new();
+    for path in files.iter().rev() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut earlier: Vec<String> = content.lines().map(str::to_string).collect();
+        let keep = earlier.len().saturating_sub(wanted - recent.len());
+        earlier.drain(..keep);
+        earlier.append(&mut recent);
+        recent = earlier;
+        if recent.len() >= wanted {
+            break;
+        }
+    }
+    Ok(RecentLogs {
+        log_dir: dir.to_string_lossy().into_owned(),
+        text: recent.join("\n"),
+    })
+}
+
+/// The last `lines` lines logged (500 by default), to attach to a bug
+/// report.
+#[tauri::command]
+pub fn get_recent_logs(app: AppHandle, lines: Option<usize>) -> CommandResult<RecentLogs> {
+    recent_logs(&app, lines)
+}
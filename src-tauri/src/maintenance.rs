@@ -0,0 +1,140 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db::Db;
+use crate::error::CommandResult;
+use crate::notes;
+use crate::search;
+
+/// Emitted as each maintenance step starts.
+pub const MAINTENANCE_PROGRESS_EVENT: &str = "maintenance-progress";
+
+const STEPS: [&str; 4] = [
+    "Checking integrity",
+    "Rebuilding search index",
+    "Compacting database",
+    "Counting rows",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceProgress {
+    /// 1-based.
+    pub step: usize,
+    pub total: usize,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableCount {
+    pub table: String,
+    pub rows: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceReport {
+    /// Whether `PRAGMA integrity_check` came back clean. When it didn't,
+    /// nothing was rewritten; restoring a backup is the safer fix.
+    pub integrity_ok: bool,
+    /// What the check reported: just `ok`, or the problems it found.
+    pub integrity_messages: Vec<String>,
+    pub size_before_bytes: i64,
+    pub size_after_bytes: i64,
+    pub tables: Vec<TableCount>,
+}
+
+fn database_size(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+}
+
+pub fn integrity_check(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    rows.collect()
+}
+
+/// Row counts of the app's tables, leaving out SQLite's own and the search
+/// index's internals.
+pub fn table_counts(conn: &Connection) -> rusqlite::Result<Vec<TableCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'search_index_%'
+         ORDER BY name",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tables
+        .into_iter()
+        .map(|table| {
+            let rows = conn.query_row(
+                &format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")),
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(TableCount { table, rows })
+        })
+        .collect()
+}
+
+fn run(app: &AppHandle) -> CommandResult<MaintenanceReport> {
+    let db = app.state::<Db>();
+    let progress = |step: usize| {
+        let _ = app.emit(
+            MAINTENANCE_PROGRESS_EVENT,
+            MaintenanceProgress {
+                step: step + 1,
+                total: STEPS.len(),
+                label: STEPS[step].to_string(),
+            },
+        );
+    };
+
+    progress(0);
+    let (size_before_bytes, integrity_messages) =
+        db.with_conn(|conn| Ok((database_size(conn)?, integrity_check(conn)?)))?;
+    let integrity_ok = integrity_messages.len() == 1 && integrity_messages[0] == "ok";
+
+    if integrity_ok {
+        progress(1);
+        let dir = notes::notes_dir(app)?;
+        db.with_conn(|conn| {
+            search::rebuild_index(conn)?;
+            if let Err(e) = notes::reindex_all(conn, &dir) {
+                return Ok(Err(e));
+            }
+            conn.execute_batch("INSERT INTO search_index (search_index) VALUES ('optimize');")?;
+            Ok(Ok(()))
+        })??;
+
+        progress(2);
+        db.with_conn(|conn| conn.execute_batch("REINDEX; VACUUM; PRAGMA optimize;"))?;
+    } else {
+        tracing::warn!("integrity check failed: {}", integrity_messages.join("; "));
+    }
+
+    progress(3);
+    let (size_after_bytes, tables) =
+        db.with_conn(|conn| Ok((database_size(conn)?, table_counts(conn)?)))?;
+    Ok(MaintenanceReport {
+        integrity_ok,
+        integrity_messages,
+        size_before_bytes,
+        size_after_bytes,
+        tables,
+    })
+}
+
+/// Check the database for corruption, rebuild the search index, compact the
+/// file and count what's in it. Runs off the main thread; progress arrives
+/// as `maintenance-progress` events.
+#[tauri::command]
+pub async fn run_maintenance(app: AppHandle) -> CommandResult<MaintenanceReport> {
+    tauri::async_runtime::spawn_blocking(move || run(&app))
+        .await
+        .map_err(|e| format!("Maintenance failed: {e}"))?
+}
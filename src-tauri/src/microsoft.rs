@@ -0,0 +1,518 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Method, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use url::Url;
+
+use crate::account_health::AccountHealth;
+use crate::calendars::{self, Calendar};
+use crate::credentials;
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::http;
+use crate::microsoft_calendar;
+use crate::microsoft_todo::{self, MicrosoftTaskList};
+use crate::rate_limit;
+use crate::timezone;
+
+const LOGIN_URL: &str = "https://login.microsoftonline.com";
+pub const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0";
+/// To Do lists and tasks, calendars and their events, and a refresh token
+/// to keep syncing.
+const SCOPES: &str = "offline_access Tasks.ReadWrite Calendars.ReadWrite";
+/// Accounts from any organization, and personal ones.
+const DEFAULT_TENANT: &str = "common";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Tries per request when Graph throttles (429) or is briefly unavailable.
+const MAX_ATTEMPTS: u32 = 4;
+/// Longest `Retry-After` honored before giving up on a request.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(120);
+
+/// A Microsoft work, school or personal account.
+#[derive(Debug, Clone, Serialize)]
+pub struct MicrosoftAccount {
+    pub id: String,
+    pub name: String,
+    pub client_id: String,
+    pub tenant: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub lists: Vec<MicrosoftTaskList>,
+    pub calendars: Vec<Calendar>,
+}
+
+/// The result of the sign-in page: pass the code from `await_oauth_code`
+/// with the redirect URI it was requested for.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewMicrosoftAccount {
+    /// Defaults to "Microsoft".
+    #[serde(default)]
+    pub name: Option<String>,
+    pub client_id: String,
+    /// Directory to sign in to; defaults to "common".
+    #[serde(default)]
+    pub tenant: Option<String>,
+    pub code: String,
+    pub redirect_uri: String,
+}
+
+/// One page of a collection. The last page of a delta query carries the
+/// link for the next one instead of a next page.
+#[derive(Debug, Deserialize)]
+pub struct Page<T> {
+    #[serde(default = "Vec::new")]
+    pub value: Vec<T>,
+    #[serde(default, rename = "@odata.nextLink")]
+    pub next_link: Option<String>,
+    #[serde(default, rename = "@odata.deltaLink")]
+    pub delta_link: Option<String>,
+}
+
+/// A date and time with the zone it's in. Graph is asked for UTC, but a
+/// task's due date keeps the zone it was set in, which Exchange may name
+/// the Windows way.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DateTimeZone {
+    pub date_time: String,
+    #[serde(default)]
+    pub time_zone: Option<String>,
+}
+
+impl DateTimeZone {
+    pub fn date(&self) -> Option<String> {
+        self.date_time.get(..10).map(str::to_string)
+    }
+
+    /// The instant, read in its zone when that's known and as UTC
+    /// otherwise.
+    pub fn instant(&self) -> Option<DateTime<Utc>> {
+        let local = NaiveDateTime::parse_from_str(&self.date_time, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+        match self
+            .time_zone
+            .as_deref()
+            .and_then(|zone| timezone::parse_any_zone(zone).ok())
+        {
+            Some(tz) => timezone::resolve_local(local, &tz).map(|dt| dt.with_timezone(&Utc)),
+            None => Some(local.and_utc()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    /// The scopes granted, space-separated.
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+fn normalize_tenant(tenant: Option<&str>) -> String {
+    tenant
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or(DEFAULT_TENANT)
+        .to_string()
+}
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<MicrosoftAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, client_id, tenant, created_at, updated_at
+         FROM microsoft_accounts ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(MicrosoftAccount {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            client_id: row.get(2)?,
+            tenant: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+            lists: Vec::new(),
+            calendars: Vec::new(),
+        })
+    })?;
+    let mut accounts = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    for account in &mut accounts {
+        account.lists = microsoft_todo::list_task_lists(conn, &account.id)?;
+        account.calendars = calendars::list_microsoft(conn, &account.id)?;
+    }
+    Ok(accounts)
+}
+
+/// The app registration and directory an account signs in with, as
+/// (client id, tenant).
+fn client_settings(
+    conn: &Connection,
+    account_id: &str,
+) -> rusqlite::Result<Option<(String, String)>> {
+    conn.query_row(
+        "SELECT client_id, tenant FROM microsoft_accounts WHERE id = ?1",
+        params![account_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("microsoft:{account_id}")
+}
+
+fn client() -> CommandResult<Client> {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(CommandError::from)
+}
+
+/// POST to the tenant's token endpoint with `grant`'s fields plus the
+/// client's.
+async fn request_token(
+    client: &Client,
+    client_id: &str,
+    tenant: &str,
+    grant: &[(&str, &str)],
+) -> CommandResult<TokenResponse> {
+    let mut form: Vec<(&str, &str)> = vec![("client_id", client_id), ("scope", SCOPES)];
+    form.extend_from_slice(grant);
+    let response = client
+        .post(format!("{LOGIN_URL}/{tenant}/oauth2/v2.0/token"))
+        .form(&form)
+        .send()
+        .await
+        .context("Microsoft sign-in")?;
+    match response.status() {
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(CommandError::unauthorized(
+            "Microsoft refused the sign-in; connect the account again",
+        )),
+        status if !status.is_success() => Err(CommandError::status(
+            status,
+            format!("Microsoft sign-in: HTTP {}", status.as_u16()),
+        )),
+        _ => http::read_json(response).await.context("Microsoft sign-in"),
+    }
+}
+
+/// How long to wait before trying a throttled request again: what the
+/// server asks for, else doubling from two seconds.
+/// An authorized connection to Microsoft Graph for one account.
+pub struct Api {
+    client: Client,
+    access_token: String,
+    /// The provider paused when Graph throttles the requests.
+    provider: &'static str,
+}
+
+impl Api {
+    /// Trade the account's saved refresh token for an access token, for
+    /// requests made by `provider`. The refresh token is replaced when
+    /// Microsoft hands out a new one.
+    pub async fn connect(db: &Db, account_id: &str, provider: &'static str) -> CommandResult<Self> {
+        let (client_id, tenant) = db
+            .with_conn(|conn| client_settings(conn, account_id))?
+            .ok_or_else(|| {
+                CommandError::not_found(format!("Microsoft account not found: {account_id}"))
+            })?;
+        let refresh_token = credentials::require(&keyring_name(account_id), "sign-in")?;
+        let client = client()?;
+        let token = request_token(
+            &client,
+            &client_id,
+            &tenant,
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &refresh_token),
+            ],
+        )
+        .await?;
+        if let Some(rotated) = token.refresh_token.as_deref() {
+            if rotated != refresh_token {
+                credentials::save(&keyring_name(account_id), Some(rotated))?;
+            }
+        }
+        Ok(Self {
+            client,
+            access_token: token.access_token,
+            provider,
+        })
+    }
+
+    /// Sign in as for `connect`, noting in `health` how it went and which
+    /// scopes are missing. `None` when the sign-in failed.
+    pub async fn check(
+        db: &Db,
+        account_id: &str,
+        provider: &'static str,
+        health: &mut AccountHealth,
+    ) -> CommandResult<Option<Self>> {
+        let (client_id, tenant) = db
+            .with_conn(|conn| client_settings(conn, account_id))?
+            .ok_or_else(|| {
+                CommandError::not_found(format!("Microsoft account not found: {account_id}"))
+            })?;
+        let refresh_token = match credentials::require(&keyring_name(account_id), "sign-in") {
+            Ok(token) => token,
+            Err(e) => {
+                health.no_credentials(&e);
+                return Ok(None);
+            }
+        };
+        let client = client()?;
+        let response = client
+            .post(format!("{LOGIN_URL}/{tenant}/oauth2/v2.0/token"))
+            .form(&[
+                ("client_id", client_id.as_str()),
+                ("scope", SCOPES),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &refresh_token),
+            ])
+            .send()
+            .await;
+        let Some(response) = health.answer_sign_in("Microsoft", response) else {
+            return Ok(None);
+        };
+        let token: TokenResponse = http::read_json(response)
+            .await
+            .context("Microsoft sign-in")?;
+        if let Some(rotated) = token.refresh_token.as_deref() {
+            if rotated != refresh_token {
+                credentials::save(&keyring_name(account_id), Some(rotated))?;
+            }
+        }
+        if let Some(granted) = &token.scope {
+            // Graph names granted scopes in full, as URLs.
+            let granted = granted.replace("https://graph.microsoft.com/", "");
+            health.scopes(
+                &granted,
+                &[
+                    ("Tasks.ReadWrite", "tasks"),
+                    ("Calendars.ReadWrite", "calendars"),
+                ],
+            );
+        }
+        Ok(Some(Self {
+            client,
+            access_token: token.access_token,
+            provider,
+        }))
+    }
+
+    /// GET `url` with the raw result, for the account health check.
+    pub async fn probe(&self, url: &str) -> Result<Response, reqwest::Error> {
+        self.client
+            .get(url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+    }
+
+    /// Send a request to `url`, waiting and trying again while Graph
+    /// throttles it. Returns the response body, or `None` for 204, and for
+    /// 404 and 410 (gone already, or an expired delta link). Times come
+    /// back in UTC.
+    pub async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        rate_limit::check(self.provider, "Microsoft")?;
+        let parsed = Url::parse(url).map_err(|e| e.to_string())?;
+        let mut attempt = 1;
+        loop {
+            let mut request = self
+                .client
+                .request(method.clone(), parsed.clone())
+                .bearer_auth(&self.access_token)
+                .header("Prefer", "outlook.timezone=\"UTC\"");
+            if let Some(body) = body {
+                request = request
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.to_string());
+            }
+            let response = request.send().await.context("Microsoft")?;
+            match response.status() {
+                StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+                    if attempt < MAX_ATTEMPTS =>
+                {
+                    let wait = rate_limit::retry_wait(response.headers(), attempt);
+                    if wait > MAX_RETRY_WAIT {
+                        return Err(rate_limit::limited(
+                            self.provider,
+                            "Microsoft",
+                            response.headers(),
+                        ));
+                    }
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                StatusCode::TOO_MANY_REQUESTS => {
+                    return Err(rate_limit::limited(
+                        self.provider,
+                        "Microsoft",
+                        response.headers(),
+                    ))
+                }
+                StatusCode::NO_CONTENT | StatusCode::NOT_FOUND | StatusCode::GONE => {
+                    return Ok(None)
+                }
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    return Err(CommandError::unauthorized(
+                        "Microsoft refused access; connect the account again",
+                    )
+                    .with_provider(self.provider))
+                }
+                status if !status.is_success() => {
+                    return Err(CommandError::status(
+                        status,
+                        format!("{method} {url}: HTTP {}", status.as_u16()),
+                    )
+                    .with_provider(self.provider))
+                }
+                _ => {
+                    return http::read_json(response)
+                        .await
+                        .map(Some)
+                        .context("Microsoft")
+                }
+            }
+        }
+    }
+
+    /// Every page of a collection.
+    pub async fn all_pages<T: DeserializeOwned>(&self, url: &str) -> CommandResult<Vec<T>> {
+        let mut items = Vec::new();
+        let mut next = url.to_string();
+        loop {
+            let page: Option<Page<T>> = self.send(Method::GET, &next, None).await?;
+            let Some(page) = page else {
+                return Err(CommandError::not_found(format!("GET {url}: not found")));
+            };
+            items.extend(page.value);
+            match page.next_link {
+                Some(link) => next = link,
+                None => return Ok(items),
+            }
+        }
+    }
+}
+
+/// The sign-in page URL for the OAuth loopback flow: start the listener
+/// with `start_oauth_listener`, open this, then pass the code from
+/// `await_oauth_code` to `connect_microsoft_account`.
+#[tauri::command]
+pub fn microsoft_auth_url(
+    client_id: String,
+    tenant: Option<String>,
+    redirect_uri: String,
+) -> CommandResult<String> {
+    let tenant = normalize_tenant(tenant.as_deref());
+    let mut url = Url::parse(&format!("{LOGIN_URL}/{tenant}/oauth2/v2.0/authorize"))
+        .map_err(|e| e.to_string())?;
+    url.query_pairs_mut()
+        .append_pair("client_id", &client_id)
+        .append_pair("redirect_uri", &redirect_uri)
+        .append_pair("response_type", "code")
+        .append_pair("response_mode", "query")
+        .append_pair("prompt", "select_account")
+        .append_pair("scope", SCOPES);
+    Ok(url.to_string())
+}
+
+/// Finish signing in to Microsoft and save the account with its To Do
+/// lists and calendars. Nothing syncs until it's enabled.
+#[tauri::command]
+pub async fn connect_microsoft_account(
+    db: State<'_, Db>,
+    input: NewMicrosoftAccount,
+) -> CommandResult<MicrosoftAccount> {
+    let client_id = input.client_id.trim().to_string();
+    if client_id.is_empty() {
+        return Err(CommandError::invalid("Enter the application (client) ID"));
+    }
+    let tenant = normalize_tenant(input.tenant.as_deref());
+    let http = client()?;
+    let token = request_token(
+        &http,
+        &client_id,
+        &tenant,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &input.code),
+            ("redirect_uri", &input.redirect_uri),
+        ],
+    )
+    .await?;
+    let refresh_token = token.refresh_token.ok_or_else(|| {
+        CommandError::unauthorized("Microsoft didn't grant offline access; try connecting again")
+    })?;
+    let api = Api {
+        client: http,
+        access_token: token.access_token,
+        provider: microsoft_todo::SOURCE,
+    };
+    let lists = microsoft_todo::task_lists(&api).await?;
+    let found = microsoft_calendar::calendars(&api).await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Microsoft".to_string());
+    credentials::save(&keyring_name(&id), Some(&refresh_token))?;
+    let stored = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO microsoft_accounts (id, name, client_id, tenant, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![id, name, client_id, tenant, now],
+        )?;
+        microsoft_todo::store_task_lists(&tx, &id, &lists)?;
+        microsoft_calendar::store_calendars(&tx, &id, &found)?;
+        tx.commit()?;
+        list_accounts(conn)
+    });
+    let accounts = match stored {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            return Err(e);
+        }
+    };
+    Ok(accounts
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| "Failed to save account".to_string())?)
+}
+
+#[tauri::command]
+pub fn list_microsoft_accounts(db: State<'_, Db>) -> CommandResult<Vec<MicrosoftAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Forget an account, its calendars and its sync state. Its tasks stay,
+/// unlinked; events pushed to its calendars stay in Outlook.
+#[tauri::command]
+pub fn remove_microsoft_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| {
+        conn.execute("DELETE FROM microsoft_accounts WHERE id = ?1", params![id])
+    })?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Microsoft account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
@@ -0,0 +1,320 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use reqwest::Method;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::json;
+use tauri::State;
+use url::Url;
+
+use crate::calendars::{self, BlockRow, Calendar, Fetched};
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult};
+use crate::ics::IcsEvent;
+use crate::microsoft::{Api, DateTimeZone, Page, GRAPH_URL};
+use crate::timezone;
+
+/// The provider paused when Graph throttles calendar requests.
+pub const SOURCE: &str = "microsoft_calendar";
+/// How Graph wants the date and time of an event it's sent.
+const GRAPH_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Deserialize)]
+struct RemoteCalendar {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    #[serde(default)]
+    display_name: Option<String>,
+}
+
+/// An event, or an occurrence of a recurring one, as the calendar view
+/// returns it. Removed ones carry only their id and `@removed`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteEvent {
+    id: String,
+    #[serde(default)]
+    subject: Option<String>,
+    #[serde(default)]
+    location: Option<Location>,
+    #[serde(default)]
+    start: Option<DateTimeZone>,
+    #[serde(default)]
+    end: Option<DateTimeZone>,
+    #[serde(default)]
+    is_all_day: bool,
+    #[serde(default)]
+    is_cancelled: bool,
+    #[serde(default)]
+    show_as: Option<String>,
+    #[serde(default, rename = "@removed")]
+    removed: Option<serde_json::Value>,
+}
+
+impl RemoteEvent {
+    /// The event to cache, or `None` when it was removed or cancelled.
+    fn to_event(&self, local: &Tz) -> Option<IcsEvent> {
+        if self.removed.is_some() || self.is_cancelled {
+            return None;
+        }
+        let instant = |time: &DateTimeZone| {
+            if self.is_all_day {
+                // All-day events run midnight to midnight on their dates,
+                // wherever the user is.
+                let date = NaiveDate::parse_from_str(&time.date()?, "%Y-%m-%d").ok()?;
+                timezone::resolve_local(date.and_time(NaiveTime::MIN), local)
+                    .map(|dt| dt.with_timezone(&Utc))
+            } else {
+                time.instant()
+            }
+        };
+        let starts_at = instant(self.start.as_ref()?)?;
+        let ends_at = self
+            .end
+            .as_ref()
+            .and_then(instant)
+            .filter(|end| *end >= starts_at)
+            .unwrap_or(starts_at);
+        let title = self
+            .subject
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .unwrap_or("Busy");
+        let location = self
+            .location
+            .as_ref()
+            .and_then(|l| l.display_name.clone())
+            .filter(|l| !l.trim().is_empty());
+        Some(IcsEvent {
+            uid: self.id.clone(),
+            title: title.to_string(),
+            location,
+            starts_at,
+            ends_at,
+            all_day: self.is_all_day,
+            busy: !self.is_all_day && self.show_as.as_deref() != Some("free"),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SavedEvent {
+    id: String,
+}
+
+/// `GRAPH_URL` with `segments` appended, each escaped.
+fn graph_url(segments: &[&str]) -> CommandResult<Url> {
+    let mut url = Url::parse(GRAPH_URL).map_err(|e| e.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| "Invalid Graph URL".to_string())?
+        .extend(segments);
+    Ok(url)
+}
+
+/// Every calendar of the account, as (url, name). The URL is the
+/// calendar's Graph URL, which is also its `calendars.url`.
+pub async fn calendars(api: &Api) -> CommandResult<Vec<(String, String)>> {
+    let found: Vec<RemoteCalendar> = api.all_pages(&format!("{GRAPH_URL}/me/calendars")).await?;
+    found
+        .into_iter()
+        .map(|c| {
+            let url = graph_url(&["me", "calendars", &c.id])?;
+            Ok((url.to_string(), c.name.unwrap_or(c.id)))
+        })
+        .collect()
+}
+
+pub fn store_calendars(
+    conn: &Connection,
+    account_id: &str,
+    found: &[(String, String)],
+) -> rusqlite::Result<()> {
+    let found: Vec<(&str, &str)> = found
+        .iter()
+        .map(|(url, name)| (url.as_str(), name.as_str()))
+        .collect();
+    calendars::store_microsoft(conn, account_id, &found)
+}
+
+/// Events of the calendar in `window` changed since `delta_link`, or all
+/// of them when there's no link. Recurring events come expanded into
+/// their occurrences, each with its own id. A link Graph has expired
+/// (410 Gone) starts over with a full fetch. Events in `pushed` came from
+/// time blocks and aren't shown.
+async fn fetch_events(
+    api: &Api,
+    calendar: &Calendar,
+    delta_link: Option<&str>,
+    window: (DateTime<Utc>, DateTime<Utc>),
+    pushed: &HashSet<String>,
+) -> CommandResult<Fetched> {
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let mut start =
+        Url::parse(&format!("{}/calendarView/delta", calendar.url)).map_err(|e| e.to_string())?;
+    start
+        .query_pairs_mut()
+        .append_pair(
+            "startDateTime",
+            &window.0.format(GRAPH_TIME_FORMAT).to_string(),
+        )
+        .append_pair(
+            "endDateTime",
+            &window.1.format(GRAPH_TIME_FORMAT).to_string(),
+        );
+    let mut full = delta_link.is_none();
+    let mut next = delta_link.map_or_else(|| start.to_string(), str::to_string);
+    let mut fetched = Fetched {
+        full,
+        ..Fetched::default()
+    };
+    loop {
+        let page: Option<Page<RemoteEvent>> = api.send(Method::GET, &next, None).await?;
+        let Some(page) = page else {
+            if full {
+                return Err(CommandError::not_found("Calendar not found in Outlook"));
+            }
+            full = true;
+            next = start.to_string();
+            fetched = Fetched {
+                full,
+                ..Fetched::default()
+            };
+            continue;
+        };
+        for event in page.value {
+            let found = if pushed.contains(&event.id) {
+                None
+            } else {
+                event.to_event(&local)
+            };
+            match found {
+                Some(found) => fetched.events.push(found),
+                None => fetched.removed.push(event.id),
+            }
+        }
+        match page.next_link {
+            Some(link) => next = link,
+            None => {
+                fetched.sync_token = page.delta_link;
+                fetched.sync_from = Some(window.0.date_naive().to_string());
+                return Ok(fetched);
+            }
+        }
+    }
+}
+
+/// The event fields for a time block, in UTC.
+fn block_body(block: &BlockRow) -> CommandResult<serde_json::Value> {
+    let (start, end) = block.span()?;
+    Ok(json!({
+        "subject": block.title,
+        "start": { "dateTime": start.format(GRAPH_TIME_FORMAT).to_string(), "timeZone": "UTC" },
+        "end": { "dateTime": end.format(GRAPH_TIME_FORMAT).to_string(), "timeZone": "UTC" },
+        "showAs": "busy",
+        "isReminderOn": false,
+    }))
+}
+
+/// Mirror time blocks from `since` on as events on the calendar, so the
+/// time shows as taken to others, and remove events whose block is gone.
+async fn push_blocks(db: &Db, api: &Api, calendar: &Calendar, since: &str) -> CommandResult<()> {
+    let (blocks, stale) = db.with_conn(|conn| {
+        Ok((
+            calendars::blocks_to_push(conn, calendar, since)?,
+            calendars::stale_block_events(conn, calendar)?,
+        ))
+    })?;
+
+    for (block_id, event_id) in stale {
+        let url = graph_url(&["me", "events", &event_id])?;
+        api.send::<serde_json::Value>(Method::DELETE, url.as_str(), None)
+            .await?;
+        db.with_conn(|conn| calendars::forget_block_event(conn, &block_id, &calendar.id))?;
+    }
+
+    for block in blocks {
+        if block.is_synced() {
+            continue;
+        }
+        let body = block_body(&block)?;
+        let updated: Option<SavedEvent> = match &block.event_id {
+            Some(event_id) => {
+                let url = graph_url(&["me", "events", event_id])?;
+                api.send(Method::PATCH, url.as_str(), Some(&body)).await?
+            }
+            None => None,
+        };
+        // New, or deleted in Outlook meanwhile: the block still exists, so
+        // it's pushed again.
+        let saved = match updated {
+            Some(saved) => saved,
+            None => {
+                let url = format!("{}/events", calendar.url);
+                let created: Option<SavedEvent> = api.send(Method::POST, &url, Some(&body)).await?;
+                created.ok_or_else(|| CommandError::not_found("Calendar not found in Outlook"))?
+            }
+        };
+        db.with_conn(|conn| calendars::save_block_event(conn, &block, &calendar.id, &saved.id))?;
+    }
+    Ok(())
+}
+
+/// Push time blocks to the calendar if it takes them, then fetch what
+/// changed on it. Returns `None` for a hidden calendar, which is only
+/// visited to clean up events pushed to it before.
+pub async fn refresh(
+    db: &Db,
+    api: &Api,
+    calendar: &Calendar,
+    window: (DateTime<Utc>, DateTime<Utc>),
+) -> CommandResult<Option<Fetched>> {
+    let since = window.0.date_naive().to_string();
+    push_blocks(db, api, calendar, &since).await?;
+    if !calendar.enabled {
+        return Ok(None);
+    }
+    let (delta_link, sync_from, pushed) = db.with_conn(|conn| {
+        let (link, from) = conn.query_row(
+            "SELECT sync_token, sync_from FROM calendars WHERE id = ?1",
+            params![calendar.id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                ))
+            },
+        )?;
+        Ok((link, from, calendars::pushed_event_ids(conn, &calendar.id)?))
+    })?;
+    // The link only covers the days it was started for; once the window
+    // has moved on, start over.
+    let delta_link = delta_link.filter(|_| sync_from.as_deref() == Some(since.as_str()));
+    fetch_events(api, calendar, delta_link.as_deref(), window, &pushed)
+        .await
+        .map(Some)
+}
+
+/// Look for calendars added to or removed from the account.
+#[tauri::command]
+pub async fn refresh_microsoft_calendars(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<Calendar>> {
+    let api = Api::connect(&db, &account_id, SOURCE).await?;
+    let found = calendars(&api).await?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_calendars(&tx, &account_id, &found)?;
+        tx.commit()?;
+        calendars::list_microsoft(conn, &account_id)
+    })
+}
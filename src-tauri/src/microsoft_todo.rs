@@ -0,0 +1,1347 @@
+use std::collections::{HashMap, HashSet};
+
+use reqwest::Method;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, State};
+
+use crate::account_health::AccountHealth;
+use crate::conflicts::{self, ConflictPolicy};
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::history::{self, ChangeSource};
+use crate::microsoft::{self, Api, DateTimeZone, Page, GRAPH_URL};
+use crate::outbox::{self, Replay};
+use crate::projects;
+use crate::reminders;
+use crate::subtasks;
+use crate::sync_provider::{
+    self, Capabilities, RemoteCollection, SyncFuture, SyncMode, SyncProvider,
+};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::trash;
+
+/// `external_refs.source` for tasks that came from Microsoft To Do.
+pub const SOURCE: &str = "microsoft_todo";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MicrosoftTaskList {
+    pub id: String,
+    pub account_id: String,
+    pub title: String,
+    /// Local project the list's tasks are filed under. New tasks in this
+    /// project are uploaded to the list.
+    pub project: Option<String>,
+    pub enabled: bool,
+    pub last_synced_at: Option<String>,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TaskListPatch {
+    pub enabled: Option<bool>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+}
+
+/// Counts include steps, which sync as subtasks.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MicrosoftSyncReport {
+    pub task_list_id: String,
+    pub title: String,
+    /// Tasks created or updated from To Do.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because they were deleted in To Do.
+    pub removed: usize,
+    /// Tasks uploaded, new or changed.
+    pub uploaded: usize,
+    /// Tasks deleted in To Do because they were deleted here.
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for MicrosoftSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.uploaded + self.deleted
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ItemBody {
+    #[serde(default)]
+    content: String,
+}
+
+/// A task as the delta query returns it. Deleted tasks carry only their
+/// id and `@removed`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteTask {
+    id: String,
+    #[serde(default, rename = "@odata.etag")]
+    etag: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    body: Option<ItemBody>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    importance: Option<String>,
+    #[serde(default)]
+    due_date_time: Option<DateTimeZone>,
+    #[serde(default)]
+    completed_date_time: Option<DateTimeZone>,
+    #[serde(default)]
+    is_reminder_on: bool,
+    #[serde(default)]
+    reminder_date_time: Option<DateTimeZone>,
+    #[serde(default)]
+    last_modified_date_time: Option<String>,
+    #[serde(default, rename = "@removed")]
+    removed: Option<serde_json::Value>,
+    /// Fetched separately, for tasks that changed.
+    #[serde(skip)]
+    steps: Option<Vec<RemoteStep>>,
+}
+
+impl RemoteTask {
+    fn is_done(&self) -> bool {
+        self.status.as_deref() == Some("completed")
+    }
+
+    fn reminder(&self) -> Option<String> {
+        self.reminder_date_time
+            .as_ref()
+            .filter(|_| self.is_reminder_on)
+            .and_then(DateTimeZone::instant)
+            .map(format_utc)
+    }
+}
+
+/// A step (checklist item) of a task.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteStep {
+    id: String,
+    #[serde(default)]
+    display_name: String,
+    #[serde(default)]
+    is_checked: bool,
+    #[serde(default)]
+    checked_date_time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteList {
+    id: String,
+    display_name: String,
+}
+
+/// The To Do importance for a priority (0-3, none to high).
+fn importance(priority: Option<i64>) -> &'static str {
+    match priority {
+        Some(p) if p >= 3 => "high",
+        Some(1) => "low",
+        _ => "normal",
+    }
+}
+
+/// The fields uploaded for a task. `null` clears a field in To Do.
+fn task_body(task: &Task, reminder: Option<&str>) -> serde_json::Value {
+    let done = task.status == STATUS_DONE;
+    let due = task.due.as_deref().and_then(|d| d.get(..10));
+    let reminder = reminder.and_then(|r| parse_utc(r).ok());
+    json!({
+        "title": task.title,
+        "body": {
+            "content": task.description.clone().unwrap_or_default(),
+            "contentType": "text",
+        },
+        "status": if done { "completed" } else { "notStarted" },
+        "importance": importance(task.priority),
+        "dueDateTime": due.map(|d| json!({ "dateTime": format!("{d}T00:00:00"), "timeZone": "UTC" })),
+        "isReminderOn": reminder.is_some(),
+        "reminderDateTime": reminder.map(|r| json!({
+            "dateTime": r.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "timeZone": "UTC",
+        })),
+    })
+}
+
+fn step_body(task: &Task) -> serde_json::Value {
+    json!({
+        "displayName": task.title,
+        "isChecked": task.status == STATUS_DONE,
+    })
+}
+
+fn row_to_list(row: &Row) -> rusqlite::Result<MicrosoftTaskList> {
+    Ok(MicrosoftTaskList {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        title: row.get(2)?,
+        project: row.get(3)?,
+        enabled: row.get(4)?,
+        last_synced_at: row.get(5)?,
+    })
+}
+
+const LIST_COLUMNS: &str = "id, account_id, title, project, enabled, last_synced_at";
+
+pub fn list_task_lists(
+    conn: &Connection,
+    account_id: &str,
+) -> rusqlite::Result<Vec<MicrosoftTaskList>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LIST_COLUMNS} FROM microsoft_task_lists
+         WHERE account_id = ?1 ORDER BY title COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map(params![account_id], row_to_list)?;
+    rows.collect()
+}
+
+fn find_task_list(conn: &Connection, id: &str) -> rusqlite::Result<Option<MicrosoftTaskList>> {
+    conn.query_row(
+        &format!("SELECT {LIST_COLUMNS} FROM microsoft_task_lists WHERE id = ?1"),
+        params![id],
+        row_to_list,
+    )
+    .optional()
+}
+
+/// Every To Do list on the account.
+pub async fn task_lists(api: &Api) -> CommandResult<Vec<RemoteList>> {
+    api.all_pages(&format!("{GRAPH_URL}/me/todo/lists")).await
+}
+
+fn tasks_url(list: &str) -> String {
+    format!("{GRAPH_URL}/me/todo/lists/{list}/tasks")
+}
+
+/// Tasks changed since `delta_link`, deletions included, or every task when
+/// `None`, with the link to pass next time. An expired link starts over;
+/// the flag says whether the tasks are the whole list.
+async fn delta(
+    api: &Api,
+    list: &str,
+    delta_link: Option<&str>,
+) -> CommandResult<(Vec<RemoteTask>, bool, String)> {
+    let start = format!("{}/delta", tasks_url(list));
+    let mut full = delta_link.is_none();
+    let mut next = delta_link.unwrap_or(&start).to_string();
+    let mut tasks = Vec::new();
+    loop {
+        let page: Option<Page<RemoteTask>> = api.send(Method::GET, &next, None).await?;
+        let Some(page) = page else {
+            if full {
+                return Err(CommandError::not_found("To Do list not found"));
+            }
+            full = true;
+            next = start.clone();
+            tasks.clear();
+            continue;
+        };
+        tasks.extend(page.value);
+        match (page.next_link, page.delta_link) {
+            (Some(link), _) => next = link,
+            (None, Some(link)) => return Ok((tasks, full, link)),
+            (None, None) => return Err("Microsoft sent no delta link".into()),
+        }
+    }
+}
+
+/// Save the lists found on an account. Lists To Do no longer has are
+/// dropped along with their sync state; their tasks stay.
+pub fn store_task_lists(
+    conn: &Connection,
+    account_id: &str,
+    found: &[RemoteList],
+) -> rusqlite::Result<()> {
+    for list in found {
+        conn.execute(
+            "INSERT INTO microsoft_task_lists (id, account_id, remote_id, title)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(account_id, remote_id) DO UPDATE SET title = excluded.title",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                account_id,
+                list.id,
+                list.display_name
+            ],
+        )?;
+    }
+    let ids: Vec<&str> = found.iter().map(|l| l.id.as_str()).collect();
+    let ids = serde_json::to_string(&ids).unwrap_or_default();
+    conn.execute(
+        "DELETE FROM microsoft_task_lists
+         WHERE account_id = ?1 AND remote_id NOT IN (SELECT value FROM json_each(?2))",
+        params![account_id, ids],
+    )?;
+    Ok(())
+}
+
+/// What the last sync knew about one To Do task.
+#[derive(Debug, Clone)]
+struct ItemRow {
+    etag: Option<String>,
+    task_id: String,
+    /// The reminder To Do had, so a reminder changed there can be told
+    /// apart from reminders set here.
+    reminder_at: Option<String>,
+    /// The task's `updated_at` when it last matched To Do.
+    synced_at: String,
+}
+
+fn load_items(conn: &Connection, task_list_id: &str) -> rusqlite::Result<HashMap<String, ItemRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, etag, task_id, reminder_at, synced_at FROM microsoft_task_items
+         WHERE task_list_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![task_list_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ItemRow {
+                etag: row.get(1)?,
+                task_id: row.get(2)?,
+                reminder_at: row.get(3)?,
+                synced_at: row.get(4)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_item(
+    conn: &Connection,
+    task_list_id: &str,
+    remote_id: &str,
+    item: &ItemRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO microsoft_task_items
+             (task_list_id, remote_id, etag, task_id, reminder_at, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(task_list_id, remote_id) DO UPDATE SET
+             etag = excluded.etag,
+             task_id = excluded.task_id,
+             reminder_at = excluded.reminder_at,
+             synced_at = excluded.synced_at",
+        params![
+            task_list_id,
+            remote_id,
+            item.etag,
+            item.task_id,
+            item.reminder_at,
+            item.synced_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Drop the link to a task and its steps.
+fn forget_item(conn: &Connection, task_list_id: &str, remote_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM microsoft_task_items WHERE task_list_id = ?1 AND remote_id = ?2",
+        params![task_list_id, remote_id],
+    )?;
+    conn.execute(
+        "DELETE FROM microsoft_step_items WHERE task_list_id = ?1 AND parent_remote_id = ?2",
+        params![task_list_id, remote_id],
+    )?;
+    Ok(())
+}
+
+/// What the last sync knew about one step.
+#[derive(Debug, Clone)]
+struct StepRow {
+    parent_remote_id: String,
+    task_id: String,
+    synced_at: String,
+}
+
+/// Steps of the list, or of one task, by remote id.
+fn load_steps(
+    conn: &Connection,
+    task_list_id: &str,
+    parent_remote_id: Option<&str>,
+) -> rusqlite::Result<HashMap<String, StepRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, parent_remote_id, task_id, synced_at FROM microsoft_step_items
+         WHERE task_list_id = ?1 AND (?2 IS NULL OR parent_remote_id = ?2)",
+    )?;
+    let rows = stmt.query_map(params![task_list_id, parent_remote_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            StepRow {
+                parent_remote_id: row.get(1)?,
+                task_id: row.get(2)?,
+                synced_at: row.get(3)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_step(
+    conn: &Connection,
+    task_list_id: &str,
+    remote_id: &str,
+    step: &StepRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO microsoft_step_items
+             (task_list_id, remote_id, parent_remote_id, task_id, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(task_list_id, remote_id) DO UPDATE SET
+             parent_remote_id = excluded.parent_remote_id,
+             task_id = excluded.task_id,
+             synced_at = excluded.synced_at",
+        params![
+            task_list_id,
+            remote_id,
+            step.parent_remote_id,
+            step.task_id,
+            step.synced_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_step(conn: &Connection, task_list_id: &str, remote_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM microsoft_step_items WHERE task_list_id = ?1 AND remote_id = ?2",
+        params![task_list_id, remote_id],
+    )?;
+    Ok(())
+}
+
+/// The soonest upcoming reminder of a task, sent as its To Do reminder.
+fn next_reminder(conn: &Connection, task_id: &str) -> rusqlite::Result<Option<String>> {
+    let at: Option<String> = conn.query_row(
+        "SELECT MIN(remind_at) FROM reminders WHERE task_id = ?1 AND remind_at >= ?2",
+        params![task_id, now_utc()],
+        |row| row.get(0),
+    )?;
+    Ok(at.and_then(|at| parse_utc(&at).ok()).map(format_utc))
+}
+
+fn set_done(task: &mut Task, done: bool, completed_at: Option<String>) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = completed_at.or_else(|| Some(now_utc()));
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// Create or update the task for `remote`. Unless `policy` says remote
+/// wins, a field edited here after To Do last changed the task keeps the
+/// local value. Returns the task and whether it was created.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    remote: &RemoteTask,
+    project: Option<&str>,
+    policy: ConflictPolicy,
+) -> rusqlite::Result<(Task, bool)> {
+    let title = remote
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or("Untitled")
+        .to_string();
+    let notes = remote
+        .body
+        .as_ref()
+        .map(|b| b.content.clone())
+        .filter(|n| !n.trim().is_empty());
+    let completed_at = remote
+        .completed_date_time
+        .as_ref()
+        .and_then(DateTimeZone::instant)
+        .map(format_utc);
+    let due = remote.due_date_time.as_ref().and_then(DateTimeZone::date);
+    let priority = match remote.importance.as_deref() {
+        Some("high") => Some(3),
+        Some("low") => Some(1),
+        _ => Some(2),
+    };
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description: notes,
+            project: project.map(str::to_string),
+            priority: priority.filter(|p| *p != 2),
+            due,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if remote.is_done() {
+            set_done(&mut task, true, completed_at);
+            task_store::write_task(conn, &task)?;
+        }
+        return Ok((task, true));
+    };
+
+    let modified = remote
+        .last_modified_date_time
+        .as_deref()
+        .and_then(|at| parse_utc(at).ok())
+        .map(|at| at.timestamp_millis());
+    let take = conflicts::remote_fields(conn, &task.id, modified, policy)?;
+    if take("title") {
+        task.title = title;
+    }
+    if take("description") {
+        task.description = notes;
+    }
+    if take("priority") && importance(task.priority) != importance(priority) {
+        task.priority = priority;
+    }
+    // To Do keeps only the date; a time of day set here survives.
+    if take("due") && task.due.as_deref().and_then(|d| d.get(..10)) != due.as_deref() {
+        task.due = due;
+    }
+    if take("status") && remote.is_done() != (task.status == STATUS_DONE) {
+        set_done(&mut task, remote.is_done(), completed_at);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    Ok((task, false))
+}
+
+/// Bring a task's subtasks in line with its steps in To Do. Subtasks edited
+/// here since the last sync keep their changes, which are uploaded.
+fn merge_steps(
+    conn: &Connection,
+    list: &MicrosoftTaskList,
+    parent_remote_id: &str,
+    parent: &Task,
+    steps: &[RemoteStep],
+    report: &mut MicrosoftSyncReport,
+) -> rusqlite::Result<()> {
+    let known = load_steps(conn, &list.id, Some(parent_remote_id))?;
+    for step in steps {
+        let row = known.get(&step.id);
+        let local = match row {
+            Some(row) => task_store::find_task(conn, &row.task_id)?,
+            None => None,
+        };
+        let title = step.display_name.trim();
+        let title = if title.is_empty() { "Untitled" } else { title };
+        let mut task = match (row, local) {
+            // Deleted here; the delete is uploaded.
+            (Some(_), None) => continue,
+            (Some(row), Some(local)) if local.updated_at > row.synced_at => continue,
+            (Some(_), Some(mut local)) => {
+                if local.title == title && (local.status == STATUS_DONE) == step.is_checked {
+                    continue;
+                }
+                local.title = title.to_string();
+                local.updated_at = now_utc();
+                report.updated += 1;
+                local
+            }
+            (None, _) => {
+                let input = NewTask {
+                    title: title.to_string(),
+                    description: None,
+                    project: parent.project.clone(),
+                    priority: None,
+                    due: None,
+                    scheduled: None,
+                    recurrence: None,
+                    tags: Vec::new(),
+                    parent_id: Some(parent.id.clone()),
+                    estimate_minutes: None,
+                };
+                report.created += 1;
+                task_store::insert_task(conn, &input, title.to_string())?
+            }
+        };
+        if step.is_checked != (task.status == STATUS_DONE) {
+            set_done(&mut task, step.is_checked, step.checked_date_time.clone());
+        }
+        task_store::write_task(conn, &task)?;
+        let row = StepRow {
+            parent_remote_id: parent_remote_id.to_string(),
+            task_id: task.id,
+            synced_at: task.updated_at,
+        };
+        save_step(conn, &list.id, &step.id, &row)?;
+    }
+
+    let listed: HashSet<&str> = steps.iter().map(|s| s.id.as_str()).collect();
+    for (remote_id, row) in &known {
+        if listed.contains(remote_id.as_str()) {
+            continue;
+        }
+        if trash::trash_task(conn, &row.task_id)? {
+            report.removed += 1;
+        }
+        forget_step(conn, &list.id, remote_id)?;
+    }
+    Ok(())
+}
+
+/// A change to send to To Do.
+enum Upload {
+    Insert {
+        item: ItemRow,
+        body: serde_json::Value,
+    },
+    Update {
+        remote_id: String,
+        item: ItemRow,
+        body: serde_json::Value,
+    },
+    Delete {
+        remote_id: String,
+        task_id: String,
+    },
+}
+
+impl Upload {
+    fn task_id(&self) -> &str {
+        match self {
+            Upload::Insert { item, .. } | Upload::Update { item, .. } => &item.task_id,
+            Upload::Delete { task_id, .. } => task_id,
+        }
+    }
+}
+
+/// Apply To Do's changes, unless only exporting, then work out which tasks
+/// to upload. `full` is set when `remote` is every task in the list rather
+/// than only the changed ones, so tasks missing from it were deleted there.
+fn merge_remote(
+    conn: &mut Connection,
+    list: &MicrosoftTaskList,
+    remote: &[RemoteTask],
+    full: bool,
+    imports: bool,
+    report: &mut MicrosoftSyncReport,
+) -> rusqlite::Result<Vec<Upload>> {
+    let tx = conn.transaction()?;
+    let items = load_items(&tx, &list.id)?;
+    let policy = conflicts::policy(&tx, SOURCE)?;
+
+    for task in remote {
+        let known = items.get(&task.id);
+        if task.removed.is_some() {
+            if let Some(item) = known {
+                if imports && trash::trash_task(&tx, &item.task_id)? {
+                    report.removed += 1;
+                }
+                forget_item(&tx, &list.id, &task.id)?;
+            }
+            continue;
+        }
+        // Our own upload coming back.
+        if known.is_some_and(|item| item.etag.is_some() && item.etag == task.etag) {
+            continue;
+        }
+        let task_id = match known {
+            Some(item) => Some(item.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, task.id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let local = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if known.is_some() && local.is_none() {
+            // Deleted here; the delete is uploaded below.
+            continue;
+        }
+        if !imports {
+            // Only the etag is taken, so local edits go up over the task.
+            if let Some(item) = known {
+                let item = ItemRow {
+                    etag: task.etag.clone(),
+                    ..item.clone()
+                };
+                save_item(&tx, &list.id, &task.id, &item)?;
+            }
+            continue;
+        }
+        let dirty = match (known, &local) {
+            (Some(item), Some(local)) => local.updated_at > item.synced_at,
+            _ => false,
+        };
+
+        let project = list.project.as_deref();
+        let (written, created) = match local {
+            Some(local)
+                if dirty
+                    && conflicts::keep_local(&tx, SOURCE, policy, &local, |tx| {
+                        write_remote(
+                            tx,
+                            Some(local.clone()),
+                            task,
+                            project,
+                            ConflictPolicy::Remote,
+                        )
+                        .map(|(task, _)| task)
+                    })? =>
+            {
+                (local, false)
+            }
+            local => write_remote(&tx, local, task, project, policy)?,
+        };
+        if created {
+            report.created += 1;
+            tx.execute(
+                "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![SOURCE, task.id, written.id, now_utc()],
+            )?;
+        } else {
+            report.updated += 1;
+        }
+        let reminder_at = task.reminder();
+        if known.map_or(reminder_at.is_some(), |item| {
+            item.reminder_at != reminder_at
+        }) {
+            let times: Vec<String> = reminder_at.iter().cloned().collect();
+            reminders::set_task_reminders(&tx, &written.id, &times)?;
+        }
+        if let Some(steps) = &task.steps {
+            merge_steps(&tx, list, &task.id, &written, steps, report)?;
+        }
+        let synced_at = match known {
+            // Local edits To Do doesn't have yet are kept dirty.
+            Some(item) if dirty && policy != ConflictPolicy::Remote => item.synced_at.clone(),
+            _ => written.updated_at.clone(),
+        };
+        let item = ItemRow {
+            etag: task.etag.clone(),
+            task_id: written.id,
+            reminder_at,
+            synced_at,
+        };
+        save_item(&tx, &list.id, &task.id, &item)?;
+    }
+
+    if full {
+        let listed: HashSet<&str> = remote.iter().map(|t| t.id.as_str()).collect();
+        for (remote_id, item) in &items {
+            if listed.contains(remote_id.as_str()) {
+                continue;
+            }
+            if imports && trash::trash_task(&tx, &item.task_id)? {
+                report.removed += 1;
+            }
+            forget_item(&tx, &list.id, remote_id)?;
+        }
+    }
+
+    let held = conflicts::held(&tx, SOURCE)?;
+    let mut uploads = Vec::new();
+    for (remote_id, item) in load_items(&tx, &list.id)? {
+        match task_store::find_task(&tx, &item.task_id)? {
+            None => uploads.push(Upload::Delete {
+                remote_id,
+                task_id: item.task_id,
+            }),
+            Some(task) if task.updated_at > item.synced_at && !held.contains(&task.id) => {
+                let reminder_at = next_reminder(&tx, &task.id)?;
+                let body = task_body(&task, reminder_at.as_deref());
+                let item = ItemRow {
+                    reminder_at,
+                    synced_at: task.updated_at,
+                    ..item
+                };
+                uploads.push(Upload::Update {
+                    remote_id,
+                    item,
+                    body,
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Subtasks go up as steps of their parent, not as tasks of their own.
+    if let Some(project) = &list.project {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM tasks
+             WHERE project = ?1 COLLATE NOCASE AND deleted_at IS NULL AND parent_id IS NULL
+               AND id NOT IN (SELECT task_id FROM microsoft_task_items)
+             ORDER BY created_at",
+        )?;
+        let ids = stmt
+            .query_map(params![project], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        for id in ids {
+            let Some(task) = task_store::find_task(&tx, &id)? else {
+                continue;
+            };
+            let reminder_at = next_reminder(&tx, &task.id)?;
+            uploads.push(Upload::Insert {
+                body: task_body(&task, reminder_at.as_deref()),
+                item: ItemRow {
+                    etag: None,
+                    task_id: task.id,
+                    reminder_at,
+                    synced_at: task.updated_at,
+                },
+            });
+        }
+    }
+
+    tx.commit()?;
+    Ok(uploads)
+}
+
+/// A step change to send to To Do.
+enum StepUpload {
+    Insert {
+        step: StepRow,
+        body: serde_json::Value,
+    },
+    Update {
+        remote_id: String,
+        step: StepRow,
+        body: serde_json::Value,
+    },
+    Delete {
+        remote_id: String,
+        parent_remote_id: String,
+        task_id: String,
+    },
+}
+
+impl StepUpload {
+    fn task_id(&self) -> &str {
+        match self {
+            StepUpload::Insert { step, .. } | StepUpload::Update { step, .. } => &step.task_id,
+            StepUpload::Delete { task_id, .. } => task_id,
+        }
+    }
+}
+
+/// Subtasks of synced tasks to upload as steps: new ones, edited ones, and
+/// ones deleted here.
+fn step_uploads(conn: &Connection, list: &MicrosoftTaskList) -> rusqlite::Result<Vec<StepUpload>> {
+    let mut uploads = Vec::new();
+    let known = load_steps(conn, &list.id, None)?;
+    let mut linked = HashSet::new();
+    for (remote_id, step) in known {
+        linked.insert(step.task_id.clone());
+        match task_store::find_task(conn, &step.task_id)? {
+            None => uploads.push(StepUpload::Delete {
+                remote_id,
+                parent_remote_id: step.parent_remote_id,
+                task_id: step.task_id,
+            }),
+            Some(task) if task.updated_at > step.synced_at => uploads.push(StepUpload::Update {
+                remote_id,
+                body: step_body(&task),
+                step: StepRow {
+                    synced_at: task.updated_at,
+                    ..step
+                },
+            }),
+            Some(_) => {}
+        }
+    }
+    for (parent_remote_id, item) in load_items(conn, &list.id)? {
+        for child in subtasks::children(conn, &item.task_id)? {
+            if linked.contains(&child.id) {
+                continue;
+            }
+            uploads.push(StepUpload::Insert {
+                body: step_body(&child),
+                step: StepRow {
+                    parent_remote_id: parent_remote_id.clone(),
+                    task_id: child.id,
+                    synced_at: child.updated_at,
+                },
+            });
+        }
+    }
+    Ok(uploads)
+}
+
+/// What came of one upload.
+enum Uploaded {
+    Saved { remote_id: String, item: ItemRow },
+    Deleted { remote_id: String },
+    StepSaved { remote_id: String, step: StepRow },
+    StepDeleted { remote_id: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct Saved {
+    id: String,
+    #[serde(default, rename = "@odata.etag")]
+    etag: Option<String>,
+}
+
+async fn upload(api: &Api, list: &str, change: Upload) -> CommandResult<Uploaded> {
+    match change {
+        Upload::Insert { item, body } => {
+            let url = tasks_url(list);
+            let created: Option<Saved> = api.send(Method::POST, &url, Some(&body)).await?;
+            let created =
+                created.ok_or_else(|| CommandError::not_found(format!("POST {url}: not found")))?;
+            Ok(Uploaded::Saved {
+                remote_id: created.id,
+                item: ItemRow {
+                    etag: created.etag,
+                    ..item
+                },
+            })
+        }
+        Upload::Update {
+            remote_id,
+            item,
+            body,
+        } => {
+            let url = format!("{}/{remote_id}", tasks_url(list));
+            let updated: Option<Saved> = api.send(Method::PATCH, &url, Some(&body)).await?;
+            match updated {
+                Some(updated) => Ok(Uploaded::Saved {
+                    remote_id,
+                    item: ItemRow {
+                        etag: updated.etag,
+                        ..item
+                    },
+                }),
+                // Deleted in To Do meanwhile. The link is dropped; the task
+                // is uploaded again as new while it's in the list's project.
+                None => Ok(Uploaded::Deleted { remote_id }),
+            }
+        }
+        Upload::Delete { remote_id, .. } => {
+            let url = format!("{}/{remote_id}", tasks_url(list));
+            api.send::<serde_json::Value>(Method::DELETE, &url, None)
+                .await?;
+            Ok(Uploaded::Deleted { remote_id })
+        }
+    }
+}
+
+async fn upload_step(api: &Api, list: &str, change: StepUpload) -> CommandResult<Uploaded> {
+    let steps_url = |parent: &str| format!("{}/{parent}/checklistItems", tasks_url(list));
+    match change {
+        StepUpload::Insert { step, body } => {
+            let url = steps_url(&step.parent_remote_id);
+            let created: Option<Saved> = api.send(Method::POST, &url, Some(&body)).await?;
+            let created =
+                created.ok_or_else(|| CommandError::not_found(format!("POST {url}: not found")))?;
+            Ok(Uploaded::StepSaved {
+                remote_id: created.id,
+                step,
+            })
+        }
+        StepUpload::Update {
+            remote_id,
+            step,
+            body,
+        } => {
+            let url = format!("{}/{remote_id}", steps_url(&step.parent_remote_id));
+            let updated: Option<Saved> = api.send(Method::PATCH, &url, Some(&body)).await?;
+            Ok(match updated {
+                Some(_) => Uploaded::StepSaved { remote_id, step },
+                None => Uploaded::StepDeleted { remote_id },
+            })
+        }
+        StepUpload::Delete {
+            remote_id,
+            parent_remote_id,
+            ..
+        } => {
+            let url = format!("{}/{remote_id}", steps_url(&parent_remote_id));
+            api.send::<serde_json::Value>(Method::DELETE, &url, None)
+                .await?;
+            Ok(Uploaded::StepDeleted { remote_id })
+        }
+    }
+}
+
+fn save_results(
+    conn: &mut Connection,
+    list: &MicrosoftTaskList,
+    results: &[Uploaded],
+    report: &mut MicrosoftSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for result in results {
+        match result {
+            Uploaded::Saved { remote_id, item } => {
+                report.uploaded += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, remote_id, item.task_id, now_utc()],
+                )?;
+                save_item(&tx, &list.id, remote_id, item)?;
+            }
+            Uploaded::Deleted { remote_id } => {
+                report.deleted += 1;
+                forget_item(&tx, &list.id, remote_id)?;
+            }
+            Uploaded::StepSaved { remote_id, step } => {
+                report.uploaded += 1;
+                save_step(&tx, &list.id, remote_id, step)?;
+            }
+            Uploaded::StepDeleted { remote_id } => {
+                report.deleted += 1;
+                forget_step(&tx, &list.id, remote_id)?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+/// Two-way sync of one list: pull what changed in To Do since the last
+/// sync, merge it field by field, and when pushing upload local changes.
+/// Steps are uploaded once their task is, since they need its id.
+async fn sync_task_list(
+    db: &Db,
+    api: &Api,
+    list: &MicrosoftTaskList,
+    mode: SyncMode,
+) -> CommandResult<MicrosoftSyncReport> {
+    let mut report = MicrosoftSyncReport {
+        task_list_id: list.id.clone(),
+        title: list.title.clone(),
+        ..MicrosoftSyncReport::default()
+    };
+    let (remote_list, delta_link) = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT remote_id, delta_link FROM microsoft_task_lists WHERE id = ?1",
+            params![list.id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+    })?;
+
+    let (mut remote, full, next_link) = delta(api, &remote_list, delta_link.as_deref()).await?;
+    let known = db.with_conn(|conn| load_items(conn, &list.id))?;
+    for task in remote.iter_mut().filter(|t| t.removed.is_none()) {
+        if known
+            .get(&task.id)
+            .is_some_and(|item| item.etag.is_some() && item.etag == task.etag)
+        {
+            continue;
+        }
+        let url = format!("{}/{}/checklistItems", tasks_url(&remote_list), task.id);
+        match api.all_pages(&url).await {
+            Ok(steps) => task.steps = Some(steps),
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    let uploads = db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(conn, list, &remote, full, mode.imports(), &mut report)
+        })?
+    })?;
+    // Importing only leaves local changes here.
+    let uploads = if mode.exports() { uploads } else { Vec::new() };
+    let uploads = db.with_conn(|conn| outbox::in_order(conn, uploads, Upload::task_id))?;
+    let mut results = Vec::new();
+    let mut replay = Replay::default();
+    for change in uploads {
+        let task_id = change.task_id().to_string();
+        match upload(api, &remote_list, change).await {
+            Ok(result) => {
+                replay.delivered(task_id);
+                results.push(result);
+            }
+            Err(e) => {
+                replay.failed(task_id, &e);
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    db.with_conn(|conn| {
+        replay.save(conn)?;
+        save_results(conn, list, &results, &mut report)
+    })?;
+
+    let step_changes = if mode.exports() {
+        db.with_conn(|conn| {
+            let changes = step_uploads(conn, list)?;
+            outbox::in_order(conn, changes, StepUpload::task_id)
+        })?
+    } else {
+        Vec::new()
+    };
+    let mut results = Vec::new();
+    let mut replay = Replay::default();
+    for change in step_changes {
+        let task_id = change.task_id().to_string();
+        match upload_step(api, &remote_list, change).await {
+            Ok(result) => {
+                replay.delivered(task_id);
+                results.push(result);
+            }
+            Err(e) => {
+                replay.failed(task_id, &e);
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    db.with_conn(|conn| {
+        replay.save(conn)?;
+        save_results(conn, list, &results, &mut report)?;
+        conn.execute(
+            "UPDATE microsoft_task_lists SET delta_link = ?2, last_synced_at = ?3 WHERE id = ?1",
+            params![list.id, next_link, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+async fn refresh_task_lists(db: &Db, account_id: &str) -> CommandResult<Vec<MicrosoftTaskList>> {
+    let api = Api::connect(db, account_id, SOURCE).await?;
+    let found = task_lists(&api).await?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_task_lists(&tx, account_id, &found)?;
+        tx.commit()?;
+        list_task_lists(conn, account_id)
+    })
+}
+
+/// Look for lists added or removed in To Do since.
+#[tauri::command]
+pub async fn refresh_microsoft_task_lists(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<MicrosoftTaskList>> {
+    refresh_task_lists(&db, &account_id).await
+}
+
+fn update_task_list(db: &Db, id: &str, patch: TaskListPatch) -> CommandResult<MicrosoftTaskList> {
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut list) = find_task_list(conn, id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Task list not found: {id}"
+            ))));
+        };
+        if let Some(project) = project {
+            list.project = project;
+        }
+        if let Some(enabled) = patch.enabled {
+            list.enabled = enabled;
+        }
+        if list.enabled && list.project.is_none() {
+            list.project = Some(list.title.clone());
+        }
+        conn.execute(
+            "UPDATE microsoft_task_lists SET project = ?2, enabled = ?3 WHERE id = ?1",
+            params![list.id, list.project, list.enabled],
+        )?;
+        Ok(Ok(list))
+    })?
+}
+
+/// Turn syncing of a list on or off, or change its project. A list is
+/// enabled with a project named after it unless one is given.
+#[tauri::command]
+pub fn update_microsoft_task_list(
+    db: State<'_, Db>,
+    id: String,
+    patch: TaskListPatch,
+) -> CommandResult<MicrosoftTaskList> {
+    update_task_list(&db, &id, patch)
+}
+
+pub struct MicrosoftTodo;
+
+impl SyncProvider for MicrosoftTodo {
+    type Report = Vec<MicrosoftSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Microsoft To Do"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: true,
+            conflicts: true,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| microsoft::list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(db, &account.id, SOURCE).await?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| microsoft::list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                if let Some(api) = Api::check(db, &account.id, SOURCE, &mut health).await? {
+                    let lists = api
+                        .probe(&format!("{GRAPH_URL}/me/todo/lists?$top=1"))
+                        .await;
+                    health.answer("Microsoft To Do", lists);
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Import))
+    }
+
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::TwoWay))
+    }
+
+    fn export<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Export))
+    }
+
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| microsoft::list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let lists = match refresh_task_lists(db, &account.id).await {
+                    Ok(lists) => lists,
+                    Err(e) => {
+                        tracing::warn!("refresh of {} failed: {e}", account.name);
+                        account.lists
+                    }
+                };
+                found.extend(lists.into_iter().map(|l| RemoteCollection {
+                    id: l.id,
+                    account_id: l.account_id,
+                    name: l.title,
+                    selected: l.enabled,
+                }));
+            }
+            Ok(found)
+        })
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        let patch = TaskListPatch {
+            enabled: Some(selected),
+            ..TaskListPatch::default()
+        };
+        update_task_list(db, id, patch).map(drop)
+    }
+}
+
+/// Sync every enabled list of `account_id`, or of all accounts. One list
+/// failing doesn't stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_microsoft_todo(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<MicrosoftSyncReport>> {
+    sync_provider::run_command(&app, &MicrosoftTodo, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+    mode: SyncMode,
+) -> CommandResult<Vec<MicrosoftSyncReport>> {
+    let accounts = db.with_conn(|conn| microsoft::list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let lists: Vec<&MicrosoftTaskList> = account.lists.iter().filter(|l| l.enabled).collect();
+        if lists.is_empty() {
+            continue;
+        }
+        let api = Api::connect(db, &account.id, SOURCE).await;
+        for list in lists {
+            let result = match &api {
+                Ok(api) => sync_task_list(db, api, list, mode).await,
+                Err(e) => Err(e.clone()),
+            };
+            let report = result.unwrap_or_else(|e| {
+                tracing::warn!("sync of {} failed: {e}", list.title);
+                MicrosoftSyncReport {
+                    task_list_id: list.id.clone(),
+                    title: list.title.clone(),
+                    errors: vec![e.to_string()],
+                    ..MicrosoftSyncReport::default()
+                }
+            });
+            progress.record(&report.title, &report);
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
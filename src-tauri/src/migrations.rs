@@ -0,0 +1,1576 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Ordered schema migrations. Append new entries at the end; never edit or
+/// reorder one that has shipped.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_tasks",
+        sql: "CREATE TABLE IF NOT EXISTS tasks (
+                  id TEXT PRIMARY KEY,
+                  title TEXT NOT NULL,
+                  description TEXT,
+                  status TEXT NOT NULL DEFAULT 'open',
+                  project TEXT,
+                  priority INTEGER,
+                  due TEXT,
+                  scheduled TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL,
+                  completed_at TEXT
+              );
+              CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks(status);
+              CREATE INDEX IF NOT EXISTS idx_tasks_scheduled ON tasks(scheduled);
+              CREATE INDEX IF NOT EXISTS idx_tasks_due ON tasks(due);",
+    },
+    Migration {
+        version: 2,
+        name: "create_time_entries",
+        sql: "CREATE TABLE time_entries (
+                  id TEXT PRIMARY KEY,
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  started_at TEXT NOT NULL,
+                  ended_at TEXT,
+                  note TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE INDEX idx_time_entries_task ON time_entries(task_id);
+              CREATE INDEX idx_time_entries_started ON time_entries(started_at);
+              CREATE UNIQUE INDEX idx_time_entries_running
+                  ON time_entries((ended_at IS NULL)) WHERE ended_at IS NULL;",
+    },
+    Migration {
+        version: 3,
+        name: "create_search_index",
+        sql: "CREATE VIRTUAL TABLE search_index USING fts5(
+                  kind UNINDEXED,
+                  ref_id UNINDEXED,
+                  title,
+                  body,
+                  tokenize = 'unicode61 remove_diacritics 2',
+                  prefix = '2 3'
+              );
+              INSERT INTO search_index (kind, ref_id, title, body)
+                  SELECT 'task', id, title, COALESCE(description, '') FROM tasks;
+              INSERT INTO search_index (kind, ref_id, title, body)
+                  SELECT 'note', id, '', note FROM time_entries WHERE note IS NOT NULL;
+
+              CREATE TRIGGER search_tasks_ai AFTER INSERT ON tasks BEGIN
+                  INSERT INTO search_index (kind, ref_id, title, body)
+                  VALUES ('task', new.id, new.title, COALESCE(new.description, ''));
+              END;
+              CREATE TRIGGER search_tasks_au AFTER UPDATE OF title, description ON tasks BEGIN
+                  DELETE FROM search_index WHERE kind = 'task' AND ref_id = old.id;
+                  INSERT INTO search_index (kind, ref_id, title, body)
+                  VALUES ('task', new.id, new.title, COALESCE(new.description, ''));
+              END;
+              CREATE TRIGGER search_tasks_ad AFTER DELETE ON tasks BEGIN
+                  DELETE FROM search_index WHERE kind = 'task' AND ref_id = old.id;
+              END;
+
+              CREATE TRIGGER search_notes_ai AFTER INSERT ON time_entries
+              WHEN new.note IS NOT NULL BEGIN
+                  INSERT INTO search_index (kind, ref_id, title, body)
+                  VALUES ('note', new.id, '', new.note);
+              END;
+              CREATE TRIGGER search_notes_au AFTER UPDATE OF note ON time_entries BEGIN
+                  DELETE FROM search_index WHERE kind = 'note' AND ref_id = old.id;
+                  INSERT INTO search_index (kind, ref_id, title, body)
+                  SELECT 'note', new.id, '', new.note WHERE new.note IS NOT NULL;
+              END;
+              CREATE TRIGGER search_notes_ad AFTER DELETE ON time_entries BEGIN
+                  DELETE FROM search_index WHERE kind = 'note' AND ref_id = old.id;
+              END;",
+    },
+    Migration {
+        version: 4,
+        name: "create_tags",
+        // FTS5 tables can't gain columns, so the task half of the search index
+        // is rebuilt with a `tags` column and its triggers recreated.
+        sql: "CREATE TABLE tags (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                  color TEXT,
+                  created_at TEXT NOT NULL
+              );
+              CREATE TABLE task_tags (
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  tag_id TEXT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                  PRIMARY KEY (task_id, tag_id)
+              );
+              CREATE INDEX idx_task_tags_tag ON task_tags(tag_id);
+
+              DROP TRIGGER search_tasks_ai;
+              DROP TRIGGER search_tasks_au;
+              DROP TRIGGER search_tasks_ad;
+              DROP TRIGGER search_notes_ai;
+              DROP TRIGGER search_notes_au;
+              DROP TRIGGER search_notes_ad;
+              DROP TABLE search_index;
+
+              CREATE VIRTUAL TABLE search_index USING fts5(
+                  kind UNINDEXED,
+                  ref_id UNINDEXED,
+                  title,
+                  body,
+                  tags,
+                  tokenize = 'unicode61 remove_diacritics 2',
+                  prefix = '2 3'
+              );
+              INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  SELECT 'task', id, title, COALESCE(description, ''), '' FROM tasks;
+              INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  SELECT 'note', id, '', note, '' FROM time_entries WHERE note IS NOT NULL;
+
+              CREATE TRIGGER search_tasks_ai AFTER INSERT ON tasks BEGIN
+                  INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  VALUES ('task', new.id, new.title, COALESCE(new.description, ''), '');
+              END;
+              CREATE TRIGGER search_tasks_au AFTER UPDATE OF title, description ON tasks BEGIN
+                  DELETE FROM search_index WHERE kind = 'task' AND ref_id = old.id;
+                  INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  SELECT 'task', id, title, COALESCE(description, ''),
+                      COALESCE((SELECT group_concat(g.name, ' ') FROM task_tags tt
+                                JOIN tags g ON g.id = tt.tag_id
+                                WHERE tt.task_id = tasks.id), '')
+                  FROM tasks WHERE id = new.id;
+              END;
+              CREATE TRIGGER search_tasks_ad AFTER DELETE ON tasks BEGIN
+                  DELETE FROM search_index WHERE kind = 'task' AND ref_id = old.id;
+              END;
+
+              CREATE TRIGGER search_notes_ai AFTER INSERT ON time_entries
+              WHEN new.note IS NOT NULL BEGIN
+                  INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  VALUES ('note', new.id, '', new.note, '');
+              END;
+              CREATE TRIGGER search_notes_au AFTER UPDATE OF note ON time_entries BEGIN
+                  DELETE FROM search_index WHERE kind = 'note' AND ref_id = old.id;
+                  INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  SELECT 'note', new.id, '', new.note, '' WHERE new.note IS NOT NULL;
+              END;
+              CREATE TRIGGER search_notes_ad AFTER DELETE ON time_entries BEGIN
+                  DELETE FROM search_index WHERE kind = 'note' AND ref_id = old.id;
+              END;
+
+              CREATE TRIGGER search_task_tags_ai AFTER INSERT ON task_tags BEGIN
+                  DELETE FROM search_index WHERE kind = 'task' AND ref_id = new.task_id;
+                  INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  SELECT 'task', id, title, COALESCE(description, ''),
+                      COALESCE((SELECT group_concat(g.name, ' ') FROM task_tags tt
+                                JOIN tags g ON g.id = tt.tag_id
+                                WHERE tt.task_id = tasks.id), '')
+                  FROM tasks WHERE id = new.task_id;
+              END;
+              CREATE TRIGGER search_task_tags_ad AFTER DELETE ON task_tags BEGIN
+                  DELETE FROM search_index WHERE kind = 'task' AND ref_id = old.task_id;
+                  INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  SELECT 'task', id, title, COALESCE(description, ''),
+                      COALESCE((SELECT group_concat(g.name, ' ') FROM task_tags tt
+                                JOIN tags g ON g.id = tt.tag_id
+                                WHERE tt.task_id = tasks.id), '')
+                  FROM tasks WHERE id = old.task_id;
+              END;
+              CREATE TRIGGER search_tags_au AFTER UPDATE OF name ON tags BEGIN
+                  DELETE FROM search_index WHERE kind = 'task'
+                      AND ref_id IN (SELECT task_id FROM task_tags WHERE tag_id = new.id);
+                  INSERT INTO search_index (kind, ref_id, title, body, tags)
+                  SELECT 'task', id, title, COALESCE(description, ''),
+                      COALESCE((SELECT group_concat(g.name, ' ') FROM task_tags tt
+                                JOIN tags g ON g.id = tt.tag_id
+                                WHERE tt.task_id = tasks.id), '')
+                  FROM tasks WHERE id IN (SELECT task_id FROM task_tags WHERE tag_id = new.id);
+              END;",
+    },
+    Migration {
+        version: 5,
+        name: "add_task_recurrence",
+        sql: "ALTER TABLE tasks ADD COLUMN recurrence TEXT;
+              ALTER TABLE tasks ADD COLUMN series_id TEXT;
+              CREATE INDEX idx_tasks_series ON tasks(series_id);",
+    },
+    Migration {
+        version: 6,
+        name: "add_time_zones",
+        sql: "ALTER TABLE tasks ADD COLUMN tz TEXT;
+              ALTER TABLE time_entries ADD COLUMN tz TEXT;",
+    },
+    Migration {
+        version: 7,
+        name: "add_entry_billable",
+        sql: "ALTER TABLE time_entries ADD COLUMN billable INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 8,
+        name: "create_external_refs",
+        sql: "CREATE TABLE external_refs (
+                  source TEXT NOT NULL,
+                  external_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  created_at TEXT NOT NULL,
+                  PRIMARY KEY (source, external_id)
+              );
+              CREATE INDEX idx_external_refs_task ON external_refs(task_id);",
+    },
+    Migration {
+        version: 9,
+        name: "create_reminders",
+        sql: "CREATE TABLE reminders (
+                  id TEXT PRIMARY KEY,
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  remind_at TEXT NOT NULL,
+                  created_at TEXT NOT NULL
+              );
+              CREATE INDEX idx_reminders_task ON reminders(task_id);
+              CREATE INDEX idx_reminders_at ON reminders(remind_at);",
+    },
+    Migration {
+        version: 10,
+        name: "create_journal",
+        // Triggers that fill `journal` are generated at startup from the live
+        // columns (see `journal::install`), so they aren't part of the schema.
+        sql: "CREATE TABLE journal_steps (
+                  id INTEGER PRIMARY KEY AUTOINCREMENT,
+                  label TEXT NOT NULL,
+                  created_at TEXT NOT NULL,
+                  undone INTEGER NOT NULL DEFAULT 0
+              );
+              CREATE TABLE journal (
+                  seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                  step_id INTEGER REFERENCES journal_steps(id) ON DELETE CASCADE,
+                  tbl TEXT NOT NULL,
+                  op TEXT NOT NULL,
+                  before TEXT,
+                  after TEXT
+              );
+              CREATE INDEX idx_journal_step ON journal(step_id);
+              CREATE TABLE journal_control (
+                  id INTEGER PRIMARY KEY CHECK (id = 1),
+                  paused INTEGER NOT NULL DEFAULT 0
+              );
+              INSERT INTO journal_control (id, paused) VALUES (1, 0);",
+    },
+    Migration {
+        version: 11,
+        name: "create_task_history",
+        // No foreign key: history outlives the task so a restored or undeleted
+        // task keeps it. `change_source` says who is writing (see `history`).
+        sql: "CREATE TABLE task_history (
+                  id INTEGER PRIMARY KEY AUTOINCREMENT,
+                  task_id TEXT NOT NULL,
+                  field TEXT NOT NULL,
+                  old_value TEXT,
+                  new_value TEXT,
+                  changed_at TEXT NOT NULL,
+                  source TEXT NOT NULL
+              );
+              CREATE INDEX idx_task_history_task ON task_history(task_id, id);
+              CREATE TABLE change_source (
+                  id INTEGER PRIMARY KEY CHECK (id = 1),
+                  source TEXT NOT NULL
+              );
+              INSERT INTO change_source (id, source) VALUES (1, 'user');
+
+              CREATE TRIGGER history_tasks_ai AFTER INSERT ON tasks BEGIN
+                  INSERT INTO task_history (task_id, field, old_value, new_value, changed_at, source)
+                  VALUES (new.id, 'created', NULL, new.title,
+                          strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                          (SELECT source FROM change_source));
+              END;
+              CREATE TRIGGER history_tasks_au AFTER UPDATE ON tasks BEGIN
+                  INSERT INTO task_history (task_id, field, old_value, new_value, changed_at, source)
+                  SELECT new.id, f.field, f.old_value, f.new_value,
+                         strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                         (SELECT source FROM change_source)
+                  FROM (SELECT 'title' AS field, old.title AS old_value, new.title AS new_value
+                        UNION ALL SELECT 'description', old.description, new.description
+                        UNION ALL SELECT 'status', old.status, new.status
+                        UNION ALL SELECT 'project', old.project, new.project
+                        UNION ALL SELECT 'priority', old.priority, new.priority
+                        UNION ALL SELECT 'due', old.due, new.due
+                        UNION ALL SELECT 'scheduled', old.scheduled, new.scheduled
+                        UNION ALL SELECT 'recurrence', old.recurrence, new.recurrence) f
+                  WHERE f.old_value IS NOT f.new_value;
+              END;
+              CREATE TRIGGER history_tasks_ad AFTER DELETE ON tasks BEGIN
+                  INSERT INTO task_history (task_id, field, old_value, new_value, changed_at, source)
+                  VALUES (old.id, 'deleted', old.title, NULL,
+                          strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                          (SELECT source FROM change_source));
+              END;
+              CREATE TRIGGER history_task_tags_ai AFTER INSERT ON task_tags BEGIN
+                  INSERT INTO task_history (task_id, field, old_value, new_value, changed_at, source)
+                  SELECT new.task_id, 'tags', NULL, name,
+                         strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                         (SELECT source FROM change_source)
+                  FROM tags WHERE id = new.tag_id;
+              END;
+              CREATE TRIGGER history_task_tags_ad AFTER DELETE ON task_tags BEGIN
+                  INSERT INTO task_history (task_id, field, old_value, new_value, changed_at, source)
+                  SELECT old.task_id, 'tags', name, NULL,
+                         strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                         (SELECT source FROM change_source)
+                  FROM tags WHERE id = old.tag_id;
+              END;",
+    },
+    Migration {
+        version: 12,
+        name: "create_attachments",
+        // Files live in a content-addressed store keyed by `hash`; rows only
+        // link them to tasks, so several rows may share one file.
+        sql: "CREATE TABLE attachments (
+                  id TEXT PRIMARY KEY,
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  file_name TEXT NOT NULL,
+                  hash TEXT NOT NULL,
+                  size_bytes INTEGER NOT NULL,
+                  created_at TEXT NOT NULL
+              );
+              CREATE INDEX idx_attachments_task ON attachments(task_id);
+              CREATE INDEX idx_attachments_hash ON attachments(hash);",
+    },
+    Migration {
+        version: 13,
+        name: "add_task_trash",
+        // Deleting a task sets `deleted_at`; the row is purged from the trash
+        // later. Every query for live tasks filters on `deleted_at IS NULL`.
+        sql: "ALTER TABLE tasks ADD COLUMN deleted_at TEXT;
+              CREATE INDEX idx_tasks_deleted ON tasks(deleted_at);
+
+              CREATE TRIGGER history_tasks_trash AFTER UPDATE OF deleted_at ON tasks
+              WHEN old.deleted_at IS NOT new.deleted_at BEGIN
+                  INSERT INTO task_history (task_id, field, old_value, new_value, changed_at, source)
+                  VALUES (new.id, 'deleted_at', old.deleted_at, new.deleted_at,
+                          strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                          (SELECT source FROM change_source));
+              END;",
+    },
+    Migration {
+        version: 14,
+        name: "create_projects",
+        // Tasks keep naming their project in `tasks.project`; a projects row
+        // with that name (case-insensitively) adds the hierarchy and display
+        // settings. Existing project names are backfilled at the top level.
+        sql: "CREATE TABLE projects (
+                  id TEXT PRIMARY KEY,
+                  parent_id TEXT REFERENCES projects(id) ON DELETE SET NULL,
+                  kind TEXT NOT NULL DEFAULT 'project' CHECK (kind IN ('area', 'project')),
+                  name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                  color TEXT,
+                  position INTEGER NOT NULL DEFAULT 0,
+                  archived_at TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE INDEX idx_projects_parent ON projects(parent_id, position);
+              CREATE INDEX idx_tasks_project ON tasks(project COLLATE NOCASE);
+
+              INSERT INTO projects (id, kind, name, position, created_at, updated_at)
+              SELECT lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4'
+                         || substr(lower(hex(randomblob(2))), 2) || '-'
+                         || substr('89ab', 1 + abs(random()) % 4, 1)
+                         || substr(lower(hex(randomblob(2))), 2) || '-'
+                         || lower(hex(randomblob(6))),
+                     'project', MIN(project),
+                     ROW_NUMBER() OVER (ORDER BY MIN(project) COLLATE NOCASE) - 1,
+                     strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                     strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+              FROM tasks
+              WHERE project IS NOT NULL AND trim(project) <> ''
+              GROUP BY project COLLATE NOCASE;",
+    },
+    Migration {
+        version: 15,
+        name: "add_subtasks",
+        // `sort_key` orders a task among its siblings; see `order_key`.
+        sql: "ALTER TABLE tasks ADD COLUMN parent_id TEXT REFERENCES tasks(id) ON DELETE CASCADE;
+              ALTER TABLE tasks ADD COLUMN sort_key TEXT;
+              CREATE INDEX idx_tasks_parent ON tasks(parent_id, sort_key);",
+    },
+    Migration {
+        version: 16,
+        name: "create_task_dependencies",
+        // `task_id` can't start until `blocker_id` is done.
+        sql: "CREATE TABLE task_dependencies (
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  blocker_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  created_at TEXT NOT NULL,
+                  PRIMARY KEY (task_id, blocker_id),
+                  CHECK (task_id != blocker_id)
+              );
+              CREATE INDEX idx_task_dependencies_blocker ON task_dependencies(blocker_id);",
+    },
+    Migration {
+        version: 17,
+        name: "create_time_blocks",
+        // `day` is the local date of `starts_at` in `tz`, kept so a day's
+        // plan is one indexed lookup.
+        sql: "CREATE TABLE time_blocks (
+                  id TEXT PRIMARY KEY,
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  day TEXT NOT NULL,
+                  starts_at TEXT NOT NULL,
+                  duration_minutes INTEGER NOT NULL CHECK (duration_minutes > 0),
+                  tz TEXT NOT NULL,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE INDEX idx_time_blocks_day ON time_blocks(day, starts_at);
+              CREATE INDEX idx_time_blocks_task ON time_blocks(task_id);",
+    },
+    Migration {
+        version: 18,
+        name: "index_task_markdown_notes",
+        // Markdown notes live in files and are indexed from Rust; this only
+        // drops a task's notes from the index along with the task.
+        sql: "CREATE TRIGGER search_markdown_ad AFTER DELETE ON tasks BEGIN
+                  DELETE FROM search_index WHERE kind = 'markdown' AND ref_id = old.id;
+              END;",
+    },
+    Migration {
+        version: 19,
+        name: "create_crdt_clocks",
+        // The triggers that stamp local edits are generated at startup from
+        // the merged fields (see `crdt::install`).
+        sql: "CREATE TABLE crdt_state (
+                  id INTEGER PRIMARY KEY CHECK (id = 1),
+                  node TEXT NOT NULL,
+                  clock INTEGER NOT NULL
+              );
+              INSERT INTO crdt_state (id, node, clock) VALUES (1, lower(hex(randomblob(16))), 0);
+              CREATE TABLE task_clocks (
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  field TEXT NOT NULL,
+                  clock INTEGER NOT NULL,
+                  node TEXT NOT NULL,
+                  PRIMARY KEY (task_id, field)
+              );
+              CREATE INDEX idx_task_clocks_clock ON task_clocks(clock);
+              CREATE TABLE task_tag_clocks (
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  tag TEXT NOT NULL COLLATE NOCASE,
+                  present INTEGER NOT NULL,
+                  clock INTEGER NOT NULL,
+                  node TEXT NOT NULL,
+                  PRIMARY KEY (task_id, tag)
+              );
+              CREATE INDEX idx_task_tag_clocks_clock ON task_tag_clocks(clock);",
+    },
+    Migration {
+        version: 20,
+        name: "create_task_templates",
+        // A template's tags and subtasks are small lists that are only ever
+        // read and written whole, so they're kept as JSON.
+        sql: "ALTER TABLE tasks ADD COLUMN estimate_minutes INTEGER
+                  CHECK (estimate_minutes > 0);
+              CREATE TABLE task_templates (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                  title TEXT NOT NULL,
+                  description TEXT,
+                  project TEXT,
+                  priority INTEGER,
+                  estimate_minutes INTEGER CHECK (estimate_minutes > 0),
+                  tags TEXT NOT NULL DEFAULT '[]',
+                  subtasks TEXT NOT NULL DEFAULT '[]',
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );",
+    },
+    Migration {
+        version: 21,
+        name: "create_goals",
+        // goal_notices records which notifications were already sent, so
+        // each goal is reported met or at risk at most once per period.
+        sql: "CREATE TABLE goals (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  metric TEXT NOT NULL CHECK (metric IN ('tracked_minutes', 'completed_tasks')),
+                  target INTEGER NOT NULL CHECK (target > 0),
+                  period TEXT NOT NULL CHECK (period IN ('day', 'week')),
+                  project TEXT,
+                  tag TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE goal_notices (
+                  goal_id TEXT NOT NULL REFERENCES goals(id) ON DELETE CASCADE,
+                  period_start TEXT NOT NULL,
+                  status TEXT NOT NULL,
+                  PRIMARY KEY (goal_id, period_start, status)
+              );",
+    },
+    Migration {
+        version: 22,
+        name: "create_billing_rules",
+        // The row with an empty project applies to every project without
+        // its own rule, and to tasks with no project.
+        sql: "CREATE TABLE billing_rules (
+                  project TEXT PRIMARY KEY COLLATE NOCASE,
+                  round_minutes INTEGER NOT NULL DEFAULT 0
+                      CHECK (round_minutes BETWEEN 0 AND 1440),
+                  rounding TEXT NOT NULL DEFAULT 'nearest'
+                      CHECK (rounding IN ('nearest', 'up', 'down')),
+                  hourly_rate REAL CHECK (hourly_rate >= 0),
+                  updated_at TEXT NOT NULL
+              );",
+    },
+    Migration {
+        version: 23,
+        name: "create_caldav",
+        // caldav_items.task_id has no foreign key: a row outliving its task
+        // is how a local delete is found and sent to the server.
+        sql: "CREATE TABLE caldav_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  server_url TEXT NOT NULL,
+                  username TEXT NOT NULL,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE caldav_collections (
+                  id TEXT PRIMARY KEY,
+                  account_id TEXT NOT NULL REFERENCES caldav_accounts(id) ON DELETE CASCADE,
+                  url TEXT NOT NULL,
+                  name TEXT NOT NULL,
+                  project TEXT,
+                  enabled INTEGER NOT NULL DEFAULT 0,
+                  ctag TEXT,
+                  last_synced_at TEXT,
+                  UNIQUE (account_id, url)
+              );
+              CREATE TABLE caldav_items (
+                  collection_id TEXT NOT NULL REFERENCES caldav_collections(id) ON DELETE CASCADE,
+                  href TEXT NOT NULL,
+                  uid TEXT NOT NULL,
+                  etag TEXT,
+                  task_id TEXT NOT NULL,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (collection_id, href)
+              );
+              CREATE INDEX idx_caldav_items_task ON caldav_items(task_id);",
+    },
+    Migration {
+        version: 24,
+        name: "create_calendars",
+        // A calendar without an account is a subscribed .ics URL.
+        // calendar_events is a cache of occurrences, rebuilt on each fetch.
+        sql: "CREATE TABLE calendars (
+                  id TEXT PRIMARY KEY,
+                  account_id TEXT REFERENCES caldav_accounts(id) ON DELETE CASCADE,
+                  url TEXT NOT NULL,
+                  name TEXT NOT NULL,
+                  enabled INTEGER NOT NULL DEFAULT 1,
+                  fetched_at TEXT,
+                  last_error TEXT,
+                  UNIQUE (account_id, url)
+              );
+              CREATE TABLE calendar_events (
+                  calendar_id TEXT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE,
+                  uid TEXT NOT NULL,
+                  title TEXT NOT NULL,
+                  location TEXT,
+                  starts_at TEXT NOT NULL,
+                  ends_at TEXT NOT NULL,
+                  all_day INTEGER NOT NULL DEFAULT 0,
+                  busy INTEGER NOT NULL DEFAULT 1,
+                  PRIMARY KEY (calendar_id, uid, starts_at)
+              );
+              CREATE INDEX idx_calendar_events_starts ON calendar_events(starts_at);",
+    },
+    Migration {
+        version: 25,
+        name: "create_google_tasks",
+        // As with caldav_items, google_task_items.task_id has no foreign
+        // key so that local deletes can be found and uploaded.
+        sql: "CREATE TABLE google_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  client_id TEXT NOT NULL,
+                  client_secret TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE google_task_lists (
+                  id TEXT PRIMARY KEY,
+                  account_id TEXT NOT NULL REFERENCES google_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  title TEXT NOT NULL,
+                  project TEXT,
+                  enabled INTEGER NOT NULL DEFAULT 0,
+                  updated_min TEXT,
+                  last_synced_at TEXT,
+                  UNIQUE (account_id, remote_id)
+              );
+              CREATE TABLE google_task_items (
+                  task_list_id TEXT NOT NULL REFERENCES google_task_lists(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  etag TEXT,
+                  task_id TEXT NOT NULL,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (task_list_id, remote_id)
+              );
+              CREATE INDEX idx_google_task_items_task ON google_task_items(task_id);",
+    },
+    Migration {
+        version: 26,
+        name: "google_calendars",
+        // Google calendars hang off the Google account the way CalDAV ones
+        // hang off theirs. google_block_events.block_id has no foreign key
+        // so that deleted blocks can be found and their events removed.
+        sql: "ALTER TABLE calendars ADD COLUMN google_account_id TEXT
+                  REFERENCES google_accounts(id) ON DELETE CASCADE;
+              ALTER TABLE calendars ADD COLUMN sync_token TEXT;
+              ALTER TABLE calendars ADD COLUMN push_blocks INTEGER NOT NULL DEFAULT 0;
+              CREATE UNIQUE INDEX idx_calendars_google ON calendars(google_account_id, url);
+              CREATE TABLE google_block_events (
+                  block_id TEXT NOT NULL,
+                  calendar_id TEXT NOT NULL REFERENCES calendars(id) ON DELETE CASCADE,
+                  event_id TEXT NOT NULL,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (block_id, calendar_id)
+              );",
+    },
+    Migration {
+        version: 27,
+        name: "create_microsoft_todo",
+        // As with google_task_items, task_id has no foreign key so that
+        // local deletes can be found and uploaded.
+        sql: "CREATE TABLE microsoft_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  client_id TEXT NOT NULL,
+                  tenant TEXT NOT NULL,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE microsoft_task_lists (
+                  id TEXT PRIMARY KEY,
+                  account_id TEXT NOT NULL REFERENCES microsoft_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  title TEXT NOT NULL,
+                  project TEXT,
+                  enabled INTEGER NOT NULL DEFAULT 0,
+                  delta_link TEXT,
+                  last_synced_at TEXT,
+                  UNIQUE (account_id, remote_id)
+              );
+              CREATE TABLE microsoft_task_items (
+                  task_list_id TEXT NOT NULL REFERENCES microsoft_task_lists(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  etag TEXT,
+                  task_id TEXT NOT NULL,
+                  reminder_at TEXT,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (task_list_id, remote_id)
+              );
+              CREATE INDEX idx_microsoft_task_items_task ON microsoft_task_items(task_id);
+              CREATE TABLE microsoft_step_items (
+                  task_list_id TEXT NOT NULL REFERENCES microsoft_task_lists(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  parent_remote_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (task_list_id, remote_id)
+              );",
+    },
+    Migration {
+        version: 28,
+        name: "outlook_calendars",
+        // A calendar view's delta link only covers the days it was started
+        // for; sync_from records the first one so the link is dropped once
+        // the fetch window moves on. Block events aren't Google's alone now.
+        sql: "ALTER TABLE calendars ADD COLUMN microsoft_account_id TEXT
+                  REFERENCES microsoft_accounts(id) ON DELETE CASCADE;
+              ALTER TABLE calendars ADD COLUMN sync_from TEXT;
+              CREATE UNIQUE INDEX idx_calendars_microsoft ON calendars(microsoft_account_id, url);
+              ALTER TABLE google_block_events RENAME TO calendar_block_events;",
+    },
+    Migration {
+        version: 29,
+        name: "create_todoist",
+        // project is the local project a Todoist project or section's tasks
+        // are filed under. todoist_items keeps where each task was and what
+        // it was last synced as, so moves and completions are only sent
+        // when they happened here.
+        sql: "CREATE TABLE todoist_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  sync_token TEXT,
+                  last_synced_at TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE todoist_projects (
+                  account_id TEXT NOT NULL REFERENCES todoist_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  name TEXT NOT NULL,
+                  project TEXT NOT NULL,
+                  PRIMARY KEY (account_id, remote_id)
+              );
+              CREATE TABLE todoist_sections (
+                  account_id TEXT NOT NULL REFERENCES todoist_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  project_remote_id TEXT NOT NULL,
+                  name TEXT NOT NULL,
+                  project TEXT NOT NULL,
+                  PRIMARY KEY (account_id, remote_id)
+              );
+              CREATE TABLE todoist_items (
+                  account_id TEXT NOT NULL REFERENCES todoist_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  project_id TEXT NOT NULL,
+                  section_id TEXT,
+                  parent_id TEXT,
+                  due TEXT,
+                  recurring INTEGER NOT NULL DEFAULT 0,
+                  checked INTEGER NOT NULL DEFAULT 0,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (account_id, remote_id)
+              );
+              CREATE INDEX idx_todoist_items_task ON todoist_items(task_id);",
+    },
+    Migration {
+        version: 30,
+        name: "caldav_presets_and_tls",
+        // provider is set for accounts added through a preset such as
+        // Nextcloud. tls_certificate is a PEM certificate to trust besides
+        // the system's, for servers with a self-signed one.
+        sql: "ALTER TABLE caldav_accounts ADD COLUMN provider TEXT;
+              ALTER TABLE caldav_accounts ADD COLUMN tls_certificate TEXT;
+              ALTER TABLE caldav_accounts ADD COLUMN accept_invalid_certs INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 31,
+        name: "create_deck",
+        // Deck boards belong to a Nextcloud account set up over CalDAV,
+        // whose password they share. done_stack is the stack whose cards
+        // count as done. deck_cards keeps the card's lastModified from the
+        // last sync, to skip cards that didn't change.
+        sql: "CREATE TABLE deck_boards (
+                  id TEXT PRIMARY KEY,
+                  account_id TEXT NOT NULL REFERENCES caldav_accounts(id) ON DELETE CASCADE,
+                  remote_id INTEGER NOT NULL,
+                  title TEXT NOT NULL,
+                  project TEXT,
+                  enabled INTEGER NOT NULL DEFAULT 0,
+                  done_stack INTEGER,
+                  last_synced_at TEXT,
+                  UNIQUE (account_id, remote_id)
+              );
+              CREATE TABLE deck_stacks (
+                  board_id TEXT NOT NULL REFERENCES deck_boards(id) ON DELETE CASCADE,
+                  remote_id INTEGER NOT NULL,
+                  title TEXT NOT NULL,
+                  position INTEGER NOT NULL DEFAULT 0,
+                  PRIMARY KEY (board_id, remote_id)
+              );
+              CREATE TABLE deck_cards (
+                  board_id TEXT NOT NULL REFERENCES deck_boards(id) ON DELETE CASCADE,
+                  remote_id INTEGER NOT NULL,
+                  task_id TEXT NOT NULL,
+                  stack_id INTEGER NOT NULL,
+                  last_modified INTEGER NOT NULL,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (board_id, remote_id)
+              );
+              CREATE INDEX idx_deck_cards_task ON deck_cards(task_id);",
+    },
+    Migration {
+        version: 32,
+        name: "create_toggl",
+        // The API token is in the keyring. toggl_entries has no foreign key
+        // on time_entries: entries archived and restored keep their Toggl
+        // id instead of being pushed again. digest is a hash of what was
+        // last sent, to tell whether the entry or its task changed since.
+        sql: "CREATE TABLE toggl_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  workspace_id INTEGER NOT NULL,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE toggl_entries (
+                  account_id TEXT NOT NULL REFERENCES toggl_accounts(id) ON DELETE CASCADE,
+                  time_entry_id TEXT NOT NULL,
+                  remote_id INTEGER,
+                  status TEXT NOT NULL,
+                  error TEXT,
+                  digest TEXT,
+                  pushed_at TEXT NOT NULL,
+                  PRIMARY KEY (account_id, time_entry_id)
+              );",
+    },
+    Migration {
+        version: 33,
+        name: "create_clockify",
+        // Like the Toggl tables, with Clockify's string ids. A project
+        // without a row in clockify_projects goes to the Clockify project of
+        // the same name.
+        sql: "CREATE TABLE clockify_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  workspace_id TEXT NOT NULL,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE clockify_projects (
+                  account_id TEXT NOT NULL REFERENCES clockify_accounts(id) ON DELETE CASCADE,
+                  project TEXT NOT NULL COLLATE NOCASE,
+                  remote_id TEXT NOT NULL,
+                  PRIMARY KEY (account_id, project)
+              );
+              CREATE TABLE clockify_entries (
+                  account_id TEXT NOT NULL REFERENCES clockify_accounts(id) ON DELETE CASCADE,
+                  time_entry_id TEXT NOT NULL,
+                  remote_id TEXT,
+                  status TEXT NOT NULL,
+                  error TEXT,
+                  digest TEXT,
+                  pushed_at TEXT NOT NULL,
+                  PRIMARY KEY (account_id, time_entry_id)
+              );",
+    },
+    Migration {
+        version: 34,
+        name: "create_harvest",
+        // Like the Clockify tables, with Harvest's numeric ids and both the
+        // project and task each entry is filed under. The default project
+        // and task take time on projects with no mapping and no Harvest
+        // project of the same name. timestamps is set for Harvest accounts
+        // that track start and end times rather than durations.
+        sql: "CREATE TABLE harvest_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  harvest_account_id INTEGER NOT NULL,
+                  timestamps INTEGER NOT NULL DEFAULT 0,
+                  default_project_id INTEGER,
+                  default_task_id INTEGER,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE harvest_projects (
+                  account_id TEXT NOT NULL REFERENCES harvest_accounts(id) ON DELETE CASCADE,
+                  project TEXT NOT NULL COLLATE NOCASE,
+                  remote_project_id INTEGER NOT NULL,
+                  remote_task_id INTEGER NOT NULL,
+                  PRIMARY KEY (account_id, project)
+              );
+              CREATE TABLE harvest_entries (
+                  account_id TEXT NOT NULL REFERENCES harvest_accounts(id) ON DELETE CASCADE,
+                  time_entry_id TEXT NOT NULL,
+                  remote_id INTEGER,
+                  status TEXT NOT NULL,
+                  error TEXT,
+                  digest TEXT,
+                  billable INTEGER,
+                  invoiced INTEGER NOT NULL DEFAULT 0,
+                  pushed_at TEXT NOT NULL,
+                  PRIMARY KEY (account_id, time_entry_id)
+              );",
+    },
+    Migration {
+        version: 35,
+        name: "create_jira",
+        // username is NULL for Jira Server accounts signing in with a
+        // personal access token. project_keys, comma-separated, limits which
+        // issue keys in task titles count. A jira_entries row can exist
+        // before its first push to hold an adjusted start time, so
+        // pushed_at is nullable; issue_key is the issue the worklog is on.
+        sql: "CREATE TABLE jira_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  base_url TEXT NOT NULL,
+                  username TEXT,
+                  cloud INTEGER NOT NULL DEFAULT 0,
+                  project_keys TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE jira_entries (
+                  account_id TEXT NOT NULL REFERENCES jira_accounts(id) ON DELETE CASCADE,
+                  time_entry_id TEXT NOT NULL,
+                  issue_key TEXT,
+                  remote_id TEXT,
+                  status TEXT NOT NULL,
+                  error TEXT,
+                  digest TEXT,
+                  started_override TEXT,
+                  pushed_at TEXT,
+                  PRIMARY KEY (account_id, time_entry_id)
+              );",
+    },
+    Migration {
+        version: 36,
+        name: "create_github",
+        // The token is in the keyring. project is where imported tasks go,
+        // or NULL for a project named after each repository. github_items
+        // tracks the issues and review requests still open, by GraphQL
+        // node id; kind is 'issue' or 'review'. updated_at and state are
+        // GitHub's from the last sync, to skip items that didn't change.
+        sql: "CREATE TABLE github_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  api_url TEXT NOT NULL,
+                  project TEXT,
+                  include_reviews INTEGER NOT NULL DEFAULT 1,
+                  last_synced_at TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE github_items (
+                  account_id TEXT NOT NULL REFERENCES github_accounts(id) ON DELETE CASCADE,
+                  node_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  kind TEXT NOT NULL,
+                  url TEXT NOT NULL,
+                  state TEXT NOT NULL,
+                  updated_at TEXT NOT NULL,
+                  PRIMARY KEY (account_id, node_id)
+              );",
+    },
+    Migration {
+        version: 37,
+        name: "create_gitlab",
+        // The token is in the keyring. base_url is GitLab's or a
+        // self-hosted server's, without /api/v4. gitlab_items tracks the
+        // open issues assigned to the user, keyed 'issue:<id>', and the
+        // pending todos, keyed 'todo:<id>'. project_id and iid are where
+        // an issue is fetched from once it stops being listed.
+        sql: "CREATE TABLE gitlab_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  base_url TEXT NOT NULL,
+                  project TEXT,
+                  include_todos INTEGER NOT NULL DEFAULT 1,
+                  last_synced_at TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE gitlab_items (
+                  account_id TEXT NOT NULL REFERENCES gitlab_accounts(id) ON DELETE CASCADE,
+                  item_key TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  kind TEXT NOT NULL,
+                  url TEXT NOT NULL,
+                  project_id INTEGER,
+                  iid INTEGER,
+                  updated_at TEXT NOT NULL,
+                  PRIMARY KEY (account_id, item_key)
+              );",
+    },
+    Migration {
+        version: 38,
+        name: "create_trello",
+        // The token is in the keyring; the API key isn't a secret. A board
+        // is imported once, then again on every sync while ongoing is set.
+        // Cards in done_list count as done. trello_cards holds cards and
+        // their checklist items, the items with the card they're on as
+        // parent_remote_id. last_activity is the card's dateLastActivity at
+        // the last import, to skip cards that didn't change.
+        sql: "CREATE TABLE trello_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  api_key TEXT NOT NULL,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE trello_boards (
+                  id TEXT PRIMARY KEY,
+                  account_id TEXT NOT NULL REFERENCES trello_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  title TEXT NOT NULL,
+                  project TEXT,
+                  done_list TEXT,
+                  ongoing INTEGER NOT NULL DEFAULT 0,
+                  imported_at TEXT,
+                  last_synced_at TEXT,
+                  UNIQUE (account_id, remote_id)
+              );
+              CREATE TABLE trello_lists (
+                  board_id TEXT NOT NULL REFERENCES trello_boards(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  title TEXT NOT NULL,
+                  position REAL NOT NULL DEFAULT 0,
+                  PRIMARY KEY (board_id, remote_id)
+              );
+              CREATE TABLE trello_cards (
+                  board_id TEXT NOT NULL REFERENCES trello_boards(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  parent_remote_id TEXT,
+                  last_activity TEXT NOT NULL,
+                  PRIMARY KEY (board_id, remote_id)
+              );",
+    },
+    Migration {
+        version: 39,
+        name: "create_notion",
+        // The integration token is in the keyring. notion_properties is a
+        // database's schema as of the last refresh, options being a JSON
+        // array of the option names of select, status and multi-select
+        // properties. The *_property columns name the properties mapped to
+        // a task's status, due date and tags; done_value and open_value are
+        // the status options for done and open tasks. notion_pages.digest
+        // is a hash of the mapped values at the last sync, to skip pages
+        // that didn't change; synced_at is the task's updated_at then.
+        sql: "CREATE TABLE notion_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  workspace TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE notion_databases (
+                  id TEXT PRIMARY KEY,
+                  account_id TEXT NOT NULL REFERENCES notion_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  title TEXT NOT NULL,
+                  project TEXT,
+                  enabled INTEGER NOT NULL DEFAULT 0,
+                  status_property TEXT,
+                  done_value TEXT,
+                  open_value TEXT,
+                  date_property TEXT,
+                  tags_property TEXT,
+                  last_synced_at TEXT,
+                  UNIQUE (account_id, remote_id)
+              );
+              CREATE TABLE notion_properties (
+                  database_id TEXT NOT NULL REFERENCES notion_databases(id) ON DELETE CASCADE,
+                  name TEXT NOT NULL,
+                  kind TEXT NOT NULL,
+                  options TEXT NOT NULL DEFAULT '[]',
+                  PRIMARY KEY (database_id, name)
+              );
+              CREATE TABLE notion_pages (
+                  database_id TEXT NOT NULL REFERENCES notion_databases(id) ON DELETE CASCADE,
+                  page_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  digest TEXT NOT NULL,
+                  synced_at TEXT NOT NULL,
+                  PRIMARY KEY (database_id, page_id)
+              );",
+    },
+    Migration {
+        version: 40,
+        name: "create_taskwarrior_tasks",
+        // What Taskwarrior keeps that tasks don't, for tasks imported from
+        // it: the uuid it knows the task by (the task's id unless that was
+        // taken), its annotations with their timestamps (a JSON array), wait and
+        // start, and any other attributes such as UDAs as a JSON object.
+        // All of it goes back out on export.
+        sql: "CREATE TABLE taskwarrior_tasks (
+                  task_id TEXT PRIMARY KEY REFERENCES tasks(id) ON DELETE CASCADE,
+                  uuid TEXT NOT NULL UNIQUE,
+                  annotations TEXT NOT NULL DEFAULT '[]',
+                  wait TEXT,
+                  start TEXT,
+                  extra TEXT NOT NULL DEFAULT '{}'
+              );",
+    },
+    Migration {
+        version: 41,
+        name: "create_todotxt",
+        // The linked todo.txt, and optionally done.txt, file; one pair at a
+        // time. todotxt_lines is each synced task's line as last read or
+        // written, in_done telling which file it's in and position its
+        // index there, to tell which lines were edited outside the app and
+        // follow them when they are.
+        sql: "CREATE TABLE todotxt_files (
+                  id TEXT PRIMARY KEY,
+                  todo_path TEXT NOT NULL,
+                  done_path TEXT,
+                  last_synced_at TEXT,
+                  created_at TEXT NOT NULL
+              );
+              CREATE TABLE todotxt_lines (
+                  file_id TEXT NOT NULL REFERENCES todotxt_files(id) ON DELETE CASCADE,
+                  task_id TEXT NOT NULL,
+                  in_done INTEGER NOT NULL DEFAULT 0,
+                  position INTEGER NOT NULL,
+                  line TEXT NOT NULL,
+                  PRIMARY KEY (file_id, task_id)
+              );",
+    },
+    Migration {
+        version: 42,
+        name: "create_org_files",
+        // The .org files whose TODO headings are synced. org_headings is each
+        // synced heading's headline and planning line as last read or
+        // written, and its index among the file's TODO headings, to tell
+        // which were edited outside the app and follow them when they are.
+        sql: "CREATE TABLE org_files (
+                  id TEXT PRIMARY KEY,
+                  path TEXT NOT NULL UNIQUE,
+                  last_synced_at TEXT,
+                  created_at TEXT NOT NULL
+              );
+              CREATE TABLE org_headings (
+                  file_id TEXT NOT NULL REFERENCES org_files(id) ON DELETE CASCADE,
+                  task_id TEXT NOT NULL,
+                  position INTEGER NOT NULL,
+                  heading TEXT NOT NULL,
+                  PRIMARY KEY (file_id, task_id)
+              );",
+    },
+    Migration {
+        version: 43,
+        name: "calendar_feeds",
+        // Any calendar can be colored; refresh_minutes is how often a
+        // subscribed .ics URL is polled. http_cache keeps the last response
+        // of such URLs with its validators, so a poll that finds the feed
+        // unchanged costs a 304 and the cached body is parsed again for the
+        // moving window.
+        sql: "ALTER TABLE calendars ADD COLUMN color TEXT;
+              ALTER TABLE calendars ADD COLUMN refresh_minutes INTEGER;
+              CREATE TABLE http_cache (
+                  url TEXT PRIMARY KEY,
+                  etag TEXT,
+                  last_modified TEXT,
+                  body TEXT NOT NULL,
+                  fetched_at TEXT NOT NULL,
+                  expires_at TEXT
+              );",
+    },    Migration {
+        version: 44,
+        name: "webhooks",
+        // URLs posted to when the events they list happen. Each post waits
+        // in webhook_deliveries until it gets a 2xx, so retries outlive a
+        // restart; the outcome of the latest attempt is kept on the hook.
+        sql: "CREATE TABLE webhooks (
+                  id TEXT PRIMARY KEY,
+                  url TEXT NOT NULL,
+                  events TEXT NOT NULL,
+                  secret TEXT NOT NULL,
+                  enabled INTEGER NOT NULL DEFAULT 1,
+                  last_status INTEGER,
+                  last_error TEXT,
+                  last_delivered_at TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE webhook_deliveries (
+                  id TEXT PRIMARY KEY,
+                  webhook_id TEXT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+                  event TEXT NOT NULL,
+                  payload TEXT NOT NULL,
+                  attempts INTEGER NOT NULL DEFAULT 0,
+                  next_attempt_at TEXT NOT NULL,
+                  created_at TEXT NOT NULL
+              );
+              CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries(next_attempt_at);",
+    },
+    Migration {
+        version: 45,
+        name: "create_imap_accounts",
+        // Mailboxes whose new messages become tasks. last_uid is the
+        // highest UID turned into a task under uid_validity; when the server
+        // resets UIDs, the Message-ID kept in external_refs stops a message
+        // from coming in twice.
+        sql: "CREATE TABLE imap_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  host TEXT NOT NULL,
+                  port INTEGER NOT NULL,
+                  username TEXT NOT NULL,
+                  folder TEXT NOT NULL,
+                  to_filter TEXT,
+                  project TEXT,
+                  enabled INTEGER NOT NULL DEFAULT 1,
+                  uid_validity INTEGER,
+                  last_uid INTEGER NOT NULL DEFAULT 0,
+                  checked_at TEXT,
+                  last_error TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );",
+    },
+    Migration {
+        version: 46,
+        name: "create_sync_conflicts",
+        // What a two-way sync does with an item changed on both sides since
+        // it last synced, per provider; missing means newest wins. Conflicts
+        // queued for the user keep the task as the remote version would
+        // leave it, as JSON, beside the unchanged local task.
+        sql: "CREATE TABLE sync_policies (
+                  provider TEXT PRIMARY KEY,
+                  policy TEXT NOT NULL
+              );
+              CREATE TABLE sync_conflicts (
+                  id TEXT PRIMARY KEY,
+                  provider TEXT NOT NULL,
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  remote TEXT NOT NULL,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL,
+                  UNIQUE (provider, task_id)
+              );",
+    },
+    Migration {
+        version: 47,
+        name: "create_sync_status",
+        // How each provider's last sync went, for the account status page:
+        // when it started and finished, when one last finished without
+        // errors, how many items it touched and a summary of its errors.
+        sql: "CREATE TABLE sync_status (
+                  provider TEXT PRIMARY KEY,
+                  last_started_at TEXT,
+                  last_finished_at TEXT,
+                  last_success_at TEXT,
+                  processed INTEGER NOT NULL DEFAULT 0,
+                  error_count INTEGER NOT NULL DEFAULT 0,
+                  last_error TEXT
+              );",
+    },
+    Migration {
+        version: 48,
+        name: "create_sync_outbox",
+        // Tasks changed here since providers last took them, one row each in
+        // the order they first changed: a task created and deleted before
+        // any provider took it leaves nothing, and an edit after a delete
+        // turns it back into an update. Changes made by syncs aren't queued.
+        // A row is tried a few times, then kept as failed until retried or
+        // edited again.
+        sql: "CREATE TABLE sync_outbox (
+                  id INTEGER PRIMARY KEY AUTOINCREMENT,
+                  task_id TEXT NOT NULL UNIQUE,
+                  op TEXT NOT NULL,
+                  queued_at TEXT NOT NULL,
+                  attempts INTEGER NOT NULL DEFAULT 0,
+                  last_error TEXT,
+                  failed_at TEXT
+              );
+              CREATE TRIGGER sync_outbox_ai AFTER INSERT ON tasks
+              WHEN (SELECT source FROM change_source) <> 'sync' BEGIN
+                  INSERT OR REPLACE INTO sync_outbox (task_id, op, queued_at)
+                  VALUES (new.id, 'create', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+              END;
+              CREATE TRIGGER sync_outbox_au AFTER UPDATE ON tasks
+              WHEN new.deleted_at IS NULL
+                  AND (SELECT source FROM change_source) <> 'sync' BEGIN
+                  INSERT INTO sync_outbox (task_id, op, queued_at)
+                  VALUES (new.id, 'update', strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+                  ON CONFLICT(task_id) DO UPDATE SET
+                      op = CASE op WHEN 'delete' THEN 'update' ELSE op END,
+                      attempts = 0,
+                      last_error = NULL,
+                      failed_at = NULL;
+              END;
+              CREATE TRIGGER sync_outbox_trash AFTER UPDATE OF deleted_at ON tasks
+              WHEN old.deleted_at IS NULL AND new.deleted_at IS NOT NULL
+                  AND (SELECT source FROM change_source) <> 'sync' BEGIN
+                  INSERT INTO sync_outbox (task_id, op, queued_at)
+                  SELECT new.id, 'delete', strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                  WHERE NOT EXISTS (SELECT 1 FROM sync_outbox WHERE task_id = new.id);
+                  DELETE FROM sync_outbox WHERE task_id = new.id AND op = 'create';
+                  UPDATE sync_outbox SET op = 'delete', attempts = 0, last_error = NULL,
+                      failed_at = NULL
+                  WHERE task_id = new.id;
+              END;
+              CREATE TRIGGER sync_outbox_ad AFTER DELETE ON tasks BEGIN
+                  DELETE FROM sync_outbox WHERE task_id = old.id AND op = 'create';
+              END;",
+    },
+    Migration {
+        version: 49,
+        name: "create_webdav_sync_files",
+        // Device bundles in the WebDAV sync folder: the etag of each other
+        // device's bundle when it was last merged, so unchanged ones aren't
+        // fetched again, and the local clock this device's own was last
+        // written at.
+        sql: "CREATE TABLE webdav_sync_files (
+                  name TEXT PRIMARY KEY,
+                  etag TEXT,
+                  clock INTEGER NOT NULL DEFAULT 0,
+                  synced_at TEXT NOT NULL
+              );",
+    },
+    Migration {
+        version: 50,
+        name: "create_change_log_files",
+        // Per-device change logs in the change log folder: how far each
+        // other device's log has been read, in bytes, and the local clock
+        // this device's own was last appended at.
+        sql: "CREATE TABLE change_log_files (
+                  name TEXT PRIMARY KEY,
+                  offset INTEGER NOT NULL DEFAULT 0,
+                  clock INTEGER NOT NULL DEFAULT 0
+              );",
+    },
+    Migration {
+        version: 51,
+        name: "add_sync_status_throttled_until",
+        // Set when the provider limited requests during its last sync, to
+        // when it's paused until.
+        sql: "ALTER TABLE sync_status ADD COLUMN throttled_until TEXT;",
+    },
+    Migration {
+        version: 52,
+        name: "create_sync_modes",
+        // Whether each provider imports, exports or syncs both ways; missing
+        // means both ways where it can push, else import.
+        sql: "CREATE TABLE sync_modes (
+                  provider TEXT PRIMARY KEY,
+                  mode TEXT NOT NULL
+              );",
+    },
+    Migration {
+        version: 53,
+        name: "add_todoist_projects_enabled",
+        // Items in a project left out of syncs are neither brought in nor
+        // uploaded.
+        sql: "ALTER TABLE todoist_projects ADD COLUMN enabled INTEGER NOT NULL DEFAULT 1;",
+    },
+    Migration {
+        version: 54,
+        name: "create_ews",
+        // Exchange accounts reached over EWS; the password is in the
+        // keyring. ews_items tracks imported tasks by item id, with the
+        // change key they had at the last sync. Calendar folders are kept
+        // with the others, by folder id.
+        sql: "CREATE TABLE ews_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  server_url TEXT NOT NULL,
+                  username TEXT NOT NULL,
+                  project TEXT,
+                  tls_certificate TEXT,
+                  accept_invalid_certs INTEGER NOT NULL DEFAULT 0,
+                  last_synced_at TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE ews_items (
+                  account_id TEXT NOT NULL REFERENCES ews_accounts(id) ON DELETE CASCADE,
+                  item_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  change_key TEXT NOT NULL,
+                  PRIMARY KEY (account_id, item_id)
+              );
+              ALTER TABLE calendars ADD COLUMN ews_account_id TEXT
+                  REFERENCES ews_accounts(id) ON DELETE CASCADE;
+              CREATE UNIQUE INDEX idx_calendars_ews ON calendars(ews_account_id, url);",
+    },
+    Migration {
+        version: 55,
+        name: "create_asana",
+        // Asana accounts and the My Tasks list they import; the personal
+        // access token or OAuth refresh token is in the keyring, and
+        // client_id is only set for OAuth. asana_tasks tracks imported
+        // tasks by gid, with whether they were complete, the section whose
+        // tag they were given and when Asana last changed them.
+        sql: "CREATE TABLE asana_accounts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  user_id TEXT NOT NULL,
+                  workspace_id TEXT NOT NULL,
+                  workspace_name TEXT NOT NULL,
+                  task_list_id TEXT NOT NULL,
+                  client_id TEXT,
+                  client_secret TEXT,
+                  project TEXT,
+                  last_synced_at TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );
+              CREATE TABLE asana_tasks (
+                  account_id TEXT NOT NULL REFERENCES asana_accounts(id) ON DELETE CASCADE,
+                  remote_id TEXT NOT NULL,
+                  task_id TEXT NOT NULL,
+                  completed INTEGER NOT NULL DEFAULT 0,
+                  section TEXT,
+                  modified_at TEXT NOT NULL,
+                  PRIMARY KEY (account_id, remote_id)
+              );",
+    },
+    Migration {
+        version: 56,
+        name: "create_location_settings",
+        // Whether the user allowed asking the system where they are, the
+        // coordinates they set by hand, if any, and the system's last fix.
+        sql: "CREATE TABLE location_settings (
+                  id INTEGER PRIMARY KEY CHECK (id = 1),
+                  consent INTEGER NOT NULL DEFAULT 0,
+                  manual_latitude REAL,
+                  manual_longitude REAL,
+                  manual_label TEXT,
+                  latitude REAL,
+                  longitude REAL,
+                  accuracy_meters REAL,
+                  located_at TEXT
+              );
+              INSERT INTO location_settings (id, consent) VALUES (1, 0);",
+    },
+    Migration {
+        version: 57,
+        name: "create_solar_reminders",
+        // Reminders relative to a sun event, e.g. 30 minutes before sunset.
+        // Each keeps one reminder row, moved to the next occurrence as the
+        // sun times shift day to day.
+        sql: "CREATE TABLE solar_reminders (
+                  id TEXT PRIMARY KEY,
+                  task_id TEXT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+                  event TEXT NOT NULL,
+                  offset_minutes INTEGER NOT NULL DEFAULT 0,
+                  created_at TEXT NOT NULL
+              );
+              CREATE INDEX idx_solar_reminders_task ON solar_reminders(task_id);
+              ALTER TABLE reminders
+                  ADD COLUMN solar_id TEXT REFERENCES solar_reminders(id) ON DELETE CASCADE;
+              CREATE INDEX idx_reminders_solar ON reminders(solar_id);",
+    },
+    Migration {
+        version: 58,
+        name: "create_theme_schedule",
+        // When to switch between the light and dark palettes: `system`
+        // follows GTK's preference, `sun` switches at sunrise and sunset,
+        // `fixed` at the times set here (local `HH:MM`).
+        sql: "CREATE TABLE theme_schedule (
+                  id INTEGER PRIMARY KEY CHECK (id = 1),
+                  mode TEXT NOT NULL DEFAULT 'system',
+                  light_at TEXT,
+                  dark_at TEXT
+              );
+              INSERT INTO theme_schedule (id, mode) VALUES (1, 'system');",
+    },
+    Migration {
+        version: 59,
+        name: "create_usage_counts",
+        // Opt-in usage statistics: how often each feature was used on each
+        // local day and, for timed ones, how long it took in all. Counts
+        // only, never what was worked on; not journaled, so never synced.
+        sql: "CREATE TABLE usage_counts (
+                  day TEXT NOT NULL,
+                  feature TEXT NOT NULL,
+                  count INTEGER NOT NULL DEFAULT 0,
+                  timed INTEGER NOT NULL DEFAULT 0,
+                  total_ms INTEGER NOT NULL DEFAULT 0,
+                  PRIMARY KEY (day, feature)
+              );",
+    },
+    Migration {
+        version: 60,
+        name: "create_scripts",
+        // User Rhai scripts run when their event happens. allowed_hosts is
+        // the JSON list of hosts the script may call over HTTP; the latest
+        // run's time and error are kept on the script.
+        sql: "CREATE TABLE scripts (
+                  id TEXT PRIMARY KEY,
+                  name TEXT NOT NULL,
+                  event TEXT NOT NULL,
+                  source TEXT NOT NULL,
+                  allowed_hosts TEXT NOT NULL DEFAULT '[]',
+                  enabled INTEGER NOT NULL DEFAULT 1,
+                  last_run_at TEXT,
+                  last_error TEXT,
+                  created_at TEXT NOT NULL,
+                  updated_at TEXT NOT NULL
+              );",
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaInfo {
+    pub version: i64,
+    pub latest_version: i64,
+    pub applied: Vec<AppliedMigration>,
+}
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn ensure_version_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+             version INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             applied_at TEXT NOT NULL
+         );",
+    )
+}
+
+pub fn current_version(conn: &Connection) -> rusqlite::Result<i64> {
+    ensure_version_table(conn)?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Apply every pending migration, each in its own transaction so a failure
+/// leaves the database at the last good version.
+pub fn run_migrations(conn: &mut Connection) -> CommandResult<i64> {
+    let current = current_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(CommandError::invalid(format!(
+            "Database schema version {current} is newer than this build supports ({latest})"
+        )));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql).map_err(|e| {
+            format!(
+                "Migration {} ({}) failed: {e}",
+                migration.version, migration.name
+            )
+        })?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, now_utc()],
+        )?;
+        tx.commit()?;
+    }
+
+    Ok(latest)
+}
+
+pub fn schema_info(conn: &Connection) -> rusqlite::Result<SchemaInfo> {
+    let version = current_version(conn)?;
+    let mut stmt =
+        conn.prepare("SELECT version, name, applied_at FROM schema_version ORDER BY version")?;
+    let applied = stmt
+        .query_map([], |row| {
+            Ok(AppliedMigration {
+                version: row.get(0)?,
+                name: row.get(1)?,
+                applied_at: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(SchemaInfo {
+        version,
+        latest_version: latest_version(),
+        applied,
+    })
+}
+
+#[tauri::command]
+pub fn get_schema_info(db: State<'_, Db>) -> CommandResult<SchemaInfo> {
+    db.with_conn(|conn| schema_info(conn))
+}
@@ -0,0 +1,617 @@
+/// An email message, reduced to what a task needs. Header values are
+/// decoded; `text` is the first plain-text body, or the HTML one with its
+/// tags stripped when there's no plain text.
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    pub subject: String,
+    pub from: String,
+    pub message_id: Option<String>,
+    pub text: String,
+    pub attachments: Vec<MailAttachment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MailAttachment {
+    pub file_name: String,
+    pub content: Vec<u8>,
+}
+
+/// A MIME header value split into its main value (lowercased) and its
+/// parameters (keys lowercased, values unquoted).
+struct HeaderValue {
+    value: String,
+    params: Vec<(String, String)>,
+}
+
+impl HeaderValue {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Split an entity at the blank line ending its header.
+fn split_entity(raw: &[u8]) -> (&[u8], &[u8]) {
+    for (index, window) in raw.windows(2).enumerate() {
+        if window == b"\n\n" {
+            return (&raw[..index], &raw[index + 2..]);
+        }
+        if raw[index..].starts_with(b"\r\n\r\n") {
+            return (&raw[..index], &raw[index + 4..]);
+        }
+    }
+    (raw, &[])
+}
+
+/// Header fields as (lowercased name, unfolded raw value), in order.
+fn parse_headers(head: &[u8]) -> Vec<(String, String)> {
+    let head = String::from_utf8_lossy(head);
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.split('\n') {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Percent-decode an RFC 2231 extended parameter (`utf-8''na%C3%AFve`).
+fn decode_extended(value: &str) -> String {
+    let mut parts = value.splitn(3, '\'');
+    let (charset, _language, encoded) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(charset), Some(language), Some(encoded)) => (charset, language, encoded),
+        _ => ("utf-8", "", value),
+    };
+    let mut bytes = Vec::new();
+    let raw = encoded.as_bytes();
+    let mut i = 0;
+    while i < raw.len() {
+        let hex = raw
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (raw[i], hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decode_charset(&bytes, charset)
+}
+
+fn parse_header_value(raw: &str) -> HeaderValue {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in raw.chars() {
+        match c {
+            _ if escaped => {
+                current.push(c);
+                escaped = false;
+            }
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => pieces.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    pieces.push(current);
+    let mut pieces = pieces.into_iter();
+    let value = pieces
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let mut params: Vec<(String, String)> = Vec::new();
+    for piece in pieces {
+        let Some((key, value)) = piece.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim().to_string();
+        // `name*=` is encoded; `name*0=`, `name*1=`… are continuations.
+        let (name, value) = match key.split_once('*') {
+            Some((name, "")) => (name.to_string(), decode_extended(&value)),
+            Some((name, "0*")) => (name.to_string(), decode_extended(&value)),
+            Some((name, section)) => {
+                let value = if section.ends_with('*') {
+                    decode_extended(&format!("''{value}"))
+                } else {
+                    value
+                };
+                if let Some((_, existing)) = params.iter_mut().find(|(k, _)| *k == name) {
+                    existing.push_str(&value);
+                    continue;
+                }
+                (name.to_string(), value)
+            }
+            None => (key, value),
+        };
+        params.retain(|(k, _)| *k != name);
+        params.push((name, value));
+    }
+    HeaderValue { value, params }
+}
+
+fn base64_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode base64, skipping line breaks and anything else outside the
+/// alphabet, as mail bodies need.
+pub fn decode_base64(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in input {
+        if byte == b'=' {
+            break;
+        }
+        let Some(value) = base64_value(byte) else {
+            continue;
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    out
+}
+
+/// Decode quoted-printable. In encoded words (`header`) `_` is a space.
+fn decode_quoted_printable(input: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'=' => {
+                let rest = &input[i + 1..];
+                if rest.starts_with(b"\r\n") {
+                    i += 3;
+                } else if rest.starts_with(b"\n") {
+                    i += 2;
+                } else if let Some(byte) = rest
+                    .get(..2)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                {
+                    out.push(byte);
+                    i += 3;
+                } else {
+                    out.push(b'=');
+                    i += 1;
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Bytes in `charset` as a string. UTF-8 (and ASCII) and Latin-1 are read
+/// exactly; anything else is read as UTF-8 with bad bytes replaced.
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.trim().to_ascii_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "latin-1" | "windows-1252" | "cp1252" => {
+            bytes.iter().map(|&b| char::from(b)).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decode RFC 2047 encoded words (`=?utf-8?B?...?=`) in a header value.
+/// Whitespace between two encoded words is dropped, as the RFC asks.
+pub fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let decoded = (|| {
+            let word = &rest[start + 2..];
+            let (charset, after) = word.split_once('?')?;
+            let (encoding, after) = after.split_once('?')?;
+            let (text, _) = after.split_once("?=")?;
+            let length = charset.len() + encoding.len() + text.len() + 2;
+            let bytes = match encoding.to_ascii_lowercase().as_str() {
+                "b" => decode_base64(text.as_bytes()),
+                "q" => decode_quoted_printable(text.as_bytes(), true),
+                _ => return None,
+            };
+            // RFC 2231 allows a language after the charset.
+            let charset = charset.split('*').next().unwrap_or(charset);
+            Some((decode_charset(&bytes, charset), length))
+        })();
+        let before = &rest[..start];
+        match decoded {
+            Some((text, length)) => {
+                if !(after_word && before.trim().is_empty()) {
+                    out.push_str(before);
+                }
+                out.push_str(&text);
+                rest = &rest[start + 2 + length + 2..];
+                after_word = true;
+            }
+            None => {
+                out.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                after_word = false;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_body(body: &[u8], encoding: Option<&str>) -> Vec<u8> {
+    match encoding.map(|e| e.trim().to_ascii_lowercase()).as_deref() {
+        Some("base64") => decode_base64(body),
+        Some("quoted-printable") => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    }
+}
+
+/// The parts of a multipart body, between its `boundary` lines.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut offset = 0;
+    for line in body.split_inclusive(|&b| b == b'\n') {
+        let trimmed = line.strip_suffix(b"\n").unwrap_or(line);
+        let trimmed = trimmed.strip_suffix(b"\r").unwrap_or(trimmed);
+        if trimmed.starts_with(delimiter.as_bytes()) {
+            if let Some(start) = start {
+                // The line break before a delimiter belongs to it.
+                let mut end = offset;
+                if body[..end].ends_with(b"\n") {
+                    end -= 1;
+                }
+                if body[..end].ends_with(b"\r") {
+                    end -= 1;
+                }
+                parts.push(&body[start..end.max(start)]);
+            }
+            if trimmed[delimiter.len()..].starts_with(b"--") {
+                return parts;
+            }
+            start = Some(offset + line.len());
+        }
+        offset += line.len();
+    }
+    if let Some(start) = start {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+/// Text of an HTML body: tags dropped, block ends as line breaks, common
+/// entities decoded, runs of blank lines collapsed.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    let mut skipping: Option<&str> = None;
+    while let Some(open) = rest.find('<') {
+        if skipping.is_none() {
+            text.push_str(&rest[..open]);
+        }
+        let Some(close) = rest[open..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[open + 1..open + close].trim().to_ascii_lowercase();
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        match skipping {
+            Some(until) if tag.starts_with('/') && name == until => skipping = None,
+            Some(_) => {}
+            None if matches!(name.as_str(), "style" | "script" | "head")
+                && !tag.starts_with('/') =>
+            {
+                skipping = Some(match name.as_str() {
+                    "style" => "style",
+                    "script" => "script",
+                    _ => "head",
+                });
+            }
+            None if matches!(
+                name.as_str(),
+                "br" | "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3" | "h4" | "blockquote"
+            ) =>
+            {
+                text.push('\n');
+            }
+            None => {}
+        }
+        rest = &rest[open + close + 1..];
+    }
+    if skipping.is_none() {
+        text.push_str(rest);
+    }
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let mut out = String::new();
+    let mut blank = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank += 1;
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank > 0 { "\n\n" } else { "\n" });
+        }
+        blank = 0;
+        out.push_str(line);
+    }
+    out
+}
+
+/// Reduce a sender-chosen file name to one harmless path component: the
+/// part after the last separator, without control characters, and never
+/// `.` or `..`.
+pub fn safe_file_name(name: &str) -> String {
+    let last = name.rsplit(['/', '\\', ':']).next().unwrap_or_default();
+    let cleaned: String = last.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim();
+    if cleaned.trim_matches('.').is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// Walk an entity and its parts, collecting bodies and attachments.
+fn walk(raw: &[u8], default_type: &str, html: &mut Option<String>, message: &mut Message) {
+    let (head, body) = split_entity(raw);
+    let headers = parse_headers(head);
+    let content_type = parse_header_value(header(&headers, "content-type").unwrap_or(default_type));
+    let disposition = header(&headers, "content-disposition").map(parse_header_value);
+    let file_name = disposition
+        .as_ref()
+        .and_then(|d| d.param("filename"))
+        .or_else(|| content_type.param("name"))
+        .map(decode_words)
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| safe_file_name(&name));
+
+    if let Some(boundary) = content_type
+        .value
+        .starts_with("multipart/")
+        .then(|| content_type.param("boundary"))
+        .flatten()
+    {
+        let default = if content_type.value == "multipart/digest" {
+            "message/rfc822"
+        } else {
+            "text/plain"
+        };
+        for part in split_multipart(body, boundary) {
+            walk(part, default, html, message);
+        }
+        return;
+    }
+
+    let content = decode_body(body, header(&headers, "content-transfer-encoding"));
+    let attached = disposition
+        .as_ref()
+        .is_some_and(|d| d.value == "attachment");
+    let charset = content_type.param("charset").unwrap_or("utf-8");
+    match content_type.value.as_str() {
+        "text/plain" if !attached && file_name.is_none() => {
+            if message.text.is_empty() {
+                message.text = decode_charset(&content, charset).trim().to_string();
+            }
+        }
+        "text/html" if !attached && file_name.is_none() => {
+            if html.is_none() {
+                *html = Some(html_to_text(&decode_charset(&content, charset)));
+            }
+        }
+        kind => {
+            let file_name = match file_name {
+                Some(name) => name,
+                None if kind == "message/rfc822" => "message.eml".to_string(),
+                // Inline images and such without a name aren't worth keeping.
+                None => return,
+            };
+            message
+                .attachments
+                .push(MailAttachment { file_name, content });
+        }
+    }
+}
+
+/// Parse a raw RFC 5322 message.
+pub fn parse(raw: &[u8]) -> Message {
+    let (head, _) = split_entity(raw);
+    let headers = parse_headers(head);
+    let mut message = Message {
+        subject: decode_words(header(&headers, "subject").unwrap_or_default())
+            .trim()
+            .to_string(),
+        from: decode_words(header(&headers, "from").unwrap_or_default())
+            .trim()
+            .to_string(),
+        message_id: header(&headers, "message-id")
+            .map(|id| {
+                id.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+            .filter(|id| !id.is_empty()),
+        ..Message::default()
+    };
+    let mut html = None;
+    walk(raw, "text/plain", &mut html, &mut message);
+    if message.text.is_empty() {
+        message.text = html.unwrap_or_default();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_words() {
+        assert_eq!(decode_words("=?UTF-8?Q?Caf=C3=A9_order?="), "Café order");
+        assert_eq!(decode_words("=?utf-8?B?w6l0w6k=?="), "été");
+        assert_eq!(
+            decode_words("=?ISO-8859-1?Q?Gr=FC=DFe?= aus Wien"),
+            "Grüße aus Wien"
+        );
+        // Whitespace between encoded words goes, around plain text it stays.
+        assert_eq!(
+            decode_words("Re: =?utf-8?Q?a?= =?utf-8?Q?b?=\r\n =?utf-8?Q?c?= d"),
+            "Re: abc d"
+        );
+        assert_eq!(decode_words("=?utf-8*en?Q?hi?="), "hi");
+        // Anything malformed is kept as it was.
+        assert_eq!(
+            decode_words("=?utf-8?X?abc?= 1 =? 2"),
+            "=?utf-8?X?abc?= 1 =? 2"
+        );
+        assert_eq!(
+            decode_words("=?utf-8?Q?unterminated"),
+            "=?utf-8?Q?unterminated"
+        );
+    }
+
+    #[test]
+    fn plain_message() {
+        let raw = b"Subject: =?UTF-8?Q?Caf=C3=A9_order?=\r\n\
+            From: \"Ann\" <ann@example.com>\r\n\
+            Message-ID: <abc@x>\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            Hello=20there=\r\n again\r\n";
+        let message = parse(raw);
+        assert_eq!(message.subject, "Café order");
+        assert_eq!(message.from, "\"Ann\" <ann@example.com>");
+        assert_eq!(message.message_id.as_deref(), Some("abc@x"));
+        assert_eq!(message.text, "Hello there again");
+        assert!(message.attachments.is_empty());
+    }
+
+    #[test]
+    fn multipart_with_attachments() {
+        let raw = b"Subject: Report\r\n\
+            Content-Type: multipart/mixed; boundary=\"XX\"\r\n\
+            \r\n\
+            preamble\r\n\
+            --XX\r\n\
+            Content-Type: multipart/alternative; boundary=YY\r\n\
+            \r\n\
+            --YY\r\n\
+            Content-Type: text/plain; charset=iso-8859-1\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            Gr=FC=DFe\r\n\
+            --YY\r\n\
+            Content-Type: text/html\r\n\
+            \r\n\
+            <p>Hi <b>you</b></p>\r\n\
+            --YY--\r\n\
+            --XX\r\n\
+            Content-Type: application/pdf; name=\"r.pdf\"\r\n\
+            Content-Disposition: attachment; filename*=UTF-8''..%2Fr%C3%A9port.pdf\r\n\
+            Content-Transfer-Encoding: base64\r\n\
+            \r\n\
+            aGVs\r\n\
+            bG8=\r\n\
+            --XX\r\n\
+            Content-Type: image/png\r\n\
+            Content-Disposition: inline\r\n\
+            \r\n\
+            PNG\r\n\
+            --XX\r\n\
+            Content-Type: message/rfc822\r\n\
+            \r\n\
+            Subject: Forwarded\r\n\
+            \r\n\
+            Old news\r\n\
+            --XX--\r\n\
+            epilogue\r\n";
+        let message = parse(raw);
+        assert_eq!(message.subject, "Report");
+        // The plain alternative wins over the HTML one.
+        assert_eq!(message.text, "Grüße");
+        let names: Vec<_> = message
+            .attachments
+            .iter()
+            .map(|a| a.file_name.as_str())
+            .collect();
+        assert_eq!(names, ["réport.pdf", "message.eml"]);
+        assert_eq!(message.attachments[0].content, b"hello");
+    }
+
+    #[test]
+    fn html_only_message() {
+        let raw = b"Content-Type: multipart/alternative; boundary=B\r\n\
+            \r\n\
+            --B\r\n\
+            Content-Type: text/html; charset=utf-8\r\n\
+            \r\n\
+            <p>Hi <b>you</b></p>\r\n\
+            --B--\r\n";
+        let message = parse(raw);
+        assert_eq!(message.subject, "");
+        assert!(message.text.contains("Hi you"), "{:?}", message.text);
+    }
+
+    #[test]
+    fn file_names_stay_in_their_folder() {
+        assert_eq!(safe_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(safe_file_name("C:\\Users\\me\\notes.txt"), "notes.txt");
+        assert_eq!(safe_file_name(".."), "attachment");
+        assert_eq!(safe_file_name(" a\u{7}b.txt "), "ab.txt");
+    }
+}
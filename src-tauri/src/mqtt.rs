@@ -0,0 +1,489 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+use tauri::{AppHandle, Manager};
+
+use crate::credentials;
+use crate::data_dir;
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::pomodoro::{self, Phase};
+use crate::session::write_atomic;
+use crate::task_store;
+use crate::time_entries;
+
+const KEYRING_ACCOUNT: &str = "mqtt:broker";
+
+const CONFIG_FILE: &str = "mqtt.json";
+/// How often the state is checked for something new to publish.
+const POLL: Duration = Duration::from_secs(2);
+/// Keep-alive asked of the broker; a ping goes out at half of it.
+const KEEP_ALIVE_SECS: u16 = 60;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Wait before trying a broker that refused or dropped the connection.
+const RECONNECT_AFTER: Duration = Duration::from_secs(30);
+
+/// Set when the config changes, so the publisher reconnects with it.
+static RECONFIGURED: AtomicBool = AtomicBool::new(true);
+/// Whether the publisher is connected, and why it last failed.
+static CONNECTION: Mutex<(bool, Option<String>)> = Mutex::new((false, None));
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub client_id: String,
+    /// Topics are published under this prefix, e.g. `daylight/state`.
+    pub base_topic: String,
+    /// Announce the sensors to Home Assistant under `discovery_prefix`.
+    pub discovery: bool,
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 1883,
+            username: None,
+            client_id: "daylight".to_string(),
+            base_topic: "daylight".to_string(),
+            discovery: true,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MqttStatus {
+    pub config: MqttConfig,
+    pub has_password: bool,
+    pub connected: bool,
+    pub error: Option<String>,
+}
+
+/// What's published to `<base_topic>/state`, as retained JSON.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Snapshot {
+    timer_running: bool,
+    task_id: Option<String>,
+    task_title: Option<String>,
+    timer_started_at: Option<String>,
+    /// `work`, `short_break` or `long_break` while a phase runs, `paused`
+    /// or `idle` otherwise.
+    pomodoro_phase: String,
+    pomodoro_ends_at: Option<String>,
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+pub fn load_config(app: &AppHandle) -> MqttConfig {
+    let Ok(path) = config_path(app) else {
+        return MqttConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            MqttConfig::default()
+        }),
+        Err(_) => MqttConfig::default(),
+    }
+}
+
+fn set_connection(connected: bool, error: Option<String>) {
+    *CONNECTION.lock().unwrap_or_else(|e| e.into_inner()) = (connected, error);
+}
+
+/// The MQTT "remaining length": seven bits a byte, low bits first.
+fn encode_length(mut length: usize, out: &mut Vec<u8>) {
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+}
+
+fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![kind];
+    encode_length(body.len(), &mut out);
+    out.extend_from_slice(body);
+    out
+}
+
+/// A CONNECT packet (MQTT 3.1.1) with a clean session and a retained
+/// `offline` will on `will_topic`.
+fn connect_packet(config: &MqttConfig, password: Option<&str>, will_topic: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    encode_bytes(b"MQTT", &mut body);
+    body.push(4);
+    // Clean session, will flag, will retain.
+    let mut flags = 0x02 | 0x04 | 0x20;
+    let username = config.username.as_deref().filter(|u| !u.is_empty());
+    if username.is_some() {
+        flags |= 0x80;
+        if password.is_some() {
+            flags |= 0x40;
+        }
+    }
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    encode_bytes(config.client_id.as_bytes(), &mut body);
+    encode_bytes(will_topic.as_bytes(), &mut body);
+    encode_bytes(b"offline", &mut body);
+    if let Some(username) = username {
+        encode_bytes(username.as_bytes(), &mut body);
+        if let Some(password) = password {
+            encode_bytes(password.as_bytes(), &mut body);
+        }
+    }
+    packet(0x10, &body)
+}
+
+/// A QoS 0 PUBLISH packet.
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    encode_bytes(topic.as_bytes(), &mut body);
+    body.extend_from_slice(payload);
+    packet(0x30 | u8::from(retain), &body)
+}
+
+fn connack_error(code: u8) -> CommandError {
+    match code {
+        1 => "The broker doesn't speak MQTT 3.1.1".into(),
+        2 => CommandError::invalid("The broker rejected the client id"),
+        3 => CommandError::new(ErrorCode::Unavailable, "The broker is unavailable"),
+        4 => CommandError::unauthorized("Wrong username or password"),
+        5 => CommandError::unauthorized("Not authorized"),
+        code => format!("Connection refused ({code})").into(),
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    last_sent: Instant,
+}
+
+impl Connection {
+    fn open(config: &MqttConfig, will_topic: &str) -> CommandResult<Self> {
+        let address = (config.host.trim(), config.port)
+            .to_socket_addrs()
+            .map_err(|e| CommandError::network(format!("Can't resolve {}: {e}", config.host)))?
+            .next()
+            .ok_or_else(|| CommandError::network(format!("Can't resolve {}", config.host)))?;
+        let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|e| {
+            CommandError::network(format!("Can't reach {}:{}: {e}", config.host, config.port))
+        })?;
+        let _ = stream.set_read_timeout(Some(CONNECT_TIMEOUT));
+        let password = credentials::load(KEYRING_ACCOUNT);
+        stream
+            .write_all(&connect_packet(config, password.as_deref(), will_topic))
+            .map_err(CommandError::network)?;
+        let mut connack = [0u8; 4];
+        stream
+            .read_exact(&mut connack)
+            .map_err(|e| CommandError::network(format!("No answer from the broker: {e}")))?;
+        if connack[0] != 0x20 {
+            return Err("Unexpected answer from the broker".into());
+        }
+        if connack[3] != 0 {
+            return Err(connack_error(connack[3]));
+        }
+        // Nothing else read matters (only ping responses come back), so
+        // reads never block the loop.
+        let _ = stream.set_nonblocking(true);
+        Ok(Self {
+            stream,
+            last_sent: Instant::now(),
+        })
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> CommandResult<()> {
+        let _ = self.stream.set_nonblocking(false);
+        let result = self.stream.write_all(bytes).map_err(CommandError::network);
+        let _ = self.stream.set_nonblocking(true);
+        self.last_sent = Instant::now();
+        result
+    }
+
+    fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> CommandResult<()> {
+        self.send(&publish_packet(topic, payload, retain))
+    }
+
+    /// Ping when nothing else went out for half the keep-alive, and drain
+    /// what the broker sent back. Fails once the broker closed the socket.
+    fn keep_alive(&mut self) -> CommandResult<()> {
+        let mut buf = [0u8; 64];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(CommandError::network("The broker closed the connection")),
+                Ok(_) => continue,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(CommandError::network(e)),
+            }
+        }
+        if self.last_sent.elapsed() >= Duration::from_secs(u64::from(KEEP_ALIVE_SECS / 2)) {
+            self.send(&[0xC0, 0x00])?;
+        }
+        Ok(())
+    }
+
+    fn close(mut self) {
+        let _ = self.send(&[0xE0, 0x00]);
+    }
+}
+
+fn topic(config: &MqttConfig, name: &str) -> String {
+    format!("{}/{name}", config.base_topic.trim_end_matches('/'))
+}
+
+/// Home Assistant discovery messages: topic and retained config for each
+/// entity, all reading `<base_topic>/state`.
+fn discovery_messages(config: &MqttConfig) -> Vec<(String, Json)> {
+    let node = config
+        .client_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let device = json!({
+        "identifiers": [node],
+        "name": "DayLight",
+        "manufacturer": "DayLight",
+    });
+    let entities = [
+        (
+            "binary_sensor",
+            "timer",
+            "Timer",
+            json!({
+                "value_template": "{{ 'ON' if value_json.timer_running else 'OFF' }}",
+                "icon": "mdi:timer-outline",
+            }),
+        ),
+        (
+            "sensor",
+            "task",
+            "Current task",
+            json!({
+                "value_template": "{{ value_json.task_title or '' }}",
+                "icon": "mdi:checkbox-marked-circle-outline",
+            }),
+        ),
+        (
+            "sensor",
+            "pomodoro_phase",
+            "Pomodoro phase",
+            json!({
+                "value_template": "{{ value_json.pomodoro_phase }}",
+                "icon": "mdi:timer-sand",
+            }),
+        ),
+        (
+            "sensor",
+            "pomodoro_ends_at",
+            "Pomodoro ends",
+            json!({
+                "value_template": "{{ value_json.pomodoro_ends_at or None }}",
+                "device_class": "timestamp",
+            }),
+        ),
+    ];
+    entities
+        .into_iter()
+        .map(|(component, object, name, extra)| {
+            let mut payload = json!({
+                "name": name,
+                "unique_id": format!("{node}_{object}"),
+                "object_id": format!("{node}_{object}"),
+                "state_topic": topic(config, "state"),
+                "availability_topic": topic(config, "status"),
+                "device": device,
+            });
+            if let (Some(payload), Some(extra)) = (payload.as_object_mut(), extra.as_object()) {
+                payload.extend(extra.clone());
+            }
+            let topic = format!(
+                "{}/{component}/{node}/{object}/config",
+                config.discovery_prefix.trim_end_matches('/')
+            );
+            (topic, payload)
+        })
+        .collect()
+}
+
+fn snapshot(app: &AppHandle) -> CommandResult<Snapshot> {
+    let db = app.state::<Db>();
+    let (entry, title) = db.with_conn(|conn| {
+        let Some(entry) = time_entries::running_entry(conn)? else {
+            return Ok((None, None));
+        };
+        let title = task_store::find_task(conn, &entry.task_id)?.map(|t| t.title);
+        Ok((Some(entry), title))
+    })?;
+    let pomodoro = pomodoro::get_pomodoro(app.state())?;
+    let phase = match (pomodoro.running, pomodoro.state.paused_remaining) {
+        (true, _) => match pomodoro.state.phase {
+            Phase::Work => "work",
+            Phase::ShortBreak => "short_break",
+            Phase::LongBreak => "long_break",
+        },
+        (false, Some(_)) => "paused",
+        (false, None) => "idle",
+    };
+    Ok(Snapshot {
+        timer_running: entry.is_some(),
+        task_id: entry.as_ref().map(|e| e.task_id.clone()),
+        task_title: title,
+        timer_started_at: entry.map(|e| e.started_at),
+        pomodoro_phase: phase.to_string(),
+        pomodoro_ends_at: pomodoro.state.ends_at.clone(),
+    })
+}
+
+/// Connect, announce availability (and the entities, with discovery on)
+/// and return the connection.
+fn open(config: &MqttConfig) -> CommandResult<Connection> {
+    let mut connection = Connection::open(config, &topic(config, "status"))?;
+    connection.publish(&topic(config, "status"), b"online", true)?;
+    if config.discovery {
+        for (topic, payload) in discovery_messages(config) {
+            connection.publish(&topic, payload.to_string().as_bytes(), true)?;
+        }
+    }
+    Ok(connection)
+}
+
+/// Publish the timer and pomodoro state whenever it changes, keeping a
+/// connection to the configured broker while enabled. The state is
+/// retained, so a light or indicator that subscribes later still gets it.
+pub fn spawn_mqtt_publisher(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || {
+        let mut config = MqttConfig::default();
+        let mut connection: Option<Connection> = None;
+        let mut published: Option<Snapshot> = None;
+        let mut retry_at: Option<Instant> = None;
+        loop {
+            if RECONFIGURED.swap(false, Ordering::SeqCst) {
+                if let Some(connection) = connection.take() {
+                    connection.close();
+                }
+                config = load_config(&handle);
+                published = None;
+                retry_at = None;
+                set_connection(false, None);
+            }
+            let ready = config.enabled && !config.host.trim().is_empty();
+            if ready && connection.is_none() && retry_at.is_none_or(|at| Instant::now() >= at) {
+                match open(&config) {
+                    Ok(opened) => {
+                        connection = Some(opened);
+                        set_connection(true, None);
+                    }
+                    Err(e) => {
+                        tracing::warn!("{e}");
+                        set_connection(false, Some(e.to_string()));
+                        retry_at = Some(Instant::now() + RECONNECT_AFTER);
+                    }
+                }
+            }
+            if let Some(live) = connection.as_mut() {
+                let result = match snapshot(&handle) {
+                    Ok(current) if published.as_ref() != Some(&current) => {
+                        let body = serde_json::to_vec(&current).unwrap_or_default();
+                        let sent = live.publish(&topic(&config, "state"), &body, true);
+                        if sent.is_ok() {
+                            published = Some(current);
+                        }
+                        sent
+                    }
+                    // Unchanged, or the database is locked: nothing new.
+                    _ => Ok(()),
+                }
+                .and_then(|_| live.keep_alive());
+                if let Err(e) = result {
+                    tracing::warn!("{e}");
+                    connection = None;
+                    published = None;
+                    set_connection(false, Some(e.to_string()));
+                    retry_at = Some(Instant::now() + RECONNECT_AFTER);
+                }
+            }
+            std::thread::sleep(POLL);
+        }
+    });
+}
+
+fn status(app: &AppHandle) -> MqttStatus {
+    let (connected, error) = CONNECTION.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    MqttStatus {
+        config: load_config(app),
+        has_password: credentials::load(KEYRING_ACCOUNT).is_some(),
+        connected,
+        error,
+    }
+}
+
+#[tauri::command]
+pub fn get_mqtt_config(app: AppHandle) -> MqttStatus {
+    status(&app)
+}
+
+/// Save the config and reconnect with it. `password` replaces the saved
+/// one; an empty string forgets it and `None` keeps it.
+#[tauri::command]
+pub fn set_mqtt_config(
+    app: AppHandle,
+    config: MqttConfig,
+    password: Option<String>,
+) -> CommandResult<MqttStatus> {
+    if config.enabled && config.host.trim().is_empty() {
+        return Err(CommandError::invalid("Enter the broker's host name"));
+    }
+    if config.client_id.trim().is_empty() || config.base_topic.trim().is_empty() {
+        return Err(CommandError::invalid(
+            "The client id and base topic can't be empty",
+        ));
+    }
+    if config.base_topic.contains(['+', '#']) {
+        return Err(CommandError::invalid(
+            "The base topic can't contain wildcards",
+        ));
+    }
+    if let Some(password) = &password {
+        credentials::save(
+            KEYRING_ACCOUNT,
+            Some(password.as_str()).filter(|p| !p.is_empty()),
+        )?;
+    }
+    let path = config_path(&app)?;
+    let body = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+    RECONFIGURED.store(true, Ordering::SeqCst);
+    set_connection(false, None);
+    Ok(status(&app))
+}
@@ -0,0 +1,735 @@
+use chrono::{
+    DateTime, Datelike, Days, FixedOffset, Local, Months, NaiveDate, NaiveTime, TimeZone, Weekday,
+};
+use serde::Serialize;
+
+use crate::error::{CommandError, CommandResult};
+use crate::locale::{self, LocaleInfo};
+use crate::rrule::Rrule;
+
+/// Result of parsing a quick-add phrase. Phrases are English; the locale
+/// decides numeric date order (3/4 = March 4 in en-US, 3 April elsewhere)
+/// and which day a week starts on.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParsedDate {
+    /// YYYY-MM-DD
+    pub date: Option<String>,
+    /// HH:MM, 24-hour.
+    pub time: Option<String>,
+    /// RFC 3339 in the reference time's offset, when both date and time are known.
+    pub datetime: Option<String>,
+    /// RRULE in the same form tasks store (`DTSTART:...;FREQ=...`).
+    pub recurrence: Option<String>,
+    /// The parts of the input that were understood, in order.
+    pub matched: Vec<String>,
+    /// The input with the date phrases removed, e.g. the task title.
+    pub remaining: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Locale {
+    month_first: bool,
+    week_start: Weekday,
+}
+
+impl Locale {
+    /// The conventions of BCP 47 `tag`, or of the OS locale without one.
+    fn parse(tag: Option<&str>) -> Self {
+        let info = match tag {
+            Some(tag) => LocaleInfo::from_tag(tag),
+            None => locale::current(),
+        };
+        Self {
+            month_first: info.date_order == "mdy",
+            week_start: info.week_start(),
+        }
+    }
+}
+
+struct Token {
+    word: String,
+    start: usize,
+    end: usize,
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                let raw = &text[s..i];
+                let word = raw
+                    .trim_end_matches([',', '.', ';', '!', '?'])
+                    .to_lowercase();
+                tokens.push(Token {
+                    word,
+                    start: s,
+                    end: i,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+#[derive(Default)]
+struct Found {
+    date: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+    /// Time implied by a word like "tonight"; an explicit time wins.
+    default_time: Option<NaiveTime>,
+    rule: Option<String>,
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    let day = match word {
+        "monday" | "mon" => Weekday::Mon,
+        "tuesday" | "tue" | "tues" => Weekday::Tue,
+        "wednesday" | "wed" => Weekday::Wed,
+        "thursday" | "thu" | "thur" | "thurs" => Weekday::Thu,
+        "friday" | "fri" => Weekday::Fri,
+        "saturday" | "sat" => Weekday::Sat,
+        "sunday" | "sun" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(day)
+}
+
+fn weekday_plural(word: &str) -> Option<Weekday> {
+    weekday(word).or_else(|| weekday(word.strip_suffix('s')?))
+}
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+fn month(word: &str) -> Option<u32> {
+    if word.len() < 3 {
+        return None;
+    }
+    let word = word.strip_suffix('.').unwrap_or(word);
+    MONTHS
+        .iter()
+        .position(|m| m.starts_with(word))
+        .map(|i| i as u32 + 1)
+}
+
+/// Day and month names that are everyday words too: abbreviations like
+/// "sun", "wed" or "mar", and "may". They only count as dates after a
+/// connector or at the end of the text.
+fn ambiguous(word: &str) -> bool {
+    if word == "may" {
+        return true;
+    }
+    let full = word.ends_with("day") || MONTHS.contains(&word);
+    !full && (weekday(word).is_some() || month(word).is_some())
+}
+
+/// "1", "1st", "22nd", "3rd", "4th".
+fn day_number(word: &str) -> Option<u32> {
+    let digits = word
+        .strip_suffix("st")
+        .or_else(|| word.strip_suffix("nd"))
+        .or_else(|| word.strip_suffix("rd"))
+        .or_else(|| word.strip_suffix("th"))
+        .unwrap_or(word);
+    let day: u32 = digits.parse().ok()?;
+    (1..=31).contains(&day).then_some(day)
+}
+
+fn ordinal(word: &str) -> Option<i32> {
+    match word {
+        "first" | "1st" => Some(1),
+        "second" | "2nd" => Some(2),
+        "third" | "3rd" => Some(3),
+        "fourth" | "4th" => Some(4),
+        "last" => Some(-1),
+        _ => None,
+    }
+}
+
+fn number(word: &str) -> Option<u32> {
+    match word {
+        "a" | "an" | "one" => Some(1),
+        "two" => Some(2),
+        "three" => Some(3),
+        "four" => Some(4),
+        "five" => Some(5),
+        "six" => Some(6),
+        "seven" => Some(7),
+        "eight" => Some(8),
+        "nine" => Some(9),
+        "ten" => Some(10),
+        _ => word.parse().ok(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+fn unit(word: &str) -> Option<Unit> {
+    match word.strip_suffix('s').unwrap_or(word) {
+        "day" => Some(Unit::Day),
+        "week" | "wk" => Some(Unit::Week),
+        "month" | "mo" => Some(Unit::Month),
+        "year" | "yr" => Some(Unit::Year),
+        _ => None,
+    }
+}
+
+fn add(date: NaiveDate, n: u32, unit: Unit) -> Option<NaiveDate> {
+    match unit {
+        Unit::Day => date.checked_add_days(Days::new(n as u64)),
+        Unit::Week => date.checked_add_days(Days::new(n as u64 * 7)),
+        Unit::Month => date.checked_add_months(Months::new(n)),
+        Unit::Year => date.checked_add_months(Months::new(n.checked_mul(12)?)),
+    }
+}
+
+/// Next `day` on or after `from`.
+fn upcoming(from: NaiveDate, day: Weekday) -> NaiveDate {
+    let ahead = (7 + day.num_days_from_monday() - from.weekday().num_days_from_monday()) % 7;
+    from + Days::new(ahead as u64)
+}
+
+fn week_start(date: NaiveDate, locale: Locale) -> NaiveDate {
+    let back =
+        (7 + date.weekday().num_days_from_monday() - locale.week_start.num_days_from_monday()) % 7;
+    date - Days::new(back as u64)
+}
+
+fn parse_time_word(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" | "midday" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+    let (clock, meridiem) = if let Some(c) = word.strip_suffix("am").or(word.strip_suffix("a")) {
+        (c, Some(false))
+    } else if let Some(c) = word.strip_suffix("pm").or(word.strip_suffix("p")) {
+        (c, Some(true))
+    } else {
+        (word, None)
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None if meridiem.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+    let hour = match meridiem {
+        Some(pm) if (1..=12).contains(&hour) => hour % 12 + if pm { 12 } else { 0 },
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn match_time(tokens: &[Token]) -> Option<(usize, NaiveTime)> {
+    let first = tokens.first()?.word.as_str();
+    if let Some(time) = parse_time_word(first) {
+        return Some((1, time));
+    }
+    // "3 pm", "10:30 am"
+    let next = tokens.get(1).map(|t| t.word.as_str());
+    if let Some(m @ ("am" | "pm" | "a.m" | "p.m")) = next {
+        let time = parse_time_word(&format!("{first}{}", m.replace('.', "")))?;
+        return Some((2, time));
+    }
+    let period = match first {
+        "morning" => 9,
+        "afternoon" => 14,
+        "evening" => 18,
+        "night" => 20,
+        _ => return None,
+    };
+    Some((1, NaiveTime::from_hms_opt(period, 0, 0)?))
+}
+
+fn numeric_date(word: &str, today: NaiveDate, locale: Locale) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some(date);
+    }
+    let parts: Vec<&str> = word.split(['/', '.']).collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let a: u32 = parts[0].parse().ok()?;
+    let b: u32 = parts[1].parse().ok()?;
+    let (month, day) = if locale.month_first { (a, b) } else { (b, a) };
+    match parts.get(2) {
+        Some(year) => {
+            let year: i32 = year.parse().ok()?;
+            let year = if year < 100 { 2000 + year } else { year };
+            NaiveDate::from_ymd_opt(year, month, day)
+        }
+        None => next_month_day(today, month, day),
+    }
+}
+
+/// `month`/`day` this year, or next year if that has already passed.
+fn next_month_day(today: NaiveDate, month: u32, day: u32) -> Option<NaiveDate> {
+    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day);
+    match this_year {
+        Some(date) if date >= today => Some(date),
+        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+    }
+}
+
+fn year_token(token: Option<&Token>) -> Option<i32> {
+    let word = token?.word.as_str();
+    let year: i32 = word.parse().ok()?;
+    (word.len() == 4).then_some(year)
+}
+
+fn match_date(tokens: &[Token], today: NaiveDate, locale: Locale) -> Option<(usize, NaiveDate)> {
+    let words: Vec<&str> = tokens.iter().take(4).map(|t| t.word.as_str()).collect();
+    let one_day = |n| today.checked_add_days(Days::new(n));
+
+    match words.as_slice() {
+        ["day", "after", "tomorrow", ..] => return Some((3, one_day(2)?)),
+        ["today" | "tonight", ..] => return Some((1, today)),
+        ["tomorrow" | "tmr" | "tmrw", ..] => return Some((1, one_day(1)?)),
+        ["yesterday", ..] => return Some((1, today.pred_opt()?)),
+        ["next", "week", ..] => return Some((2, week_start(today, locale) + Days::new(7))),
+        ["next", "month", ..] => {
+            let first = today.with_day(1)?;
+            return Some((2, first.checked_add_months(Months::new(1))?));
+        }
+        ["next", "year", ..] => return Some((2, NaiveDate::from_ymd_opt(today.year() + 1, 1, 1)?)),
+        ["this", "weekend", ..] | ["weekend", ..] => {
+            let n = if words[0] == "this" { 2 } else { 1 };
+            return Some((n, upcoming(today, Weekday::Sat)));
+        }
+        ["end", "of", "the", period] | ["end", "of", period, ..] => {
+            let n = if words[2] == "the" { 4 } else { 3 };
+            let date = match *period {
+                "week" => upcoming(today, Weekday::Fri),
+                "month" => {
+                    let first = today.with_day(1)?;
+                    first.checked_add_months(Months::new(1))?.pred_opt()?
+                }
+                "year" => NaiveDate::from_ymd_opt(today.year(), 12, 31)?,
+                _ => return None,
+            };
+            return Some((n, date));
+        }
+        ["in", n, u, ..] => {
+            if let (Some(n), Some(u)) = (number(n), unit(u)) {
+                return Some((3, add(today, n, u)?));
+            }
+        }
+        [n, u, "from", "now", ..] => {
+            if let (Some(n), Some(u)) = (number(n), unit(u)) {
+                return Some((4, add(today, n, u)?));
+            }
+        }
+        _ => {}
+    }
+
+    // "next friday" is the Friday of next week; "friday" / "this friday" is the
+    // next Friday on or after today.
+    let (skip, next) = match words.first() {
+        Some(&"next") => (1, true),
+        Some(&"this") => (1, false),
+        _ => (0, false),
+    };
+    if let Some(day) = words.get(skip).and_then(|w| weekday(w)) {
+        let date = if next {
+            upcoming(week_start(today, locale) + Days::new(7), day)
+        } else {
+            upcoming(today, day)
+        };
+        return Some((skip + 1, date));
+    }
+
+    // "march 14", "mar 14th 2026"
+    if let (Some(m), Some(d)) = (
+        words.first().and_then(|w| month(w)),
+        words.get(1).and_then(|w| day_number(w)),
+    ) {
+        return Some(match year_token(tokens.get(2)) {
+            Some(year) => (3, NaiveDate::from_ymd_opt(year, m, d)?),
+            None => (2, next_month_day(today, m, d)?),
+        });
+    }
+    // "14 march", "14th of march 2026"
+    if let Some(d) = words.first().and_then(|w| day_number(w)) {
+        let of = usize::from(words.get(1) == Some(&"of"));
+        if let Some(m) = words.get(1 + of).and_then(|w| month(w)) {
+            return Some(match year_token(tokens.get(2 + of)) {
+                Some(year) => (3 + of, NaiveDate::from_ymd_opt(year, m, d)?),
+                None => (2 + of, next_month_day(today, m, d)?),
+            });
+        }
+    }
+
+    let date = numeric_date(words.first()?, today, locale)?;
+    Some((1, date))
+}
+
+/// Recurrence phrases, returning the RRULE body without DTSTART.
+fn match_recurrence(tokens: &[Token]) -> Option<(usize, String)> {
+    let words: Vec<&str> = tokens.iter().take(8).map(|t| t.word.as_str()).collect();
+    match words.first()? {
+        &"daily" => return Some((1, "FREQ=DAILY".into())),
+        &"weekly" => return Some((1, "FREQ=WEEKLY".into())),
+        &"biweekly" | &"fortnightly" => return Some((1, "FREQ=WEEKLY;INTERVAL=2".into())),
+        &"monthly" => return Some((1, "FREQ=MONTHLY".into())),
+        &"yearly" | &"annually" => return Some((1, "FREQ=YEARLY".into())),
+        &"weekdays" => return Some((1, "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".into())),
+        &"every" | &"each" => {}
+        _ => return None,
+    }
+
+    let rest = &words[1..];
+    let freq = |u: Unit| match u {
+        Unit::Day => "DAILY",
+        Unit::Week => "WEEKLY",
+        Unit::Month => "MONTHLY",
+        Unit::Year => "YEARLY",
+    };
+    match rest {
+        ["weekday", ..] | ["weekdays", ..] => {
+            return Some((2, "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".into()))
+        }
+        ["weekend", ..] | ["weekends", ..] => return Some((2, "FREQ=WEEKLY;BYDAY=SA,SU".into())),
+        ["other", u, ..] => {
+            let u = unit(u)?;
+            return Some((3, format!("FREQ={};INTERVAL=2", freq(u))));
+        }
+        [o, d, ..] if ordinal(o).is_some() && weekday(d).is_some() => {
+            let code = &rrule_code(weekday(d)?);
+            return Some((3, format!("FREQ=MONTHLY;BYDAY={}{code}", ordinal(o)?)));
+        }
+        _ => {}
+    }
+
+    if let Some(u) = rest.first().and_then(|w| unit(w)) {
+        let mut rule = format!("FREQ={}", freq(u));
+        let mut used = 2;
+        // "every month on the 15th"
+        if u == Unit::Month {
+            if let ["on", "the", d, ..] = &rest[1..] {
+                if let Some(d) = day_number(d) {
+                    rule.push_str(&format!(";BYMONTHDAY={d}"));
+                    used += 3;
+                }
+            }
+        }
+        return Some((used, rule));
+    }
+    if let (Some(n), Some(u)) = (
+        rest.first().and_then(|w| number(w)),
+        rest.get(1).and_then(|w| unit(w)),
+    ) {
+        return Some((3, format!("FREQ={};INTERVAL={n}", freq(u))));
+    }
+    // "every 15th"
+    if let Some(d) = rest
+        .first()
+        .filter(|w| w.len() > 2)
+        .and_then(|w| day_number(w))
+    {
+        return Some((2, format!("FREQ=MONTHLY;BYMONTHDAY={d}")));
+    }
+
+    // "every mon, wed and fri"
+    let mut days = Vec::new();
+    let mut used = 1;
+    for word in rest {
+        if let Some(day) = weekday_plural(word) {
+            days.push(rrule_code(day));
+        } else if *word != "and" && *word != "&" {
+            break;
+        }
+        used += 1;
+    }
+    if days.is_empty() {
+        return None;
+    }
+    while matches!(words.get(used - 1), Some(&"and") | Some(&"&")) {
+        used -= 1;
+    }
+    Some((used, format!("FREQ=WEEKLY;BYDAY={}", days.join(","))))
+}
+
+fn rrule_code(day: Weekday) -> String {
+    day.to_string()[..2].to_ascii_uppercase()
+}
+
+const CONNECTORS: [&str; 5] = ["on", "at", "by", "due", "@"];
+
+pub fn parse(text: &str, locale: Option<&str>, reference: DateTime<FixedOffset>) -> ParsedDate {
+    let locale = Locale::parse(locale);
+    let today = reference.date_naive();
+    let tokens = tokenize(text);
+    let mut found = Found::default();
+    let mut used = vec![false; tokens.len()];
+    let mut matched = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let connector = usize::from(CONNECTORS.contains(&tokens[i].word.as_str()));
+        let rest = &tokens[i + connector..];
+
+        let consumed = if found.rule.is_none() {
+            match_recurrence(rest).map(|(n, rule)| {
+                found.rule = Some(rule);
+                n
+            })
+        } else {
+            None
+        }
+        .or_else(|| {
+            if found.date.is_some() {
+                return None;
+            }
+            match_date(rest, today, locale)
+                .filter(|&(n, _)| {
+                    connector == 1
+                        || i + n == tokens.len()
+                        || matches!(rest[0].word.as_str(), "next" | "this")
+                        || !rest[..n].iter().any(|t| ambiguous(&t.word))
+                })
+                .map(|(n, date)| {
+                    found.date = Some(date);
+                    if rest[0].word == "tonight" {
+                        found.default_time = NaiveTime::from_hms_opt(20, 0, 0);
+                    }
+                    n
+                })
+        })
+        .or_else(|| {
+            if found.time.is_some() {
+                return None;
+            }
+            // A bare number only counts as a time after "at": "at 9".
+            let bare = rest
+                .first()
+                .filter(|_| connector == 1 && tokens[i].word == "at")
+                .and_then(|t| t.word.parse::<u32>().ok())
+                .and_then(|h| NaiveTime::from_hms_opt(h, 0, 0));
+            match bare {
+                Some(time) => Some((1, time)),
+                None => match_time(rest),
+            }
+            .map(|(n, time)| {
+                found.time = Some(time);
+                n
+            })
+        });
+
+        match consumed {
+            Some(n) => {
+                let span = &tokens[i..i + connector + n];
+                matched.push(text[span[0].start..span[span.len() - 1].end].to_string());
+                used[i..i + connector + n].fill(true);
+                i += connector + n;
+            }
+            None => i += 1,
+        }
+    }
+
+    let remaining = tokens
+        .iter()
+        .zip(&used)
+        .filter(|(_, used)| !**used)
+        .map(|(t, _)| &text[t.start..t.end])
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let time = found.time.or(found.default_time);
+    let mut date = found.date;
+    let mut recurrence = None;
+
+    if let Some(rule) = &found.rule {
+        let start = date.unwrap_or(today);
+        let full = format!("DTSTART:{};{rule}", start.format("%Y%m%d"));
+        if let Ok(parsed) = Rrule::parse(&full) {
+            date = date.or_else(|| parsed.occurrences().next());
+            recurrence = Some(parsed.to_string());
+        }
+    }
+
+    // A lone time that has already passed today means tomorrow.
+    if date.is_none() {
+        if let Some(time) = time {
+            date = Some(if today.and_time(time) > reference.naive_local() {
+                today
+            } else {
+                today + Days::new(1)
+            });
+        }
+    }
+
+    let datetime = date.zip(time).and_then(|(d, t)| {
+        reference
+            .offset()
+            .from_local_datetime(&d.and_time(t))
+            .single()
+            .map(|dt| dt.to_rfc3339())
+    });
+
+    ParsedDate {
+        date: date.map(|d| d.format("%Y-%m-%d").to_string()),
+        time: time.map(|t| t.format("%H:%M").to_string()),
+        datetime,
+        recurrence,
+        matched,
+        remaining,
+    }
+}
+
+/// Parse date/time/recurrence phrases out of quick-add text. `locale`
+/// defaults to the OS's; `reference_time` is RFC 3339 and defaults to now.
+#[tauri::command]
+pub fn parse_natural_date(
+    text: String,
+    locale: Option<String>,
+    reference_time: Option<String>,
+) -> CommandResult<ParsedDate> {
+    let reference = match reference_time {
+        Some(value) => DateTime::parse_from_rfc3339(&value)
+            .map_err(|e| CommandError::invalid(format!("Invalid reference time '{value}': {e}")))?,
+        None => Local::now().fixed_offset(),
+    };
+    Ok(parse(&text, locale.as_deref(), reference))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday 15 January 2025, 10:00 UTC.
+    fn at(text: &str, locale: &str) -> ParsedDate {
+        let reference = DateTime::parse_from_rfc3339("2025-01-15T10:00:00+00:00").unwrap();
+        parse(text, Some(locale), reference)
+    }
+
+    fn date_of(text: &str) -> Option<String> {
+        at(text, "en-US").date
+    }
+
+    #[test]
+    fn reads_dates_and_times_out_of_titles() {
+        let parsed = at("Call mom tomorrow at 3pm", "en-US");
+        assert_eq!(parsed.date.as_deref(), Some("2025-01-16"));
+        assert_eq!(parsed.time.as_deref(), Some("15:00"));
+        assert_eq!(
+            parsed.datetime.as_deref(),
+            Some("2025-01-16T15:00:00+00:00")
+        );
+        assert_eq!(parsed.matched, ["tomorrow", "at 3pm"]);
+        assert_eq!(parsed.remaining, "Call mom");
+    }
+
+    #[test]
+    fn relative_phrases() {
+        assert_eq!(
+            date_of("Pay rent next friday").as_deref(),
+            Some("2025-01-24")
+        );
+        assert_eq!(date_of("Pay rent friday").as_deref(), Some("2025-01-17"));
+        assert_eq!(date_of("Report in 2 weeks").as_deref(), Some("2025-01-29"));
+        assert_eq!(
+            date_of("Invoice end of month").as_deref(),
+            Some("2025-01-31")
+        );
+        assert_eq!(date_of("Trip 14th of march").as_deref(), Some("2025-03-14"));
+        assert_eq!(date_of("Renew jan 3").as_deref(), Some("2026-01-03"));
+    }
+
+    #[test]
+    fn numeric_dates_follow_the_locale() {
+        assert_eq!(
+            at("Dentist 3/4", "en-US").date.as_deref(),
+            Some("2025-03-04")
+        );
+        assert_eq!(
+            at("Dentist 3/4", "en-GB").date.as_deref(),
+            Some("2025-04-03")
+        );
+        assert_eq!(date_of("Dentist 2025-02-01").as_deref(), Some("2025-02-01"));
+    }
+
+    #[test]
+    fn times_without_dates() {
+        // 9:00 has passed at 10:00, so it's tomorrow's.
+        let parsed = at("Standup at 9", "en-US");
+        assert_eq!(parsed.date.as_deref(), Some("2025-01-16"));
+        assert_eq!(parsed.time.as_deref(), Some("09:00"));
+
+        assert_eq!(at("Film tonight", "en-US").time.as_deref(), Some("20:00"));
+        assert_eq!(
+            at("Film tonight at 9:30pm", "en-US").time.as_deref(),
+            Some("21:30")
+        );
+    }
+
+    #[test]
+    fn recurrences() {
+        let parsed = at("Gym every mon, wed and fri", "en-US");
+        assert_eq!(
+            parsed.recurrence.as_deref(),
+            Some("DTSTART:20250115;FREQ=WEEKLY;BYDAY=MO,WE,FR")
+        );
+        assert_eq!(parsed.date.as_deref(), Some("2025-01-15"));
+        assert_eq!(parsed.remaining, "Gym");
+
+        let parsed = at("Review every last friday", "en-US");
+        assert_eq!(
+            parsed.recurrence.as_deref(),
+            Some("DTSTART:20250115;FREQ=MONTHLY;BYDAY=-1FR")
+        );
+        assert_eq!(parsed.date.as_deref(), Some("2025-01-31"));
+    }
+
+    #[test]
+    fn ambiguous_words_need_context() {
+        assert_eq!(date_of("Buy sun cream"), None);
+        assert_eq!(date_of("Ask if we may borrow the car"), None);
+        assert_eq!(date_of("Fix mar 3 bug in parser"), None);
+        assert_eq!(at("Buy sun cream", "en-US").remaining, "Buy sun cream");
+
+        assert_eq!(date_of("Lunch on sat").as_deref(), Some("2025-01-18"));
+        assert_eq!(date_of("next sat wash car").as_deref(), Some("2025-01-25"));
+        assert_eq!(date_of("Water plants sun").as_deref(), Some("2025-01-19"));
+        assert_eq!(date_of("Dinner may 5").as_deref(), Some("2025-05-05"));
+        assert_eq!(
+            date_of("Dinner saturday with Sam").as_deref(),
+            Some("2025-01-18")
+        );
+    }
+
+    #[test]
+    fn overflowing_offsets_are_ignored() {
+        assert_eq!(date_of("Plan in 4000000000 years"), None);
+        assert_eq!(date_of("Plan in 4000000000 days"), None);
+        assert_eq!(date_of("Plan in 4000000000 months"), None);
+        assert_eq!(
+            add(NaiveDate::MAX, 1, Unit::Day),
+            None,
+            "the last representable date has no successor"
+        );
+    }
+}
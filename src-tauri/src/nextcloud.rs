@@ -0,0 +1,244 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use tokio::time::{sleep, Instant};
+use url::Url;
+
+use crate::caldav::{self, CaldavAccount, NewCaldavAccount, TlsOptions, PROVIDER_NEXTCLOUD};
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::http;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Shown to the user in Nextcloud's list of devices and sessions.
+const USER_AGENT: &str = "DayLight";
+/// Where the WebDAV endpoints live, under the base URL.
+const DAV_PATH: &str = "remote.php/dav/";
+const LOGIN_PATH: &str = "index.php/login/v2";
+/// How often a login is checked on while the user signs in.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long Nextcloud keeps a login open.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(20 * 60);
+/// Path segments that start the part of a Nextcloud URL after its base.
+const ROUTE_SEGMENTS: [&str; 5] = ["index.php", "remote.php", "apps", "login", "settings"];
+
+/// A browser sign-in started with `start_nextcloud_login`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NextcloudLogin {
+    /// The page to open for the user to sign in and grant access.
+    pub login_url: String,
+    pub poll: NextcloudPoll,
+}
+
+/// Where to ask whether the user finished signing in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NextcloudPoll {
+    pub endpoint: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewNextcloudAccount {
+    /// Defaults to `user@host`.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The address Nextcloud is opened at in a browser.
+    pub server_url: String,
+    /// With `password`, for signing in with an app password made under
+    /// Settings > Security instead of through the browser.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// A browser sign-in to wait for.
+    #[serde(default)]
+    pub login: Option<NextcloudPoll>,
+    /// How long to wait for the browser sign-in; up to the 20 minutes
+    /// Nextcloud keeps it open.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub tls: TlsOptions,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginStarted {
+    login: String,
+    poll: NextcloudPoll,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginGranted {
+    server: String,
+    login_name: String,
+    app_password: String,
+}
+
+fn client(tls: &TlsOptions) -> CommandResult<Client> {
+    let builder = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(USER_AGENT);
+    Ok(tls.apply(builder)?.build()?)
+}
+
+/// The base URL of the Nextcloud at `value`, ending in a slash. Takes the
+/// address with or without a scheme, and any page of the web interface or
+/// its WebDAV URL.
+pub fn base_url(value: &str) -> CommandResult<Url> {
+    let value = value.trim();
+    let value = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("https://{value}")
+    };
+    let mut url = Url::parse(&value)
+        .map_err(|e| CommandError::invalid(format!("Invalid server URL: {e}")))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(CommandError::invalid(
+            "The server URL must start with http:// or https://",
+        ));
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+    let mut path = String::from("/");
+    for segment in url.path().split('/').filter(|s| !s.is_empty()) {
+        if ROUTE_SEGMENTS.contains(&segment) {
+            break;
+        }
+        path.push_str(segment);
+        path.push('/');
+    }
+    url.set_path(&path);
+    Ok(url)
+}
+
+/// Ask Nextcloud once whether the sign-in went through.
+async fn poll_once(client: &Client, poll: &NextcloudPoll) -> CommandResult<Option<LoginGranted>> {
+    let response = client
+        .post(&poll.endpoint)
+        .form(&[("token", poll.token.as_str())])
+        .send()
+        .await
+        .context("Nextcloud")?;
+    match response.status() {
+        StatusCode::NOT_FOUND => Ok(None),
+        status if !status.is_success() => Err(CommandError::status(
+            status,
+            format!("Nextcloud: HTTP {}", status.as_u16()),
+        )),
+        _ => http::read_json(response)
+            .await
+            .map(Some)
+            .context("Nextcloud"),
+    }
+}
+
+/// Wait for the user to finish signing in, up to `limit`.
+async fn await_login(
+    client: &Client,
+    poll: &NextcloudPoll,
+    limit: Duration,
+) -> CommandResult<LoginGranted> {
+    let deadline = Instant::now() + limit.min(LOGIN_TIMEOUT);
+    loop {
+        if let Some(granted) = poll_once(client, poll).await? {
+            return Ok(granted);
+        }
+        if Instant::now() + POLL_INTERVAL > deadline {
+            return Err("Timed out waiting for the Nextcloud sign-in".into());
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Start signing in to Nextcloud through the browser: open `login_url`,
+/// then pass `poll` to `add_nextcloud_account`, which waits for the user to
+/// grant access and gets an app password for DayLight.
+#[tauri::command]
+pub async fn start_nextcloud_login(
+    server_url: String,
+    tls: Option<TlsOptions>,
+) -> CommandResult<NextcloudLogin> {
+    let base = base_url(&server_url)?;
+    let client = client(&tls.unwrap_or_default())?;
+    let url = base.join(LOGIN_PATH).map_err(|e| e.to_string())?;
+    let response = client
+        .post(url)
+        .send()
+        .await
+        .with_context(|| base.host_str().unwrap_or("server").to_string())?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Err(CommandError::not_found(
+            "No Nextcloud found at this address",
+        ));
+    }
+    if !status.is_success() {
+        return Err(CommandError::status(
+            status,
+            format!("Nextcloud: HTTP {}", status.as_u16()),
+        ));
+    }
+    let started: LoginStarted = http::read_json(response).await.context("Nextcloud")?;
+    Ok(NextcloudLogin {
+        login_url: started.login,
+        poll: started.poll,
+    })
+}
+
+/// Add a Nextcloud account with an app password, or once a browser sign-in
+/// went through, and find the task lists of its Tasks app and its
+/// calendars. Lists and calendars are named with the account so those of
+/// two accounts can be told apart. Nothing syncs or shows until it's
+/// enabled.
+#[tauri::command]
+pub async fn add_nextcloud_account(
+    db: State<'_, Db>,
+    input: NewNextcloudAccount,
+) -> CommandResult<CaldavAccount> {
+    let tls = input.tls;
+    let (base, username, password) = match (&input.login, input.username, input.password) {
+        (Some(poll), _, _) => {
+            let limit = input
+                .timeout_ms
+                .map_or(LOGIN_TIMEOUT, Duration::from_millis);
+            let granted = await_login(&client(&tls)?, poll, limit).await?;
+            (
+                base_url(&granted.server)?,
+                granted.login_name,
+                granted.app_password,
+            )
+        }
+        (None, Some(username), Some(password)) => {
+            (base_url(&input.server_url)?, username, password)
+        }
+        (None, _, _) => {
+            return Err(CommandError::invalid(
+                "Sign in through the browser or enter an app password",
+            ))
+        }
+    };
+    let username = username.trim().to_string();
+    let host = base.host_str().unwrap_or("nextcloud");
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| format!("{username}@{host}"));
+    let server = base.join(DAV_PATH).map_err(|e| e.to_string())?;
+    caldav::add_account(
+        &db,
+        NewCaldavAccount {
+            name: Some(name),
+            server_url: server.to_string(),
+            username,
+            password,
+            tls,
+        },
+        Some(PROVIDER_NEXTCLOUD),
+    )
+    .await
+}
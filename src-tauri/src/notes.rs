@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::data_dir;
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::search::KIND_MARKDOWN;
+use crate::session::write_atomic;
+use crate::task_store;
+
+/// Emitted when a task's notes file was changed outside the app.
+pub const NOTE_CHANGED_EVENT: &str = "task-note-changed";
+
+const NOTES_DIR: &str = "notes";
+const NOTE_EXT: &str = "md";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteChange {
+    pub task_id: String,
+    /// The new contents; empty when the file was deleted.
+    pub content: String,
+}
+
+pub fn notes_dir(app: &AppHandle) -> CommandResult<PathBuf> {
+    data_dir::app_data_dir(app).map(|dir| dir.join(NOTES_DIR))
+}
+
+/// `<task id>.md` in the notes dir. Ids are checked so one can't point
+/// outside it.
+fn note_path(dir: &Path, task_id: &str) -> CommandResult<PathBuf> {
+    if task_id.is_empty()
+        || !task_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(CommandError::invalid(format!("Invalid task id: {task_id}")));
+    }
+    Ok(dir.join(format!("{task_id}.{NOTE_EXT}")))
+}
+
+/// The task a notes file belongs to, if it's one of ours.
+fn note_task_id(path: &Path) -> Option<String> {
+    if path.extension()? != NOTE_EXT {
+        return None;
+    }
+    Some(path.file_stem()?.to_str()?.to_string())
+}
+
+fn read_note(path: &Path) -> CommandResult<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(CommandError::from(e).context(format!("Failed to read {}", path.display()))),
+    }
+}
+
+fn indexed_note(conn: &Connection, task_id: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT body FROM search_index WHERE kind = ?1 AND ref_id = ?2",
+        params![KIND_MARKDOWN, task_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Put a task's notes in the search index, or take them out when empty.
+/// Returns false if the index already had exactly this content.
+pub fn index_note(conn: &Connection, task_id: &str, content: &str) -> rusqlite::Result<bool> {
+    let indexed = indexed_note(conn, task_id)?;
+    if indexed.as_deref().unwrap_or("") == content {
+        return Ok(false);
+    }
+    conn.execute(
+        "DELETE FROM search_index WHERE kind = ?1 AND ref_id = ?2",
+        params![KIND_MARKDOWN, task_id],
+    )?;
+    if !content.trim().is_empty() {
+        conn.execute(
+            "INSERT INTO search_index (kind, ref_id, title, body, tags)
+             SELECT ?1, id, '', ?3, '' FROM tasks WHERE id = ?2",
+            params![KIND_MARKDOWN, task_id, content],
+        )?;
+    }
+    Ok(true)
+}
+
+/// Bring the index in line with the notes on disk, for edits made while
+/// the app wasn't running or the database was locked.
+pub fn reindex_all(conn: &Connection, dir: &Path) -> CommandResult<()> {
+    let db_err = |e: rusqlite::Error| e.to_string();
+    let mut on_disk = HashSet::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for path in entries.flatten().map(|e| e.path()) {
+            let Some(task_id) = note_task_id(&path) else {
+                continue;
+            };
+            let content = read_note(&path)?;
+            index_note(conn, &task_id, &content).map_err(db_err)?;
+            on_disk.insert(task_id);
+        }
+    }
+    let mut stmt = conn
+        .prepare("SELECT ref_id FROM search_index WHERE kind = ?1")
+        .map_err(db_err)?;
+    let indexed = stmt
+        .query_map(params![KIND_MARKDOWN], |row| row.get::<_, String>(0))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(db_err)?;
+    for task_id in indexed.iter().filter(|id| !on_disk.contains(*id)) {
+        index_note(conn, task_id, "").map_err(db_err)?;
+    }
+    Ok(())
+}
+
+/// Reindex every note. Runs at startup and again once the database is
+/// unlocked.
+pub fn sync_index(app: &AppHandle) {
+    let db = app.state::<Db>();
+    if db.is_locked() {
+        return;
+    }
+    let result = notes_dir(app).and_then(|dir| db.with_conn(|conn| Ok(reindex_all(conn, &dir)))?);
+    if let Err(e) = result {
+        tracing::warn!("reindex failed: {e}");
+    }
+}
+
+/// Pick up a file changed outside the app: reindex it and tell the UI.
+/// Our own writes are already indexed, so they're skipped.
+fn reload_note(app: &AppHandle, path: &Path) -> CommandResult<()> {
+    let Some(task_id) = note_task_id(path) else {
+        return Ok(());
+    };
+    let db = app.state::<Db>();
+    if db.is_locked() {
+        return Ok(());
+    }
+    let content = read_note(path)?;
+    let changed = db.with_conn(|conn| {
+        if task_store::find_task(conn, &task_id)?.is_none() {
+            return Ok(false);
+        }
+        index_note(conn, &task_id, &content)
+    })?;
+    if changed {
+        let _ = app.emit(NOTE_CHANGED_EVENT, NoteChange { task_id, content });
+    }
+    Ok(())
+}
+
+/// Index the notes on disk, then watch the notes dir for edits made in
+/// another editor.
+pub fn spawn_note_watcher(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || {
+        sync_index(&handle);
+        let dir = match notes_dir(&handle) {
+            Ok(dir) => dir,
+            Err(e) => {
+                tracing::warn!("{e}");
+                return;
+            }
+        };
+        if let Err(e) = fs::create_dir_all(&dir) {
+            tracing::warn!("failed to create {}: {e}", dir.display());
+            return;
+        }
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = match RecommendedWatcher::new(
+            move |res: Result<notify::Event, notify::Error>| {
+                if let Ok(event) = res {
+                    for path in event.paths {
+                        let _ = tx.send(path);
+                    }
+                }
+            },
+            Config::default(),
+        ) {
+            Ok(w) => w,
+            Err(e) => {
+                tracing::warn!("failed to start watcher: {e}");
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            tracing::warn!("failed to watch {}: {e}", dir.display());
+            return;
+        }
+
+        // Editors often save in several steps; wait for them to settle.
+        let debounce = Duration::from_millis(300);
+        while let Ok(path) = rx.recv() {
+            let mut changed = HashSet::from([path]);
+            let deadline = Instant::now() + debounce;
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                match rx.recv_timeout(remaining) {
+                    Ok(path) => {
+                        changed.insert(path);
+                    }
+                    Err(_) => break,
+                }
+            }
+            for path in changed {
+                if let Err(e) = reload_note(&handle, &path) {
+                    tracing::warn!("{e}");
+                }
+            }
+        }
+    });
+}
+
+/// A task's notes as markdown; empty if it has none.
+#[tauri::command]
+pub fn read_task_note(app: AppHandle, task_id: String) -> CommandResult<String> {
+    read_note(&note_path(&notes_dir(&app)?, &task_id)?)
+}
+
+/// Save a task's notes. Saving empty notes deletes the file.
+#[tauri::command]
+pub fn write_task_note(
+    app: AppHandle,
+    db: State<'_, Db>,
+    task_id: String,
+    content: String,
+) -> CommandResult<()> {
+    let dir = notes_dir(&app)?;
+    let path = note_path(&dir, &task_id)?;
+    if db
+        .with_conn(|conn| task_store::find_task(conn, &task_id))?
+        .is_none()
+    {
+        return Err(CommandError::not_found(format!(
+            "Task not found: {task_id}"
+        )));
+    }
+    // Index before writing, so the watcher doesn't take the write for an
+    // outside edit.
+    db.with_conn(|conn| index_note(conn, &task_id, &content))?;
+    if content.is_empty() {
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(
+                    CommandError::from(e).context(format!("Failed to remove {}", path.display()))
+                )
+            }
+        }
+    } else {
+        fs::create_dir_all(&dir).context("Failed to create notes dir")?;
+        write_atomic(&path, content.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Where a task's notes file lives, created empty if missing, for opening
+/// in another editor.
+#[tauri::command]
+pub fn get_task_note_path(
+    app: AppHandle,
+    db: State<'_, Db>,
+    task_id: String,
+) -> CommandResult<String> {
+    let dir = notes_dir(&app)?;
+    let path = note_path(&dir, &task_id)?;
+    if db
+        .with_conn(|conn| task_store::find_task(conn, &task_id))?
+        .is_none()
+    {
+        return Err(CommandError::not_found(format!(
+            "Task not found: {task_id}"
+        )));
+    }
+    if !path.exists() {
+        fs::create_dir_all(&dir).context("Failed to create notes dir")?;
+        fs::write(&path, "").with_context(|| format!("Failed to create {}", path.display()))?;
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
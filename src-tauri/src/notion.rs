@@ -0,0 +1,1720 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Method, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::account_health::AccountHealth;
+use crate::conflicts::{self, ConflictPolicy};
+use crate::credentials;
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::http;
+use crate::outbox::{self, Replay};
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{
+    self, Capabilities, RemoteCollection, SyncFuture, SyncMode, SyncProvider,
+};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::tags;
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+
+/// `external_refs.source` for tasks that came from Notion pages.
+const SOURCE: &str = "notion";
+
+const API_URL: &str = "https://api.notion.com/v1";
+/// The API version requests are made against; Notion answers in that
+/// version's shapes whatever its latest is.
+const NOTION_VERSION: &str = "2022-06-28";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Notion allows an average of three requests a second per integration.
+const REQUEST_INTERVAL: Duration = Duration::from_millis(350);
+/// A throttled request is retried this many times before giving up.
+const MAX_ATTEMPTS: u32 = 4;
+const PAGE_SIZE: usize = 100;
+
+pub const KIND_TITLE: &str = "title";
+pub const KIND_STATUS: &str = "status";
+pub const KIND_SELECT: &str = "select";
+pub const KIND_CHECKBOX: &str = "checkbox";
+pub const KIND_DATE: &str = "date";
+pub const KIND_MULTI_SELECT: &str = "multi_select";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NotionAccount {
+    pub id: String,
+    pub name: String,
+    /// The workspace the integration belongs to.
+    pub workspace: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewNotionAccount {
+    /// Defaults to the workspace name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// An internal integration token. Only databases shared with the
+    /// integration are visible.
+    pub token: String,
+}
+
+/// A property of a database, with its options for select, status and
+/// multi-select ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotionProperty {
+    pub name: String,
+    pub kind: String,
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NotionDatabase {
+    pub id: String,
+    pub account_id: String,
+    pub title: String,
+    /// Local project the database's pages are filed under. New tasks in
+    /// this project are added to the database.
+    pub project: Option<String>,
+    pub enabled: bool,
+    /// The status, select or checkbox property a task's status maps to.
+    pub status_property: Option<String>,
+    /// The option of `status_property` done tasks have; checkboxes are
+    /// ticked instead.
+    pub done_value: Option<String>,
+    /// The option open tasks get when added or reopened.
+    pub open_value: Option<String>,
+    /// The date property a task's due date maps to.
+    pub date_property: Option<String>,
+    /// The multi-select property a task's tags map to.
+    pub tags_property: Option<String>,
+    pub properties: Vec<NotionProperty>,
+    pub last_synced_at: Option<String>,
+}
+
+impl NotionDatabase {
+    fn property(&self, name: Option<&str>) -> Option<&NotionProperty> {
+        let name = name?;
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    fn title_property(&self) -> Option<&NotionProperty> {
+        self.properties.iter().find(|p| p.kind == KIND_TITLE)
+    }
+}
+
+/// For the optional fields, a missing key leaves them alone while an
+/// explicit `null` clears them; a cleared property is no longer synced.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NotionDatabasePatch {
+    pub enabled: Option<bool>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub status_property: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub done_value: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub open_value: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub date_property: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub tags_property: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NotionSyncReport {
+    pub database_id: String,
+    pub title: String,
+    /// Tasks created or updated from Notion.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because their page was deleted in Notion.
+    pub removed: usize,
+    /// Tasks uploaded, new or changed.
+    pub uploaded: usize,
+    /// Pages archived in Notion because their task was deleted here.
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for NotionSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.uploaded + self.deleted
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RichText {
+    #[serde(default)]
+    plain_text: String,
+}
+
+fn plain_text(parts: &[RichText]) -> String {
+    parts.iter().map(|p| p.plain_text.as_str()).collect()
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RemoteOption {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RemoteOptions {
+    #[serde(default)]
+    options: Vec<RemoteOption>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteProperty {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    status: Option<RemoteOptions>,
+    #[serde(default)]
+    select: Option<RemoteOptions>,
+    #[serde(default)]
+    multi_select: Option<RemoteOptions>,
+}
+
+impl RemoteProperty {
+    fn options(&self) -> Vec<String> {
+        self.status
+            .iter()
+            .chain(&self.select)
+            .chain(&self.multi_select)
+            .flat_map(|o| o.options.iter().map(|o| o.name.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteDatabase {
+    id: String,
+    #[serde(default)]
+    title: Vec<RichText>,
+    #[serde(default)]
+    properties: HashMap<String, RemoteProperty>,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    in_trash: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemotePage {
+    id: String,
+    last_edited_time: String,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    in_trash: bool,
+    #[serde(default)]
+    properties: HashMap<String, serde_json::Value>,
+}
+
+impl RemotePage {
+    /// When the page last changed, in milliseconds, as the clocks of local
+    /// edits are. Notion rounds it to the minute.
+    fn modified_ms(&self) -> i64 {
+        parse_utc(&self.last_edited_time).map_or(0, |at| at.timestamp_millis())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListResponse<T> {
+    #[serde(default = "Vec::new")]
+    results: Vec<T>,
+    #[serde(default)]
+    has_more: bool,
+    #[serde(default)]
+    next_cursor: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RemoteBot {
+    #[serde(default)]
+    workspace_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteUser {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    bot: RemoteBot,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteError {
+    #[serde(default)]
+    message: String,
+}
+
+/// A page's mapped values. Unmapped ones are `None`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct PageFields {
+    title: String,
+    done: Option<bool>,
+    due: Option<Option<String>>,
+    tags: Option<Vec<String>>,
+}
+
+impl PageFields {
+    fn digest(&self) -> String {
+        let text = serde_json::to_string(self).unwrap_or_default();
+        format!("{:x}", Sha256::digest(text.as_bytes()))
+    }
+}
+
+/// A Notion date as tasks keep due dates: dates as they are, times as
+/// wall-clock times in `local`, or a date when at local midnight.
+fn from_notion_date(start: &str, local: &Tz) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(start, "%Y-%m-%d") {
+        return Some(date.to_string());
+    }
+    let at = DateTime::parse_from_rfc3339(start)
+        .ok()?
+        .with_timezone(local);
+    if at.time() == NaiveTime::MIN {
+        Some(at.date_naive().to_string())
+    } else {
+        Some(at.format("%Y-%m-%dT%H:%M").to_string())
+    }
+}
+
+/// The Notion date for a task's due date, a time pinned to `local`.
+fn to_notion_date(due: &str, local: &Tz) -> Option<String> {
+    if NaiveDate::parse_from_str(due, "%Y-%m-%d").is_ok() {
+        return Some(due.to_string());
+    }
+    let at = NaiveDateTime::parse_from_str(due, "%Y-%m-%dT%H:%M").ok()?;
+    timezone::resolve_local(at, local).map(|at| at.to_rfc3339())
+}
+
+/// A mapped property together with its value on `page`.
+fn page_value<'a>(
+    page: &'a RemotePage,
+    property: Option<&'a NotionProperty>,
+) -> Option<(&'a NotionProperty, &'a serde_json::Value)> {
+    let property = property?;
+    page.properties.get(&property.name).map(|v| (property, v))
+}
+
+/// The values of `page` for the properties `database` maps.
+fn read_page(page: &RemotePage, database: &NotionDatabase, local: &Tz) -> PageFields {
+    let value = |property| page_value(page, property);
+    let title = value(database.title_property())
+        .and_then(|(_, v)| v.get(KIND_TITLE).cloned())
+        .and_then(|v| serde_json::from_value::<Vec<RichText>>(v).ok())
+        .map(|parts| plain_text(&parts).trim().to_string())
+        .unwrap_or_default();
+    let done =
+        value(database.property(database.status_property.as_deref())).and_then(|(property, v)| {
+            match property.kind.as_str() {
+                KIND_CHECKBOX => v.get(KIND_CHECKBOX).and_then(|c| c.as_bool()),
+                kind => {
+                    let done_value = database.done_value.as_deref()?;
+                    let name = v
+                        .get(kind)
+                        .and_then(|o| o.get("name"))
+                        .and_then(|n| n.as_str());
+                    Some(name == Some(done_value))
+                }
+            }
+        });
+    let due = value(database.property(database.date_property.as_deref())).map(|(_, v)| {
+        v.get(KIND_DATE)
+            .and_then(|d| d.get("start"))
+            .and_then(|s| s.as_str())
+            .and_then(|start| from_notion_date(start, local))
+    });
+    let tags = value(database.property(database.tags_property.as_deref())).map(|(_, v)| {
+        let names: Vec<String> = v
+            .get(KIND_MULTI_SELECT)
+            .and_then(|o| o.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|o| o.get("name").and_then(|n| n.as_str()))
+            .map(str::to_string)
+            .filter(|n| !n.trim().is_empty())
+            .collect();
+        tags::normalize_names(&names).unwrap_or_default()
+    });
+    PageFields {
+        title,
+        done,
+        due,
+        tags,
+    }
+}
+
+/// The page properties for `task`, for the properties `database` maps.
+/// Notion makes a multi-select option for a tag it hasn't seen; option
+/// names can't hold commas.
+fn page_properties(task: &Task, database: &NotionDatabase, local: &Tz) -> serde_json::Value {
+    let mut properties = serde_json::Map::new();
+    if let Some(title) = database.title_property() {
+        properties.insert(
+            title.name.clone(),
+            json!({ "title": [{ "type": "text", "text": { "content": task.title } }] }),
+        );
+    }
+    if let Some(status) = database.property(database.status_property.as_deref()) {
+        let done = task.status == STATUS_DONE;
+        let value = match status.kind.as_str() {
+            KIND_CHECKBOX => Some(json!({ "checkbox": done })),
+            kind => {
+                let name = if done {
+                    &database.done_value
+                } else {
+                    &database.open_value
+                };
+                name.as_ref().map(|name| json!({ kind: { "name": name } }))
+            }
+        };
+        if let Some(value) = value {
+            properties.insert(status.name.clone(), value);
+        }
+    }
+    if let Some(date) = database.property(database.date_property.as_deref()) {
+        let start = task
+            .due
+            .as_deref()
+            .and_then(|due| to_notion_date(due, local));
+        let value = match start {
+            Some(start) => json!({ "date": { "start": start } }),
+            None => json!({ "date": null }),
+        };
+        properties.insert(date.name.clone(), value);
+    }
+    if let Some(tags) = database.property(database.tags_property.as_deref()) {
+        let options: Vec<serde_json::Value> = task
+            .tags
+            .iter()
+            .map(|tag| json!({ "name": tag.replace(',', " ") }))
+            .collect();
+        properties.insert(tags.name.clone(), json!({ "multi_select": options }));
+    }
+    serde_json::Value::Object(properties)
+}
+
+fn row_to_account(row: &Row) -> rusqlite::Result<NotionAccount> {
+    Ok(NotionAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        workspace: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+const ACCOUNT_COLUMNS: &str = "id, name, workspace, created_at, updated_at";
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<NotionAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM notion_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<NotionAccount>> {
+    conn.query_row(
+        &format!("SELECT {ACCOUNT_COLUMNS} FROM notion_accounts WHERE id = ?1"),
+        params![id],
+        row_to_account,
+    )
+    .optional()
+}
+
+fn row_to_database(row: &Row) -> rusqlite::Result<NotionDatabase> {
+    Ok(NotionDatabase {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        title: row.get(2)?,
+        project: row.get(3)?,
+        enabled: row.get(4)?,
+        status_property: row.get(5)?,
+        done_value: row.get(6)?,
+        open_value: row.get(7)?,
+        date_property: row.get(8)?,
+        tags_property: row.get(9)?,
+        last_synced_at: row.get(10)?,
+        properties: Vec::new(),
+    })
+}
+
+const DATABASE_COLUMNS: &str = "id, account_id, title, project, enabled, status_property,
+    done_value, open_value, date_property, tags_property, last_synced_at";
+
+fn load_properties(conn: &Connection, database_id: &str) -> rusqlite::Result<Vec<NotionProperty>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, kind, options FROM notion_properties
+         WHERE database_id = ?1 ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map(params![database_id], |row| {
+        let options: String = row.get(2)?;
+        Ok(NotionProperty {
+            name: row.get(0)?,
+            kind: row.get(1)?,
+            options: serde_json::from_str(&options).unwrap_or_default(),
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn list_databases(
+    conn: &Connection,
+    account_id: &str,
+) -> rusqlite::Result<Vec<NotionDatabase>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {DATABASE_COLUMNS} FROM notion_databases
+         WHERE account_id = ?1 ORDER BY title COLLATE NOCASE"
+    ))?;
+    let mut databases = stmt
+        .query_map(params![account_id], row_to_database)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for database in &mut databases {
+        database.properties = load_properties(conn, &database.id)?;
+    }
+    Ok(databases)
+}
+
+fn find_database(conn: &Connection, id: &str) -> rusqlite::Result<Option<NotionDatabase>> {
+    let database = conn
+        .query_row(
+            &format!("SELECT {DATABASE_COLUMNS} FROM notion_databases WHERE id = ?1"),
+            params![id],
+            row_to_database,
+        )
+        .optional()?;
+    let Some(mut database) = database else {
+        return Ok(None);
+    };
+    database.properties = load_properties(conn, &database.id)?;
+    Ok(Some(database))
+}
+
+fn save_mapping(conn: &Connection, database: &NotionDatabase) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE notion_databases
+         SET project = ?2, enabled = ?3, status_property = ?4, done_value = ?5,
+             open_value = ?6, date_property = ?7, tags_property = ?8
+         WHERE id = ?1",
+        params![
+            database.id,
+            database.project,
+            database.enabled,
+            database.status_property,
+            database.done_value,
+            database.open_value,
+            database.date_property,
+            database.tags_property
+        ],
+    )?;
+    Ok(())
+}
+
+/// The first property of one of `kinds` whose name is one of `names`,
+/// or failing that the first of those kinds at all.
+fn pick_property(database: &NotionDatabase, kinds: &[&str], names: &[&str]) -> Option<String> {
+    let candidates: Vec<&NotionProperty> = database
+        .properties
+        .iter()
+        .filter(|p| kinds.contains(&p.kind.as_str()))
+        .collect();
+    candidates
+        .iter()
+        .find(|p| names.iter().any(|n| p.name.eq_ignore_ascii_case(n)))
+        .or(candidates.first())
+        .map(|p| p.name.clone())
+}
+
+/// Drop mappings to properties or options the database no longer has,
+/// then, when `guess` is set, map what's unmapped to the likeliest
+/// properties.
+fn check_mapping(database: &mut NotionDatabase, guess: bool) {
+    let kind_of = |db: &NotionDatabase, name: &Option<String>| {
+        db.property(name.as_deref()).map(|p| p.kind.clone())
+    };
+    if !matches!(
+        kind_of(database, &database.status_property).as_deref(),
+        Some(KIND_STATUS | KIND_SELECT | KIND_CHECKBOX)
+    ) {
+        database.status_property = None;
+    }
+    if kind_of(database, &database.date_property).as_deref() != Some(KIND_DATE) {
+        database.date_property = None;
+    }
+    if kind_of(database, &database.tags_property).as_deref() != Some(KIND_MULTI_SELECT) {
+        database.tags_property = None;
+    }
+    if guess {
+        if database.status_property.is_none() {
+            database.status_property = pick_property(database, &[KIND_STATUS], &["Status"])
+                .or_else(|| pick_property(database, &[KIND_CHECKBOX], &["Done", "Completed"]));
+        }
+        if database.date_property.is_none() {
+            database.date_property = pick_property(database, &[KIND_DATE], &["Due", "Due date"]);
+        }
+        if database.tags_property.is_none() {
+            database.tags_property = pick_property(database, &[KIND_MULTI_SELECT], &["Tags"]);
+        }
+    }
+
+    let options = match database.property(database.status_property.as_deref()) {
+        Some(p) if p.kind != KIND_CHECKBOX => p.options.clone(),
+        _ => Vec::new(),
+    };
+    for value in [&mut database.done_value, &mut database.open_value] {
+        if value.as_ref().is_some_and(|v| !options.contains(v)) {
+            *value = None;
+        }
+    }
+    if guess && !options.is_empty() {
+        if database.done_value.is_none() {
+            database.done_value = options
+                .iter()
+                .find(|o| {
+                    ["Done", "Complete", "Completed"]
+                        .iter()
+                        .any(|n| o.eq_ignore_ascii_case(n))
+                })
+                .or(options.last())
+                .cloned();
+        }
+        if database.open_value.is_none() {
+            database.open_value = options.first().cloned();
+        }
+    }
+}
+
+/// Save a database's schema, and bring its mapping in line with it.
+/// Before its first sync, what's unmapped is guessed.
+fn store_properties(
+    conn: &Connection,
+    database: &mut NotionDatabase,
+    found: &HashMap<String, RemoteProperty>,
+) -> rusqlite::Result<()> {
+    for (name, property) in found {
+        let options = serde_json::to_string(&property.options()).unwrap_or_default();
+        conn.execute(
+            "INSERT INTO notion_properties (database_id, name, kind, options)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(database_id, name) DO UPDATE SET
+                 kind = excluded.kind,
+                 options = excluded.options",
+            params![database.id, name, property.kind, options],
+        )?;
+    }
+    let names: Vec<&String> = found.keys().collect();
+    conn.execute(
+        "DELETE FROM notion_properties
+         WHERE database_id = ?1 AND name NOT IN (SELECT value FROM json_each(?2))",
+        params![
+            database.id,
+            serde_json::to_string(&names).unwrap_or_default()
+        ],
+    )?;
+    database.properties = load_properties(conn, &database.id)?;
+    check_mapping(database, database.last_synced_at.is_none());
+    save_mapping(conn, database)
+}
+
+/// Save the databases shared with an account's integration. Databases it
+/// can no longer see, or that were deleted, are dropped along with their
+/// sync state; their tasks stay.
+fn store_databases(
+    conn: &Connection,
+    account_id: &str,
+    found: &[RemoteDatabase],
+) -> rusqlite::Result<()> {
+    let found: Vec<&RemoteDatabase> = found
+        .iter()
+        .filter(|d| !d.archived && !d.in_trash)
+        .collect();
+    for remote in &found {
+        let title = match plain_text(&remote.title).trim() {
+            "" => "Untitled".to_string(),
+            title => title.to_string(),
+        };
+        conn.execute(
+            "INSERT INTO notion_databases (id, account_id, remote_id, title)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(account_id, remote_id) DO UPDATE SET title = excluded.title",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                account_id,
+                remote.id,
+                title
+            ],
+        )?;
+        let id: String = conn.query_row(
+            "SELECT id FROM notion_databases WHERE account_id = ?1 AND remote_id = ?2",
+            params![account_id, remote.id],
+            |row| row.get(0),
+        )?;
+        if let Some(mut database) = find_database(conn, &id)? {
+            store_properties(conn, &mut database, &remote.properties)?;
+        }
+    }
+    let ids: Vec<&str> = found.iter().map(|d| d.id.as_str()).collect();
+    conn.execute(
+        "DELETE FROM notion_databases
+         WHERE account_id = ?1 AND remote_id NOT IN (SELECT value FROM json_each(?2))",
+        params![account_id, serde_json::to_string(&ids).unwrap_or_default()],
+    )?;
+    Ok(())
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("notion:{account_id}")
+}
+
+/// A connection to the Notion API with one account's token. Requests are
+/// spaced out to stay under Notion's rate limit.
+struct Api {
+    client: Client,
+    token: String,
+    /// When the next request may go out.
+    next: Mutex<Instant>,
+}
+
+impl Api {
+    fn new(token: String) -> CommandResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self {
+            client,
+            token,
+            next: Mutex::new(Instant::now()),
+        })
+    }
+
+    fn connect(account: &NotionAccount) -> CommandResult<Self> {
+        Self::new(credentials::require(&keyring_name(&account.id), "token")?)
+    }
+
+    async fn throttle(&self) {
+        let wait = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let at = (*next).max(now);
+            *next = at + REQUEST_INTERVAL;
+            at - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Ask who the token belongs to, for the account health check.
+    async fn probe(&self) -> Result<Response, reqwest::Error> {
+        self.client
+            .get(format!("{API_URL}/users/me"))
+            .bearer_auth(&self.token)
+            .header("Notion-Version", NOTION_VERSION)
+            .send()
+            .await
+    }
+
+    /// Send a request to `path` under the API. `None` when Notion has no
+    /// such thing, or hasn't shared it with the integration.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        rate_limit::check(SOURCE, "Notion")?;
+        let url = format!("{API_URL}/{path}");
+        let mut attempt = 1;
+        loop {
+            self.throttle().await;
+            let mut request = self
+                .client
+                .request(method.clone(), &url)
+                .bearer_auth(&self.token)
+                .header("Notion-Version", NOTION_VERSION);
+            if let Some(body) = body {
+                request = request
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.to_string());
+            }
+            let response = request.send().await.context("Notion")?;
+            match response.status() {
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                    if attempt < MAX_ATTEMPTS =>
+                {
+                    tokio::time::sleep(rate_limit::retry_wait(response.headers(), attempt)).await;
+                    attempt += 1;
+                }
+                StatusCode::NOT_FOUND => return Ok(None),
+                StatusCode::UNAUTHORIZED => {
+                    return Err(CommandError::unauthorized(
+                        "Notion refused the token; connect the account again",
+                    )
+                    .with_provider(SOURCE))
+                }
+                StatusCode::TOO_MANY_REQUESTS => {
+                    return Err(rate_limit::limited(SOURCE, "Notion", response.headers()))
+                }
+                status if !status.is_success() => {
+                    let message = http::read_json::<RemoteError>(response)
+                        .await
+                        .map(|e| e.message)
+                        .unwrap_or_default();
+                    let message = if message.is_empty() {
+                        format!("Notion: HTTP {}", status.as_u16())
+                    } else {
+                        format!("Notion: {message}")
+                    };
+                    return Err(CommandError::status(status, message).with_provider(SOURCE));
+                }
+                _ => return http::read_json(response).await.map(Some).context("Notion"),
+            }
+        }
+    }
+
+    /// Every result of a paginated POST to `path`.
+    async fn list<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        filter: serde_json::Value,
+    ) -> CommandResult<Option<Vec<T>>> {
+        let mut results = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut body = filter.clone();
+            body["page_size"] = json!(PAGE_SIZE);
+            if let Some(cursor) = &cursor {
+                body["start_cursor"] = json!(cursor);
+            }
+            let Some(page) = self
+                .send::<ListResponse<T>>(Method::POST, path, Some(&body))
+                .await?
+            else {
+                return Ok(None);
+            };
+            results.extend(page.results);
+            match page.next_cursor {
+                Some(next) if page.has_more => cursor = Some(next),
+                _ => return Ok(Some(results)),
+            }
+        }
+    }
+
+    /// The databases shared with the integration.
+    async fn databases(&self) -> CommandResult<Vec<RemoteDatabase>> {
+        let filter = json!({ "filter": { "property": "object", "value": "database" } });
+        Ok(self.list("search", filter).await?.unwrap_or_default())
+    }
+}
+
+/// What the last sync knew about one page.
+#[derive(Debug, Clone)]
+struct PageRow {
+    task_id: String,
+    digest: String,
+    /// The task's `updated_at` when it last matched Notion.
+    synced_at: String,
+}
+
+fn load_pages(conn: &Connection, database_id: &str) -> rusqlite::Result<HashMap<String, PageRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT page_id, task_id, digest, synced_at FROM notion_pages WHERE database_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![database_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            PageRow {
+                task_id: row.get(1)?,
+                digest: row.get(2)?,
+                synced_at: row.get(3)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_page(
+    conn: &Connection,
+    database_id: &str,
+    page_id: &str,
+    page: &PageRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO notion_pages (database_id, page_id, task_id, digest, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(database_id, page_id) DO UPDATE SET
+             task_id = excluded.task_id,
+             digest = excluded.digest,
+             synced_at = excluded.synced_at",
+        params![
+            database_id,
+            page_id,
+            page.task_id,
+            page.digest,
+            page.synced_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_page(conn: &Connection, database_id: &str, page_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM notion_pages WHERE database_id = ?1 AND page_id = ?2",
+        params![database_id, page_id],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = Some(now_utc());
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// Create or update the task for a page's mapped values. Unless `policy`
+/// says remote wins, a field edited here after the page last changed in
+/// Notion keeps the local value; fields whose property isn't mapped always
+/// do. Returns the task and whether it was created, or `None` when nothing
+/// changed.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    fields: &PageFields,
+    project: Option<&str>,
+    modified: i64,
+    policy: ConflictPolicy,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let title = if fields.title.is_empty() {
+        "Untitled".to_string()
+    } else {
+        fields.title.clone()
+    };
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description: None,
+            project: project.map(str::to_string),
+            priority: None,
+            due: fields.due.clone().flatten(),
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if fields.done == Some(true) {
+            set_done(&mut task, true);
+            task_store::write_task(conn, &task)?;
+        }
+        if let Some(labels) = &fields.tags {
+            tags::set_task_tags(conn, &task.id, labels)?;
+            task.tags = labels.clone();
+        }
+        return Ok(Some((task, true)));
+    };
+
+    let take = conflicts::remote_fields(conn, &task.id, Some(modified), policy)?;
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if let Some(due) = &fields.due {
+        if take("due") {
+            task.due = due.clone();
+        }
+    }
+    if let Some(done) = fields.done {
+        if take("status") && done != (task.status == STATUS_DONE) {
+            set_done(&mut task, done);
+        }
+    }
+    let mut tags_changed = false;
+    if let Some(labels) = &fields.tags {
+        let mut have: Vec<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+        let mut want: Vec<String> = labels.iter().map(|t| t.to_lowercase()).collect();
+        have.sort();
+        want.sort();
+        tags_changed = take("tags") && have != want;
+    }
+
+    let changed =
+        task.title != before.title || task.due != before.due || task.status != before.status;
+    if !changed && !tags_changed {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    if let (true, Some(labels)) = (tags_changed, &fields.tags) {
+        tags::set_task_tags(conn, &task.id, labels)?;
+        task.tags = labels.clone();
+    }
+    Ok(Some((task, false)))
+}
+
+/// A change to send to Notion.
+#[derive(Debug)]
+enum Upload {
+    Insert { task: Task },
+    Update { page_id: String, task: Task },
+    Archive { page_id: String, task_id: String },
+}
+
+impl Upload {
+    fn task_id(&self) -> &str {
+        match self {
+            Upload::Insert { task } | Upload::Update { task, .. } => &task.id,
+            Upload::Archive { task_id, .. } => task_id,
+        }
+    }
+}
+
+/// Apply the database's pages, unless only exporting, then work out which
+/// tasks to upload. Linked pages missing from `pages` were deleted or
+/// archived in Notion.
+fn merge_remote(
+    conn: &mut Connection,
+    database: &NotionDatabase,
+    pages: &[RemotePage],
+    local: &Tz,
+    imports: bool,
+    report: &mut NotionSyncReport,
+) -> rusqlite::Result<Vec<Upload>> {
+    let tx = conn.transaction()?;
+    let known = load_pages(&tx, &database.id)?;
+    let policy = conflicts::policy(&tx, SOURCE)?;
+    let pages: Vec<&RemotePage> = pages
+        .iter()
+        .filter(|p| !p.archived && !p.in_trash)
+        .collect();
+
+    for page in &pages {
+        let fields = read_page(page, database, local);
+        let digest = fields.digest();
+        let row = known.get(&page.id);
+        if row.is_some_and(|r| r.digest == digest) {
+            continue;
+        }
+        let task_id = match row {
+            Some(row) => Some(row.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, page.id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if row.is_some() && existing.is_none() {
+            // Deleted here; the page is archived below.
+            continue;
+        }
+        if !imports {
+            // Only the digest is taken, so local edits go up over the page.
+            if let Some(row) = row {
+                let row = PageRow {
+                    digest,
+                    ..row.clone()
+                };
+                save_page(&tx, &database.id, &page.id, &row)?;
+            }
+            continue;
+        }
+        let dirty = match (row, &existing) {
+            (Some(row), Some(task)) if task.updated_at > row.synced_at => {
+                Some(row.synced_at.clone())
+            }
+            _ => None,
+        };
+        let project = database.project.as_deref();
+        let modified = page.modified_ms();
+        let written = match existing {
+            Some(existing)
+                if dirty.is_some()
+                    && conflicts::keep_local(&tx, SOURCE, policy, &existing, |tx| {
+                        let forced = ConflictPolicy::Remote;
+                        let written = write_remote(
+                            tx,
+                            Some(existing.clone()),
+                            &fields,
+                            project,
+                            modified,
+                            forced,
+                        )?;
+                        Ok(written.map_or_else(|| existing.clone(), |(task, _)| task))
+                    })? =>
+            {
+                None
+            }
+            existing => write_remote(&tx, existing, &fields, project, modified, policy)?,
+        };
+        let task_id = match written {
+            Some((task, true)) => {
+                report.created += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, page.id, task.id, now_utc()],
+                )?;
+                task.id
+            }
+            Some((task, false)) => {
+                report.updated += 1;
+                task.id
+            }
+            None => task_id.unwrap_or_default(),
+        };
+        let Some(task) = task_store::find_task(&tx, &task_id)? else {
+            continue;
+        };
+        let row = PageRow {
+            task_id: task.id,
+            digest,
+            // Local edits Notion doesn't have yet are kept dirty.
+            synced_at: dirty
+                .filter(|_| policy != ConflictPolicy::Remote)
+                .unwrap_or(task.updated_at),
+        };
+        save_page(&tx, &database.id, &page.id, &row)?;
+    }
+
+    let listed: HashSet<&str> = pages.iter().map(|p| p.id.as_str()).collect();
+    for (page_id, row) in &known {
+        if listed.contains(page_id.as_str()) {
+            continue;
+        }
+        if imports && trash::trash_task(&tx, &row.task_id)? {
+            report.removed += 1;
+        }
+        forget_page(&tx, &database.id, page_id)?;
+    }
+
+    let held = conflicts::held(&tx, SOURCE)?;
+    let mut uploads = Vec::new();
+    for (page_id, row) in load_pages(&tx, &database.id)? {
+        match task_store::find_task(&tx, &row.task_id)? {
+            None => uploads.push(Upload::Archive {
+                page_id,
+                task_id: row.task_id,
+            }),
+            Some(task) if task.updated_at > row.synced_at && !held.contains(&task.id) => {
+                uploads.push(Upload::Update { page_id, task })
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Notion pages have no subtasks, so only top-level tasks go up.
+    if let Some(project) = &database.project {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM tasks
+             WHERE project = ?1 COLLATE NOCASE AND deleted_at IS NULL AND parent_id IS NULL
+               AND status = ?2 AND id NOT IN (SELECT task_id FROM notion_pages)
+             ORDER BY created_at",
+        )?;
+        let ids = stmt
+            .query_map(params![project, STATUS_OPEN], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        for id in ids {
+            if let Some(task) = task_store::find_task(&tx, &id)? {
+                uploads.push(Upload::Insert { task });
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(uploads)
+}
+
+/// What came of one upload.
+enum Uploaded {
+    Saved { page_id: String, page: PageRow },
+    Deleted { page_id: String },
+}
+
+/// Uploads for one database.
+struct Uploader<'a> {
+    api: &'a Api,
+    remote_id: String,
+    database: &'a NotionDatabase,
+    local: Tz,
+}
+
+impl Uploader<'_> {
+    /// The link to keep for `task` now that Notion has it as `page`.
+    fn saved(&self, page: &RemotePage, task: Task) -> Uploaded {
+        let digest = read_page(page, self.database, &self.local).digest();
+        Uploaded::Saved {
+            page_id: page.id.clone(),
+            page: PageRow {
+                task_id: task.id,
+                digest,
+                synced_at: task.updated_at,
+            },
+        }
+    }
+
+    async fn upload(&self, change: Upload) -> CommandResult<Uploaded> {
+        match change {
+            Upload::Insert { task } => {
+                let body = json!({
+                    "parent": { "database_id": self.remote_id },
+                    "properties": page_properties(&task, self.database, &self.local),
+                });
+                let created: Option<RemotePage> =
+                    self.api.send(Method::POST, "pages", Some(&body)).await?;
+                let created = created
+                    .ok_or_else(|| CommandError::not_found("Database not found in Notion"))?;
+                Ok(self.saved(&created, task))
+            }
+            Upload::Update { page_id, task } => {
+                let body = json!({
+                    "properties": page_properties(&task, self.database, &self.local),
+                });
+                let updated: Option<RemotePage> = self
+                    .api
+                    .send(Method::PATCH, &format!("pages/{page_id}"), Some(&body))
+                    .await?;
+                // Deleted in Notion meanwhile. The link is dropped; the task
+                // is added again as new while it's in the database's
+                // project.
+                let Some(updated) = updated else {
+                    return Ok(Uploaded::Deleted { page_id });
+                };
+                Ok(self.saved(&updated, task))
+            }
+            Upload::Archive { page_id, .. } => {
+                let body = json!({ "archived": true });
+                self.api
+                    .send::<serde_json::Value>(
+                        Method::PATCH,
+                        &format!("pages/{page_id}"),
+                        Some(&body),
+                    )
+                    .await?;
+                Ok(Uploaded::Deleted { page_id })
+            }
+        }
+    }
+}
+
+fn save_results(
+    conn: &mut Connection,
+    database: &NotionDatabase,
+    results: &[Uploaded],
+    report: &mut NotionSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for result in results {
+        match result {
+            Uploaded::Saved { page_id, page } => {
+                report.uploaded += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, page_id, page.task_id, now_utc()],
+                )?;
+                save_page(&tx, &database.id, page_id, page)?;
+            }
+            Uploaded::Deleted { page_id } => {
+                report.deleted += 1;
+                forget_page(&tx, &database.id, page_id)?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+/// Two-way sync of one database: refresh its schema, fetch its pages,
+/// merge their mapped values field by field, and when pushing upload local
+/// changes.
+async fn sync_database(
+    db: &Db,
+    api: &Api,
+    database: &NotionDatabase,
+    mode: SyncMode,
+) -> CommandResult<NotionSyncReport> {
+    let mut report = NotionSyncReport {
+        database_id: database.id.clone(),
+        title: database.title.clone(),
+        ..NotionSyncReport::default()
+    };
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let remote_id: String = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT remote_id FROM notion_databases WHERE id = ?1",
+            params![database.id],
+            |row| row.get(0),
+        )
+    })?;
+    let not_found = "Database not found in Notion; share it with the integration again";
+    let found: RemoteDatabase = api
+        .send(Method::GET, &format!("databases/{remote_id}"), None)
+        .await?
+        .ok_or(not_found)?;
+    let pages: Vec<RemotePage> = api
+        .list(&format!("databases/{remote_id}/query"), json!({}))
+        .await?
+        .ok_or(not_found)?;
+
+    let mut database = database.clone();
+    let uploads = db.with_conn(|conn| {
+        store_properties(conn, &mut database, &found.properties)?;
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(conn, &database, &pages, &local, mode.imports(), &mut report)
+        })?
+    })?;
+    if database.title_property().is_none() {
+        return Err("The database has no title property".into());
+    }
+
+    let uploader = Uploader {
+        api,
+        remote_id,
+        database: &database,
+        local,
+    };
+    // Importing only leaves local changes here.
+    let uploads = if mode.exports() { uploads } else { Vec::new() };
+    let uploads = db.with_conn(|conn| outbox::in_order(conn, uploads, Upload::task_id))?;
+    let mut results = Vec::new();
+    let mut replay = Replay::default();
+    for change in uploads {
+        let task_id = change.task_id().to_string();
+        match uploader.upload(change).await {
+            Ok(result) => {
+                replay.delivered(task_id);
+                results.push(result);
+            }
+            Err(e) => {
+                replay.failed(task_id, &e);
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    db.with_conn(|conn| {
+        replay.save(conn)?;
+        save_results(conn, &database, &results, &mut report)?;
+        conn.execute(
+            "UPDATE notion_databases SET last_synced_at = ?2 WHERE id = ?1",
+            params![database.id, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+fn find_notion_account(db: &Db, account_id: &str) -> CommandResult<NotionAccount> {
+    db.with_conn(|conn| find_account(conn, account_id))?
+        .ok_or_else(|| CommandError::not_found(format!("Notion account not found: {account_id}")))
+}
+
+/// Check an integration token and save the account, with the databases
+/// shared with it. None syncs until it's enabled.
+#[tauri::command]
+pub async fn connect_notion_account(
+    db: State<'_, Db>,
+    input: NewNotionAccount,
+) -> CommandResult<NotionAccount> {
+    let token = input.token.trim().to_string();
+    if token.is_empty() {
+        return Err(CommandError::invalid("Enter the Notion integration token"));
+    }
+    let api = Api::new(token.clone())?;
+    let user: RemoteUser = api
+        .send(Method::GET, "users/me", None)
+        .await?
+        .ok_or_else(|| CommandError::unauthorized("Notion: no integration for this token"))?;
+    let databases = api.databases().await?;
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let workspace = user.bot.workspace_name.filter(|w| !w.trim().is_empty());
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or_else(|| workspace.clone())
+        .or(user.name)
+        .unwrap_or_else(|| "Notion".to_string());
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO notion_accounts (id, name, workspace, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![id, name, workspace, now],
+        )?;
+        store_databases(&tx, &id, &databases)?;
+        tx.commit()?;
+        find_account(conn, &id)
+    });
+    match stored {
+        Ok(Some(account)) => Ok(account),
+        Ok(None) => Err("Failed to save account".into()),
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_notion_accounts(db: State<'_, Db>) -> CommandResult<Vec<NotionAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Forget an account and its databases. Synced tasks stay, unlinked.
+#[tauri::command]
+pub fn remove_notion_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db
+        .with_conn(|conn| conn.execute("DELETE FROM notion_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Notion account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+#[tauri::command]
+pub fn list_notion_databases(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<NotionDatabase>> {
+    db.with_conn(|conn| list_databases(conn, &account_id))
+}
+
+async fn refresh_databases(db: &Db, account_id: &str) -> CommandResult<Vec<NotionDatabase>> {
+    let account = find_notion_account(db, account_id)?;
+    let found = Api::connect(&account)?.databases().await?;
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_databases(&tx, &account.id, &found)?;
+        tx.commit()?;
+        list_databases(conn, &account.id)
+    })
+}
+
+/// Look for databases shared with or removed from the integration since,
+/// and changes to their properties.
+#[tauri::command]
+pub async fn refresh_notion_databases(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<NotionDatabase>> {
+    refresh_databases(&db, &account_id).await
+}
+
+fn update_database(db: &Db, id: &str, patch: NotionDatabasePatch) -> CommandResult<NotionDatabase> {
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut database) = find_database(conn, id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Notion database not found: {id}"
+            ))));
+        };
+        let before = (
+            database.status_property.clone(),
+            database.done_value.clone(),
+            database.open_value.clone(),
+            database.date_property.clone(),
+            database.tags_property.clone(),
+        );
+        let checks: [(&Option<Option<String>>, &[&str]); 3] = [
+            (
+                &patch.status_property,
+                &[KIND_STATUS, KIND_SELECT, KIND_CHECKBOX],
+            ),
+            (&patch.date_property, &[KIND_DATE]),
+            (&patch.tags_property, &[KIND_MULTI_SELECT]),
+        ];
+        for (name, kinds) in checks {
+            let Some(Some(name)) = name else {
+                continue;
+            };
+            match database.property(Some(name)) {
+                None => {
+                    return Ok(Err(CommandError::not_found(format!(
+                        "Property not found in this database: {name}"
+                    ))))
+                }
+                Some(p) if !kinds.contains(&p.kind.as_str()) => {
+                    return Ok(Err(CommandError::invalid(format!(
+                        "{name} can't be mapped here"
+                    ))))
+                }
+                Some(_) => {}
+            }
+        }
+        if let Some(status) = patch.status_property {
+            if status != database.status_property {
+                database.done_value = None;
+                database.open_value = None;
+            }
+            database.status_property = status;
+        }
+        if let Some(date) = patch.date_property {
+            database.date_property = date;
+        }
+        if let Some(tags) = patch.tags_property {
+            database.tags_property = tags;
+        }
+        let options = database
+            .property(database.status_property.as_deref())
+            .map(|p| p.options.clone())
+            .unwrap_or_default();
+        for (value, patch) in [
+            (&mut database.done_value, patch.done_value),
+            (&mut database.open_value, patch.open_value),
+        ] {
+            let Some(new) = patch else {
+                continue;
+            };
+            if new.as_ref().is_some_and(|v| !options.contains(v)) {
+                return Ok(Err(CommandError::not_found(format!(
+                    "Option not found: {}",
+                    new.unwrap_or_default()
+                ))));
+            }
+            *value = new;
+        }
+        if let Some(project) = project {
+            database.project = project;
+        }
+        if let Some(enabled) = patch.enabled {
+            database.enabled = enabled;
+        }
+        if database.enabled && database.project.is_none() {
+            database.project = Some(database.title.clone());
+        }
+        let after = (
+            database.status_property.clone(),
+            database.done_value.clone(),
+            database.open_value.clone(),
+            database.date_property.clone(),
+            database.tags_property.clone(),
+        );
+        if before != after {
+            conn.execute(
+                "UPDATE notion_pages SET digest = '' WHERE database_id = ?1",
+                params![database.id],
+            )?;
+        }
+        save_mapping(conn, &database)?;
+        Ok(Ok(database))
+    })?
+}
+
+/// Turn syncing of a database on or off, or change its project or which
+/// properties map to a task's status, due date and tags. A database is
+/// enabled with a project named after it unless one is given. A new
+/// mapping applies to every page on the next sync.
+#[tauri::command]
+pub fn update_notion_database(
+    db: State<'_, Db>,
+    id: String,
+    patch: NotionDatabasePatch,
+) -> CommandResult<NotionDatabase> {
+    update_database(&db, &id, patch)
+}
+
+pub struct Notion;
+
+impl SyncProvider for Notion {
+    type Report = Vec<NotionSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Notion"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: true,
+            conflicts: true,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(&account)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                match Api::connect(&account) {
+                    Ok(api) => {
+                        health.answer("Notion", api.probe().await);
+                    }
+                    Err(e) => health.no_credentials(&e),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Import))
+    }
+
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::TwoWay))
+    }
+
+    fn export<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Export))
+    }
+
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let databases = match refresh_databases(db, &account.id).await {
+                    Ok(databases) => databases,
+                    Err(e) => {
+                        tracing::warn!("refresh of {} failed: {e}", account.name);
+                        db.with_conn(|conn| list_databases(conn, &account.id))?
+                    }
+                };
+                found.extend(databases.into_iter().map(|d| RemoteCollection {
+                    id: d.id,
+                    account_id: d.account_id,
+                    name: d.title,
+                    selected: d.enabled,
+                }));
+            }
+            Ok(found)
+        })
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        let patch = NotionDatabasePatch {
+            enabled: Some(selected),
+            ..NotionDatabasePatch::default()
+        };
+        update_database(db, id, patch).map(drop)
+    }
+}
+
+/// Sync every enabled database of `account_id`, or of all accounts. One
+/// database failing doesn't stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_notion(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<NotionSyncReport>> {
+    sync_provider::run_command(&app, &Notion, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+    mode: SyncMode,
+) -> CommandResult<Vec<NotionSyncReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let databases = db.with_conn(|conn| list_databases(conn, &account.id))?;
+        let api = Api::connect(&account);
+        for database in databases.iter().filter(|d| d.enabled) {
+            let result = match &api {
+                Ok(api) => sync_database(db, api, database, mode).await,
+                Err(e) => Err(e.clone()),
+            };
+            let report = result.unwrap_or_else(|e| {
+                tracing::warn!("sync of {} failed: {e}", database.title);
+                NotionSyncReport {
+                    database_id: database.id.clone(),
+                    title: database.title.clone(),
+                    errors: vec![e.to_string()],
+                    ..NotionSyncReport::default()
+                }
+            });
+            progress.record(&report.title, &report);
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
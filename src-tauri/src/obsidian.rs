@@ -0,0 +1,605 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::data_dir;
+use crate::db::{format_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::locale;
+use crate::session::write_atomic;
+use crate::task_store::{self, Task, TaskFilter, STATUS_DONE, STATUS_OPEN};
+use crate::time_entries::{self, TimeEntry};
+use crate::timezone;
+
+/// Emitted with the `ObsidianExport` after an export.
+pub const OBSIDIAN_EXPORTED_EVENT: &str = "obsidian-exported";
+/// Emitted with the error message when a scheduled or manual export fails.
+pub const OBSIDIAN_FAILED_EVENT: &str = "obsidian-export-failed";
+
+const CONFIG_FILE: &str = "obsidian.json";
+const SCHEDULE_POLL: Duration = Duration::from_secs(600);
+const PROJECTS_DIR: &str = "Projects";
+const DAILY_DIR: &str = "Daily";
+const INBOX_FILE: &str = "Inbox.md";
+/// First frontmatter key of every file the export writes. Only files that
+/// start with it are ever replaced or removed.
+const MARKER: &str = "---\ndaylight: ";
+
+/// When the last export ran, for the scheduler.
+static LAST_EXPORT: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObsidianConfig {
+    /// Export on a schedule as well as when asked.
+    pub enabled: bool,
+    pub vault: Option<String>,
+    /// The folder inside the vault the export writes to.
+    pub folder: String,
+    pub interval_hours: u32,
+    /// Daily notes, and completed tasks in the project lists, cover this
+    /// many days back, today included.
+    pub days: u32,
+}
+
+impl Default for ObsidianConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vault: None,
+            folder: "DayLight".to_string(),
+            interval_hours: 6,
+            days: 14,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ObsidianExport {
+    pub folder: String,
+    /// Files created or changed.
+    pub written: usize,
+    pub unchanged: usize,
+    /// Files from earlier exports with nothing to say any more.
+    pub removed: usize,
+    pub exported_at: String,
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+pub fn load_config(app: &AppHandle) -> ObsidianConfig {
+    let Ok(path) = config_path(app) else {
+        return ObsidianConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            ObsidianConfig::default()
+        }),
+        Err(_) => ObsidianConfig::default(),
+    }
+}
+
+/// The export folder, which has to be inside the vault.
+fn export_dir(config: &ObsidianConfig) -> CommandResult<PathBuf> {
+    let vault = config
+        .vault
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| CommandError::invalid("Choose an Obsidian vault"))?;
+    if !Path::new(vault).is_dir() {
+        return Err(CommandError::not_found(format!("Vault not found: {vault}")));
+    }
+    let folder = Path::new(config.folder.trim());
+    let inside = folder
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if folder.as_os_str().is_empty() || !inside {
+        return Err(CommandError::invalid(
+            "The export folder must be a folder inside the vault",
+        ));
+    }
+    Ok(Path::new(vault).join(folder))
+}
+
+/// A string as a YAML scalar. JSON strings are valid YAML.
+fn yaml(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// A name usable as a file name on every platform and as an Obsidian link.
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches(['.', ' ']);
+    if name.is_empty() {
+        "Untitled".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// A tag name as an Obsidian `#tag`, which can't hold spaces or most
+/// punctuation.
+fn tag(name: &str) -> String {
+    let tag: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || matches!(c, '_' | '-' | '/') => c,
+            _ => '-',
+        })
+        .collect();
+    format!("#{tag}")
+}
+
+fn local_date(at: &str, local: &Tz) -> Option<NaiveDate> {
+    parse_utc(at)
+        .ok()
+        .map(|at| at.with_timezone(local).date_naive())
+}
+
+fn date_of(value: Option<&str>) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value?.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// A task as a checkbox line, with dates and priority in the emoji format
+/// of the Tasks community plugin so they stay queryable in the vault.
+fn task_line(task: &Task, depth: usize, local: &Tz) -> String {
+    let done = task.status == STATUS_DONE;
+    let title = task.title.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut line = format!(
+        "{}- [{}] {title}",
+        "    ".repeat(depth),
+        if done { 'x' } else { ' ' }
+    );
+    match task.priority {
+        Some(p) if p >= 3 => line.push_str(" ⏫"),
+        Some(1) => line.push_str(" 🔽"),
+        _ => {}
+    }
+    if let Some(scheduled) = date_of(task.scheduled.as_deref()) {
+        line.push_str(&format!(" ⏳ {scheduled}"));
+    }
+    if let Some(due) = date_of(task.due.as_deref()) {
+        line.push_str(&format!(" 📅 {due}"));
+    }
+    if let Some(completed) = task
+        .completed_at
+        .as_deref()
+        .and_then(|at| local_date(at, local))
+        .filter(|_| done)
+    {
+        line.push_str(&format!(" ✅ {completed}"));
+    }
+    for name in &task.tags {
+        line.push(' ');
+        line.push_str(&tag(name));
+    }
+    line
+}
+
+/// Each task of a list with its subtasks nested under it, as a branch of
+/// lines per top-level task. Subtasks whose parent isn't in the list start
+/// their own branch.
+fn task_tree<'a>(tasks: &[&'a Task], local: &Tz) -> Vec<(&'a Task, Vec<String>)> {
+    let ids: HashSet<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+    let mut children: HashMap<&str, Vec<&Task>> = HashMap::new();
+    for task in tasks {
+        if let Some(parent) = task.parent_id.as_deref().filter(|p| ids.contains(p)) {
+            children.entry(parent).or_default().push(task);
+        }
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
+    }
+    fn walk(
+        task: &Task,
+        depth: usize,
+        children: &HashMap<&str, Vec<&Task>>,
+        local: &Tz,
+        out: &mut Vec<String>,
+    ) {
+        out.push(task_line(task, depth, local));
+        for child in children.get(task.id.as_str()).into_iter().flatten() {
+            walk(child, depth + 1, children, local, out);
+        }
+    }
+    tasks
+        .iter()
+        .filter(|t| t.parent_id.as_deref().is_none_or(|p| !ids.contains(p)))
+        .map(|task| {
+            let mut lines = Vec::new();
+            walk(task, 0, &children, local, &mut lines);
+            (*task, lines)
+        })
+        .collect()
+}
+
+/// A project's (or the inbox's) list: open tasks, then those completed
+/// within the window. Subtasks stay with their parent whatever their
+/// status.
+fn list_file(name: &str, project: Option<&str>, tasks: &[&Task], local: &Tz) -> String {
+    let done = tasks.iter().filter(|t| t.status == STATUS_DONE).count();
+    let updated = tasks
+        .iter()
+        .map(|t| t.updated_at.as_str())
+        .max()
+        .unwrap_or_default();
+    let mut out = format!("{MARKER}list\n");
+    if let Some(project) = project {
+        out.push_str(&format!("project: {}\n", yaml(project)));
+    }
+    out.push_str(&format!(
+        "open: {}\ndone: {done}\nupdated: {}\n---\n\n# {name}\n",
+        tasks.len() - done,
+        yaml(updated)
+    ));
+    let (completed, open): (Vec<_>, Vec<_>) = task_tree(tasks, local)
+        .into_iter()
+        .partition(|(root, _)| root.status == STATUS_DONE);
+    for (heading, branches) in [(None, open), (Some("Completed"), completed)] {
+        if branches.is_empty() {
+            continue;
+        }
+        if let Some(heading) = heading {
+            out.push_str(&format!("\n## {heading}\n"));
+        }
+        out.push('\n');
+        for (_, lines) in branches {
+            for line in lines {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// The minutes of `entry` that fall within `[from, to)`.
+fn minutes_within(
+    entry: &TimeEntry,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> i64 {
+    let Ok(start) = parse_utc(&entry.started_at) else {
+        return 0;
+    };
+    let end = entry
+        .ended_at
+        .as_deref()
+        .and_then(|at| parse_utc(at).ok())
+        .unwrap_or(now);
+    (end.min(to) - start.max(from)).num_minutes().max(0)
+}
+
+/// A day's note: what was completed, due and scheduled that day, and the
+/// time tracked. `None` for a day with nothing to say.
+fn daily_file(
+    date: NaiveDate,
+    tasks: &[Task],
+    entries: &[TimeEntry],
+    local: &Tz,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let start = |date: NaiveDate| {
+        timezone::resolve_local(date.and_time(NaiveTime::MIN), local).map(|at| at.to_utc())
+    };
+    let (from, to) = (start(date)?, start(date.succ_opt()?)?);
+    let titles: HashMap<&str, &str> = tasks
+        .iter()
+        .map(|t| (t.id.as_str(), t.title.as_str()))
+        .collect();
+
+    let completed: Vec<&Task> = tasks
+        .iter()
+        .filter(|t| t.status == STATUS_DONE)
+        .filter(|t| {
+            t.completed_at
+                .as_deref()
+                .and_then(|at| local_date(at, local))
+                == Some(date)
+        })
+        .collect();
+    let open = |value: fn(&Task) -> Option<&str>| -> Vec<&Task> {
+        tasks
+            .iter()
+            .filter(|t| t.status == STATUS_OPEN && date_of(value(t)) == Some(date))
+            .collect()
+    };
+    let due = open(|t| t.due.as_deref());
+    let scheduled = open(|t| t.scheduled.as_deref());
+    let worked: Vec<(&TimeEntry, i64)> = entries
+        .iter()
+        .map(|e| (e, minutes_within(e, from, to, now)))
+        .filter(|(_, minutes)| *minutes > 0)
+        .collect();
+    if completed.is_empty() && due.is_empty() && scheduled.is_empty() && worked.is_empty() {
+        return None;
+    }
+
+    let tracked: i64 = worked.iter().map(|(_, minutes)| minutes).sum();
+    let mut out = format!(
+        "{MARKER}daily\ndate: {date}\ncompleted: {}\ntracked_minutes: {tracked}\n---\n\n# {}\n",
+        completed.len(),
+        date.format("%A, %B %-d, %Y")
+    );
+    for (heading, tasks) in [
+        ("Completed", &completed),
+        ("Due", &due),
+        ("Scheduled", &scheduled),
+    ] {
+        if tasks.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n## {heading}\n\n"));
+        for task in tasks.iter() {
+            out.push_str(&task_line(task, 0, local));
+            out.push('\n');
+        }
+    }
+    if !worked.is_empty() {
+        out.push_str("\n## Time\n\n");
+        let locale = locale::current();
+        for (entry, minutes) in worked {
+            let clock = |at: &str| {
+                parse_utc(at)
+                    .ok()
+                    .map(|at| locale.format_time(at.with_timezone(local).time()))
+            };
+            let start = clock(&entry.started_at).unwrap_or_default();
+            let end = entry
+                .ended_at
+                .as_deref()
+                .and_then(clock)
+                .unwrap_or_else(|| "now".to_string());
+            let title = titles
+                .get(entry.task_id.as_str())
+                .copied()
+                .unwrap_or("Deleted task");
+            out.push_str(&format!(
+                "- {start}–{end} {title} ({}h {:02}m)",
+                minutes / 60,
+                minutes % 60
+            ));
+            if let Some(note) = entry
+                .note
+                .as_deref()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+            {
+                out.push_str(&format!(
+                    " — {}",
+                    note.split_whitespace().collect::<Vec<_>>().join(" ")
+                ));
+            }
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+/// Every file the export writes, by path within the export folder, and the
+/// daily notes it covers whether or not they have anything to say.
+fn render(
+    tasks: &[Task],
+    entries: &[TimeEntry],
+    today: NaiveDate,
+    days: u32,
+    local: &Tz,
+    now: DateTime<Utc>,
+) -> (Vec<(PathBuf, String)>, Vec<PathBuf>) {
+    let first = today
+        .checked_sub_days(Days::new(u64::from(days.max(1) - 1)))
+        .unwrap_or(today);
+    let recent = |task: &&Task| {
+        task.status == STATUS_OPEN
+            || task
+                .completed_at
+                .as_deref()
+                .and_then(|at| local_date(at, local))
+                .is_some_and(|d| d >= first)
+    };
+
+    let mut files = Vec::new();
+    let mut projects: HashMap<&str, Vec<&Task>> = HashMap::new();
+    let mut inbox = Vec::new();
+    for task in tasks.iter().filter(recent) {
+        match task.project.as_deref() {
+            Some(project) => projects.entry(project).or_default().push(task),
+            None => inbox.push(task),
+        }
+    }
+    if !inbox.is_empty() {
+        files.push((
+            PathBuf::from(INBOX_FILE),
+            list_file("Inbox", None, &inbox, local),
+        ));
+    }
+    let mut names: Vec<&str> = projects.keys().copied().collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    for name in names {
+        let path = Path::new(PROJECTS_DIR).join(format!("{}.md", file_name(name)));
+        files.push((path, list_file(name, Some(name), &projects[name], local)));
+    }
+
+    let mut covered = Vec::new();
+    let mut date = first;
+    while date <= today {
+        let path = Path::new(DAILY_DIR).join(format!("{date}.md"));
+        if let Some(note) = daily_file(date, tasks, entries, local, now) {
+            files.push((path.clone(), note));
+        }
+        covered.push(path);
+        let Some(next) = date.succ_opt() else {
+            break;
+        };
+        date = next;
+    }
+    (files, covered)
+}
+
+fn is_ours(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|content| content.replace("\r\n", "\n").starts_with(MARKER))
+}
+
+/// Write the export into the vault. Files are only touched when their
+/// content changed, so the vault's sync and history see real edits only.
+/// Notes from earlier exports are removed when they have nothing left to
+/// say: project lists for projects without tasks, the inbox, and daily
+/// notes within the window. Daily notes older than that are kept.
+pub fn export(app: &AppHandle) -> CommandResult<ObsidianExport> {
+    let config = load_config(app);
+    let dir = export_dir(&config)?;
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let now = Utc::now();
+    let today = now.with_timezone(&local).date_naive();
+    let from = today
+        .checked_sub_days(Days::new(u64::from(config.days.max(1))))
+        .and_then(|d| timezone::resolve_local(d.and_time(NaiveTime::MIN), &local))
+        .map(|at| format_utc(at.to_utc()))
+        .unwrap_or_default();
+
+    let db = app.state::<Db>();
+    let (tasks, entries) = db.with_conn(|conn| {
+        let tasks = task_store::query_tasks(conn, &TaskFilter::default())?;
+        let entries = time_entries::entries_in_range(conn, &from, &format_utc(now), None)?;
+        Ok((tasks, entries))
+    })?;
+    let (files, covered) = render(&tasks, &entries, today, config.days, &local, now);
+
+    let mut report = ObsidianExport {
+        folder: dir.to_string_lossy().to_string(),
+        written: 0,
+        unchanged: 0,
+        removed: 0,
+        exported_at: format_utc(now),
+    };
+    for sub in [PROJECTS_DIR, DAILY_DIR] {
+        fs::create_dir_all(dir.join(sub))
+            .with_context(|| format!("Failed to create {}", dir.join(sub).display()))?;
+    }
+    let mut written = HashSet::new();
+    for (path, content) in &files {
+        let path = dir.join(path);
+        written.insert(path.clone());
+        if fs::read_to_string(&path).is_ok_and(|current| current == *content) {
+            report.unchanged += 1;
+            continue;
+        }
+        if path.exists() && !is_ours(&path) {
+            return Err(CommandError::conflict(format!(
+                "{} exists and wasn't written by DayLight",
+                path.display()
+            )));
+        }
+        write_atomic(&path, content.as_bytes())?;
+        report.written += 1;
+    }
+
+    let mut stale: Vec<PathBuf> = covered.iter().map(|p| dir.join(p)).collect();
+    stale.push(dir.join(INBOX_FILE));
+    if let Ok(listing) = fs::read_dir(dir.join(PROJECTS_DIR)) {
+        stale.extend(
+            listing
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "md")),
+        );
+    }
+    for path in stale {
+        if written.contains(&path) || !path.is_file() || !is_ours(&path) {
+            continue;
+        }
+        fs::remove_file(&path).with_context(|| format!("Failed to delete {}", path.display()))?;
+        report.removed += 1;
+    }
+    Ok(report)
+}
+
+/// Export, emitting `OBSIDIAN_EXPORTED_EVENT` or `OBSIDIAN_FAILED_EVENT`.
+pub fn run_export(app: &AppHandle) -> CommandResult<ObsidianExport> {
+    let result = export(app);
+    *LAST_EXPORT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    match &result {
+        Ok(report) => {
+            let _ = app.emit(OBSIDIAN_EXPORTED_EVENT, report);
+        }
+        Err(e) => {
+            tracing::warn!("{e}");
+            let _ = app.emit(OBSIDIAN_FAILED_EVENT, e);
+        }
+    }
+    result
+}
+
+fn export_due(app: &AppHandle, config: &ObsidianConfig) -> bool {
+    if !config.enabled || app.state::<Db>().is_locked() {
+        return false;
+    }
+    let interval = Duration::from_secs(u64::from(config.interval_hours.max(1)) * 3600);
+    LAST_EXPORT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_none_or(|last| last.elapsed() >= interval)
+}
+
+/// Export whenever the configured interval has passed since the last one,
+/// and once soon after launch. The config is re-read on every check.
+pub fn spawn_obsidian_scheduler(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || loop {
+        if export_due(&handle, &load_config(&handle)) {
+            let _ = run_export(&handle);
+        }
+        std::thread::sleep(SCHEDULE_POLL);
+    });
+}
+
+#[tauri::command]
+pub fn get_obsidian_config(app: AppHandle) -> ObsidianConfig {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn set_obsidian_config(
+    app: AppHandle,
+    config: ObsidianConfig,
+) -> CommandResult<ObsidianConfig> {
+    if config
+        .vault
+        .as_deref()
+        .is_some_and(|v| !v.trim().is_empty())
+        || config.enabled
+    {
+        export_dir(&config)?;
+    }
+    let path = config_path(&app)?;
+    let body = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn export_obsidian_now(app: AppHandle) -> CommandResult<ObsidianExport> {
+    run_export(&app)
+}
@@ -0,0 +1,103 @@
+/// Base-62 digits in ASCII order, so keys sort correctly as SQLite text.
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+fn digit_value(c: u8) -> usize {
+    DIGITS.iter().position(|d| *d == c).unwrap_or(0)
+}
+
+/// Midpoint of two digit strings with `a < b` (`None` meaning past the
+/// end). Neither may end in the zero digit, and neither does the result.
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> Vec<u8> {
+    if let Some(b) = b {
+        // Skip the shared prefix, reading a missing digit of `a` as zero.
+        let shared = b
+            .iter()
+            .enumerate()
+            .take_while(|(i, c)| a.get(*i).copied().unwrap_or(DIGITS[0]) == **c)
+            .count();
+        if shared > 0 {
+            let mut key = b[..shared].to_vec();
+            key.extend(midpoint(a.get(shared..).unwrap_or(&[]), Some(&b[shared..])));
+            return key;
+        }
+    }
+
+    let low = a.first().map_or(0, |c| digit_value(*c));
+    let high = b
+        .and_then(|b| b.first())
+        .map_or(DIGITS.len(), |c| digit_value(*c));
+    if high > low + 1 {
+        return vec![DIGITS[(low + high) / 2]];
+    }
+    match b {
+        // `b`'s first digit alone already sorts between the two.
+        Some(b) if b.len() > 1 => vec![b[0]],
+        _ => {
+            let mut key = vec![DIGITS[low]];
+            key.extend(midpoint(a.get(1..).unwrap_or(&[]), None));
+            key
+        }
+    }
+}
+
+/// Fractional index: a key sorting after `before` and ahead of `after`, with
+/// `None` on either side meaning that end of the list. Moving an item only
+/// rewrites its own key, so the order of everything else stays put.
+/// `before` must sort ahead of `after`.
+pub fn between(before: Option<&str>, after: Option<&str>) -> String {
+    let key = midpoint(before.unwrap_or("").as_bytes(), after.map(str::as_bytes));
+    String::from_utf8(key).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `between`, checking the key sorts strictly inside its bounds and
+    /// doesn't end in the zero digit.
+    fn key(before: Option<&str>, after: Option<&str>) -> String {
+        let key = between(before, after);
+        assert!(!key.is_empty() && !key.ends_with('0'), "bad key {key:?}");
+        assert!(
+            before.is_none_or(|b| b < key.as_str()),
+            "{before:?} !< {key}"
+        );
+        assert!(after.is_none_or(|a| key.as_str() < a), "{key} !< {after:?}");
+        key
+    }
+
+    #[test]
+    fn ends_of_the_list() {
+        assert_eq!(key(None, None), "V");
+        key(Some("V"), None);
+        key(None, Some("V"));
+        key(Some("z"), None);
+        key(None, Some("1"));
+    }
+
+    #[test]
+    fn between_adjacent_keys() {
+        assert_eq!(key(Some("V"), Some("W")), "VV");
+        assert_eq!(key(Some("a"), Some("a1")), "a0V");
+        key(Some("Vz"), Some("W"));
+        key(Some("V"), Some("V01"));
+        key(Some("yzz"), Some("z"));
+        key(Some("zz"), None);
+    }
+
+    #[test]
+    fn repeated_inserts_stay_ordered() {
+        let mut keys = vec![key(None, None)];
+        // Always just after the first item, then always at the front.
+        for _ in 0..200 {
+            let next = key(Some(&keys[0]), keys.get(1).map(String::as_str));
+            keys.insert(1, next);
+        }
+        for _ in 0..200 {
+            let next = key(None, Some(&keys[0]));
+            keys.insert(0, next);
+        }
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(keys.iter().all(|k| k.len() <= 201));
+    }
+}
@@ -0,0 +1,1034 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+use crate::crdt;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::session::write_atomic;
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::tags;
+use crate::task_store::{self, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+
+const UNTITLED: &str = "Untitled";
+/// Planning keywords in the order org writes them.
+const PLANNING: [&str; 3] = ["CLOSED", "DEADLINE", "SCHEDULED"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrgFile {
+    pub id: String,
+    pub path: String,
+    pub last_synced_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OrgSyncReport {
+    /// Tasks created or updated from headings added or edited in the files.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because their heading was deleted or is no
+    /// longer a TODO.
+    pub removed: usize,
+    /// Headings rewritten for tasks changed here.
+    pub written: usize,
+    /// Files that couldn't be synced, with why.
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for OrgSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.written
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+/// The TODO keywords in effect for a file: its `#+TODO:` lines, or org's
+/// default `TODO | DONE`.
+#[derive(Debug, Clone)]
+struct Keywords {
+    open: Vec<String>,
+    done: Vec<String>,
+}
+
+fn keywords(text: &str) -> Keywords {
+    let (mut open, mut done) = (Vec::new(), Vec::new());
+    for line in text.lines() {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let key = key.to_ascii_lowercase();
+        if !matches!(key.as_str(), "#+todo" | "#+seq_todo" | "#+typ_todo") {
+            continue;
+        }
+        // Drop fast-access keys and logging flags: `WAIT(w@/!)` is `WAIT`.
+        let words: Vec<&str> = value
+            .split_whitespace()
+            .map(|w| w.split('(').next().unwrap_or(w))
+            .filter(|w| !w.is_empty())
+            .collect();
+        let owned = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        match words.iter().position(|w| *w == "|") {
+            Some(bar) => {
+                open.extend(owned(&words[..bar]));
+                done.extend(owned(&words[bar + 1..]));
+            }
+            // Without a bar the last keyword is the done one.
+            None if !words.is_empty() => {
+                open.extend(owned(&words[..words.len() - 1]));
+                done.extend(owned(&words[words.len() - 1..]));
+            }
+            None => {}
+        }
+    }
+    if open.is_empty() && done.is_empty() {
+        open.push("TODO".to_string());
+        done.push("DONE".to_string());
+    }
+    Keywords { open, done }
+}
+
+type When = (NaiveDate, Option<NaiveTime>);
+
+/// A timestamp such as `<2030-01-05 Sat 10:00 +1w>`.
+#[derive(Debug, Clone, PartialEq)]
+struct Stamp {
+    active: bool,
+    date: NaiveDate,
+    time: Option<NaiveTime>,
+    /// Repeater and warning period, such as `+1w -2d`, kept as written.
+    suffix: String,
+    text: String,
+}
+
+fn parse_stamp(text: &str) -> Option<Stamp> {
+    let active = text.starts_with('<');
+    let inner = text.get(1..text.len().checked_sub(1)?)?;
+    let mut words = inner.split_whitespace();
+    let date = NaiveDate::parse_from_str(words.next()?, "%Y-%m-%d").ok()?;
+    let (mut time, mut suffix) = (None, Vec::new());
+    for word in words {
+        let lead = word.chars().next().unwrap_or(' ');
+        if suffix.is_empty() && time.is_none() {
+            if lead.is_ascii_digit() {
+                // `10:00-11:30` is a range; its start is the time.
+                let start = word.split('-').next().unwrap_or(word);
+                time = NaiveTime::parse_from_str(start, "%H:%M").ok();
+                if time.is_some() {
+                    continue;
+                }
+            } else if !matches!(lead, '+' | '-' | '.') {
+                // The day name, in whatever language it was written.
+                continue;
+            }
+        }
+        suffix.push(word);
+    }
+    Some(Stamp {
+        active,
+        date,
+        time,
+        suffix: suffix.join(" "),
+        text: text.to_string(),
+    })
+}
+
+/// The timestamp for `when`, keeping `old` as written when it's the same
+/// moment, and its repeater when it isn't.
+fn stamp_text(old: Option<&Stamp>, when: When, active: bool) -> String {
+    if let Some(old) = old.filter(|old| (old.date, old.time) == when) {
+        return old.text.clone();
+    }
+    let (date, time) = when;
+    let mut text = date.format("%Y-%m-%d %a").to_string();
+    if let Some(time) = time {
+        text.push_str(&time.format(" %H:%M").to_string());
+    }
+    if let Some(old) = old.filter(|old| !old.suffix.is_empty()) {
+        text.push(' ');
+        text.push_str(&old.suffix);
+    }
+    match old.map_or(active, |old| old.active) {
+        true => format!("<{text}>"),
+        false => format!("[{text}]"),
+    }
+}
+
+/// The line of `CLOSED:`, `DEADLINE:` and `SCHEDULED:` stamps under a
+/// headline.
+#[derive(Debug, Clone, Default)]
+struct Planning {
+    indent: String,
+    entries: Vec<(String, Stamp)>,
+    /// Whatever followed that couldn't be read, kept as written.
+    tail: String,
+}
+
+impl Planning {
+    fn get(&self, kind: &str) -> Option<&Stamp> {
+        self.entries.iter().find(|(k, _)| k == kind).map(|(_, s)| s)
+    }
+}
+
+fn parse_planning(line: &str) -> Option<Planning> {
+    let trimmed = line.trim_start();
+    let starts = |text: &str| {
+        PLANNING.into_iter().find(|kind| {
+            text.strip_prefix(kind)
+                .is_some_and(|rest| rest.starts_with(':'))
+        })
+    };
+    starts(trimmed)?;
+    let mut planning = Planning {
+        indent: line[..line.len() - trimmed.len()].to_string(),
+        ..Planning::default()
+    };
+    let mut rest = trimmed;
+    loop {
+        rest = rest.trim_start();
+        let Some(kind) = starts(rest) else {
+            break;
+        };
+        let after = rest[kind.len() + 1..].trim_start();
+        let close = match after.chars().next() {
+            Some('<') => '>',
+            Some('[') => ']',
+            _ => break,
+        };
+        let Some(stamp) = after
+            .find(close)
+            .and_then(|end| parse_stamp(&after[..=end]).map(|stamp| (stamp, end)))
+        else {
+            break;
+        };
+        planning.entries.push((kind.to_string(), stamp.0));
+        rest = &after[stamp.1 + 1..];
+    }
+    planning.tail = rest.trim().to_string();
+    Some(planning)
+}
+
+/// A headline's parts after its stars.
+#[derive(Debug, Clone, PartialEq)]
+struct Headline {
+    stars: usize,
+    keyword: String,
+    done: bool,
+    priority: Option<char>,
+    title: String,
+    tags: Vec<String>,
+    /// The column the tags end at, which org lines them up by.
+    tags_end: Option<usize>,
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '%')
+}
+
+fn parse_tags(word: &str) -> Option<Vec<String>> {
+    let inner = word.strip_prefix(':')?.strip_suffix(':')?;
+    let tags: Vec<String> = inner.split(':').map(str::to_string).collect();
+    tags.iter()
+        .all(|t| !t.is_empty() && t.chars().all(is_tag_char))
+        .then_some(tags)
+}
+
+/// Read a headline, if it's a TODO one: stars, a keyword, an optional
+/// `[#A]` priority cookie, the title and `:tags:`.
+fn parse_headline(line: &str, keywords: &Keywords) -> Option<Headline> {
+    let stars = line.bytes().take_while(|b| *b == b'*').count();
+    if stars == 0 {
+        return None;
+    }
+    let rest = line[stars..].strip_prefix(' ')?.trim_start();
+    let (keyword, rest) = rest.split_once([' ', '\t']).unwrap_or((rest, ""));
+    let done = keywords.done.iter().any(|k| k == keyword);
+    if !done && !keywords.open.iter().any(|k| k == keyword) {
+        return None;
+    }
+    let mut rest = rest.trim();
+    let mut priority = None;
+    if let [b'[', b'#', p, b']', ..] = rest.as_bytes() {
+        if p.is_ascii_alphanumeric() {
+            priority = Some(*p as char);
+            rest = rest[4..].trim_start();
+        }
+    }
+    let (title, tags) = match rest.rsplit_once([' ', '\t']) {
+        Some((title, last)) => match parse_tags(last) {
+            Some(tags) => (title.trim_end(), tags),
+            None => (rest, Vec::new()),
+        },
+        None => match parse_tags(rest) {
+            Some(tags) => ("", tags),
+            None => (rest, Vec::new()),
+        },
+    };
+    Some(Headline {
+        stars,
+        keyword: keyword.to_string(),
+        done,
+        priority,
+        title: title.to_string(),
+        tags_end: (!tags.is_empty()).then(|| line.trim_end().chars().count()),
+        tags,
+    })
+}
+
+fn render_headline(headline: &Headline) -> String {
+    let mut line = format!("{} {}", "*".repeat(headline.stars), headline.keyword);
+    if let Some(priority) = headline.priority {
+        line.push_str(&format!(" [#{priority}]"));
+    }
+    if !headline.title.is_empty() {
+        line.push(' ');
+        line.push_str(&headline.title);
+    }
+    if !headline.tags.is_empty() {
+        let tags = format!(":{}:", headline.tags.join(":"));
+        let width = line.chars().count() + tags.chars().count();
+        let pad = match headline.tags_end {
+            Some(end) if end > width => end - width,
+            _ => 1,
+        };
+        line.push_str(&" ".repeat(pad));
+        line.push_str(&tags);
+    }
+    line
+}
+
+/// A TODO heading and where it is in the file.
+#[derive(Debug, Clone)]
+struct Heading {
+    headline: Headline,
+    /// The headline's bytes, without the line ending.
+    range: Range<usize>,
+    eol: String,
+    planning: Planning,
+    /// The planning line's bytes without its line ending, and the ending.
+    planning_range: Option<(Range<usize>, String)>,
+    /// The headline and planning line as written; what's compared between
+    /// syncs to tell whether the heading was edited.
+    text: String,
+}
+
+/// Each line's start, text and line ending.
+fn lines(text: &str) -> Vec<(usize, &str, &str)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for chunk in text.split_inclusive('\n') {
+        let body = chunk
+            .strip_suffix('\n')
+            .map_or(chunk, |b| b.strip_suffix('\r').unwrap_or(b));
+        lines.push((start, body, &chunk[body.len()..]));
+        start += chunk.len();
+    }
+    lines
+}
+
+fn parse(text: &str, keywords: &Keywords) -> Vec<Heading> {
+    let lines = lines(text);
+    let mut headings = Vec::new();
+    for (i, (start, line, eol)) in lines.iter().enumerate() {
+        let Some(headline) = parse_headline(line, keywords) else {
+            continue;
+        };
+        let next = lines.get(i + 1).and_then(|(start, line, eol)| {
+            parse_planning(line).map(|planning| (planning, *start..start + line.len(), *line, *eol))
+        });
+        let mut heading = Heading {
+            headline,
+            range: *start..start + line.len(),
+            eol: eol.to_string(),
+            planning: Planning::default(),
+            planning_range: None,
+            text: line.to_string(),
+        };
+        if let Some((planning, range, line, eol)) = next {
+            heading.planning = planning;
+            heading.planning_range = Some((range, eol.to_string()));
+            heading.text.push('\n');
+            heading.text.push_str(line);
+        }
+        headings.push(heading);
+    }
+    headings
+}
+
+/// What a heading says, to compare with what its task says.
+#[derive(Debug, Clone, PartialEq)]
+struct Fields {
+    keyword: String,
+    priority: Option<char>,
+    title: String,
+    tags: Vec<String>,
+    deadline: Option<When>,
+    scheduled: Option<When>,
+    closed: Option<When>,
+}
+
+impl Fields {
+    fn of(heading: &Heading) -> Fields {
+        let when = |kind: &str| heading.planning.get(kind).map(|s| (s.date, s.time));
+        Fields {
+            keyword: heading.headline.keyword.clone(),
+            priority: heading.headline.priority,
+            title: heading.headline.title.clone(),
+            tags: heading.headline.tags.clone(),
+            deadline: when("DEADLINE"),
+            scheduled: when("SCHEDULED"),
+            closed: when("CLOSED"),
+        }
+    }
+
+    fn when(&self, kind: &str) -> Option<When> {
+        match kind {
+            "CLOSED" => self.closed,
+            "DEADLINE" => self.deadline,
+            _ => self.scheduled,
+        }
+    }
+
+    /// Whether the headlines say the same, whatever the order of tags.
+    fn same_headline(&self, other: &Fields) -> bool {
+        let tags = |fields: &Fields| {
+            let mut tags: Vec<String> = fields.tags.iter().map(|t| t.to_lowercase()).collect();
+            tags.sort();
+            tags
+        };
+        self.keyword == other.keyword
+            && self.priority == other.priority
+            && self.title == other.title
+            && tags(self) == tags(other)
+    }
+
+    fn same_planning(&self, other: &Fields) -> bool {
+        self.deadline == other.deadline
+            && self.scheduled == other.scheduled
+            && self.closed == other.closed
+    }
+}
+
+/// A project or tag name as an org tag: other characters become `_`.
+fn token(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if is_tag_char(c) { c } else { '_' })
+        .collect()
+}
+
+/// [#A] onto 3 (high), [#B] onto 2 and the rest onto 1 (low).
+fn daylight_priority(priority: char) -> i64 {
+    match priority {
+        'A' => 3,
+        'B' => 2,
+        _ => 1,
+    }
+}
+
+fn org_priority(priority: Option<i64>) -> Option<char> {
+    match priority? {
+        p if p >= 3 => Some('A'),
+        2 => Some('B'),
+        1 => Some('C'),
+        _ => None,
+    }
+}
+
+/// A due or scheduled value as a date and optional time.
+fn when_of(value: Option<&str>) -> Option<When> {
+    let value = value?;
+    if let Some(at) = value
+        .get(..16)
+        .and_then(|v| NaiveDateTime::parse_from_str(v, "%Y-%m-%dT%H:%M").ok())
+    {
+        return Some((at.date(), Some(at.time())));
+    }
+    let date = NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()?;
+    Some((date, None))
+}
+
+fn value_of(when: Option<When>) -> Option<String> {
+    match when? {
+        (date, Some(time)) => Some(format!("{date}T{}", time.format("%H:%M"))),
+        (date, None) => Some(date.to_string()),
+    }
+}
+
+/// What `heading` should say for `task`, keeping what it had that tasks
+/// don't: its keyword when still right, and the spelling of its tags.
+fn fields_for(task: &Task, heading: &Heading, keywords: &Keywords, local: &Tz) -> Fields {
+    let current = Fields::of(heading);
+    let done = task.status == STATUS_DONE;
+    let keyword = match (done, heading.headline.done) {
+        (true, true) | (false, false) => current.keyword.clone(),
+        (true, false) => keywords.done.first().cloned().unwrap_or_default(),
+        (false, true) => keywords.open.first().cloned().unwrap_or_default(),
+    };
+    let priority = match current.priority {
+        Some(p) if Some(daylight_priority(p)) == task.priority => Some(p),
+        _ => org_priority(task.priority),
+    };
+    // Tags the heading has keep their place and spelling; new ones go last.
+    let wanted: Vec<String> = task.tags.iter().map(|tag| token(tag)).collect();
+    let mut tags: Vec<String> = current
+        .tags
+        .iter()
+        .filter(|t| wanted.iter().any(|w| w.eq_ignore_ascii_case(t)))
+        .cloned()
+        .collect();
+    for tag in wanted {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            tags.push(tag);
+        }
+    }
+    let closed = match (done, heading.headline.done) {
+        (true, true) => current.closed,
+        (true, false) => task
+            .completed_at
+            .as_deref()
+            .and_then(|at| parse_utc(at).ok())
+            .map(|at| {
+                let at = at.with_timezone(local).naive_local();
+                (
+                    at.date(),
+                    NaiveTime::from_hms_opt(at.hour(), at.minute(), 0),
+                )
+            }),
+        (false, _) => None,
+    };
+    Fields {
+        keyword,
+        priority,
+        title: task.title.split_whitespace().collect::<Vec<_>>().join(" "),
+        tags,
+        deadline: when_of(task.due.as_deref()),
+        scheduled: when_of(task.scheduled.as_deref()),
+        closed,
+    }
+}
+
+/// The planning line for `fields`, keeping the order and spelling of the
+/// stamps `planning` already has. `None` when there's nothing to plan.
+fn render_planning(planning: &Planning, fields: &Fields) -> Option<String> {
+    let mut parts = Vec::new();
+    for (kind, stamp) in &planning.entries {
+        if let Some(when) = fields.when(kind) {
+            parts.push(format!("{kind}: {}", stamp_text(Some(stamp), when, true)));
+        }
+    }
+    for kind in PLANNING {
+        if let (None, Some(when)) = (planning.get(kind), fields.when(kind)) {
+            parts.push(format!(
+                "{kind}: {}",
+                stamp_text(None, when, kind != "CLOSED")
+            ));
+        }
+    }
+    if !planning.tail.is_empty() {
+        parts.push(planning.tail.clone());
+    }
+    (!parts.is_empty()).then(|| format!("{}{}", planning.indent, parts.join(" ")))
+}
+
+/// A replacement of some of the file's bytes.
+type Edit = (Range<usize>, String);
+
+/// The edits that make `heading` say `fields`, and its text after them.
+/// Only the headline and planning line are touched.
+fn heading_edits(heading: &Heading, fields: &Fields, eol: &str) -> (Vec<Edit>, String) {
+    let current = Fields::of(heading);
+    let mut edits = Vec::new();
+    let mut text = heading.text.lines().next().unwrap_or_default().to_string();
+    if !fields.same_headline(&current) {
+        let headline = Headline {
+            keyword: fields.keyword.clone(),
+            priority: fields.priority,
+            title: fields.title.clone(),
+            tags: fields.tags.clone(),
+            ..heading.headline.clone()
+        };
+        text = render_headline(&headline);
+        edits.push((heading.range.clone(), text.clone()));
+    }
+    let planning = if fields.same_planning(&current) {
+        heading.text.lines().nth(1).map(str::to_string)
+    } else {
+        let line = render_planning(&heading.planning, fields);
+        match (&heading.planning_range, &line) {
+            (Some((range, _)), Some(line)) => edits.push((range.clone(), line.clone())),
+            (Some((range, ending)), None) => {
+                edits.push((range.start..range.end + ending.len(), String::new()));
+            }
+            // A headline on the file's last line has no ending to follow.
+            (None, Some(line)) if heading.eol.is_empty() => {
+                edits.push((heading.range.end..heading.range.end, format!("{eol}{line}")));
+            }
+            (None, Some(line)) => {
+                let at = heading.range.end + heading.eol.len();
+                edits.push((at..at, format!("{line}{}", heading.eol)));
+            }
+            (None, None) => {}
+        }
+        line
+    };
+    if let Some(planning) = planning {
+        text.push('\n');
+        text.push_str(&planning);
+    }
+    (edits, text)
+}
+
+fn splice(text: &str, mut edits: Vec<Edit>) -> String {
+    edits.sort_by_key(|(range, _)| (range.start, range.end));
+    let mut out = String::with_capacity(text.len());
+    let mut at = 0;
+    for (range, replacement) in edits {
+        out.push_str(&text[at..range.start]);
+        out.push_str(&replacement);
+        at = range.end;
+    }
+    out.push_str(&text[at..]);
+    out
+}
+
+fn set_done(task: &mut Task, done: bool, closed: Option<When>, local: &Tz) {
+    if !done {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+        return;
+    }
+    task.status = STATUS_DONE.to_string();
+    let at = closed
+        .and_then(|(date, time)| {
+            timezone::resolve_local(date.and_time(time.unwrap_or(NaiveTime::MIN)), local)
+        })
+        .map(|at| format_utc(at.to_utc()));
+    task.completed_at = at.or_else(|| Some(now_utc()));
+}
+
+/// The tags for `fields`, keeping the names of `current` ones written as
+/// their token.
+fn tags_of(fields: &Fields, current: &[String]) -> Vec<String> {
+    let names: Vec<String> = fields
+        .tags
+        .iter()
+        .map(|tag| {
+            current
+                .iter()
+                .find(|t| token(t).eq_ignore_ascii_case(tag))
+                .cloned()
+                .unwrap_or_else(|| tag.clone())
+        })
+        .collect();
+    tags::normalize_names(&names).unwrap_or_default()
+}
+
+/// Create or update the task for a heading added or edited in a file. A
+/// field edited here after the file was saved keeps the local value.
+/// Returns the task and whether it was created, or `None` when nothing
+/// changed.
+fn write_heading(
+    conn: &Connection,
+    existing: Option<Task>,
+    heading: &Heading,
+    modified: i64,
+    local: &Tz,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let fields = Fields::of(heading);
+    let title = if fields.title.is_empty() {
+        UNTITLED.to_string()
+    } else {
+        fields.title.clone()
+    };
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description: None,
+            project: None,
+            priority: fields.priority.map(daylight_priority),
+            due: value_of(fields.deadline),
+            scheduled: value_of(fields.scheduled),
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if heading.headline.done {
+            set_done(&mut task, true, fields.closed, local);
+            task_store::write_task(conn, &task)?;
+        }
+        let names = tags_of(&fields, &[]);
+        tags::set_task_tags(conn, &task.id, &names)?;
+        task.tags = names;
+        return Ok(Some((task, true)));
+    };
+
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| stamps.get(field).is_none_or(|s| s.clock <= modified);
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("priority") && fields.priority.map(daylight_priority) != task.priority {
+        task.priority = fields.priority.map(daylight_priority);
+    }
+    // A stored time with seconds reads back as the same minute; keep it.
+    if take("due") && fields.deadline != when_of(task.due.as_deref()) {
+        task.due = value_of(fields.deadline);
+    }
+    if take("scheduled") && fields.scheduled != when_of(task.scheduled.as_deref()) {
+        task.scheduled = value_of(fields.scheduled);
+    }
+    if take("status") && heading.headline.done != (task.status == STATUS_DONE) {
+        set_done(&mut task, heading.headline.done, fields.closed, local);
+    }
+    let names = tags_of(&fields, &task.tags);
+    let take_tags = crdt::newest_tag_clock(conn, &task.id)?.is_none_or(|c| c <= modified);
+    let tags_changed = take_tags && {
+        let mut have: Vec<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+        let mut want: Vec<String> = names.iter().map(|t| t.to_lowercase()).collect();
+        have.sort();
+        want.sort();
+        have != want
+    };
+
+    let changed = task.title != before.title
+        || task.priority != before.priority
+        || task.due != before.due
+        || task.scheduled != before.scheduled
+        || task.status != before.status;
+    if !changed && !tags_changed {
+        return Ok(None);
+    }
+    if changed {
+        task.updated_at = now_utc();
+        task_store::write_task(conn, &task)?;
+    }
+    if tags_changed {
+        tags::set_task_tags(conn, &task.id, &names)?;
+        task.tags = names;
+    }
+    Ok(Some((task, false)))
+}
+
+/// A task's heading as the last sync left it.
+#[derive(Debug, Clone)]
+struct HeadingRow {
+    task_id: String,
+    position: usize,
+    heading: String,
+}
+
+fn load_rows(conn: &Connection, file_id: &str) -> rusqlite::Result<Vec<HeadingRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_id, position, heading FROM org_headings
+         WHERE file_id = ?1 ORDER BY position",
+    )?;
+    let rows = stmt.query_map(params![file_id], |row| {
+        Ok(HeadingRow {
+            task_id: row.get(0)?,
+            position: row.get::<_, i64>(1)? as usize,
+            heading: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Match the file's TODO headings to the tasks they were last synced as:
+/// the same text first, wherever it moved, then the same title, then the
+/// title sharing the most words, then the same place.
+fn match_headings(
+    headings: &[Heading],
+    rows: &[HeadingRow],
+    keywords: &Keywords,
+) -> Vec<Option<usize>> {
+    let words =
+        |title: &str| -> Vec<String> { title.split_whitespace().map(str::to_lowercase).collect() };
+    let row_titles: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let first = row.heading.lines().next().unwrap_or_default();
+            parse_headline(first, keywords).map_or_else(Vec::new, |h| words(&h.title))
+        })
+        .collect();
+    let mut owner: Vec<Option<usize>> = vec![None; headings.len()];
+    let mut free = vec![true; rows.len()];
+    let mut claim = |owner: &mut Vec<Option<usize>>, i: usize, score: &dyn Fn(usize) -> usize| {
+        if owner[i].is_some() {
+            return;
+        }
+        let best = (0..rows.len())
+            .filter(|&r| free[r])
+            .map(|r| (score(r), r))
+            .filter(|(score, _)| *score > 0)
+            .min_by_key(|(score, r)| (usize::MAX - score, *r));
+        if let Some((_, r)) = best {
+            free[r] = false;
+            owner[i] = Some(r);
+        }
+    };
+    for (i, heading) in headings.iter().enumerate() {
+        claim(&mut owner, i, &|r| {
+            usize::from(rows[r].heading == heading.text)
+        });
+    }
+    for (i, heading) in headings.iter().enumerate() {
+        let title = words(&heading.headline.title);
+        claim(&mut owner, i, &|r| usize::from(row_titles[r] == title));
+    }
+    for (i, heading) in headings.iter().enumerate() {
+        let title = words(&heading.headline.title);
+        claim(&mut owner, i, &|r| {
+            row_titles[r].iter().filter(|w| title.contains(w)).count()
+        });
+    }
+    for i in 0..headings.len() {
+        claim(&mut owner, i, &|r| usize::from(rows[r].position == i));
+    }
+    owner
+}
+
+/// Apply a file's TODO headings to the tasks and work out the edits that
+/// bring the file up to date: headings edited there update their task, new
+/// ones add tasks, deleted ones trash theirs, and tasks changed here
+/// rewrite their headline and planning line. Tasks added here aren't
+/// written to the files; there's no telling where in them they'd go.
+fn merge(
+    conn: &Connection,
+    file_id: &str,
+    text: &str,
+    modified: i64,
+    local: &Tz,
+    report: &mut OrgSyncReport,
+) -> rusqlite::Result<Vec<Edit>> {
+    let keywords = keywords(text);
+    let headings = parse(text, &keywords);
+    let rows = load_rows(conn, file_id)?;
+    let owner = match_headings(&headings, &rows, &keywords);
+    let eol = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut kept: Vec<(String, String)> = Vec::new();
+    let mut matched = vec![false; rows.len()];
+    let mut edits = Vec::new();
+    for (i, heading) in headings.iter().enumerate() {
+        let row = owner[i].map(|r| &rows[r]);
+        if let Some(r) = owner[i] {
+            matched[r] = true;
+        }
+        let existing = match row {
+            Some(row) => task_store::find_task(conn, &row.task_id)?,
+            None => None,
+        };
+        if let (Some(row), None) = (row, &existing) {
+            // Deleted here. The heading is the user's writing, so it stays;
+            // restoring the task picks it up again.
+            kept.push((row.task_id.clone(), heading.text.clone()));
+            continue;
+        }
+        let task = if row.is_some_and(|row| row.heading == heading.text) {
+            existing
+        } else {
+            match write_heading(conn, existing.clone(), heading, modified, local)? {
+                Some((task, true)) => {
+                    report.created += 1;
+                    Some(task)
+                }
+                Some((task, false)) => {
+                    report.updated += 1;
+                    Some(task)
+                }
+                None => existing,
+            }
+        };
+        let Some(task) = task else {
+            continue;
+        };
+        let fields = fields_for(&task, heading, &keywords, local);
+        let (heading_edits, text) = heading_edits(heading, &fields, eol);
+        if !heading_edits.is_empty() {
+            report.written += 1;
+            edits.extend(heading_edits);
+        }
+        kept.push((task.id, text));
+    }
+
+    for (row, _) in rows.iter().zip(&matched).filter(|(_, matched)| !**matched) {
+        if trash::trash_task(conn, &row.task_id)? {
+            report.removed += 1;
+        }
+    }
+
+    conn.execute(
+        "DELETE FROM org_headings WHERE file_id = ?1",
+        params![file_id],
+    )?;
+    for (position, (task_id, heading)) in kept.iter().enumerate() {
+        conn.execute(
+            "INSERT OR REPLACE INTO org_headings (file_id, task_id, position, heading)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![file_id, task_id, position as i64, heading],
+        )?;
+    }
+    Ok(edits)
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Sync one file. Its edits are written before the changes to tasks are
+/// committed, so a file that can't be written leaves both as they were.
+fn sync_file(db: &Db, file: &OrgFile, local: &Tz) -> CommandResult<OrgSyncReport> {
+    let path = Path::new(&file.path);
+    let read_at = modified_at(path);
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", file.path))?;
+    let modified = read_at
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as i64);
+
+    let mut report = OrgSyncReport::default();
+    db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            let tx = conn.transaction()?;
+            let edits = merge(&tx, &file.id, &text, modified, local, &mut report)?;
+            if !edits.is_empty() {
+                // Saved again since it was read; don't overwrite that save.
+                if modified_at(path) != read_at {
+                    return Ok(Err(CommandError::conflict(format!(
+                        "{} changed while syncing",
+                        file.path
+                    ))));
+                }
+                if let Err(e) = write_atomic(path, splice(&text, edits).as_bytes()) {
+                    return Ok(Err(e));
+                }
+            }
+            tx.execute(
+                "UPDATE org_files SET last_synced_at = ?2 WHERE id = ?1",
+                params![file.id, now_utc()],
+            )?;
+            tx.commit()?;
+            Ok(Ok(()))
+        })?
+    })??;
+    Ok(report)
+}
+
+fn row_to_file(row: &rusqlite::Row) -> rusqlite::Result<OrgFile> {
+    Ok(OrgFile {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        last_synced_at: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
+fn load_files(conn: &Connection) -> rusqlite::Result<Vec<OrgFile>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, path, last_synced_at, created_at FROM org_files ORDER BY created_at",
+    )?;
+    let rows = stmt.query_map([], row_to_file)?;
+    rows.collect()
+}
+
+#[tauri::command]
+pub fn list_org_files(db: State<'_, Db>) -> CommandResult<Vec<OrgFile>> {
+    db.with_conn(|conn| load_files(conn))
+}
+
+/// Sync the TODO headings of an .org file. Nothing is read or written
+/// until `sync_org_files`.
+#[tauri::command]
+pub fn add_org_file(db: State<'_, Db>, path: String) -> CommandResult<OrgFile> {
+    let path = path.trim().to_string();
+    if !Path::new(&path).is_file() {
+        return Err(CommandError::not_found(format!("File not found: {path}")));
+    }
+    if !path.to_lowercase().ends_with(".org") {
+        return Err(CommandError::invalid("Choose an .org file"));
+    }
+    let file = db.with_conn(|conn| {
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM org_files WHERE path = ?1",
+                params![path],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if exists.is_some() {
+            return Ok(Err(CommandError::conflict(format!(
+                "Already syncing {path}"
+            ))));
+        }
+        conn.execute(
+            "INSERT INTO org_files (id, path, created_at) VALUES (?1, ?2, ?3)",
+            params![uuid::Uuid::new_v4().to_string(), path, now_utc()],
+        )?;
+        conn.query_row(
+            "SELECT id, path, last_synced_at, created_at FROM org_files WHERE path = ?1",
+            params![path],
+            row_to_file,
+        )
+        .map(Ok)
+    })??;
+    Ok(file)
+}
+
+/// Stop syncing a file. It and its tasks stay as they are.
+#[tauri::command]
+pub fn remove_org_file(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let removed =
+        db.with_conn(|conn| conn.execute("DELETE FROM org_files WHERE id = ?1", params![id]))?;
+    if removed == 0 {
+        return Err(CommandError::not_found(format!("Org file not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Sync every file. One that fails is reported in `errors` and doesn't
+/// stop the rest.
+#[tauri::command]
+pub fn sync_org_files(app: AppHandle, db: State<'_, Db>) -> CommandResult<OrgSyncReport> {
+    let progress = Tracker::start(&app, "orgmode");
+    let result = sync_files(&db, &progress);
+    progress.finish(&result);
+    result
+}
+
+fn sync_files(db: &Db, progress: &Tracker) -> CommandResult<OrgSyncReport> {
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let files = db.with_conn(|conn| load_files(conn))?;
+    let mut report = OrgSyncReport::default();
+    for file in files {
+        match sync_file(db, &file, &local) {
+            Ok(synced) => {
+                progress.record(&file.path, &synced);
+                report.created += synced.created;
+                report.updated += synced.updated;
+                report.removed += synced.removed;
+                report.written += synced.written;
+            }
+            Err(e) => {
+                tracing::warn!("{e}");
+                report.errors.push(e.to_string());
+            }
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+use chrono::Utc;
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{format_utc, now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::sync_provider::{self, AnyProvider};
+use crate::sync_status;
+
+/// Tries before a change is kept back as failed.
+const MAX_ATTEMPTS: i64 = 5;
+const REPLAY_POLL: Duration = Duration::from_secs(300);
+/// Changes to tasks no provider took in this long are dropped: the tasks
+/// aren't synced anywhere.
+const UNCLAIMED_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxItem {
+    pub id: i64,
+    pub task_id: String,
+    /// The task's title, while it's still around.
+    pub title: Option<String>,
+    /// `create`, `update` or `delete`.
+    pub op: String,
+    pub queued_at: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    /// Set once it failed too often; it's held back until retried or edited
+    /// again.
+    pub failed_at: Option<String>,
+}
+
+fn row_to_item(row: &Row) -> rusqlite::Result<OutboxItem> {
+    Ok(OutboxItem {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        title: row.get(2)?,
+        op: row.get(3)?,
+        queued_at: row.get(4)?,
+        attempts: row.get(5)?,
+        last_error: row.get(6)?,
+        failed_at: row.get(7)?,
+    })
+}
+
+pub fn list(conn: &Connection) -> rusqlite::Result<Vec<OutboxItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT o.id, o.task_id, t.title, o.op, o.queued_at, o.attempts, o.last_error,
+                o.failed_at
+         FROM sync_outbox o LEFT JOIN tasks t ON t.id = o.task_id
+         ORDER BY o.id",
+    )?;
+    let rows = stmt.query_map([], row_to_item)?;
+    rows.collect()
+}
+
+/// `uploads` in the order their tasks changed here, those with no queued
+/// change last, leaving out the ones that failed for good.
+pub fn in_order<T>(
+    conn: &Connection,
+    uploads: Vec<T>,
+    task_id: impl Fn(&T) -> &str,
+) -> rusqlite::Result<Vec<T>> {
+    let mut stmt = conn.prepare("SELECT task_id, id, failed_at IS NOT NULL FROM sync_outbox")?;
+    let queued = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                (row.get::<_, i64>(1)?, row.get(2)?),
+            ))
+        })?
+        .collect::<rusqlite::Result<HashMap<String, (i64, bool)>>>()?;
+    let mut uploads: Vec<(i64, T)> = uploads
+        .into_iter()
+        .filter_map(|upload| match queued.get(task_id(&upload)) {
+            Some((_, true)) => None,
+            Some((id, false)) => Some((*id, upload)),
+            None => Some((i64::MAX, upload)),
+        })
+        .collect();
+    uploads.sort_by_key(|(id, _)| *id);
+    Ok(uploads.into_iter().map(|(_, upload)| upload).collect())
+}
+
+/// Ids of tasks whose change failed for good.
+pub fn failed(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT task_id FROM sync_outbox WHERE failed_at IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// What came of one sync's uploads, saved with its results.
+#[derive(Debug, Default)]
+pub struct Replay {
+    delivered: Vec<String>,
+    failed: Vec<(String, String)>,
+}
+
+impl Replay {
+    pub fn delivered(&mut self, task_id: String) {
+        self.delivered.push(task_id);
+    }
+
+    pub fn failed(&mut self, task_id: String, error: impl fmt::Display) {
+        self.failed.push((task_id, error.to_string()));
+    }
+
+    pub fn save(&self, conn: &Connection) -> rusqlite::Result<()> {
+        for task_id in &self.delivered {
+            conn.execute(
+                "DELETE FROM sync_outbox WHERE task_id = ?1",
+                params![task_id],
+            )?;
+        }
+        let now = now_utc();
+        for (task_id, error) in &self.failed {
+            conn.execute(
+                "UPDATE sync_outbox SET
+                     attempts = attempts + 1,
+                     last_error = ?2,
+                     failed_at = CASE WHEN attempts + 1 >= ?3 THEN ?4 END
+                 WHERE task_id = ?1",
+                params![task_id, error, MAX_ATTEMPTS, now],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Drop changes to tasks no provider has a link to that have waited
+/// `UNCLAIMED_DAYS`.
+fn prune(conn: &Connection) -> rusqlite::Result<usize> {
+    let cutoff = format_utc(Utc::now() - chrono::Duration::days(UNCLAIMED_DAYS));
+    conn.execute(
+        "DELETE FROM sync_outbox
+         WHERE queued_at < ?1 AND task_id NOT IN (SELECT task_id FROM external_refs)",
+        params![cutoff],
+    )
+}
+
+/// Two-way providers set to upload whose last sync didn't get through, so
+/// their changes may still be waiting.
+fn stalled(conn: &Connection) -> rusqlite::Result<Vec<&'static dyn AnyProvider>> {
+    let waiting: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sync_outbox WHERE failed_at IS NULL",
+        [],
+        |row| row.get(0),
+    )?;
+    if waiting == 0 {
+        return Ok(Vec::new());
+    }
+    let mut providers = Vec::new();
+    for provider in sync_provider::two_way() {
+        let status = sync_status::load(conn, provider.id())?;
+        if status.last_finished_at.is_some()
+            && status.error_count > 0
+            && sync_provider::mode(conn, provider)?.exports()
+        {
+            providers.push(provider);
+        }
+    }
+    Ok(providers)
+}
+
+/// Sync again the providers that couldn't be reached while changes wait
+/// for them, so they go out once the connection is back.
+async fn replay(app: &AppHandle) {
+    let db = app.state::<Db>();
+    if db.is_locked() {
+        return;
+    }
+    let providers = db.with_conn(|conn| {
+        prune(conn)?;
+        stalled(conn)
+    });
+    let providers = match providers {
+        Ok(providers) => providers,
+        Err(e) => {
+            tracing::warn!("{e}");
+            return;
+        }
+    };
+    for provider in providers {
+        if let Err(e) = provider.sync(app, None, true).await {
+            tracing::warn!("replay to {} failed: {e}", provider.id());
+        }
+    }
+}
+
+pub fn spawn_outbox_replayer(app: &AppHandle) {
+    let handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(REPLAY_POLL).await;
+            replay(&handle).await;
+        }
+    });
+}
+
+/// Changes waiting for providers, in the order they'll go out, those that
+/// failed for good included.
+#[tauri::command]
+pub fn list_outbox(db: State<'_, Db>) -> CommandResult<Vec<OutboxItem>> {
+    db.with_conn(|conn| list(conn))
+}
+
+/// Try a failed change again on the next sync.
+#[tauri::command]
+pub fn retry_outbox_item(db: State<'_, Db>, id: i64) -> CommandResult<()> {
+    let updated = db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE sync_outbox SET attempts = 0, failed_at = NULL WHERE id = ?1",
+            params![id],
+        )
+    })?;
+    if updated == 0 {
+        return Err(CommandError::not_found(format!(
+            "Outbox item not found: {id}"
+        )));
+    }
+    Ok(())
+}
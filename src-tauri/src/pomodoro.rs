@@ -0,0 +1,459 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::data_dir;
+use crate::db::{format_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::session::write_atomic;
+use crate::task_store;
+use crate::time_entries;
+use crate::usage;
+
+/// Emitted every second while a phase runs, with the current status.
+pub const POMODORO_TICK_EVENT: &str = "pomodoro-tick";
+/// Emitted when a phase ends, finished or skipped, so the UI can notify.
+pub const POMODORO_PHASE_EVENT: &str = "pomodoro-phase-changed";
+
+const CONFIG_FILE: &str = "pomodoro.json";
+const STATE_FILE: &str = "pomodoro-state.json";
+const TICK: Duration = Duration::from_secs(1);
+/// A phase noticed ending longer ago than this (the app was closed, or the
+/// machine asleep) doesn't auto-start the next one.
+const AUTO_START_GRACE: chrono::Duration = chrono::Duration::minutes(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub work_minutes: u32,
+    pub short_break_minutes: u32,
+    pub long_break_minutes: u32,
+    /// Every this many work phases, the break is a long one.
+    pub long_break_every: u32,
+    pub auto_start_breaks: bool,
+    pub auto_start_work: bool,
+    /// Log work phases on a task as time entries.
+    pub track_time: bool,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+            auto_start_breaks: true,
+            auto_start_work: false,
+            track_time: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    #[default]
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+/// Persisted between runs so a pomodoro survives a restart. A phase is
+/// running when `ends_at` is set, paused when `paused_remaining` is, and
+/// otherwise waiting to be started.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PomodoroState {
+    /// The phase running, paused, or up next.
+    pub phase: Phase,
+    pub task_id: Option<String>,
+    pub ends_at: Option<String>,
+    pub paused_remaining: Option<i64>,
+    /// Work phases finished since the last long break.
+    pub completed: u32,
+    /// Time entry logging the running work phase.
+    pub entry_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PomodoroStatus {
+    #[serde(flatten)]
+    pub state: PomodoroState,
+    pub running: bool,
+    /// Seconds left in the phase: counting down while running, frozen while
+    /// paused, the full length while waiting.
+    pub remaining_seconds: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseChange {
+    pub finished: Phase,
+    /// False when the phase was skipped.
+    pub completed: bool,
+    /// The time entry a finished work phase was logged to.
+    pub entry_id: Option<String>,
+    pub status: PomodoroStatus,
+}
+
+pub struct PomodoroEngine {
+    state: Mutex<PomodoroState>,
+    config: Mutex<PomodoroConfig>,
+}
+
+impl PomodoroEngine {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(PomodoroState::default()),
+            config: Mutex::new(PomodoroConfig::default()),
+        }
+    }
+
+    fn config(&self) -> PomodoroConfig {
+        self.config
+            .lock()
+            .map(|config| config.clone())
+            .unwrap_or_default()
+    }
+}
+
+fn data_path(app: &AppHandle, file: &str) -> CommandResult<PathBuf> {
+    data_dir::app_data_dir(app).map(|dir| dir.join(file))
+}
+
+fn load_config(app: &AppHandle) -> PomodoroConfig {
+    let Ok(path) = data_path(app, CONFIG_FILE) else {
+        return PomodoroConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            PomodoroConfig::default()
+        }),
+        Err(_) => PomodoroConfig::default(),
+    }
+}
+
+fn save_state(app: &AppHandle, state: &PomodoroState) {
+    let result = data_path(app, STATE_FILE).and_then(|path| {
+        let body = serde_json::to_vec_pretty(state).map_err(|e| e.to_string())?;
+        write_atomic(&path, &body)
+    });
+    if let Err(e) = result {
+        tracing::warn!("failed to save state: {e}");
+    }
+}
+
+fn phase_length(config: &PomodoroConfig, phase: Phase) -> chrono::Duration {
+    let minutes = match phase {
+        Phase::Work => config.work_minutes,
+        Phase::ShortBreak => config.short_break_minutes,
+        Phase::LongBreak => config.long_break_minutes,
+    };
+    chrono::Duration::minutes(minutes.max(1) as i64)
+}
+
+fn ends_at(state: &PomodoroState) -> Option<DateTime<Utc>> {
+    state.ends_at.as_deref().and_then(|t| parse_utc(t).ok())
+}
+
+fn status(state: &PomodoroState, config: &PomodoroConfig) -> PomodoroStatus {
+    let remaining = match (ends_at(state), state.paused_remaining) {
+        (Some(end), _) => (end - Utc::now()).num_seconds().max(0),
+        (None, Some(paused)) => paused,
+        (None, None) => phase_length(config, state.phase).num_seconds(),
+    };
+    PomodoroStatus {
+        state: state.clone(),
+        running: state.ends_at.is_some(),
+        remaining_seconds: remaining,
+    }
+}
+
+/// Start (or resume) the current phase at `at`, timing its task if it's a
+/// work phase.
+fn begin(app: &AppHandle, state: &mut PomodoroState, config: &PomodoroConfig, at: DateTime<Utc>) {
+    let length = match state.paused_remaining.take() {
+        Some(seconds) => chrono::Duration::seconds(seconds),
+        None => phase_length(config, state.phase),
+    };
+    state.ends_at = Some(format_utc(at + length));
+    let (Phase::Work, Some(task_id), true) = (state.phase, &state.task_id, config.track_time)
+    else {
+        return;
+    };
+    // The engine owns its entries, so undoing one mustn't pull it out from
+    // under a running phase.
+    let db = app.state::<Db>().unjournaled();
+    let started = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let entry = time_entries::start_running(&tx, task_id, &format_utc(at), None, None)?;
+        tx.commit()?;
+        Ok(entry)
+    });
+    match started {
+        Ok(entry) => state.entry_id = Some(entry.id),
+        Err(e) => tracing::warn!("failed to start time entry: {e}"),
+    }
+}
+
+/// Stop the phase's time entry at `at`, unless the user already moved on to
+/// another one. Returns the entry's id.
+fn end_entry(app: &AppHandle, state: &mut PomodoroState, at: DateTime<Utc>) -> Option<String> {
+    let entry_id = state.entry_id.take()?;
+    let db = app.state::<Db>().unjournaled();
+    let result = db.with_conn(|conn| {
+        if time_entries::running_entry(conn)?.is_some_and(|e| e.id == entry_id) {
+            time_entries::stop_running(conn, &format_utc(at))?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        tracing::warn!("failed to stop time entry: {e}");
+    }
+    Some(entry_id)
+}
+
+/// End the running or paused phase at `at` and move to the next one,
+/// starting it straight away if the config says so and `live` is set.
+fn advance(
+    app: &AppHandle,
+    state: &mut PomodoroState,
+    config: &PomodoroConfig,
+    at: DateTime<Utc>,
+    completed: bool,
+    live: bool,
+) -> PhaseChange {
+    let finished = state.phase;
+    let entry_id = end_entry(app, state, at);
+    state.ends_at = None;
+    state.paused_remaining = None;
+    state.phase = match finished {
+        Phase::Work => {
+            if completed {
+                state.completed += 1;
+            }
+            if state.completed > 0
+                && state
+                    .completed
+                    .is_multiple_of(config.long_break_every.max(1))
+            {
+                Phase::LongBreak
+            } else {
+                Phase::ShortBreak
+            }
+        }
+        Phase::LongBreak => {
+            state.completed = 0;
+            Phase::Work
+        }
+        Phase::ShortBreak => Phase::Work,
+    };
+    let auto_start = match state.phase {
+        Phase::Work => config.auto_start_work,
+        _ => config.auto_start_breaks,
+    };
+    if live && auto_start {
+        begin(app, state, config, at);
+    }
+    PhaseChange {
+        finished,
+        completed,
+        entry_id: entry_id.filter(|_| completed),
+        status: status(state, config),
+    }
+}
+
+/// Apply `f` to the engine's state, then persist it and let the rest of the
+/// app know. Events go out after the lock is released.
+fn update(
+    app: &AppHandle,
+    f: impl FnOnce(&mut PomodoroState, &PomodoroConfig) -> CommandResult<Option<PhaseChange>>,
+) -> CommandResult<PomodoroStatus> {
+    let engine = app.state::<PomodoroEngine>();
+    let config = engine.config();
+    let (change, status) = {
+        let mut state = engine.state.lock().map_err(|_| "Lock poisoned")?;
+        let change = f(&mut state, &config)?;
+        save_state(app, &state);
+        (change, status(&state, &config))
+    };
+    if let Some(change) = change {
+        let _ = app.emit(POMODORO_PHASE_EVENT, change);
+    }
+    time_entries::notify(app);
+    Ok(status)
+}
+
+fn tick(app: &AppHandle) {
+    let engine = app.state::<PomodoroEngine>();
+    let config = engine.config();
+    let Some(status) = engine.state.lock().ok().map(|s| status(&s, &config)) else {
+        return;
+    };
+    let Some(end) = ends_at(&status.state) else {
+        return;
+    };
+    if status.remaining_seconds > 0 {
+        let _ = app.emit(POMODORO_TICK_EVENT, &status);
+        #[cfg(desktop)]
+        if status.remaining_seconds % 60 == 0 {
+            crate::tray::refresh_timer(app);
+        }
+        return;
+    }
+    let live = Utc::now() - end < AUTO_START_GRACE;
+    let result = update(app, |state, config| {
+        // A command may have changed the phase since the check above.
+        if ends_at(state) != Some(end) {
+            return Ok(None);
+        }
+        Ok(Some(advance(app, state, config, end, true, live)))
+    });
+    if let Err(e) = result {
+        tracing::warn!("{e}");
+    }
+}
+
+/// Restore the config and state saved by the last run and start the ticker.
+/// A phase that ran out while the app was closed is finished when first
+/// noticed.
+pub fn spawn_pomodoro_ticker(app: &AppHandle) {
+    let engine = app.state::<PomodoroEngine>();
+    if let Ok(mut config) = engine.config.lock() {
+        *config = load_config(app);
+    }
+    if let Ok(content) = data_path(app, STATE_FILE)
+        .and_then(|path| fs::read_to_string(path).map_err(CommandError::from))
+    {
+        match serde_json::from_str::<PomodoroState>(&content) {
+            Ok(saved) => {
+                if let Ok(mut state) = engine.state.lock() {
+                    *state = saved;
+                }
+            }
+            Err(e) => tracing::warn!("discarding unreadable state: {e}"),
+        }
+    }
+
+    let handle = app.clone();
+    std::thread::spawn(move || loop {
+        tick(&handle);
+        std::thread::sleep(TICK);
+    });
+}
+
+/// Short label for the tray, e.g. `focus, 12 min left`.
+pub fn tray_label(app: &AppHandle) -> Option<String> {
+    let engine = app.state::<PomodoroEngine>();
+    let state = engine.state.lock().ok()?;
+    let end = ends_at(&state)?;
+    let minutes = ((end - Utc::now()).num_seconds().max(0) + 59) / 60;
+    let phase = match state.phase {
+        Phase::Work => "focus",
+        Phase::ShortBreak | Phase::LongBreak => "break",
+    };
+    Some(format!("{phase}, {minutes} min left"))
+}
+
+#[tauri::command]
+pub fn get_pomodoro_config(engine: State<'_, PomodoroEngine>) -> PomodoroConfig {
+    engine.config()
+}
+
+/// Save the config. A running phase keeps its length; the new one applies
+/// from the next phase.
+#[tauri::command]
+pub fn set_pomodoro_config(
+    app: AppHandle,
+    engine: State<'_, PomodoroEngine>,
+    config: PomodoroConfig,
+) -> CommandResult<PomodoroConfig> {
+    let path = data_path(&app, CONFIG_FILE)?;
+    let body = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+    *engine.config.lock().map_err(|_| "Lock poisoned")? = config.clone();
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn get_pomodoro(engine: State<'_, PomodoroEngine>) -> CommandResult<PomodoroStatus> {
+    let config = engine.config();
+    let state = engine.state.lock().map_err(|_| "Lock poisoned")?;
+    Ok(status(&state, &config))
+}
+
+/// Start a fresh work phase now, optionally on a task, abandoning whatever
+/// phase was in progress.
+#[tauri::command]
+pub fn start_pomodoro(
+    app: AppHandle,
+    db: State<'_, Db>,
+    task_id: Option<String>,
+) -> CommandResult<PomodoroStatus> {
+    if let Some(task_id) = &task_id {
+        db.with_conn(|conn| task_store::find_task(conn, task_id))?
+            .ok_or_else(|| CommandError::not_found(format!("Task not found: {task_id}")))?;
+    }
+    usage::record(&db, "pomodoro", None);
+    let now = Utc::now();
+    update(&app, |state, config| {
+        end_entry(&app, state, now);
+        *state = PomodoroState {
+            task_id,
+            completed: state.completed,
+            ..PomodoroState::default()
+        };
+        begin(&app, state, config, now);
+        Ok(None)
+    })
+}
+
+#[tauri::command]
+pub fn pause_pomodoro(app: AppHandle) -> CommandResult<PomodoroStatus> {
+    let now = Utc::now();
+    update(&app, |state, _config| {
+        let Some(end) = ends_at(state) else {
+            return Err(CommandError::not_found("No pomodoro is running"));
+        };
+        end_entry(&app, state, now);
+        state.ends_at = None;
+        state.paused_remaining = Some((end - now).num_seconds().max(0));
+        Ok(None)
+    })
+}
+
+/// Resume a paused phase, or start the one that's up next.
+#[tauri::command]
+pub fn resume_pomodoro(app: AppHandle) -> CommandResult<PomodoroStatus> {
+    update(&app, |state, config| {
+        if state.ends_at.is_none() {
+            begin(&app, state, config, Utc::now());
+        }
+        Ok(None)
+    })
+}
+
+/// End the current phase early without counting it and move to the next.
+#[tauri::command]
+pub fn skip_pomodoro_phase(app: AppHandle) -> CommandResult<PomodoroStatus> {
+    update(&app, |state, config| {
+        let change = advance(&app, state, config, Utc::now(), false, true);
+        Ok(Some(change))
+    })
+}
+
+/// Stop the timer and reset the cycle.
+#[tauri::command]
+pub fn stop_pomodoro(app: AppHandle) -> CommandResult<PomodoroStatus> {
+    update(&app, |state, _config| {
+        end_entry(&app, state, Utc::now());
+        *state = PomodoroState::default();
+        Ok(None)
+    })
+}
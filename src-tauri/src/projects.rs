@@ -0,0 +1,433 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::task_store::{double_option, STATUS_DONE, STATUS_OPEN};
+
+/// Areas group projects and other areas; tasks are filed under projects.
+pub const KIND_AREA: &str = "area";
+pub const KIND_PROJECT: &str = "project";
+
+const PROJECT_COLUMNS: &str =
+    "id, parent_id, kind, name, color, position, archived_at, created_at, updated_at";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Project {
+    pub id: String,
+    /// The area this sits in; `None` at the top level.
+    pub parent_id: Option<String>,
+    pub kind: String,
+    pub name: String,
+    pub color: Option<String>,
+    /// Order among siblings.
+    pub position: i64,
+    pub archived_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewProject {
+    pub name: String,
+    /// `area` or `project` (the default).
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectPatch {
+    /// Renaming also renames the project on its tasks.
+    pub name: Option<String>,
+    /// An explicit `null` moves it to the top level.
+    #[serde(deserialize_with = "double_option")]
+    pub parent_id: Option<Option<String>>,
+    /// `Some("")` clears the color.
+    pub color: Option<String>,
+    pub archived: Option<bool>,
+}
+
+/// Totals for a project or area, including everything nested under it.
+/// Tasks in the trash aren't counted.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectRollup {
+    pub project_id: String,
+    pub open_tasks: i64,
+    pub done_tasks: i64,
+    /// Logged time, with a running timer counted up to now.
+    pub tracked_seconds: i64,
+}
+
+fn row_to_project(row: &Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        parent_id: row.get(1)?,
+        kind: row.get(2)?,
+        name: row.get(3)?,
+        color: row.get(4)?,
+        position: row.get(5)?,
+        archived_at: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+pub fn normalize_name(name: &str) -> CommandResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::invalid("Project name cannot be empty"));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_kind(kind: &str) -> CommandResult<()> {
+    match kind {
+        KIND_AREA | KIND_PROJECT => Ok(()),
+        other => Err(CommandError::invalid(format!(
+            "Invalid project kind: {other}"
+        ))),
+    }
+}
+
+pub fn find_project(conn: &Connection, id: &str) -> rusqlite::Result<Option<Project>> {
+    conn.query_row(
+        &format!("SELECT {PROJECT_COLUMNS} FROM projects WHERE id = ?1"),
+        params![id],
+        row_to_project,
+    )
+    .optional()
+}
+
+/// Look a project up by name. Names are unique case-insensitively.
+pub fn find_project_by_name(conn: &Connection, name: &str) -> rusqlite::Result<Option<Project>> {
+    conn.query_row(
+        &format!("SELECT {PROJECT_COLUMNS} FROM projects WHERE name = ?1"),
+        params![name],
+        row_to_project,
+    )
+    .optional()
+}
+
+fn next_position(conn: &Connection, parent_id: Option<&str>) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(position) + 1, 0) FROM projects WHERE parent_id IS ?1",
+        params![parent_id],
+        |row| row.get(0),
+    )
+}
+
+/// Give a project name used by a task a row of its own, at the end of the
+/// top level. Called whenever a task is written.
+pub fn ensure_project(conn: &Connection, name: &str) -> rusqlite::Result<()> {
+    let name = name.trim();
+    if name.is_empty() || find_project_by_name(conn, name)?.is_some() {
+        return Ok(());
+    }
+    let now = now_utc();
+    conn.execute(
+        &format!(
+            "INSERT INTO projects ({PROJECT_COLUMNS})
+             VALUES (?1, NULL, ?2, ?3, NULL, ?4, NULL, ?5, ?5)"
+        ),
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            KIND_PROJECT,
+            name,
+            next_position(conn, None)?,
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+/// Check `parent_id` can hold project `id` (`None` for a new one): it must
+/// be an area, and not the project itself or anything nested under it.
+fn check_parent(conn: &Connection, id: Option<&str>, parent_id: &str) -> CommandResult<()> {
+    let db_err = |e: rusqlite::Error| e.to_string();
+    let parent = find_project(conn, parent_id)
+        .map_err(db_err)?
+        .ok_or_else(|| CommandError::not_found(format!("Project not found: {parent_id}")))?;
+    if parent.kind != KIND_AREA {
+        return Err(CommandError::invalid(format!(
+            "'{}' is not an area",
+            parent.name
+        )));
+    }
+    let Some(id) = id else {
+        return Ok(());
+    };
+    let cycle: bool = conn
+        .query_row(
+            "WITH RECURSIVE ancestors(id) AS (
+                 SELECT ?1
+                 UNION SELECT p.parent_id FROM projects p
+                 JOIN ancestors a ON p.id = a.id WHERE p.parent_id IS NOT NULL
+             )
+             SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = ?2)",
+            params![parent_id, id],
+            |row| row.get(0),
+        )
+        .map_err(db_err)?;
+    if cycle {
+        return Err(CommandError::invalid(
+            "An area can't be moved inside itself",
+        ));
+    }
+    Ok(())
+}
+
+pub fn list(conn: &Connection, include_archived: bool) -> rusqlite::Result<Vec<Project>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM projects
+         WHERE ?1 OR archived_at IS NULL
+         ORDER BY position, name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map(params![include_archived], row_to_project)?;
+    rows.collect()
+}
+
+/// Open and done task counts and tracked time for every project, each
+/// including its descendants.
+pub fn rollups(conn: &Connection) -> rusqlite::Result<Vec<ProjectRollup>> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE tree(root, id) AS (
+             SELECT id, id FROM projects
+             UNION ALL SELECT tree.root, p.id FROM projects p JOIN tree ON p.parent_id = tree.id
+         ),
+         own(project_id, open_tasks, done_tasks, tracked_seconds) AS (
+             SELECT p.id, SUM(t.status = ?1), SUM(t.status = ?2),
+                    SUM((SELECT COALESCE(SUM(CAST(ROUND(
+                             (julianday(COALESCE(e.ended_at, ?3)) - julianday(e.started_at))
+                             * 86400) AS INTEGER)), 0)
+                         FROM time_entries e WHERE e.task_id = t.id))
+             FROM projects p JOIN tasks t ON p.name = t.project
+             WHERE t.deleted_at IS NULL
+             GROUP BY p.id
+         )
+         SELECT tree.root, COALESCE(SUM(own.open_tasks), 0), COALESCE(SUM(own.done_tasks), 0),
+                COALESCE(SUM(own.tracked_seconds), 0)
+         FROM tree LEFT JOIN own ON own.project_id = tree.id
+         GROUP BY tree.root",
+    )?;
+    let rows = stmt.query_map(params![STATUS_OPEN, STATUS_DONE, now_utc()], |row| {
+        Ok(ProjectRollup {
+            project_id: row.get(0)?,
+            open_tasks: row.get(1)?,
+            done_tasks: row.get(2)?,
+            tracked_seconds: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Projects and areas in display order. Archived ones are left out unless
+/// `include_archived` is set.
+#[tauri::command]
+pub fn list_projects(
+    db: State<'_, Db>,
+    include_archived: Option<bool>,
+) -> CommandResult<Vec<Project>> {
+    db.with_conn(|conn| list(conn, include_archived.unwrap_or(false)))
+}
+
+#[tauri::command]
+pub fn get_project_rollups(db: State<'_, Db>) -> CommandResult<Vec<ProjectRollup>> {
+    db.with_conn(|conn| rollups(conn))
+}
+
+#[tauri::command]
+pub fn create_project(db: State<'_, Db>, input: NewProject) -> CommandResult<Project> {
+    let name = normalize_name(&input.name)?;
+    let kind = input.kind.unwrap_or_else(|| KIND_PROJECT.to_string());
+    validate_kind(&kind)?;
+    let color = input.color.filter(|c| !c.trim().is_empty());
+
+    db.with_conn(|conn| {
+        if let Some(other) = find_project_by_name(conn, &name)? {
+            return Ok(Err(CommandError::conflict(format!(
+                "'{}' already exists",
+                other.name
+            ))));
+        }
+        if let Some(parent_id) = &input.parent_id {
+            if let Err(e) = check_parent(conn, None, parent_id) {
+                return Ok(Err(e));
+            }
+        }
+        let now = now_utc();
+        let project = Project {
+            id: uuid::Uuid::new_v4().to_string(),
+            parent_id: input.parent_id.clone(),
+            kind: kind.clone(),
+            name: name.clone(),
+            color: color.clone(),
+            position: next_position(conn, input.parent_id.as_deref())?,
+            archived_at: None,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+        conn.execute(
+            &format!(
+                "INSERT INTO projects ({PROJECT_COLUMNS})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+            ),
+            params![
+                project.id,
+                project.parent_id,
+                project.kind,
+                project.name,
+                project.color,
+                project.position,
+                project.archived_at,
+                project.created_at,
+                project.updated_at,
+            ],
+        )?;
+        Ok(Ok(project))
+    })?
+}
+
+/// Rename, recolor, move or (un)archive a project or area. A move puts it
+/// last among its new siblings.
+#[tauri::command]
+pub fn update_project(
+    db: State<'_, Db>,
+    id: String,
+    patch: ProjectPatch,
+) -> CommandResult<Project> {
+    let name = patch.name.as_deref().map(normalize_name).transpose()?;
+
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let Some(project) = find_project(&tx, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Project not found: {id}"
+            ))));
+        };
+        let now = now_utc();
+        if let Some(name) = &name {
+            if let Some(other) = find_project_by_name(&tx, name)? {
+                if other.id != project.id {
+                    return Ok(Err(CommandError::conflict(format!(
+                        "'{}' already exists",
+                        other.name
+                    ))));
+                }
+            }
+            tx.execute(
+                "UPDATE projects SET name = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, name, now],
+            )?;
+            tx.execute(
+                "UPDATE tasks SET project = ?2, updated_at = ?3
+                 WHERE project = ?1 COLLATE NOCASE",
+                params![project.name, name, now],
+            )?;
+        }
+        if let Some(parent_id) = &patch.parent_id {
+            if *parent_id != project.parent_id {
+                if let Some(parent_id) = parent_id {
+                    if let Err(e) = check_parent(&tx, Some(&id), parent_id) {
+                        return Ok(Err(e));
+                    }
+                }
+                tx.execute(
+                    "UPDATE projects SET parent_id = ?2, position = ?3, updated_at = ?4
+                     WHERE id = ?1",
+                    params![
+                        id,
+                        parent_id,
+                        next_position(&tx, parent_id.as_deref())?,
+                        now
+                    ],
+                )?;
+            }
+        }
+        if let Some(color) = &patch.color {
+            let color = (!color.trim().is_empty()).then_some(color);
+            tx.execute(
+                "UPDATE projects SET color = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, color, now],
+            )?;
+        }
+        if let Some(archived) = patch.archived {
+            tx.execute(
+                "UPDATE projects SET archived_at = CASE WHEN ?2 THEN COALESCE(archived_at, ?3) END,
+                     updated_at = ?3
+                 WHERE id = ?1",
+                params![id, archived, now],
+            )?;
+        }
+        let updated = find_project(&tx, &id)?;
+        tx.commit()?;
+        Ok(updated.ok_or_else(|| CommandError::not_found(format!("Project not found: {id}"))))
+    })?
+}
+
+/// Place `ids` in this order under `parent_id` (the top level when `None`),
+/// moving any that live elsewhere.
+#[tauri::command]
+pub fn reorder_projects(
+    db: State<'_, Db>,
+    parent_id: Option<String>,
+    ids: Vec<String>,
+) -> CommandResult<Vec<Project>> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let now = now_utc();
+        for (position, id) in ids.iter().enumerate() {
+            let Some(project) = find_project(&tx, id)? else {
+                return Ok(Err(CommandError::not_found(format!(
+                    "Project not found: {id}"
+                ))));
+            };
+            if let Some(parent_id) = &parent_id {
+                if project.parent_id.as_ref() != Some(parent_id) {
+                    if let Err(e) = check_parent(&tx, Some(id), parent_id) {
+                        return Ok(Err(e));
+                    }
+                }
+            }
+            tx.execute(
+                "UPDATE projects SET parent_id = ?2, position = ?3, updated_at = ?4
+                 WHERE id = ?1",
+                params![id, parent_id, position as i64, now],
+            )?;
+        }
+        let projects = list(&tx, true)?;
+        tx.commit()?;
+        Ok(Ok(projects))
+    })?
+}
+
+/// Delete a project or area. What was nested in it moves up a level, and
+/// its tasks are kept without a project.
+#[tauri::command]
+pub fn delete_project(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let Some(project) = find_project(&tx, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Project not found: {id}"
+            ))));
+        };
+        let now = now_utc();
+        tx.execute(
+            "UPDATE projects SET parent_id = ?2, updated_at = ?3 WHERE parent_id = ?1",
+            params![id, project.parent_id, now],
+        )?;
+        tx.execute(
+            "UPDATE tasks SET project = NULL, updated_at = ?2 WHERE project = ?1 COLLATE NOCASE",
+            params![project.name, now],
+        )?;
+        tx.execute("DELETE FROM projects WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(Ok(()))
+    })?
+}
@@ -0,0 +1,189 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::locale;
+use crate::timezone;
+
+/// The pause after a throttle that doesn't say how long, doubled for each
+/// one in a row.
+const FIRST_BACKOFF: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+/// Up to this share is added to every pause, so devices that were throttled
+/// together don't all come back at once.
+const JITTER: f64 = 0.2;
+/// Reset values above this are Unix times rather than seconds from now.
+const EPOCH_RESET: i64 = 1_000_000_000;
+
+/// Providers paused because they're limiting requests.
+static THROTTLES: Mutex<Vec<Throttle>> = Mutex::new(Vec::new());
+
+struct Throttle {
+    provider: &'static str,
+    until: DateTime<Utc>,
+    /// Throttles in a row, without a sync getting through in between.
+    strikes: u32,
+    /// Whether a sync is already set to run once it ends.
+    retry: bool,
+}
+
+fn header<'a>(headers: &'a HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+}
+
+/// `Retry-After`, in seconds or as a date.
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = header(headers, &[RETRY_AFTER.as_str()])?;
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+/// How long the server asks us to wait: `Retry-After`, in seconds or as a
+/// date, else until a used-up quota resets.
+pub fn requested_wait(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    if let Some(wait) = retry_after(headers, now) {
+        return Some(wait);
+    }
+    let remaining = header(headers, &["x-ratelimit-remaining", "ratelimit-remaining"])?;
+    if remaining != "0" {
+        return None;
+    }
+    let reset = header(headers, &["x-ratelimit-reset", "ratelimit-reset"])?
+        .parse::<i64>()
+        .ok()?;
+    if reset > EPOCH_RESET {
+        let at = DateTime::from_timestamp(reset, 0)?;
+        Some((at - now).to_std().unwrap_or_default())
+    } else {
+        Some(Duration::from_secs(reset.max(0) as u64))
+    }
+}
+
+/// `FIRST_BACKOFF` doubled `strikes` times, up to `MAX_BACKOFF`.
+pub fn backoff(strikes: u32) -> Duration {
+    FIRST_BACKOFF
+        .saturating_mul(2u32.saturating_pow(strikes))
+        .min(MAX_BACKOFF)
+}
+
+/// The pause before retrying a request turned away for the moment, on
+/// its `attempt`th try: `Retry-After` if the server sent one, else a second
+/// doubled for each attempt.
+pub fn retry_wait(headers: &HeaderMap, attempt: u32) -> Duration {
+    retry_after(headers, Utc::now())
+        .unwrap_or_else(|| Duration::from_secs(2u64.saturating_pow(attempt)))
+}
+
+/// `wait` and up to `JITTER` of it more.
+fn jittered(wait: Duration) -> Duration {
+    let random = (uuid::Uuid::new_v4().as_u128() % 1000) as f64 / 1000.0;
+    wait.mul_f64(1.0 + JITTER * random)
+}
+
+/// The local time `at` falls on, written as the user's locale does, with
+/// the date too unless it's today.
+pub fn clock(at: DateTime<Utc>) -> String {
+    fn written<Tz: TimeZone>(at: DateTime<Tz>, now: DateTime<Tz>) -> String {
+        let locale = locale::current();
+        if at.date_naive() == now.date_naive() {
+            locale.format_time(at.time())
+        } else {
+            locale.format_datetime(&at)
+        }
+    }
+    let now = Utc::now();
+    match timezone::parse_zone(&timezone::system_zone()) {
+        Ok(zone) => written(at.with_timezone(&zone), now.with_timezone(&zone)),
+        Err(_) => written(
+            at.with_timezone(&chrono::Local),
+            now.with_timezone(&chrono::Local),
+        ),
+    }
+}
+
+/// Pause `provider` for as long as the response's `headers` ask, or by
+/// backing off, and say until when in an error for the sync report.
+pub fn limited(provider: &'static str, name: &str, headers: &HeaderMap) -> CommandError {
+    let now = Utc::now();
+    let mut throttles = THROTTLES.lock().unwrap_or_else(|e| e.into_inner());
+    let index = match throttles.iter().position(|t| t.provider == provider) {
+        Some(index) => index,
+        None => {
+            throttles.push(Throttle {
+                provider,
+                until: now,
+                strikes: 0,
+                retry: false,
+            });
+            throttles.len() - 1
+        }
+    };
+    let throttle = &mut throttles[index];
+    let wait = requested_wait(headers, now).unwrap_or_else(|| backoff(throttle.strikes));
+    let until = now + chrono::Duration::from_std(jittered(wait)).unwrap_or_default();
+    throttle.until = throttle.until.max(until);
+    throttle.strikes += 1;
+    paused(provider, name, throttle.until)
+}
+
+/// When `provider`'s pause ends, while it's paused.
+pub fn until(provider: &str) -> Option<DateTime<Utc>> {
+    let throttles = THROTTLES.lock().unwrap_or_else(|e| e.into_inner());
+    throttles
+        .iter()
+        .find(|t| t.provider == provider)
+        .map(|t| t.until)
+        .filter(|until| *until > Utc::now())
+}
+
+/// Fail without asking while `provider` is paused.
+pub fn check(provider: &str, name: &str) -> CommandResult<()> {
+    match until(provider) {
+        Some(until) => Err(paused(provider, name, until)),
+        None => Ok(()),
+    }
+}
+
+fn paused(provider: &str, name: &str, until: DateTime<Utc>) -> CommandError {
+    CommandError::new(
+        ErrorCode::RateLimited,
+        format!("{name} is limiting requests until {}", clock(until)),
+    )
+    .with_provider(provider)
+}
+
+/// Forget the throttles before a sync that got through.
+pub fn clear(provider: &str) {
+    let mut throttles = THROTTLES.lock().unwrap_or_else(|e| e.into_inner());
+    throttles.retain(|t| t.provider != provider || t.until > Utc::now());
+}
+
+/// Claim the retry after `provider`'s pause; false when one is already set.
+pub fn claim_retry(provider: &str) -> bool {
+    let mut throttles = THROTTLES.lock().unwrap_or_else(|e| e.into_inner());
+    match throttles.iter_mut().find(|t| t.provider == provider) {
+        Some(throttle) if !throttle.retry => {
+            throttle.retry = true;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Let a later throttle of `provider` set another retry.
+pub fn release_retry(provider: &str) {
+    let mut throttles = THROTTLES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(throttle) = throttles.iter_mut().find(|t| t.provider == provider) {
+        throttle.retry = false;
+    }
+}
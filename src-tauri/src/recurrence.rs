@@ -0,0 +1,244 @@
+use std::time::Duration;
+
+use chrono::{Days, Local, NaiveDate, NaiveTime, Utc};
+use rusqlite::{params, Connection};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db::{format_utc, now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::rrule::Rrule;
+use crate::tags;
+use crate::task_store::{self, Task, STATUS_OPEN, TASK_COLUMNS};
+use crate::timezone;
+
+/// Emitted with the newly created instances whenever recurring tasks are
+/// materialized, either on completion or when the date rolls over.
+pub const RECURRENCE_EVENT: &str = "recurring-tasks-created";
+
+const ROLLOVER_POLL: Duration = Duration::from_secs(60);
+const DEFAULT_PREVIEW: u32 = 10;
+const MAX_PREVIEW: u32 = 500;
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+fn date_prefix(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// The date an instance is for: its scheduled date, falling back to due.
+fn instance_date(task: &Task) -> Option<NaiveDate> {
+    task.scheduled
+        .as_deref()
+        .or(task.due.as_deref())
+        .and_then(date_prefix)
+}
+
+/// Move the date part of `value` by `days`, keeping any time suffix.
+fn shift_date(value: &str, days: i64) -> String {
+    let Some(date) = date_prefix(value) else {
+        return value.to_string();
+    };
+    let shifted = if days >= 0 {
+        date.checked_add_days(Days::new(days as u64))
+    } else {
+        date.checked_sub_days(Days::new(days.unsigned_abs()))
+    };
+    match shifted {
+        Some(shifted) => format!("{}{}", shifted.format("%Y-%m-%d"), &value[10..]),
+        None => value.to_string(),
+    }
+}
+
+/// Create the instance that follows `task` in its series, if the rule has
+/// one and it doesn't exist yet. Overdue series resume from today rather
+/// than replaying every missed date.
+pub fn next_instance(
+    conn: &Connection,
+    task: &Task,
+    today: NaiveDate,
+) -> rusqlite::Result<Option<Task>> {
+    let Some(rule) = task.recurrence.as_deref() else {
+        return Ok(None);
+    };
+    let rule = match Rrule::parse(rule) {
+        Ok(rule) => rule,
+        Err(e) => {
+            tracing::warn!("skipping task {}: {e}", task.id);
+            return Ok(None);
+        }
+    };
+
+    let anchor = instance_date(task);
+    let after = anchor
+        .unwrap_or(rule.dtstart)
+        .max(today.pred_opt().unwrap_or(today));
+    let Some(next) = rule.next_after(after) else {
+        return Ok(None);
+    };
+
+    let series_id = task.series_id.clone().unwrap_or_else(|| task.id.clone());
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM tasks WHERE series_id = ?1 AND deleted_at IS NULL
+             AND substr(COALESCE(scheduled, due), 1, 10) = ?2)",
+        params![series_id, next.format("%Y-%m-%d").to_string()],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(None);
+    }
+
+    let offset = anchor.map(|a| (next - a).num_days());
+    let (scheduled, due) = match offset {
+        Some(days) => (
+            task.scheduled.as_deref().map(|s| shift_date(s, days)),
+            task.due.as_deref().map(|d| shift_date(d, days)),
+        ),
+        None => (Some(next.format("%Y-%m-%d").to_string()), None),
+    };
+
+    let now = now_utc();
+    let mut instance = Task {
+        id: uuid::Uuid::new_v4().to_string(),
+        title: task.title.clone(),
+        description: task.description.clone(),
+        status: STATUS_OPEN.to_string(),
+        project: task.project.clone(),
+        priority: task.priority,
+        due,
+        scheduled,
+        created_at: now.clone(),
+        updated_at: now,
+        completed_at: None,
+        recurrence: task.recurrence.clone(),
+        series_id: Some(series_id),
+        tz: task.tz.clone(),
+        parent_id: task.parent_id.clone(),
+        sort_key: task.sort_key.clone(),
+        estimate_minutes: task.estimate_minutes,
+        tags: Vec::new(),
+        is_blocked: false,
+    };
+    task_store::write_task(conn, &instance)?;
+    tags::set_task_tags(conn, &instance.id, &task.tags)?;
+    instance.tags = task.tags.clone();
+    Ok(Some(instance))
+}
+
+/// Make sure every series whose latest instance is in the past has an
+/// instance for today or later.
+pub fn roll_over(conn: &Connection, today: NaiveDate) -> rusqlite::Result<Vec<Task>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TASK_COLUMNS} FROM tasks t
+         WHERE recurrence IS NOT NULL AND series_id IS NOT NULL AND deleted_at IS NULL
+           AND NOT EXISTS (
+               SELECT 1 FROM tasks later
+               WHERE later.series_id = t.series_id AND later.deleted_at IS NULL
+                 AND COALESCE(later.scheduled, later.due) > COALESCE(t.scheduled, t.due)
+           )"
+    ))?;
+    let mut latest = stmt
+        .query_map([], task_store::row_to_task)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tags::load_task_tags(conn, &mut latest)?;
+
+    let mut created = Vec::new();
+    for task in latest {
+        if instance_date(&task).is_some_and(|date| date < today) {
+            created.extend(next_instance(conn, &task, today)?);
+        }
+    }
+    Ok(created)
+}
+
+pub fn run_roll_over(app: &AppHandle) {
+    let db = app.state::<Db>().unjournaled();
+    let result = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let created = roll_over(&tx, today())?;
+        tx.commit()?;
+        Ok(created)
+    });
+    match result {
+        Ok(created) if !created.is_empty() => {
+            let _ = app.emit(RECURRENCE_EVENT, created);
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("rollover failed: {e}"),
+    }
+}
+
+/// Materialize recurring tasks now and again whenever the local date changes.
+pub fn spawn_rollover_watcher(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || {
+        let mut last_day = None;
+        loop {
+            let day = today();
+            if last_day != Some(day) {
+                run_roll_over(&handle);
+                crate::scripts::day_started(&handle, day);
+                last_day = Some(day);
+            }
+            std::thread::sleep(ROLLOVER_POLL);
+        }
+    });
+}
+
+fn preview(rule: &str, from: Option<String>, limit: Option<u32>) -> CommandResult<Vec<NaiveDate>> {
+    let rule = Rrule::parse(rule)?;
+    let from = match from {
+        Some(from) => date_prefix(&from)
+            .ok_or_else(|| CommandError::invalid(format!("Invalid date: {from}")))?,
+        None => rule.dtstart,
+    };
+    let limit = limit.unwrap_or(DEFAULT_PREVIEW).min(MAX_PREVIEW) as usize;
+
+    Ok(rule
+        .occurrences()
+        .skip_while(|date| *date < from)
+        .take(limit)
+        .collect())
+}
+
+/// Preview the dates a rule produces, starting at `from` (inclusive).
+#[tauri::command]
+pub fn expand_rrule(
+    rule: String,
+    from: Option<String>,
+    limit: Option<u32>,
+) -> CommandResult<Vec<String>> {
+    Ok(preview(&rule, from, limit)?
+        .into_iter()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .collect())
+}
+
+/// Like `expand_rrule`, but pins every occurrence to the same wall-clock
+/// `time` (HH:MM) in `zone` and returns UTC timestamps, so a 09:00 series
+/// stays at 09:00 local across DST changes.
+#[tauri::command]
+pub fn expand_rrule_at(
+    rule: String,
+    time: String,
+    zone: String,
+    from: Option<String>,
+    limit: Option<u32>,
+) -> CommandResult<Vec<String>> {
+    let tz = timezone::parse_zone(&zone)?;
+    let time = NaiveTime::parse_from_str(&time, "%H:%M")
+        .map_err(|_| CommandError::invalid(format!("Invalid time: {time}")))?;
+
+    preview(&rule, from, limit)?
+        .into_iter()
+        .map(|date| {
+            timezone::resolve_local(date.and_time(time), &tz)
+                .map(|dt| format_utc(dt.with_timezone(&Utc)))
+                .ok_or_else(|| {
+                    CommandError::invalid(format!("{date} {time} does not exist in {zone}"))
+                })
+        })
+        .collect()
+}
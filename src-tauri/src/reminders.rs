@@ -0,0 +1,339 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::location;
+use crate::settings;
+use crate::sun::{self, SunTimes};
+
+/// How often sun-relative reminders are moved to their next occurrence.
+/// Sun times drift by a minute or two a day, so once an hour keeps them
+/// close and picks up a move to another place.
+const SOLAR_POLL: StdDuration = StdDuration::from_secs(3600);
+/// Offsets reach at most half a day either side of the event.
+const MAX_SOLAR_OFFSET_MINUTES: i64 = 720;
+
+/// Sun events a reminder can follow.
+pub const SOLAR_EVENTS: &[&str] = &[
+    "civil_dawn",
+    "sunrise",
+    "solar_noon",
+    "sunset",
+    "civil_dusk",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Reminder {
+    pub id: String,
+    pub task_id: String,
+    /// When to remind, RFC 3339 UTC.
+    pub remind_at: String,
+    pub created_at: String,
+    /// The sun-relative reminder this is the next occurrence of, if any.
+    pub solar_id: Option<String>,
+}
+
+/// A reminder relative to a sun event where the user is, such as 30
+/// minutes before sunset.
+#[derive(Debug, Clone, Serialize)]
+pub struct SolarReminder {
+    pub id: String,
+    pub task_id: String,
+    /// One of `SOLAR_EVENTS`.
+    pub event: String,
+    /// Minutes after the event; negative for before.
+    pub offset_minutes: i64,
+    /// When it next fires, if it's been worked out.
+    pub next_at: Option<String>,
+    pub created_at: String,
+}
+
+fn row_to_reminder(row: &Row) -> rusqlite::Result<Reminder> {
+    Ok(Reminder {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        remind_at: row.get(2)?,
+        created_at: row.get(3)?,
+        solar_id: row.get(4)?,
+    })
+}
+
+fn row_to_solar(row: &Row) -> rusqlite::Result<SolarReminder> {
+    Ok(SolarReminder {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        event: row.get(2)?,
+        offset_minutes: row.get(3)?,
+        next_at: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+const SOLAR_COLUMNS: &str = "s.id, s.task_id, s.event, s.offset_minutes,
+    (SELECT MIN(r.remind_at) FROM reminders r WHERE r.solar_id = s.id), s.created_at";
+
+pub fn add_reminder(
+    conn: &Connection,
+    task_id: &str,
+    remind_at: &str,
+) -> rusqlite::Result<Reminder> {
+    let reminder = Reminder {
+        id: uuid::Uuid::new_v4().to_string(),
+        task_id: task_id.to_string(),
+        remind_at: remind_at.to_string(),
+        created_at: now_utc(),
+        solar_id: None,
+    };
+    conn.execute(
+        "INSERT INTO reminders (id, task_id, remind_at, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            reminder.id,
+            reminder.task_id,
+            reminder.remind_at,
+            reminder.created_at
+        ],
+    )?;
+    Ok(reminder)
+}
+
+/// Replace a task's reminders with `times` (RFC 3339 UTC). Sun-relative
+/// reminders stay.
+pub fn set_task_reminders(
+    conn: &Connection,
+    task_id: &str,
+    times: &[String],
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM reminders WHERE task_id = ?1 AND solar_id IS NULL",
+        params![task_id],
+    )?;
+    for time in times {
+        add_reminder(conn, task_id, time)?;
+    }
+    Ok(())
+}
+
+/// Reminders for one task, or every upcoming reminder when `task_id` is
+/// omitted, soonest first.
+#[tauri::command]
+pub fn list_reminders(db: State<'_, Db>, task_id: Option<String>) -> CommandResult<Vec<Reminder>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, remind_at, created_at, solar_id FROM reminders
+             WHERE ((?1 IS NOT NULL AND task_id = ?1) OR (?1 IS NULL AND remind_at >= ?2))
+               AND task_id IN (SELECT id FROM tasks WHERE deleted_at IS NULL)
+             ORDER BY remind_at",
+        )?;
+        let rows = stmt.query_map(params![task_id, now_utc()], row_to_reminder)?;
+        rows.collect()
+    })
+}
+
+fn event_time(times: &SunTimes, event: &str) -> Option<String> {
+    match event {
+        "civil_dawn" => times.civil_dawn.clone(),
+        "sunrise" => times.sunrise.clone(),
+        "solar_noon" => Some(times.solar_noon.clone()),
+        "sunset" => times.sunset.clone(),
+        "civil_dusk" => times.civil_dusk.clone(),
+        _ => None,
+    }
+}
+
+/// The first time after `now` that falls `offset_minutes` from `event` at
+/// `lat`, `lon`. `None` while the event doesn't happen, as with sunset
+/// during the polar day.
+fn next_solar_time(
+    event: &str,
+    offset_minutes: i64,
+    lat: f64,
+    lon: f64,
+    now: DateTime<Utc>,
+) -> CommandResult<Option<DateTime<Utc>>> {
+    let today = now.date_naive();
+    // A day either side covers offsets that cross midnight.
+    for days in -1..=2 {
+        let times = sun::sun_times(today + Duration::days(days), lat, lon)?;
+        let Some(at) = event_time(&times, event) else {
+            continue;
+        };
+        let at = parse_utc(&at)? + Duration::minutes(offset_minutes);
+        if at > now {
+            return Ok(Some(at));
+        }
+    }
+    Ok(None)
+}
+
+/// Point a sun-relative reminder's reminder row at `at`, leaving it be if
+/// it's already there so an unchanged schedule isn't rewritten.
+fn place_solar(
+    conn: &Connection,
+    rule: &SolarReminder,
+    at: Option<DateTime<Utc>>,
+) -> rusqlite::Result<()> {
+    let at = at.map(format_utc);
+    if rule.next_at == at {
+        return Ok(());
+    }
+    conn.execute(
+        "DELETE FROM reminders WHERE solar_id = ?1",
+        params![rule.id],
+    )?;
+    if let Some(at) = at {
+        conn.execute(
+            "INSERT INTO reminders (id, task_id, remind_at, created_at, solar_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                rule.task_id,
+                at,
+                now_utc(),
+                rule.id
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn load_solar(conn: &Connection, task_id: Option<&str>) -> rusqlite::Result<Vec<SolarReminder>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SOLAR_COLUMNS} FROM solar_reminders s
+         WHERE ?1 IS NULL OR s.task_id = ?1
+         ORDER BY s.created_at"
+    ))?;
+    let rows = stmt.query_map(params![task_id], row_to_solar)?;
+    rows.collect()
+}
+
+/// Move each sun-relative reminder (or just `only`) to its next occurrence
+/// where the user is. Without a location, reminders keep their last time.
+pub async fn schedule_solar(db: &Db, only: Option<&str>) -> CommandResult<()> {
+    let rules: Vec<SolarReminder> = db
+        .with_conn(|conn| load_solar(conn, None))?
+        .into_iter()
+        .filter(|rule| only.is_none_or(|id| rule.id == id))
+        .collect();
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let here = location::current(db).await?;
+    let now = Utc::now();
+    let mut placed = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let at = next_solar_time(
+            &rule.event,
+            rule.offset_minutes,
+            here.latitude,
+            here.longitude,
+            now,
+        )?;
+        placed.push((rule, at));
+    }
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        for (rule, at) in &placed {
+            place_solar(&tx, rule, *at)?;
+        }
+        tx.commit()
+    })
+}
+
+pub fn spawn_solar_scheduler(app: &AppHandle) {
+    let handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // Sun reminders stay where they are while the setting is off.
+            if settings::load(&handle).reminders.solar {
+                let db = handle.state::<Db>().unjournaled();
+                if let Err(e) = schedule_solar(&db, None).await {
+                    tracing::warn!("scheduling sun reminders failed: {e}");
+                }
+            }
+            tokio::time::sleep(SOLAR_POLL).await;
+        }
+    });
+}
+
+/// Remind about a task `offset_minutes` from a sun event (negative for
+/// before), every day, at the user's location: for routines that follow
+/// the daylight rather than the clock.
+#[tauri::command]
+pub async fn add_solar_reminder(
+    db: State<'_, Db>,
+    task_id: String,
+    event: String,
+    offset_minutes: i64,
+) -> CommandResult<SolarReminder> {
+    if !SOLAR_EVENTS.contains(&event.as_str()) {
+        return Err(CommandError::invalid(format!(
+            "Unknown sun event '{event}'; expected one of {}",
+            SOLAR_EVENTS.join(", ")
+        )));
+    }
+    if offset_minutes.abs() > MAX_SOLAR_OFFSET_MINUTES {
+        return Err(CommandError::invalid(format!(
+            "The offset must be within {MAX_SOLAR_OFFSET_MINUTES} minutes of the event"
+        )));
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    db.with_conn(|conn| {
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM tasks WHERE id = ?1 AND deleted_at IS NULL",
+                params![task_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            return Ok(Err(CommandError::not_found(format!(
+                "Task not found: {task_id}"
+            ))));
+        }
+        conn.execute(
+            "INSERT INTO solar_reminders (id, task_id, event, offset_minutes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, task_id, event, offset_minutes, now_utc()],
+        )?;
+        Ok(Ok(()))
+    })??;
+    if let Err(e) = schedule_solar(&db, Some(&id)).await {
+        tracing::warn!("scheduling sun reminder {id} failed: {e}");
+    }
+    db.with_conn(|conn| {
+        conn.query_row(
+            &format!("SELECT {SOLAR_COLUMNS} FROM solar_reminders s WHERE s.id = ?1"),
+            params![id],
+            row_to_solar,
+        )
+    })
+}
+
+/// Sun-relative reminders for one task, or all of them.
+#[tauri::command]
+pub fn list_solar_reminders(
+    db: State<'_, Db>,
+    task_id: Option<String>,
+) -> CommandResult<Vec<SolarReminder>> {
+    db.with_conn(|conn| load_solar(conn, task_id.as_deref()))
+}
+
+/// Stop a sun-relative reminder; its upcoming reminder goes with it.
+#[tauri::command]
+pub fn remove_solar_reminder(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let removed = db
+        .with_conn(|conn| conn.execute("DELETE FROM solar_reminders WHERE id = ?1", params![id]))?;
+    if removed == 0 {
+        return Err(CommandError::not_found(format!(
+            "Sun reminder not found: {id}"
+        )));
+    }
+    Ok(())
+}
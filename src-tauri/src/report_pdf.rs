@@ -0,0 +1,504 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use printpdf::{
+    Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point,
+    Rect, Rgb,
+};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::billing;
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::reports::{self, CompletionReport, GroupBy, Range, ReportQuery, TimeReport};
+use crate::session::write_atomic;
+use crate::usage;
+
+/// A4 portrait, in millimetres.
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 18.0;
+const CHART_HEIGHT: f32 = 45.0;
+const TITLE_SIZE: f32 = 18.0;
+const HEADING_SIZE: f32 = 13.0;
+const BODY_SIZE: f32 = 10.0;
+const SMALL_SIZE: f32 = 8.0;
+/// Points to millimetres.
+const PT: f32 = 25.4 / 72.0;
+/// Period labels fitted under a chart before some are skipped.
+const MAX_CHART_LABELS: usize = 12;
+/// Bar colours as RGB fractions.
+const TIME_COLOR: (f32, f32, f32) = (0.23, 0.51, 0.96);
+const COMPLETION_COLOR: (f32, f32, f32) = (0.06, 0.73, 0.51);
+/// Embedded in each report so names in Greek, Cyrillic, Hebrew and other
+/// scripts past the PDF built-in fonts' Latin-1 come out right. DejaVu has
+/// no CJK glyphs. Shipped in `fonts/` among the bundle's resources rather
+/// than compiled into the binary.
+const REGULAR_FONT: &str = "DejaVuSans.ttf";
+const BOLD_FONT: &str = "DejaVuSans-Bold.ttf";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReportRange {
+    /// RFC 3339 instant, or a `YYYY-MM-DD` date (midnight in `zone`).
+    pub from: String,
+    /// Exclusive RFC 3339 instant, or an inclusive `YYYY-MM-DD` date.
+    pub to: String,
+    /// IANA zone that decides where days start. Defaults to the system zone.
+    #[serde(default)]
+    pub zone: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReportPdfOptions {
+    /// Heading on the first page, such as the client's name.
+    pub title: String,
+    /// How the charts split the range: `day` for a weekly report, `week`
+    /// or `month` for longer ones.
+    pub group_by: GroupBy,
+    pub project: Option<String>,
+    pub tag: Option<String>,
+    pub charts: bool,
+    pub completions: bool,
+    /// List time per task, as a timesheet.
+    pub tasks: bool,
+}
+
+impl Default for ReportPdfOptions {
+    fn default() -> Self {
+        Self {
+            title: "Time report".to_string(),
+            group_by: GroupBy::Day,
+            project: None,
+            tag: None,
+            charts: true,
+            completions: true,
+            tasks: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportPdfSummary {
+    pub path: String,
+    pub pages: usize,
+    pub total_seconds: i64,
+    pub completed: i64,
+}
+
+/// Everything the PDF shows, read in one go.
+struct ReportData {
+    time: TimeReport,
+    by_project: TimeReport,
+    by_task: TimeReport,
+    completions: CompletionReport,
+}
+
+/// The font files a report embeds, as read from the bundle's resources.
+struct Fonts {
+    regular: Vec<u8>,
+    bold: Vec<u8>,
+}
+
+impl Fonts {
+    fn load(app: &AppHandle) -> CommandResult<Self> {
+        let dir = app
+            .path()
+            .resource_dir()
+            .map_err(|e| e.to_string())?
+            .join("fonts");
+        let read = |name: &str| {
+            let path = dir.join(name);
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+        };
+        Ok(Self {
+            regular: read(REGULAR_FONT)?,
+            bold: read(BOLD_FONT)?,
+        })
+    }
+}
+
+fn line_height(size: f32) -> f32 {
+    size * PT * 1.45
+}
+
+/// Cut `text` to about `width` mm at `size`. This goes by DejaVu Sans's
+/// average glyph width rather than measuring each glyph.
+fn fit(text: &str, width: f32, size: f32) -> String {
+    let max = (width / (size * PT * 0.55)) as usize;
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max.saturating_sub(3)).collect();
+    cut.push_str("...");
+    cut
+}
+
+fn period_label(key: &str, group_by: GroupBy) -> String {
+    let parsed = match group_by {
+        GroupBy::Month => NaiveDate::parse_from_str(&format!("{key}-01"), "%Y-%m-%d")
+            .map(|d| d.format("%b %Y").to_string()),
+        _ => NaiveDate::parse_from_str(key, "%Y-%m-%d").map(|d| d.format("%b %-d").to_string()),
+    };
+    parsed.unwrap_or_else(|_| key.to_string())
+}
+
+/// A document being laid out top to bottom, page after page.
+struct Pdf {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    /// Where the next line's baseline goes, in mm from the page bottom.
+    y: f32,
+    pages: usize,
+}
+
+impl Pdf {
+    fn new(title: &str, fonts: &Fonts) -> CommandResult<Self> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Report");
+        let regular = doc
+            .add_external_font(fonts.regular.as_slice())
+            .map_err(|e| e.to_string())?;
+        let bold = doc
+            .add_external_font(fonts.bold.as_slice())
+            .map_err(|e| e.to_string())?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Self {
+            doc,
+            layer,
+            regular,
+            bold,
+            y: PAGE_HEIGHT - MARGIN,
+            pages: 1,
+        })
+    }
+
+    fn page_break(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Report");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+        self.pages += 1;
+    }
+
+    /// Start a new page unless `height` mm still fit on this one.
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.page_break();
+        }
+    }
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    fn text(&self, text: &str, size: f32, x: f32, y: f32, bold: bool) {
+        let font = if bold { &self.bold } else { &self.regular };
+        self.layer.use_text(text, size, Mm(x), Mm(y), font);
+    }
+
+    fn line(&mut self, text: &str, size: f32, bold: bool) {
+        self.row(&[(0.0, PAGE_WIDTH - 2.0 * MARGIN, text)], size, bold);
+    }
+
+    /// One line of cells, each `(offset, width, text)` in mm from the left
+    /// margin.
+    fn row(&mut self, cells: &[(f32, f32, &str)], size: f32, bold: bool) {
+        let height = line_height(size);
+        self.reserve(height);
+        self.y -= height;
+        for (offset, width, text) in cells {
+            self.text(
+                &fit(text, *width, size),
+                size,
+                MARGIN + offset,
+                self.y,
+                bold,
+            );
+        }
+    }
+
+    /// A horizontal line from the left margin, `width` mm long.
+    fn hline(&self, y: f32, width: f32, thickness: f32, grey: f32) {
+        self.layer
+            .set_outline_color(Color::Rgb(Rgb::new(grey, grey, grey, None)));
+        self.layer.set_outline_thickness(thickness);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(y)), false),
+                (Point::new(Mm(MARGIN + width), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    /// A thin rule across the page under the last line.
+    fn rule(&mut self) {
+        self.hline(self.y - 1.5, PAGE_WIDTH - 2.0 * MARGIN, 0.5, 0.6);
+        self.gap(2.0);
+    }
+
+    /// A titled bar chart over the page's width, with light gridlines at
+    /// quarters of the largest value. Drawn with PDF paths rather than
+    /// pre-rendered SVG: printpdf's `svg` feature needs usvg and fontdb.
+    fn chart(
+        &mut self,
+        title: &str,
+        labels: &[String],
+        values: &[f64],
+        max_label: &str,
+        (r, g, b): (f32, f32, f32),
+    ) {
+        let width = PAGE_WIDTH - 2.0 * MARGIN;
+        let label_height = line_height(SMALL_SIZE);
+        self.reserve(line_height(HEADING_SIZE) + CHART_HEIGHT + 2.0 * label_height + 4.0);
+        self.line(title, HEADING_SIZE, true);
+        self.row(&[(0.0, width, max_label)], SMALL_SIZE, false);
+        self.gap(1.0);
+
+        let bottom = self.y - CHART_HEIGHT;
+        for quarter in 1..=4 {
+            let y = bottom + CHART_HEIGHT * quarter as f32 / 4.0;
+            self.hline(y, width, 0.5, 0.87);
+        }
+        let slot = width / values.len().max(1) as f32;
+        let bar = slot * 0.7;
+        let max = values.iter().copied().fold(0.0, f64::max);
+        self.layer
+            .set_fill_color(Color::Rgb(Rgb::new(r, g, b, None)));
+        for (i, value) in values.iter().enumerate() {
+            if max <= 0.0 || *value <= 0.0 {
+                continue;
+            }
+            let x = MARGIN + i as f32 * slot + (slot - bar) / 2.0;
+            let top = bottom + (value / max) as f32 * CHART_HEIGHT;
+            self.layer
+                .add_rect(Rect::new(Mm(x), Mm(bottom), Mm(x + bar), Mm(top)));
+        }
+        self.hline(bottom, width, 1.0, 0.53);
+        self.layer
+            .set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+
+        let every = labels.len().div_ceil(MAX_CHART_LABELS).max(1);
+        let y = bottom - label_height;
+        for (i, label) in labels.iter().enumerate().step_by(every) {
+            let x = MARGIN + i as f32 * slot;
+            let text = fit(label, slot * every as f32, SMALL_SIZE);
+            self.text(&text, SMALL_SIZE, x, y, false);
+        }
+        self.y = y - 4.0;
+    }
+
+    fn finish(self) -> CommandResult<(Vec<u8>, usize)> {
+        let pages = self.pages;
+        let bytes = self.doc.save_to_bytes().map_err(|e| e.to_string())?;
+        Ok((bytes, pages))
+    }
+}
+
+fn describe(range: &ReportRange, options: &ReportPdfOptions) -> String {
+    let mut text = format!("{} to {}", range.from, range.to);
+    if let Some(zone) = &range.zone {
+        let _ = write!(text, " ({zone})");
+    }
+    if let Some(project) = &options.project {
+        let _ = write!(text, ", project {project}");
+    }
+    if let Some(tag) = &options.tag {
+        let _ = write!(text, ", tag {tag}");
+    }
+    text
+}
+
+fn render(
+    range: &ReportRange,
+    options: &ReportPdfOptions,
+    data: &ReportData,
+    fonts: &Fonts,
+) -> CommandResult<(Vec<u8>, usize)> {
+    let mut pdf = Pdf::new(&options.title, fonts)?;
+    let width = PAGE_WIDTH - 2.0 * MARGIN;
+    pdf.line(&options.title, TITLE_SIZE, true);
+    pdf.line(&describe(range, options), BODY_SIZE, false);
+    pdf.gap(4.0);
+
+    let time = &data.time;
+    let mut summary = vec![
+        format!("Tracked: {} h", billing::hours(time.total_seconds)),
+        format!("Billable: {} h", billing::hours(time.billable_seconds)),
+        format!("Entries: {}", time.entry_count),
+    ];
+    if options.completions {
+        summary.push(format!("Tasks completed: {}", data.completions.total));
+    }
+    for line in &summary {
+        pdf.line(line, BODY_SIZE, false);
+    }
+    pdf.gap(4.0);
+
+    if options.charts {
+        let labels: Vec<String> = time
+            .buckets
+            .iter()
+            .map(|b| period_label(&b.key, options.group_by))
+            .collect();
+        let hours: Vec<f64> = time
+            .buckets
+            .iter()
+            .map(|b| b.total_seconds as f64 / 3600.0)
+            .collect();
+        let max = hours.iter().copied().fold(0.0, f64::max);
+        pdf.chart(
+            "Time tracked",
+            &labels,
+            &hours,
+            &format!("Up to {max:.2} h"),
+            TIME_COLOR,
+        );
+
+        if options.completions {
+            let completions = &data.completions;
+            let labels: Vec<String> = completions
+                .buckets
+                .iter()
+                .map(|b| period_label(&b.key, options.group_by))
+                .collect();
+            let counts: Vec<f64> = completions.buckets.iter().map(|b| b.count as f64).collect();
+            let max = completions
+                .buckets
+                .iter()
+                .map(|b| b.count)
+                .max()
+                .unwrap_or(0);
+            pdf.chart(
+                "Tasks completed",
+                &labels,
+                &counts,
+                &format!("Up to {max}"),
+                COMPLETION_COLOR,
+            );
+        }
+    }
+
+    let columns = |name: f32| [(0.0, name), (name + 4.0, 30.0), (name + 36.0, 30.0)];
+    let [name, hours, extra] = columns(width - 70.0);
+
+    pdf.reserve(line_height(HEADING_SIZE) + 3.0 * line_height(BODY_SIZE));
+    pdf.line("By project", HEADING_SIZE, true);
+    pdf.row(
+        &[
+            (name.0, name.1, "Project"),
+            (hours.0, hours.1, "Hours"),
+            (extra.0, extra.1, "Billable"),
+        ],
+        BODY_SIZE,
+        true,
+    );
+    pdf.rule();
+    for bucket in &data.by_project.buckets {
+        pdf.row(
+            &[
+                (name.0, name.1, &bucket.label),
+                (hours.0, hours.1, &billing::hours(bucket.total_seconds)),
+                (extra.0, extra.1, &billing::hours(bucket.billable_seconds)),
+            ],
+            BODY_SIZE,
+            false,
+        );
+    }
+
+    if options.tasks {
+        pdf.gap(4.0);
+        pdf.reserve(line_height(HEADING_SIZE) + 3.0 * line_height(BODY_SIZE));
+        pdf.line("By task", HEADING_SIZE, true);
+        pdf.row(
+            &[
+                (name.0, name.1, "Task"),
+                (hours.0, hours.1, "Hours"),
+                (extra.0, extra.1, "Entries"),
+            ],
+            BODY_SIZE,
+            true,
+        );
+        pdf.rule();
+        for bucket in &data.by_task.buckets {
+            pdf.row(
+                &[
+                    (name.0, name.1, &bucket.label),
+                    (hours.0, hours.1, &billing::hours(bucket.total_seconds)),
+                    (extra.0, extra.1, &bucket.entry_count.to_string()),
+                ],
+                BODY_SIZE,
+                false,
+            );
+        }
+    }
+
+    pdf.finish()
+}
+
+fn query(range: &ReportRange, options: &ReportPdfOptions, group_by: GroupBy) -> ReportQuery {
+    ReportQuery {
+        from: range.from.clone(),
+        to: range.to.clone(),
+        group_by,
+        zone: range.zone.clone(),
+        project: options.project.clone(),
+        tag: options.tag.clone(),
+    }
+}
+
+/// Render the range's time and completions to a PDF at `path`, for sending
+/// a timesheet to a client: totals, bar charts over `options.group_by`,
+/// then hours by project and by task.
+#[tauri::command]
+pub fn export_report_pdf(
+    app: AppHandle,
+    db: State<'_, Db>,
+    path: String,
+    range: ReportRange,
+    options: Option<ReportPdfOptions>,
+) -> CommandResult<ReportPdfSummary> {
+    let mut options = options.unwrap_or_default();
+    if !options.group_by.is_calendar() {
+        return Err(CommandError::invalid(
+            "Report charts must be grouped by day, week or month",
+        ));
+    }
+    options.title = match options.title.trim() {
+        "" => ReportPdfOptions::default().title,
+        title => title.to_string(),
+    };
+
+    let calendar = query(&range, &options, options.group_by);
+    let report_range = Range::from_query(&calendar)?;
+    let data = db.with_conn(|conn| {
+        Ok(ReportData {
+            time: reports::time_report(conn, &calendar, &report_range)?,
+            by_project: reports::time_report(
+                conn,
+                &query(&range, &options, GroupBy::Project),
+                &report_range,
+            )?,
+            by_task: reports::time_report(
+                conn,
+                &query(&range, &options, GroupBy::Task),
+                &report_range,
+            )?,
+            completions: reports::completion_report(conn, &calendar, &report_range)?,
+        })
+    })?;
+
+    let (bytes, pages) = render(&range, &options, &data, &Fonts::load(&app)?)?;
+    write_atomic(Path::new(&path), &bytes)?;
+    usage::record(&db, "report_pdf", None);
+    Ok(ReportPdfSummary {
+        path,
+        pages,
+        total_seconds: data.time.total_seconds,
+        completed: data.completions.total,
+    })
+}
@@ -0,0 +1,481 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::{format_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::task_store::STATUS_DONE;
+use crate::timezone;
+
+/// Separates tag names inside a `group_concat`; can't appear in a name typed
+/// by a user.
+const TAG_SEPARATOR: char = '\u{1f}';
+
+const NO_PROJECT: &str = "No project";
+const UNTAGGED: &str = "Untagged";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    Day,
+    /// Weeks start on Monday; the key is the Monday's date.
+    Week,
+    Month,
+    Project,
+    /// A task with several tags counts towards each of them, so tag buckets
+    /// can add up to more than the report total.
+    Tag,
+    Task,
+}
+
+impl GroupBy {
+    pub fn is_calendar(self) -> bool {
+        matches!(self, GroupBy::Day | GroupBy::Week | GroupBy::Month)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReportQuery {
+    /// RFC 3339 instant, or a `YYYY-MM-DD` date (midnight in `zone`).
+    pub from: String,
+    /// Exclusive RFC 3339 instant, or an inclusive `YYYY-MM-DD` date.
+    pub to: String,
+    pub group_by: GroupBy,
+    /// IANA zone that decides where days start. Defaults to the system zone.
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeBucket {
+    /// Date (`YYYY-MM-DD`), month (`YYYY-MM`), project, tag or task id.
+    /// Empty for "no project" / "untagged".
+    pub key: String,
+    pub label: String,
+    pub total_seconds: i64,
+    pub billable_seconds: i64,
+    pub entry_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeReport {
+    pub group_by: GroupBy,
+    pub from: String,
+    pub to: String,
+    /// Calendar groupings include every period in the range, empty or not,
+    /// in order. Other groupings are sorted by total, largest first.
+    pub buckets: Vec<TimeBucket>,
+    pub total_seconds: i64,
+    pub billable_seconds: i64,
+    pub non_billable_seconds: i64,
+    pub entry_count: i64,
+    /// Mean seconds per bucket.
+    pub average_seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CountBucket {
+    pub key: String,
+    pub label: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionReport {
+    pub group_by: GroupBy,
+    pub from: String,
+    pub to: String,
+    pub buckets: Vec<CountBucket>,
+    pub total: i64,
+    /// Mean completions per bucket.
+    pub average: f64,
+}
+
+pub struct Range {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    tz: Tz,
+}
+
+fn local_midnight(date: NaiveDate, tz: &Tz) -> Option<DateTime<Utc>> {
+    timezone::resolve_local(date.and_time(NaiveTime::MIN), tz).map(|dt| dt.with_timezone(&Utc))
+}
+
+pub fn parse_bound(value: &str, tz: &Tz, inclusive_date: bool) -> CommandResult<DateTime<Utc>> {
+    let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") else {
+        return parse_utc(value);
+    };
+    let date = if inclusive_date {
+        date.succ_opt()
+            .ok_or_else(|| CommandError::invalid(format!("Invalid date: {value}")))?
+    } else {
+        date
+    };
+    local_midnight(date, tz).ok_or_else(|| CommandError::invalid(format!("Invalid date: {value}")))
+}
+
+impl Range {
+    pub fn from_query(query: &ReportQuery) -> CommandResult<Self> {
+        let zone = query.zone.clone().unwrap_or_else(timezone::system_zone);
+        let tz = timezone::parse_zone(&zone)?;
+        let from = parse_bound(&query.from, &tz, false)?;
+        let to = parse_bound(&query.to, &tz, true)?;
+        if to <= from {
+            return Err(CommandError::invalid(
+                "Report range must end after it starts",
+            ));
+        }
+        Ok(Self { from, to, tz })
+    }
+
+    pub fn local_date(&self, at: DateTime<Utc>) -> NaiveDate {
+        at.with_timezone(&self.tz).date_naive()
+    }
+
+    /// Every calendar key in the range, in order.
+    fn periods(&self, group_by: GroupBy) -> Vec<String> {
+        let last = self.local_date(self.to - chrono::Duration::milliseconds(1));
+        let mut keys: Vec<String> = Vec::new();
+        for date in self.local_date(self.from).iter_days() {
+            if date > last {
+                break;
+            }
+            let key = period_key(date, group_by);
+            if keys.last() != Some(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+}
+
+pub fn period_key(date: NaiveDate, group_by: GroupBy) -> String {
+    match group_by {
+        GroupBy::Week => {
+            let monday = date
+                .checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+                .unwrap_or(date);
+            monday.format("%Y-%m-%d").to_string()
+        }
+        GroupBy::Month => date.format("%Y-%m").to_string(),
+        _ => date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// One time entry clipped to the report range, with what it's grouped by.
+struct EntrySpan {
+    task_id: String,
+    title: String,
+    project: Option<String>,
+    tags: Vec<String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    billable: bool,
+}
+
+/// Buckets keyed by group key. Calendar keys sort chronologically as text,
+/// so a `BTreeMap` keeps them in order.
+struct TimeBuckets {
+    buckets: BTreeMap<String, TimeBucket>,
+}
+
+impl TimeBuckets {
+    fn new() -> Self {
+        Self {
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    fn bucket(&mut self, key: &str, label: &str) -> &mut TimeBucket {
+        self.buckets
+            .entry(key.to_string())
+            .or_insert_with(|| TimeBucket {
+                key: key.to_string(),
+                label: label.to_string(),
+                total_seconds: 0,
+                billable_seconds: 0,
+                entry_count: 0,
+            })
+    }
+
+    fn add(&mut self, key: &str, label: &str, seconds: i64, billable: bool, new_entry: bool) {
+        let bucket = self.bucket(key, label);
+        bucket.total_seconds += seconds;
+        if billable {
+            bucket.billable_seconds += seconds;
+        }
+        if new_entry {
+            bucket.entry_count += 1;
+        }
+    }
+}
+
+/// Add `span` to the buckets, splitting it at local midnights for calendar
+/// groupings so an entry that runs past midnight counts towards both days.
+fn add_span(buckets: &mut TimeBuckets, span: &EntrySpan, range: &Range, group_by: GroupBy) {
+    let seconds = (span.end - span.start).num_seconds();
+    match group_by {
+        GroupBy::Project => {
+            let key = span.project.as_deref().unwrap_or("");
+            let label = span.project.as_deref().unwrap_or(NO_PROJECT);
+            buckets.add(key, label, seconds, span.billable, true);
+        }
+        GroupBy::Tag if span.tags.is_empty() => {
+            buckets.add("", UNTAGGED, seconds, span.billable, true);
+        }
+        GroupBy::Tag => {
+            for tag in &span.tags {
+                buckets.add(tag, tag, seconds, span.billable, true);
+            }
+        }
+        GroupBy::Task => {
+            buckets.add(&span.task_id, &span.title, seconds, span.billable, true);
+        }
+        GroupBy::Day | GroupBy::Week | GroupBy::Month => {
+            let mut start = span.start;
+            let mut last_key: Option<String> = None;
+            while start < span.end {
+                let date = range.local_date(start);
+                let boundary = date
+                    .succ_opt()
+                    .and_then(|next| local_midnight(next, &range.tz))
+                    .map_or(span.end, |midnight| midnight.min(span.end));
+                let key = period_key(date, group_by);
+                let new_entry = last_key.as_deref() != Some(key.as_str());
+                buckets.add(
+                    &key,
+                    &key,
+                    (boundary - start).num_seconds(),
+                    span.billable,
+                    new_entry,
+                );
+                last_key = Some(key);
+                start = boundary;
+            }
+        }
+    }
+}
+
+fn split_tags(tags: Option<String>) -> Vec<String> {
+    tags.map(|t| {
+        t.split(TAG_SEPARATOR)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+const TASK_TAGS_SQL: &str = "(SELECT group_concat(g.name, char(31)) FROM task_tags tt \
+     JOIN tags g ON g.id = tt.tag_id WHERE tt.task_id = t.id)";
+
+pub const TAG_FILTER_SQL: &str = "(?4 IS NULL OR EXISTS (SELECT 1 FROM task_tags tt \
+     JOIN tags g ON g.id = tt.tag_id WHERE tt.task_id = t.id AND g.name = ?4))";
+
+/// Aggregate time entries overlapping the range. Rows are folded into
+/// buckets as they're read, so the range can be arbitrarily long.
+pub fn time_report(
+    conn: &Connection,
+    query: &ReportQuery,
+    range: &Range,
+) -> rusqlite::Result<TimeReport> {
+    let now = Utc::now();
+    let from = format_utc(range.from);
+    let to = format_utc(range.to);
+
+    let mut buckets = TimeBuckets::new();
+    if query.group_by.is_calendar() {
+        for key in range.periods(query.group_by) {
+            buckets.bucket(&key, &key);
+        }
+    }
+
+    let (mut total, mut billable, mut entry_count) = (0i64, 0i64, 0i64);
+    let sql = format!(
+        "SELECT e.task_id, t.title, t.project, {TASK_TAGS_SQL},
+                e.started_at, e.ended_at, e.billable
+         FROM time_entries e JOIN tasks t ON t.id = e.task_id
+         WHERE t.deleted_at IS NULL AND e.started_at < ?2 AND (e.ended_at IS NULL OR e.ended_at > ?1)
+           AND (?3 IS NULL OR t.project = ?3)
+           AND {TAG_FILTER_SQL}"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params![from, to, query.project, query.tag])?;
+    while let Some(row) = rows.next()? {
+        let started_at: String = row.get(4)?;
+        let ended_at: Option<String> = row.get(5)?;
+        let Ok(start) = parse_utc(&started_at) else {
+            continue;
+        };
+        let end = match ended_at.as_deref().map(parse_utc) {
+            Some(Ok(end)) => end,
+            Some(Err(_)) => continue,
+            None => now,
+        };
+        let span = EntrySpan {
+            task_id: row.get(0)?,
+            title: row.get(1)?,
+            project: row.get(2)?,
+            tags: split_tags(row.get(3)?),
+            start: start.max(range.from),
+            end: end.min(range.to),
+            billable: row.get(6)?,
+        };
+        if span.end <= span.start {
+            continue;
+        }
+
+        let seconds = (span.end - span.start).num_seconds();
+        total += seconds;
+        if span.billable {
+            billable += seconds;
+        }
+        entry_count += 1;
+        add_span(&mut buckets, &span, range, query.group_by);
+    }
+
+    let mut buckets: Vec<TimeBucket> = buckets.buckets.into_values().collect();
+    if !query.group_by.is_calendar() {
+        buckets.sort_by(|a, b| {
+            b.total_seconds
+                .cmp(&a.total_seconds)
+                .then_with(|| a.label.to_lowercase().cmp(&b.label.to_lowercase()))
+        });
+    }
+    let average_seconds = if buckets.is_empty() {
+        0.0
+    } else {
+        total as f64 / buckets.len() as f64
+    };
+
+    Ok(TimeReport {
+        group_by: query.group_by,
+        from,
+        to,
+        buckets,
+        total_seconds: total,
+        billable_seconds: billable,
+        non_billable_seconds: total - billable,
+        entry_count,
+        average_seconds,
+    })
+}
+
+/// Count tasks completed in the range.
+pub fn completion_report(
+    conn: &Connection,
+    query: &ReportQuery,
+    range: &Range,
+) -> rusqlite::Result<CompletionReport> {
+    let from = format_utc(range.from);
+    let to = format_utc(range.to);
+
+    let mut counts: BTreeMap<String, CountBucket> = BTreeMap::new();
+    let mut bump = |key: &str, label: &str, by: i64| {
+        counts
+            .entry(key.to_string())
+            .or_insert_with(|| CountBucket {
+                key: key.to_string(),
+                label: label.to_string(),
+                count: 0,
+            })
+            .count += by;
+    };
+    if query.group_by.is_calendar() {
+        for key in range.periods(query.group_by) {
+            bump(&key, &key, 0);
+        }
+    }
+
+    let mut total = 0i64;
+    let sql = format!(
+        "SELECT t.completed_at, t.project, {TASK_TAGS_SQL}
+         FROM tasks t
+         WHERE t.status = '{STATUS_DONE}' AND t.deleted_at IS NULL
+           AND t.completed_at >= ?1 AND t.completed_at < ?2
+           AND (?3 IS NULL OR t.project = ?3)
+           AND {TAG_FILTER_SQL}"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params![from, to, query.project, query.tag])?;
+    while let Some(row) = rows.next()? {
+        let completed_at: String = row.get(0)?;
+        let Ok(completed_at) = parse_utc(&completed_at) else {
+            continue;
+        };
+        total += 1;
+        match query.group_by {
+            GroupBy::Project => {
+                let project: Option<String> = row.get(1)?;
+                let key = project.as_deref().unwrap_or("");
+                bump(key, project.as_deref().unwrap_or(NO_PROJECT), 1);
+            }
+            GroupBy::Tag => {
+                let tags = split_tags(row.get(2)?);
+                if tags.is_empty() {
+                    bump("", UNTAGGED, 1);
+                }
+                for tag in &tags {
+                    bump(tag, tag, 1);
+                }
+            }
+            group_by => {
+                let key = period_key(range.local_date(completed_at), group_by);
+                bump(&key, &key, 1);
+            }
+        }
+    }
+
+    let mut buckets: Vec<CountBucket> = counts.into_values().collect();
+    if !query.group_by.is_calendar() {
+        buckets.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.label.to_lowercase().cmp(&b.label.to_lowercase()))
+        });
+    }
+    let average = if buckets.is_empty() {
+        0.0
+    } else {
+        total as f64 / buckets.len() as f64
+    };
+
+    Ok(CompletionReport {
+        group_by: query.group_by,
+        from,
+        to,
+        buckets,
+        total,
+        average,
+    })
+}
+
+/// Time tracked in the range, grouped for charting.
+#[tauri::command]
+pub fn report_time(db: State<'_, Db>, query: ReportQuery) -> CommandResult<TimeReport> {
+    let range = Range::from_query(&query)?;
+    db.with_conn(|conn| time_report(conn, &query, &range))
+}
+
+/// Tasks completed in the range, grouped for charting.
+#[tauri::command]
+pub fn report_completions(
+    db: State<'_, Db>,
+    query: ReportQuery,
+) -> CommandResult<CompletionReport> {
+    if query.group_by == GroupBy::Task {
+        return Err(CommandError::invalid(
+            "Completions can't be grouped by task",
+        ));
+    }
+    let range = Range::from_query(&query)?;
+    db.with_conn(|conn| completion_report(conn, &query, &range))
+}
@@ -0,0 +1,541 @@
+use std::fmt;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+use crate::error::{CommandError, CommandResult};
+
+/// Give up looking for the next occurrence after this many days, so a rule
+/// that can never match (e.g. BYMONTHDAY=31 with BYMONTH=2) terminates.
+const MAX_SCAN_DAYS: u32 = 366 * 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A BYDAY entry: a weekday, optionally with an ordinal within the month or
+/// year (`2TU` = second Tuesday, `-1FR` = last Friday).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByDay {
+    pub nth: Option<i32>,
+    pub weekday: Weekday,
+}
+
+/// Date-based RFC 5545 recurrence rule, in the `DTSTART:YYYYMMDD;FREQ=...`
+/// form the frontend already writes to task frontmatter. EXDATEs and the
+/// non-standard `X-SKIP-WEEKENDS` flag live in the same string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rrule {
+    pub dtstart: NaiveDate,
+    pub freq: Freq,
+    pub interval: u32,
+    pub by_day: Vec<ByDay>,
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDate>,
+    pub exdates: Vec<NaiveDate>,
+    /// Move occurrences that land on a weekend to the following Monday.
+    pub skip_weekends: bool,
+}
+
+fn parse_date(value: &str) -> CommandResult<NaiveDate> {
+    let digits: String = value
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .take(8)
+        .collect();
+    NaiveDate::parse_from_str(&digits, "%Y%m%d")
+        .map_err(|_| CommandError::invalid(format!("Invalid date: {value}")))
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+fn parse_weekday(code: &str) -> CommandResult<Weekday> {
+    match code {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        other => Err(CommandError::invalid(format!("Invalid weekday: {other}"))),
+    }
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_by_day(value: &str) -> CommandResult<ByDay> {
+    if !value.is_ascii() || value.len() < 2 {
+        return Err(CommandError::invalid(format!("Invalid BYDAY: {value}")));
+    }
+    let split = value.len().saturating_sub(2);
+    let (nth, code) = value.split_at(split);
+    let nth = if nth.is_empty() {
+        None
+    } else {
+        let n: i32 = nth
+            .trim_start_matches('+')
+            .parse()
+            .map_err(|_| CommandError::invalid(format!("Invalid BYDAY: {value}")))?;
+        if n == 0 || n.abs() > 53 {
+            return Err(CommandError::invalid(format!("Invalid BYDAY: {value}")));
+        }
+        Some(n)
+    };
+    Ok(ByDay {
+        nth,
+        weekday: parse_weekday(code)?,
+    })
+}
+
+fn parse_list<T>(value: &str, f: impl Fn(&str) -> CommandResult<T>) -> CommandResult<Vec<T>> {
+    value.split(',').map(|v| f(v.trim())).collect()
+}
+
+fn parse_int<T: std::str::FromStr>(key: &str, value: &str) -> CommandResult<T> {
+    value
+        .parse()
+        .map_err(|_| CommandError::invalid(format!("Invalid {key}: {value}")))
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|d| d.pred_opt())
+        .map(|d| d.day())
+        .unwrap_or(31)
+}
+
+fn days_in_year(year: i32) -> u32 {
+    if NaiveDate::from_ymd_opt(year, 2, 29).is_some() {
+        366
+    } else {
+        365
+    }
+}
+
+/// Does `date` match an ordinal weekday counted within a span of `len` days
+/// where `date` is day `pos` (1-based)?
+fn matches_nth(nth: i32, pos: u32, len: u32) -> bool {
+    let from_start = (pos as i32 - 1) / 7 + 1;
+    let from_end = -((len as i32 - pos as i32) / 7 + 1);
+    nth == from_start || nth == from_end
+}
+
+impl Rrule {
+    pub fn parse(input: &str) -> CommandResult<Self> {
+        let mut dtstart = None;
+        let mut freq = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut by_month_day = Vec::new();
+        let mut by_month = Vec::new();
+        let mut count = None;
+        let mut until = None;
+        let mut exdates = Vec::new();
+        let mut skip_weekends = false;
+        // Property whose value follows a parameter, as in `DTSTART;VALUE=DATE:20250101`.
+        let mut pending: Option<String> = None;
+
+        for token in input.split([';', '\n', '\r']) {
+            let token = token.trim();
+            let token = token.strip_prefix("RRULE:").unwrap_or(token);
+            if token.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match token.split_once(':') {
+                Some((param, value)) if param.contains('=') => match pending.take() {
+                    Some(key) => (key, value),
+                    None => continue,
+                },
+                Some((key, value)) => (key.to_ascii_uppercase(), value),
+                None => match token.split_once('=') {
+                    Some((key, value)) => (key.to_ascii_uppercase(), value),
+                    None => {
+                        pending = Some(token.to_ascii_uppercase());
+                        continue;
+                    }
+                },
+            };
+            let value = value.trim();
+
+            match key.as_str() {
+                "DTSTART" => dtstart = Some(parse_date(value)?),
+                "FREQ" => {
+                    freq = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Freq::Daily,
+                        "WEEKLY" => Freq::Weekly,
+                        "MONTHLY" => Freq::Monthly,
+                        "YEARLY" => Freq::Yearly,
+                        other => {
+                            return Err(CommandError::invalid(format!("Unsupported FREQ: {other}")))
+                        }
+                    })
+                }
+                "INTERVAL" => interval = parse_int::<u32>("INTERVAL", value)?.max(1),
+                "COUNT" => count = Some(parse_int("COUNT", value)?),
+                "UNTIL" => until = Some(parse_date(value)?),
+                "BYDAY" => by_day = parse_list(&value.to_ascii_uppercase(), parse_by_day)?,
+                "BYMONTHDAY" => {
+                    by_month_day = parse_list(value, |v| {
+                        let day: i32 = parse_int("BYMONTHDAY", v)?;
+                        if day == 0 || day.abs() > 31 {
+                            return Err(CommandError::invalid(format!("Invalid BYMONTHDAY: {v}")));
+                        }
+                        Ok(day)
+                    })?
+                }
+                "BYMONTH" => {
+                    by_month = parse_list(value, |v| {
+                        let month: u32 = parse_int("BYMONTH", v)?;
+                        if !(1..=12).contains(&month) {
+                            return Err(CommandError::invalid(format!("Invalid BYMONTH: {v}")));
+                        }
+                        Ok(month)
+                    })?
+                }
+                "EXDATE" => exdates.extend(parse_list(value, parse_date)?),
+                "X-SKIP-WEEKENDS" => skip_weekends = value.eq_ignore_ascii_case("TRUE"),
+                // WKST and friends don't change date-level expansion here.
+                _ => {}
+            }
+        }
+
+        let freq = freq.ok_or_else(|| CommandError::invalid("Recurrence rule is missing FREQ"))?;
+        let dtstart =
+            dtstart.ok_or_else(|| CommandError::invalid("Recurrence rule is missing DTSTART"))?;
+        exdates.sort();
+        exdates.dedup();
+
+        Ok(Self {
+            dtstart,
+            freq,
+            interval,
+            by_day,
+            by_month_day,
+            by_month,
+            count,
+            until,
+            exdates,
+            skip_weekends,
+        })
+    }
+
+    /// Is `date` in the period grid (every `interval` days/weeks/months/years
+    /// counted from DTSTART)?
+    fn in_interval(&self, date: NaiveDate) -> bool {
+        let start = self.dtstart;
+        let n = self.interval as i64;
+        match self.freq {
+            Freq::Daily => (date - start).num_days() % n == 0,
+            Freq::Weekly => {
+                let week_start =
+                    |d: NaiveDate| d - Days::new(d.weekday().num_days_from_monday() as u64);
+                ((week_start(date) - week_start(start)).num_days() / 7) % n == 0
+            }
+            Freq::Monthly => {
+                let months = (date.year() - start.year()) as i64 * 12 + date.month() as i64
+                    - start.month() as i64;
+                months % n == 0
+            }
+            Freq::Yearly => ((date.year() - start.year()) as i64) % n == 0,
+        }
+    }
+
+    fn matches_by_day(&self, date: NaiveDate) -> bool {
+        self.by_day.iter().any(|rule| {
+            if rule.weekday != date.weekday() {
+                return false;
+            }
+            let Some(nth) = rule.nth else {
+                return true;
+            };
+            // Ordinals count within the month, except for yearly rules
+            // without BYMONTH where they count within the year.
+            if self.freq == Freq::Yearly && self.by_month.is_empty() {
+                matches_nth(nth, date.ordinal(), days_in_year(date.year()))
+            } else {
+                matches_nth(nth, date.day(), days_in_month(date.year(), date.month()))
+            }
+        })
+    }
+
+    fn matches_month_day(&self, date: NaiveDate) -> bool {
+        let len = days_in_month(date.year(), date.month()) as i32;
+        let day = date.day() as i32;
+        self.by_month_day
+            .iter()
+            .any(|&d| d == day || (d < 0 && len + d + 1 == day))
+    }
+
+    /// Does the unfiltered rule produce `date`? Omitted BYxxx parts default
+    /// to DTSTART's weekday/day/month as RFC 5545 prescribes.
+    fn matches(&self, date: NaiveDate) -> bool {
+        if date < self.dtstart || !self.in_interval(date) {
+            return false;
+        }
+        if !self.by_month.is_empty() && !self.by_month.contains(&date.month()) {
+            return false;
+        }
+        if !self.by_month_day.is_empty() && !self.matches_month_day(date) {
+            return false;
+        }
+        if !self.by_day.is_empty() && !self.matches_by_day(date) {
+            return false;
+        }
+
+        let has_day_rule = !self.by_day.is_empty() || !self.by_month_day.is_empty();
+        match self.freq {
+            Freq::Daily => true,
+            Freq::Weekly => !self.by_day.is_empty() || date.weekday() == self.dtstart.weekday(),
+            Freq::Monthly => has_day_rule || date.day() == self.dtstart.day(),
+            Freq::Yearly => {
+                let month_ok = !self.by_month.is_empty() || date.month() == self.dtstart.month();
+                month_ok && (has_day_rule || date.day() == self.dtstart.day())
+            }
+        }
+    }
+
+    /// Every occurrence in order, after EXDATEs and weekend skipping.
+    pub fn occurrences(&self) -> Occurrences<'_> {
+        Occurrences {
+            rule: self,
+            cursor: Some(self.dtstart),
+            produced: 0,
+            last: None,
+        }
+    }
+
+    /// First occurrence strictly after `date`.
+    pub fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        self.occurrences().find(|d| *d > date)
+    }
+}
+
+pub struct Occurrences<'a> {
+    rule: &'a Rrule,
+    cursor: Option<NaiveDate>,
+    /// Raw occurrences seen so far, for COUNT (which ignores EXDATEs).
+    produced: u32,
+    last: Option<NaiveDate>,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = NaiveDate;
+
+    fn next(&mut self) -> Option<NaiveDate> {
+        let rule = self.rule;
+        let mut scanned = 0;
+        loop {
+            let date = self.cursor?;
+            if rule.until.is_some_and(|until| date > until)
+                || rule.count.is_some_and(|count| self.produced >= count)
+                || scanned > MAX_SCAN_DAYS
+            {
+                self.cursor = None;
+                return None;
+            }
+            self.cursor = date.succ_opt();
+            scanned += 1;
+
+            if !rule.matches(date) {
+                continue;
+            }
+            self.produced += 1;
+            if rule.exdates.binary_search(&date).is_ok() {
+                continue;
+            }
+
+            let date = match date.weekday() {
+                Weekday::Sat if rule.skip_weekends => date + Days::new(2),
+                Weekday::Sun if rule.skip_weekends => date + Days::new(1),
+                _ => date,
+            };
+            if self.last.is_some_and(|last| date <= last) {
+                continue;
+            }
+            self.last = Some(date);
+            return Some(date);
+        }
+    }
+}
+
+impl Rrule {
+    /// The rule as an RFC 5545 `RRULE` value (`FREQ=...;...`), without the
+    /// DTSTART, EXDATEs or DayLight-only extensions.
+    pub fn rrule_value(&self) -> String {
+        let freq = match self.freq {
+            Freq::Daily => "DAILY",
+            Freq::Weekly => "WEEKLY",
+            Freq::Monthly => "MONTHLY",
+            Freq::Yearly => "YEARLY",
+        };
+        let mut out = format!("FREQ={freq}");
+        if self.interval > 1 {
+            out.push_str(&format!(";INTERVAL={}", self.interval));
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|d| match d.nth {
+                    Some(n) => format!("{n}{}", weekday_code(d.weekday)),
+                    None => weekday_code(d.weekday).to_string(),
+                })
+                .collect();
+            out.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(|d| d.to_string()).collect();
+            out.push_str(&format!(";BYMONTHDAY={}", days.join(",")));
+        }
+        if !self.by_month.is_empty() {
+            let months: Vec<String> = self.by_month.iter().map(|m| m.to_string()).collect();
+            out.push_str(&format!(";BYMONTH={}", months.join(",")));
+        }
+        if let Some(count) = self.count {
+            out.push_str(&format!(";COUNT={count}"));
+        }
+        if let Some(until) = self.until {
+            out.push_str(&format!(";UNTIL={}", format_date(until)));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Rrule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DTSTART:{};{}",
+            format_date(self.dtstart),
+            self.rrule_value()
+        )?;
+        if !self.exdates.is_empty() {
+            let dates: Vec<String> = self.exdates.iter().map(|d| format_date(*d)).collect();
+            write!(f, ";EXDATE:{}", dates.join(","))?;
+        }
+        if self.skip_weekends {
+            write!(f, ";X-SKIP-WEEKENDS=TRUE")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn first(rule: &str, n: usize) -> Vec<NaiveDate> {
+        Rrule::parse(rule).unwrap().occurrences().take(n).collect()
+    }
+
+    #[test]
+    fn byday_ordinals_count_within_the_month() {
+        let dates = first("DTSTART:20250101;FREQ=MONTHLY;BYDAY=2TU", 3);
+        assert_eq!(
+            dates,
+            [date(2025, 1, 14), date(2025, 2, 11), date(2025, 3, 11)]
+        );
+
+        let dates = first("DTSTART:20250101;FREQ=MONTHLY;BYDAY=-1FR", 3);
+        assert_eq!(
+            dates,
+            [date(2025, 1, 31), date(2025, 2, 28), date(2025, 3, 28)]
+        );
+    }
+
+    #[test]
+    fn yearly_byday_ordinals_count_within_the_year() {
+        let dates = first("DTSTART:20250101;FREQ=YEARLY;BYDAY=1MO", 2);
+        assert_eq!(dates, [date(2025, 1, 6), date(2026, 1, 5)]);
+
+        let dates = first("DTSTART:20250101;FREQ=YEARLY;BYMONTH=11;BYDAY=4TH", 2);
+        assert_eq!(dates, [date(2025, 11, 27), date(2026, 11, 26)]);
+    }
+
+    #[test]
+    fn count_includes_excluded_dates() {
+        let dates = first(
+            "DTSTART:20250106;FREQ=DAILY;COUNT=4;EXDATE:20250107,20250108",
+            10,
+        );
+        assert_eq!(dates, [date(2025, 1, 6), date(2025, 1, 9)]);
+    }
+
+    #[test]
+    fn until_is_inclusive() {
+        let dates = first("DTSTART:20250106;FREQ=WEEKLY;UNTIL=20250120", 10);
+        assert_eq!(
+            dates,
+            [date(2025, 1, 6), date(2025, 1, 13), date(2025, 1, 20)]
+        );
+    }
+
+    #[test]
+    fn skip_weekends_moves_to_monday_once() {
+        // Sat 4th, Sun 5th and Mon 6th all land on Monday the 6th.
+        let dates = first("DTSTART:20250103;FREQ=DAILY;X-SKIP-WEEKENDS=TRUE", 3);
+        assert_eq!(
+            dates,
+            [date(2025, 1, 3), date(2025, 1, 6), date(2025, 1, 7)]
+        );
+
+        let dates = first(
+            "DTSTART:20250101;FREQ=MONTHLY;BYMONTHDAY=1;X-SKIP-WEEKENDS=TRUE",
+            6,
+        );
+        assert_eq!(dates[5], date(2025, 6, 2));
+    }
+
+    #[test]
+    fn impossible_rules_end() {
+        let rule = Rrule::parse("DTSTART:20250101;FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=31").unwrap();
+        assert_eq!(rule.occurrences().next(), None);
+    }
+
+    #[test]
+    fn round_trips() {
+        let text = "DTSTART:20250101;FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR;COUNT=5;\
+                    EXDATE:20250131;X-SKIP-WEEKENDS=TRUE";
+        let rule = Rrule::parse(text).unwrap();
+        assert_eq!(rule.to_string(), text);
+        assert_eq!(Rrule::parse(&rule.to_string()).unwrap(), rule);
+    }
+
+    #[test]
+    fn reads_parameters_and_rrule_prefixes() {
+        let rule =
+            Rrule::parse("DTSTART;VALUE=DATE:20250101\nRRULE:FREQ=WEEKLY;BYDAY=MO,WE").unwrap();
+        assert_eq!(rule.dtstart, date(2025, 1, 1));
+        assert_eq!(rule.by_day.len(), 2);
+        assert!(Rrule::parse("DTSTART:20250101").is_err());
+        assert!(Rrule::parse("FREQ=DAILY;BYMONTHDAY=32;DTSTART:20250101").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::calendars;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::dependencies::BLOCKED_SQL;
+use crate::error::{CommandError, CommandResult};
+use crate::ics;
+use crate::task_store::{self, STATUS_OPEN};
+use crate::timezone;
+
+const BLOCK_COLUMNS: &str =
+    "id, task_id, day, starts_at, duration_minutes, tz, created_at, updated_at";
+const MAX_BLOCK_MINUTES: i64 = 24 * 60;
+
+/// A stretch of a day set aside for a task.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeBlock {
+    pub id: String,
+    pub task_id: String,
+    /// The date the block starts on, in `tz` (YYYY-MM-DD).
+    pub day: String,
+    pub starts_at: String,
+    pub duration_minutes: i64,
+    pub tz: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Ids of other blocks on the same day that it overlaps.
+    pub overlaps: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTimeBlock {
+    pub task_id: String,
+    pub starts_at: String,
+    pub duration_minutes: i64,
+}
+
+/// Dragging a block moves its start; dragging an edge changes its length.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TimeBlockPatch {
+    pub starts_at: Option<String>,
+    pub duration_minutes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PlanOptions {
+    /// Working hours, as local `HH:MM`.
+    pub day_start: String,
+    pub day_end: String,
+    /// Time given to each task without an estimate.
+    pub block_minutes: i64,
+    /// Breathing room left after each block.
+    pub gap_minutes: i64,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            day_start: "09:00".to_string(),
+            day_end: "17:00".to_string(),
+            block_minutes: 30,
+            gap_minutes: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProposedBlock {
+    pub task_id: String,
+    pub title: String,
+    pub starts_at: String,
+    pub duration_minutes: i64,
+}
+
+/// A suggested plan; nothing is saved until blocks are created from it.
+#[derive(Debug, Clone, Serialize)]
+pub struct DayPlanProposal {
+    pub day: String,
+    pub blocks: Vec<ProposedBlock>,
+    /// Tasks that didn't fit in the free time.
+    pub unplaced: Vec<String>,
+}
+
+type Span = (DateTime<Utc>, DateTime<Utc>);
+
+fn row_to_block(row: &Row) -> rusqlite::Result<TimeBlock> {
+    Ok(TimeBlock {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        day: row.get(2)?,
+        starts_at: row.get(3)?,
+        duration_minutes: row.get(4)?,
+        tz: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        overlaps: Vec::new(),
+    })
+}
+
+fn span(block: &TimeBlock) -> Option<Span> {
+    let start = parse_utc(&block.starts_at).ok()?;
+    Some((start, start + Duration::minutes(block.duration_minutes)))
+}
+
+fn parse_day(day: &str) -> CommandResult<NaiveDate> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| CommandError::invalid(format!("Invalid date: {day}")))
+}
+
+fn local_day(instant: DateTime<Utc>, tz: &Tz) -> String {
+    instant.with_timezone(tz).date_naive().to_string()
+}
+
+fn validate_duration(minutes: i64) -> CommandResult<()> {
+    if minutes <= 0 || minutes > MAX_BLOCK_MINUTES {
+        return Err(CommandError::invalid(format!(
+            "Invalid block length: {minutes} minutes"
+        )));
+    }
+    Ok(())
+}
+
+fn find_block(conn: &Connection, id: &str) -> rusqlite::Result<Option<TimeBlock>> {
+    conn.query_row(
+        &format!("SELECT {BLOCK_COLUMNS} FROM time_blocks WHERE id = ?1"),
+        params![id],
+        row_to_block,
+    )
+    .optional()
+}
+
+fn write_block(conn: &Connection, block: &TimeBlock) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO time_blocks ({BLOCK_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(id) DO UPDATE SET
+                 task_id = excluded.task_id,
+                 day = excluded.day,
+                 starts_at = excluded.starts_at,
+                 duration_minutes = excluded.duration_minutes,
+                 tz = excluded.tz,
+                 updated_at = excluded.updated_at"
+        ),
+        params![
+            block.id,
+            block.task_id,
+            block.day,
+            block.starts_at,
+            block.duration_minutes,
+            block.tz,
+            block.created_at,
+            block.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// A day's blocks in time order, each with the blocks it overlaps. Blocks
+/// for tasks in the trash are left out.
+pub fn blocks_on(conn: &Connection, day: &str) -> rusqlite::Result<Vec<TimeBlock>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {BLOCK_COLUMNS} FROM time_blocks
+         WHERE day = ?1 AND task_id IN (SELECT id FROM tasks WHERE deleted_at IS NULL)
+         ORDER BY starts_at, id"
+    ))?;
+    let mut blocks = stmt
+        .query_map(params![day], row_to_block)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let spans: Vec<(String, Option<Span>)> =
+        blocks.iter().map(|b| (b.id.clone(), span(b))).collect();
+    for block in &mut blocks {
+        let Some((start, end)) = span(block) else {
+            continue;
+        };
+        block.overlaps = spans
+            .iter()
+            .filter(|(id, other)| {
+                *id != block.id && other.is_some_and(|(s, e)| s < end && start < e)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+    }
+    Ok(blocks)
+}
+
+/// Time already spoken for on `day`: planned blocks, and tasks scheduled at
+/// a time of day, which are fixed appointments.
+fn busy_spans(conn: &Connection, day: &str) -> rusqlite::Result<Vec<Span>> {
+    let mut busy: Vec<Span> = blocks_on(conn, day)?.iter().filter_map(span).collect();
+    let mut stmt = conn.prepare(
+        "SELECT scheduled, tz FROM tasks
+         WHERE deleted_at IS NULL AND status = ?1
+           AND length(scheduled) > 10 AND substr(scheduled, 1, 10) = ?2",
+    )?;
+    let rows = stmt.query_map(params![STATUS_OPEN, day], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+    })?;
+    for row in rows {
+        let (scheduled, tz) = row?;
+        if let Some(start) = ics::local_instant(&scheduled, tz.as_deref()) {
+            busy.push((start, start + Duration::minutes(ics::DEFAULT_BLOCK_MINUTES)));
+        }
+    }
+    busy.sort();
+    Ok(busy)
+}
+
+/// Open, unblocked tasks that belong on `day` and have no block there yet:
+/// scheduled for it or earlier without a time, or due by then. Overdue
+/// and higher-priority tasks come first.
+fn plan_candidates(
+    conn: &Connection,
+    day: &str,
+) -> rusqlite::Result<Vec<(String, String, Option<i64>)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, title, estimate_minutes FROM tasks
+         WHERE deleted_at IS NULL AND status = ?1 AND NOT {BLOCKED_SQL}
+           AND (scheduled IS NULL OR length(scheduled) = 10)
+           AND (scheduled <= ?2 OR substr(due, 1, 10) <= ?2)
+           AND id NOT IN (SELECT task_id FROM time_blocks WHERE day = ?2)
+         ORDER BY due IS NULL, due, priority IS NULL, priority DESC, created_at, id"
+    ))?;
+    let rows = stmt.query_map(params![STATUS_OPEN, day], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })?;
+    rows.collect()
+}
+
+/// `window` minus the busy spans, in time order.
+fn free_spans(window: Span, busy: &[Span]) -> Vec<Span> {
+    let mut free = Vec::new();
+    let mut cursor = window.0;
+    for &(start, end) in busy {
+        if end <= cursor || start >= window.1 {
+            continue;
+        }
+        if start > cursor {
+            free.push((cursor, start));
+        }
+        cursor = cursor.max(end);
+    }
+    if cursor < window.1 {
+        free.push((cursor, window.1));
+    }
+    free
+}
+
+/// Pack the day's tasks, in order, into the free time within working hours,
+/// around meetings in the user's calendars. Today's plan starts from now
+/// rather than the start of the day.
+pub fn propose(
+    conn: &Connection,
+    day: NaiveDate,
+    options: &PlanOptions,
+    now: DateTime<Utc>,
+) -> CommandResult<DayPlanProposal> {
+    validate_duration(options.block_minutes)?;
+    let tz = timezone::parse_zone(&timezone::system_zone())?;
+    let at = |time: &str| -> CommandResult<DateTime<Utc>> {
+        let time = NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| CommandError::invalid(format!("Invalid time of day: {time}")))?;
+        timezone::resolve_local(day.and_time(time), &tz)
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok_or_else(|| CommandError::invalid(format!("Invalid time of day: {time}")))
+    };
+    let (start, end) = (at(&options.day_start)?, at(&options.day_end)?);
+    // Round now up to the next five minutes so blocks start on the clock.
+    let now = now + Duration::seconds(299 - (now.timestamp() + 299) % 300);
+
+    let day_str = day.to_string();
+    let db_err = |e: rusqlite::Error| e.to_string();
+    let mut busy = busy_spans(conn, &day_str).map_err(db_err)?;
+    busy.extend(calendars::busy_between(conn, start, end).map_err(db_err)?);
+    busy.sort();
+    let mut free = free_spans((start.max(now), end), &busy);
+    let gap = Duration::minutes(options.gap_minutes.max(0));
+
+    let mut blocks = Vec::new();
+    let mut unplaced = Vec::new();
+    for (task_id, title, estimate) in plan_candidates(conn, &day_str).map_err(db_err)? {
+        let minutes = estimate
+            .unwrap_or(options.block_minutes)
+            .min(MAX_BLOCK_MINUTES);
+        let length = Duration::minutes(minutes);
+        let Some(slot) = free.iter_mut().find(|(s, e)| *e - *s >= length) else {
+            unplaced.push(task_id);
+            continue;
+        };
+        blocks.push(ProposedBlock {
+            task_id,
+            title,
+            starts_at: format_utc(slot.0),
+            duration_minutes: minutes,
+        });
+        slot.0 = (slot.0 + length + gap).min(slot.1);
+    }
+    Ok(DayPlanProposal {
+        day: day_str,
+        blocks,
+        unplaced,
+    })
+}
+
+#[tauri::command]
+pub fn list_time_blocks(db: State<'_, Db>, day: String) -> CommandResult<Vec<TimeBlock>> {
+    parse_day(&day)?;
+    db.with_conn(|conn| blocks_on(conn, &day))
+}
+
+#[tauri::command]
+pub fn create_time_block(db: State<'_, Db>, input: NewTimeBlock) -> CommandResult<TimeBlock> {
+    validate_duration(input.duration_minutes)?;
+    let starts_at = parse_utc(&input.starts_at)?;
+    let zone = timezone::system_zone();
+    let tz = timezone::parse_zone(&zone)?;
+    let now = now_utc();
+    let block = TimeBlock {
+        id: uuid::Uuid::new_v4().to_string(),
+        task_id: input.task_id,
+        day: local_day(starts_at, &tz),
+        starts_at: format_utc(starts_at),
+        duration_minutes: input.duration_minutes,
+        tz: zone,
+        created_at: now.clone(),
+        updated_at: now,
+        overlaps: Vec::new(),
+    };
+    db.with_conn(|conn| {
+        if task_store::find_task(conn, &block.task_id)?.is_none() {
+            return Ok(Err(CommandError::not_found(format!(
+                "Task not found: {}",
+                block.task_id
+            ))));
+        }
+        write_block(conn, &block)?;
+        let placed = blocks_on(conn, &block.day)?
+            .into_iter()
+            .find(|b| b.id == block.id);
+        Ok(Ok(placed.unwrap_or(block)))
+    })?
+}
+
+/// Move or resize a block. Moving it past midnight files it under the new
+/// day.
+#[tauri::command]
+pub fn reschedule_time_block(
+    db: State<'_, Db>,
+    id: String,
+    patch: TimeBlockPatch,
+) -> CommandResult<TimeBlock> {
+    if let Some(minutes) = patch.duration_minutes {
+        validate_duration(minutes)?;
+    }
+    let starts_at = patch.starts_at.as_deref().map(parse_utc).transpose()?;
+    db.with_conn(|conn| {
+        let Some(mut block) = find_block(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Time block not found: {id}"
+            ))));
+        };
+        if let Some(starts_at) = starts_at {
+            let tz = match timezone::parse_zone(&block.tz) {
+                Ok(tz) => tz,
+                Err(e) => return Ok(Err(e)),
+            };
+            block.day = local_day(starts_at, &tz);
+            block.starts_at = format_utc(starts_at);
+        }
+        if let Some(minutes) = patch.duration_minutes {
+            block.duration_minutes = minutes;
+        }
+        block.updated_at = now_utc();
+        write_block(conn, &block)?;
+        let placed = blocks_on(conn, &block.day)?
+            .into_iter()
+            .find(|b| b.id == block.id);
+        Ok(Ok(placed.unwrap_or(block)))
+    })?
+}
+
+#[tauri::command]
+pub fn delete_time_block(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM time_blocks WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Time block not found: {id}"
+        )));
+    }
+    Ok(())
+}
+
+/// Suggest blocks for `day` (default today) by packing its tasks into the
+/// free time between the working hours in `options`.
+#[tauri::command]
+pub fn propose_day_plan(
+    db: State<'_, Db>,
+    day: Option<String>,
+    options: Option<PlanOptions>,
+) -> CommandResult<DayPlanProposal> {
+    let day = match day {
+        Some(day) => parse_day(&day)?,
+        None => crate::recurrence::today(),
+    };
+    let options = options.unwrap_or_default();
+    db.with_conn(|conn| Ok(propose(conn, day, &options, Utc::now())))?
+}
+
+/// Mark the blocks on `day` (default today) as the plan for it, once
+/// they're arranged, and announce the plan to webhooks. Returns the blocks.
+#[tauri::command]
+pub fn finalize_day_plan(
+    app: AppHandle,
+    db: State<'_, Db>,
+    day: Option<String>,
+) -> CommandResult<Vec<TimeBlock>> {
+    let day = match day {
+        Some(day) => parse_day(&day)?,
+        None => crate::recurrence::today(),
+    }
+    .format("%Y-%m-%d")
+    .to_string();
+    let blocks = db.with_conn(|conn| blocks_on(conn, &day))?;
+    if blocks.is_empty() {
+        return Err(CommandError::not_found(format!(
+            "Nothing is planned for {day}"
+        )));
+    }
+    crate::webhooks::day_plan_finalized(&app, &day, &blocks);
+    Ok(blocks)
+}
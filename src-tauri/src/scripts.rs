@@ -0,0 +1,647 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDate};
+use rhai::module_resolvers::DummyModuleResolver;
+use reqwest::redirect;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::events::EntryChanged;
+use crate::recurrence;
+use crate::task_store::{self, NewTask};
+use crate::time_entries;
+use crate::usage;
+
+pub const TASK_COMPLETED: &str = "task.completed";
+pub const TIMER_STOPPED: &str = "timer.stopped";
+/// Sent when the local date changes, and at launch if it hadn't been sent
+/// yet today; each script runs once a day.
+pub const DAY_STARTED: &str = "day.started";
+const EVENTS: &[&str] = &[TASK_COMPLETED, TIMER_STOPPED, DAY_STARTED];
+
+/// Sent to the frontend by a script's `notify`.
+pub const SCRIPT_NOTIFICATION_EVENT: &str = "script-notification";
+
+/// Limits on one run, so a runaway script can't hang or flood the app.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_LEN: usize = 1 << 20;
+const MAX_COLLECTION_LEN: usize = 10_000;
+const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_TASKS_PER_RUN: usize = 50;
+const MAX_NOTIFICATIONS_PER_RUN: usize = 5;
+const MAX_REQUESTS_PER_RUN: usize = 10;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+/// Printed lines kept from a run.
+const MAX_OUTPUT_LINES: usize = 100;
+/// Where the last error is cut, so one odd message can't bloat the row.
+const MAX_ERROR_LEN: usize = 500;
+
+/// Runs one at a time, in the order their events happened.
+static RUNNING: Mutex<()> = Mutex::new(());
+
+type RhaiResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Script {
+    pub id: String,
+    pub name: String,
+    /// One of `EVENTS`.
+    pub event: String,
+    /// Rhai source. The event is in the `event` constant.
+    pub source: String,
+    /// Hosts `http_get` and `http_post` may reach; nothing else is.
+    pub allowed_hosts: Vec<String>,
+    pub enabled: bool,
+    pub last_run_at: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewScript {
+    pub name: String,
+    pub event: String,
+    pub source: String,
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ScriptPatch {
+    pub name: Option<String>,
+    pub event: Option<String>,
+    pub source: Option<String>,
+    pub allowed_hosts: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRun {
+    /// What it printed, one entry per `print` or `debug`.
+    pub output: Vec<String>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptNotification {
+    pub script: String,
+    pub title: String,
+    pub body: Option<String>,
+}
+
+/// What a run has used of its limits, and printed.
+#[derive(Default)]
+struct RunState {
+    tasks: usize,
+    notifications: usize,
+    requests: usize,
+    output: Vec<String>,
+}
+
+const SCRIPT_COLUMNS: &str = "id, name, event, source, allowed_hosts, enabled, last_run_at,
+     last_error, created_at, updated_at";
+
+fn row_to_script(row: &Row) -> rusqlite::Result<Script> {
+    let allowed_hosts: String = row.get(4)?;
+    Ok(Script {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        event: row.get(2)?,
+        source: row.get(3)?,
+        allowed_hosts: serde_json::from_str(&allowed_hosts).unwrap_or_default(),
+        enabled: row.get(5)?,
+        last_run_at: row.get(6)?,
+        last_error: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+    })
+}
+
+fn list(conn: &Connection) -> rusqlite::Result<Vec<Script>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SCRIPT_COLUMNS} FROM scripts ORDER BY created_at, id"
+    ))?;
+    let rows = stmt.query_map([], row_to_script)?;
+    rows.collect()
+}
+
+fn find(conn: &Connection, id: &str) -> rusqlite::Result<Option<Script>> {
+    conn.query_row(
+        &format!("SELECT {SCRIPT_COLUMNS} FROM scripts WHERE id = ?1"),
+        params![id],
+        row_to_script,
+    )
+    .optional()
+}
+
+fn write(conn: &Connection, script: &Script) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO scripts
+             (id, name, event, source, allowed_hosts, enabled, last_run_at, last_error,
+              created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            script.id,
+            script.name,
+            script.event,
+            script.source,
+            serde_json::to_string(&script.allowed_hosts).unwrap_or_default(),
+            script.enabled,
+            script.last_run_at,
+            script.last_error,
+            script.created_at,
+            script.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn validate_name(name: &str) -> CommandResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::invalid("Script name cannot be empty"));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_event(event: &str) -> CommandResult<String> {
+    if EVENTS.contains(&event) {
+        Ok(event.to_string())
+    } else {
+        Err(CommandError::invalid(format!(
+            "Unknown script event: {event} (expected one of {})",
+            EVENTS.join(", ")
+        )))
+    }
+}
+
+/// Check that `source` compiles, so mistakes show up when saving rather
+/// than on the next event.
+fn validate_source(source: &str) -> CommandResult<String> {
+    engine()
+        .compile(source)
+        .map_err(|e| format!("Script doesn't compile: {e}"))?;
+    Ok(source.to_string())
+}
+
+/// Bare host names, lowercased, once each.
+fn validate_hosts(hosts: &[String]) -> CommandResult<Vec<String>> {
+    let mut valid: Vec<String> = Vec::new();
+    for host in hosts {
+        let host = host.trim().to_ascii_lowercase();
+        if host.is_empty() || host.contains(['/', ':', '@']) || url::Host::parse(&host).is_err() {
+            return Err(CommandError::invalid(format!("Not a host name: {host}")));
+        }
+        if !valid.contains(&host) {
+            valid.push(host);
+        }
+    }
+    Ok(valid)
+}
+
+/// An engine with the script limits but none of the app functions, and no
+/// way to load files or evaluate strings.
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_string_size(MAX_STRING_LEN);
+    engine.set_max_array_size(MAX_COLLECTION_LEN);
+    engine.set_max_map_size(MAX_COLLECTION_LEN);
+    engine
+}
+
+/// Count one use of a limited function, failing the run once it's spent.
+fn take(
+    state: &Mutex<RunState>,
+    counter: fn(&mut RunState) -> &mut usize,
+    limit: usize,
+    what: &str,
+) -> RhaiResult<()> {
+    let mut state = state.lock().map_err(|_| "Lock poisoned")?;
+    let used = counter(&mut state);
+    if *used >= limit {
+        return Err(format!("A script can {what} at most {limit} times a run").into());
+    }
+    *used += 1;
+    Ok(())
+}
+
+fn log_line(state: &Mutex<RunState>, script: &str, line: String) {
+    tracing::info!("script {script}: {line}");
+    if let Ok(mut state) = state.lock() {
+        if state.output.len() < MAX_OUTPUT_LINES {
+            state.output.push(line);
+        }
+    }
+}
+
+fn create_task(app: &AppHandle, state: &Mutex<RunState>, input: NewTask) -> RhaiResult<Dynamic> {
+    take(state, |s| &mut s.tasks, MAX_TASKS_PER_RUN, "create tasks")?;
+    let db = app.state::<Db>().unjournaled();
+    let task = task_store::create(&db, input).map_err(|e| e.message)?;
+    rhai::serde::to_dynamic(&task)
+}
+
+fn notify(
+    app: &AppHandle,
+    state: &Mutex<RunState>,
+    script: &str,
+    title: &str,
+    body: Option<&str>,
+) -> RhaiResult<()> {
+    take(
+        state,
+        |s| &mut s.notifications,
+        MAX_NOTIFICATIONS_PER_RUN,
+        "notify",
+    )?;
+    let _ = app.emit(
+        SCRIPT_NOTIFICATION_EVENT,
+        ScriptNotification {
+            script: script.to_string(),
+            title: title.to_string(),
+            body: body.map(str::to_string),
+        },
+    );
+    Ok(())
+}
+
+/// Send a request to one of `allowed_hosts` and return the body of a 2xx
+/// response. A body that isn't a string is sent as JSON.
+fn request(
+    state: &Mutex<RunState>,
+    allowed_hosts: &[String],
+    url: &str,
+    body: Option<Dynamic>,
+) -> RhaiResult<String> {
+    take(
+        state,
+        |s| &mut s.requests,
+        MAX_REQUESTS_PER_RUN,
+        "make requests",
+    )?;
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid URL {url}: {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Not an http(s) URL: {url}").into());
+    }
+    let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+    if !allowed_hosts.contains(&host) {
+        return Err(format!("{host} isn't one of this script's allowed hosts").into());
+    }
+
+    // Every redirect hop has to stay within the allowlist too.
+    let hosts = allowed_hosts.to_vec();
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(redirect::Policy::custom(move |attempt| {
+            let url = attempt.url();
+            let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("Too many redirects")
+            } else if !matches!(url.scheme(), "http" | "https") || !hosts.contains(&host) {
+                attempt.error(format!("Redirected to {host}, which isn't an allowed host"))
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let request = match body {
+        None => client.get(parsed),
+        Some(body) if body.is_string() => client
+            .post(parsed)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body.into_string()?),
+        Some(body) => client
+            .post(parsed)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&body).map_err(|e| e.to_string())?),
+    };
+    let request = request.header("User-Agent", "DayLight-Scripts");
+    tauri::async_runtime::block_on(async move {
+        let mut response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("HTTP {}", status.as_u16()));
+        }
+        // Counted as it arrives, so an endless body is cut off early.
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            if body.len() + chunk.len() > MAX_STRING_LEN {
+                return Err("Response is too large".to_string());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    })
+    .map_err(Into::into)
+}
+
+/// The engine for one run of `script`, with the app functions it may call:
+///
+/// - `create_task(title)` or `create_task(#{ title, due, project, tags, ... })`
+/// - `notify(title)` or `notify(title, body)`
+/// - `http_get(url)` and `http_post(url, body)`, to `allowed_hosts` only
+/// - `parse_json(text)`
+fn run_engine(
+    app: &AppHandle,
+    script: &Script,
+    state: &Arc<Mutex<RunState>>,
+    deadline: Instant,
+) -> Engine {
+    let mut engine = engine();
+    engine.on_progress(move |_| {
+        (Instant::now() > deadline).then(|| Dynamic::from("Script ran too long"))
+    });
+
+    let (name, st) = (script.name.clone(), state.clone());
+    engine.on_print(move |text| log_line(&st, &name, text.to_string()));
+    let (name, st) = (script.name.clone(), state.clone());
+    engine.on_debug(move |text, _, pos| log_line(&st, &name, format!("{pos:?}: {text}")));
+
+    let (handle, st) = (app.clone(), state.clone());
+    engine.register_fn("create_task", move |title: &str| {
+        let input = serde_json::from_value(json!({ "title": title })).map_err(|e| e.to_string())?;
+        create_task(&handle, &st, input)
+    });
+    let (handle, st) = (app.clone(), state.clone());
+    engine.register_fn("create_task", move |fields: rhai::Map| {
+        let input = rhai::serde::from_dynamic(&Dynamic::from_map(fields))?;
+        create_task(&handle, &st, input)
+    });
+
+    let (handle, st, name) = (app.clone(), state.clone(), script.name.clone());
+    engine.register_fn("notify", move |title: &str| {
+        notify(&handle, &st, &name, title, None)
+    });
+    let (handle, st, name) = (app.clone(), state.clone(), script.name.clone());
+    engine.register_fn("notify", move |title: &str, body: &str| {
+        notify(&handle, &st, &name, title, Some(body))
+    });
+
+    let (st, hosts) = (state.clone(), script.allowed_hosts.clone());
+    engine.register_fn("http_get", move |url: &str| request(&st, &hosts, url, None));
+    let (st, hosts) = (state.clone(), script.allowed_hosts.clone());
+    engine.register_fn("http_post", move |url: &str, body: Dynamic| {
+        request(&st, &hosts, url, Some(body))
+    });
+
+    engine.register_fn("parse_json", |text: &str| -> RhaiResult<Dynamic> {
+        let value: Json = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {e}"))?;
+        rhai::serde::to_dynamic(&value)
+    });
+    engine
+}
+
+/// Run `script` with `event` as its `event` constant.
+fn run(app: &AppHandle, script: &Script, event: &Json) -> ScriptRun {
+    let started = Instant::now();
+    let state = Arc::new(Mutex::new(RunState::default()));
+    let engine = run_engine(app, script, &state, started + RUN_TIMEOUT);
+    let result = rhai::serde::to_dynamic(event).and_then(|event| {
+        let mut scope = Scope::new();
+        scope.push_constant("event", event);
+        engine.run_with_scope(&mut scope, &script.source)
+    });
+    let output = state
+        .lock()
+        .map(|mut state| std::mem::take(&mut state.output))
+        .unwrap_or_default();
+    ScriptRun {
+        output,
+        error: result.err().map(|e| e.to_string()),
+        duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+    }
+}
+
+/// Run `script` for an event and keep the outcome on it.
+fn run_and_record(app: &AppHandle, script: &Script, event: &Json) {
+    let outcome = run(app, script, event);
+    if let Some(e) = &outcome.error {
+        tracing::warn!("script {} failed: {e}", script.name);
+    }
+    let db = app.state::<Db>();
+    usage::record(
+        &db,
+        "script",
+        Some(Duration::from_millis(outcome.duration_ms)),
+    );
+    let error = outcome
+        .error
+        .map(|e| e.chars().take(MAX_ERROR_LEN).collect::<String>());
+    let recorded = db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE scripts SET last_run_at = ?2, last_error = ?3 WHERE id = ?1",
+            params![script.id, now_utc(), error],
+        )
+    });
+    if let Err(e) = recorded {
+        tracing::warn!("{e}");
+    }
+}
+
+fn ran_on(script: &Script, day: NaiveDate) -> bool {
+    script
+        .last_run_at
+        .as_deref()
+        .and_then(|at| parse_utc(at).ok())
+        .is_some_and(|at| at.with_timezone(&Local).date_naive() == day)
+}
+
+/// Run each enabled script for `event` once per item of `data`, built
+/// with the connection, on a thread of their own. Does nothing without a
+/// script for the event.
+fn dispatch(
+    app: &AppHandle,
+    event: &str,
+    data: impl FnOnce(&Connection) -> rusqlite::Result<Vec<Json>>,
+) {
+    let db = app.state::<Db>();
+    let today = recurrence::today();
+    let loaded = db.with_conn(|conn| {
+        let scripts: Vec<Script> = list(conn)?
+            .into_iter()
+            .filter(|s| s.enabled && s.event == event)
+            .filter(|s| event != DAY_STARTED || !ran_on(s, today))
+            .collect();
+        if scripts.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((scripts, data(conn)?)))
+    });
+    let (scripts, items) = match loaded {
+        Ok(Some(loaded)) => loaded,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("failed to load scripts for {event}: {e}");
+            return;
+        }
+    };
+
+    let handle = app.clone();
+    let event = event.to_string();
+    std::thread::spawn(move || {
+        let _running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        for mut item in items {
+            item["name"] = Json::from(event.as_str());
+            for script in &scripts {
+                run_and_record(&handle, script, &item);
+            }
+        }
+    });
+}
+
+/// Tasks just marked done.
+pub fn tasks_completed(app: &AppHandle, ids: &[String]) {
+    if ids.is_empty() {
+        return;
+    }
+    dispatch(app, TASK_COMPLETED, |conn| {
+        let mut items = Vec::new();
+        for id in ids {
+            if let Some(task) = task_store::find_task(conn, id)? {
+                items.push(json!({ "task": task }));
+            }
+        }
+        Ok(items)
+    });
+}
+
+/// Time entries just stopped, with their task.
+pub fn timers_stopped(app: &AppHandle, entries: &[EntryChanged]) {
+    if entries.is_empty() {
+        return;
+    }
+    dispatch(app, TIMER_STOPPED, |conn| {
+        let mut items = Vec::new();
+        for changed in entries {
+            let Some(entry) = time_entries::find_entry(conn, &changed.id)? else {
+                continue;
+            };
+            let task = task_store::find_task(conn, &entry.task_id)?;
+            items.push(json!({ "entry": entry, "task": task }));
+        }
+        Ok(items)
+    });
+}
+
+/// The local date is now `day`.
+pub fn day_started(app: &AppHandle, day: NaiveDate) {
+    dispatch(app, DAY_STARTED, |_| {
+        Ok(vec![json!({ "day": day.to_string() })])
+    });
+}
+
+#[tauri::command]
+pub fn list_scripts(db: State<'_, Db>) -> CommandResult<Vec<Script>> {
+    db.with_conn(|conn| list(conn))
+}
+
+#[tauri::command]
+pub fn create_script(db: State<'_, Db>, input: NewScript) -> CommandResult<Script> {
+    let now = now_utc();
+    let script = Script {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: validate_name(&input.name)?,
+        event: validate_event(&input.event)?,
+        source: validate_source(&input.source)?,
+        allowed_hosts: validate_hosts(&input.allowed_hosts)?,
+        enabled: true,
+        last_run_at: None,
+        last_error: None,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    db.with_conn(|conn| write(conn, &script))?;
+    Ok(script)
+}
+
+#[tauri::command]
+pub fn update_script(db: State<'_, Db>, id: String, patch: ScriptPatch) -> CommandResult<Script> {
+    let name = patch.name.as_deref().map(validate_name).transpose()?;
+    let event = patch.event.as_deref().map(validate_event).transpose()?;
+    let source = patch.source.as_deref().map(validate_source).transpose()?;
+    let allowed_hosts = patch
+        .allowed_hosts
+        .as_deref()
+        .map(validate_hosts)
+        .transpose()?;
+    db.with_conn(|conn| {
+        let Some(mut script) = find(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Script not found: {id}"
+            ))));
+        };
+        if let Some(name) = name {
+            script.name = name;
+        }
+        if let Some(event) = event {
+            script.event = event;
+        }
+        if let Some(source) = source {
+            script.source = source;
+            script.last_error = None;
+        }
+        if let Some(allowed_hosts) = allowed_hosts {
+            script.allowed_hosts = allowed_hosts;
+        }
+        if let Some(enabled) = patch.enabled {
+            script.enabled = enabled;
+        }
+        script.updated_at = now_utc();
+        write(conn, &script)?;
+        Ok(Ok(script))
+    })?
+}
+
+#[tauri::command]
+pub fn delete_script(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM scripts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!("Script not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Run a script now, even a disabled one, with `event` as its event (an
+/// empty one by default), and return what it printed. What it does is done
+/// for real, but the run isn't kept on the script.
+#[tauri::command]
+pub async fn run_script(
+    app: AppHandle,
+    db: State<'_, Db>,
+    id: String,
+    event: Option<Json>,
+) -> CommandResult<ScriptRun> {
+    let script = db
+        .with_conn(|conn| find(conn, &id))?
+        .ok_or_else(|| CommandError::not_found(format!("Script not found: {id}")))?;
+    let mut event = match event {
+        Some(event @ Json::Object(_)) => event,
+        Some(_) => return Err(CommandError::invalid("The event must be an object")),
+        None => json!({}),
+    };
+    event["name"] = Json::from(script.event.as_str());
+    let outcome = tauri::async_runtime::spawn_blocking(move || {
+        let _running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        run(&app, &script, &event)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(outcome)
+}
@@ -0,0 +1,156 @@
+use std::time::Instant;
+
+use rusqlite::types::ToSql;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult};
+use crate::usage;
+
+/// Index row kinds. Tasks index their title, description and tags; time
+/// entries index their note and resolve to the task they were logged against;
+/// a task's markdown notes file is indexed under the task's id.
+pub const KIND_TASK: &str = "task";
+pub const KIND_NOTE: &str = "note";
+pub const KIND_MARKDOWN: &str = "markdown";
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 200;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchFilters {
+    /// Restrict to these kinds (`task`, `note`, `markdown`). Empty means all.
+    pub kinds: Vec<String>,
+    pub status: Option<String>,
+    pub project: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub kind: String,
+    /// Id of the matching row (task or time entry; the task for markdown).
+    pub id: String,
+    pub task_id: String,
+    /// Task title, with matches wrapped in `<mark>` for task hits.
+    pub title: String,
+    /// Excerpt around the best match in the body, matches wrapped in `<mark>`.
+    pub snippet: String,
+    /// BM25 score; lower is a better match.
+    pub rank: f64,
+}
+
+/// Turn free-form input into an FTS5 query. Every word is quoted so user
+/// punctuation can't be read as query syntax, and the last word is treated as
+/// a prefix so results update as the user types.
+pub fn fts_query(input: &str) -> Option<String> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let (last, rest) = words.split_last()?;
+    let quote = |word: &str| format!("\"{}\"", word.replace('"', "\"\""));
+
+    let mut terms: Vec<String> = rest.iter().map(|w| quote(w)).collect();
+    terms.push(format!("{}*", quote(last)));
+    Some(terms.join(" "))
+}
+
+/// Rebuild the task and time entry rows of the index from their tables, in
+/// case the triggers that maintain them ever missed a change. Markdown notes
+/// are indexed from their files by `notes::reindex_all`.
+pub fn rebuild_index(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "DELETE FROM search_index WHERE kind IN ('task', 'note');
+         INSERT INTO search_index (kind, ref_id, title, body, tags)
+             SELECT 'task', id, title, COALESCE(description, ''),
+                 COALESCE((SELECT group_concat(g.name, ' ') FROM task_tags tt
+                           JOIN tags g ON g.id = tt.tag_id
+                           WHERE tt.task_id = tasks.id), '')
+             FROM tasks;
+         INSERT INTO search_index (kind, ref_id, title, body, tags)
+             SELECT 'note', id, '', note, '' FROM time_entries WHERE note IS NOT NULL;",
+    )
+}
+
+pub fn search_index(
+    conn: &Connection,
+    query: &str,
+    filters: &SearchFilters,
+) -> rusqlite::Result<Vec<SearchHit>> {
+    let mut sql = String::from(
+        "SELECT search_index.kind, search_index.ref_id, t.id,
+                CASE search_index.kind
+                    WHEN 'task' THEN highlight(search_index, 2, '<mark>', '</mark>')
+                    ELSE t.title
+                END,
+                snippet(search_index, 3, '<mark>', '</mark>', '…', 12),
+                bm25(search_index, 0.0, 0.0, 10.0, 1.0, 5.0) AS rank
+         FROM search_index
+         JOIN tasks t ON t.id = CASE search_index.kind
+             WHEN 'note' THEN (SELECT task_id FROM time_entries WHERE id = search_index.ref_id)
+             ELSE search_index.ref_id
+         END
+         WHERE search_index MATCH ? AND t.deleted_at IS NULL",
+    );
+    let mut values: Vec<Box<dyn ToSql>> = vec![Box::new(query.to_string())];
+
+    if !filters.kinds.is_empty() {
+        let placeholders = vec!["?"; filters.kinds.len()].join(", ");
+        sql.push_str(&format!(" AND search_index.kind IN ({placeholders})"));
+        for kind in &filters.kinds {
+            values.push(Box::new(kind.clone()));
+        }
+    }
+    if let Some(status) = &filters.status {
+        sql.push_str(" AND t.status = ?");
+        values.push(Box::new(status.clone()));
+    }
+    if let Some(project) = &filters.project {
+        sql.push_str(" AND t.project = ?");
+        values.push(Box::new(project.clone()));
+    }
+
+    let limit = filters.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    sql.push_str(" ORDER BY rank LIMIT ?");
+    values.push(Box::new(limit));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let params: Vec<&dyn ToSql> = values.iter().map(|v| v.as_ref()).collect();
+    let rows = stmt.query_map(params.as_slice(), |row| {
+        Ok(SearchHit {
+            kind: row.get(0)?,
+            id: row.get(1)?,
+            task_id: row.get(2)?,
+            title: row.get(3)?,
+            snippet: row.get(4)?,
+            rank: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[tauri::command]
+pub fn search(
+    db: State<'_, Db>,
+    query: String,
+    filters: Option<SearchFilters>,
+) -> CommandResult<Vec<SearchHit>> {
+    let Some(query) = fts_query(&query) else {
+        return Ok(Vec::new());
+    };
+    let filters = filters.unwrap_or_default();
+    if let Some(kind) = filters
+        .kinds
+        .iter()
+        .find(|k| !matches!(k.as_str(), KIND_TASK | KIND_NOTE | KIND_MARKDOWN))
+    {
+        return Err(CommandError::invalid(format!(
+            "Invalid search kind: {kind}"
+        )));
+    }
+    let started = Instant::now();
+    let hits = db.with_conn(|conn| search_index(conn, &query, &filters))?;
+    usage::record(&db, "search", Some(started.elapsed()));
+    Ok(hits)
+}
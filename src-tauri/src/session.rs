@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+
+use crate::data_dir;
+use crate::db::Db;
+use crate::error::{CommandResult, ResultExt};
+use crate::timer;
+
+const RESTART_STATE_FILE: &str = "restart-state.json";
+/// How long `restart_app` waits for the windows to flush before going ahead
+/// anyway, so a wedged renderer can still be restarted.
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Emitted before a restart. Windows flush their pending writes, then call
+/// `restart_ready`.
+pub const BEFORE_RESTART_EVENT: &str = "daylight:before-restart";
+
+pub struct RestartState {
+    ready: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+impl RestartState {
+    pub fn new() -> Self {
+        Self {
+            ready: Mutex::new(None),
+        }
+    }
+}
+
+fn restart_state_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    let dir = data_dir::app_data_dir(app)?;
+    fs::create_dir_all(&dir).context("Failed to create app data dir")?;
+    Ok(dir.join(RESTART_STATE_FILE))
+}
+
+/// Write `contents` next to `path` and rename over it, so a crash mid-write
+/// never leaves a truncated file behind.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> CommandResult<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Put what the backend holds on disk: the running timer in its file and
+/// the database's WAL in the main file.
+fn flush(app: &AppHandle) {
+    timer::sync(app);
+    if let Err(e) = app.state::<Db>().close() {
+        tracing::warn!("{e}");
+    }
+}
+
+/// Snapshot the frontend's transient UI state (current route, selected
+/// day, scroll position, ...) and relaunch the process. Windows get
+/// `daylight:before-restart` and a few seconds to flush their writes; then
+/// the timer and database are flushed and the process replaced.
+#[tauri::command]
+pub async fn restart_app(
+    app: AppHandle,
+    state: State<'_, RestartState>,
+    ui_state: serde_json::Value,
+) -> CommandResult<()> {
+    let path = restart_state_path(&app)?;
+    let body = serde_json::to_vec(&ui_state).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+
+    let (tx, rx) = oneshot::channel();
+    *state.ready.lock().map_err(|_| "Lock poisoned")? = Some(tx);
+    let _ = app.emit(BEFORE_RESTART_EVENT, ());
+    if timeout(READY_TIMEOUT, rx).await.is_err() {
+        tracing::warn!("restarting without the window having flushed");
+    }
+    flush(&app);
+    app.restart()
+}
+
+/// The window has flushed its pending writes; a waiting `restart_app` goes
+/// ahead.
+#[tauri::command]
+pub fn restart_ready(state: State<'_, RestartState>) {
+    let ready = state.ready.lock().ok().and_then(|mut ready| ready.take());
+    if let Some(ready) = ready {
+        let _ = ready.send(());
+    }
+}
+
+/// Return the UI state saved by `restart_app`, if any, and delete it so it
+/// is only restored once.
+#[tauri::command]
+pub fn take_restart_state(app: AppHandle) -> CommandResult<Option<serde_json::Value>> {
+    let path = restart_state_path(&app)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path).context("Failed to read restart state")?;
+    let _ = fs::remove_file(&path);
+
+    match serde_json::from_str(&content) {
+        Ok(state) => Ok(Some(state)),
+        Err(e) => {
+            tracing::warn!("discarding unreadable restart state: {e}");
+            Ok(None)
+        }
+    }
+}
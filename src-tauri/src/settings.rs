@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as Json};
+use tauri::{AppHandle, Emitter};
+
+use crate::data_dir;
+use crate::error::{CommandError, CommandResult};
+use crate::session::write_atomic;
+
+const SETTINGS_FILE: &str = "settings.json";
+/// Bumped when a setting is renamed or reshaped; `migrate` brings older
+/// files up to date.
+const SCHEMA_VERSION: u32 = 1;
+
+/// Emitted with a `SettingsChanged` payload after any change.
+pub const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+
+/// The frontend's old `localStorage` keys and the settings they became.
+const LEGACY_KEYS: &[(&str, &str)] = &[
+    ("daylight-theme", "appearance.theme"),
+    ("daylight-data-path", "data_path"),
+    ("daylight-editor-path", "editor_path"),
+    ("daylight-shortcuts-debug", "debug.shortcuts"),
+    ("daylight-gtk-debug", "debug.gtk_theme"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppearanceSettings {
+    /// `system`, or a theme name such as `flexoki-dark`.
+    pub theme: String,
+}
+
+impl Default for AppearanceSettings {
+    fn default() -> Self {
+        Self {
+            theme: "system".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TraySettings {
+    /// Start with only the tray icon, as `--hidden` does.
+    pub start_hidden: bool,
+    /// Name the running timer and pomodoro in the tray tooltip.
+    pub show_timer: bool,
+}
+
+impl Default for TraySettings {
+    fn default() -> Self {
+        Self {
+            start_hidden: false,
+            show_timer: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReminderSettings {
+    /// Keep moving sun-relative reminders to their next occurrence.
+    pub solar: bool,
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        Self { solar: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugSettings {
+    /// Log keyboard shortcut handling in the webview console.
+    pub shortcuts: bool,
+    /// Log how the GTK palette was applied.
+    pub gtk_theme: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrivacySettings {
+    /// Count feature use in the local database, for the year in review.
+    /// Nothing is ever sent anywhere.
+    pub usage_stats: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub version: u32,
+    pub appearance: AppearanceSettings,
+    /// The folder markdown task files are read from and written to.
+    pub data_path: Option<String>,
+    /// The folder the markdown editor opens.
+    pub editor_path: Option<String>,
+    pub tray: TraySettings,
+    pub reminders: ReminderSettings,
+    pub privacy: PrivacySettings,
+    pub debug: DebugSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            appearance: AppearanceSettings::default(),
+            data_path: None,
+            editor_path: None,
+            tray: TraySettings::default(),
+            reminders: ReminderSettings::default(),
+            privacy: PrivacySettings::default(),
+            debug: DebugSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsChanged {
+    /// Dotted keys that changed, e.g. `tray.start_hidden`; empty when
+    /// everything was reset.
+    pub keys: Vec<String>,
+    pub settings: Settings,
+}
+
+fn settings_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(SETTINGS_FILE))
+}
+
+/// Bring a settings file written by an older version up to date.
+fn migrate(mut raw: Map<String, Json>) -> Map<String, Json> {
+    let version = raw.get("version").and_then(Json::as_u64).unwrap_or(0);
+    if version < 1 {
+        // Before versioning the theme sat at the top level.
+        if let Some(theme) = raw.remove("theme") {
+            raw.insert(
+                "appearance".to_string(),
+                serde_json::json!({ "theme": theme }),
+            );
+        }
+    }
+    raw.insert("version".to_string(), Json::from(SCHEMA_VERSION));
+    raw
+}
+
+fn parse(text: &str) -> CommandResult<Settings> {
+    let raw: Map<String, Json> = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    serde_json::from_value(Json::Object(migrate(raw))).map_err(|e| e.to_string().into())
+}
+
+/// The saved settings, with defaults for anything not set.
+pub fn load(app: &AppHandle) -> Settings {
+    let Ok(path) = settings_path(app) else {
+        return Settings::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => parse(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable settings: {e}");
+            Settings::default()
+        }),
+        Err(_) => Settings::default(),
+    }
+}
+
+fn validate(settings: &Settings) -> CommandResult<()> {
+    if settings.appearance.theme.trim().is_empty() {
+        return Err(CommandError::invalid("The theme can't be empty"));
+    }
+    for path in [&settings.data_path, &settings.editor_path]
+        .into_iter()
+        .flatten()
+    {
+        if path.trim().is_empty() {
+            return Err(CommandError::invalid(
+                "A folder can't be blank; reset it instead",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn save(app: &AppHandle, settings: &Settings, keys: Vec<String>) -> CommandResult<Settings> {
+    validate(settings)?;
+    let body = serde_json::to_vec_pretty(settings).map_err(|e| e.to_string())?;
+    write_atomic(&settings_path(app)?, &body)?;
+    let _ = app.emit(
+        SETTINGS_CHANGED_EVENT,
+        SettingsChanged {
+            keys,
+            settings: settings.clone(),
+        },
+    );
+    crate::usage::apply(settings);
+    #[cfg(desktop)]
+    crate::tray::refresh_timer(app);
+    Ok(settings.clone())
+}
+
+/// The value at dotted `key` in `tree`, if the schema has it.
+fn slot<'a>(tree: &'a mut Json, key: &str) -> CommandResult<&'a mut Json> {
+    let mut node = tree;
+    for part in key.split('.') {
+        node = node
+            .as_object_mut()
+            .and_then(|object| object.get_mut(part))
+            .ok_or_else(|| CommandError::invalid(format!("Unknown setting: {key}")))?;
+    }
+    Ok(node)
+}
+
+/// `settings` with `key` set to `value`, checked against the schema.
+fn with_value(settings: &Settings, key: &str, value: Json) -> CommandResult<Settings> {
+    if key == "version" {
+        return Err("The settings version can't be set".into());
+    }
+    let mut tree = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    *slot(&mut tree, key)? = value;
+    serde_json::from_value(tree)
+        .map_err(|e| CommandError::invalid(format!("Invalid value for {key}: {e}")))
+}
+
+#[tauri::command]
+pub fn get_settings(app: AppHandle) -> Settings {
+    load(&app)
+}
+
+/// Set one setting by its dotted key, e.g. `tray.show_timer`.
+#[tauri::command]
+pub fn set_setting(app: AppHandle, key: String, value: Json) -> CommandResult<Settings> {
+    let settings = with_value(&load(&app), &key, value)?;
+    save(&app, &settings, vec![key])
+}
+
+/// Put one setting, or all of them when `key` is omitted, back to its
+/// default.
+#[tauri::command]
+pub fn reset_settings(app: AppHandle, key: Option<String>) -> CommandResult<Settings> {
+    let defaults = Settings::default();
+    match key {
+        None => Ok(save(&app, &defaults, Vec::new())?),
+        Some(key) => {
+            let mut tree = serde_json::to_value(&defaults).map_err(|e| e.to_string())?;
+            let value = slot(&mut tree, &key)?.take();
+            let settings = with_value(&load(&app), &key, value)?;
+            Ok(save(&app, &settings, vec![key])?)
+        }
+    }
+}
+
+/// Take over settings the frontend kept in `localStorage`, by their old
+/// keys. Settings already changed here win; unknown keys are ignored.
+#[tauri::command]
+pub fn import_legacy_settings(
+    app: AppHandle,
+    values: HashMap<String, String>,
+) -> CommandResult<Settings> {
+    let defaults = serde_json::to_value(Settings::default()).map_err(|e| e.to_string())?;
+    let mut settings = load(&app);
+    let mut keys = Vec::new();
+    for (old, key) in LEGACY_KEYS {
+        let Some(raw) = values.get(*old).filter(|raw| !raw.trim().is_empty()) else {
+            continue;
+        };
+        let mut current = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+        let mut defaults = defaults.clone();
+        if slot(&mut current, key)? != slot(&mut defaults, key)? {
+            continue;
+        }
+        let value = match slot(&mut defaults, key)? {
+            Json::Bool(_) => Json::Bool(raw == "1" || raw == "true"),
+            _ => Json::String(raw.clone()),
+        };
+        match with_value(&settings, key, value) {
+            Ok(updated) => {
+                settings = updated;
+                keys.push(key.to_string());
+            }
+            Err(e) => tracing::warn!("skipping legacy {old}: {e}"),
+        }
+    }
+    if keys.is_empty() {
+        return Ok(settings);
+    }
+    save(&app, &settings, keys)
+}
@@ -0,0 +1,284 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{Datelike, NaiveDate, Utc, Weekday};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::{format_utc, parse_utc, Db};
+use crate::error::CommandResult;
+use crate::ics;
+use crate::reports::{self, GroupBy, Range, ReportQuery, TAG_FILTER_SQL};
+use crate::task_store::STATUS_DONE;
+
+const DEFAULT_TOP_PROJECTS: usize = 5;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatsQuery {
+    /// RFC 3339 instant, or a `YYYY-MM-DD` date (midnight in `zone`).
+    pub from: String,
+    /// Exclusive RFC 3339 instant, or an inclusive `YYYY-MM-DD` date.
+    pub to: String,
+    /// IANA zone that decides where days start. Defaults to the system zone.
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// How many projects to rank. Defaults to 5.
+    #[serde(default)]
+    pub top_projects: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeekdayHours {
+    /// `Mon` to `Sun`.
+    pub weekday: String,
+    pub total_hours: f64,
+    /// Total divided by how many of this weekday the range covers.
+    pub average_hours: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectStats {
+    /// Empty for tasks without a project.
+    pub project: String,
+    pub label: String,
+    pub tracked_seconds: i64,
+    pub completed: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub from: String,
+    pub to: String,
+    /// Consecutive days with a completion, up to the end of the range. A day
+    /// with nothing done yet doesn't break the streak until it's over.
+    pub current_streak: i64,
+    /// Longest run of such days within the range.
+    pub longest_streak: i64,
+    pub completed: i64,
+    /// Completed tasks that had a due date, and how many of them were done
+    /// by it.
+    pub with_due: i64,
+    pub on_time: i64,
+    /// `on_time / with_due`; absent when nothing completed had a due date.
+    pub on_time_rate: Option<f64>,
+    /// Monday first.
+    pub weekday_hours: Vec<WeekdayHours>,
+    /// Most tracked time first, then most completions.
+    pub busiest_projects: Vec<ProjectStats>,
+}
+
+fn report_query(query: &StatsQuery, group_by: GroupBy) -> ReportQuery {
+    ReportQuery {
+        from: query.from.clone(),
+        to: query.to.clone(),
+        group_by,
+        zone: query.zone.clone(),
+        project: query.project.clone(),
+        tag: query.tag.clone(),
+    }
+}
+
+/// Whether a task completed at `completed_at` met `due`, a local date or a
+/// local date and time in the task's zone.
+fn met_due(
+    completed_at: chrono::DateTime<Utc>,
+    due: &str,
+    tz: Option<&str>,
+    range: &Range,
+) -> bool {
+    match ics::local_instant(due, tz) {
+        Some(deadline) => completed_at <= deadline,
+        None => range.local_date(completed_at).to_string().as_str() <= due,
+    }
+}
+
+/// Longest run of consecutive days in `days` within `first..=last`, and the
+/// run ending at `last` (or the day before, if `last` has none).
+fn streaks(days: &BTreeSet<NaiveDate>, first: NaiveDate, last: NaiveDate) -> (i64, i64) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut prev: Option<NaiveDate> = None;
+    for &day in days.range(first..=last) {
+        run = match prev {
+            Some(p) if p.succ_opt() == Some(day) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        prev = Some(day);
+    }
+
+    let mut day = if days.contains(&last) {
+        last
+    } else {
+        match last.pred_opt() {
+            Some(day) => day,
+            None => return (0, longest),
+        }
+    };
+    let mut current = 0;
+    while days.contains(&day) {
+        current += 1;
+        match day.pred_opt() {
+            Some(prev) => day = prev,
+            None => break,
+        }
+    }
+    (current, longest)
+}
+
+/// Completion streaks and on-time counts. Streaks look back past the start
+/// of the range so a current streak isn't cut short by it.
+fn completion_stats(
+    conn: &Connection,
+    query: &StatsQuery,
+    range: &Range,
+) -> rusqlite::Result<Stats> {
+    let from = format_utc(range.from);
+    let to = format_utc(range.to);
+    let sql = format!(
+        "SELECT t.completed_at, t.due, t.tz FROM tasks t
+         WHERE t.status = ?5 AND t.deleted_at IS NULL AND t.completed_at < ?2
+           AND (?3 IS NULL OR t.project = ?3)
+           AND {TAG_FILTER_SQL}
+         ORDER BY t.completed_at"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query(params![from, to, query.project, query.tag, STATUS_DONE])?;
+
+    let mut days = BTreeSet::new();
+    let (mut completed, mut with_due, mut on_time) = (0i64, 0i64, 0i64);
+    while let Some(row) = rows.next()? {
+        let completed_at: String = row.get(0)?;
+        let Ok(completed_at) = parse_utc(&completed_at) else {
+            continue;
+        };
+        days.insert(range.local_date(completed_at));
+        if completed_at < range.from {
+            continue;
+        }
+        completed += 1;
+        let due: Option<String> = row.get(1)?;
+        if let Some(due) = due {
+            with_due += 1;
+            let tz: Option<String> = row.get(2)?;
+            if met_due(completed_at, &due, tz.as_deref(), range) {
+                on_time += 1;
+            }
+        }
+    }
+
+    let end = range.to.min(Utc::now()).max(range.from);
+    let last = range.local_date(end - chrono::Duration::milliseconds(1));
+    let (current_streak, longest_streak) = streaks(&days, range.local_date(range.from), last);
+    Ok(Stats {
+        from,
+        to,
+        current_streak,
+        longest_streak,
+        completed,
+        with_due,
+        on_time,
+        on_time_rate: (with_due > 0).then(|| on_time as f64 / with_due as f64),
+        weekday_hours: Vec::new(),
+        busiest_projects: Vec::new(),
+    })
+}
+
+/// Tracked hours per weekday, from the report's per-day buckets.
+fn weekday_hours(
+    conn: &Connection,
+    query: &StatsQuery,
+    range: &Range,
+) -> rusqlite::Result<Vec<WeekdayHours>> {
+    let report = reports::time_report(conn, &report_query(query, GroupBy::Day), range)?;
+
+    let mut totals = [(0i64, 0i64); 7];
+    for bucket in &report.buckets {
+        let Ok(date) = NaiveDate::parse_from_str(&bucket.key, "%Y-%m-%d") else {
+            continue;
+        };
+        let slot = &mut totals[date.weekday().num_days_from_monday() as usize];
+        slot.0 += bucket.total_seconds;
+        slot.1 += 1;
+    }
+    let mut weekday = Weekday::Mon;
+    let mut hours = Vec::with_capacity(7);
+    for (seconds, days) in totals {
+        let total_hours = seconds as f64 / 3600.0;
+        hours.push(WeekdayHours {
+            weekday: weekday.to_string(),
+            total_hours,
+            average_hours: if days > 0 {
+                total_hours / days as f64
+            } else {
+                0.0
+            },
+        });
+        weekday = weekday.succ();
+    }
+    Ok(hours)
+}
+
+/// Projects ranked by time tracked and tasks completed in the range.
+fn busiest_projects(
+    conn: &Connection,
+    query: &StatsQuery,
+    range: &Range,
+) -> rusqlite::Result<Vec<ProjectStats>> {
+    let by_project = report_query(query, GroupBy::Project);
+    let time = reports::time_report(conn, &by_project, range)?;
+    let done = reports::completion_report(conn, &by_project, range)?;
+
+    let mut projects: HashMap<String, ProjectStats> = HashMap::new();
+    for bucket in time.buckets {
+        projects.insert(
+            bucket.key.clone(),
+            ProjectStats {
+                project: bucket.key,
+                label: bucket.label,
+                tracked_seconds: bucket.total_seconds,
+                completed: 0,
+            },
+        );
+    }
+    for bucket in done.buckets {
+        projects
+            .entry(bucket.key.clone())
+            .or_insert_with(|| ProjectStats {
+                project: bucket.key,
+                label: bucket.label,
+                tracked_seconds: 0,
+                completed: 0,
+            })
+            .completed = bucket.count;
+    }
+
+    let mut projects: Vec<ProjectStats> = projects.into_values().collect();
+    projects.sort_by(|a, b| {
+        b.tracked_seconds
+            .cmp(&a.tracked_seconds)
+            .then_with(|| b.completed.cmp(&a.completed))
+            .then_with(|| a.label.to_lowercase().cmp(&b.label.to_lowercase()))
+    });
+    projects.truncate(query.top_projects.unwrap_or(DEFAULT_TOP_PROJECTS));
+    Ok(projects)
+}
+
+pub fn stats(conn: &Connection, query: &StatsQuery, range: &Range) -> rusqlite::Result<Stats> {
+    let mut stats = completion_stats(conn, query, range)?;
+    stats.weekday_hours = weekday_hours(conn, query, range)?;
+    stats.busiest_projects = busiest_projects(conn, query, range)?;
+    Ok(stats)
+}
+
+/// Dashboard figures for the range, worked out here so the frontend doesn't
+/// need every task and entry.
+#[tauri::command]
+pub fn get_stats(db: State<'_, Db>, query: StatsQuery) -> CommandResult<Stats> {
+    let range = Range::from_query(&report_query(&query, GroupBy::Day))?;
+    db.with_conn(|conn| stats(conn, &query, &range))
+}
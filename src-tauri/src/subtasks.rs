@@ -0,0 +1,194 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::{now_utc, Db};
+use crate::dependencies;
+use crate::error::{CommandError, CommandResult};
+use crate::order_key;
+use crate::tags;
+use crate::task_store::{self, row_to_task, Task, STATUS_DONE, TASK_COLUMNS};
+
+/// How far along a task is, judged by its direct subtasks. Subtasks in the
+/// trash aren't counted.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskProgress {
+    pub task_id: String,
+    pub total: i64,
+    pub done: i64,
+}
+
+/// A task's subtasks, in their manual order.
+pub fn children(conn: &Connection, parent_id: &str) -> rusqlite::Result<Vec<Task>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TASK_COLUMNS} FROM tasks
+         WHERE parent_id = ?1 AND deleted_at IS NULL
+         ORDER BY sort_key, created_at, id"
+    ))?;
+    let mut tasks = stmt
+        .query_map(params![parent_id], row_to_task)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tags::load_task_tags(conn, &mut tasks)?;
+    dependencies::load_blocked(conn, &mut tasks)?;
+    Ok(tasks)
+}
+
+/// A sort key placing a new subtask of `parent_id` after all the others.
+/// Trashed subtasks count, so restoring one can't collide with the new key.
+pub fn append_key(conn: &Connection, parent_id: &str) -> rusqlite::Result<String> {
+    let last: Option<String> = conn.query_row(
+        "SELECT MAX(sort_key) FROM tasks WHERE parent_id = ?1",
+        params![parent_id],
+        |row| row.get(0),
+    )?;
+    Ok(order_key::between(last.as_deref(), None))
+}
+
+/// Check `parent_id` can hold task `id`: it must exist outside the trash,
+/// and not be the task itself or one of its subtasks.
+fn check_parent(conn: &Connection, id: &str, parent_id: &str) -> CommandResult<()> {
+    let db_err = |e: rusqlite::Error| e.to_string();
+    if task_store::find_task(conn, parent_id)
+        .map_err(db_err)?
+        .is_none()
+    {
+        return Err(CommandError::not_found(format!(
+            "Task not found: {parent_id}"
+        )));
+    }
+    let cycle: bool = conn
+        .query_row(
+            "WITH RECURSIVE ancestors(id) AS (
+                 SELECT ?1
+                 UNION SELECT t.parent_id FROM tasks t
+                 JOIN ancestors a ON t.id = a.id WHERE t.parent_id IS NOT NULL
+             )
+             SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = ?2)",
+            params![parent_id, id],
+            |row| row.get(0),
+        )
+        .map_err(db_err)?;
+    if cycle {
+        return Err(CommandError::invalid("A task can't be moved under itself"));
+    }
+    Ok(())
+}
+
+pub fn progress(conn: &Connection, id: &str) -> rusqlite::Result<TaskProgress> {
+    conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(status = ?2), 0) FROM tasks
+         WHERE parent_id = ?1 AND deleted_at IS NULL",
+        params![id, STATUS_DONE],
+        |row| {
+            Ok(TaskProgress {
+                task_id: id.to_string(),
+                total: row.get(0)?,
+                done: row.get(1)?,
+            })
+        },
+    )
+}
+
+#[tauri::command]
+pub fn list_subtasks(db: State<'_, Db>, parent_id: String) -> CommandResult<Vec<Task>> {
+    db.with_conn(|conn| children(conn, &parent_id))
+}
+
+/// Make a task a subtask of `parent_id`, after its existing subtasks, or
+/// a top-level task again when `parent_id` is `None`.
+#[tauri::command]
+pub fn set_task_parent(
+    db: State<'_, Db>,
+    id: String,
+    parent_id: Option<String>,
+) -> CommandResult<Task> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let Some(task) = task_store::find_task(&tx, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Task not found: {id}"
+            ))));
+        };
+        if task.parent_id == parent_id {
+            return Ok(Ok(task));
+        }
+        let sort_key = match &parent_id {
+            Some(parent_id) => {
+                if let Err(e) = check_parent(&tx, &id, parent_id) {
+                    return Ok(Err(e));
+                }
+                Some(append_key(&tx, parent_id)?)
+            }
+            None => None,
+        };
+        tx.execute(
+            "UPDATE tasks SET parent_id = ?2, sort_key = ?3, updated_at = ?4 WHERE id = ?1",
+            params![id, parent_id, sort_key, now_utc()],
+        )?;
+        let task = task_store::find_task(&tx, &id)?;
+        tx.commit()?;
+        Ok(task.ok_or_else(|| CommandError::not_found(format!("Task not found: {id}"))))
+    })?
+}
+
+/// Move a subtask to just after its sibling `after_id`, or to the front when
+/// `after_id` is `None`. Only the moved task's key changes.
+#[tauri::command]
+pub fn move_subtask(
+    db: State<'_, Db>,
+    id: String,
+    after_id: Option<String>,
+) -> CommandResult<Task> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let Some(task) = task_store::find_task(&tx, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Task not found: {id}"
+            ))));
+        };
+        let Some(parent_id) = task.parent_id.clone() else {
+            return Ok(Err(CommandError::invalid(format!(
+                "Task is not a subtask: {id}"
+            ))));
+        };
+        let before = match &after_id {
+            Some(after_id) if *after_id == id => return Ok(Ok(task)),
+            Some(after_id) => {
+                let key: Option<Option<String>> = tx
+                    .query_row(
+                        "SELECT sort_key FROM tasks
+                         WHERE id = ?1 AND parent_id = ?2 AND deleted_at IS NULL",
+                        params![after_id, parent_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let Some(key) = key else {
+                    return Ok(Err(CommandError::invalid(format!(
+                        "Not a sibling subtask: {after_id}"
+                    ))));
+                };
+                key
+            }
+            None => None,
+        };
+        let after: Option<String> = tx.query_row(
+            "SELECT MIN(sort_key) FROM tasks
+             WHERE parent_id = ?1 AND id != ?2 AND (?3 IS NULL OR sort_key > ?3)",
+            params![parent_id, id, before],
+            |row| row.get(0),
+        )?;
+        let sort_key = order_key::between(before.as_deref(), after.as_deref());
+        tx.execute(
+            "UPDATE tasks SET sort_key = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, sort_key, now_utc()],
+        )?;
+        let task = task_store::find_task(&tx, &id)?;
+        tx.commit()?;
+        Ok(task.ok_or_else(|| CommandError::not_found(format!("Task not found: {id}"))))
+    })?
+}
+
+#[tauri::command]
+pub fn get_task_progress(db: State<'_, Db>, ids: Vec<String>) -> CommandResult<Vec<TaskProgress>> {
+    db.with_conn(|conn| ids.iter().map(|id| progress(conn, id)).collect())
+}
@@ -0,0 +1,408 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::{format_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::location;
+
+/// Altitude of the sun's centre at sunrise and sunset: the disc's radius
+/// plus refraction at the horizon, below it.
+const SUNRISE_ALTITUDE: f64 = -0.833;
+/// Civil twilight ends once the sun is this far below the horizon.
+const CIVIL_ALTITUDE: f64 = -6.0;
+/// Golden hour is while the sun is between these altitudes, low and warm.
+const GOLDEN_LOW_ALTITUDE: f64 = -4.0;
+const GOLDEN_HIGH_ALTITUDE: f64 = 6.0;
+/// The most days `get_daylight` covers at once.
+const MAX_DAYLIGHT_DAYS: i64 = 366;
+/// Julian day of 2000-01-01 12:00 UTC, the J2000 epoch.
+const J2000: f64 = 2_451_545.0;
+/// Julian day of the Unix epoch.
+const UNIX_EPOCH_JD: f64 = 2_440_587.5;
+const MINUTES_PER_DAY: f64 = 1440.0;
+
+/// When the sun rises and sets on a day at a place, as RFC 3339 UTC
+/// instants. Sunrise and sunset are missing on days the sun stays above
+/// or below the horizon, as twilight is when it never gets 6° below it or
+/// above that.
+#[derive(Debug, Clone, Serialize)]
+pub struct SunTimes {
+    pub date: String,
+    pub civil_dawn: Option<String>,
+    pub sunrise: Option<String>,
+    pub solar_noon: String,
+    pub sunset: Option<String>,
+    pub civil_dusk: Option<String>,
+    /// Minutes from sunrise to sunset; 0 or 1440 while the sun doesn't set
+    /// or rise.
+    pub day_length_minutes: i64,
+}
+
+/// A stretch of golden hour, as RFC 3339 UTC instants.
+#[derive(Debug, Clone, Serialize)]
+pub struct GoldenHour {
+    pub start: String,
+    pub end: String,
+}
+
+/// How much daylight a day has and how much is left of it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Daylight {
+    pub date: String,
+    pub sunrise: Option<String>,
+    pub sunset: Option<String>,
+    pub day_length_minutes: i64,
+    /// Minutes of daylight still to come: all of it for days ahead, none
+    /// for days gone.
+    pub remaining_minutes: i64,
+    /// The morning's and evening's, or one long one on days the sun stays
+    /// low.
+    pub golden_hours: Vec<GoldenHour>,
+}
+
+/// Where the sun is at an instant, as the sunrise equation needs it.
+struct SolarPosition {
+    /// Declination, in radians.
+    declination: f64,
+    /// Apparent minus mean solar time, in minutes.
+    equation_of_time: f64,
+}
+
+fn julian_day(at: DateTime<Utc>) -> f64 {
+    UNIX_EPOCH_JD + at.timestamp_millis() as f64 / 86_400_000.0
+}
+
+/// NOAA's solar position equations, after Meeus' Astronomical Algorithms.
+fn solar_position(jd: f64) -> SolarPosition {
+    let t = (jd - J2000) / 36525.0;
+    let mean_longitude = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+    let mean_anomaly = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude =
+        (mean_longitude + center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+    let mean_obliquity =
+        23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+    let declination = (obliquity.sin() * apparent_longitude.sin()).asin();
+
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_longitude.to_radians();
+    let e = eccentricity;
+    let equation_of_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * e * m.sin() + 4.0 * e * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * e * e * (2.0 * m).sin())
+        .to_degrees();
+    SolarPosition {
+        declination,
+        equation_of_time,
+    }
+}
+
+/// `minutes` after midnight UTC of `date`, to the second.
+fn instant(date: NaiveDate, minutes: f64) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN).and_utc();
+    midnight + Duration::seconds((minutes * 60.0).round() as i64)
+}
+
+/// Minutes after midnight UTC of `date` that the sun crosses the meridian
+/// at `lon`.
+fn solar_noon(date: NaiveDate, lon: f64) -> f64 {
+    let mut noon = 720.0 - 4.0 * lon;
+    // The equation of time barely moves in a day; twice is plenty.
+    for _ in 0..2 {
+        let position = solar_position(julian_day(instant(date, noon)));
+        noon = 720.0 - 4.0 * lon - position.equation_of_time;
+    }
+    noon
+}
+
+/// How the sun passes `altitude` degrees at `lat` on one day.
+enum Crossing {
+    /// Minutes after midnight UTC it goes up through it and comes back
+    /// down.
+    At(f64, f64),
+    /// It stays above all day.
+    Above,
+    /// It stays below all day.
+    Below,
+}
+
+/// When the sun is at `altitude` on `date`, refined around the times
+/// themselves since the sun's declination moves through the day.
+fn crossing(date: NaiveDate, lat: f64, lon: f64, noon: f64, altitude: f64) -> Crossing {
+    let hour_angle = |minutes: f64| {
+        let position = solar_position(julian_day(instant(date, minutes)));
+        let lat = lat.to_radians();
+        let cos = (altitude.to_radians().sin() - lat.sin() * position.declination.sin())
+            / (lat.cos() * position.declination.cos());
+        (position, cos)
+    };
+    let (_, cos) = hour_angle(noon);
+    if cos > 1.0 {
+        return Crossing::Below;
+    }
+    if cos < -1.0 {
+        return Crossing::Above;
+    }
+    let mut times = [0.0; 2];
+    for (slot, sign) in times.iter_mut().zip([-1.0, 1.0]) {
+        let mut at = noon + sign * 4.0 * cos.acos().to_degrees();
+        for _ in 0..2 {
+            let (position, cos) = hour_angle(at);
+            let degrees = cos.clamp(-1.0, 1.0).acos().to_degrees();
+            at = 720.0 - 4.0 * lon - position.equation_of_time + sign * 4.0 * degrees;
+        }
+        *slot = at;
+    }
+    Crossing::At(times[0], times[1])
+}
+
+fn check_coordinates(lat: f64, lon: f64) -> CommandResult<()> {
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(CommandError::invalid(format!(
+            "Latitude must be between -90 and 90: {lat}"
+        )));
+    }
+    if !(-180.0..=180.0).contains(&lon) {
+        return Err(CommandError::invalid(format!(
+            "Longitude must be between -180 and 180: {lon}"
+        )));
+    }
+    Ok(())
+}
+
+fn parse_date(date: &str) -> CommandResult<NaiveDate> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|e| CommandError::invalid(format!("Invalid date '{date}': {e}")))
+}
+
+/// Sunrise, sunset, solar noon and civil twilight on `date` at `lat`,
+/// `lon` in degrees, north and east positive.
+pub fn sun_times(date: NaiveDate, lat: f64, lon: f64) -> CommandResult<SunTimes> {
+    check_coordinates(lat, lon)?;
+    let noon = solar_noon(date, lon);
+    let at = |minutes: f64| format_utc(instant(date, minutes));
+
+    let (sunrise, sunset, day_length_minutes) =
+        match crossing(date, lat, lon, noon, SUNRISE_ALTITUDE) {
+            Crossing::At(rise, set) => (Some(at(rise)), Some(at(set)), (set - rise).round() as i64),
+            Crossing::Above => (None, None, MINUTES_PER_DAY as i64),
+            Crossing::Below => (None, None, 0),
+        };
+    let (civil_dawn, civil_dusk) = match crossing(date, lat, lon, noon, CIVIL_ALTITUDE) {
+        Crossing::At(dawn, dusk) => (Some(at(dawn)), Some(at(dusk))),
+        Crossing::Above | Crossing::Below => (None, None),
+    };
+    Ok(SunTimes {
+        date: date.to_string(),
+        civil_dawn,
+        sunrise,
+        solar_noon: at(noon),
+        sunset,
+        civil_dusk,
+        day_length_minutes,
+    })
+}
+
+/// Day length, daylight left as of `now` and golden hours on `date` at
+/// `lat`, `lon`. While the sun doesn't set, the day is taken to run from
+/// one solar midnight to the next.
+fn daylight(date: NaiveDate, lat: f64, lon: f64, now: DateTime<Utc>) -> Daylight {
+    let noon = solar_noon(date, lon);
+    let at = |minutes: f64| format_utc(instant(date, minutes));
+    let (light, sunrise, sunset) = match crossing(date, lat, lon, noon, SUNRISE_ALTITUDE) {
+        Crossing::At(rise, set) => (Some((rise, set)), Some(at(rise)), Some(at(set))),
+        Crossing::Above => (Some((noon - 720.0, noon + 720.0)), None, None),
+        Crossing::Below => (None, None, None),
+    };
+    let now = (now - instant(date, 0.0)).num_seconds() as f64 / 60.0;
+    let (day_length_minutes, remaining_minutes) = match light {
+        Some((start, end)) => (
+            (end - start).round() as i64,
+            (end - now.max(start)).max(0.0).round() as i64,
+        ),
+        None => (0, 0),
+    };
+
+    let low = crossing(date, lat, lon, noon, GOLDEN_LOW_ALTITUDE);
+    let high = crossing(date, lat, lon, noon, GOLDEN_HIGH_ALTITUDE);
+    let windows = match (low, high) {
+        (Crossing::At(dawn, dusk), Crossing::At(up, down)) => vec![(dawn, up), (down, dusk)],
+        (Crossing::At(dawn, dusk), Crossing::Below) => vec![(dawn, dusk)],
+        // The sun stays out but dips low around midnight.
+        (Crossing::Above, Crossing::At(up, down)) => vec![(down, up + MINUTES_PER_DAY)],
+        _ => Vec::new(),
+    };
+    Daylight {
+        date: date.to_string(),
+        sunrise,
+        sunset,
+        day_length_minutes,
+        remaining_minutes,
+        golden_hours: windows
+            .into_iter()
+            .map(|(start, end)| GoldenHour {
+                start: at(start),
+                end: at(end),
+            })
+            .collect(),
+    }
+}
+
+/// Sun times for `date` (`YYYY-MM-DD`) at `lat`, `lon`. The date is the
+/// one around the place's own solar noon.
+#[tauri::command]
+pub fn get_sun_times(lat: f64, lon: f64, date: String) -> CommandResult<SunTimes> {
+    sun_times(parse_date(&date)?, lat, lon)
+}
+
+/// Sun times where the user is, as the location settings say, for `date`
+/// or today.
+#[tauri::command]
+pub async fn get_sun_times_here(
+    db: State<'_, Db>,
+    date: Option<String>,
+) -> CommandResult<SunTimes> {
+    let day = match date {
+        Some(date) => parse_date(&date)?,
+        None => Local::now().date_naive(),
+    };
+    let here = location::current(&db).await?;
+    sun_times(day, here.latitude, here.longitude)
+}
+
+/// Daylight for each day from `from` to `to`, both `YYYY-MM-DD` and
+/// today when missing, where the user is: for a "daylight remaining"
+/// gauge beside the schedule.
+#[tauri::command]
+pub async fn get_daylight(
+    db: State<'_, Db>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<Vec<Daylight>> {
+    let today = Local::now().date_naive();
+    let from = from
+        .as_deref()
+        .map(parse_date)
+        .transpose()?
+        .unwrap_or(today);
+    let to = to.as_deref().map(parse_date).transpose()?.unwrap_or(from);
+    if to < from {
+        return Err(CommandError::invalid("The range ends before it starts"));
+    }
+    if (to - from).num_days() >= MAX_DAYLIGHT_DAYS {
+        return Err(CommandError::invalid(format!(
+            "Ask for at most {MAX_DAYLIGHT_DAYS} days at once"
+        )));
+    }
+    let here = location::current(&db).await?;
+    check_coordinates(here.latitude, here.longitude)?;
+    let now = Utc::now();
+    Ok(from
+        .iter_days()
+        .take_while(|date| *date <= to)
+        .map(|date| daylight(date, here.latitude, here.longitude, now))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    const TROMSO: (f64, f64) = (69.65, 18.96);
+    const LONGYEARBYEN: (f64, f64) = (78.22, 15.65);
+
+    #[test]
+    fn mid_latitudes_match_published_times() {
+        // London on the June solstice: sunrise 04:43 BST, sunset 21:21 BST.
+        let times = sun_times(date(2025, 6, 21), 51.5074, -0.1278).unwrap();
+        assert!(times
+            .sunrise
+            .as_deref()
+            .unwrap()
+            .starts_with("2025-06-21T03:4"));
+        assert!(times
+            .sunset
+            .as_deref()
+            .unwrap()
+            .starts_with("2025-06-21T20:2"));
+        assert!((995..=1000).contains(&times.day_length_minutes));
+        assert!(times.civil_dawn < times.sunrise);
+        assert!(times.civil_dusk > times.sunset);
+    }
+
+    #[test]
+    fn midnight_sun() {
+        let (lat, lon) = TROMSO;
+        let times = sun_times(date(2025, 6, 21), lat, lon).unwrap();
+        assert_eq!(times.sunrise, None);
+        assert_eq!(times.sunset, None);
+        assert_eq!(times.civil_dawn, None);
+        assert_eq!(times.day_length_minutes, 1440);
+
+        // The day runs from solar midnight, over an hour before 00:00 UTC here.
+        let day = date(2025, 6, 21);
+        let midnight = solar_noon(day, lon) - 720.0;
+        let day = daylight(day, lat, lon, instant(day, 0.0));
+        assert_eq!(day.day_length_minutes, 1440);
+        assert_eq!(day.remaining_minutes, 1440 + midnight.round() as i64);
+        // The sun dips under 6° around midnight but never sets.
+        assert_eq!(day.golden_hours.len(), 1);
+        assert!(day.golden_hours[0].start < day.golden_hours[0].end);
+    }
+
+    #[test]
+    fn polar_night() {
+        // Tromsø still gets civil twilight around noon; Svalbard doesn't.
+        let (lat, lon) = TROMSO;
+        let times = sun_times(date(2025, 12, 21), lat, lon).unwrap();
+        assert_eq!(
+            (times.sunrise.as_ref(), times.day_length_minutes),
+            (None, 0)
+        );
+        assert!(times.civil_dawn.is_some() && times.civil_dusk.is_some());
+
+        let (lat, lon) = LONGYEARBYEN;
+        let times = sun_times(date(2025, 12, 21), lat, lon).unwrap();
+        assert_eq!((times.sunrise, times.civil_dawn), (None, None));
+        let day = daylight(date(2025, 12, 21), lat, lon, Utc::now());
+        assert_eq!((day.day_length_minutes, day.remaining_minutes), (0, 0));
+        assert!(day.golden_hours.is_empty());
+    }
+
+    #[test]
+    fn poles_and_bad_coordinates() {
+        let summer = sun_times(date(2025, 6, 21), 90.0, 0.0).unwrap();
+        assert_eq!(summer.day_length_minutes, 1440);
+        let winter = sun_times(date(2025, 6, 21), -90.0, 0.0).unwrap();
+        assert_eq!(winter.day_length_minutes, 0);
+        assert!(sun_times(date(2025, 6, 21), 90.5, 0.0).is_err());
+        assert!(sun_times(date(2025, 6, 21), 0.0, -181.0).is_err());
+    }
+
+    #[test]
+    fn daylight_left_counts_down() {
+        let day = date(2025, 3, 20);
+        let (lat, lon) = (0.0, 0.0);
+        let morning = daylight(day, lat, lon, instant(day, 0.0));
+        assert!((720..=735).contains(&morning.day_length_minutes));
+        assert_eq!(morning.remaining_minutes, morning.day_length_minutes);
+        assert_eq!(morning.golden_hours.len(), 2);
+
+        let noon = daylight(day, lat, lon, instant(day, solar_noon(day, lon)));
+        assert!((morning.day_length_minutes / 2 - noon.remaining_minutes).abs() <= 1);
+        let night = daylight(day, lat, lon, instant(day, 1439.0));
+        assert_eq!(night.remaining_minutes, 0);
+    }
+}
@@ -0,0 +1,490 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::account_health::AccountHealth;
+use crate::asana::Asana;
+use crate::caldav::Caldav;
+use crate::db::Db;
+use crate::deck::Deck;
+use crate::error::{CommandError, CommandResult};
+use crate::ews::Ews;
+use crate::github::Github;
+use crate::gitlab::Gitlab;
+use crate::google_tasks::GoogleTasks;
+use crate::microsoft_todo::MicrosoftTodo;
+use crate::notion::Notion;
+use crate::rate_limit;
+use crate::sync_status::{self, SyncOutcome, SyncStatus, Tracker};
+use crate::todoist::Todoist;
+use crate::trello::Trello;
+use crate::usage;
+
+pub type SyncFuture<'a, T> = Pin<Box<dyn Future<Output = CommandResult<T>> + Send + 'a>>;
+
+/// Every provider synced through accounts, in the order the sync page
+/// lists them.
+pub const REGISTRY: &[&dyn AnyProvider] = &[
+    &Asana,
+    &Caldav,
+    &Deck,
+    &Ews,
+    &Github,
+    &Gitlab,
+    &GoogleTasks,
+    &MicrosoftTodo,
+    &Notion,
+    &Todoist,
+    &Trello,
+];
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Capabilities {
+    /// Local changes are uploaded, not just remote ones brought in.
+    pub push: bool,
+    /// Items changed on both sides follow the provider's conflict policy.
+    pub conflicts: bool,
+}
+
+/// Which way a provider syncs, set per provider by the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    /// Remote changes come in and local ones go up.
+    TwoWay,
+    /// Remote changes come in; nothing here is ever uploaded.
+    Import,
+    /// Local changes go up; remote ones, and remote items new or deleted,
+    /// are left out here.
+    Export,
+}
+
+impl SyncMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            SyncMode::TwoWay => "two_way",
+            SyncMode::Import => "import",
+            SyncMode::Export => "export",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "two_way" => Some(SyncMode::TwoWay),
+            "import" => Some(SyncMode::Import),
+            "export" => Some(SyncMode::Export),
+            _ => None,
+        }
+    }
+
+    /// Whether remote changes are written here.
+    pub fn imports(self) -> bool {
+        self != SyncMode::Export
+    }
+
+    /// Whether local changes are uploaded.
+    pub fn exports(self) -> bool {
+        self != SyncMode::Import
+    }
+}
+
+/// A service tasks are synced with. Each lives in its own module; the
+/// running guard, progress and status are `run`'s.
+pub trait SyncProvider: Sync {
+    type Report: SyncOutcome + Send + 'static;
+
+    /// Its name in sync status, conflict policies and external refs.
+    fn id(&self) -> &'static str;
+    /// Its name in messages.
+    fn name(&self) -> &'static str;
+    fn capabilities(&self) -> Capabilities;
+    /// Load the credentials of `account_id`, or of every account, refreshing
+    /// any that expired, without syncing.
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()>;
+    /// Bring in what changed remotely, leaving local changes for the next
+    /// push.
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report>;
+    /// Pull, then upload what changed here, checked against the remote
+    /// versions just pulled. Providers that only import just pull.
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        self.pull(db, account_id, progress)
+    }
+
+    /// Upload what changed here without writing remote changes locally.
+    /// Only providers that push can.
+    fn export<'a>(
+        &'a self,
+        _db: &'a Db,
+        _account_id: Option<&'a str>,
+        _progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(async move {
+            Err(CommandError::invalid(format!(
+                "{} can't export",
+                SyncProvider::name(self)
+            )))
+        })
+    }
+
+    /// The lists, calendars, boards or projects of `account_id`, or of
+    /// every account, looked up again on the service, with whether each
+    /// takes part in syncs. Providers that sync everything have none.
+    fn collections<'a>(
+        &'a self,
+        _db: &'a Db,
+        _account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move { Ok(Vec::new()) })
+    }
+
+    /// How `account_id`, or every account, connects: whether its
+    /// credentials are taken, with the scopes syncs need, and how the
+    /// service's clock compares.
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>>;
+
+    /// Let collection `id` take part in syncs, or leave it out.
+    fn select_collection(&self, _db: &Db, _id: &str, _selected: bool) -> CommandResult<()> {
+        Err(CommandError::invalid(format!(
+            "{} has no lists to choose from",
+            SyncProvider::name(self)
+        )))
+    }
+}
+
+/// A `SyncProvider` with its report left out, for the registry.
+pub trait AnyProvider: Sync {
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+    fn capabilities(&self) -> Capabilities;
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()>;
+    fn sync<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        account_id: Option<&'a str>,
+        push: bool,
+    ) -> SyncFuture<'a, ()>;
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>>;
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()>;
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>>;
+}
+
+impl<P: SyncProvider> AnyProvider for P {
+    fn id(&self) -> &'static str {
+        SyncProvider::id(self)
+    }
+
+    fn name(&self) -> &'static str {
+        SyncProvider::name(self)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        SyncProvider::capabilities(self)
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        SyncProvider::authenticate(self, db, account_id)
+    }
+
+    fn sync<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        account_id: Option<&'a str>,
+        push: bool,
+    ) -> SyncFuture<'a, ()> {
+        Box::pin(async move { run(app, self, account_id, push).await.map(drop) })
+    }
+
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        SyncProvider::collections(self, db, account_id)
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        SyncProvider::select_collection(self, db, id, selected)
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        SyncProvider::check(self, db, account_id)
+    }
+}
+
+/// A list, calendar, board or project on a service.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteCollection {
+    pub id: String,
+    pub account_id: String,
+    pub name: String,
+    /// Whether it takes part in syncs. Items in the others are neither
+    /// brought in nor uploaded.
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderMode {
+    pub provider: String,
+    pub mode: SyncMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderInfo {
+    pub id: String,
+    pub name: String,
+    pub capabilities: Capabilities,
+}
+
+pub fn find(id: &str) -> CommandResult<&'static dyn AnyProvider> {
+    REGISTRY
+        .iter()
+        .copied()
+        .find(|p| p.id() == id)
+        .ok_or_else(|| CommandError::invalid(format!("Unknown sync provider: {id}")))
+}
+
+/// How `provider` is set to sync: as the user chose, else both ways when
+/// it can push.
+pub fn mode(conn: &Connection, provider: &dyn AnyProvider) -> rusqlite::Result<SyncMode> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT mode FROM sync_modes WHERE provider = ?1",
+            params![provider.id()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let fallback = if provider.capabilities().push {
+        SyncMode::TwoWay
+    } else {
+        SyncMode::Import
+    };
+    Ok(stored
+        .as_deref()
+        .and_then(SyncMode::parse)
+        .unwrap_or(fallback))
+}
+
+/// Providers that upload local changes, so an item can change on both
+/// sides between two syncs.
+pub fn two_way() -> impl Iterator<Item = &'static dyn AnyProvider> {
+    REGISTRY
+        .iter()
+        .copied()
+        .filter(|p| p.capabilities().conflicts)
+}
+
+/// Sync `provider` once, the way its mode allows, pushing or only pulling,
+/// telling the UI how it goes. Fails if it's already syncing, if it only
+/// exports and `push` is false, or if it's paused because it was limiting
+/// requests; it syncs again by itself once the pause is over.
+pub async fn run<P: SyncProvider>(
+    app: &AppHandle,
+    provider: &P,
+    account_id: Option<&str>,
+    push: bool,
+) -> CommandResult<P::Report> {
+    if let Some(until) = rate_limit::until(provider.id()) {
+        retry_after(app, provider.id(), until);
+    }
+    rate_limit::check(provider.id(), provider.name())?;
+    // What a sync brings in isn't the user's edit to undo.
+    let db = app.state::<Db>().unjournaled();
+    let mode = db.with_conn(|conn| mode(conn, provider))?;
+    if mode == SyncMode::Export && !push {
+        return Err(CommandError::invalid(format!(
+            "{} is set to export only",
+            provider.name()
+        )));
+    }
+    let progress = Tracker::try_start(app, provider.id())
+        .ok_or_else(|| format!("A {} sync is already running", provider.name()))?;
+    let started = Instant::now();
+    let result = match mode {
+        SyncMode::TwoWay if push => provider.push(&db, account_id, &progress).await,
+        SyncMode::TwoWay | SyncMode::Import => provider.pull(&db, account_id, &progress).await,
+        SyncMode::Export => provider.export(&db, account_id, &progress).await,
+    };
+    progress.finish(&result);
+    usage::record(
+        &db,
+        &format!("sync:{}", provider.id()),
+        Some(started.elapsed()),
+    );
+    match rate_limit::until(provider.id()) {
+        Some(until) => retry_after(app, provider.id(), until),
+        None => rate_limit::clear(provider.id()),
+    }
+    result
+}
+
+/// `run`, pushing, for a provider's own sync command: its error names the
+/// provider.
+pub async fn run_command<P: SyncProvider>(
+    app: &AppHandle,
+    provider: &P,
+    account_id: Option<&str>,
+) -> CommandResult<P::Report> {
+    run(app, provider, account_id, true)
+        .await
+        .map_err(|e| e.with_provider(provider.id()))
+}
+
+/// Push `id`, every account of it, once its pause ends at `until`, unless
+/// that's already set to happen.
+fn retry_after(app: &AppHandle, id: &'static str, until: DateTime<Utc>) {
+    if !rate_limit::claim_retry(id) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep((until - Utc::now()).to_std().unwrap_or_default()).await;
+        rate_limit::release_retry(id);
+        let result = match find(id) {
+            Ok(provider) => provider.sync(&app, None, true).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("retry of {id} failed: {e}");
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_sync_providers() -> Vec<ProviderInfo> {
+    REGISTRY
+        .iter()
+        .map(|p| ProviderInfo {
+            id: p.id().to_string(),
+            name: p.name().to_string(),
+            capabilities: p.capabilities(),
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_sync_modes(db: State<'_, Db>) -> CommandResult<Vec<ProviderMode>> {
+    db.with_conn(|conn| {
+        REGISTRY
+            .iter()
+            .map(|provider| {
+                Ok(ProviderMode {
+                    provider: provider.id().to_string(),
+                    mode: mode(conn, *provider)?,
+                })
+            })
+            .collect()
+    })
+}
+
+/// Takes effect on the provider's next sync. Providers that can't push
+/// only import. Remote changes left out while exporting only come in once
+/// they change again.
+#[tauri::command]
+pub fn set_sync_mode(
+    db: State<'_, Db>,
+    provider: String,
+    mode: SyncMode,
+) -> CommandResult<ProviderMode> {
+    let found = find(&provider)?;
+    if mode != SyncMode::Import && !found.capabilities().push {
+        return Err(CommandError::invalid(format!(
+            "{} can only import",
+            found.name()
+        )));
+    }
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO sync_modes (provider, mode) VALUES (?1, ?2)
+             ON CONFLICT(provider) DO UPDATE SET mode = excluded.mode",
+            params![provider, mode.as_str()],
+        )
+    })?;
+    Ok(ProviderMode { provider, mode })
+}
+
+/// The lists, calendars, boards or projects `provider` can sync, for
+/// `account_id` or every account, and whether each is selected.
+#[tauri::command]
+pub async fn list_remote_collections(
+    db: State<'_, Db>,
+    provider: String,
+    account_id: Option<String>,
+) -> CommandResult<Vec<RemoteCollection>> {
+    find(&provider)?
+        .collections(&db, account_id.as_deref())
+        .await
+}
+
+/// Takes effect on the provider's next sync. Tasks already brought in
+/// from a collection left out stay.
+#[tauri::command]
+pub fn select_remote_collection(
+    db: State<'_, Db>,
+    provider: String,
+    id: String,
+    selected: bool,
+) -> CommandResult<()> {
+    find(&provider)?.select_collection(&db, &id, selected)
+}
+
+/// Check the credentials of `account_id`, or of every account of
+/// `provider`.
+#[tauri::command]
+pub async fn authenticate_sync_provider(
+    db: State<'_, Db>,
+    provider: String,
+    account_id: Option<String>,
+) -> CommandResult<()> {
+    find(&provider)?
+        .authenticate(&db, account_id.as_deref())
+        .await
+}
+
+/// Sync `provider`, only pulling when `pull_only`, and return its status.
+/// The provider's own command returns the full reports.
+#[tauri::command]
+pub async fn run_sync_provider(
+    app: AppHandle,
+    provider: String,
+    account_id: Option<String>,
+    pull_only: Option<bool>,
+) -> CommandResult<SyncStatus> {
+    let provider = find(&provider)?;
+    provider
+        .sync(&app, account_id.as_deref(), !pull_only.unwrap_or(false))
+        .await
+        .map_err(|e| e.with_provider(provider.id()))?;
+    app.state::<Db>()
+        .with_conn(|conn| sync_status::load(conn, provider.id()))
+}
@@ -0,0 +1,304 @@
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{format_utc, now_utc, Db};
+use crate::error::CommandResult;
+use crate::rate_limit;
+
+/// Emitted as a sync starts, as each of its collections, lists, boards or
+/// accounts is done, and as it ends.
+pub const SYNC_PROGRESS_EVENT: &str = "sync-progress";
+
+/// Providers whose syncs are tracked, in the order the status page lists
+/// them.
+pub const PROVIDERS: &[&str] = &[
+    "caldav",
+    "deck",
+    "github",
+    "gitlab",
+    "google_tasks",
+    "microsoft_todo",
+    "notion",
+    "orgmode",
+    "todoist",
+    "todotxt",
+    "trello",
+    "webdav",
+];
+
+/// Providers with a sync running right now.
+static RUNNING: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncPhase {
+    Started,
+    /// One target is done; the others may still be going.
+    Synced,
+    /// Done without errors.
+    Finished,
+    /// Done, but with at least one error.
+    Failed,
+    /// Done, but the provider is limiting requests; it syncs again once
+    /// that's over.
+    Throttled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncProgress {
+    pub provider: String,
+    pub phase: SyncPhase,
+    /// The collection, list, board, file or account just synced, for
+    /// `Synced`.
+    pub target: Option<String>,
+    /// Items created, updated, removed or uploaded: by the target for
+    /// `Synced`, by the whole sync once it's done.
+    pub processed: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncStatus {
+    pub provider: String,
+    pub running: bool,
+    pub last_started_at: Option<String>,
+    pub last_finished_at: Option<String>,
+    /// When a sync last finished without errors.
+    pub last_success_at: Option<String>,
+    /// Items the last finished sync processed.
+    pub processed: usize,
+    /// Errors the last finished sync ran into.
+    pub error_count: usize,
+    /// The first of them, saying how many more there were.
+    pub last_error: Option<String>,
+    /// Until when the provider is paused because it was limiting requests.
+    pub throttled_until: Option<String>,
+}
+
+/// A provider's sync report, as far as the status page cares.
+pub trait SyncOutcome {
+    fn processed(&self) -> usize;
+    fn errors(&self) -> Vec<String>;
+}
+
+impl<T: SyncOutcome> SyncOutcome for Vec<T> {
+    fn processed(&self) -> usize {
+        self.iter().map(SyncOutcome::processed).sum()
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.iter().flat_map(SyncOutcome::errors).collect()
+    }
+}
+
+/// Follows one sync of a provider: tells the UI how it goes, and records
+/// how it went.
+pub struct Tracker {
+    app: AppHandle,
+    provider: &'static str,
+}
+
+impl Tracker {
+    pub fn start(app: &AppHandle, provider: &'static str) -> Self {
+        RUNNING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(provider);
+        Self::begin(app, provider)
+    }
+
+    /// Like `start`, unless a sync of `provider` is already running.
+    pub fn try_start(app: &AppHandle, provider: &'static str) -> Option<Self> {
+        {
+            let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+            if running.contains(&provider) {
+                return None;
+            }
+            running.push(provider);
+        }
+        Some(Self::begin(app, provider))
+    }
+
+    fn begin(app: &AppHandle, provider: &'static str) -> Self {
+        let result = app.state::<Db>().with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO sync_status (provider, last_started_at) VALUES (?1, ?2)
+                 ON CONFLICT(provider) DO UPDATE SET last_started_at = excluded.last_started_at",
+                params![provider, now_utc()],
+            )
+        });
+        if let Err(e) = result {
+            tracing::warn!("couldn't record the start of a {provider} sync: {e}");
+        }
+        let tracker = Tracker {
+            app: app.clone(),
+            provider,
+        };
+        tracker.emit(SyncPhase::Started, None, 0, Vec::new());
+        tracker
+    }
+
+    /// Report that `target` is done.
+    pub fn record(&self, target: &str, outcome: &impl SyncOutcome) {
+        self.emit(
+            SyncPhase::Synced,
+            Some(target.to_string()),
+            outcome.processed(),
+            outcome.errors(),
+        );
+    }
+
+    /// Report how the whole sync went and keep it for `get_sync_status`.
+    pub fn finish(self, result: &CommandResult<impl SyncOutcome>) {
+        let (processed, errors) = match result {
+            Ok(outcome) => (outcome.processed(), outcome.errors()),
+            Err(e) => (0, vec![e.to_string()]),
+        };
+        let throttled_until = rate_limit::until(self.provider).map(format_utc);
+        let result = self.app.state::<Db>().with_conn(|conn| {
+            save_finish(
+                conn,
+                self.provider,
+                processed,
+                &errors,
+                throttled_until.as_deref(),
+            )
+        });
+        if let Err(e) = result {
+            tracing::warn!("couldn't record how a {} sync went: {e}", self.provider);
+        }
+        let phase = if throttled_until.is_some() {
+            SyncPhase::Throttled
+        } else if errors.is_empty() {
+            SyncPhase::Finished
+        } else {
+            SyncPhase::Failed
+        };
+        self.emit(phase, None, processed, errors);
+    }
+
+    fn emit(
+        &self,
+        phase: SyncPhase,
+        target: Option<String>,
+        processed: usize,
+        errors: Vec<String>,
+    ) {
+        let _ = self.app.emit(
+            SYNC_PROGRESS_EVENT,
+            SyncProgress {
+                provider: self.provider.to_string(),
+                phase,
+                target,
+                processed,
+                errors,
+            },
+        );
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(i) = running.iter().position(|p| *p == self.provider) {
+            running.remove(i);
+        }
+    }
+}
+
+/// The first error, saying how many more there were.
+fn summarize(errors: &[String]) -> Option<String> {
+    let first = errors.first()?;
+    Some(match errors.len() {
+        1 => first.clone(),
+        n => format!("{first} (and {} more)", n - 1),
+    })
+}
+
+fn save_finish(
+    conn: &Connection,
+    provider: &str,
+    processed: usize,
+    errors: &[String],
+    throttled_until: Option<&str>,
+) -> rusqlite::Result<usize> {
+    let now = now_utc();
+    conn.execute(
+        "INSERT INTO sync_status
+             (provider, last_started_at, last_finished_at, last_success_at,
+              processed, error_count, last_error, throttled_until)
+         VALUES (?1, ?2, ?2, CASE WHEN ?4 = 0 THEN ?2 END, ?3, ?4, ?5, ?6)
+         ON CONFLICT(provider) DO UPDATE SET
+             last_finished_at = excluded.last_finished_at,
+             last_success_at = COALESCE(excluded.last_success_at, last_success_at),
+             processed = excluded.processed,
+             error_count = excluded.error_count,
+             last_error = excluded.last_error,
+             throttled_until = excluded.throttled_until",
+        params![
+            provider,
+            now,
+            processed as i64,
+            errors.len() as i64,
+            summarize(errors),
+            throttled_until,
+        ],
+    )
+}
+
+fn row_to_status(row: &Row) -> rusqlite::Result<SyncStatus> {
+    Ok(SyncStatus {
+        provider: row.get(0)?,
+        running: false,
+        last_started_at: row.get(1)?,
+        last_finished_at: row.get(2)?,
+        last_success_at: row.get(3)?,
+        processed: row.get::<_, i64>(4)? as usize,
+        error_count: row.get::<_, i64>(5)? as usize,
+        last_error: row.get(6)?,
+        throttled_until: row.get(7)?,
+    })
+}
+
+pub fn load(conn: &Connection, provider: &str) -> rusqlite::Result<SyncStatus> {
+    let status = conn
+        .query_row(
+            "SELECT provider, last_started_at, last_finished_at, last_success_at,
+                    processed, error_count, last_error, throttled_until
+             FROM sync_status WHERE provider = ?1",
+            params![provider],
+            row_to_status,
+        )
+        .optional()?;
+    Ok(status.unwrap_or_else(|| SyncStatus {
+        provider: provider.to_string(),
+        running: false,
+        last_started_at: None,
+        last_finished_at: None,
+        last_success_at: None,
+        processed: 0,
+        error_count: 0,
+        last_error: None,
+        throttled_until: None,
+    }))
+}
+
+/// How each provider's last sync went, those never synced included.
+#[tauri::command]
+pub fn get_sync_status(db: State<'_, Db>) -> CommandResult<Vec<SyncStatus>> {
+    let running = RUNNING.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    db.with_conn(|conn| {
+        PROVIDERS
+            .iter()
+            .map(|provider| {
+                Ok(SyncStatus {
+                    running: running.contains(provider),
+                    ..load(conn, provider)?
+                })
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::task_store::Task;
+
+const TAG_COLUMNS: &str = "id, name, color, created_at, \
+     (SELECT COUNT(*) FROM task_tags tt JOIN tasks t ON t.id = tt.task_id \
+      WHERE tt.tag_id = tags.id AND t.deleted_at IS NULL)";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tag {
+    pub id: String,
+    pub name: String,
+    pub color: Option<String>,
+    pub created_at: String,
+    pub task_count: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TagPatch {
+    pub name: Option<String>,
+    /// `Some("")` clears the color.
+    pub color: Option<String>,
+}
+
+fn row_to_tag(row: &Row) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+        created_at: row.get(3)?,
+        task_count: row.get(4)?,
+    })
+}
+
+/// Trim a tag name and drop a leading `#`, so "#work" and "work" are the same tag.
+pub fn normalize_name(name: &str) -> CommandResult<String> {
+    let name = name.trim().trim_start_matches('#').trim();
+    if name.is_empty() {
+        return Err(CommandError::invalid("Tag name cannot be empty"));
+    }
+    Ok(name.to_string())
+}
+
+/// Normalize a list of names, dropping case-insensitive duplicates.
+pub fn normalize_names(names: &[String]) -> CommandResult<Vec<String>> {
+    let mut out: Vec<String> = Vec::new();
+    for name in names {
+        let name = normalize_name(name)?;
+        if !out.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+            out.push(name);
+        }
+    }
+    Ok(out)
+}
+
+pub fn find_tag(conn: &Connection, id: &str) -> rusqlite::Result<Option<Tag>> {
+    conn.query_row(
+        &format!("SELECT {TAG_COLUMNS} FROM tags WHERE id = ?1"),
+        params![id],
+        row_to_tag,
+    )
+    .optional()
+}
+
+/// Look a tag up by name. Names are unique case-insensitively.
+pub fn find_tag_by_name(conn: &Connection, name: &str) -> rusqlite::Result<Option<Tag>> {
+    conn.query_row(
+        &format!("SELECT {TAG_COLUMNS} FROM tags WHERE name = ?1"),
+        params![name],
+        row_to_tag,
+    )
+    .optional()
+}
+
+/// Return the tag called `name`, creating it if needed.
+pub fn ensure_tag(conn: &Connection, name: &str) -> rusqlite::Result<Tag> {
+    if let Some(tag) = find_tag_by_name(conn, name)? {
+        return Ok(tag);
+    }
+    let tag = Tag {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        color: None,
+        created_at: now_utc(),
+        task_count: 0,
+    };
+    conn.execute(
+        "INSERT INTO tags (id, name, color, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![tag.id, tag.name, tag.color, tag.created_at],
+    )?;
+    Ok(tag)
+}
+
+/// Replace a task's tags with `names` (already normalized), creating any
+/// tags that don't exist yet.
+pub fn set_task_tags(conn: &Connection, task_id: &str, names: &[String]) -> rusqlite::Result<()> {
+    let mut keep: Vec<String> = Vec::new();
+    for name in names {
+        let tag = ensure_tag(conn, name)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag_id) VALUES (?1, ?2)",
+            params![task_id, tag.id],
+        )?;
+        keep.push(tag.id);
+    }
+    conn.execute(
+        "DELETE FROM task_tags WHERE task_id = ?1
+         AND tag_id NOT IN (SELECT value FROM json_each(?2))",
+        params![task_id, serde_json::to_string(&keep).unwrap_or_default()],
+    )?;
+    Ok(())
+}
+
+/// Fill in `tags` for each task with one query.
+pub fn load_task_tags(conn: &Connection, tasks: &mut [Task]) -> rusqlite::Result<()> {
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+    let mut stmt = conn.prepare(
+        "SELECT tt.task_id, g.name FROM task_tags tt
+         JOIN tags g ON g.id = tt.tag_id
+         WHERE tt.task_id IN (SELECT value FROM json_each(?1))
+         ORDER BY g.name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map(
+        params![serde_json::to_string(&ids).unwrap_or_default()],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+
+    let mut by_task: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows {
+        let (task_id, name) = row?;
+        by_task.entry(task_id).or_default().push(name);
+    }
+    for task in tasks.iter_mut() {
+        task.tags = by_task.remove(&task.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_tags(db: State<'_, Db>) -> CommandResult<Vec<Tag>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TAG_COLUMNS} FROM tags ORDER BY name COLLATE NOCASE"
+        ))?;
+        let rows = stmt.query_map([], row_to_tag)?;
+        rows.collect()
+    })
+}
+
+#[tauri::command]
+pub fn create_tag(db: State<'_, Db>, name: String, color: Option<String>) -> CommandResult<Tag> {
+    let name = normalize_name(&name)?;
+    db.with_conn(|conn| {
+        let tag = ensure_tag(conn, &name)?;
+        if color.is_some() {
+            conn.execute(
+                "UPDATE tags SET color = ?2 WHERE id = ?1",
+                params![tag.id, color],
+            )?;
+        }
+        find_tag(conn, &tag.id).map(|t| t.unwrap_or(tag))
+    })
+}
+
+/// Rename or recolor a tag. Renaming onto another existing tag is refused;
+/// use `merge_tags` for that.
+#[tauri::command]
+pub fn update_tag(db: State<'_, Db>, id: String, patch: TagPatch) -> CommandResult<Tag> {
+    let name = patch.name.as_deref().map(normalize_name).transpose()?;
+
+    db.with_conn(|conn| {
+        let Some(tag) = find_tag(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!("Tag not found: {id}"))));
+        };
+        if let Some(name) = &name {
+            if let Some(other) = find_tag_by_name(conn, name)? {
+                if other.id != tag.id {
+                    return Ok(Err(CommandError::conflict(format!(
+                        "A tag named '{}' already exists; merge the tags instead",
+                        other.name
+                    ))));
+                }
+            }
+            conn.execute("UPDATE tags SET name = ?2 WHERE id = ?1", params![id, name])?;
+        }
+        if let Some(color) = &patch.color {
+            let color = (!color.trim().is_empty()).then_some(color);
+            conn.execute(
+                "UPDATE tags SET color = ?2 WHERE id = ?1",
+                params![id, color],
+            )?;
+        }
+        Ok(find_tag(conn, &id)?
+            .ok_or_else(|| CommandError::not_found(format!("Tag not found: {id}"))))
+    })?
+}
+
+#[tauri::command]
+pub fn delete_tag(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM tags WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!("Tag not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Move every task tagged with one of `sources` onto `target`, then delete
+/// the source tags.
+#[tauri::command]
+pub fn merge_tags(db: State<'_, Db>, sources: Vec<String>, target: String) -> CommandResult<Tag> {
+    let sources: Vec<String> = sources.into_iter().filter(|s| *s != target).collect();
+    let sources_json = serde_json::to_string(&sources).map_err(|e| e.to_string())?;
+
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        if find_tag(&tx, &target)?.is_none() {
+            return Ok(None);
+        }
+        tx.execute(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag_id)
+             SELECT task_id, ?1 FROM task_tags
+             WHERE tag_id IN (SELECT value FROM json_each(?2))",
+            params![target, sources_json],
+        )?;
+        tx.execute(
+            "DELETE FROM tags WHERE id IN (SELECT value FROM json_each(?1))",
+            params![sources_json],
+        )?;
+        let merged = find_tag(&tx, &target)?;
+        tx.commit()?;
+        Ok(merged)
+    })?
+    .ok_or_else(|| CommandError::not_found(format!("Tag not found: {target}")))
+}
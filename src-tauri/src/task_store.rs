@@ -0,0 +1,778 @@
+use rusqlite::types::{ToSql, Value};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value as Json;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::{now_utc, Db};
+use crate::dependencies::{self, BLOCKED_SQL};
+use crate::error::{CommandError, CommandResult};
+use crate::projects;
+use crate::recurrence;
+use crate::rrule::Rrule;
+use crate::subtasks;
+use crate::tags;
+use crate::time_entries;
+use crate::timezone;
+use crate::trash;
+
+pub const STATUS_OPEN: &str = "open";
+pub const STATUS_DONE: &str = "done";
+
+const DEFAULT_PAGE_SIZE: usize = 200;
+const MAX_PAGE_SIZE: usize = 1000;
+
+pub const TASK_COLUMNS: &str = "id, title, description, status, project, priority, due, \
+     scheduled, created_at, updated_at, completed_at, recurrence, series_id, tz, parent_id, sort_key, \
+     estimate_minutes";
+/// How many columns `TASK_COLUMNS` selects; extra columns after them start
+/// at this index.
+pub const TASK_COLUMN_COUNT: usize = 17;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Task {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub status: String,
+    pub project: Option<String>,
+    pub priority: Option<i64>,
+    pub due: Option<String>,
+    pub scheduled: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub completed_at: Option<String>,
+    /// RRULE string (see `rrule::Rrule`). Completing the task materializes
+    /// the next instance.
+    pub recurrence: Option<String>,
+    /// Shared by every instance of a recurring task: the id of the first one.
+    pub series_id: Option<String>,
+    /// IANA zone the task was created in. Timestamps are UTC; dates and
+    /// times in `due`/`scheduled` are wall-clock in this zone.
+    pub tz: Option<String>,
+    /// The task this is a subtask of.
+    pub parent_id: Option<String>,
+    /// Order among its siblings (see `order_key`); set for subtasks.
+    pub sort_key: Option<String>,
+    /// Expected minutes of work. Sizes the task's blocks in a proposed day
+    /// plan.
+    pub estimate_minutes: Option<i64>,
+    /// Tag names, sorted case-insensitively.
+    pub tags: Vec<String>,
+    /// Waiting on a task that isn't done yet; see `dependencies`.
+    pub is_blocked: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTask {
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i64>,
+    #[serde(default)]
+    pub due: Option<String>,
+    #[serde(default)]
+    pub scheduled: Option<String>,
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Create it as the last subtask of this task.
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub estimate_minutes: Option<i64>,
+}
+
+/// Partial update. For nullable fields, a missing key leaves the value alone
+/// while an explicit `null` clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TaskPatch {
+    pub title: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    pub status: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub priority: Option<Option<i64>>,
+    #[serde(deserialize_with = "double_option")]
+    pub due: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub scheduled: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub recurrence: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub estimate_minutes: Option<Option<i64>>,
+    /// Replaces the task's tags when present.
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TaskFilter {
+    pub status: Option<String>,
+    pub project: Option<String>,
+    /// Only tasks scheduled on this date (YYYY-MM-DD).
+    pub scheduled: Option<String>,
+    /// Only tasks due on or before this date (YYYY-MM-DD).
+    pub due_before: Option<String>,
+    /// Only tasks carrying every one of these tags.
+    pub tags_all: Vec<String>,
+    /// Only tasks carrying at least one of these tags.
+    pub tags_any: Vec<String>,
+    /// Exclude tasks carrying any of these tags.
+    pub tags_none: Vec<String>,
+    /// Only blocked tasks, or only unblocked ones. Open and unblocked is the
+    /// next-actions list.
+    pub blocked: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    Title,
+    Status,
+    Project,
+    Priority,
+    Due,
+    Scheduled,
+    CreatedAt,
+    UpdatedAt,
+    CompletedAt,
+}
+
+impl SortField {
+    fn column(self) -> &'static str {
+        match self {
+            SortField::Title => "title COLLATE NOCASE",
+            SortField::Status => "status",
+            SortField::Project => "project COLLATE NOCASE",
+            SortField::Priority => "priority",
+            SortField::Due => "due",
+            SortField::Scheduled => "scheduled",
+            SortField::CreatedAt => "created_at",
+            SortField::UpdatedAt => "updated_at",
+            SortField::CompletedAt => "completed_at",
+        }
+    }
+
+    fn value(self, task: &Task) -> Value {
+        let text = |s: &Option<String>| s.clone().map_or(Value::Null, Value::Text);
+        match self {
+            SortField::Title => Value::Text(task.title.clone()),
+            SortField::Status => Value::Text(task.status.clone()),
+            SortField::Project => text(&task.project),
+            SortField::Priority => task.priority.map_or(Value::Null, Value::Integer),
+            SortField::Due => text(&task.due),
+            SortField::Scheduled => text(&task.scheduled),
+            SortField::CreatedAt => Value::Text(task.created_at.clone()),
+            SortField::UpdatedAt => Value::Text(task.updated_at.clone()),
+            SortField::CompletedAt => text(&task.completed_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SortKey {
+    pub field: SortField,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    /// Earlier keys win; ties go by id. Defaults to `created_at`.
+    pub sort: Vec<SortKey>,
+    /// Defaults to 200, at most 1000.
+    pub limit: Option<usize>,
+    /// `next_cursor` from the previous page, with the same filter and sort.
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskPage {
+    pub tasks: Vec<Task>,
+    /// Absent on the last page.
+    pub next_cursor: Option<String>,
+    /// How many tasks match the filter, on the first page only.
+    pub total: Option<i64>,
+}
+
+pub fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+pub fn row_to_task(row: &Row) -> rusqlite::Result<Task> {
+    Ok(Task {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        status: row.get(3)?,
+        project: row.get(4)?,
+        priority: row.get(5)?,
+        due: row.get(6)?,
+        scheduled: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        completed_at: row.get(10)?,
+        recurrence: row.get(11)?,
+        series_id: row.get(12)?,
+        tz: row.get(13)?,
+        parent_id: row.get(14)?,
+        sort_key: row.get(15)?,
+        estimate_minutes: row.get(16)?,
+        tags: Vec::new(),
+        is_blocked: false,
+    })
+}
+
+pub fn validate_status(status: &str) -> CommandResult<()> {
+    match status {
+        STATUS_OPEN | STATUS_DONE => Ok(()),
+        other => Err(CommandError::invalid(format!(
+            "Invalid task status: {other}"
+        ))),
+    }
+}
+
+pub fn validate_title(title: &str) -> CommandResult<String> {
+    let trimmed = title.trim();
+    if trimmed.is_empty() {
+        return Err(CommandError::invalid("Task title cannot be empty"));
+    }
+    Ok(trimmed.to_string())
+}
+
+pub fn validate_estimate(minutes: i64) -> CommandResult<i64> {
+    if minutes <= 0 {
+        return Err(CommandError::invalid("Estimate must be at least a minute"));
+    }
+    Ok(minutes)
+}
+
+/// Parse and re-serialize a recurrence rule so stored rules are canonical.
+pub fn validate_recurrence(rule: &str) -> CommandResult<String> {
+    Rrule::parse(rule).map(|r| r.to_string())
+}
+
+pub fn find_task(conn: &Connection, id: &str) -> rusqlite::Result<Option<Task>> {
+    let task = conn
+        .query_row(
+            &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE id = ?1 AND deleted_at IS NULL"),
+            params![id],
+            row_to_task,
+        )
+        .optional()?;
+    let Some(mut task) = task else {
+        return Ok(None);
+    };
+    tags::load_task_tags(conn, std::slice::from_mut(&mut task))?;
+    dependencies::load_blocked(conn, std::slice::from_mut(&mut task))?;
+    Ok(Some(task))
+}
+
+pub fn insert_task(conn: &Connection, input: &NewTask, title: String) -> rusqlite::Result<Task> {
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let task = Task {
+        id: id.clone(),
+        title,
+        description: input.description.clone(),
+        status: STATUS_OPEN.to_string(),
+        project: input.project.clone(),
+        priority: input.priority,
+        due: input.due.clone(),
+        scheduled: input.scheduled.clone(),
+        created_at: now.clone(),
+        updated_at: now,
+        completed_at: None,
+        series_id: input.recurrence.as_ref().map(|_| id),
+        recurrence: input.recurrence.clone(),
+        tz: Some(timezone::system_zone()),
+        sort_key: match &input.parent_id {
+            Some(parent_id) => Some(subtasks::append_key(conn, parent_id)?),
+            None => None,
+        },
+        parent_id: input.parent_id.clone(),
+        estimate_minutes: input.estimate_minutes,
+        tags: Vec::new(),
+        is_blocked: false,
+    };
+    write_task(conn, &task)?;
+    Ok(task)
+}
+
+/// Insert a task row, or overwrite every column of an existing one. This is an
+/// upsert rather than `INSERT OR REPLACE` so that rewriting a task does not
+/// delete the row and cascade into rows referencing it. `parent_id` and
+/// `sort_key` are only written on insert; `subtasks` moves tasks after that.
+pub fn write_task(conn: &Connection, task: &Task) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO tasks ({TASK_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+             ON CONFLICT(id) DO UPDATE SET
+                 title = excluded.title,
+                 description = excluded.description,
+                 status = excluded.status,
+                 project = excluded.project,
+                 priority = excluded.priority,
+                 due = excluded.due,
+                 scheduled = excluded.scheduled,
+                 created_at = excluded.created_at,
+                 updated_at = excluded.updated_at,
+                 completed_at = excluded.completed_at,
+                 recurrence = excluded.recurrence,
+                 series_id = excluded.series_id,
+                 tz = excluded.tz,
+                 estimate_minutes = excluded.estimate_minutes"
+        ),
+        params![
+            task.id,
+            task.title,
+            task.description,
+            task.status,
+            task.project,
+            task.priority,
+            task.due,
+            task.scheduled,
+            task.created_at,
+            task.updated_at,
+            task.completed_at,
+            task.recurrence,
+            task.series_id,
+            task.tz,
+            task.parent_id,
+            task.sort_key,
+            task.estimate_minutes,
+        ],
+    )?;
+    if let Some(project) = &task.project {
+        projects::ensure_project(conn, project)?;
+    }
+    Ok(())
+}
+
+/// Apply `patch` to `task` in memory, maintaining `completed_at` on status changes.
+pub fn apply_patch(task: &mut Task, patch: &TaskPatch) {
+    if let Some(title) = &patch.title {
+        task.title = title.clone();
+    }
+    if let Some(description) = &patch.description {
+        task.description = description.clone();
+    }
+    if let Some(status) = &patch.status {
+        if *status != task.status {
+            task.completed_at = if status == STATUS_DONE {
+                Some(now_utc())
+            } else {
+                None
+            };
+        }
+        task.status = status.clone();
+    }
+    if let Some(project) = &patch.project {
+        task.project = project.clone();
+    }
+    if let Some(priority) = patch.priority {
+        task.priority = priority;
+    }
+    if let Some(due) = &patch.due {
+        task.due = due.clone();
+    }
+    if let Some(scheduled) = &patch.scheduled {
+        task.scheduled = scheduled.clone();
+    }
+    if let Some(recurrence) = &patch.recurrence {
+        task.recurrence = recurrence.clone();
+        if task.recurrence.is_some() && task.series_id.is_none() {
+            task.series_id = Some(task.id.clone());
+        }
+    }
+    if let Some(estimate) = patch.estimate_minutes {
+        task.estimate_minutes = estimate;
+    }
+    if let Some(tags) = &patch.tags {
+        task.tags = tags.clone();
+    }
+    task.updated_at = now_utc();
+}
+
+/// Check and canonicalize the fields of a patch from the frontend.
+pub fn validate_patch(patch: TaskPatch) -> CommandResult<TaskPatch> {
+    if let Some(status) = &patch.status {
+        validate_status(status)?;
+    }
+    Ok(TaskPatch {
+        title: patch.title.as_deref().map(validate_title).transpose()?,
+        tags: patch
+            .tags
+            .as_deref()
+            .map(tags::normalize_names)
+            .transpose()?,
+        recurrence: match patch.recurrence {
+            Some(Some(rule)) => Some(Some(validate_recurrence(&rule)?)),
+            other => other,
+        },
+        estimate_minutes: match patch.estimate_minutes {
+            Some(Some(minutes)) => Some(Some(validate_estimate(minutes)?)),
+            other => other,
+        },
+        ..patch
+    })
+}
+
+/// Apply a validated patch to `task` and write it. When that completes the
+/// task, also returns the next instance of a recurring one and the tasks it
+/// no longer blocks.
+pub fn save_patch(
+    conn: &Connection,
+    mut task: Task,
+    patch: &TaskPatch,
+) -> rusqlite::Result<(Task, Option<Task>, Vec<Task>)> {
+    let was_done = task.status == STATUS_DONE;
+    apply_patch(&mut task, patch);
+    write_task(conn, &task)?;
+    if let Some(names) = &patch.tags {
+        tags::set_task_tags(conn, &task.id, names)?;
+        tags::load_task_tags(conn, std::slice::from_mut(&mut task))?;
+    }
+    if was_done || task.status != STATUS_DONE {
+        return Ok((task, None, Vec::new()));
+    }
+    let next = recurrence::next_instance(conn, &task, recurrence::today())?;
+    let unblocked = dependencies::unblocked_by(conn, &task.id)?;
+    Ok((task, next, unblocked))
+}
+
+/// Ids of tasks carrying any tag named in the JSON array bound to `?`.
+const TAGGED_SQL: &str = "SELECT tt.task_id FROM task_tags tt \
+     JOIN tags g ON g.id = tt.tag_id \
+     WHERE g.name IN (SELECT value FROM json_each(?))";
+
+fn tag_list(names: &[String]) -> String {
+    serde_json::to_string(names).unwrap_or_default()
+}
+
+/// WHERE conditions for `filter`, with their `?` values in order.
+fn filter_clauses(filter: &TaskFilter) -> (Vec<String>, Vec<Box<dyn ToSql>>) {
+    let mut clauses: Vec<String> = vec!["deleted_at IS NULL".to_string()];
+    let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+    if let Some(status) = &filter.status {
+        clauses.push("status = ?".to_string());
+        values.push(Box::new(status.clone()));
+    }
+    if let Some(project) = &filter.project {
+        clauses.push("project = ?".to_string());
+        values.push(Box::new(project.clone()));
+    }
+    if let Some(scheduled) = &filter.scheduled {
+        clauses.push("scheduled = ?".to_string());
+        values.push(Box::new(scheduled.clone()));
+    }
+    if let Some(due_before) = &filter.due_before {
+        clauses.push("due IS NOT NULL AND due <= ?".to_string());
+        values.push(Box::new(due_before.clone()));
+    }
+    if !filter.tags_all.is_empty() {
+        clauses.push(format!(
+            "id IN ({TAGGED_SQL} GROUP BY tt.task_id HAVING COUNT(*) = ?)"
+        ));
+        values.push(Box::new(tag_list(&filter.tags_all)));
+        values.push(Box::new(filter.tags_all.len() as i64));
+    }
+    if !filter.tags_any.is_empty() {
+        clauses.push(format!("id IN ({TAGGED_SQL})"));
+        values.push(Box::new(tag_list(&filter.tags_any)));
+    }
+    if !filter.tags_none.is_empty() {
+        clauses.push(format!("id NOT IN ({TAGGED_SQL})"));
+        values.push(Box::new(tag_list(&filter.tags_none)));
+    }
+    match filter.blocked {
+        Some(true) => clauses.push(BLOCKED_SQL.to_string()),
+        Some(false) => clauses.push(format!("NOT {BLOCKED_SQL}")),
+        None => {}
+    }
+    (clauses, values)
+}
+
+fn select_tasks(
+    conn: &Connection,
+    sql: &str,
+    values: &[Box<dyn ToSql>],
+) -> rusqlite::Result<Vec<Task>> {
+    let mut stmt = conn.prepare(sql)?;
+    let params: Vec<&dyn ToSql> = values.iter().map(|v| v.as_ref()).collect();
+    let mut tasks = stmt
+        .query_map(params.as_slice(), row_to_task)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    tags::load_task_tags(conn, &mut tasks)?;
+    dependencies::load_blocked(conn, &mut tasks)?;
+    Ok(tasks)
+}
+
+pub fn query_tasks(conn: &Connection, filter: &TaskFilter) -> rusqlite::Result<Vec<Task>> {
+    let (clauses, values) = filter_clauses(filter);
+    let sql = format!(
+        "SELECT {TASK_COLUMNS} FROM tasks WHERE {} ORDER BY created_at, id",
+        clauses.join(" AND ")
+    );
+    select_tasks(conn, &sql, &values)
+}
+
+/// Where a page starts: the sort values of the last task on the previous
+/// page, then its id.
+fn parse_cursor(cursor: &str, sort: &[SortKey]) -> CommandResult<Vec<Value>> {
+    let invalid = || CommandError::invalid("Invalid page cursor");
+    let items: Vec<Json> = serde_json::from_str(cursor).map_err(|_| invalid())?;
+    if items.len() != sort.len() + 1 {
+        return Err(invalid());
+    }
+    items
+        .into_iter()
+        .map(|item| match item {
+            Json::Null => Ok(Value::Null),
+            Json::Number(n) => n.as_i64().map(Value::Integer).ok_or_else(invalid),
+            Json::String(s) => Ok(Value::Text(s)),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+fn make_cursor(task: &Task, sort: &[SortKey]) -> String {
+    let mut items: Vec<Json> = sort
+        .iter()
+        .map(|key| match key.field.value(task) {
+            Value::Integer(i) => Json::from(i),
+            Value::Text(s) => Json::from(s),
+            _ => Json::Null,
+        })
+        .collect();
+    items.push(Json::from(task.id.clone()));
+    Json::Array(items).to_string()
+}
+
+/// One page of the tasks matching `filter`, in `page.sort` order. Empty
+/// values sort last whichever way a key runs, and ties go by id, so paging
+/// neither skips nor repeats a task while the list is unchanged.
+pub fn query_task_page(
+    conn: &Connection,
+    filter: &TaskFilter,
+    page: &PageRequest,
+) -> rusqlite::Result<CommandResult<TaskPage>> {
+    let sort: &[SortKey] = if page.sort.is_empty() {
+        &[SortKey {
+            field: SortField::CreatedAt,
+            descending: false,
+        }]
+    } else {
+        &page.sort
+    };
+    let after = match page.cursor.as_deref().map(|c| parse_cursor(c, sort)) {
+        Some(Ok(after)) => Some(after),
+        Some(Err(e)) => return Ok(Err(e)),
+        None => None,
+    };
+    let limit = page
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let (mut clauses, mut values) = filter_clauses(filter);
+
+    // Only the first page counts, so later pages stay cheap.
+    let total = if after.is_none() {
+        let sql = format!("SELECT COUNT(*) FROM tasks WHERE {}", clauses.join(" AND "));
+        let params: Vec<&dyn ToSql> = values.iter().map(|v| v.as_ref()).collect();
+        Some(conn.query_row(&sql, params.as_slice(), |row| row.get(0))?)
+    } else {
+        None
+    };
+
+    // Each sort key is two: whether the value is empty, then the value.
+    let mut keys: Vec<(String, bool, Value)> = Vec::new();
+    let mut order = Vec::new();
+    for (i, key) in sort.iter().enumerate() {
+        let column = key.field.column();
+        let dir = if key.descending { "DESC" } else { "ASC" };
+        order.push(format!("({column} IS NULL), {column} {dir}"));
+        if let Some(after) = &after {
+            let value = after[i].clone();
+            keys.push((
+                format!("({column} IS NULL)"),
+                false,
+                Value::Integer(matches!(value, Value::Null) as i64),
+            ));
+            keys.push((column.to_string(), key.descending, value));
+        }
+    }
+    order.push("id".to_string());
+    if let Some(after) = &after {
+        keys.push(("id".to_string(), false, after[sort.len()].clone()));
+        let mut alternatives = Vec::new();
+        for (i, (expr, descending, _)) in keys.iter().enumerate() {
+            let mut terms: Vec<String> = keys[..i]
+                .iter()
+                .map(|(e, _, _)| format!("{e} IS ?"))
+                .collect();
+            terms.push(format!("{expr} {} ?", if *descending { "<" } else { ">" }));
+            alternatives.push(format!("({})", terms.join(" AND ")));
+            for (_, _, value) in &keys[..=i] {
+                values.push(Box::new(value.clone()));
+            }
+        }
+        clauses.push(format!("({})", alternatives.join(" OR ")));
+    }
+
+    let sql = format!(
+        "SELECT {TASK_COLUMNS} FROM tasks WHERE {} ORDER BY {} LIMIT {}",
+        clauses.join(" AND "),
+        order.join(", "),
+        limit + 1
+    );
+    let mut tasks = select_tasks(conn, &sql, &values)?;
+    let next_cursor = if tasks.len() > limit {
+        tasks.truncate(limit);
+        tasks.last().map(|task| make_cursor(task, sort))
+    } else {
+        None
+    };
+    Ok(Ok(TaskPage {
+        tasks,
+        next_cursor,
+        total,
+    }))
+}
+
+#[tauri::command]
+pub fn create_task(db: State<'_, Db>, input: NewTask) -> CommandResult<Task> {
+    create(&db, input)
+}
+
+/// Validate and insert a task through `db`, which background jobs pass
+/// unjournaled.
+pub fn create(db: &Db, input: NewTask) -> CommandResult<Task> {
+    let title = validate_title(&input.title)?;
+    let tag_names = tags::normalize_names(&input.tags)?;
+    let input = NewTask {
+        recurrence: input
+            .recurrence
+            .as_deref()
+            .map(validate_recurrence)
+            .transpose()?,
+        estimate_minutes: input.estimate_minutes.map(validate_estimate).transpose()?,
+        ..input
+    };
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        if let Some(parent_id) = &input.parent_id {
+            if find_task(&tx, parent_id)?.is_none() {
+                return Ok(Err(CommandError::not_found(format!(
+                    "Task not found: {parent_id}"
+                ))));
+            }
+        }
+        let mut task = insert_task(&tx, &input, title)?;
+        tags::set_task_tags(&tx, &task.id, &tag_names)?;
+        tags::load_task_tags(&tx, std::slice::from_mut(&mut task))?;
+        tx.commit()?;
+        Ok(Ok(task))
+    })?
+}
+
+#[tauri::command]
+pub fn get_task(db: State<'_, Db>, id: String) -> CommandResult<Option<Task>> {
+    db.with_conn(|conn| find_task(conn, &id))
+}
+
+/// Completing a recurring task also creates its next instance, announced
+/// with `recurrence::RECURRENCE_EVENT`.
+#[tauri::command]
+pub fn update_task(
+    app: AppHandle,
+    db: State<'_, Db>,
+    id: String,
+    patch: TaskPatch,
+) -> CommandResult<Task> {
+    let patch = validate_patch(patch)?;
+
+    let (task, next, unblocked) = db
+        .with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let Some(task) = find_task(&tx, &id)? else {
+                return Ok(None);
+            };
+            let saved = save_patch(&tx, task, &patch)?;
+            tx.commit()?;
+            Ok(Some(saved))
+        })?
+        .ok_or_else(|| CommandError::not_found(format!("Task not found: {id}")))?;
+
+    if let Some(next) = next {
+        let _ = app.emit(recurrence::RECURRENCE_EVENT, vec![next]);
+    }
+    if !unblocked.is_empty() {
+        let _ = app.emit(dependencies::UNBLOCKED_EVENT, unblocked);
+    }
+    Ok(task)
+}
+
+/// Move a task and its subtasks to the trash; see `trash` for restoring and
+/// purging.
+#[tauri::command]
+pub fn delete_task(app: AppHandle, db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let (deleted, stopped) = db.with_conn(|conn| {
+        let was_running = time_entries::running_entry(conn)?.is_some();
+        let tx = conn.transaction()?;
+        let deleted = trash::trash_task(&tx, &id)?;
+        let stopped = was_running && time_entries::running_entry(&tx)?.is_none();
+        tx.commit()?;
+        Ok((deleted, stopped))
+    })?;
+    if !deleted {
+        return Err(CommandError::not_found(format!("Task not found: {id}")));
+    }
+    if stopped {
+        time_entries::notify(&app);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_tasks(db: State<'_, Db>, filter: Option<TaskFilter>) -> CommandResult<Vec<Task>> {
+    let filter = normalize_filter(filter)?;
+    db.with_conn(|conn| query_tasks(conn, &filter))
+}
+
+fn normalize_filter(filter: Option<TaskFilter>) -> CommandResult<TaskFilter> {
+    let filter = filter.unwrap_or_default();
+    Ok(TaskFilter {
+        tags_all: tags::normalize_names(&filter.tags_all)?,
+        tags_any: tags::normalize_names(&filter.tags_any)?,
+        tags_none: tags::normalize_names(&filter.tags_none)?,
+        ..filter
+    })
+}
+
+/// `list_tasks` a page at a time, for lists too long to load at once.
+#[tauri::command]
+pub fn list_tasks_page(
+    db: State<'_, Db>,
+    filter: Option<TaskFilter>,
+    page: Option<PageRequest>,
+) -> CommandResult<TaskPage> {
+    let filter = normalize_filter(filter)?;
+    let page = page.unwrap_or_default();
+    db.with_conn(|conn| query_task_page(conn, &filter, &page))?
+}
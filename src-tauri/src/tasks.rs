@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
+use crate::error::{CommandResult, ResultExt};
+
 /// Minimal frontmatter fields needed for categorization.
 /// All fields optional — missing YAML keys just become None/empty.
 #[derive(Debug, Deserialize, Default)]
@@ -179,10 +181,7 @@ fn is_syncthing_conflict(filename: &str) -> bool {
 }
 
 #[tauri::command]
-pub fn load_grouped_tasks(
-    tasks_dir: String,
-    today: String,
-) -> Result<GroupedTaskFiles, String> {
+pub fn load_grouped_tasks(tasks_dir: String, today: String) -> CommandResult<GroupedTaskFiles> {
     let dir_path = Path::new(&tasks_dir);
 
     if !dir_path.exists() {
@@ -195,7 +194,7 @@ pub fn load_grouped_tasks(
         });
     }
 
-    let entries = fs::read_dir(dir_path).map_err(|e| format!("Failed to read directory: {}", e))?;
+    let entries = fs::read_dir(dir_path).context("Failed to read directory")?;
 
     let mut now = Vec::new();
     let mut past = Vec::new();
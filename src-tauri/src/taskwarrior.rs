@@ -0,0 +1,741 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use tauri::State;
+
+use crate::crdt;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::export::write_streamed;
+use crate::history::{self, ChangeSource};
+use crate::projects;
+use crate::tags;
+use crate::task_store::{self, row_to_task, Task, STATUS_DONE, STATUS_OPEN, TASK_COLUMNS};
+use crate::timezone;
+use crate::trash;
+
+/// Taskwarrior's timestamp format, always UTC.
+const TW_TIME: &str = "%Y%m%dT%H%M%SZ";
+const UNTITLED: &str = "Untitled";
+
+const STATUS_PENDING: &str = "pending";
+const STATUS_WAITING: &str = "waiting";
+const STATUS_COMPLETED: &str = "completed";
+const STATUS_DELETED: &str = "deleted";
+const STATUS_RECURRING: &str = "recurring";
+
+/// Attributes Taskwarrior computes rather than stores; they're dropped on
+/// import and left for it to work out again.
+const COMPUTED: &[&str] = &["id", "urgency"];
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct Annotation {
+    entry: String,
+    description: String,
+}
+
+/// A task as `task export` writes it and `task import` reads it. Attributes
+/// with no counterpart here, UDAs included, are kept in `extra`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct TwTask {
+    uuid: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "default_status")]
+    status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheduled: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wait: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<Annotation>,
+    #[serde(
+        default,
+        deserialize_with = "uuid_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    depends: Vec<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+fn default_status() -> String {
+    STATUS_PENDING.to_string()
+}
+
+/// `depends` is a comma-separated string up to Taskwarrior 2.5 and an
+/// array since.
+fn uuid_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Text(String),
+        Items(Vec<String>),
+    }
+    let uuids = match Option::<List>::deserialize(deserializer)? {
+        None => Vec::new(),
+        Some(List::Text(text)) => text.split(',').map(str::to_string).collect(),
+        Some(List::Items(items)) => items,
+    };
+    Ok(uuids
+        .iter()
+        .map(|u| u.trim().to_lowercase())
+        .filter(|u| !u.is_empty())
+        .collect())
+}
+
+impl TwTask {
+    fn text(&self, key: &str) -> Option<&str> {
+        self.extra.get(key).and_then(Value::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TaskwarriorImportReport {
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because they were deleted in Taskwarrior.
+    pub removed: usize,
+    /// Recurring templates, and deleted tasks that were never imported.
+    pub skipped: usize,
+    /// Parts of tasks that couldn't be carried over.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskwarriorExportSummary {
+    pub tasks: u64,
+}
+
+/// What `taskwarrior_tasks` keeps for a task.
+#[derive(Debug, Clone, Default)]
+struct Stored {
+    uuid: String,
+    annotations: Vec<Annotation>,
+    wait: Option<String>,
+    start: Option<String>,
+    extra: Map<String, Value>,
+}
+
+fn parse_tw_time(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, TW_TIME)
+        .map(|at| at.and_utc())
+        .ok()
+        .or_else(|| parse_utc(value).ok())
+}
+
+fn format_tw_time(at: DateTime<Utc>) -> String {
+    at.format(TW_TIME).to_string()
+}
+
+/// A stored timestamp in Taskwarrior's format.
+fn tw_time(value: &str) -> Option<String> {
+    parse_utc(value).ok().map(format_tw_time)
+}
+
+/// A Taskwarrior due or scheduled time as tasks keep them: a date when at
+/// local midnight, else a wall-clock time in `local`.
+fn local_value(value: &str, local: &Tz) -> Option<String> {
+    let at = parse_tw_time(value)?.with_timezone(local);
+    if at.time() == NaiveTime::MIN {
+        Some(at.date_naive().to_string())
+    } else {
+        Some(at.format("%Y-%m-%dT%H:%M").to_string())
+    }
+}
+
+/// A task's due or scheduled value as a Taskwarrior time; dates are local
+/// midnight.
+fn tw_value(value: &str, local: &Tz) -> Option<String> {
+    let at = match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => date.and_time(NaiveTime::MIN),
+        Err(_) => NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").ok()?,
+    };
+    timezone::resolve_local(at, local).map(|at| format_tw_time(at.with_timezone(&Utc)))
+}
+
+/// H/M/L onto 3/2/1 (high/normal/low).
+fn daylight_priority(value: &str) -> Option<i64> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "H" => Some(3),
+        "M" => Some(2),
+        "L" => Some(1),
+        _ => None,
+    }
+}
+
+fn tw_priority(priority: Option<i64>) -> Option<String> {
+    let value = match priority? {
+        p if p >= 3 => "H",
+        2 => "M",
+        1 => "L",
+        _ => return None,
+    };
+    Some(value.to_string())
+}
+
+/// A recurrence rule for Taskwarrior's `recur` period, starting on
+/// `start`. Only periods a rule can express are carried over.
+fn recurrence(recur: &str, start: NaiveDate) -> Option<String> {
+    let recur = recur.trim().to_lowercase();
+    let (freq, interval) = match recur.as_str() {
+        "daily" | "day" => ("DAILY", 1),
+        "weekdays" => ("WEEKLY;BYDAY=MO,TU,WE,TH,FR", 1),
+        "weekly" | "week" => ("WEEKLY", 1),
+        "biweekly" | "fortnight" => ("WEEKLY", 2),
+        "monthly" | "month" => ("MONTHLY", 1),
+        "bimonthly" => ("MONTHLY", 2),
+        "quarterly" => ("MONTHLY", 3),
+        "semiannual" => ("MONTHLY", 6),
+        "yearly" | "annual" | "year" => ("YEARLY", 1),
+        "biannual" | "biyearly" => ("YEARLY", 2),
+        _ => {
+            let split = recur.find(|c: char| !c.is_ascii_digit())?;
+            let (count, unit) = recur.split_at(split);
+            let count: u32 = count.parse().ok().filter(|&n| n > 0)?;
+            match unit {
+                "d" | "day" | "days" => ("DAILY", count),
+                "w" | "wk" | "wks" | "week" | "weeks" => ("WEEKLY", count),
+                "mo" | "mos" | "month" | "months" => ("MONTHLY", count),
+                "q" | "qtr" | "qtrs" | "quarter" | "quarters" => ("MONTHLY", count * 3),
+                "y" | "yr" | "yrs" | "year" | "years" => ("YEARLY", count),
+                _ => return None,
+            }
+        }
+    };
+    let rule = format!(
+        "DTSTART:{};FREQ={freq};INTERVAL={interval}",
+        start.format("%Y%m%d")
+    );
+    task_store::validate_recurrence(&rule).ok()
+}
+
+/// Parse `task export` output: a JSON array, or one task per line as older
+/// versions wrote.
+fn parse(text: &str) -> CommandResult<Vec<TwTask>> {
+    let text = text.trim();
+    if text.starts_with('[') {
+        return serde_json::from_str(text)
+            .map_err(|e| CommandError::invalid(format!("Not a Taskwarrior export: {e}")));
+    }
+    text.lines()
+        .map(|line| line.trim().trim_end_matches(','))
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .map_err(|e| CommandError::invalid(format!("Not a Taskwarrior export: {e}")))
+        })
+        .collect()
+}
+
+/// The task `uuid` was imported into, or the task with that id when it was
+/// exported from here.
+fn linked_task(conn: &Connection, uuid: &str) -> rusqlite::Result<Option<String>> {
+    let linked = conn
+        .query_row(
+            "SELECT task_id FROM taskwarrior_tasks WHERE uuid = ?1",
+            params![uuid],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if linked.is_some() {
+        return Ok(linked);
+    }
+    conn.query_row(
+        "SELECT id FROM tasks WHERE id = ?1 AND deleted_at IS NULL",
+        params![uuid],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn id_taken(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    conn.query_row("SELECT 1 FROM tasks WHERE id = ?1", params![id], |_| Ok(()))
+        .optional()
+        .map(|found| found.is_some())
+}
+
+fn save_stored(conn: &Connection, task_id: &str, stored: &Stored) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO taskwarrior_tasks (task_id, uuid, annotations, wait, start, extra)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(task_id) DO UPDATE SET
+             uuid = excluded.uuid,
+             annotations = excluded.annotations,
+             wait = excluded.wait,
+             start = excluded.start,
+             extra = excluded.extra",
+        params![
+            task_id,
+            stored.uuid,
+            serde_json::to_string(&stored.annotations).unwrap_or_default(),
+            stored.wait,
+            stored.start,
+            serde_json::to_string(&stored.extra).unwrap_or_default()
+        ],
+    )?;
+    Ok(())
+}
+
+fn load_stored(conn: &Connection) -> rusqlite::Result<HashMap<String, Stored>> {
+    let mut stmt = conn
+        .prepare("SELECT task_id, uuid, annotations, wait, start, extra FROM taskwarrior_tasks")?;
+    let rows = stmt.query_map([], |row| {
+        let annotations: String = row.get(2)?;
+        let extra: String = row.get(5)?;
+        Ok((
+            row.get::<_, String>(0)?,
+            Stored {
+                uuid: row.get(1)?,
+                annotations: serde_json::from_str(&annotations).unwrap_or_default(),
+                wait: row.get(3)?,
+                start: row.get(4)?,
+                extra: serde_json::from_str(&extra).unwrap_or_default(),
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Create or update the task for `item`. A field edited here after the
+/// task was last modified in Taskwarrior keeps the local value. Returns the
+/// task and whether it was created.
+fn write_item(
+    conn: &Connection,
+    item: &TwTask,
+    recurrence: Option<String>,
+    local: &Tz,
+    warnings: &mut Vec<String>,
+) -> rusqlite::Result<(Task, bool)> {
+    let now = now_utc();
+    let uuid = item.uuid.trim().to_lowercase();
+    let existing = match linked_task(conn, &uuid)? {
+        Some(id) => task_store::find_task(conn, &id)?,
+        None => None,
+    };
+    let created = existing.is_none();
+    let mut task = match existing {
+        Some(task) => task,
+        None => {
+            // The uuid becomes the task's id, unless a task had it before.
+            let id = if id_taken(conn, &uuid)? {
+                uuid::Uuid::new_v4().to_string()
+            } else {
+                uuid.clone()
+            };
+            let created_at = item
+                .entry
+                .as_deref()
+                .and_then(parse_tw_time)
+                .map_or_else(|| now.clone(), format_utc);
+            Task {
+                id,
+                title: String::new(),
+                description: None,
+                status: STATUS_OPEN.to_string(),
+                project: None,
+                priority: None,
+                due: None,
+                scheduled: None,
+                created_at,
+                updated_at: now.clone(),
+                completed_at: None,
+                recurrence: None,
+                series_id: None,
+                tz: Some(local.name().to_string()),
+                parent_id: None,
+                sort_key: None,
+                estimate_minutes: None,
+                tags: Vec::new(),
+                is_blocked: false,
+            }
+        }
+    };
+
+    let label = match item.description.trim() {
+        "" => UNTITLED,
+        title => title,
+    };
+    let project = item
+        .project
+        .as_deref()
+        .and_then(|name| match projects::normalize_name(name) {
+            Ok(name) => Some(name),
+            Err(e) => {
+                warnings.push(format!("{label}: project not imported: {e}"));
+                None
+            }
+        });
+    let priority = item.priority.as_deref().and_then(|value| {
+        let priority = daylight_priority(value);
+        if priority.is_none() {
+            warnings.push(format!("{label}: priority {value} not imported"));
+        }
+        priority
+    });
+    let description: Vec<&str> = item
+        .annotations
+        .iter()
+        .map(|a| a.description.as_str())
+        .collect();
+
+    let modified = item
+        .modified
+        .as_deref()
+        .and_then(parse_tw_time)
+        .map(|at| at.timestamp_millis());
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| match (modified, stamps.get(field)) {
+        (Some(modified), Some(stamp)) => stamp.clock <= modified,
+        _ => true,
+    };
+
+    if take("title") {
+        task.title = label.to_string();
+    }
+    if take("description") {
+        task.description = Some(description.join("\n")).filter(|d| !d.is_empty());
+    }
+    if take("project") {
+        task.project = project;
+    }
+    if take("priority") {
+        task.priority = priority;
+    }
+    if take("due") {
+        task.due = item.due.as_deref().and_then(|d| local_value(d, local));
+    }
+    if take("scheduled") {
+        task.scheduled = item
+            .scheduled
+            .as_deref()
+            .and_then(|s| local_value(s, local));
+    }
+    if recurrence.is_some() && take("recurrence") {
+        task.series_id = task.series_id.or_else(|| Some(task.id.clone()));
+        task.recurrence = recurrence;
+    }
+    if take("status") {
+        if item.status == STATUS_COMPLETED {
+            task.status = STATUS_DONE.to_string();
+            task.completed_at = item
+                .end
+                .as_deref()
+                .and_then(parse_tw_time)
+                .map(format_utc)
+                .or(task.completed_at)
+                .or_else(|| Some(now.clone()));
+        } else {
+            task.status = STATUS_OPEN.to_string();
+            task.completed_at = None;
+        }
+    }
+    task.updated_at = now;
+    task_store::write_task(conn, &task)?;
+
+    let tags_edited = match modified {
+        Some(modified) => crdt::newest_tag_clock(conn, &task.id)?.is_some_and(|c| c > modified),
+        None => false,
+    };
+    if !tags_edited {
+        let names = tags::normalize_names(&item.tags).unwrap_or_else(|e| {
+            warnings.push(format!("{label}: tags not imported: {e}"));
+            Vec::new()
+        });
+        tags::set_task_tags(conn, &task.id, &names)?;
+        task.tags = names;
+    }
+
+    let mut extra = item.extra.clone();
+    for key in COMPUTED {
+        extra.remove(*key);
+    }
+    let stored = Stored {
+        uuid,
+        annotations: item.annotations.clone(),
+        wait: item.wait.clone(),
+        start: item.start.clone(),
+        extra,
+    };
+    save_stored(conn, &task.id, &stored)?;
+    Ok((task, created))
+}
+
+/// Import the tasks of a `task export`, in one transaction. Tasks imported
+/// before are updated in place, and keep their uuid for the next export.
+/// Recurring templates aren't tasks here: the next pending instance of
+/// each gets its recurrence instead.
+pub fn import(conn: &mut Connection, text: &str) -> CommandResult<TaskwarriorImportReport> {
+    let items = parse(text)?;
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let mut report = TaskwarriorImportReport::default();
+
+    let templates: HashMap<&str, &str> = items
+        .iter()
+        .filter(|t| t.status == STATUS_RECURRING)
+        .filter_map(|t| Some((t.uuid.as_str(), t.text("recur")?)))
+        .collect();
+    let mut heads: HashMap<&str, &TwTask> = HashMap::new();
+    for item in &items {
+        let Some(parent) = item.text("parent") else {
+            continue;
+        };
+        if item.status != STATUS_PENDING || !templates.contains_key(parent) {
+            continue;
+        }
+        let due = item.due.as_deref().and_then(parse_tw_time);
+        let earlier = heads
+            .get(parent)
+            .and_then(|head| head.due.as_deref().and_then(parse_tw_time))
+            .is_none_or(|head| due.is_some_and(|due| due < head));
+        if earlier {
+            heads.insert(parent, item);
+        }
+    }
+
+    let db_err = |e: rusqlite::Error| format!("Failed to import Taskwarrior tasks: {e}");
+    let tx = conn.transaction().map_err(db_err)?;
+    let mut imported: HashMap<String, String> = HashMap::new();
+    for item in &items {
+        let uuid = item.uuid.trim().to_lowercase();
+        match item.status.as_str() {
+            STATUS_RECURRING => {
+                report.skipped += 1;
+                continue;
+            }
+            STATUS_DELETED => {
+                match linked_task(&tx, &uuid).map_err(db_err)? {
+                    Some(id) if trash::trash_task(&tx, &id).map_err(db_err)? => report.removed += 1,
+                    _ => report.skipped += 1,
+                }
+                continue;
+            }
+            STATUS_PENDING | STATUS_WAITING | STATUS_COMPLETED => {}
+            other => {
+                report
+                    .warnings
+                    .push(format!("{}: unknown status {other}", item.description));
+            }
+        }
+        let recurrence = item
+            .text("parent")
+            .filter(|parent| heads.get(parent).is_some_and(|h| std::ptr::eq(*h, item)))
+            .and_then(|parent| {
+                let start = item.due.as_deref().and_then(|d| local_value(d, &local))?;
+                let start = NaiveDate::parse_from_str(&start[..10], "%Y-%m-%d").ok()?;
+                let rule = recurrence(templates[parent], start);
+                if rule.is_none() {
+                    report.warnings.push(format!(
+                        "{}: recurrence {} not imported",
+                        item.description, templates[parent]
+                    ));
+                }
+                rule
+            });
+        let (task, created) =
+            write_item(&tx, item, recurrence, &local, &mut report.warnings).map_err(db_err)?;
+        if created {
+            report.created += 1;
+        } else {
+            report.updated += 1;
+        }
+        imported.insert(uuid, task.id);
+    }
+
+    // Dependencies once every task exists, Taskwarrior's replacing the
+    // task's blockers.
+    for item in &items {
+        let Some(task_id) = imported.get(&item.uuid.trim().to_lowercase()) else {
+            continue;
+        };
+        tx.execute(
+            "DELETE FROM task_dependencies WHERE task_id = ?1",
+            params![task_id],
+        )
+        .map_err(db_err)?;
+        for uuid in &item.depends {
+            let blocker = match imported.get(uuid) {
+                Some(id) => Some(id.clone()),
+                None => linked_task(&tx, uuid).map_err(db_err)?,
+            };
+            let Some(blocker) = blocker.filter(|b| b != task_id) else {
+                continue;
+            };
+            tx.execute(
+                "INSERT INTO task_dependencies (task_id, blocker_id, created_at)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT DO NOTHING",
+                params![task_id, blocker, now_utc()],
+            )
+            .map_err(db_err)?;
+        }
+    }
+    tx.commit().map_err(db_err)?;
+    Ok(report)
+}
+
+/// The annotations for a task's description, a line each. Lines that were
+/// annotations in Taskwarrior keep their timestamps.
+fn annotations(task: &Task, stored: &[Annotation]) -> Vec<Annotation> {
+    let mut known = stored.to_vec();
+    let entry = tw_time(&task.updated_at).unwrap_or_default();
+    task.description
+        .as_deref()
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(
+            |line| match known.iter().position(|a| a.description == line) {
+                Some(i) => known.remove(i),
+                None => Annotation {
+                    entry: entry.clone(),
+                    description: line.to_string(),
+                },
+            },
+        )
+        .collect()
+}
+
+fn to_tw(task: &Task, stored: Option<&Stored>, depends: Vec<String>, local: &Tz) -> TwTask {
+    let stored = stored.cloned().unwrap_or_default();
+    let done = task.status == STATUS_DONE;
+    TwTask {
+        uuid: match stored.uuid.as_str() {
+            "" => task.id.clone(),
+            uuid => uuid.to_string(),
+        },
+        description: task.title.clone(),
+        status: if done {
+            STATUS_COMPLETED
+        } else {
+            STATUS_PENDING
+        }
+        .to_string(),
+        entry: tw_time(&task.created_at),
+        modified: tw_time(&task.updated_at),
+        end: if done {
+            tw_time(task.completed_at.as_deref().unwrap_or(&task.updated_at))
+        } else {
+            None
+        },
+        due: task.due.as_deref().and_then(|d| tw_value(d, local)),
+        scheduled: task.scheduled.as_deref().and_then(|s| tw_value(s, local)),
+        wait: stored.wait,
+        start: if done { None } else { stored.start },
+        project: task.project.clone(),
+        priority: tw_priority(task.priority),
+        tags: task.tags.clone(),
+        annotations: annotations(task, &stored.annotations),
+        depends,
+        extra: stored.extra,
+    }
+}
+
+/// Write every task not in the trash to `path` as `task import` reads it.
+/// Tasks from Taskwarrior go back under their uuid with their annotations,
+/// wait, start and UDAs; others under their id. Recurrence only goes back
+/// for instances of a Taskwarrior template, which keep its period.
+pub fn export(conn: &Connection, path: &Path) -> CommandResult<TaskwarriorExportSummary> {
+    let db_err = |e: rusqlite::Error| format!("Failed to read tasks for export: {e}");
+    let write_err = |e: std::io::Error| {
+        CommandError::from(e).context(format!("Failed to write {}", path.display()))
+    };
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let stored = load_stored(conn).map_err(db_err)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE deleted_at IS NULL ORDER BY created_at"
+        ))
+        .map_err(db_err)?;
+    let mut tasks = stmt
+        .query_map([], row_to_task)
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(db_err)?;
+    tags::load_task_tags(conn, &mut tasks).map_err(db_err)?;
+
+    let uuid_of = |id: &str| stored.get(id).map_or(id, |s| s.uuid.as_str()).to_string();
+    let mut blockers: HashMap<String, Vec<String>> = HashMap::new();
+    let mut stmt = conn
+        .prepare(
+            "SELECT d.task_id, d.blocker_id FROM task_dependencies d
+             JOIN tasks b ON b.id = d.blocker_id
+             WHERE b.deleted_at IS NULL
+             ORDER BY d.created_at",
+        )
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(db_err)?;
+    for (task_id, blocker_id) in rows {
+        blockers
+            .entry(task_id)
+            .or_default()
+            .push(uuid_of(&blocker_id));
+    }
+
+    let mut summary = TaskwarriorExportSummary { tasks: 0 };
+    write_streamed(path, |out| {
+        out.write_all(b"[\n").map_err(write_err)?;
+        for task in &tasks {
+            let depends = blockers.remove(&task.id).unwrap_or_default();
+            let item = to_tw(task, stored.get(&task.id), depends, &local);
+            if summary.tasks > 0 {
+                out.write_all(b",\n").map_err(write_err)?;
+            }
+            serde_json::to_writer(&mut *out, &item).map_err(|e| e.to_string())?;
+            summary.tasks += 1;
+        }
+        out.write_all(b"\n]\n").map_err(write_err)
+    })?;
+    Ok(summary)
+}
+
+/// Import the output of `task export` from a file.
+#[tauri::command]
+pub fn import_taskwarrior(
+    db: State<'_, Db>,
+    path: String,
+) -> CommandResult<TaskwarriorImportReport> {
+    let text = fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+    db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Import, |conn| import(conn, &text))
+    })?
+}
+
+/// Export tasks to a file `task import` can read, to move back to
+/// Taskwarrior or round-trip through it.
+#[tauri::command]
+pub fn export_taskwarrior(
+    db: State<'_, Db>,
+    path: String,
+) -> CommandResult<TaskwarriorExportSummary> {
+    db.with_conn(|conn| Ok(export(conn, Path::new(&path))))?
+}
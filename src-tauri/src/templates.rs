@@ -0,0 +1,423 @@
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::projects;
+use crate::recurrence;
+use crate::tags;
+use crate::task_store::{self, double_option, NewTask, Task};
+
+const TEMPLATE_COLUMNS: &str = "id, name, title, description, project, priority, \
+     estimate_minutes, tags, subtasks, created_at, updated_at";
+
+/// One entry in a template's checklist, created as a subtask.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateItem {
+    /// A title pattern, filled in like the template's.
+    pub title: String,
+    #[serde(default)]
+    pub estimate_minutes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskTemplate {
+    pub id: String,
+    pub name: String,
+    /// Title pattern. `{date}`, `{weekday}`, `{week}`, `{month}` and `{year}`
+    /// are filled in from the new task's scheduled or due date, or today.
+    pub title: String,
+    pub description: Option<String>,
+    pub project: Option<String>,
+    pub priority: Option<i64>,
+    pub estimate_minutes: Option<i64>,
+    pub tags: Vec<String>,
+    pub subtasks: Vec<TemplateItem>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTemplate {
+    pub name: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i64>,
+    #[serde(default)]
+    pub estimate_minutes: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub subtasks: Vec<TemplateItem>,
+}
+
+/// Partial update. For nullable fields, a missing key leaves the value alone
+/// while an explicit `null` clears it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TemplatePatch {
+    pub name: Option<String>,
+    pub title: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub priority: Option<Option<i64>>,
+    #[serde(deserialize_with = "double_option")]
+    pub estimate_minutes: Option<Option<i64>>,
+    pub tags: Option<Vec<String>>,
+    pub subtasks: Option<Vec<TemplateItem>>,
+}
+
+/// What to change from the template for one task. Same rules as
+/// `TemplatePatch`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TemplateOverrides {
+    pub title: Option<String>,
+    #[serde(deserialize_with = "double_option")]
+    pub description: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    #[serde(deserialize_with = "double_option")]
+    pub priority: Option<Option<i64>>,
+    #[serde(deserialize_with = "double_option")]
+    pub estimate_minutes: Option<Option<i64>>,
+    pub due: Option<String>,
+    pub scheduled: Option<String>,
+    /// Replaces the template's tags when present.
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateInstance {
+    pub task: Task,
+    pub subtasks: Vec<Task>,
+}
+
+fn row_to_template(row: &Row) -> rusqlite::Result<TaskTemplate> {
+    let tags: String = row.get(7)?;
+    let subtasks: String = row.get(8)?;
+    Ok(TaskTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        title: row.get(2)?,
+        description: row.get(3)?,
+        project: row.get(4)?,
+        priority: row.get(5)?,
+        estimate_minutes: row.get(6)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        subtasks: serde_json::from_str(&subtasks).unwrap_or_default(),
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+fn normalize_name(name: &str) -> CommandResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::invalid("Template name cannot be empty"));
+    }
+    Ok(name.to_string())
+}
+
+/// Check a template's fields and put them in canonical form.
+fn validate(template: &mut TaskTemplate) -> CommandResult<()> {
+    template.name = normalize_name(&template.name)?;
+    template.title = task_store::validate_title(&template.title)?;
+    template.project = template
+        .project
+        .as_deref()
+        .map(projects::normalize_name)
+        .transpose()?;
+    if let Some(minutes) = template.estimate_minutes {
+        task_store::validate_estimate(minutes)?;
+    }
+    template.tags = tags::normalize_names(&template.tags)?;
+    for item in &mut template.subtasks {
+        item.title = task_store::validate_title(&item.title)?;
+        if let Some(minutes) = item.estimate_minutes {
+            task_store::validate_estimate(minutes)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn find_template(conn: &Connection, id: &str) -> rusqlite::Result<Option<TaskTemplate>> {
+    conn.query_row(
+        &format!("SELECT {TEMPLATE_COLUMNS} FROM task_templates WHERE id = ?1"),
+        params![id],
+        row_to_template,
+    )
+    .optional()
+}
+
+/// Whether another template already has `name`. Names are unique
+/// case-insensitively.
+fn name_taken(conn: &Connection, name: &str, id: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM task_templates WHERE name = ?1 AND id != ?2)",
+        params![name, id],
+        |row| row.get(0),
+    )
+}
+
+fn write_template(conn: &Connection, template: &TaskTemplate) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO task_templates ({TEMPLATE_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(id) DO UPDATE SET
+                 name = excluded.name,
+                 title = excluded.title,
+                 description = excluded.description,
+                 project = excluded.project,
+                 priority = excluded.priority,
+                 estimate_minutes = excluded.estimate_minutes,
+                 tags = excluded.tags,
+                 subtasks = excluded.subtasks,
+                 updated_at = excluded.updated_at"
+        ),
+        params![
+            template.id,
+            template.name,
+            template.title,
+            template.description,
+            template.project,
+            template.priority,
+            template.estimate_minutes,
+            serde_json::to_string(&template.tags).unwrap_or_default(),
+            serde_json::to_string(&template.subtasks).unwrap_or_default(),
+            template.created_at,
+            template.updated_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Fill in the date placeholders of a title pattern. Anything else in
+/// braces is left as written.
+pub fn fill_title(pattern: &str, date: NaiveDate) -> String {
+    pattern
+        .replace("{date}", &date.format("%Y-%m-%d").to_string())
+        .replace("{weekday}", &date.format("%A").to_string())
+        .replace("{week}", &date.iso_week().week().to_string())
+        .replace("{month}", &date.format("%B").to_string())
+        .replace("{year}", &date.year().to_string())
+}
+
+/// The date a new task's title is filled in for: when it's scheduled, else
+/// when it's due, else today.
+fn instance_date(overrides: &TemplateOverrides) -> NaiveDate {
+    [&overrides.scheduled, &overrides.due]
+        .into_iter()
+        .flatten()
+        .find_map(|date| NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok())
+        .unwrap_or_else(recurrence::today)
+}
+
+/// Create a task and its subtasks from `template`. Returns `Err` for
+/// values that don't make a valid task.
+pub fn instantiate(
+    conn: &Connection,
+    template: &TaskTemplate,
+    overrides: &TemplateOverrides,
+) -> rusqlite::Result<CommandResult<TemplateInstance>> {
+    let date = instance_date(overrides);
+    let title = overrides.title.as_deref().unwrap_or(&template.title);
+    let title = match task_store::validate_title(&fill_title(title, date)) {
+        Ok(title) => title,
+        Err(e) => return Ok(Err(e)),
+    };
+    let project = match &overrides.project {
+        Some(Some(project)) => match projects::normalize_name(project) {
+            Ok(project) => Some(project),
+            Err(e) => return Ok(Err(e)),
+        },
+        Some(None) => None,
+        None => template.project.clone(),
+    };
+    let estimate_minutes = match overrides.estimate_minutes {
+        Some(Some(minutes)) => match task_store::validate_estimate(minutes) {
+            Ok(minutes) => Some(minutes),
+            Err(e) => return Ok(Err(e)),
+        },
+        Some(None) => None,
+        None => template.estimate_minutes,
+    };
+    let tag_names = match overrides.tags.as_deref().map(tags::normalize_names) {
+        Some(Ok(names)) => names,
+        Some(Err(e)) => return Ok(Err(e)),
+        None => template.tags.clone(),
+    };
+
+    let input = NewTask {
+        title: title.clone(),
+        description: overrides
+            .description
+            .clone()
+            .unwrap_or_else(|| template.description.clone()),
+        project: project.clone(),
+        priority: overrides.priority.unwrap_or(template.priority),
+        due: overrides.due.clone(),
+        scheduled: overrides.scheduled.clone(),
+        recurrence: None,
+        tags: Vec::new(),
+        parent_id: None,
+        estimate_minutes,
+    };
+    let mut task = task_store::insert_task(conn, &input, title)?;
+    tags::set_task_tags(conn, &task.id, &tag_names)?;
+    tags::load_task_tags(conn, std::slice::from_mut(&mut task))?;
+
+    let mut subtasks = Vec::with_capacity(template.subtasks.len());
+    for item in &template.subtasks {
+        let title = fill_title(&item.title, date);
+        let input = NewTask {
+            title: title.clone(),
+            description: None,
+            project: project.clone(),
+            priority: None,
+            due: None,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: Some(task.id.clone()),
+            estimate_minutes: item.estimate_minutes,
+        };
+        subtasks.push(task_store::insert_task(conn, &input, title)?);
+    }
+    Ok(Ok(TemplateInstance { task, subtasks }))
+}
+
+#[tauri::command]
+pub fn list_templates(db: State<'_, Db>) -> CommandResult<Vec<TaskTemplate>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {TEMPLATE_COLUMNS} FROM task_templates ORDER BY name COLLATE NOCASE"
+        ))?;
+        let rows = stmt.query_map([], row_to_template)?;
+        rows.collect()
+    })
+}
+
+#[tauri::command]
+pub fn create_template(db: State<'_, Db>, input: NewTemplate) -> CommandResult<TaskTemplate> {
+    let now = now_utc();
+    let mut template = TaskTemplate {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: input.name,
+        title: input.title,
+        description: input.description,
+        project: input.project,
+        priority: input.priority,
+        estimate_minutes: input.estimate_minutes,
+        tags: input.tags,
+        subtasks: input.subtasks,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    validate(&mut template)?;
+    db.with_conn(|conn| {
+        if name_taken(conn, &template.name, &template.id)? {
+            return Ok(Err(CommandError::conflict(format!(
+                "A template named '{}' already exists",
+                template.name
+            ))));
+        }
+        write_template(conn, &template)?;
+        Ok(Ok(template))
+    })?
+}
+
+#[tauri::command]
+pub fn update_template(
+    db: State<'_, Db>,
+    id: String,
+    patch: TemplatePatch,
+) -> CommandResult<TaskTemplate> {
+    db.with_conn(|conn| {
+        let Some(mut template) = find_template(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Template not found: {id}"
+            ))));
+        };
+        if let Some(name) = &patch.name {
+            template.name = name.clone();
+        }
+        if let Some(title) = &patch.title {
+            template.title = title.clone();
+        }
+        if let Some(description) = &patch.description {
+            template.description = description.clone();
+        }
+        if let Some(project) = &patch.project {
+            template.project = project.clone();
+        }
+        if let Some(priority) = patch.priority {
+            template.priority = priority;
+        }
+        if let Some(estimate) = patch.estimate_minutes {
+            template.estimate_minutes = estimate;
+        }
+        if let Some(tags) = &patch.tags {
+            template.tags = tags.clone();
+        }
+        if let Some(subtasks) = &patch.subtasks {
+            template.subtasks = subtasks.clone();
+        }
+        if let Err(e) = validate(&mut template) {
+            return Ok(Err(e));
+        }
+        if name_taken(conn, &template.name, &template.id)? {
+            return Ok(Err(CommandError::conflict(format!(
+                "A template named '{}' already exists",
+                template.name
+            ))));
+        }
+        template.updated_at = now_utc();
+        write_template(conn, &template)?;
+        Ok(Ok(template))
+    })?
+}
+
+#[tauri::command]
+pub fn delete_template(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM task_templates WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!("Template not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Create a task from a template, with a subtask for each checklist item,
+/// as one undo step.
+#[tauri::command]
+pub fn instantiate_template(
+    db: State<'_, Db>,
+    id: String,
+    overrides: Option<TemplateOverrides>,
+) -> CommandResult<TemplateInstance> {
+    let overrides = overrides.unwrap_or_default();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let Some(template) = find_template(&tx, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Template not found: {id}"
+            ))));
+        };
+        let instance = instantiate(&tx, &template, &overrides)?;
+        if instance.is_ok() {
+            tx.commit()?;
+        }
+        Ok(instance)
+    })?
+}
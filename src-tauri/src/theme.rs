@@ -3,15 +3,49 @@ use std::fs;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
+use chrono::{Local, NaiveTime, Utc};
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
-use serde::Serialize;
-use tauri::{AppHandle, Emitter};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::location;
+use crate::sun;
+
+/// Emitted when the palette should be reloaded: GTK's theme files changed,
+/// or the theme schedule switched between light and dark.
+pub const THEME_CHANGED_EVENT: &str = "gtk-theme-changed";
+
+/// How often the theme schedule is checked for a switch.
+const SCHEDULE_POLL: Duration = Duration::from_secs(60);
+
+pub const SCHEDULE_SYSTEM: &str = "system";
+pub const SCHEDULE_SUN: &str = "sun";
+pub const SCHEDULE_FIXED: &str = "fixed";
 
 #[derive(Debug, Clone, Serialize)]
 pub struct GtkThemeColors {
     pub colors: HashMap<String, String>,
     pub prefer_dark: bool,
     pub theme_path: Option<String>,
+    /// Whether `prefer_dark` comes from the theme schedule rather than
+    /// GTK's preference.
+    pub scheduled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThemeSchedule {
+    /// `system` to follow GTK, `sun` to go light at sunrise and dark at
+    /// sunset where the user is, or `fixed` for `light_at` and `dark_at`.
+    pub mode: String,
+    /// Local `HH:MM` to switch to the light palette, for `fixed`.
+    #[serde(default)]
+    pub light_at: Option<String>,
+    /// Local `HH:MM` to switch to the dark palette, for `fixed`.
+    #[serde(default)]
+    pub dark_at: Option<String>,
 }
 
 /// Resolve the GTK4 theme CSS file by reading ~/.config/gtk-4.0/gtk.css
@@ -71,7 +105,10 @@ fn parse_define_colors(css: &str) -> HashMap<String, String> {
             // Format: name value;
             if let Some(space_idx) = rest.find(' ') {
                 let name = rest[..space_idx].to_string();
-                let value = rest[space_idx + 1..].trim_end_matches(';').trim().to_string();
+                let value = rest[space_idx + 1..]
+                    .trim_end_matches(';')
+                    .trim()
+                    .to_string();
                 colors.insert(name, value);
             }
         }
@@ -102,14 +139,85 @@ fn read_dark_preference() -> bool {
     false
 }
 
-#[tauri::command]
-pub fn get_gtk_colors() -> Result<GtkThemeColors, String> {
+fn load_schedule(conn: &Connection) -> rusqlite::Result<ThemeSchedule> {
+    conn.query_row(
+        "SELECT mode, light_at, dark_at FROM theme_schedule WHERE id = 1",
+        [],
+        |row| {
+            Ok(ThemeSchedule {
+                mode: row.get(0)?,
+                light_at: row.get(1)?,
+                dark_at: row.get(2)?,
+            })
+        },
+    )
+}
+
+fn parse_clock(value: &str) -> CommandResult<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|e| CommandError::invalid(format!("Invalid time '{value}', expected HH:MM: {e}")))
+}
+
+/// Whether `now` falls in the dark stretch from `dark_at` to `light_at`,
+/// which usually wraps past midnight.
+fn dark_between(now: NaiveTime, light_at: NaiveTime, dark_at: NaiveTime) -> bool {
+    if dark_at > light_at {
+        now >= dark_at || now < light_at
+    } else {
+        now >= dark_at && now < light_at
+    }
+}
+
+/// Whether it's dark now where the user is: before today's sunrise or
+/// after its sunset, all day during the polar night.
+async fn dark_by_sun(db: &Db) -> CommandResult<bool> {
+    let here = location::current(db).await?;
+    let times = sun::sun_times(Local::now().date_naive(), here.latitude, here.longitude)?;
+    match (times.sunrise, times.sunset) {
+        (Some(sunrise), Some(sunset)) => {
+            let now = Utc::now();
+            Ok(now < parse_utc(&sunrise)? || now >= parse_utc(&sunset)?)
+        }
+        _ => Ok(times.day_length_minutes == 0),
+    }
+}
+
+/// The palette the theme schedule calls for now: `Some(true)` for dark,
+/// `None` to leave it to GTK's preference. A schedule that can't be
+/// worked out, as without a location, leaves it to GTK too.
+async fn scheduled_dark(db: &Db) -> Option<bool> {
+    let schedule = match db.with_conn(|conn| load_schedule(conn)) {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            tracing::warn!("{e}");
+            return None;
+        }
+    };
+    let dark = match schedule.mode.as_str() {
+        SCHEDULE_SUN => dark_by_sun(db).await,
+        SCHEDULE_FIXED => match (&schedule.light_at, &schedule.dark_at) {
+            (Some(light_at), Some(dark_at)) => parse_clock(light_at).and_then(|light_at| {
+                Ok(dark_between(
+                    Local::now().time(),
+                    light_at,
+                    parse_clock(dark_at)?,
+                ))
+            }),
+            _ => Err(CommandError::invalid(
+                "Fixed theme schedule is missing its times",
+            )),
+        },
+        _ => return None,
+    };
+    dark.map_err(|e| tracing::warn!("schedule: {e}")).ok()
+}
+
+fn read_gtk_colors() -> CommandResult<GtkThemeColors> {
     let theme_path = resolve_gtk_theme_path();
 
     let colors = match &theme_path {
         Some(path) => {
-            let css =
-                fs::read_to_string(path).map_err(|e| format!("Failed to read theme CSS: {e}"))?;
+            let css = fs::read_to_string(path).context("Failed to read theme CSS")?;
             parse_define_colors(&css)
         }
         None => HashMap::new(),
@@ -119,11 +227,102 @@ pub fn get_gtk_colors() -> Result<GtkThemeColors, String> {
         colors,
         prefer_dark: read_dark_preference(),
         theme_path: theme_path.map(|p| p.to_string_lossy().into_owned()),
+        scheduled: false,
     })
 }
 
+/// GTK's palette, light or dark as the theme schedule says when it says.
+async fn current_colors(db: &Db) -> CommandResult<GtkThemeColors> {
+    let mut colors = read_gtk_colors()?;
+    if let Some(dark) = scheduled_dark(db).await {
+        colors.prefer_dark = dark;
+        colors.scheduled = true;
+    }
+    Ok(colors)
+}
+
+#[tauri::command]
+pub async fn get_gtk_colors(db: State<'_, Db>) -> CommandResult<GtkThemeColors> {
+    current_colors(&db).await
+}
+
+#[tauri::command]
+pub fn get_theme_schedule(db: State<'_, Db>) -> CommandResult<ThemeSchedule> {
+    db.with_conn(|conn| load_schedule(conn))
+}
+
+/// Set when to switch between light and dark, and switch now if the new
+/// schedule calls for it.
+#[tauri::command]
+pub async fn set_theme_schedule(
+    app: AppHandle,
+    db: State<'_, Db>,
+    schedule: ThemeSchedule,
+) -> CommandResult<ThemeSchedule> {
+    let clock = |value: &Option<String>| -> CommandResult<Option<String>> {
+        value
+            .as_deref()
+            .map(|v| parse_clock(v).map(|t| t.format("%H:%M").to_string()))
+            .transpose()
+    };
+    let (light_at, dark_at) = (clock(&schedule.light_at)?, clock(&schedule.dark_at)?);
+    match schedule.mode.as_str() {
+        SCHEDULE_SYSTEM | SCHEDULE_SUN => {}
+        SCHEDULE_FIXED => {
+            if light_at.is_none() || dark_at.is_none() {
+                return Err(CommandError::invalid(
+                    "A fixed schedule needs both a light and a dark time",
+                ));
+            }
+            if light_at == dark_at {
+                return Err(CommandError::invalid(
+                    "The light and dark times must differ",
+                ));
+            }
+        }
+        other => {
+            return Err(CommandError::invalid(format!(
+                "Unknown theme schedule: {other}"
+            )))
+        }
+    }
+    let saved = db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE theme_schedule SET mode = ?1, light_at = ?2, dark_at = ?3 WHERE id = 1",
+            params![schedule.mode, light_at, dark_at],
+        )?;
+        load_schedule(conn)
+    })?;
+    let _ = app.emit(THEME_CHANGED_EVENT, current_colors(&db).await?);
+    Ok(saved)
+}
+
+/// Switch between the light and dark palettes as the theme schedule says,
+/// emitting `THEME_CHANGED_EVENT` with the new palette at each switch.
+pub fn spawn_theme_scheduler(app: &AppHandle) {
+    let handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let mut last = None;
+        loop {
+            let db = handle.state::<Db>();
+            let dark = scheduled_dark(&db).await;
+            if last.is_some_and(|last| last != dark) {
+                match current_colors(&db).await {
+                    Ok(colors) => {
+                        let _ = handle.emit(THEME_CHANGED_EVENT, colors);
+                    }
+                    Err(e) => tracing::warn!("{e}"),
+                }
+            }
+            last = Some(dark);
+            tokio::time::sleep(SCHEDULE_POLL).await;
+        }
+    });
+}
+
 /// Start a file watcher on ~/.config/gtk-4.0/ (and the imported theme dir)
-/// that emits a "gtk-theme-changed" Tauri event on changes.
+/// that emits `THEME_CHANGED_EVENT` on changes.
 pub fn setup_gtk_watcher(app: &AppHandle) {
     let handle = app.clone();
 
@@ -177,7 +376,7 @@ pub fn setup_gtk_watcher(app: &AppHandle) {
                     break;
                 }
             }
-            let _ = handle.emit("gtk-theme-changed", ());
+            let _ = handle.emit(THEME_CHANGED_EVENT, ());
         }
     });
 }
@@ -0,0 +1,475 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::task_store;
+use crate::timezone;
+use crate::usage;
+
+pub const TIME_ENTRIES_EVENT: &str = "time-entries-changed";
+
+pub const ENTRY_COLUMNS: &str =
+    "id, task_id, started_at, ended_at, note, created_at, updated_at, tz, billable";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeEntry {
+    pub id: String,
+    pub task_id: String,
+    pub started_at: String,
+    /// `None` while the entry is running. At most one entry runs at a time.
+    pub ended_at: Option<String>,
+    pub note: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// IANA zone the entry was recorded in; timestamps themselves are UTC.
+    pub tz: Option<String>,
+    pub billable: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TimeEntryPatch {
+    pub task_id: Option<String>,
+    pub started_at: Option<String>,
+    pub ended_at: Option<String>,
+    pub note: Option<String>,
+    pub billable: Option<bool>,
+}
+
+pub fn row_to_entry(row: &Row) -> rusqlite::Result<TimeEntry> {
+    Ok(TimeEntry {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        started_at: row.get(2)?,
+        ended_at: row.get(3)?,
+        note: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        tz: row.get(7)?,
+        billable: row.get(8)?,
+    })
+}
+
+pub fn find_entry(conn: &Connection, id: &str) -> rusqlite::Result<Option<TimeEntry>> {
+    conn.query_row(
+        &format!("SELECT {ENTRY_COLUMNS} FROM time_entries WHERE id = ?1"),
+        params![id],
+        row_to_entry,
+    )
+    .optional()
+}
+
+pub fn running_entry(conn: &Connection) -> rusqlite::Result<Option<TimeEntry>> {
+    conn.query_row(
+        &format!("SELECT {ENTRY_COLUMNS} FROM time_entries WHERE ended_at IS NULL"),
+        [],
+        row_to_entry,
+    )
+    .optional()
+}
+
+/// Insert or update an entry by id. Unlike `INSERT OR REPLACE`, a second
+/// running entry fails on the unique index instead of evicting the first.
+pub fn write_entry(conn: &Connection, entry: &TimeEntry) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO time_entries ({ENTRY_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(id) DO UPDATE SET
+                 task_id = excluded.task_id,
+                 started_at = excluded.started_at,
+                 ended_at = excluded.ended_at,
+                 note = excluded.note,
+                 updated_at = excluded.updated_at,
+                 tz = excluded.tz,
+                 billable = excluded.billable"
+        ),
+        params![
+            entry.id,
+            entry.task_id,
+            entry.started_at,
+            entry.ended_at,
+            entry.note,
+            entry.created_at,
+            entry.updated_at,
+            entry.tz,
+            entry.billable,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Close the running entry (if any) at `at`.
+pub fn stop_running(conn: &Connection, at: &str) -> rusqlite::Result<Option<TimeEntry>> {
+    let Some(mut entry) = running_entry(conn)? else {
+        return Ok(None);
+    };
+    entry.ended_at = Some(at.to_string());
+    entry.updated_at = now_utc();
+    write_entry(conn, &entry)?;
+    Ok(Some(entry))
+}
+
+pub fn new_entry(task_id: &str, started_at: String, note: Option<String>) -> TimeEntry {
+    let now = now_utc();
+    TimeEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        task_id: task_id.to_string(),
+        started_at,
+        ended_at: None,
+        note,
+        created_at: now.clone(),
+        updated_at: now,
+        tz: Some(timezone::system_zone()),
+        billable: false,
+    }
+}
+
+fn validate_span(started_at: &str, ended_at: Option<&str>) -> CommandResult<()> {
+    let start = parse_utc(started_at)?;
+    if let Some(end) = ended_at {
+        if parse_utc(end)? <= start {
+            return Err(CommandError::invalid("Time entry must end after it starts"));
+        }
+    }
+    Ok(())
+}
+
+/// Entries overlapping `[from, to)`, oldest first.
+pub fn entries_in_range(
+    conn: &Connection,
+    from: &str,
+    to: &str,
+    task_id: Option<&str>,
+) -> rusqlite::Result<Vec<TimeEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS} FROM time_entries
+         WHERE started_at < ?2 AND (ended_at IS NULL OR ended_at > ?1)
+           AND (?3 IS NULL OR task_id = ?3)
+           AND task_id IN (SELECT id FROM tasks WHERE deleted_at IS NULL)
+         ORDER BY started_at"
+    ))?;
+    let rows = stmt.query_map(params![from, to, task_id], row_to_entry)?;
+    rows.collect()
+}
+
+pub fn notify(app: &AppHandle) {
+    crate::timer::sync(app);
+    let _ = app.emit(TIME_ENTRIES_EVENT, ());
+    #[cfg(desktop)]
+    crate::tray::refresh_timer(app);
+}
+
+/// Whether new time on `task_id` is billable by default: whatever its most
+/// recent entry was.
+fn last_billable(conn: &Connection, task_id: &str) -> rusqlite::Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT billable FROM time_entries WHERE task_id = ?1
+             ORDER BY started_at DESC LIMIT 1",
+            params![task_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(false))
+}
+
+/// Start timing `task_id` at `at`, stopping whatever was running first.
+/// `billable` defaults to the task's previous entry.
+pub fn start_running(
+    conn: &Connection,
+    task_id: &str,
+    at: &str,
+    note: Option<String>,
+    billable: Option<bool>,
+) -> rusqlite::Result<TimeEntry> {
+    stop_running(conn, at)?;
+    let mut entry = new_entry(task_id, at.to_string(), note);
+    entry.billable = match billable {
+        Some(billable) => billable,
+        None => last_billable(conn, task_id)?,
+    };
+    write_entry(conn, &entry)?;
+    Ok(entry)
+}
+
+/// Start timing `task_id`, stopping whatever was running first. `billable`
+/// defaults to the task's previous entry.
+#[tauri::command]
+pub fn start_entry(
+    app: AppHandle,
+    db: State<'_, Db>,
+    task_id: String,
+    note: Option<String>,
+    billable: Option<bool>,
+) -> CommandResult<TimeEntry> {
+    let entry = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let entry = start_running(&tx, &task_id, &now_utc(), note, billable)?;
+        tx.commit()?;
+        Ok(entry)
+    })?;
+    usage::record(&db, "timer", None);
+    notify(&app);
+    Ok(entry)
+}
+
+/// The most recent entry on a task that still exists.
+fn last_entry(conn: &Connection) -> rusqlite::Result<Option<TimeEntry>> {
+    conn.query_row(
+        &format!(
+            "SELECT {ENTRY_COLUMNS} FROM time_entries
+             WHERE task_id IN (SELECT id FROM tasks WHERE deleted_at IS NULL)
+             ORDER BY started_at DESC LIMIT 1"
+        ),
+        [],
+        row_to_entry,
+    )
+    .optional()
+}
+
+/// Start timing the task of the most recent entry again, for the "Start
+/// last timer" quick action. Leaves a running timer alone.
+pub fn restart_last(app: &AppHandle, db: &Db) -> CommandResult<Option<TimeEntry>> {
+    let started = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        if running_entry(&tx)?.is_some() {
+            return Ok(None);
+        }
+        let Some(last) = last_entry(&tx)? else {
+            return Ok(None);
+        };
+        let entry = start_running(&tx, &last.task_id, &now_utc(), None, None)?;
+        tx.commit()?;
+        Ok(Some(entry))
+    })?;
+    if started.is_some() {
+        usage::record(db, "timer", None);
+        notify(app);
+    }
+    Ok(started)
+}
+
+#[tauri::command]
+pub fn stop_entry(app: AppHandle, db: State<'_, Db>) -> CommandResult<Option<TimeEntry>> {
+    let stopped = db.with_conn(|conn| stop_running(conn, &now_utc()))?;
+    notify(&app);
+    Ok(stopped)
+}
+
+#[tauri::command]
+pub fn get_running_entry(db: State<'_, Db>) -> CommandResult<Option<TimeEntry>> {
+    db.with_conn(|conn| running_entry(conn))
+}
+
+#[tauri::command]
+pub fn edit_entry(
+    app: AppHandle,
+    db: State<'_, Db>,
+    id: String,
+    patch: TimeEntryPatch,
+) -> CommandResult<TimeEntry> {
+    let mut entry = db
+        .with_conn(|conn| find_entry(conn, &id))?
+        .ok_or_else(|| CommandError::not_found(format!("Time entry not found: {id}")))?;
+
+    if let Some(task_id) = patch.task_id {
+        entry.task_id = task_id;
+    }
+    if let Some(started_at) = patch.started_at {
+        entry.started_at = format_utc(parse_utc(&started_at)?);
+    }
+    if let Some(ended_at) = patch.ended_at {
+        entry.ended_at = Some(format_utc(parse_utc(&ended_at)?));
+    }
+    if let Some(note) = patch.note {
+        entry.note = if note.trim().is_empty() {
+            None
+        } else {
+            Some(note)
+        };
+    }
+    if let Some(billable) = patch.billable {
+        entry.billable = billable;
+    }
+    validate_span(&entry.started_at, entry.ended_at.as_deref())?;
+    entry.updated_at = now_utc();
+
+    db.with_conn(|conn| write_entry(conn, &entry))?;
+    notify(&app);
+    Ok(entry)
+}
+
+/// Split an entry at `at` into two back-to-back entries for the same task.
+/// If the original was running, the second half keeps running.
+#[tauri::command]
+pub fn split_entry(
+    app: AppHandle,
+    db: State<'_, Db>,
+    id: String,
+    at: String,
+) -> CommandResult<(TimeEntry, TimeEntry)> {
+    let split_at = parse_utc(&at)?;
+    let mut first = db
+        .with_conn(|conn| find_entry(conn, &id))?
+        .ok_or_else(|| CommandError::not_found(format!("Time entry not found: {id}")))?;
+
+    let start = parse_utc(&first.started_at)?;
+    let end = first.ended_at.as_deref().map(parse_utc).transpose()?;
+    if split_at <= start || end.is_some_and(|end| split_at >= end) {
+        return Err(CommandError::invalid(
+            "Split point must fall inside the entry",
+        ));
+    }
+
+    let split_at = format_utc(split_at);
+    let mut second = new_entry(&first.task_id, split_at.clone(), first.note.clone());
+    second.billable = first.billable;
+    second.ended_at = first.ended_at.take();
+    first.ended_at = Some(split_at);
+    first.updated_at = now_utc();
+
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        // Close the first half before inserting the second so the
+        // single-running-entry index is never violated.
+        write_entry(&tx, &first)?;
+        write_entry(&tx, &second)?;
+        tx.commit()
+    })?;
+    notify(&app);
+    Ok((first, second))
+}
+
+/// What to do with a span the user was away for during an entry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleAction {
+    /// Drop the idle span; the time either side stays on the task.
+    Trim,
+    /// Keep the idle span as an entry of its own on the same task.
+    Split,
+    /// Move the idle span to another task, such as a break.
+    Reassign(String),
+}
+
+/// Cut `[from, to)` out of `entry` and deal with it per `action`. Returns
+/// the resulting entries in time order; if the original was running, the
+/// last one keeps running.
+pub fn apply_idle(
+    conn: &Connection,
+    entry: &TimeEntry,
+    from: &str,
+    to: &str,
+    action: &IdleAction,
+) -> rusqlite::Result<Vec<TimeEntry>> {
+    let mut first = entry.clone();
+    first.ended_at = Some(from.to_string());
+    first.updated_at = now_utc();
+
+    let idle = match action {
+        IdleAction::Trim => None,
+        IdleAction::Split => {
+            let mut idle = new_entry(&entry.task_id, from.to_string(), entry.note.clone());
+            idle.billable = entry.billable;
+            Some(idle)
+        }
+        IdleAction::Reassign(task_id) => {
+            let mut idle = new_entry(task_id, from.to_string(), None);
+            idle.billable = last_billable(conn, task_id)?;
+            Some(idle)
+        }
+    }
+    .map(|mut idle| {
+        idle.ended_at = Some(to.to_string());
+        idle
+    });
+
+    let rest = if entry.ended_at.as_deref() == Some(to) {
+        None
+    } else {
+        let mut rest = new_entry(&entry.task_id, to.to_string(), entry.note.clone());
+        rest.billable = entry.billable;
+        rest.ended_at = entry.ended_at.clone();
+        Some(rest)
+    };
+
+    // Closing the original first keeps the single-running-entry index happy.
+    let entries: Vec<TimeEntry> = [Some(first), idle, rest].into_iter().flatten().collect();
+    for entry in &entries {
+        write_entry(conn, entry)?;
+    }
+    Ok(entries)
+}
+
+/// Handle a span the idle detector reports the user was away for during
+/// entry `id`, in one transaction.
+#[tauri::command]
+pub fn resolve_idle_time(
+    app: AppHandle,
+    db: State<'_, Db>,
+    id: String,
+    idle_from: String,
+    idle_to: String,
+    action: IdleAction,
+) -> CommandResult<Vec<TimeEntry>> {
+    let from = parse_utc(&idle_from)?;
+    let to = parse_utc(&idle_to)?;
+    if to <= from {
+        return Err(CommandError::invalid("Idle span must end after it starts"));
+    }
+    let (from, to) = (format_utc(from), format_utc(to));
+    let entries = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let Some(entry) = find_entry(&tx, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Time entry not found: {id}"
+            ))));
+        };
+        let end = entry.ended_at.clone().unwrap_or_else(now_utc);
+        if from <= entry.started_at || to > end {
+            return Ok(Err(CommandError::invalid(
+                "Idle span must fall inside the entry",
+            )));
+        }
+        if let IdleAction::Reassign(task_id) = &action {
+            if task_store::find_task(&tx, task_id)?.is_none() {
+                return Ok(Err(CommandError::not_found(format!(
+                    "Task not found: {task_id}"
+                ))));
+            }
+        }
+        let entries = apply_idle(&tx, &entry, &from, &to, &action)?;
+        tx.commit()?;
+        Ok(Ok(entries))
+    })??;
+    notify(&app);
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn delete_entry(app: AppHandle, db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM time_entries WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Time entry not found: {id}"
+        )));
+    }
+    notify(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_entries(
+    db: State<'_, Db>,
+    from: String,
+    to: String,
+    task_id: Option<String>,
+) -> CommandResult<Vec<TimeEntry>> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    db.with_conn(|conn| entries_in_range(conn, &from, &to, task_id.as_deref()))
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{RequestBuilder, StatusCode};
+use rusqlite::types::FromSql;
+use rusqlite::{params, Connection, ToSql};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::db::{now_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::http;
+use crate::rate_limit;
+use crate::sync_provider::SyncFuture;
+use crate::task_store::{self, Task};
+use crate::time_entries::{self, TimeEntry};
+
+/// A throttled request is retried this many times before giving up.
+const MAX_ATTEMPTS: u32 = 4;
+
+pub const STATUS_SYNCED: &str = "synced";
+pub const STATUS_FAILED: &str = "failed";
+/// Never pushed, or changed since it was.
+pub const STATUS_PENDING: &str = "pending";
+/// Still running; pushed once it's stopped.
+pub const STATUS_RUNNING: &str = "running";
+
+/// What every pushed-entry table keeps, after the account and entry ids.
+const COLUMNS: [&str; 5] = ["remote_id", "status", "error", "digest", "pushed_at"];
+
+/// The services a push is running to, so two can't send the same entry
+/// twice.
+static PUSHING: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// Marks a push to one service as running until it's dropped.
+pub struct Pushing(&'static str);
+
+impl Pushing {
+    pub fn start(service: &'static str) -> CommandResult<Self> {
+        let mut running = PUSHING.lock().unwrap_or_else(|e| e.into_inner());
+        if running.contains(&service) {
+            return Err(format!("A {service} push is already running").into());
+        }
+        running.push(service);
+        Ok(Self(service))
+    }
+}
+
+impl Drop for Pushing {
+    fn drop(&mut self) {
+        let mut running = PUSHING.lock().unwrap_or_else(|e| e.into_inner());
+        running.retain(|s| *s != self.0);
+    }
+}
+
+/// How one service's API answers, for [`send`].
+pub struct Service {
+    /// The provider paused while the service is limiting requests.
+    pub id: &'static str,
+    pub name: &'static str,
+    /// The error when the service turns the credentials away.
+    pub refused: &'static str,
+    /// Statuses meaning the credentials were turned away.
+    pub refused_statuses: &'static [StatusCode],
+    /// Statuses meaning there's no such thing, or nothing came back.
+    pub empty_statuses: &'static [StatusCode],
+    /// The service's explanation in the body of a failed request.
+    pub message: fn(&str) -> String,
+}
+
+/// Send the request `build` makes, with `body` as JSON, waiting and trying
+/// again while the service is throttling. When it still is, the service is
+/// paused for as long as it asks. `None` for one of the service's empty
+/// statuses.
+pub async fn send<T: DeserializeOwned>(
+    service: &Service,
+    build: impl Fn() -> RequestBuilder,
+    body: Option<&serde_json::Value>,
+) -> CommandResult<Option<T>> {
+    let name = service.name;
+    rate_limit::check(service.id, name)?;
+    let mut attempt = 1;
+    loop {
+        let mut request = build();
+        if let Some(body) = body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await.with_context(|| name.to_string())?;
+        let status = response.status();
+        let throttled = matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        );
+        if throttled && attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(rate_limit::retry_wait(response.headers(), attempt)).await;
+            attempt += 1;
+            continue;
+        }
+        if service.empty_statuses.contains(&status) {
+            return Ok(None);
+        }
+        if service.refused_statuses.contains(&status) {
+            return Err(CommandError::unauthorized(service.refused).with_provider(service.id));
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(rate_limit::limited(service.id, name, response.headers()));
+        }
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            let message = (service.message)(&text);
+            let message = message.trim();
+            let message = if message.is_empty() {
+                format!("{name}: HTTP {}", status.as_u16())
+            } else {
+                format!("{name}: {message}")
+            };
+            return Err(CommandError::status(status, message).with_provider(service.id));
+        }
+        return http::read_json(response)
+            .await
+            .map(Some)
+            .with_context(|| name.to_string());
+    }
+}
+
+/// The task's title and the entry's note, as services describe an entry.
+pub fn description(entry: &TimeEntry, task: Option<&Task>) -> String {
+    let title = task.map_or("", |t| t.title.as_str()).trim();
+    let note = entry.note.as_deref().map(str::trim).unwrap_or("");
+    match (title.is_empty(), note.is_empty()) {
+        (_, true) => title.to_string(),
+        (true, false) => note.to_string(),
+        (false, false) => format!("{title} – {note}"),
+    }
+}
+
+/// The task's project, by name.
+pub fn project(task: Option<&Task>) -> Option<String> {
+    task.and_then(|t| t.project.as_deref())
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+}
+
+/// The hash of what an entry sends, kept with its remote id to tell when
+/// it needs pushing again.
+pub fn digest(export: &impl Serialize) -> String {
+    let text = serde_json::to_string(export).unwrap_or_default();
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+/// What was last pushed for an entry.
+#[derive(Debug, Clone, Default)]
+pub struct PushedRow<Id, E = ()> {
+    pub remote_id: Option<Id>,
+    pub status: String,
+    pub error: Option<String>,
+    pub digest: Option<String>,
+    pub pushed_at: Option<String>,
+    /// What only this service keeps about the entry.
+    pub extra: E,
+}
+
+/// Columns a service keeps for each pushed entry beside the common ones.
+pub trait Extra: Default {
+    const COLUMNS: &'static [&'static str];
+
+    /// Read the columns, the first at `first`.
+    fn read(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Self>;
+
+    /// The values of the columns, in order.
+    fn values(&self) -> Vec<&dyn ToSql>;
+}
+
+impl Extra for () {
+    const COLUMNS: &'static [&'static str] = &[];
+
+    fn read(_row: &rusqlite::Row, _first: usize) -> rusqlite::Result<Self> {
+        Ok(())
+    }
+
+    fn values(&self) -> Vec<&dyn ToSql> {
+        Vec::new()
+    }
+}
+
+fn columns<E: Extra>() -> Vec<&'static str> {
+    COLUMNS.iter().chain(E::COLUMNS).copied().collect()
+}
+
+/// What was last pushed to `account_id` for each entry, by entry id, from
+/// `table`.
+pub fn load_rows<Id: FromSql, E: Extra>(
+    conn: &Connection,
+    table: &str,
+    account_id: &str,
+) -> rusqlite::Result<HashMap<String, PushedRow<Id, E>>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT time_entry_id, {} FROM {table} WHERE account_id = ?1",
+        columns::<E>().join(", ")
+    ))?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            PushedRow {
+                remote_id: row.get(1)?,
+                status: row.get(2)?,
+                error: row.get(3)?,
+                digest: row.get(4)?,
+                pushed_at: row.get(5)?,
+                extra: E::read(row, COLUMNS.len() + 1)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+pub fn save_row<Id: ToSql, E: Extra>(
+    conn: &Connection,
+    table: &str,
+    account_id: &str,
+    time_entry_id: &str,
+    row: &PushedRow<Id, E>,
+) -> rusqlite::Result<()> {
+    let columns = columns::<E>();
+    let placeholders: Vec<String> = (1..=columns.len() + 2).map(|i| format!("?{i}")).collect();
+    let updates: Vec<String> = columns
+        .iter()
+        .map(|c| format!("{c} = excluded.{c}"))
+        .collect();
+    let mut values: Vec<&dyn ToSql> = vec![
+        &account_id,
+        &time_entry_id,
+        &row.remote_id,
+        &row.status,
+        &row.error,
+        &row.digest,
+        &row.pushed_at,
+    ];
+    values.extend(row.extra.values());
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} (account_id, time_entry_id, {})
+             VALUES ({})
+             ON CONFLICT(account_id, time_entry_id) DO UPDATE SET {}",
+            columns.join(", "),
+            placeholders.join(", "),
+            updates.join(", ")
+        ),
+        &*values,
+    )?;
+    Ok(())
+}
+
+/// A time entry in the pushed range, with what it sends now and what it
+/// sent last.
+pub struct Outgoing<X, Id, E = ()> {
+    pub entry: TimeEntry,
+    /// `None` while the entry runs.
+    pub export: Option<X>,
+    pub row: Option<PushedRow<Id, E>>,
+}
+
+impl<X: Serialize, Id, E> Outgoing<X, Id, E> {
+    /// Whether the service has the entry as it is here: what it sends now
+    /// went before, and the push didn't fail.
+    pub fn is_current(&self) -> bool {
+        match (&self.export, &self.row) {
+            (Some(export), Some(row)) => {
+                row.status != STATUS_FAILED
+                    && row.remote_id.is_some()
+                    && row.digest.as_deref() == Some(digest(export).as_str())
+            }
+            _ => false,
+        }
+    }
+
+    /// `running`, `synced` or `pending`, or why the last push didn't go
+    /// through, like `failed`.
+    pub fn status(&self) -> &str {
+        match (&self.export, &self.row) {
+            (None, _) => STATUS_RUNNING,
+            _ if self.is_current() => STATUS_SYNCED,
+            (Some(_), Some(row)) if row.status != STATUS_SYNCED && !row.status.is_empty() => {
+                &row.status
+            }
+            _ => STATUS_PENDING,
+        }
+    }
+}
+
+/// Where one time entry stands with an account of a service that keeps
+/// nothing else about it.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryStatus<Id> {
+    pub time_entry_id: String,
+    /// `synced`, `pending`, `failed` or `running`.
+    pub status: String,
+    /// The remote time entry's id, once it was created.
+    pub remote_id: Option<Id>,
+    /// Why the last push of the entry failed.
+    pub error: Option<String>,
+    pub pushed_at: Option<String>,
+}
+
+impl<X: Serialize, Id: Clone> Outgoing<X, Id> {
+    pub fn entry_status(&self) -> EntryStatus<Id> {
+        let row = self.row.as_ref();
+        EntryStatus {
+            time_entry_id: self.entry.id.clone(),
+            status: self.status().to_string(),
+            remote_id: row.and_then(|r| r.remote_id.clone()),
+            error: row.and_then(|r| r.error.clone()),
+            pushed_at: row.and_then(|r| r.pushed_at.clone()),
+        }
+    }
+}
+
+/// The time entries between `from` and `to` (UTC), each with what it sends
+/// and what `account_id` was last sent for it in `table`. `export` makes
+/// what a stopped entry sends from the entry, when it ended, its task and
+/// its last push.
+pub fn outgoing<X, Id: FromSql, E: Extra>(
+    conn: &Connection,
+    table: &str,
+    account_id: &str,
+    from: &str,
+    to: &str,
+    mut export: impl FnMut(
+        &TimeEntry,
+        &str,
+        Option<&Task>,
+        Option<&PushedRow<Id, E>>,
+    ) -> CommandResult<X>,
+) -> CommandResult<Vec<Outgoing<X, Id, E>>> {
+    let entries = time_entries::entries_in_range(conn, from, to, None)?;
+    let mut rows = load_rows(conn, table, account_id)?;
+    let mut tasks: HashMap<String, Option<Task>> = HashMap::new();
+    let mut outgoing = Vec::new();
+    for entry in entries {
+        if !tasks.contains_key(&entry.task_id) {
+            let task = task_store::find_task(conn, &entry.task_id)?;
+            tasks.insert(entry.task_id.clone(), task);
+        }
+        let task = tasks.get(&entry.task_id).and_then(Option::as_ref);
+        let row = rows.remove(&entry.id);
+        let export = match &entry.ended_at {
+            Some(ended_at) => Some(export(&entry, ended_at, task, row.as_ref())?),
+            None => None,
+        };
+        outgoing.push(Outgoing { entry, export, row });
+    }
+    Ok(outgoing)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PushReport {
+    pub account_id: String,
+    pub created: usize,
+    pub updated: usize,
+    /// Entries already in the service as they are here.
+    pub unchanged: usize,
+    /// Running entries, left until they're stopped.
+    pub running: usize,
+    pub failed: usize,
+    pub errors: Vec<String>,
+}
+
+/// Where entries go in a service that takes each one as it is.
+pub trait Target<X, Id> {
+    /// Create the entry, or update it when it has a remote id. An entry
+    /// deleted in the service is made again. Returns the remote id and
+    /// whether it was created.
+    fn push<'a>(&'a mut self, export: &'a X, remote_id: Option<Id>) -> SyncFuture<'a, (Id, bool)>;
+}
+
+/// Push the stopped entries of `outgoing` that changed since they last
+/// went to `account_id`, keeping what went in `table`. `label` names an
+/// entry in the report's errors. One entry failing doesn't stop the
+/// others; its error is kept as its status.
+pub async fn push_entries<X: Serialize, Id: ToSql + Clone>(
+    db: &Db,
+    table: &str,
+    account_id: &str,
+    outgoing: Vec<Outgoing<X, Id>>,
+    target: &mut impl Target<X, Id>,
+    label: fn(&X) -> &str,
+) -> CommandResult<PushReport> {
+    let mut report = PushReport {
+        account_id: account_id.to_string(),
+        ..PushReport::default()
+    };
+    for item in outgoing {
+        let Some(export) = &item.export else {
+            report.running += 1;
+            continue;
+        };
+        if item.is_current() {
+            report.unchanged += 1;
+            continue;
+        }
+        let previous = item.row.as_ref().and_then(|r| r.remote_id.clone());
+        let row = match target.push(export, previous.clone()).await {
+            Ok((remote_id, created)) => {
+                if created {
+                    report.created += 1;
+                } else {
+                    report.updated += 1;
+                }
+                PushedRow {
+                    remote_id: Some(remote_id),
+                    status: STATUS_SYNCED.to_string(),
+                    error: None,
+                    digest: Some(digest(export)),
+                    pushed_at: Some(now_utc()),
+                    extra: (),
+                }
+            }
+            Err(e) => {
+                tracing::warn!("push of entry {} failed: {e}", item.entry.id);
+                report.failed += 1;
+                report.errors.push(format!("{}: {e}", label(export)));
+                PushedRow {
+                    remote_id: previous,
+                    status: STATUS_FAILED.to_string(),
+                    error: Some(e.to_string()),
+                    digest: item.row.as_ref().and_then(|r| r.digest.clone()),
+                    pushed_at: Some(now_utc()),
+                    extra: (),
+                }
+            }
+        };
+        // Saved as each goes, so an interrupted push can't create an entry
+        // twice.
+        db.with_conn(|conn| save_row(conn, table, account_id, &item.entry.id, &row))?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,303 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::data_dir;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::session::write_atomic;
+use crate::task_store;
+use crate::time_entries::{self, TimeEntry};
+
+/// Emitted when a timer was found running from before the app last stopped,
+/// whether by a crash, a reboot or quitting. The user should keep it, trim it
+/// or discard it.
+pub const TIMER_RECOVERY_EVENT: &str = "timer-recovery-needed";
+
+const TIMER_FILE: &str = "timer.json";
+const HEARTBEAT: Duration = Duration::from_secs(30);
+/// A heartbeat older than this at startup means the timer went on running
+/// while nothing was watching it.
+const STALE_AFTER: chrono::Duration = chrono::Duration::minutes(2);
+
+/// The running entry as last seen alive, kept next to the database so a
+/// timer survives even if its last write to the database didn't.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TimerFile {
+    entry_id: String,
+    task_id: String,
+    started_at: String,
+    note: Option<String>,
+    billable: bool,
+    last_seen: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimerStatus {
+    pub entry: TimeEntry,
+    /// Wall-clock seconds since the entry started.
+    pub elapsed_seconds: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveredTimer {
+    pub entry: TimeEntry,
+    pub task_title: Option<String>,
+    /// When the app last saw the timer running, if it knows; the natural
+    /// point to trim the entry to.
+    pub last_seen: Option<String>,
+    pub elapsed_seconds: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerResolution {
+    /// Leave it running.
+    Keep,
+    /// Stop it at the given time, e.g. `last_seen`.
+    StopAt(String),
+    /// Delete the entry altogether.
+    Discard,
+}
+
+#[derive(Default)]
+struct TimerInner {
+    /// Set once the startup check has run; heartbeats wait for it so they
+    /// don't overwrite the evidence.
+    checked: bool,
+    recovered: Option<RecoveredTimer>,
+}
+
+pub struct TimerState {
+    inner: Mutex<TimerInner>,
+}
+
+impl TimerState {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(TimerInner::default()),
+        }
+    }
+}
+
+fn timer_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    data_dir::app_data_dir(app).map(|dir| dir.join(TIMER_FILE))
+}
+
+fn read_timer_file(app: &AppHandle) -> Option<TimerFile> {
+    let content = fs::read_to_string(timer_path(app).ok()?).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            tracing::warn!("ignoring unreadable {TIMER_FILE}: {e}");
+            None
+        }
+    }
+}
+
+fn elapsed_seconds(entry: &TimeEntry) -> i64 {
+    parse_utc(&entry.started_at)
+        .map(|start| (Utc::now() - start).num_seconds().max(0))
+        .unwrap_or(0)
+}
+
+/// Rewrite the timer file from the database: the running entry stamped as
+/// seen now, or no file when nothing runs.
+fn write_heartbeat(app: &AppHandle, running: Option<&TimeEntry>) -> CommandResult<()> {
+    let path = timer_path(app)?;
+    let Some(entry) = running else {
+        return match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => {
+                Err(CommandError::from(e).context(format!("Failed to remove {}", path.display())))
+            }
+        };
+    };
+    let file = TimerFile {
+        entry_id: entry.id.clone(),
+        task_id: entry.task_id.clone(),
+        started_at: entry.started_at.clone(),
+        note: entry.note.clone(),
+        billable: entry.billable,
+        last_seen: now_utc(),
+    };
+    let body = serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)
+}
+
+/// Put an entry the database lost back as running, if its task is still
+/// there and nothing else has started since.
+fn reinsert(conn: &Connection, file: &TimerFile) -> rusqlite::Result<Option<TimeEntry>> {
+    let lost: bool = conn.query_row(
+        "SELECT NOT EXISTS (SELECT 1 FROM time_entries WHERE id = ?1)
+            AND NOT EXISTS (SELECT 1 FROM time_entries WHERE ended_at IS NULL)
+            AND EXISTS (SELECT 1 FROM tasks WHERE id = ?2 AND deleted_at IS NULL)",
+        params![file.entry_id, file.task_id],
+        |row| row.get(0),
+    )?;
+    if !lost {
+        return Ok(None);
+    }
+    let mut entry = time_entries::new_entry(&file.task_id, file.started_at.clone(), None);
+    entry.id = file.entry_id.clone();
+    entry.note = file.note.clone();
+    entry.billable = file.billable;
+    time_entries::write_entry(conn, &entry)?;
+    Ok(Some(entry))
+}
+
+/// Look for a timer left running by the previous run. Runs once, as soon as
+/// the database is readable.
+pub fn check_recovery(app: &AppHandle) {
+    let state = app.state::<TimerState>();
+    let db = app.state::<Db>().unjournaled();
+    let Ok(mut inner) = state.inner.lock() else {
+        return;
+    };
+    if inner.checked || db.is_locked() {
+        return;
+    }
+    let file = read_timer_file(app);
+    let found = db.with_conn(|conn| {
+        let running = match &file {
+            Some(file) => match reinsert(conn, file)? {
+                Some(entry) => Some(entry),
+                None => time_entries::running_entry(conn)?,
+            },
+            None => time_entries::running_entry(conn)?,
+        };
+        let Some(entry) = running else {
+            return Ok(None);
+        };
+        let title = task_store::find_task(conn, &entry.task_id)?.map(|t| t.title);
+        Ok(Some((entry, title)))
+    });
+    inner.checked = true;
+    let (entry, task_title) = match found {
+        Ok(Some(found)) => found,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("recovery check failed: {e}");
+            return;
+        }
+    };
+    let last_seen = file.filter(|f| f.entry_id == entry.id).map(|f| f.last_seen);
+    let stale = match last_seen.as_deref().map(parse_utc) {
+        Some(Ok(seen)) => Utc::now() - seen > STALE_AFTER,
+        _ => true,
+    };
+    if !stale {
+        return;
+    }
+    let recovered = RecoveredTimer {
+        elapsed_seconds: elapsed_seconds(&entry),
+        entry,
+        task_title,
+        last_seen,
+    };
+    inner.recovered = Some(recovered.clone());
+    drop(inner);
+    let _ = app.emit(TIMER_RECOVERY_EVENT, recovered);
+    time_entries::notify(app);
+}
+
+/// Record the running entry on disk. Called whenever entries change and on
+/// every heartbeat.
+pub fn sync(app: &AppHandle) {
+    let state = app.state::<TimerState>();
+    if !state.inner.lock().is_ok_and(|inner| inner.checked) {
+        return;
+    }
+    let db = app.state::<Db>();
+    let result = db
+        .with_conn(|conn| time_entries::running_entry(conn))
+        .and_then(|running| write_heartbeat(app, running.as_ref()));
+    if let Err(e) = result {
+        tracing::warn!("{e}");
+    }
+}
+
+/// Check for a crashed timer, then keep the timer file's heartbeat fresh.
+pub fn spawn_timer_heartbeat(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || loop {
+        check_recovery(&handle);
+        if !handle.state::<Db>().is_locked() {
+            sync(&handle);
+        }
+        std::thread::sleep(HEARTBEAT);
+    });
+}
+
+/// The running entry with its elapsed time worked out from the wall clock,
+/// so it stays right across sleep and restarts.
+#[tauri::command]
+pub fn get_timer_status(db: State<'_, Db>) -> CommandResult<Option<TimerStatus>> {
+    let running = db.with_conn(|conn| time_entries::running_entry(conn))?;
+    Ok(running.map(|entry| TimerStatus {
+        elapsed_seconds: elapsed_seconds(&entry),
+        entry,
+    }))
+}
+
+/// A timer recovered at startup that the user hasn't dealt with yet.
+#[tauri::command]
+pub fn get_timer_recovery(state: State<'_, TimerState>) -> CommandResult<Option<RecoveredTimer>> {
+    let inner = state.inner.lock().map_err(|_| "Lock poisoned")?;
+    Ok(inner.recovered.clone())
+}
+
+#[tauri::command]
+pub fn resolve_timer_recovery(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, TimerState>,
+    resolution: TimerResolution,
+) -> CommandResult<Option<TimeEntry>> {
+    let mut inner = state.inner.lock().map_err(|_| "Lock poisoned")?;
+    let Some(recovered) = inner.recovered.clone() else {
+        return Err(CommandError::not_found("No timer to recover"));
+    };
+    let id = recovered.entry.id;
+    let result = match resolution {
+        TimerResolution::Keep => db.with_conn(|conn| time_entries::find_entry(conn, &id))?,
+        TimerResolution::StopAt(at) => {
+            let at = parse_utc(&at)?;
+            let start = parse_utc(&recovered.entry.started_at)?;
+            if at <= start || at > Utc::now() {
+                return Err(CommandError::invalid(
+                    "Stop time must fall between the start and now",
+                ));
+            }
+            db.with_conn(|conn| {
+                let Some(mut entry) = time_entries::find_entry(conn, &id)? else {
+                    return Ok(None);
+                };
+                if entry.ended_at.is_none() {
+                    entry.ended_at = Some(format_utc(at));
+                    entry.updated_at = now_utc();
+                    time_entries::write_entry(conn, &entry)?;
+                }
+                Ok(Some(entry))
+            })?
+        }
+        TimerResolution::Discard => {
+            db.with_conn(|conn| {
+                conn.execute("DELETE FROM time_entries WHERE id = ?1", params![id])
+            })?;
+            None
+        }
+    };
+    inner.recovered = None;
+    drop(inner);
+    time_entries::notify(&app);
+    Ok(result)
+}
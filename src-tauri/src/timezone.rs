@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::db::{format_utc, parse_utc};
+use crate::error::{CommandError, CommandResult};
+
+/// Emitted when the system time zone (or its UTC offset) changes, e.g. after
+/// travel, so the UI can recompute "today".
+pub const TIME_ZONE_EVENT: &str = "time-zone-changed";
+
+const ZONE_POLL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ZoneInfo {
+    /// IANA name, e.g. "Europe/Berlin". "UTC" if the system zone is unknown.
+    pub name: String,
+    pub offset_seconds: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ZoneChange {
+    previous: ZoneInfo,
+    current: ZoneInfo,
+}
+
+/// IANA name of the system time zone, stored alongside timestamps so the
+/// wall-clock time the user saw can be reconstructed later.
+pub fn system_zone() -> String {
+    iana_time_zone::get_timezone().unwrap_or_else(|_| "UTC".to_string())
+}
+
+fn current_zone() -> ZoneInfo {
+    ZoneInfo {
+        name: system_zone(),
+        offset_seconds: Local::now().offset().local_minus_utc(),
+    }
+}
+
+pub fn parse_zone(name: &str) -> CommandResult<Tz> {
+    name.parse::<Tz>()
+        .map_err(|_| CommandError::invalid(format!("Unknown time zone: {name}")))
+}
+
+/// Exchange (Windows) zone names, as Outlook events carry them, and the
+/// IANA zone each stands for — the "001" territory of CLDR's mapping.
+const WINDOWS_ZONES: &[(&str, &str)] = &[
+    ("Dateline Standard Time", "Etc/GMT+12"),
+    ("UTC-11", "Etc/GMT+11"),
+    ("Hawaiian Standard Time", "Pacific/Honolulu"),
+    ("Alaskan Standard Time", "America/Anchorage"),
+    ("Pacific Standard Time (Mexico)", "America/Tijuana"),
+    ("Pacific Standard Time", "America/Los_Angeles"),
+    ("US Mountain Standard Time", "America/Phoenix"),
+    ("Mountain Standard Time (Mexico)", "America/Mazatlan"),
+    ("Mountain Standard Time", "America/Denver"),
+    ("Central America Standard Time", "America/Guatemala"),
+    ("Central Standard Time (Mexico)", "America/Mexico_City"),
+    ("Central Standard Time", "America/Chicago"),
+    ("Canada Central Standard Time", "America/Regina"),
+    ("SA Pacific Standard Time", "America/Bogota"),
+    ("Eastern Standard Time (Mexico)", "America/Cancun"),
+    ("Eastern Standard Time", "America/New_York"),
+    ("US Eastern Standard Time", "America/Indianapolis"),
+    ("Venezuela Standard Time", "America/Caracas"),
+    ("Atlantic Standard Time", "America/Halifax"),
+    ("SA Western Standard Time", "America/La_Paz"),
+    ("Pacific SA Standard Time", "America/Santiago"),
+    ("Newfoundland Standard Time", "America/St_Johns"),
+    ("E. South America Standard Time", "America/Sao_Paulo"),
+    ("Argentina Standard Time", "America/Buenos_Aires"),
+    ("SA Eastern Standard Time", "America/Cayenne"),
+    ("Greenland Standard Time", "America/Godthab"),
+    ("UTC-02", "Etc/GMT+2"),
+    ("Azores Standard Time", "Atlantic/Azores"),
+    ("Cape Verde Standard Time", "Atlantic/Cape_Verde"),
+    ("UTC", "Etc/UTC"),
+    ("GMT Standard Time", "Europe/London"),
+    ("Greenwich Standard Time", "Atlantic/Reykjavik"),
+    ("Morocco Standard Time", "Africa/Casablanca"),
+    ("W. Europe Standard Time", "Europe/Berlin"),
+    ("Central Europe Standard Time", "Europe/Budapest"),
+    ("Romance Standard Time", "Europe/Paris"),
+    ("Central European Standard Time", "Europe/Warsaw"),
+    ("W. Central Africa Standard Time", "Africa/Lagos"),
+    ("GTB Standard Time", "Europe/Bucharest"),
+    ("E. Europe Standard Time", "Europe/Chisinau"),
+    ("Egypt Standard Time", "Africa/Cairo"),
+    ("FLE Standard Time", "Europe/Kiev"),
+    ("Israel Standard Time", "Asia/Jerusalem"),
+    ("South Africa Standard Time", "Africa/Johannesburg"),
+    ("Turkey Standard Time", "Europe/Istanbul"),
+    ("Arabic Standard Time", "Asia/Baghdad"),
+    ("Arab Standard Time", "Asia/Riyadh"),
+    ("Russian Standard Time", "Europe/Moscow"),
+    ("E. Africa Standard Time", "Africa/Nairobi"),
+    ("Iran Standard Time", "Asia/Tehran"),
+    ("Arabian Standard Time", "Asia/Dubai"),
+    ("Afghanistan Standard Time", "Asia/Kabul"),
+    ("Pakistan Standard Time", "Asia/Karachi"),
+    ("West Asia Standard Time", "Asia/Tashkent"),
+    ("India Standard Time", "Asia/Calcutta"),
+    ("Sri Lanka Standard Time", "Asia/Colombo"),
+    ("Nepal Standard Time", "Asia/Katmandu"),
+    ("Central Asia Standard Time", "Asia/Almaty"),
+    ("Bangladesh Standard Time", "Asia/Dhaka"),
+    ("Myanmar Standard Time", "Asia/Rangoon"),
+    ("SE Asia Standard Time", "Asia/Bangkok"),
+    ("China Standard Time", "Asia/Shanghai"),
+    ("Singapore Standard Time", "Asia/Singapore"),
+    ("Taipei Standard Time", "Asia/Taipei"),
+    ("W. Australia Standard Time", "Australia/Perth"),
+    ("Korea Standard Time", "Asia/Seoul"),
+    ("Tokyo Standard Time", "Asia/Tokyo"),
+    ("Cen. Australia Standard Time", "Australia/Adelaide"),
+    ("AUS Central Standard Time", "Australia/Darwin"),
+    ("E. Australia Standard Time", "Australia/Brisbane"),
+    ("AUS Eastern Standard Time", "Australia/Sydney"),
+    ("West Pacific Standard Time", "Pacific/Port_Moresby"),
+    ("Tasmania Standard Time", "Australia/Hobart"),
+    ("Vladivostok Standard Time", "Asia/Vladivostok"),
+    ("Central Pacific Standard Time", "Pacific/Guadalcanal"),
+    ("New Zealand Standard Time", "Pacific/Auckland"),
+    ("Fiji Standard Time", "Pacific/Fiji"),
+    ("Tonga Standard Time", "Pacific/Tongatapu"),
+    ("Samoa Standard Time", "Pacific/Apia"),
+    ("Line Islands Standard Time", "Pacific/Kiritimati"),
+];
+
+/// A zone by IANA name, or by the Windows name Exchange uses for it.
+pub fn parse_any_zone(name: &str) -> CommandResult<Tz> {
+    parse_zone(name).or_else(|e| {
+        WINDOWS_ZONES
+            .iter()
+            .find(|(windows, _)| windows.eq_ignore_ascii_case(name))
+            .map_or(Err(e), |(_, iana)| parse_zone(iana))
+    })
+}
+
+/// Pin a wall-clock time to an instant in `tz`. Times skipped by a DST jump
+/// move forward past the gap; times repeated by a fall-back use the first.
+pub fn resolve_local(local: NaiveDateTime, tz: &Tz) -> Option<DateTime<Tz>> {
+    let mut probe = local;
+    // Gaps are at most a couple of hours; step forward until we're out.
+    for _ in 0..16 {
+        if let Some(dt) = tz.from_local_datetime(&probe).earliest() {
+            return Some(dt);
+        }
+        probe += chrono::Duration::minutes(15);
+    }
+    None
+}
+
+fn parse_local(value: &str) -> CommandResult<NaiveDateTime> {
+    [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|fmt| NaiveDateTime::parse_from_str(value, fmt).ok())
+    .ok_or_else(|| CommandError::invalid(format!("Invalid local time: {value}")))
+}
+
+/// Watch for system time zone changes and emit `TIME_ZONE_EVENT`.
+pub fn spawn_zone_watcher(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || {
+        let mut last = current_zone();
+        loop {
+            std::thread::sleep(ZONE_POLL);
+            let current = current_zone();
+            if current != last {
+                tracing::info!("{} -> {}", last.name, current.name);
+                let _ = handle.emit(
+                    TIME_ZONE_EVENT,
+                    ZoneChange {
+                        previous: last,
+                        current: current.clone(),
+                    },
+                );
+                last = current;
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_time_zone() -> ZoneInfo {
+    current_zone()
+}
+
+/// Render an RFC 3339 instant in another zone (RFC 3339 with that zone's offset).
+#[tauri::command]
+pub fn convert_time(timestamp: String, zone: String) -> CommandResult<String> {
+    let tz = parse_zone(&zone)?;
+    Ok(parse_utc(&timestamp)?.with_timezone(&tz).to_rfc3339())
+}
+
+/// Convert a wall-clock time in `zone` (`YYYY-MM-DDTHH:MM[:SS]`) to the
+/// canonical UTC timestamp used in storage.
+#[tauri::command]
+pub fn local_to_utc(local: String, zone: String) -> CommandResult<String> {
+    let tz = parse_zone(&zone)?;
+    let dt = resolve_local(parse_local(&local)?, &tz)
+        .ok_or_else(|| CommandError::invalid(format!("{local} does not exist in {zone}")))?;
+    Ok(format_utc(dt.with_timezone(&Utc)))
+}
+
+/// Offset of `zone` at `timestamp` (default now), in seconds east of UTC.
+#[tauri::command]
+pub fn zone_offset(zone: String, timestamp: Option<String>) -> CommandResult<i32> {
+    let tz = parse_zone(&zone)?;
+    let at = match timestamp {
+        Some(t) => parse_utc(&t)?,
+        None => Utc::now(),
+    };
+    Ok(tz
+        .offset_from_utc_datetime(&at.naive_utc())
+        .fix()
+        .local_minus_utc())
+}
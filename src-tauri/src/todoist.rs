@@ -0,0 +1,1512 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
+use reqwest::{Client, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, State};
+
+use crate::account_health::AccountHealth;
+use crate::conflicts::{self, ConflictPolicy};
+use crate::crdt;
+use crate::credentials;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::http;
+use crate::outbox::{self, Replay};
+use crate::rate_limit;
+use crate::subtasks;
+use crate::sync_provider::{
+    self, Capabilities, RemoteCollection, SyncFuture, SyncMode, SyncProvider,
+};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::tags;
+use crate::task_store::{self, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+
+/// `external_refs.source` for tasks that came from Todoist.
+const SOURCE: &str = "todoist";
+
+const SYNC_URL: &str = "https://api.todoist.com/api/v1/sync";
+/// Token asking for everything, as on the first sync.
+const FULL_SYNC: &str = "*";
+const RESOURCE_TYPES: &str = r#"["projects","sections","items"]"#;
+/// Most commands Todoist takes in one request.
+const MAX_COMMANDS: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TodoistAccount {
+    pub id: String,
+    pub name: String,
+    pub last_synced_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTodoistAccount {
+    /// Defaults to the Todoist user's name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The API token from Todoist's integration settings.
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TodoistSyncReport {
+    pub account_id: String,
+    pub name: String,
+    /// Tasks created or updated from Todoist.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because they were deleted in Todoist.
+    pub removed: usize,
+    /// Tasks uploaded, new or changed.
+    pub uploaded: usize,
+    /// Tasks deleted in Todoist because they were deleted here.
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for TodoistSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.uploaded + self.deleted
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteUser {
+    #[serde(default)]
+    full_name: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteProject {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    is_deleted: bool,
+    #[serde(default)]
+    is_archived: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteSection {
+    id: String,
+    project_id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    is_deleted: bool,
+    #[serde(default)]
+    is_archived: bool,
+}
+
+/// A due date: a date, a floating local time, or a UTC time ending in `Z`
+/// for tasks pinned to a zone.
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteDue {
+    date: String,
+    #[serde(default)]
+    is_recurring: bool,
+}
+
+impl RemoteDue {
+    /// The due date as tasks keep it: a date, or a wall-clock time in
+    /// `local`.
+    fn local(&self, local: &Tz) -> Option<String> {
+        if self.date.len() == 10 {
+            return Some(self.date.clone());
+        }
+        if self.date.ends_with('Z') {
+            let at = parse_utc(&self.date).ok()?;
+            return Some(at.with_timezone(local).format("%Y-%m-%dT%H:%M").to_string());
+        }
+        let at = NaiveDateTime::parse_from_str(&self.date, "%Y-%m-%dT%H:%M:%S").ok()?;
+        Some(at.format("%Y-%m-%dT%H:%M").to_string())
+    }
+}
+
+/// A task as the sync returns it. Deleted ones keep their fields and set
+/// `is_deleted`.
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteItem {
+    id: String,
+    project_id: String,
+    #[serde(default)]
+    section_id: Option<String>,
+    #[serde(default)]
+    parent_id: Option<String>,
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    description: String,
+    /// 1 (none) to 4 (urgent).
+    #[serde(default = "default_priority")]
+    priority: i64,
+    #[serde(default)]
+    due: Option<RemoteDue>,
+    #[serde(default)]
+    labels: Vec<String>,
+    #[serde(default)]
+    checked: bool,
+    #[serde(default)]
+    completed_at: Option<String>,
+    #[serde(default)]
+    updated_at: Option<String>,
+    #[serde(default)]
+    is_deleted: bool,
+}
+
+fn default_priority() -> i64 {
+    1
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SyncResponse {
+    #[serde(default)]
+    sync_token: Option<String>,
+    #[serde(default)]
+    full_sync: bool,
+    #[serde(default)]
+    user: Option<RemoteUser>,
+    #[serde(default)]
+    projects: Vec<RemoteProject>,
+    #[serde(default)]
+    sections: Vec<RemoteSection>,
+    #[serde(default)]
+    items: Vec<RemoteItem>,
+    /// "ok", or the error, for each command by uuid.
+    #[serde(default)]
+    sync_status: HashMap<String, serde_json::Value>,
+    /// Ids given to items added under a temporary id.
+    #[serde(default)]
+    temp_id_mapping: HashMap<String, String>,
+}
+
+/// The priority (0-3, none to high) for a Todoist priority.
+fn from_todoist_priority(priority: i64) -> Option<i64> {
+    match priority {
+        4 => Some(3),
+        3 => Some(2),
+        2 => Some(1),
+        _ => None,
+    }
+}
+
+fn to_todoist_priority(priority: Option<i64>) -> i64 {
+    match priority {
+        Some(p @ 1..=3) => p + 1,
+        _ => 1,
+    }
+}
+
+/// The due date Todoist takes for a task's: dates stay dates, times go up
+/// as floating local times.
+fn to_todoist_due(due: Option<&str>) -> serde_json::Value {
+    match due {
+        Some(date) if date.len() == 10 => json!({ "date": date }),
+        Some(at) => match NaiveDateTime::parse_from_str(at, "%Y-%m-%dT%H:%M") {
+            Ok(at) => json!({ "date": at.format("%Y-%m-%dT%H:%M:%S").to_string() }),
+            Err(_) => json!({ "date": at.get(..10).unwrap_or(at) }),
+        },
+        None => serde_json::Value::Null,
+    }
+}
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<TodoistAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, last_synced_at, created_at, updated_at
+         FROM todoist_accounts ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TodoistAccount {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            last_synced_at: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("todoist:{account_id}")
+}
+
+/// A connection to the Todoist Sync API with one account's token.
+struct Api {
+    client: Client,
+    token: String,
+}
+
+impl Api {
+    fn new(token: String) -> CommandResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self { client, token })
+    }
+
+    fn connect(account_id: &str) -> CommandResult<Self> {
+        Self::new(credentials::require(
+            &keyring_name(account_id),
+            "API token",
+        )?)
+    }
+
+    /// Ask for just the user, for the account health check.
+    async fn probe(&self) -> Result<Response, reqwest::Error> {
+        self.client
+            .post(SYNC_URL)
+            .bearer_auth(&self.token)
+            .form(&[("sync_token", "*"), ("resource_types", r#"["user"]"#)])
+            .send()
+            .await
+    }
+
+    async fn sync(&self, form: &[(&str, &str)]) -> CommandResult<SyncResponse> {
+        rate_limit::check(SOURCE, "Todoist")?;
+        let response = self
+            .client
+            .post(SYNC_URL)
+            .bearer_auth(&self.token)
+            .form(form)
+            .send()
+            .await
+            .context("Todoist")?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(CommandError::unauthorized(
+                "Todoist refused the API token; connect the account again",
+            )
+            .with_provider(SOURCE)),
+            StatusCode::TOO_MANY_REQUESTS => {
+                Err(rate_limit::limited(SOURCE, "Todoist", response.headers()))
+            }
+            status if !status.is_success() => Err(CommandError::status(
+                status,
+                format!("Todoist: HTTP {}", status.as_u16()),
+            )
+            .with_provider(SOURCE)),
+            _ => http::read_json(response).await.context("Todoist"),
+        }
+    }
+}
+
+/// Save the projects and sections the sync sent. Each is filed under a
+/// local project named after it ("Project / Section" for sections) when
+/// first seen; renaming it in Todoist keeps that project. Deleted and
+/// archived ones are dropped; their tasks stay.
+fn store_folders(
+    conn: &Connection,
+    account_id: &str,
+    projects: &[RemoteProject],
+    sections: &[RemoteSection],
+) -> rusqlite::Result<()> {
+    for project in projects {
+        if project.is_deleted || project.is_archived {
+            conn.execute(
+                "DELETE FROM todoist_projects WHERE account_id = ?1 AND remote_id = ?2",
+                params![account_id, project.id],
+            )?;
+            continue;
+        }
+        let name = project.name.trim();
+        let name = if name.is_empty() { "Todoist" } else { name };
+        conn.execute(
+            "INSERT INTO todoist_projects (account_id, remote_id, name, project)
+             VALUES (?1, ?2, ?3, ?3)
+             ON CONFLICT(account_id, remote_id) DO UPDATE SET name = excluded.name",
+            params![account_id, project.id, name],
+        )?;
+    }
+    for section in sections {
+        if section.is_deleted || section.is_archived {
+            conn.execute(
+                "DELETE FROM todoist_sections WHERE account_id = ?1 AND remote_id = ?2",
+                params![account_id, section.id],
+            )?;
+            continue;
+        }
+        let parent: Option<String> = conn
+            .query_row(
+                "SELECT project FROM todoist_projects WHERE account_id = ?1 AND remote_id = ?2",
+                params![account_id, section.project_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let name = section.name.trim();
+        let project = match parent {
+            Some(parent) => format!("{parent} / {name}"),
+            None => name.to_string(),
+        };
+        conn.execute(
+            "INSERT INTO todoist_sections (account_id, remote_id, project_remote_id, name, project)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(account_id, remote_id) DO UPDATE SET
+                 project_remote_id = excluded.project_remote_id,
+                 name = excluded.name",
+            params![account_id, section.id, section.project_id, name, project],
+        )?;
+    }
+    Ok(())
+}
+
+/// Where a task sits in Todoist.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Location {
+    project_id: String,
+    section_id: Option<String>,
+    parent_id: Option<String>,
+}
+
+/// The account's synced projects and sections and the local projects
+/// they're filed under.
+struct Folders {
+    /// Local project by (project id, section id).
+    local: HashMap<(String, Option<String>), String>,
+    /// The other way, by lowercase local project name.
+    remote: HashMap<String, (String, Option<String>)>,
+    /// Projects left out of syncs.
+    skipped: HashSet<String>,
+}
+
+impl Folders {
+    fn load(conn: &Connection, account_id: &str) -> rusqlite::Result<Self> {
+        let mut stmt = conn.prepare(
+            "SELECT remote_id FROM todoist_projects WHERE account_id = ?1 AND enabled = 0",
+        )?;
+        let skipped = stmt
+            .query_map(params![account_id], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<HashSet<_>>>()?;
+        let mut stmt = conn.prepare(
+            "SELECT remote_id, NULL, project FROM todoist_projects
+             WHERE account_id = ?1 AND enabled = 1
+             UNION ALL
+             SELECT s.project_remote_id, s.remote_id, s.project FROM todoist_sections s
+             JOIN todoist_projects p
+               ON p.account_id = s.account_id AND p.remote_id = s.project_remote_id
+             WHERE s.account_id = ?1 AND p.enabled = 1",
+        )?;
+        let rows = stmt.query_map(params![account_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        let mut folders = Folders {
+            local: HashMap::new(),
+            remote: HashMap::new(),
+            skipped,
+        };
+        for row in rows {
+            let (project_id, section_id, project) = row?;
+            // Projects come first, so a name shared with a section goes to
+            // the project.
+            folders
+                .remote
+                .entry(project.to_lowercase())
+                .or_insert_with(|| (project_id.clone(), section_id.clone()));
+            folders.local.insert((project_id, section_id), project);
+        }
+        Ok(folders)
+    }
+
+    fn project_for(&self, item: &RemoteItem) -> Option<&str> {
+        self.local
+            .get(&(item.project_id.clone(), item.section_id.clone()))
+            .or_else(|| self.local.get(&(item.project_id.clone(), None)))
+            .map(String::as_str)
+    }
+
+    fn folder_for(&self, project: Option<&str>) -> Option<&(String, Option<String>)> {
+        self.remote.get(&project?.to_lowercase())
+    }
+
+    /// Whether items in `project_id` take part in syncs.
+    fn synced(&self, project_id: &str) -> bool {
+        !self.skipped.contains(project_id)
+    }
+}
+
+/// What the last sync knew about one Todoist task.
+#[derive(Debug, Clone)]
+struct ItemRow {
+    task_id: String,
+    location: Location,
+    /// The due date it had, as tasks keep it. A recurring task's due date
+    /// is only sent when it was changed here, since sending it ends the
+    /// recurrence.
+    due: Option<String>,
+    recurring: bool,
+    checked: bool,
+    /// The task's `updated_at` when it last matched Todoist.
+    synced_at: String,
+}
+
+fn load_items(conn: &Connection, account_id: &str) -> rusqlite::Result<HashMap<String, ItemRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, task_id, project_id, section_id, parent_id, due, recurring, checked,
+                synced_at
+         FROM todoist_items WHERE account_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            ItemRow {
+                task_id: row.get(1)?,
+                location: Location {
+                    project_id: row.get(2)?,
+                    section_id: row.get(3)?,
+                    parent_id: row.get(4)?,
+                },
+                due: row.get(5)?,
+                recurring: row.get(6)?,
+                checked: row.get(7)?,
+                synced_at: row.get(8)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_item(
+    conn: &Connection,
+    account_id: &str,
+    remote_id: &str,
+    item: &ItemRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO todoist_items
+             (account_id, remote_id, task_id, project_id, section_id, parent_id, due, recurring,
+              checked, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT(account_id, remote_id) DO UPDATE SET
+             task_id = excluded.task_id,
+             project_id = excluded.project_id,
+             section_id = excluded.section_id,
+             parent_id = excluded.parent_id,
+             due = excluded.due,
+             recurring = excluded.recurring,
+             checked = excluded.checked,
+             synced_at = excluded.synced_at",
+        params![
+            account_id,
+            remote_id,
+            item.task_id,
+            item.location.project_id,
+            item.location.section_id,
+            item.location.parent_id,
+            item.due,
+            item.recurring,
+            item.checked,
+            item.synced_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_item(conn: &Connection, account_id: &str, remote_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM todoist_items WHERE account_id = ?1 AND remote_id = ?2",
+        params![account_id, remote_id],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool, completed_at: Option<String>) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = completed_at.or_else(|| Some(now_utc()));
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// What `write_remote` did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Written {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+/// Create or update the task for `remote`. Unless `policy` says remote
+/// wins, a field edited here after Todoist last changed the task keeps the
+/// local value. Parents are set afterwards, once every task in the sync
+/// exists.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    remote: &RemoteItem,
+    project: Option<&str>,
+    local: &Tz,
+    policy: ConflictPolicy,
+) -> rusqlite::Result<(Task, Written)> {
+    let title = remote.content.trim();
+    let title = if title.is_empty() { "Untitled" } else { title }.to_string();
+    let description = Some(remote.description.clone()).filter(|d| !d.trim().is_empty());
+    let priority = from_todoist_priority(remote.priority);
+    let due = remote.due.as_ref().and_then(|due| due.local(local));
+    let labels = tags::normalize_names(
+        &remote
+            .labels
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .cloned()
+            .collect::<Vec<_>>(),
+    )
+    .unwrap_or_default();
+    let completed_at = remote
+        .completed_at
+        .as_deref()
+        .and_then(|at| parse_utc(at).ok())
+        .map(format_utc);
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description,
+            project: project.map(str::to_string),
+            priority,
+            due,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if remote.checked {
+            set_done(&mut task, true, completed_at);
+            task_store::write_task(conn, &task)?;
+        }
+        tags::set_task_tags(conn, &task.id, &labels)?;
+        task.tags = labels;
+        return Ok((task, Written::Created));
+    };
+
+    let modified = remote
+        .updated_at
+        .as_deref()
+        .and_then(|at| parse_utc(at).ok())
+        .map(|at| at.timestamp_millis());
+    let take = conflicts::remote_fields(conn, &task.id, modified, policy)?;
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("description") {
+        task.description = description;
+    }
+    if take("priority") {
+        task.priority = priority;
+    }
+    if take("due") {
+        task.due = due;
+    }
+    if take("project") && project.is_some() {
+        task.project = project.map(str::to_string);
+    }
+    if take("status") && remote.checked != (task.status == STATUS_DONE) {
+        set_done(&mut task, remote.checked, completed_at);
+    }
+    let tags_changed = {
+        let mut have: Vec<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+        let mut want: Vec<String> = labels.iter().map(|t| t.to_lowercase()).collect();
+        have.sort();
+        want.sort();
+        take("tags") && have != want
+    };
+
+    let changed = task.title != before.title
+        || task.description != before.description
+        || task.priority != before.priority
+        || task.due != before.due
+        || task.project != before.project
+        || task.status != before.status;
+    if !changed && !tags_changed {
+        return Ok((task, Written::Unchanged));
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    if tags_changed {
+        tags::set_task_tags(conn, &task.id, &labels)?;
+        task.tags = labels;
+    }
+    Ok((task, Written::Updated))
+}
+
+/// Make `task_id` a subtask of `parent_id`, or top-level when `None`.
+fn set_parent(conn: &Connection, task_id: &str, parent_id: Option<&str>) -> rusqlite::Result<()> {
+    let sort_key = match parent_id {
+        Some(parent_id) => Some(subtasks::append_key(conn, parent_id)?),
+        None => None,
+    };
+    conn.execute(
+        "UPDATE tasks SET parent_id = ?2, sort_key = ?3, updated_at = ?4 WHERE id = ?1",
+        params![task_id, parent_id, sort_key, now_utc()],
+    )?;
+    Ok(())
+}
+
+/// Apply what the sync sent, reading due times in `local`, unless only
+/// exporting. Returns the commands for local changes.
+fn merge_remote(
+    conn: &mut Connection,
+    account_id: &str,
+    response: &SyncResponse,
+    local: &Tz,
+    imports: bool,
+    report: &mut TodoistSyncReport,
+) -> rusqlite::Result<Vec<Change>> {
+    let tx = conn.transaction()?;
+    store_folders(&tx, account_id, &response.projects, &response.sections)?;
+    let folders = Folders::load(&tx, account_id)?;
+    let items = load_items(&tx, account_id)?;
+    let policy = conflicts::policy(&tx, SOURCE)?;
+
+    // Remote id to task id, for parents.
+    let mut linked: HashMap<String, String> = items
+        .iter()
+        .map(|(remote_id, item)| (remote_id.clone(), item.task_id.clone()))
+        .collect();
+    // Items written, with the sync time to keep for those edited here too.
+    let mut merged: Vec<(&RemoteItem, String, Option<String>)> = Vec::new();
+    for remote in &response.items {
+        if !folders.synced(&remote.project_id) {
+            continue;
+        }
+        let known = items.get(&remote.id);
+        if remote.is_deleted {
+            if let Some(item) = known {
+                if imports && trash::trash_task(&tx, &item.task_id)? {
+                    report.removed += 1;
+                }
+                forget_item(&tx, account_id, &remote.id)?;
+                linked.remove(&remote.id);
+            }
+            continue;
+        }
+        let task_id = match known {
+            Some(item) => Some(item.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, remote.id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if known.is_some() && existing.is_none() {
+            // Deleted here; the delete is uploaded below.
+            continue;
+        }
+        if !imports {
+            // Only where the item is and its state are taken, so local
+            // edits go up over it.
+            if let Some(item) = known {
+                let item = ItemRow {
+                    location: Location {
+                        project_id: remote.project_id.clone(),
+                        section_id: remote.section_id.clone(),
+                        parent_id: remote.parent_id.clone(),
+                    },
+                    due: remote.due.as_ref().and_then(|due| due.local(local)),
+                    recurring: remote.due.as_ref().is_some_and(|due| due.is_recurring),
+                    checked: remote.checked,
+                    ..item.clone()
+                };
+                save_item(&tx, account_id, &remote.id, &item)?;
+            }
+            continue;
+        }
+        let dirty = match (known, &existing) {
+            (Some(item), Some(task)) if task.updated_at > item.synced_at => {
+                Some(item.synced_at.clone())
+            }
+            _ => None,
+        };
+        let project = folders.project_for(remote);
+        let (written, outcome) = match existing {
+            Some(existing)
+                if dirty.is_some()
+                    && conflicts::keep_local(&tx, SOURCE, policy, &existing, |tx| {
+                        let forced = ConflictPolicy::Remote;
+                        write_remote(tx, Some(existing.clone()), remote, project, local, forced)
+                            .map(|(task, _)| task)
+                    })? =>
+            {
+                (existing, Written::Unchanged)
+            }
+            existing => write_remote(&tx, existing, remote, project, local, policy)?,
+        };
+        // Remote wins: the local edits are gone, so there's nothing to upload.
+        let dirty = dirty.filter(|_| policy != ConflictPolicy::Remote);
+        match outcome {
+            Written::Created => {
+                report.created += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, remote.id, written.id, now_utc()],
+                )?;
+            }
+            Written::Updated => report.updated += 1,
+            Written::Unchanged => {}
+        }
+        linked.insert(remote.id.clone(), written.id.clone());
+        merged.push((remote, written.id, dirty));
+    }
+
+    for (remote, task_id, dirty) in &merged {
+        let parent = remote
+            .parent_id
+            .as_ref()
+            .and_then(|id| linked.get(id))
+            .cloned();
+        let Some(task) = task_store::find_task(&tx, task_id)? else {
+            continue;
+        };
+        let modified = remote
+            .updated_at
+            .as_deref()
+            .and_then(|at| parse_utc(at).ok())
+            .map(|at| at.timestamp_millis());
+        let moved_here = match (modified, crdt::field_stamps(&tx, task_id)?.get("parent_id")) {
+            (Some(modified), Some(stamp)) => stamp.clock > modified,
+            _ => false,
+        };
+        if task.parent_id != parent && !moved_here {
+            set_parent(&tx, task_id, parent.as_deref())?;
+        }
+        let Some(task) = task_store::find_task(&tx, task_id)? else {
+            continue;
+        };
+        let item = ItemRow {
+            task_id: task.id,
+            location: Location {
+                project_id: remote.project_id.clone(),
+                section_id: remote.section_id.clone(),
+                parent_id: remote.parent_id.clone(),
+            },
+            due: remote.due.as_ref().and_then(|due| due.local(local)),
+            recurring: remote.due.as_ref().is_some_and(|due| due.is_recurring),
+            checked: remote.checked,
+            // Local edits Todoist doesn't have yet are kept dirty.
+            synced_at: dirty.clone().unwrap_or(task.updated_at),
+        };
+        save_item(&tx, account_id, &remote.id, &item)?;
+    }
+
+    let changes = local_changes(&tx, account_id, &folders)?;
+    tx.commit()?;
+    Ok(changes)
+}
+
+/// One local change as Todoist commands, with the link to save once every
+/// command went through.
+#[derive(Debug)]
+struct Change {
+    commands: Vec<serde_json::Value>,
+    outcome: Outcome,
+}
+
+#[derive(Debug)]
+enum Outcome {
+    /// `remote_id` may be a temporary id until Todoist maps it.
+    Saved {
+        remote_id: String,
+        item: ItemRow,
+    },
+    Deleted {
+        remote_id: String,
+        task_id: String,
+    },
+}
+
+impl Change {
+    fn task_id(&self) -> &str {
+        match &self.outcome {
+            Outcome::Saved { item, .. } => &item.task_id,
+            Outcome::Deleted { task_id, .. } => task_id,
+        }
+    }
+}
+
+fn command(kind: &str, args: serde_json::Value) -> serde_json::Value {
+    json!({
+        "type": kind,
+        "uuid": uuid::Uuid::new_v4().to_string(),
+        "args": args,
+    })
+}
+
+/// The fields sent for a task, new or changed. `due` is left out when
+/// `None`.
+fn item_args(task: &Task, due: Option<serde_json::Value>) -> serde_json::Value {
+    let mut args = json!({
+        "content": task.title,
+        "description": task.description.clone().unwrap_or_default(),
+        "priority": to_todoist_priority(task.priority),
+        "labels": task.tags,
+    });
+    if let Some(due) = due {
+        args["due"] = due;
+    }
+    args
+}
+
+/// The commands uploading local changes: edits and deletes of linked
+/// tasks, open tasks new to a project that came from Todoist, and new
+/// subtasks of linked tasks, leaving out projects not synced.
+fn local_changes(
+    conn: &Connection,
+    account_id: &str,
+    folders: &Folders,
+) -> rusqlite::Result<Vec<Change>> {
+    let mut items = load_items(conn, account_id)?;
+    items.retain(|_, item| folders.synced(&item.location.project_id));
+    let held = conflicts::held(conn, SOURCE)?;
+    let by_task: HashMap<String, String> = items
+        .iter()
+        .map(|(remote_id, item)| (item.task_id.clone(), remote_id.clone()))
+        .collect();
+    let mut changes = Vec::new();
+
+    for (remote_id, item) in &items {
+        let Some(task) = task_store::find_task(conn, &item.task_id)? else {
+            changes.push(Change {
+                commands: vec![command("item_delete", json!({ "id": remote_id }))],
+                outcome: Outcome::Deleted {
+                    remote_id: remote_id.clone(),
+                    task_id: item.task_id.clone(),
+                },
+            });
+            continue;
+        };
+        if task.updated_at <= item.synced_at || held.contains(&task.id) {
+            continue;
+        }
+        let due_changed = task.due != item.due;
+        let due = due_changed.then(|| to_todoist_due(task.due.as_deref()));
+        let mut args = item_args(&task, due);
+        args["id"] = json!(remote_id);
+        let mut commands = vec![command("item_update", args)];
+
+        let parent = task.parent_id.as_ref().and_then(|id| by_task.get(id));
+        let mut location = item.location.clone();
+        match parent {
+            Some(parent) if location.parent_id.as_ref() != Some(parent) => {
+                commands.push(command(
+                    "item_move",
+                    json!({ "id": remote_id, "parent_id": parent }),
+                ));
+                location.parent_id = Some(parent.clone());
+            }
+            Some(_) => {}
+            None => {
+                let folder = folders
+                    .folder_for(task.project.as_deref())
+                    .cloned()
+                    .unwrap_or((location.project_id.clone(), location.section_id.clone()));
+                if location.parent_id.is_some()
+                    || (folder.0.as_str(), folder.1.as_deref())
+                        != (location.project_id.as_str(), location.section_id.as_deref())
+                {
+                    let args = match &folder.1 {
+                        Some(section) => json!({ "id": remote_id, "section_id": section }),
+                        None => json!({ "id": remote_id, "project_id": folder.0 }),
+                    };
+                    commands.push(command("item_move", args));
+                    location = Location {
+                        project_id: folder.0,
+                        section_id: folder.1,
+                        parent_id: None,
+                    };
+                }
+            }
+        }
+
+        let done = task.status == STATUS_DONE;
+        if done != item.checked {
+            // Closing a recurring task moves it to its next date, which
+            // comes back with the next sync.
+            let kind = if done {
+                "item_close"
+            } else {
+                "item_uncomplete"
+            };
+            commands.push(command(kind, json!({ "id": remote_id })));
+        }
+        changes.push(Change {
+            commands,
+            outcome: Outcome::Saved {
+                remote_id: remote_id.clone(),
+                item: ItemRow {
+                    task_id: task.id.clone(),
+                    location,
+                    due: task.due.clone(),
+                    recurring: item.recurring && !due_changed,
+                    checked: done,
+                    synced_at: task.updated_at.clone(),
+                },
+            },
+        });
+    }
+
+    // New tasks, a level at a time so subtasks can name their parents by
+    // temporary id.
+    let mut remote_ids: HashMap<String, String> = by_task;
+    let mut pending: Vec<Task> = Vec::new();
+    if !folders.remote.is_empty() {
+        let mut stmt = conn.prepare(
+            "SELECT id FROM tasks
+             WHERE deleted_at IS NULL AND parent_id IS NULL AND status = ?1
+               AND project IS NOT NULL
+               AND id NOT IN (SELECT task_id FROM todoist_items WHERE account_id = ?2)
+             ORDER BY created_at",
+        )?;
+        let ids = stmt
+            .query_map(params![STATUS_OPEN, account_id], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+        for id in ids {
+            if let Some(task) = task_store::find_task(conn, &id)? {
+                if folders.folder_for(task.project.as_deref()).is_some() {
+                    pending.push(task);
+                }
+            }
+        }
+    }
+    let mut parents: Vec<String> = remote_ids.keys().cloned().collect();
+    loop {
+        for task in pending.drain(..) {
+            if let Some(change) = add_change(task, folders, &mut remote_ids) {
+                parents.push(change.0);
+                changes.push(change.1);
+            }
+        }
+        for parent in parents.drain(..) {
+            for child in subtasks::children(conn, &parent)? {
+                if child.status == STATUS_OPEN && !remote_ids.contains_key(&child.id) {
+                    pending.push(child);
+                }
+            }
+        }
+        if pending.is_empty() {
+            return Ok(changes);
+        }
+    }
+}
+
+/// An `item_add` for a new task, under its parent when that's in Todoist
+/// and in its project's folder otherwise. Records the temporary id in
+/// `remote_ids` and returns it with the task id.
+fn add_change(
+    task: Task,
+    folders: &Folders,
+    remote_ids: &mut HashMap<String, String>,
+) -> Option<(String, Change)> {
+    let temp_id = uuid::Uuid::new_v4().to_string();
+    let mut args = item_args(&task, Some(to_todoist_due(task.due.as_deref())));
+    let location = match task.parent_id.as_ref().and_then(|id| remote_ids.get(id)) {
+        Some(parent) => {
+            args["parent_id"] = json!(parent);
+            Location {
+                project_id: String::new(),
+                section_id: None,
+                parent_id: Some(parent.clone()),
+            }
+        }
+        None => {
+            let (project_id, section_id) = folders.folder_for(task.project.as_deref())?.clone();
+            args["project_id"] = json!(project_id);
+            if let Some(section) = &section_id {
+                args["section_id"] = json!(section);
+            }
+            Location {
+                project_id,
+                section_id,
+                parent_id: None,
+            }
+        }
+    };
+    let mut add = command("item_add", args);
+    add["temp_id"] = json!(temp_id);
+    remote_ids.insert(task.id.clone(), temp_id.clone());
+    let change = Change {
+        commands: vec![add],
+        outcome: Outcome::Saved {
+            remote_id: temp_id,
+            item: ItemRow {
+                task_id: task.id.clone(),
+                location,
+                due: task.due,
+                recurring: false,
+                checked: false,
+                synced_at: task.updated_at,
+            },
+        },
+    };
+    Some((task.id, change))
+}
+
+/// Replace temporary ids Todoist has mapped in `value`'s string fields.
+fn map_temp_ids(value: &mut serde_json::Value, mapping: &HashMap<String, String>) {
+    for key in ["id", "parent_id"] {
+        if let Some(id) = value.get(key).and_then(|v| v.as_str()) {
+            if let Some(real) = mapping.get(id) {
+                value[key] = json!(real);
+            }
+        }
+    }
+}
+
+/// Send the changes in batches, each as big as Todoist takes, saving the
+/// links of the changes whose commands all went through after each one.
+async fn upload(
+    db: &Db,
+    api: &Api,
+    account_id: &str,
+    changes: Vec<Change>,
+    report: &mut TodoistSyncReport,
+) -> CommandResult<()> {
+    let mut mapping: HashMap<String, String> = HashMap::new();
+    let mut pending = changes.into_iter().peekable();
+    while pending.peek().is_some() {
+        let mut batch = Vec::new();
+        let mut count = 0;
+        while let Some(change) =
+            pending.next_if(|c| count == 0 || count + c.commands.len() <= MAX_COMMANDS)
+        {
+            count += change.commands.len();
+            batch.push(change);
+        }
+        for change in &mut batch {
+            for command in &mut change.commands {
+                map_temp_ids(&mut command["args"], &mapping);
+            }
+        }
+        let commands: Vec<&serde_json::Value> =
+            batch.iter().flat_map(|c| c.commands.iter()).collect();
+        let commands = serde_json::to_string(&commands).unwrap_or_default();
+        let response = api.sync(&[("commands", commands.as_str())]).await?;
+        mapping.extend(response.temp_id_mapping);
+        let mut done = Vec::new();
+        let mut replay = Replay::default();
+        for change in batch {
+            let errors: Vec<String> = change
+                .commands
+                .iter()
+                .filter_map(|c| {
+                    let uuid = c["uuid"].as_str().unwrap_or_default();
+                    match response.sync_status.get(uuid) {
+                        Some(status) if status == "ok" => None,
+                        Some(status) => Some(format!("{}: {status}", c["type"])),
+                        None => Some(format!("{}: no result", c["type"])),
+                    }
+                })
+                .collect();
+            if !errors.is_empty() {
+                replay.failed(change.task_id().to_string(), errors.join("; "));
+                report.errors.extend(errors);
+                continue;
+            }
+            replay.delivered(change.task_id().to_string());
+            let outcome = match change.outcome {
+                Outcome::Saved {
+                    remote_id,
+                    mut item,
+                } => {
+                    let remote_id = mapping.get(&remote_id).cloned().unwrap_or(remote_id);
+                    if let Some(parent) = &item.location.parent_id {
+                        if let Some(real) = mapping.get(parent) {
+                            item.location.parent_id = Some(real.clone());
+                        }
+                    }
+                    Outcome::Saved { remote_id, item }
+                }
+                deleted => deleted,
+            };
+            done.push(outcome);
+        }
+        db.with_conn(|conn| {
+            replay.save(conn)?;
+            save_results(conn, account_id, &done, report)
+        })?;
+    }
+    Ok(())
+}
+
+fn save_results(
+    conn: &mut Connection,
+    account_id: &str,
+    results: &[Outcome],
+    report: &mut TodoistSyncReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for result in results {
+        match result {
+            Outcome::Saved { remote_id, item } => {
+                report.uploaded += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, remote_id, item.task_id, now_utc()],
+                )?;
+                save_item(&tx, account_id, remote_id, item)?;
+            }
+            Outcome::Deleted { remote_id, .. } => {
+                report.deleted += 1;
+                forget_item(&tx, account_id, remote_id)?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+/// Save the account's projects and sections as Todoist has them now.
+async fn refresh_projects(db: &Db, account_id: &str) -> CommandResult<()> {
+    let response = Api::connect(account_id)?
+        .sync(&[
+            ("sync_token", FULL_SYNC),
+            ("resource_types", r#"["projects","sections"]"#),
+        ])
+        .await?;
+    db.with_conn(|conn| store_folders(conn, account_id, &response.projects, &response.sections))
+}
+
+fn list_projects(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<RemoteCollection>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, name, enabled FROM todoist_projects
+         WHERE account_id = ?1 ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map(params![account_id], |row| {
+        Ok(RemoteCollection {
+            id: row.get(0)?,
+            account_id: account_id.to_string(),
+            name: row.get(1)?,
+            selected: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Two-way sync of an account: pull what changed since the last sync
+/// token, merge it field by field, and when pushing upload local changes
+/// as batched commands. A full sync (the first, or after Todoist drops the
+/// token) doesn't list completed tasks, so tasks missing from it are kept.
+async fn sync_account(
+    db: &Db,
+    api: &Api,
+    account: &TodoistAccount,
+    mode: SyncMode,
+) -> CommandResult<TodoistSyncReport> {
+    let mut report = TodoistSyncReport {
+        account_id: account.id.clone(),
+        name: account.name.clone(),
+        ..TodoistSyncReport::default()
+    };
+    let sync_token = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT sync_token FROM todoist_accounts WHERE id = ?1",
+            params![account.id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+    })?;
+    let sync_token = sync_token.unwrap_or_else(|| FULL_SYNC.to_string());
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let response = api
+        .sync(&[
+            ("sync_token", sync_token.as_str()),
+            ("resource_types", RESOURCE_TYPES),
+        ])
+        .await?;
+    if response.full_sync && sync_token != FULL_SYNC {
+        tracing::info!("sync token of {} expired; synced in full", account.name);
+    }
+
+    let changes = db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(
+                conn,
+                &account.id,
+                &response,
+                &local,
+                mode.imports(),
+                &mut report,
+            )
+        })?
+    })?;
+    // Importing only leaves local changes here.
+    let changes = if mode.exports() { changes } else { Vec::new() };
+    // Already in causal order, parents before their subtasks.
+    let failed = db.with_conn(|conn| outbox::failed(conn))?;
+    let changes = changes
+        .into_iter()
+        .filter(|c| !failed.contains(c.task_id()))
+        .collect();
+    upload(db, api, &account.id, changes, &mut report).await?;
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE todoist_accounts SET sync_token = ?2, last_synced_at = ?3 WHERE id = ?1",
+            params![account.id, response.sync_token, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+/// Check an API token and save the account. Nothing syncs until
+/// `sync_todoist` runs.
+#[tauri::command]
+pub async fn connect_todoist_account(
+    db: State<'_, Db>,
+    input: NewTodoistAccount,
+) -> CommandResult<TodoistAccount> {
+    let token = input.token.trim().to_string();
+    if token.is_empty() {
+        return Err(CommandError::invalid("Enter the Todoist API token"));
+    }
+    let api = Api::new(token.clone())?;
+    let response = api
+        .sync(&[("sync_token", FULL_SYNC), ("resource_types", r#"["user"]"#)])
+        .await?;
+    let user = response.user.and_then(|u| u.full_name.or(u.email));
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .or(user)
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Todoist".to_string());
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO todoist_accounts (id, name, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?3)",
+            params![id, name, now],
+        )?;
+        list_accounts(conn)
+    });
+    let accounts = match stored {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            return Err(e);
+        }
+    };
+    Ok(accounts
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| "Failed to save account".to_string())?)
+}
+
+#[tauri::command]
+pub fn list_todoist_accounts(db: State<'_, Db>) -> CommandResult<Vec<TodoistAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Forget an account and its sync state. Its tasks stay, unlinked.
+#[tauri::command]
+pub fn remove_todoist_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db.with_conn(|conn| {
+        conn.execute("DELETE FROM todoist_accounts WHERE id = ?1", params![id])
+    })?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Todoist account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+pub struct Todoist;
+
+impl SyncProvider for Todoist {
+    type Report = Vec<TodoistSyncReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Todoist"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: true,
+            conflicts: true,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(&account.id)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                match Api::connect(&account.id) {
+                    Ok(api) => {
+                        health.answer("Todoist", api.probe().await);
+                    }
+                    Err(e) => health.no_credentials(&e),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Import))
+    }
+
+    fn push<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::TwoWay))
+    }
+
+    fn export<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(sync_accounts(db, account_id, progress, SyncMode::Export))
+    }
+
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                if let Err(e) = refresh_projects(db, &account.id).await {
+                    tracing::warn!("refresh of {} failed: {e}", account.name);
+                }
+                found.extend(db.with_conn(|conn| list_projects(conn, &account.id))?);
+            }
+            Ok(found)
+        })
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        let changed = db.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE todoist_projects SET enabled = ?2 WHERE remote_id = ?1",
+                params![id, selected],
+            )?;
+            if selected {
+                // What changed in it meanwhile only comes with a full sync.
+                conn.execute(
+                    "UPDATE todoist_accounts SET sync_token = NULL
+                     WHERE id IN (SELECT account_id FROM todoist_projects WHERE remote_id = ?1)",
+                    params![id],
+                )?;
+            }
+            Ok(changed)
+        })?;
+        if changed == 0 {
+            return Err(CommandError::not_found(format!(
+                "Todoist project not found: {id}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Sync `account_id`, or every account. One account failing doesn't stop
+/// the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_todoist(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<TodoistSyncReport>> {
+    sync_provider::run_command(&app, &Todoist, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+    mode: SyncMode,
+) -> CommandResult<Vec<TodoistSyncReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let result = match Api::connect(&account.id) {
+            Ok(api) => sync_account(db, &api, &account, mode).await,
+            Err(e) => Err(e),
+        };
+        let report = result.unwrap_or_else(|e| {
+            tracing::warn!("sync of {} failed: {e}", account.name);
+            TodoistSyncReport {
+                account_id: account.id.clone(),
+                name: account.name.clone(),
+                errors: vec![e.to_string()],
+                ..TodoistSyncReport::default()
+            }
+        });
+        progress.record(&report.name, &report);
+        reports.push(report);
+    }
+    Ok(reports)
+}
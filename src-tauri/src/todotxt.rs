@@ -0,0 +1,973 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::crdt;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::history::{self, ChangeSource};
+use crate::projects;
+use crate::session::write_atomic;
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::tags;
+use crate::task_store::{self, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+
+/// Emitted with the `TodoTxtSyncReport` after a background sync that changed
+/// tasks or the files.
+pub const TODOTXT_SYNCED_EVENT: &str = "todotxt-synced";
+/// Emitted with the error message when a background sync fails.
+pub const TODOTXT_FAILED_EVENT: &str = "todotxt-sync-failed";
+
+const UNTITLED: &str = "Untitled";
+/// Editors often save in several steps; wait for them to settle.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Held while a sync runs, so the watcher and a manual sync don't
+/// interleave.
+static SYNCING: Mutex<()> = Mutex::new(());
+/// The watcher on the linked files, and the way to ask for a sync.
+static WATCH: Mutex<Option<Watch>> = Mutex::new(None);
+
+struct Watch {
+    _watcher: RecommendedWatcher,
+    poke: Sender<()>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TodoTxtLink {
+    pub id: String,
+    pub todo_path: String,
+    /// Where completed tasks are archived, as `todo.sh archive` does.
+    pub done_path: Option<String>,
+    pub last_synced_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTodoTxtLink {
+    pub todo_path: String,
+    #[serde(default)]
+    pub done_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TodoTxtSyncReport {
+    /// Tasks created or updated from lines added or edited in the files.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks moved to the trash because their line was deleted.
+    pub removed: usize,
+    /// Lines added or rewritten for tasks changed here.
+    pub written: usize,
+}
+
+impl TodoTxtSyncReport {
+    fn is_empty(&self) -> bool {
+        self.processed() == 0
+    }
+}
+
+impl SyncOutcome for TodoTxtSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.removed + self.written
+    }
+
+    fn errors(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// One todo.txt line, read into its parts.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Item {
+    done: bool,
+    priority: Option<char>,
+    completed: Option<NaiveDate>,
+    created: Option<NaiveDate>,
+    /// The words that aren't projects, contexts or `key:value` tags.
+    title: String,
+    /// The first `+project`; later ones are kept in `extras`.
+    project: Option<String>,
+    contexts: Vec<String>,
+    due: Option<NaiveDate>,
+    /// `t:`, the date a task shows up from.
+    threshold: Option<NaiveDate>,
+    /// Tokens this app doesn't read, kept as written.
+    extras: Vec<String>,
+}
+
+impl Item {
+    /// Whether two items say the same, whatever the order of their tokens.
+    fn same(&self, other: &Item) -> bool {
+        let contexts = |item: &Item| {
+            let mut contexts: Vec<String> =
+                item.contexts.iter().map(|c| c.to_lowercase()).collect();
+            contexts.sort();
+            contexts
+        };
+        let mut a = self.clone();
+        let mut b = other.clone();
+        a.contexts = contexts(self);
+        b.contexts = contexts(other);
+        a == b
+    }
+}
+
+fn leading_date(text: &str) -> Option<(NaiveDate, &str)> {
+    let (word, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let date = NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?;
+    Some((date, rest.trim_start()))
+}
+
+fn leading_priority(text: &str) -> Option<(char, &str)> {
+    match text.as_bytes() {
+        [b'(', p, b')', b' ', ..] if p.is_ascii_uppercase() => {
+            Some((*p as char, text[4..].trim_start()))
+        }
+        _ => None,
+    }
+}
+
+/// Read a line per the todo.txt format: `x`, completion and creation dates
+/// or `(A)` and a creation date, then the description with its `+project`,
+/// `@context` and `key:value` tokens.
+fn parse_line(line: &str) -> Item {
+    let mut item = Item::default();
+    let mut rest = line.trim();
+    if let Some(after) = rest.strip_prefix("x ") {
+        item.done = true;
+        rest = after.trim_start();
+        if let Some((date, after)) = leading_date(rest) {
+            item.completed = Some(date);
+            rest = after;
+            if let Some((date, after)) = leading_date(rest) {
+                item.created = Some(date);
+                rest = after;
+            }
+        }
+    }
+    // Some clients keep the priority when marking a task done.
+    if let Some((priority, after)) = leading_priority(rest) {
+        item.priority = Some(priority);
+        rest = after;
+    }
+    if item.created.is_none() {
+        if let Some((date, after)) = leading_date(rest) {
+            item.created = Some(date);
+            rest = after;
+        }
+    }
+
+    let mut words = Vec::new();
+    for word in rest.split_whitespace() {
+        if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+            if item.project.is_none() {
+                item.project = Some(project.to_string());
+            } else {
+                item.extras.push(word.to_string());
+            }
+            continue;
+        }
+        if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            item.contexts.push(context.to_string());
+            continue;
+        }
+        let tag = word.split_once(':').filter(|(key, value)| {
+            !key.is_empty() && !value.is_empty() && !value.starts_with("//")
+        });
+        let Some((key, value)) = tag else {
+            words.push(word);
+            continue;
+        };
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+        match (key, date) {
+            ("due", Some(date)) if item.due.is_none() => item.due = Some(date),
+            ("t", Some(date)) if item.threshold.is_none() => item.threshold = Some(date),
+            ("pri", _) if value.len() == 1 && value.as_bytes()[0].is_ascii_uppercase() => {
+                item.priority = value.chars().next();
+            }
+            _ => item.extras.push(word.to_string()),
+        }
+    }
+    item.title = words.join(" ");
+    item
+}
+
+/// Write an item as a line. A done task's priority goes in `pri:`, as
+/// todo.sh keeps it.
+fn render(item: &Item) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if item.done {
+        parts.push("x".to_string());
+        if let Some(completed) = item.completed {
+            parts.push(completed.to_string());
+            if let Some(created) = item.created {
+                parts.push(created.to_string());
+            }
+        }
+    } else {
+        if let Some(priority) = item.priority {
+            parts.push(format!("({priority})"));
+        }
+        if let Some(created) = item.created {
+            parts.push(created.to_string());
+        }
+    }
+    if !item.title.is_empty() {
+        parts.push(item.title.clone());
+    }
+    if let Some(project) = &item.project {
+        parts.push(format!("+{project}"));
+    }
+    parts.extend(item.contexts.iter().map(|c| format!("@{c}")));
+    if let Some(due) = item.due {
+        parts.push(format!("due:{due}"));
+    }
+    if let Some(threshold) = item.threshold {
+        parts.push(format!("t:{threshold}"));
+    }
+    if let (true, Some(priority)) = (item.done, item.priority) {
+        parts.push(format!("pri:{priority}"));
+    }
+    parts.extend(item.extras.iter().cloned());
+    parts.join(" ")
+}
+
+/// A project or tag name as one token: whitespace becomes `_`.
+fn token(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// (A) onto 3 (high), (B) onto 2 and the rest onto 1 (low).
+fn daylight_priority(priority: char) -> i64 {
+    match priority {
+        'A' => 3,
+        'B' => 2,
+        _ => 1,
+    }
+}
+
+fn todotxt_priority(priority: Option<i64>) -> Option<char> {
+    match priority? {
+        p if p >= 3 => Some('A'),
+        2 => Some('B'),
+        1 => Some('C'),
+        _ => None,
+    }
+}
+
+/// The date of a due or scheduled value.
+fn date_of(value: Option<&str>) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value?.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// `date` as a due or scheduled value, keeping the time of `current` when
+/// it's on that date.
+fn keep_time(date: Option<NaiveDate>, current: Option<&str>) -> Option<String> {
+    let date = date?;
+    match current {
+        Some(current) if date_of(Some(current)) == Some(date) => Some(current.to_string()),
+        _ => Some(date.to_string()),
+    }
+}
+
+/// The line for `task`, keeping what `previous` had that tasks don't: the
+/// spelling of its tokens, its creation date and unread tokens.
+fn item_for(task: &Task, previous: Option<&Item>, local: &Tz) -> Item {
+    let done = task.status == STATUS_DONE;
+    let local_date = |at: &str| {
+        parse_utc(at)
+            .ok()
+            .map(|at| at.with_timezone(local).date_naive())
+    };
+    let completed = || task.completed_at.as_deref().and_then(local_date);
+    // Dates are kept as the line had them, so lines written without them
+    // stay that way.
+    let (created, completed) = match previous {
+        Some(previous) if done && previous.done => (previous.created, previous.completed),
+        Some(previous) if done => (previous.created, completed()),
+        Some(previous) => (previous.created, None),
+        None => (local_date(&task.created_at), completed().filter(|_| done)),
+    };
+    let previous = previous.cloned().unwrap_or_default();
+    let priority = match previous.priority {
+        Some(p) if Some(daylight_priority(p)) == task.priority => Some(p),
+        _ => todotxt_priority(task.priority),
+    };
+    let contexts = task
+        .tags
+        .iter()
+        .map(|tag| {
+            let tag = token(tag);
+            previous
+                .contexts
+                .iter()
+                .find(|c| c.eq_ignore_ascii_case(&tag))
+                .cloned()
+                .unwrap_or(tag)
+        })
+        .collect();
+    Item {
+        done,
+        priority,
+        completed,
+        created,
+        title: task.title.split_whitespace().collect::<Vec<_>>().join(" "),
+        project: task.project.as_deref().map(token),
+        contexts,
+        due: date_of(task.due.as_deref()),
+        threshold: date_of(task.scheduled.as_deref()),
+        extras: previous.extras,
+    }
+}
+
+fn set_done(task: &mut Task, done: bool, completed: Option<NaiveDate>, local: &Tz) {
+    if !done {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+        return;
+    }
+    task.status = STATUS_DONE.to_string();
+    let at = completed
+        .and_then(|date| timezone::resolve_local(date.and_time(NaiveTime::MIN), local))
+        .map(|at| format_utc(at.to_utc()));
+    task.completed_at = at.or_else(|| Some(now_utc()));
+}
+
+/// The project for `item`, keeping `current` when written as its token.
+fn project_of(item: &Item, current: Option<&str>) -> Option<String> {
+    let project = item.project.as_deref()?;
+    match current {
+        Some(current) if token(current) == project => Some(current.to_string()),
+        _ => projects::normalize_name(project).ok(),
+    }
+}
+
+/// The tags for `item`, keeping the names of `current` ones written as
+/// their token.
+fn tags_of(item: &Item, current: &[String]) -> Vec<String> {
+    let names: Vec<String> = item
+        .contexts
+        .iter()
+        .map(|context| {
+            current
+                .iter()
+                .find(|t| token(t).eq_ignore_ascii_case(context))
+                .cloned()
+                .unwrap_or_else(|| context.clone())
+        })
+        .collect();
+    tags::normalize_names(&names).unwrap_or_default()
+}
+
+/// Create or update the task for a line added or edited in the files. A
+/// field edited here after the file was saved keeps the local value.
+/// Returns the task and whether it was created, or `None` when nothing
+/// changed.
+fn write_item(
+    conn: &Connection,
+    existing: Option<Task>,
+    item: &Item,
+    modified: i64,
+    local: &Tz,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let title = if item.title.is_empty() {
+        UNTITLED.to_string()
+    } else {
+        item.title.clone()
+    };
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description: None,
+            project: project_of(item, None),
+            priority: item.priority.map(daylight_priority),
+            due: item.due.map(|d| d.to_string()),
+            scheduled: item.threshold.map(|d| d.to_string()),
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if item.done {
+            set_done(&mut task, true, item.completed, local);
+            task_store::write_task(conn, &task)?;
+        }
+        let names = tags_of(item, &[]);
+        tags::set_task_tags(conn, &task.id, &names)?;
+        task.tags = names;
+        return Ok(Some((task, true)));
+    };
+
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| stamps.get(field).is_none_or(|s| s.clock <= modified);
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("project") {
+        task.project = project_of(item, task.project.as_deref());
+    }
+    if take("priority") {
+        let unchanged = item.priority.map(daylight_priority) == task.priority
+            || (item.priority.is_none() && task.priority.is_some_and(|p| p < 1));
+        if !unchanged {
+            task.priority = item.priority.map(daylight_priority);
+        }
+    }
+    if take("due") {
+        task.due = keep_time(item.due, task.due.as_deref());
+    }
+    if take("scheduled") {
+        task.scheduled = keep_time(item.threshold, task.scheduled.as_deref());
+    }
+    if take("status") && item.done != (task.status == STATUS_DONE) {
+        set_done(&mut task, item.done, item.completed, local);
+    }
+    let names = tags_of(item, &task.tags);
+    let take_tags = crdt::newest_tag_clock(conn, &task.id)?.is_none_or(|c| c <= modified);
+    let tags_changed = take_tags && {
+        let mut have: Vec<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+        let mut want: Vec<String> = names.iter().map(|t| t.to_lowercase()).collect();
+        have.sort();
+        want.sort();
+        have != want
+    };
+
+    let changed = task.title != before.title
+        || task.project != before.project
+        || task.priority != before.priority
+        || task.due != before.due
+        || task.scheduled != before.scheduled
+        || task.status != before.status;
+    if !changed && !tags_changed {
+        return Ok(None);
+    }
+    if changed {
+        task.updated_at = now_utc();
+        task_store::write_task(conn, &task)?;
+    }
+    if tags_changed {
+        tags::set_task_tags(conn, &task.id, &names)?;
+        task.tags = names;
+    }
+    Ok(Some((task, false)))
+}
+
+/// A task's line as the last sync left it.
+#[derive(Debug, Clone)]
+struct LineRow {
+    task_id: String,
+    in_done: bool,
+    position: usize,
+    line: String,
+}
+
+fn load_lines(conn: &Connection, file_id: &str) -> rusqlite::Result<Vec<LineRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_id, in_done, position, line FROM todotxt_lines
+         WHERE file_id = ?1 ORDER BY in_done, position",
+    )?;
+    let rows = stmt.query_map(params![file_id], |row| {
+        Ok(LineRow {
+            task_id: row.get(0)?,
+            in_done: row.get(1)?,
+            position: row.get::<_, i64>(2)? as usize,
+            line: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// The linked files as read for one sync.
+#[derive(Debug, Clone, Default)]
+struct Files {
+    todo: Vec<String>,
+    done: Vec<String>,
+    /// When each was last saved, in milliseconds; 0 when missing.
+    todo_modified: i64,
+    done_modified: i64,
+}
+
+fn split_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Match the lines of the files to the tasks they were last synced as:
+/// the same text first, wherever it moved (as `todo.sh archive` moves done
+/// lines to done.txt), then the same title, then the same place.
+fn match_lines(lines: &[(bool, String)], rows: &[LineRow]) -> Vec<Option<usize>> {
+    let mut owner: Vec<Option<usize>> = vec![None; lines.len()];
+    let mut free: Vec<bool> = vec![true; rows.len()];
+    let mut claim = |owner: &mut Vec<Option<usize>>, i: usize, pick: &dyn Fn(&LineRow) -> bool| {
+        if owner[i].is_some() {
+            return;
+        }
+        if let Some(r) = (0..rows.len()).find(|&r| free[r] && pick(&rows[r])) {
+            free[r] = false;
+            owner[i] = Some(r);
+        }
+    };
+    for (i, (in_done, text)) in lines.iter().enumerate() {
+        claim(&mut owner, i, &|row| {
+            row.in_done == *in_done && row.line == *text
+        });
+    }
+    for (i, (_, text)) in lines.iter().enumerate() {
+        claim(&mut owner, i, &|row| row.line == *text);
+    }
+    for (i, (_, text)) in lines.iter().enumerate() {
+        let title = parse_line(text).title.to_lowercase();
+        claim(&mut owner, i, &|row| {
+            parse_line(&row.line).title.to_lowercase() == title
+        });
+    }
+    let mut positions = [0, 0];
+    let places: Vec<usize> = lines
+        .iter()
+        .map(|(in_done, _)| {
+            let place = positions[usize::from(*in_done)];
+            positions[usize::from(*in_done)] += 1;
+            place
+        })
+        .collect();
+    for (i, (in_done, _)) in lines.iter().enumerate() {
+        claim(&mut owner, i, &|row| {
+            row.in_done == *in_done && row.position == places[i]
+        });
+    }
+    owner
+}
+
+/// Apply the files to the tasks and work out what the files should say
+/// now: lines edited there update their task, new lines add tasks, deleted
+/// lines trash theirs; tasks changed here rewrite their line, and open
+/// tasks the files don't have yet are added to todo.txt. Returns the new
+/// todo.txt and done.txt lines.
+fn merge(
+    conn: &mut Connection,
+    file_id: &str,
+    files: &Files,
+    local: &Tz,
+    report: &mut TodoTxtSyncReport,
+) -> rusqlite::Result<(Vec<String>, Vec<String>)> {
+    let tx = conn.transaction()?;
+    let rows = load_lines(&tx, file_id)?;
+    let lines: Vec<(bool, String)> = files
+        .todo
+        .iter()
+        .map(|line| (false, line.clone()))
+        .chain(files.done.iter().map(|line| (true, line.clone())))
+        .collect();
+    let owner = match_lines(&lines, &rows);
+
+    let mut kept: Vec<(bool, String, String)> = Vec::new();
+    let mut matched = vec![false; rows.len()];
+    for (i, (in_done, text)) in lines.iter().enumerate() {
+        let item = parse_line(text);
+        let modified = if *in_done {
+            files.done_modified
+        } else {
+            files.todo_modified
+        };
+        let row = owner[i].map(|r| &rows[r]);
+        if let Some(r) = owner[i] {
+            matched[r] = true;
+        }
+        let existing = match row {
+            Some(row) => task_store::find_task(&tx, &row.task_id)?,
+            None => None,
+        };
+        if row.is_some() && existing.is_none() {
+            // Deleted here; the line goes.
+            report.written += 1;
+            continue;
+        }
+        let task = if row.is_some_and(|row| row.line == *text) {
+            existing
+        } else {
+            match write_item(&tx, existing.clone(), &item, modified, local)? {
+                Some((task, true)) => {
+                    report.created += 1;
+                    Some(task)
+                }
+                Some((task, false)) => {
+                    report.updated += 1;
+                    Some(task)
+                }
+                None => existing,
+            }
+        };
+        let Some(task) = task else {
+            continue;
+        };
+        let wanted = item_for(&task, Some(&item), local);
+        let line = if wanted.same(&item) {
+            text.clone()
+        } else {
+            report.written += 1;
+            render(&wanted)
+        };
+        kept.push((*in_done, task.id, line));
+    }
+
+    for (row, _) in rows.iter().zip(&matched).filter(|(_, matched)| !**matched) {
+        if trash::trash_task(&tx, &row.task_id)? {
+            report.removed += 1;
+        }
+    }
+
+    let linked: Vec<&str> = kept.iter().map(|(_, id, _)| id.as_str()).collect();
+    let mut stmt = tx.prepare(
+        "SELECT id FROM tasks
+         WHERE deleted_at IS NULL AND parent_id IS NULL AND status = ?1
+           AND id NOT IN (SELECT value FROM json_each(?2))
+         ORDER BY created_at",
+    )?;
+    let new_ids = stmt
+        .query_map(
+            params![
+                STATUS_OPEN,
+                serde_json::to_string(&linked).unwrap_or_default()
+            ],
+            |row| row.get::<_, String>(0),
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+    for id in new_ids {
+        if let Some(task) = task_store::find_task(&tx, &id)? {
+            report.written += 1;
+            kept.push((
+                false,
+                task.id.clone(),
+                render(&item_for(&task, None, local)),
+            ));
+        }
+    }
+
+    tx.execute(
+        "DELETE FROM todotxt_lines WHERE file_id = ?1",
+        params![file_id],
+    )?;
+    let (mut todo, mut done) = (Vec::new(), Vec::new());
+    for (in_done, task_id, line) in kept {
+        let lines = if in_done { &mut done } else { &mut todo };
+        tx.execute(
+            "INSERT OR REPLACE INTO todotxt_lines (file_id, task_id, in_done, position, line)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![file_id, task_id, in_done, lines.len() as i64, line],
+        )?;
+        lines.push(line);
+    }
+    tx.commit()?;
+    Ok((todo, done))
+}
+
+fn row_to_link(row: &rusqlite::Row) -> rusqlite::Result<TodoTxtLink> {
+    Ok(TodoTxtLink {
+        id: row.get(0)?,
+        todo_path: row.get(1)?,
+        done_path: row.get(2)?,
+        last_synced_at: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+fn find_link(conn: &Connection) -> rusqlite::Result<Option<TodoTxtLink>> {
+    conn.query_row(
+        "SELECT id, todo_path, done_path, last_synced_at, created_at FROM todotxt_files",
+        [],
+        row_to_link,
+    )
+    .optional()
+}
+
+fn modified_ms(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+/// A file's lines, none when it doesn't exist yet.
+fn read_lines(path: &Path) -> CommandResult<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(split_lines(&text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(CommandError::from(e).context(format!("Failed to read {}", path.display()))),
+    }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Replace a file with `lines` unless it already has them. A file saved
+/// since it was read is left alone; the watcher syncs again for that save.
+fn write_lines(
+    path: &Path,
+    lines: &[String],
+    read: &[String],
+    read_at: Option<SystemTime>,
+) -> CommandResult<()> {
+    if lines == read {
+        return Ok(());
+    }
+    if modified_at(path) != read_at {
+        return Ok(());
+    }
+    let mut text = lines.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    write_atomic(path, text.as_bytes())
+}
+
+/// Sync the linked files with the tasks once.
+pub fn sync_link(db: &Db, link: &TodoTxtLink) -> CommandResult<TodoTxtSyncReport> {
+    let _syncing = SYNCING.lock().unwrap_or_else(|e| e.into_inner());
+    // Lines merged in from the files aren't an edit to undo here.
+    let db = &db.unjournaled();
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let todo_path = PathBuf::from(&link.todo_path);
+    let done_path = link.done_path.as_ref().map(PathBuf::from);
+    let todo_at = modified_at(&todo_path);
+    let done_at = done_path.as_deref().and_then(modified_at);
+    let files = Files {
+        todo: read_lines(&todo_path)?,
+        done: match &done_path {
+            Some(path) => read_lines(path)?,
+            None => Vec::new(),
+        },
+        todo_modified: modified_ms(&todo_path),
+        done_modified: done_path.as_deref().map_or(0, modified_ms),
+    };
+
+    let mut report = TodoTxtSyncReport::default();
+    let (todo, done) = db.with_conn(|conn| {
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge(conn, &link.id, &files, &local, &mut report)
+        })?
+    })?;
+    write_lines(&todo_path, &todo, &files.todo, todo_at)?;
+    if let Some(path) = &done_path {
+        write_lines(path, &done, &files.done, done_at)?;
+    }
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE todotxt_files SET last_synced_at = ?2 WHERE id = ?1",
+            params![link.id, now_utc()],
+        )
+    })?;
+    Ok(report)
+}
+
+/// Sync in the background, telling the UI how it went.
+fn sync_now(app: &AppHandle) {
+    let db = app.state::<Db>();
+    if db.is_locked() {
+        return;
+    }
+    let result = db
+        .with_conn(|conn| find_link(conn))
+        .and_then(|link| link.map(|link| sync_link(&db, &link)).transpose());
+    match result {
+        Ok(Some(report)) if !report.is_empty() => {
+            let _ = app.emit(TODOTXT_SYNCED_EVENT, report);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::warn!("sync failed: {e}");
+            let _ = app.emit(TODOTXT_FAILED_EVENT, e);
+        }
+    }
+}
+
+/// Ask for a sync because tasks changed. Called for every change, the
+/// sync's own included; a sync with nothing to do changes nothing, so that
+/// ends there.
+pub fn tasks_changed() {
+    let watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(watch) = watch.as_ref() {
+        let _ = watch.poke.send(());
+    }
+}
+
+/// Sync the linked files, then again whenever they're saved or tasks
+/// change. Replaces any earlier watch, or just stops it when nothing is
+/// linked.
+pub fn watch(app: &AppHandle) {
+    let db = app.state::<Db>();
+    let link = if db.is_locked() {
+        None
+    } else {
+        db.with_conn(|conn| find_link(conn)).unwrap_or_else(|e| {
+            tracing::warn!("{e}");
+            None
+        })
+    };
+    let mut slot = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    *slot = None;
+    let Some(link) = link else {
+        return;
+    };
+
+    let paths: Vec<PathBuf> = std::iter::once(&link.todo_path)
+        .chain(&link.done_path)
+        .map(PathBuf::from)
+        .collect();
+    let names: Vec<_> = paths
+        .iter()
+        .filter_map(|p| p.file_name())
+        .map(|n| n.to_os_string())
+        .collect();
+    let (tx, rx) = mpsc::channel::<()>();
+    let events = tx.clone();
+    let mut watcher = match RecommendedWatcher::new(
+        move |res: Result<notify::Event, notify::Error>| {
+            let Ok(event) = res else {
+                return;
+            };
+            let ours = event
+                .paths
+                .iter()
+                .any(|p| p.file_name().is_some_and(|n| names.iter().any(|m| m == n)));
+            if ours {
+                let _ = events.send(());
+            }
+        },
+        Config::default(),
+    ) {
+        Ok(w) => w,
+        Err(e) => {
+            tracing::warn!("failed to start watcher: {e}");
+            return;
+        }
+    };
+    // Saving replaces the file more often than not, so watch its folder.
+    for path in &paths {
+        let Some(dir) = path.parent() else {
+            continue;
+        };
+        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            tracing::warn!("failed to watch {}: {e}", dir.display());
+        }
+    }
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        while rx.recv().is_ok() {
+            let deadline = Instant::now() + DEBOUNCE;
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                if rx.recv_timeout(remaining).is_err() {
+                    break;
+                }
+            }
+            sync_now(&handle);
+        }
+    });
+    let _ = tx.send(());
+    *slot = Some(Watch {
+        _watcher: watcher,
+        poke: tx,
+    });
+}
+
+fn check_path(path: &str) -> CommandResult<String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(CommandError::invalid("Choose a file"));
+    }
+    let dir = Path::new(path)
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty());
+    if dir.is_none_or(|d| !d.is_dir()) {
+        return Err(CommandError::not_found(format!(
+            "Folder not found for {path}"
+        )));
+    }
+    Ok(path.to_string())
+}
+
+#[tauri::command]
+pub fn get_todotxt_link(db: State<'_, Db>) -> CommandResult<Option<TodoTxtLink>> {
+    db.with_conn(|conn| find_link(conn))
+}
+
+/// Sync with a todo.txt file, and optionally the done.txt it's archived
+/// to, in place of any linked before. The files are created if missing.
+/// Every open task not in them yet is added to todo.txt.
+#[tauri::command]
+pub fn link_todotxt(
+    app: AppHandle,
+    db: State<'_, Db>,
+    input: NewTodoTxtLink,
+) -> CommandResult<TodoTxtLink> {
+    let todo_path = check_path(&input.todo_path)?;
+    let done_path = match input.done_path.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(path) => Some(check_path(path)?),
+    };
+    if done_path.as_deref() == Some(todo_path.as_str()) {
+        return Err(CommandError::invalid(
+            "todo.txt and done.txt must be different files",
+        ));
+    }
+    let link = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM todotxt_files", [])?;
+        tx.execute(
+            "INSERT INTO todotxt_files (id, todo_path, done_path, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                todo_path,
+                done_path,
+                now_utc()
+            ],
+        )?;
+        let link = find_link(&tx)?;
+        tx.commit()?;
+        Ok(link)
+    })?;
+    watch(&app);
+    Ok(link.ok_or_else(|| "Failed to save link".to_string())?)
+}
+
+/// Stop syncing with the files. They and the tasks stay as they are.
+#[tauri::command]
+pub fn unlink_todotxt(app: AppHandle, db: State<'_, Db>) -> CommandResult<()> {
+    db.with_conn(|conn| conn.execute("DELETE FROM todotxt_files", []))?;
+    watch(&app);
+    Ok(())
+}
+
+/// Sync now rather than waiting for a change.
+#[tauri::command]
+pub fn sync_todotxt(app: AppHandle, db: State<'_, Db>) -> CommandResult<TodoTxtSyncReport> {
+    let link = db
+        .with_conn(|conn| find_link(conn))?
+        .ok_or_else(|| CommandError::not_found("No todo.txt file is linked"))?;
+    let progress = Tracker::start(&app, "todotxt");
+    let result = sync_link(&db, &link);
+    progress.finish(&result);
+    result
+}
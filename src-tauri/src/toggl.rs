@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::SecondsFormat;
+use reqwest::{Client, Method, StatusCode};
+use rusqlite::{params, Connection};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::State;
+
+use crate::credentials;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::sync_provider::SyncFuture;
+use crate::task_store::Task;
+use crate::time_entries::TimeEntry;
+use crate::time_push::{self, EntryStatus, PushReport, Pushing, Service};
+
+const API_URL: &str = "https://api.track.toggl.com/api/v9";
+/// Shown in Toggl as what made the entry.
+const CREATED_WITH: &str = "DayLight";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const PAGE_SIZE: usize = 200;
+/// Pushed entries, by account.
+const ENTRIES_TABLE: &str = "toggl_entries";
+
+const SERVICE: Service = Service {
+    id: "toggl",
+    name: "Toggl",
+    refused: "Toggl refused the API token; connect the account again",
+    refused_statuses: &[StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN],
+    empty_statuses: &[StatusCode::NOT_FOUND],
+    // Toggl explains a refused entry in a plain-text body.
+    message: |text| text.trim().trim_matches('"').to_string(),
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TogglAccount {
+    pub id: String,
+    pub name: String,
+    /// The workspace entries are pushed to.
+    pub workspace_id: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTogglAccount {
+    /// Defaults to the Toggl user's name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The API token from the Toggl Track profile page.
+    pub token: String,
+    /// Defaults to the user's default workspace.
+    #[serde(default)]
+    pub workspace_id: Option<i64>,
+}
+
+pub type TogglPushReport = PushReport;
+
+/// Where one time entry stands with a Toggl account.
+pub type TogglEntryStatus = EntryStatus<i64>;
+
+#[derive(Debug, Deserialize)]
+struct RemoteUser {
+    #[serde(default)]
+    fullname: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    default_workspace_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteWorkspace {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteProject {
+    id: i64,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteEntry {
+    id: i64,
+}
+
+/// What a stopped entry sends to Toggl, with its project by name. Its hash
+/// is kept with the Toggl id to tell when it needs pushing again.
+#[derive(Debug, Clone, Serialize)]
+struct Export {
+    description: String,
+    start: String,
+    stop: String,
+    duration: i64,
+    project: Option<String>,
+    tags: Vec<String>,
+    billable: bool,
+}
+
+impl Export {
+    fn new(entry: &TimeEntry, ended_at: &str, task: Option<&Task>) -> CommandResult<Self> {
+        let start = parse_utc(&entry.started_at)?;
+        let stop = parse_utc(ended_at)?;
+        Ok(Self {
+            description: time_push::description(entry, task),
+            start: start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            stop: stop.to_rfc3339_opts(SecondsFormat::Secs, true),
+            duration: (stop - start).num_seconds().max(0),
+            project: time_push::project(task),
+            tags: task.map(|t| t.tags.clone()).unwrap_or_default(),
+            billable: entry.billable,
+        })
+    }
+
+    /// The request body, with the project resolved to its Toggl id.
+    fn body(&self, workspace_id: i64, project_id: Option<i64>) -> serde_json::Value {
+        let mut body = json!({
+            "created_with": CREATED_WITH,
+            "workspace_id": workspace_id,
+            "description": self.description,
+            "start": self.start,
+            "stop": self.stop,
+            "duration": self.duration,
+            "project_id": project_id,
+            "tags": self.tags,
+        });
+        // Free workspaces refuse the field outright, even when false.
+        if self.billable {
+            body["billable"] = json!(true);
+        }
+        body
+    }
+}
+
+type Outgoing = time_push::Outgoing<Export, i64>;
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<TogglAccount>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, workspace_id, created_at, updated_at
+         FROM toggl_accounts ORDER BY name COLLATE NOCASE",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TogglAccount {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            workspace_id: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> CommandResult<TogglAccount> {
+    list_accounts(conn)?
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| CommandError::not_found(format!("Toggl account not found: {id}")))
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("toggl:{account_id}")
+}
+
+/// A connection to the Toggl Track API with one account's token.
+struct Api {
+    client: Client,
+    token: String,
+}
+
+impl Api {
+    fn new(token: String) -> CommandResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self { client, token })
+    }
+
+    fn connect(account_id: &str) -> CommandResult<Self> {
+        Self::new(credentials::require(
+            &keyring_name(account_id),
+            "API token",
+        )?)
+    }
+
+    /// Send a request to `path` under the API. `None` when Toggl has no such
+    /// thing.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> CommandResult<Option<T>> {
+        let url = format!("{API_URL}{path}");
+        let request = || {
+            self.client
+                .request(method.clone(), &url)
+                .basic_auth(&self.token, Some("api_token"))
+        };
+        time_push::send(&SERVICE, request, body).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> CommandResult<T> {
+        self.send(Method::GET, path, None)
+            .await?
+            .ok_or_else(|| CommandError::not_found(format!("Toggl: {path} not found")))
+    }
+
+    /// Every project in the workspace, archived ones too, by lowercased
+    /// name.
+    async fn projects(&self, workspace_id: i64) -> CommandResult<HashMap<String, i64>> {
+        let mut projects = HashMap::new();
+        for page in 1.. {
+            let found: Vec<RemoteProject> = self
+                .get(&format!(
+                    "/workspaces/{workspace_id}/projects?active=both&per_page={PAGE_SIZE}&page={page}"
+                ))
+                .await?;
+            let done = found.len() < PAGE_SIZE;
+            for project in found {
+                projects
+                    .entry(project.name.to_lowercase())
+                    .or_insert(project.id);
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(projects)
+    }
+}
+
+/// The time entries between `from` and `to` (UTC), each with what it sends
+/// to Toggl and what `account_id` was last sent for it.
+fn outgoing(
+    conn: &Connection,
+    account_id: &str,
+    from: &str,
+    to: &str,
+) -> CommandResult<Vec<Outgoing>> {
+    time_push::outgoing(
+        conn,
+        ENTRIES_TABLE,
+        account_id,
+        from,
+        to,
+        |entry, ended_at, task, _| Export::new(entry, ended_at, task),
+    )
+}
+
+/// Pushes entries to one workspace, creating projects as they come up.
+struct Pusher<'a> {
+    api: &'a Api,
+    workspace_id: i64,
+    /// Fetched when the first entry with a project is pushed.
+    projects: Option<HashMap<String, i64>>,
+}
+
+impl Pusher<'_> {
+    /// The Toggl project named `name`, ignoring case; made when there's none.
+    async fn project_id(&mut self, name: &str) -> CommandResult<i64> {
+        let projects = match &mut self.projects {
+            Some(projects) => projects,
+            None => self
+                .projects
+                .insert(self.api.projects(self.workspace_id).await?),
+        };
+        let key = name.to_lowercase();
+        if let Some(id) = projects.get(&key) {
+            return Ok(*id);
+        }
+        let created: RemoteProject = self
+            .api
+            .send(
+                Method::POST,
+                &format!("/workspaces/{}/projects", self.workspace_id),
+                Some(&json!({ "name": name, "active": true })),
+            )
+            .await?
+            .ok_or_else(|| CommandError::not_found("Toggl: the workspace is gone"))?;
+        projects.insert(key, created.id);
+        Ok(created.id)
+    }
+
+    async fn push_entry(
+        &mut self,
+        export: &Export,
+        remote_id: Option<i64>,
+    ) -> CommandResult<(i64, bool)> {
+        let project_id = match &export.project {
+            Some(name) => Some(self.project_id(name).await?),
+            None => None,
+        };
+        let body = export.body(self.workspace_id, project_id);
+        let base = format!("/workspaces/{}/time_entries", self.workspace_id);
+        if let Some(remote_id) = remote_id {
+            let updated: Option<RemoteEntry> = self
+                .api
+                .send(Method::PUT, &format!("{base}/{remote_id}"), Some(&body))
+                .await?;
+            if let Some(updated) = updated {
+                return Ok((updated.id, false));
+            }
+        }
+        let created: RemoteEntry = self
+            .api
+            .send(Method::POST, &base, Some(&body))
+            .await?
+            .ok_or_else(|| CommandError::not_found("Toggl: the workspace is gone"))?;
+        Ok((created.id, true))
+    }
+}
+
+impl time_push::Target<Export, i64> for Pusher<'_> {
+    fn push<'a>(
+        &'a mut self,
+        export: &'a Export,
+        remote_id: Option<i64>,
+    ) -> SyncFuture<'a, (i64, bool)> {
+        Box::pin(self.push_entry(export, remote_id))
+    }
+}
+
+async fn push_account(
+    db: &Db,
+    account_id: &str,
+    from: &str,
+    to: &str,
+) -> CommandResult<TogglPushReport> {
+    let account = db.with_conn(|conn| Ok(find_account(conn, account_id)))??;
+    let api = Api::connect(&account.id)?;
+    let outgoing = db.with_conn(|conn| Ok(outgoing(conn, &account.id, from, to)))??;
+    let mut pusher = Pusher {
+        api: &api,
+        workspace_id: account.workspace_id,
+        projects: None,
+    };
+    time_push::push_entries(
+        db,
+        ENTRIES_TABLE,
+        &account.id,
+        outgoing,
+        &mut pusher,
+        |export| &export.description,
+    )
+    .await
+}
+
+/// Check an API token and save the account, pushing to `workspace_id` or
+/// the user's default workspace.
+#[tauri::command]
+pub async fn connect_toggl_account(
+    db: State<'_, Db>,
+    input: NewTogglAccount,
+) -> CommandResult<TogglAccount> {
+    let token = input.token.trim().to_string();
+    if token.is_empty() {
+        return Err(CommandError::invalid("Enter the Toggl Track API token"));
+    }
+    let api = Api::new(token.clone())?;
+    let user: RemoteUser = api.get("/me").await?;
+    let workspace_id = match input.workspace_id {
+        Some(id) => {
+            let workspaces: Vec<RemoteWorkspace> = api.get("/workspaces").await?;
+            if !workspaces.iter().any(|w| w.id == id) {
+                return Err(CommandError::not_found(format!(
+                    "No Toggl workspace {id} for this account"
+                )));
+            }
+            id
+        }
+        None => user
+            .default_workspace_id
+            .ok_or_else(|| CommandError::invalid("This Toggl account has no workspace"))?,
+    };
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .or(user.fullname)
+        .or(user.email)
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Toggl Track".to_string());
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO toggl_accounts (id, name, workspace_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![id, name, workspace_id, now],
+        )?;
+        list_accounts(conn)
+    });
+    let accounts = match stored {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            return Err(e);
+        }
+    };
+    Ok(accounts
+        .into_iter()
+        .find(|a| a.id == id)
+        .ok_or_else(|| "Failed to save account".to_string())?)
+}
+
+#[tauri::command]
+pub fn list_toggl_accounts(db: State<'_, Db>) -> CommandResult<Vec<TogglAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Forget an account and which entries went to it. Entries already in
+/// Toggl stay there.
+#[tauri::command]
+pub fn remove_toggl_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM toggl_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Toggl account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+/// Push the stopped time entries between `from` and `to` to Toggl: the
+/// task's title (and the entry's note) as the description, its project as
+/// the Toggl project, made when missing, and its tags. Entries pushed
+/// before are updated when they or their task changed, and skipped
+/// otherwise. One entry failing doesn't stop the others; its error is kept
+/// as its status.
+#[tauri::command]
+pub async fn push_to_toggl(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+) -> CommandResult<TogglPushReport> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    let _pushing = Pushing::start("Toggl")?;
+    push_account(&db, &account_id, &from, &to).await
+}
+
+/// Where each time entry between `from` and `to` stands with `account_id`.
+#[tauri::command]
+pub fn list_toggl_entry_status(
+    db: State<'_, Db>,
+    account_id: String,
+    from: String,
+    to: String,
+) -> CommandResult<Vec<TogglEntryStatus>> {
+    let from = format_utc(parse_utc(&from)?);
+    let to = format_utc(parse_utc(&to)?);
+    db.with_conn(|conn| {
+        Ok(find_account(conn, &account_id)
+            .and_then(|_| outgoing(conn, &account_id, &from, &to))
+            .map(|items| items.iter().map(Outgoing::entry_status).collect()))
+    })?
+}
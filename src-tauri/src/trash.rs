@@ -0,0 +1,275 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::Utc;
+use rusqlite::types::ToSql;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::data_dir;
+use crate::db::{format_utc, now_utc, parse_utc, Db};
+use crate::error::CommandResult;
+use crate::session::write_atomic;
+use crate::tags;
+use crate::task_store::{self, row_to_task, Task, TASK_COLUMNS, TASK_COLUMN_COUNT};
+use crate::time_entries;
+
+/// Emitted with the number of tasks removed when the retention policy
+/// empties part of the trash.
+pub const TRASH_PURGED_EVENT: &str = "trash-purged";
+
+const CONFIG_FILE: &str = "trash.json";
+const PURGE_POLL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrashConfig {
+    /// Days a deleted task stays in the trash. 0 keeps it until purged by
+    /// hand.
+    pub retention_days: u32,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedTask {
+    pub task: Task,
+    pub deleted_at: String,
+    /// When the retention policy will purge it, if it will.
+    pub purge_at: Option<String>,
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    data_dir::app_data_dir(app).map(|dir| dir.join(CONFIG_FILE))
+}
+
+pub fn load_config(app: &AppHandle) -> TrashConfig {
+    let Ok(path) = config_path(app) else {
+        return TrashConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            TrashConfig::default()
+        }),
+        Err(_) => TrashConfig::default(),
+    }
+}
+
+fn purge_at(deleted_at: &str, config: &TrashConfig) -> Option<String> {
+    if config.retention_days == 0 {
+        return None;
+    }
+    let deleted_at = parse_utc(deleted_at).ok()?;
+    Some(format_utc(
+        deleted_at + chrono::Duration::days(config.retention_days as i64),
+    ))
+}
+
+fn row_to_trashed(row: &Row) -> rusqlite::Result<(Task, String)> {
+    Ok((row_to_task(row)?, row.get(TASK_COLUMN_COUNT)?))
+}
+
+/// Live tasks in the subtree rooted at task `?1`, for use in SQL.
+const SUBTREE: &str = "WITH RECURSIVE subtree(id) AS (
+         SELECT ?1
+         UNION SELECT t.id FROM tasks t
+         JOIN subtree s ON t.parent_id = s.id WHERE t.deleted_at IS NULL
+     )";
+
+/// Move a task and its subtasks to the trash, stopping the timer if it's
+/// running on any of them. Returns false if there's no such task outside
+/// the trash.
+pub fn trash_task(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    let now = now_utc();
+    if task_store::find_task(conn, id)?.is_none() {
+        return Ok(false);
+    }
+    if let Some(entry) = time_entries::running_entry(conn)? {
+        let in_subtree: bool = conn.query_row(
+            &format!("{SUBTREE} SELECT EXISTS (SELECT 1 FROM subtree WHERE id = ?2)"),
+            params![id, entry.task_id],
+            |row| row.get(0),
+        )?;
+        if in_subtree {
+            time_entries::stop_running(conn, &now)?;
+        }
+    }
+    conn.execute(
+        &format!(
+            "{SUBTREE}
+             UPDATE tasks SET deleted_at = ?2, updated_at = ?2
+             WHERE id IN (SELECT id FROM subtree) AND deleted_at IS NULL"
+        ),
+        params![id, now],
+    )?;
+    Ok(true)
+}
+
+pub fn list_trashed(conn: &Connection) -> rusqlite::Result<Vec<(Task, String)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {TASK_COLUMNS}, deleted_at FROM tasks
+         WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id"
+    ))?;
+    let (mut tasks, deleted): (Vec<Task>, Vec<String>) = stmt
+        .query_map([], row_to_trashed)?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    tags::load_task_tags(conn, &mut tasks)?;
+    Ok(tasks.into_iter().zip(deleted).collect())
+}
+
+/// Take tasks back out of the trash, along with the subtasks that were
+/// trashed with them. A subtask whose parent is still in the trash comes
+/// back as a top-level task. Returns the restored tasks.
+pub fn restore_tasks(conn: &Connection, ids: &[String]) -> rusqlite::Result<Vec<Task>> {
+    let now = now_utc();
+    let mut restored = Vec::new();
+    for id in ids {
+        let deleted_at: Option<String> = conn
+            .query_row(
+                "SELECT deleted_at FROM tasks WHERE id = ?1 AND deleted_at IS NOT NULL",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(deleted_at) = deleted_at else {
+            continue;
+        };
+        conn.execute(
+            "WITH RECURSIVE subtree(id) AS (
+                 SELECT ?1
+                 UNION SELECT t.id FROM tasks t
+                 JOIN subtree s ON t.parent_id = s.id WHERE t.deleted_at = ?2
+             )
+             UPDATE tasks SET deleted_at = NULL, updated_at = ?3
+             WHERE id IN (SELECT id FROM subtree)",
+            params![id, deleted_at, now],
+        )?;
+        conn.execute(
+            "UPDATE tasks SET parent_id = NULL, sort_key = NULL
+             WHERE id = ?1
+               AND parent_id IN (SELECT id FROM tasks WHERE deleted_at IS NOT NULL)",
+            params![id],
+        )?;
+        restored.extend(task_store::find_task(conn, id)?);
+    }
+    Ok(restored)
+}
+
+/// Delete trashed tasks for good: those in `ids`, or all of them when
+/// `ids` is `None`. Their time entries, reminders and attachments go too.
+pub fn purge(conn: &Connection, ids: Option<&[String]>) -> rusqlite::Result<usize> {
+    let Some(ids) = ids else {
+        return conn.execute("DELETE FROM tasks WHERE deleted_at IS NOT NULL", []);
+    };
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let placeholders = vec!["?"; ids.len()].join(", ");
+    let params: Vec<&dyn ToSql> = ids.iter().map(|id| id as &dyn ToSql).collect();
+    conn.execute(
+        &format!("DELETE FROM tasks WHERE deleted_at IS NOT NULL AND id IN ({placeholders})"),
+        params.as_slice(),
+    )
+}
+
+/// Delete tasks that have been in the trash longer than the retention period.
+pub fn purge_expired(conn: &Connection, config: &TrashConfig) -> rusqlite::Result<usize> {
+    if config.retention_days == 0 {
+        return Ok(0);
+    }
+    let cutoff = Utc::now() - chrono::Duration::days(config.retention_days as i64);
+    conn.execute(
+        "DELETE FROM tasks WHERE deleted_at IS NOT NULL AND deleted_at < ?1",
+        params![format_utc(cutoff)],
+    )
+}
+
+/// Whether `id` is a task in the trash.
+pub fn is_trashed(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM tasks WHERE id = ?1 AND deleted_at IS NOT NULL)",
+        params![id],
+        |row| row.get(0),
+    )
+}
+
+fn run_purge(app: &AppHandle) {
+    let db = app.state::<Db>().unjournaled();
+    if db.is_locked() {
+        return;
+    }
+    let config = load_config(app);
+    match db.with_conn(|conn| purge_expired(conn, &config)) {
+        Ok(0) => {}
+        Ok(purged) => {
+            let _ = app.emit(TRASH_PURGED_EVENT, purged);
+        }
+        Err(e) => tracing::warn!("purge failed: {e}"),
+    }
+}
+
+/// Apply the retention policy now and then hourly. The config is re-read on
+/// every pass.
+pub fn spawn_trash_purger(app: &AppHandle) {
+    let handle = app.clone();
+
+    std::thread::spawn(move || loop {
+        run_purge(&handle);
+        std::thread::sleep(PURGE_POLL);
+    });
+}
+
+#[tauri::command]
+pub fn get_trash_config(app: AppHandle) -> TrashConfig {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn set_trash_config(app: AppHandle, config: TrashConfig) -> CommandResult<TrashConfig> {
+    let path = config_path(&app)?;
+    let body = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+    run_purge(&app);
+    Ok(config)
+}
+
+/// Deleted tasks, most recently deleted first.
+#[tauri::command]
+pub fn list_trash(app: AppHandle, db: State<'_, Db>) -> CommandResult<Vec<TrashedTask>> {
+    let config = load_config(&app);
+    let trashed = db.with_conn(|conn| list_trashed(conn))?;
+    Ok(trashed
+        .into_iter()
+        .map(|(task, deleted_at)| TrashedTask {
+            purge_at: purge_at(&deleted_at, &config),
+            task,
+            deleted_at,
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub fn restore_from_trash(db: State<'_, Db>, ids: Vec<String>) -> CommandResult<Vec<Task>> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let restored = restore_tasks(&tx, &ids)?;
+        tx.commit()?;
+        Ok(restored)
+    })
+}
+
+/// Permanently delete the given trashed tasks, or empty the trash when
+/// `ids` is omitted. Returns how many were deleted.
+#[tauri::command]
+pub fn purge_trash(db: State<'_, Db>, ids: Option<Vec<String>>) -> CommandResult<usize> {
+    db.with_conn(|conn| purge(conn, ids.as_deref()))
+}
@@ -0,0 +1,104 @@
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager};
+
+use crate::actions::{self, QuickAction};
+use crate::db::Db;
+use crate::pomodoro;
+use crate::settings;
+use crate::task_store;
+use crate::time_entries;
+use crate::window_effects;
+
+pub const TRAY_ID: &str = "daylight-tray";
+
+/// Create the tray icon. Left click brings the main window forward; the menu
+/// offers the same quick actions as the jump list.
+pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
+    let show = MenuItem::with_id(app, "show", "Show DayLight", true, None::<&str>)?;
+    let new_task = MenuItem::with_id(app, "new-task", "New task", true, None::<&str>)?;
+    let start_last_timer = MenuItem::with_id(
+        app,
+        "start-last-timer",
+        "Start last timer",
+        true,
+        None::<&str>,
+    )?;
+    let quick_add = MenuItem::with_id(app, "quick-add", "Quick add…", true, None::<&str>)?;
+    let mini_timer = MenuItem::with_id(app, "mini-timer", "Mini timer", true, None::<&str>)?;
+    let separator = PredefinedMenuItem::separator(app)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &show,
+            &new_task,
+            &start_last_timer,
+            &quick_add,
+            &mini_timer,
+            &separator,
+            &quit,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("DayLight")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "show" => {
+                actions::focus_main_window(app);
+            }
+            "new-task" => actions::dispatch(app, &QuickAction::NewTask),
+            "start-last-timer" => actions::dispatch(app, &QuickAction::StartLastTimer),
+            label @ ("quick-add" | "mini-timer") => window_effects::toggle_popup(app, label),
+            "quit" => app.exit(0),
+            _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                actions::focus_main_window(tray.app_handle());
+            }
+        });
+
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+
+    builder.build(app)?;
+    refresh_timer(app);
+    Ok(())
+}
+
+/// Reflect the running time entry and pomodoro phase in the tray tooltip.
+pub fn refresh_timer(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    if !settings::load(app).tray.show_timer {
+        let _ = tray.set_tooltip(Some("DayLight"));
+        return;
+    }
+    let db = app.state::<Db>();
+    let running = db.with_conn(|conn| {
+        let Some(entry) = time_entries::running_entry(conn)? else {
+            return Ok(None);
+        };
+        let title = task_store::find_task(conn, &entry.task_id)?.map(|t| t.title);
+        Ok(Some(title.unwrap_or_else(|| "Untitled task".to_string())))
+    });
+
+    let mut tooltip = match running {
+        Ok(Some(title)) => format!("DayLight — timing {title}"),
+        _ => "DayLight".to_string(),
+    };
+    if let Some(pomodoro) = pomodoro::tray_label(app) {
+        tooltip = format!("{tooltip} ({pomodoro})");
+    }
+    let _ = tray.set_tooltip(Some(tooltip));
+}
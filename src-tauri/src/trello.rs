@@ -0,0 +1,1207 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveTime};
+use chrono_tz::Tz;
+use reqwest::{Client, Response, StatusCode};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::account_health::AccountHealth;
+use crate::crdt;
+use crate::credentials;
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::history::{self, ChangeSource};
+use crate::projects;
+use crate::rate_limit;
+use crate::sync_provider::{self, Capabilities, RemoteCollection, SyncFuture, SyncProvider};
+use crate::sync_status::{SyncOutcome, Tracker};
+use crate::tags;
+use crate::task_store::{self, double_option, NewTask, Task, STATUS_DONE, STATUS_OPEN};
+use crate::timezone;
+use crate::trash;
+
+/// `external_refs.source` for tasks that came from Trello cards.
+const SOURCE: &str = "trello";
+
+const API_URL: &str = "https://api.trello.com/1";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const CARD_FIELDS: &str = "name,desc,due,dueComplete,idList,labels,dateLastActivity,closed";
+
+const CHECK_ITEM_COMPLETE: &str = "complete";
+
+/// Set while an import runs, so two can't interleave their writes.
+static IMPORTING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrelloAccount {
+    pub id: String,
+    pub name: String,
+    pub api_key: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewTrelloAccount {
+    /// Defaults to the Trello member's full name.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub api_key: String,
+    /// A token granted to `api_key` with read access.
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrelloList {
+    pub id: String,
+    pub title: String,
+    pub position: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrelloBoard {
+    pub id: String,
+    pub account_id: String,
+    pub title: String,
+    /// Local project the board's cards are filed under; named after the
+    /// board unless set before the first import.
+    pub project: Option<String>,
+    /// The list whose cards are done. Defaults to the board's last list.
+    pub done_list: Option<String>,
+    /// Whether every sync imports the board again, picking up changes.
+    pub ongoing: bool,
+    pub lists: Vec<TrelloList>,
+    pub imported_at: Option<String>,
+    pub last_synced_at: Option<String>,
+}
+
+/// For `project`, a missing key leaves it alone while an explicit `null`
+/// goes back to naming it after the board.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TrelloBoardPatch {
+    #[serde(deserialize_with = "double_option")]
+    pub project: Option<Option<String>>,
+    pub done_list: Option<String>,
+    pub ongoing: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TrelloImportReport {
+    pub board_id: String,
+    pub title: String,
+    /// Tasks made for new cards and checklist items, subtasks for the
+    /// latter.
+    pub created: usize,
+    pub updated: usize,
+    /// Tasks completed because their card was archived in Trello.
+    pub completed: usize,
+    /// Tasks moved to the trash because their card or checklist item was
+    /// deleted in Trello.
+    pub removed: usize,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for TrelloImportReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + self.completed + self.removed
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteMember {
+    username: String,
+    #[serde(default)]
+    full_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteBoard {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    closed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteList {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    pos: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteLabel {
+    #[serde(default)]
+    name: String,
+    /// Unnamed labels go by their color.
+    #[serde(default)]
+    color: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteCheckItem {
+    id: String,
+    #[serde(default)]
+    name: String,
+    /// `complete` or `incomplete`.
+    #[serde(default)]
+    state: String,
+    #[serde(default)]
+    pos: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteChecklist {
+    #[serde(default)]
+    pos: f64,
+    #[serde(default)]
+    check_items: Vec<RemoteCheckItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteCard {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    desc: String,
+    /// An ISO 8601 time in UTC.
+    #[serde(default)]
+    due: Option<String>,
+    #[serde(default)]
+    due_complete: bool,
+    id_list: String,
+    #[serde(default)]
+    labels: Vec<RemoteLabel>,
+    date_last_activity: String,
+    /// Archived.
+    #[serde(default)]
+    closed: bool,
+    #[serde(default)]
+    checklists: Vec<RemoteChecklist>,
+}
+
+impl RemoteCard {
+    fn label_names(&self) -> Vec<String> {
+        let names: Vec<String> = self
+            .labels
+            .iter()
+            .map(|l| match l.name.trim() {
+                "" => l.color.clone().unwrap_or_default(),
+                name => name.to_string(),
+            })
+            .filter(|n| !n.trim().is_empty())
+            .collect();
+        tags::normalize_names(&names).unwrap_or_default()
+    }
+
+    /// The checklist items, checklist by checklist, in Trello's order.
+    fn check_items(&self) -> Vec<&RemoteCheckItem> {
+        let mut checklists: Vec<&RemoteChecklist> = self.checklists.iter().collect();
+        checklists.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+        checklists
+            .into_iter()
+            .flat_map(|list| {
+                let mut items: Vec<&RemoteCheckItem> = list.check_items.iter().collect();
+                items.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+                items
+            })
+            .collect()
+    }
+
+    /// When the card last changed, in milliseconds, as the clocks of local
+    /// edits are.
+    fn modified_ms(&self) -> i64 {
+        parse_utc(&self.date_last_activity).map_or(0, |at| at.timestamp_millis())
+    }
+}
+
+/// A card's due time as tasks keep it: a date when it falls at local
+/// midnight, a wall-clock time in `local` otherwise.
+fn from_trello_due(due: Option<&str>, local: &Tz) -> Option<String> {
+    let at = DateTime::parse_from_rfc3339(due?)
+        .ok()?
+        .with_timezone(local);
+    if at.time() == NaiveTime::MIN {
+        Some(at.date_naive().to_string())
+    } else {
+        Some(at.format("%Y-%m-%dT%H:%M").to_string())
+    }
+}
+
+fn row_to_account(row: &Row) -> rusqlite::Result<TrelloAccount> {
+    Ok(TrelloAccount {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        api_key: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+const ACCOUNT_COLUMNS: &str = "id, name, api_key, created_at, updated_at";
+
+pub fn list_accounts(conn: &Connection) -> rusqlite::Result<Vec<TrelloAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ACCOUNT_COLUMNS} FROM trello_accounts ORDER BY name COLLATE NOCASE"
+    ))?;
+    let rows = stmt.query_map([], row_to_account)?;
+    rows.collect()
+}
+
+fn find_account(conn: &Connection, id: &str) -> rusqlite::Result<Option<TrelloAccount>> {
+    conn.query_row(
+        &format!("SELECT {ACCOUNT_COLUMNS} FROM trello_accounts WHERE id = ?1"),
+        params![id],
+        row_to_account,
+    )
+    .optional()
+}
+
+fn row_to_board(row: &Row) -> rusqlite::Result<TrelloBoard> {
+    Ok(TrelloBoard {
+        id: row.get(0)?,
+        account_id: row.get(1)?,
+        title: row.get(2)?,
+        project: row.get(3)?,
+        done_list: row.get(4)?,
+        ongoing: row.get(5)?,
+        imported_at: row.get(6)?,
+        last_synced_at: row.get(7)?,
+        lists: Vec::new(),
+    })
+}
+
+const BOARD_COLUMNS: &str =
+    "id, account_id, title, project, done_list, ongoing, imported_at, last_synced_at";
+
+fn load_lists(conn: &Connection, board_id: &str) -> rusqlite::Result<Vec<TrelloList>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, title, position FROM trello_lists
+         WHERE board_id = ?1 ORDER BY position, remote_id",
+    )?;
+    let rows = stmt.query_map(params![board_id], |row| {
+        Ok(TrelloList {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            position: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn list_boards(conn: &Connection, account_id: &str) -> rusqlite::Result<Vec<TrelloBoard>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {BOARD_COLUMNS} FROM trello_boards
+         WHERE account_id = ?1 ORDER BY title COLLATE NOCASE"
+    ))?;
+    let mut boards = stmt
+        .query_map(params![account_id], row_to_board)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for board in &mut boards {
+        board.lists = load_lists(conn, &board.id)?;
+    }
+    Ok(boards)
+}
+
+fn find_board(conn: &Connection, id: &str) -> rusqlite::Result<Option<TrelloBoard>> {
+    let board = conn
+        .query_row(
+            &format!("SELECT {BOARD_COLUMNS} FROM trello_boards WHERE id = ?1"),
+            params![id],
+            row_to_board,
+        )
+        .optional()?;
+    let Some(mut board) = board else {
+        return Ok(None);
+    };
+    board.lists = load_lists(conn, &board.id)?;
+    Ok(Some(board))
+}
+
+fn keyring_name(account_id: &str) -> String {
+    format!("trello:{account_id}")
+}
+
+/// A connection to the REST API with one account's key and token.
+struct Api {
+    client: Client,
+    key: String,
+    token: String,
+}
+
+impl Api {
+    fn new(key: String, token: String) -> CommandResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self { client, key, token })
+    }
+
+    fn connect(account: &TrelloAccount) -> CommandResult<Self> {
+        Self::new(
+            account.api_key.clone(),
+            credentials::require(&keyring_name(&account.id), "token")?,
+        )
+    }
+
+    /// Ask who the token belongs to, for the account health check.
+    async fn probe(&self) -> Result<Response, reqwest::Error> {
+        self.client
+            .get(format!("{API_URL}/members/me"))
+            .query(&[("key", &self.key), ("token", &self.token)])
+            .query(&[("fields", "id")])
+            .send()
+            .await
+    }
+
+    /// GET `path`; `None` when it isn't there.
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> CommandResult<Option<T>> {
+        rate_limit::check(SOURCE, "Trello")?;
+        let response = self
+            .client
+            .get(format!("{API_URL}/{path}"))
+            .query(&[("key", &self.key), ("token", &self.token)])
+            .query(query)
+            .send()
+            .await
+            .context("Trello")?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED => {
+                return Err(CommandError::unauthorized(
+                    "Trello refused the key or token; connect the account again",
+                )
+                .with_provider(SOURCE))
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                return Err(rate_limit::limited(SOURCE, "Trello", response.headers()))
+            }
+            StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => {
+                return Err(CommandError::status(
+                    status,
+                    format!("Trello: HTTP {}", status.as_u16()),
+                )
+                .with_provider(SOURCE))
+            }
+            _ => {}
+        }
+        let text = response.text().await?;
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Trello: {e}").into())
+    }
+}
+
+/// Save the boards found on an account. Boards Trello no longer has, or
+/// that were closed, are dropped along with their import state; their
+/// tasks stay.
+fn store_boards(
+    conn: &Connection,
+    account_id: &str,
+    found: &[RemoteBoard],
+) -> rusqlite::Result<()> {
+    let found: Vec<&RemoteBoard> = found.iter().filter(|b| !b.closed).collect();
+    for board in &found {
+        conn.execute(
+            "INSERT INTO trello_boards (id, account_id, remote_id, title)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(account_id, remote_id) DO UPDATE SET title = excluded.title",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                account_id,
+                board.id,
+                board.name
+            ],
+        )?;
+    }
+    let ids: Vec<&str> = found.iter().map(|b| b.id.as_str()).collect();
+    conn.execute(
+        "DELETE FROM trello_boards
+         WHERE account_id = ?1 AND remote_id NOT IN (SELECT value FROM json_each(?2))",
+        params![account_id, serde_json::to_string(&ids).unwrap_or_default()],
+    )?;
+    Ok(())
+}
+
+/// Save the board's open lists, and pick its last list as the done one
+/// when none is set or the one set is gone.
+fn store_lists(
+    conn: &Connection,
+    board: &mut TrelloBoard,
+    found: &[RemoteList],
+) -> rusqlite::Result<()> {
+    for list in found {
+        conn.execute(
+            "INSERT INTO trello_lists (board_id, remote_id, title, position)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(board_id, remote_id) DO UPDATE SET
+                 title = excluded.title,
+                 position = excluded.position",
+            params![board.id, list.id, list.name, list.pos],
+        )?;
+    }
+    let ids: Vec<&str> = found.iter().map(|l| l.id.as_str()).collect();
+    conn.execute(
+        "DELETE FROM trello_lists
+         WHERE board_id = ?1 AND remote_id NOT IN (SELECT value FROM json_each(?2))",
+        params![board.id, serde_json::to_string(&ids).unwrap_or_default()],
+    )?;
+    board.lists = load_lists(conn, &board.id)?;
+    if !board
+        .lists
+        .iter()
+        .any(|l| Some(&l.id) == board.done_list.as_ref())
+    {
+        board.done_list = board.lists.last().map(|l| l.id.clone());
+        conn.execute(
+            "UPDATE trello_boards SET done_list = ?2 WHERE id = ?1",
+            params![board.id, board.done_list],
+        )?;
+    }
+    Ok(())
+}
+
+/// What the last import knew about one card or checklist item.
+#[derive(Debug, Clone)]
+struct CardRow {
+    task_id: String,
+    /// The card a checklist item is on; `None` for cards.
+    parent_remote_id: Option<String>,
+    last_activity: String,
+}
+
+fn load_cards(conn: &Connection, board_id: &str) -> rusqlite::Result<HashMap<String, CardRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT remote_id, task_id, parent_remote_id, last_activity FROM trello_cards
+         WHERE board_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![board_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            CardRow {
+                task_id: row.get(1)?,
+                parent_remote_id: row.get(2)?,
+                last_activity: row.get(3)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn save_card(
+    conn: &Connection,
+    board_id: &str,
+    remote_id: &str,
+    card: &CardRow,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO trello_cards (board_id, remote_id, task_id, parent_remote_id, last_activity)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(board_id, remote_id) DO UPDATE SET
+             task_id = excluded.task_id,
+             parent_remote_id = excluded.parent_remote_id,
+             last_activity = excluded.last_activity",
+        params![
+            board_id,
+            remote_id,
+            card.task_id,
+            card.parent_remote_id,
+            card.last_activity
+        ],
+    )?;
+    Ok(())
+}
+
+fn forget_card(conn: &Connection, board_id: &str, remote_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM trello_cards WHERE board_id = ?1 AND remote_id = ?2",
+        params![board_id, remote_id],
+    )?;
+    Ok(())
+}
+
+fn set_done(task: &mut Task, done: bool) {
+    if done {
+        task.status = STATUS_DONE.to_string();
+        task.completed_at = Some(now_utc());
+    } else {
+        task.status = STATUS_OPEN.to_string();
+        task.completed_at = None;
+    }
+}
+
+/// Create or update the task for `card`, done when `done`. A field edited
+/// here after the card last changed in Trello keeps the local value.
+/// Returns the task and whether it was created, or `None` when nothing
+/// changed.
+fn write_remote(
+    conn: &Connection,
+    existing: Option<Task>,
+    card: &RemoteCard,
+    done: bool,
+    project: Option<&str>,
+    local: &Tz,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let title = card.name.trim();
+    let title = if title.is_empty() { "Untitled" } else { title }.to_string();
+    let description = Some(card.desc.clone()).filter(|d| !d.trim().is_empty());
+    let due = from_trello_due(card.due.as_deref(), local);
+    let labels = card.label_names();
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description,
+            project: project.map(str::to_string),
+            priority: None,
+            due,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: None,
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if done {
+            set_done(&mut task, true);
+            task_store::write_task(conn, &task)?;
+        }
+        tags::set_task_tags(conn, &task.id, &labels)?;
+        task.tags = labels;
+        return Ok(Some((task, true)));
+    };
+
+    let modified = card.modified_ms();
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| stamps.get(field).is_none_or(|s| s.clock <= modified);
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("description") {
+        task.description = description;
+    }
+    if take("due") {
+        task.due = due;
+    }
+    if take("status") && done != (task.status == STATUS_DONE) {
+        set_done(&mut task, done);
+    }
+    let take_tags = crdt::newest_tag_clock(conn, &task.id)?.is_none_or(|c| c <= modified);
+    let mut have: Vec<String> = task.tags.iter().map(|t| t.to_lowercase()).collect();
+    let mut want: Vec<String> = labels.iter().map(|t| t.to_lowercase()).collect();
+    have.sort();
+    want.sort();
+    let tags_changed = take_tags && have != want;
+
+    let changed = task.title != before.title
+        || task.description != before.description
+        || task.due != before.due
+        || task.status != before.status;
+    if !changed && !tags_changed {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    if tags_changed {
+        tags::set_task_tags(conn, &task.id, &labels)?;
+        task.tags = labels;
+    }
+    Ok(Some((task, false)))
+}
+
+/// Create or update the subtask of `parent` for a checklist item. Items
+/// carry no time of their own, so the card's stands in for it.
+fn write_check_item(
+    conn: &Connection,
+    existing: Option<Task>,
+    item: &RemoteCheckItem,
+    parent: &Task,
+    modified: i64,
+) -> rusqlite::Result<Option<(Task, bool)>> {
+    let title = item.name.trim();
+    let title = if title.is_empty() { "Untitled" } else { title }.to_string();
+    let done = item.state == CHECK_ITEM_COMPLETE;
+
+    let Some(mut task) = existing else {
+        let input = NewTask {
+            title: title.clone(),
+            description: None,
+            project: parent.project.clone(),
+            priority: None,
+            due: None,
+            scheduled: None,
+            recurrence: None,
+            tags: Vec::new(),
+            parent_id: Some(parent.id.clone()),
+            estimate_minutes: None,
+        };
+        let mut task = task_store::insert_task(conn, &input, title)?;
+        if done {
+            set_done(&mut task, true);
+            task_store::write_task(conn, &task)?;
+        }
+        return Ok(Some((task, true)));
+    };
+
+    let stamps = crdt::field_stamps(conn, &task.id)?;
+    let take = |field: &str| stamps.get(field).is_none_or(|s| s.clock <= modified);
+    let before = task.clone();
+    if take("title") {
+        task.title = title;
+    }
+    if take("status") && done != (task.status == STATUS_DONE) {
+        set_done(&mut task, done);
+    }
+    if task.title == before.title && task.status == before.status {
+        return Ok(None);
+    }
+    task.updated_at = now_utc();
+    task_store::write_task(conn, &task)?;
+    Ok(Some((task, false)))
+}
+
+/// Apply the board's cards, archived ones included. Cards in the done list
+/// or with their due date marked complete are done; checklist items become
+/// subtasks of their card's task. Archived cards complete their task and
+/// are no longer followed; cards and items deleted in Trello move theirs
+/// to the trash.
+fn merge_remote(
+    conn: &mut Connection,
+    board: &TrelloBoard,
+    cards: &[RemoteCard],
+    local: &Tz,
+    report: &mut TrelloImportReport,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let known = load_cards(&tx, &board.id)?;
+    let project = board.project.as_deref();
+    let archived: HashSet<&str> = cards
+        .iter()
+        .filter(|c| c.closed)
+        .map(|c| c.id.as_str())
+        .collect();
+    let mut seen: HashSet<&str> = HashSet::new();
+
+    for card in cards.iter().filter(|c| !c.closed) {
+        seen.insert(&card.id);
+        let items = card.check_items();
+        seen.extend(items.iter().map(|item| item.id.as_str()));
+        let row = known.get(&card.id);
+        if row.is_some_and(|r| r.last_activity == card.date_last_activity) {
+            continue;
+        }
+        let task_id = match row {
+            Some(row) => Some(row.task_id.clone()),
+            None => tx
+                .query_row(
+                    "SELECT task_id FROM external_refs WHERE source = ?1 AND external_id = ?2",
+                    params![SOURCE, card.id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?,
+        };
+        let existing = match &task_id {
+            Some(id) => task_store::find_task(&tx, id)?,
+            None => None,
+        };
+        if task_id.is_some() && existing.is_none() {
+            // Deleted here: it stays deleted.
+            continue;
+        }
+        let done = board.done_list.as_deref() == Some(card.id_list.as_str()) || card.due_complete;
+        let task_id = match write_remote(&tx, existing, card, done, project, local)? {
+            Some((task, true)) => {
+                report.created += 1;
+                tx.execute(
+                    "INSERT OR IGNORE INTO external_refs (source, external_id, task_id, created_at)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![SOURCE, card.id, task.id, now_utc()],
+                )?;
+                task.id
+            }
+            Some((task, false)) => {
+                report.updated += 1;
+                task.id
+            }
+            None => task_id.unwrap_or_default(),
+        };
+        let Some(parent) = task_store::find_task(&tx, &task_id)? else {
+            continue;
+        };
+        let row = CardRow {
+            task_id: parent.id.clone(),
+            parent_remote_id: None,
+            last_activity: card.date_last_activity.clone(),
+        };
+        save_card(&tx, &board.id, &card.id, &row)?;
+
+        for item in items {
+            let row = known.get(&item.id);
+            let existing = match row {
+                Some(row) => task_store::find_task(&tx, &row.task_id)?,
+                None => None,
+            };
+            if row.is_some() && existing.is_none() {
+                continue;
+            }
+            let written = write_check_item(&tx, existing, item, &parent, card.modified_ms())?;
+            let task_id = match written {
+                Some((task, created)) => {
+                    if created {
+                        report.created += 1;
+                    } else {
+                        report.updated += 1;
+                    }
+                    task.id
+                }
+                None => row.map(|r| r.task_id.clone()).unwrap_or_default(),
+            };
+            let row = CardRow {
+                task_id,
+                parent_remote_id: Some(card.id.clone()),
+                last_activity: card.date_last_activity.clone(),
+            };
+            save_card(&tx, &board.id, &item.id, &row)?;
+        }
+    }
+
+    for (remote_id, row) in &known {
+        if seen.contains(remote_id.as_str()) {
+            continue;
+        }
+        let on_archived = match &row.parent_remote_id {
+            Some(parent) => archived.contains(parent.as_str()),
+            None => archived.contains(remote_id.as_str()),
+        };
+        if !on_archived {
+            if trash::trash_task(&tx, &row.task_id)? {
+                report.removed += 1;
+            }
+        } else if row.parent_remote_id.is_none() {
+            if let Some(mut task) = task_store::find_task(&tx, &row.task_id)? {
+                if task.status != STATUS_DONE {
+                    set_done(&mut task, true);
+                    task.updated_at = now_utc();
+                    task_store::write_task(&tx, &task)?;
+                    report.completed += 1;
+                }
+            }
+        }
+        forget_card(&tx, &board.id, remote_id)?;
+    }
+    tx.commit()
+}
+
+async fn import_board(
+    db: &Db,
+    api: &Api,
+    board: &TrelloBoard,
+) -> CommandResult<TrelloImportReport> {
+    let mut report = TrelloImportReport {
+        board_id: board.id.clone(),
+        title: board.title.clone(),
+        ..TrelloImportReport::default()
+    };
+    let local = timezone::parse_zone(&timezone::system_zone())?;
+    let remote_id: String = db.with_conn(|conn| {
+        conn.query_row(
+            "SELECT remote_id FROM trello_boards WHERE id = ?1",
+            params![board.id],
+            |row| row.get(0),
+        )
+    })?;
+    let lists: Vec<RemoteList> = api
+        .get(
+            &format!("boards/{remote_id}/lists"),
+            &[("filter", "open"), ("fields", "name,pos")],
+        )
+        .await?
+        .ok_or_else(|| CommandError::not_found("Board not found in Trello"))?;
+    let cards: Vec<RemoteCard> = api
+        .get(
+            &format!("boards/{remote_id}/cards"),
+            &[
+                ("filter", "all"),
+                ("fields", CARD_FIELDS),
+                ("checklists", "all"),
+                ("checklist_fields", "pos"),
+            ],
+        )
+        .await?
+        .unwrap_or_default();
+
+    let mut board = board.clone();
+    db.with_conn(|conn| {
+        store_lists(conn, &mut board, &lists)?;
+        if board.project.is_none() {
+            board.project = Some(board.title.clone());
+        }
+        history::with_source(conn, ChangeSource::Sync, |conn| {
+            merge_remote(conn, &board, &cards, &local, &mut report)
+        })??;
+        let now = now_utc();
+        conn.execute(
+            "UPDATE trello_boards
+             SET project = ?2, imported_at = COALESCE(imported_at, ?3), last_synced_at = ?3
+             WHERE id = ?1",
+            params![board.id, board.project, now],
+        )
+    })?;
+    Ok(report)
+}
+
+fn find_trello_account(db: &Db, account_id: &str) -> CommandResult<TrelloAccount> {
+    db.with_conn(|conn| find_account(conn, account_id))?
+        .ok_or_else(|| CommandError::not_found(format!("Trello account not found: {account_id}")))
+}
+
+/// Check a key and token and save the account, then look up its boards.
+/// Nothing is imported until a board is.
+#[tauri::command]
+pub async fn connect_trello_account(
+    db: State<'_, Db>,
+    input: NewTrelloAccount,
+) -> CommandResult<TrelloAccount> {
+    let key = input.api_key.trim().to_string();
+    let token = input.token.trim().to_string();
+    if key.is_empty() || token.is_empty() {
+        return Err(CommandError::invalid("Enter the Trello API key and token"));
+    }
+    let api = Api::new(key.clone(), token.clone())?;
+    let member: RemoteMember = api
+        .get("members/me", &[("fields", "username,fullName")])
+        .await?
+        .ok_or_else(|| CommandError::unauthorized("Trello: no member for this token"))?;
+    let boards: Vec<RemoteBoard> = api
+        .get("members/me/boards", &[("fields", "name,closed")])
+        .await?
+        .unwrap_or_default();
+
+    let now = now_utc();
+    let id = uuid::Uuid::new_v4().to_string();
+    let name = input
+        .name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or(Some(member.full_name).filter(|n| !n.trim().is_empty()))
+        .unwrap_or(member.username);
+    credentials::save(&keyring_name(&id), Some(&token))?;
+    let stored = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO trello_accounts (id, name, api_key, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![id, name, key, now],
+        )?;
+        store_boards(&tx, &id, &boards)?;
+        tx.commit()?;
+        find_account(conn, &id)
+    });
+    match stored {
+        Ok(Some(account)) => Ok(account),
+        Ok(None) => Err("Failed to save account".into()),
+        Err(e) => {
+            let _ = credentials::save(&keyring_name(&id), None);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_trello_accounts(db: State<'_, Db>) -> CommandResult<Vec<TrelloAccount>> {
+    db.with_conn(|conn| list_accounts(conn))
+}
+
+/// Forget an account and its boards. Imported tasks stay, unlinked.
+#[tauri::command]
+pub fn remove_trello_account(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted = db
+        .with_conn(|conn| conn.execute("DELETE FROM trello_accounts WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!(
+            "Trello account not found: {id}"
+        )));
+    }
+    credentials::save(&keyring_name(&id), None)
+}
+
+#[tauri::command]
+pub fn list_trello_boards(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<TrelloBoard>> {
+    db.with_conn(|conn| list_boards(conn, &account_id))
+}
+
+async fn refresh_boards(db: &Db, account_id: &str) -> CommandResult<Vec<TrelloBoard>> {
+    let account = find_trello_account(db, account_id)?;
+    let api = Api::connect(&account)?;
+    let found: Vec<RemoteBoard> = api
+        .get("members/me/boards", &[("fields", "name,closed")])
+        .await?
+        .unwrap_or_default();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        store_boards(&tx, &account.id, &found)?;
+        tx.commit()?;
+        list_boards(conn, &account.id)
+    })
+}
+
+/// Look for boards added to or closed in Trello since.
+#[tauri::command]
+pub async fn refresh_trello_boards(
+    db: State<'_, Db>,
+    account_id: String,
+) -> CommandResult<Vec<TrelloBoard>> {
+    refresh_boards(&db, &account_id).await
+}
+
+fn update_board(db: &Db, id: &str, patch: TrelloBoardPatch) -> CommandResult<TrelloBoard> {
+    let project = match &patch.project {
+        Some(Some(name)) => Some(Some(projects::normalize_name(name)?)),
+        other => other.clone(),
+    };
+    db.with_conn(|conn| {
+        let Some(mut board) = find_board(conn, id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Trello board not found: {id}"
+            ))));
+        };
+        if let Some(project) = project {
+            board.project = project;
+        }
+        if let Some(ongoing) = patch.ongoing {
+            board.ongoing = ongoing;
+        }
+        if let Some(list) = patch.done_list {
+            if !board.lists.iter().any(|l| l.id == list) {
+                return Ok(Err(CommandError::not_found(format!(
+                    "List not found on this board: {list}"
+                ))));
+            }
+            if board.done_list.as_ref() != Some(&list) {
+                // Every card's status depends on it.
+                conn.execute(
+                    "UPDATE trello_cards SET last_activity = '' WHERE board_id = ?1",
+                    params![board.id],
+                )?;
+            }
+            board.done_list = Some(list);
+        }
+        conn.execute(
+            "UPDATE trello_boards SET project = ?2, done_list = ?3, ongoing = ?4 WHERE id = ?1",
+            params![board.id, board.project, board.done_list, board.ongoing],
+        )?;
+        Ok(Ok(board))
+    })?
+}
+
+/// Change a board's project or done list, or whether syncs keep importing
+/// it. A new done list applies to every card on the next import.
+#[tauri::command]
+pub fn update_trello_board(
+    db: State<'_, Db>,
+    id: String,
+    patch: TrelloBoardPatch,
+) -> CommandResult<TrelloBoard> {
+    update_board(&db, &id, patch)
+}
+
+/// Import a board's cards into its project, or bring an imported board up
+/// to date.
+#[tauri::command]
+pub async fn import_trello_board(
+    db: State<'_, Db>,
+    id: String,
+) -> CommandResult<TrelloImportReport> {
+    if IMPORTING.swap(true, Ordering::SeqCst) {
+        return Err("A Trello import is already running".into());
+    }
+    let result = import_one(&db, &id).await;
+    IMPORTING.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn import_one(db: &Db, id: &str) -> CommandResult<TrelloImportReport> {
+    let board = db
+        .with_conn(|conn| find_board(conn, id))?
+        .ok_or_else(|| CommandError::not_found(format!("Trello board not found: {id}")))?;
+    let account = find_trello_account(db, &board.account_id)?;
+    let api = Api::connect(&account)?;
+    import_board(db, &api, &board).await
+}
+
+pub struct Trello;
+
+impl SyncProvider for Trello {
+    type Report = Vec<TrelloImportReport>;
+
+    fn id(&self) -> &'static str {
+        SOURCE
+    }
+
+    fn name(&self) -> &'static str {
+        "Trello"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            push: false,
+            conflicts: false,
+        }
+    }
+
+    fn authenticate<'a>(&'a self, db: &'a Db, account_id: Option<&'a str>) -> SyncFuture<'a, ()> {
+        Box::pin(async move {
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_none_or(|id| id == account.id) {
+                    Api::connect(&account)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn check<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<AccountHealth>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let mut health = AccountHealth::new(&account.id, &account.name);
+                match Api::connect(&account) {
+                    Ok(api) => {
+                        health.answer("Trello", api.probe().await);
+                    }
+                    Err(e) => health.no_credentials(&e),
+                }
+                found.push(health.finish());
+            }
+            Ok(found)
+        })
+    }
+
+    fn pull<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+        progress: &'a Tracker,
+    ) -> SyncFuture<'a, Self::Report> {
+        Box::pin(async move {
+            if IMPORTING.swap(true, Ordering::SeqCst) {
+                return Err("A Trello import is already running".into());
+            }
+            let result = sync_accounts(db, account_id, progress).await;
+            IMPORTING.store(false, Ordering::SeqCst);
+            result
+        })
+    }
+
+    /// Boards, selected when syncs keep importing them.
+    fn collections<'a>(
+        &'a self,
+        db: &'a Db,
+        account_id: Option<&'a str>,
+    ) -> SyncFuture<'a, Vec<RemoteCollection>> {
+        Box::pin(async move {
+            let mut found = Vec::new();
+            for account in db.with_conn(|conn| list_accounts(conn))? {
+                if account_id.is_some_and(|id| id != account.id) {
+                    continue;
+                }
+                let boards = match refresh_boards(db, &account.id).await {
+                    Ok(boards) => boards,
+                    Err(e) => {
+                        tracing::warn!("refresh of {} failed: {e}", account.name);
+                        db.with_conn(|conn| list_boards(conn, &account.id))?
+                    }
+                };
+                found.extend(boards.into_iter().map(|b| RemoteCollection {
+                    id: b.id,
+                    account_id: b.account_id,
+                    name: b.title,
+                    selected: b.ongoing,
+                }));
+            }
+            Ok(found)
+        })
+    }
+
+    fn select_collection(&self, db: &Db, id: &str, selected: bool) -> CommandResult<()> {
+        let patch = TrelloBoardPatch {
+            ongoing: Some(selected),
+            ..TrelloBoardPatch::default()
+        };
+        update_board(db, id, patch).map(drop)
+    }
+}
+
+/// Import again every ongoing board of `account_id`, or of all accounts.
+/// One board failing doesn't stop the others; its error is in its report.
+#[tauri::command]
+pub async fn sync_trello(
+    app: AppHandle,
+    account_id: Option<String>,
+) -> CommandResult<Vec<TrelloImportReport>> {
+    sync_provider::run_command(&app, &Trello, account_id.as_deref()).await
+}
+
+async fn sync_accounts(
+    db: &Db,
+    account_id: Option<&str>,
+    progress: &Tracker,
+) -> CommandResult<Vec<TrelloImportReport>> {
+    let accounts = db.with_conn(|conn| list_accounts(conn))?;
+    let mut reports = Vec::new();
+    for account in accounts {
+        if account_id.is_some_and(|id| id != account.id) {
+            continue;
+        }
+        let boards = db.with_conn(|conn| list_boards(conn, &account.id))?;
+        let api = Api::connect(&account);
+        for board in boards.iter().filter(|b| b.ongoing) {
+            let result = match &api {
+                Ok(api) => import_board(db, api, board).await,
+                Err(e) => Err(e.clone()),
+            };
+            let report = result.unwrap_or_else(|e| {
+                tracing::warn!("import of {} failed: {e}", board.title);
+                TrelloImportReport {
+                    board_id: board.id.clone(),
+                    title: board.title.clone(),
+                    errors: vec![e.to_string()],
+                    ..TrelloImportReport::default()
+                }
+            });
+            progress.record(&report.title, &report);
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::Db;
+use crate::error::{CommandError, CommandResult};
+use crate::settings::Settings;
+use crate::stats::{self, Stats, StatsQuery};
+
+/// Features are names the code chooses, like `sync:todoist` or
+/// `view:calendar`, never anything the user typed.
+const MAX_FEATURE_LEN: usize = 64;
+/// Features listed in the year in review.
+const TOP_FEATURES: usize = 10;
+
+/// Whether usage is counted, from the `privacy.usage_stats` setting. Off
+/// until the settings are loaded.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureUsage {
+    pub feature: String,
+    pub count: i64,
+    /// Time spent over the uses that were timed.
+    pub total_ms: i64,
+    pub average_ms: Option<i64>,
+    /// The last local day it was used, `YYYY-MM-DD`.
+    pub last_used: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodUsage {
+    /// `YYYY-MM-DD` for a day, `YYYY-MM` for a month.
+    pub period: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageSummary {
+    /// Whether usage is being counted now.
+    pub enabled: bool,
+    /// The first and last local day anything was counted in the range.
+    pub first_day: Option<String>,
+    pub last_day: Option<String>,
+    pub active_days: i64,
+    pub total: i64,
+    /// Most used first.
+    pub features: Vec<FeatureUsage>,
+    pub by_month: Vec<PeriodUsage>,
+    pub busiest_day: Option<PeriodUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct YearInReview {
+    pub year: i32,
+    pub usage: UsageSummary,
+    /// Completions, streaks and tracked time over the year.
+    pub stats: Stats,
+}
+
+pub fn apply(settings: &Settings) {
+    ENABLED.store(settings.privacy.usage_stats, Ordering::Relaxed);
+}
+
+fn valid_feature(feature: &str) -> bool {
+    !feature.is_empty()
+        && feature.len() <= MAX_FEATURE_LEN
+        && feature
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-:.".contains(c))
+}
+
+fn bump(conn: &Connection, day: &str, feature: &str, ms: Option<i64>) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO usage_counts (day, feature, count, timed, total_ms)
+         VALUES (?1, ?2, 1, ?3, ?4)
+         ON CONFLICT (day, feature) DO UPDATE SET
+             count = count + 1,
+             timed = timed + excluded.timed,
+             total_ms = total_ms + excluded.total_ms",
+        params![day, feature, ms.is_some() as i64, ms.unwrap_or(0)],
+    )?;
+    Ok(())
+}
+
+/// Count one use of `feature` today, and how long it took when timed. Does
+/// nothing unless the user opted in.
+pub fn record(db: &Db, feature: &str, duration: Option<Duration>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if !valid_feature(feature) {
+        tracing::warn!("not counting malformed feature {feature:?}");
+        return;
+    }
+    let day = Local::now().date_naive().to_string();
+    let ms = duration.map(|d| i64::try_from(d.as_millis()).unwrap_or(i64::MAX));
+    if let Err(e) = db.with_conn(|conn| bump(conn, &day, feature, ms)) {
+        tracing::warn!("counting {feature} failed: {e}");
+    }
+}
+
+fn check_day(day: &Option<String>) -> CommandResult<()> {
+    match day {
+        Some(day) if NaiveDate::parse_from_str(day, "%Y-%m-%d").is_err() => {
+            Err(CommandError::invalid(format!("Invalid date: {day}")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Usage between local days `from` and `to`, both inclusive and open when
+/// omitted.
+pub fn summary(
+    conn: &Connection,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> rusqlite::Result<UsageSummary> {
+    const RANGE: &str = "(?1 IS NULL OR day >= ?1) AND (?2 IS NULL OR day <= ?2)";
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT feature, SUM(count), SUM(total_ms), SUM(timed), MAX(day)
+         FROM usage_counts WHERE {RANGE}
+         GROUP BY feature ORDER BY SUM(count) DESC, feature"
+    ))?;
+    let features = stmt
+        .query_map(params![from, to], |row| {
+            let total_ms: i64 = row.get(2)?;
+            let timed: i64 = row.get(3)?;
+            Ok(FeatureUsage {
+                feature: row.get(0)?,
+                count: row.get(1)?,
+                total_ms,
+                average_ms: (timed > 0).then(|| total_ms / timed),
+                last_used: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT substr(day, 1, 7), SUM(count) FROM usage_counts WHERE {RANGE}
+         GROUP BY substr(day, 1, 7) ORDER BY 1"
+    ))?;
+    let by_month = stmt
+        .query_map(params![from, to], |row| {
+            Ok(PeriodUsage {
+                period: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let busiest_day = conn
+        .query_row(
+            &format!(
+                "SELECT day, SUM(count) FROM usage_counts WHERE {RANGE}
+                 GROUP BY day ORDER BY SUM(count) DESC, day LIMIT 1"
+            ),
+            params![from, to],
+            |row| {
+                Ok(PeriodUsage {
+                    period: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            },
+        )
+        .optional()?;
+
+    let (first_day, last_day, active_days, total) = conn.query_row(
+        &format!(
+            "SELECT MIN(day), MAX(day), COUNT(DISTINCT day), COALESCE(SUM(count), 0)
+             FROM usage_counts WHERE {RANGE}"
+        ),
+        params![from, to],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+
+    Ok(UsageSummary {
+        enabled: ENABLED.load(Ordering::Relaxed),
+        first_day,
+        last_day,
+        active_days,
+        total,
+        features,
+        by_month,
+        busiest_day,
+    })
+}
+
+/// Count a use of a frontend feature, such as opening a view. Ignored
+/// unless the user opted in.
+#[tauri::command]
+pub fn record_usage(db: State<'_, Db>, feature: String, duration_ms: Option<u64>) {
+    record(&db, &feature, duration_ms.map(Duration::from_millis));
+}
+
+/// Feature use between local days `from` and `to` (`YYYY-MM-DD`, both
+/// inclusive), or over everything counted.
+#[tauri::command]
+pub fn get_usage_stats(
+    db: State<'_, Db>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<UsageSummary> {
+    check_day(&from)?;
+    check_day(&to)?;
+    db.with_conn(|conn| summary(conn, from.as_deref(), to.as_deref()))
+}
+
+/// Delete usage counted before local day `before`, or all of it. Returns
+/// how many day-and-feature counts went.
+#[tauri::command]
+pub fn purge_usage_stats(db: State<'_, Db>, before: Option<String>) -> CommandResult<usize> {
+    check_day(&before)?;
+    db.with_conn(|conn| {
+        conn.execute(
+            "DELETE FROM usage_counts WHERE ?1 IS NULL OR day < ?1",
+            params![before],
+        )
+    })
+}
+
+/// A year of DayLight: the features used most and when, beside the
+/// year's completions, streaks and tracked time. All worked out from the
+/// local database.
+#[tauri::command]
+pub fn get_year_in_review(
+    db: State<'_, Db>,
+    year: i32,
+    zone: Option<String>,
+) -> CommandResult<YearInReview> {
+    let from = NaiveDate::from_ymd_opt(year, 1, 1)
+        .ok_or_else(|| CommandError::invalid(format!("Invalid year: {year}")))?;
+    let to = NaiveDate::from_ymd_opt(year, 12, 31)
+        .ok_or_else(|| CommandError::invalid(format!("Invalid year: {year}")))?;
+    let (from, to) = (from.to_string(), to.to_string());
+    let mut usage = db.with_conn(|conn| summary(conn, Some(&from), Some(&to)))?;
+    usage.features.truncate(TOP_FEATURES);
+    let stats = stats::get_stats(
+        db,
+        StatsQuery {
+            from,
+            to,
+            zone,
+            project: None,
+            tag: None,
+            top_projects: None,
+        },
+    )?;
+    Ok(YearInReview { year, usage, stats })
+}
@@ -0,0 +1,251 @@
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use url::Url;
+
+use crate::db::{format_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::http;
+use crate::location;
+
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+/// Today and tomorrow.
+const FORECAST_DAYS: &str = "2";
+/// Open-Meteo doesn't say how long a forecast holds; it's refreshed
+/// about this often.
+const FRESH_MINUTES: i64 = 30;
+/// Coordinates are rounded to about a kilometre: plenty for a forecast,
+/// and one cached response serves the whole neighbourhood.
+const COORDINATE_DECIMALS: i32 = 2;
+
+const DAILY_FIELDS: &str = "weather_code,temperature_2m_max,temperature_2m_min,\
+                            precipitation_probability_max,precipitation_sum,wind_speed_10m_max";
+const HOURLY_FIELDS: &str = "weather_code,temperature_2m,precipitation_probability";
+
+pub const UNIT_CELSIUS: &str = "celsius";
+pub const UNIT_FAHRENHEIT: &str = "fahrenheit";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HourForecast {
+    /// Wall-clock time at the place, `YYYY-MM-DDTHH:MM`.
+    pub time: String,
+    pub condition: String,
+    pub weather_code: Option<i64>,
+    pub temperature: Option<f64>,
+    pub precipitation_probability: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayForecast {
+    pub date: String,
+    pub condition: String,
+    /// The WMO weather code, for picking an icon.
+    pub weather_code: Option<i64>,
+    pub temperature_max: Option<f64>,
+    pub temperature_min: Option<f64>,
+    pub precipitation_probability: Option<i64>,
+    pub precipitation_mm: Option<f64>,
+    pub wind_speed_max_kmh: Option<f64>,
+    pub hours: Vec<HourForecast>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Forecast {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// The place's IANA time zone, which the dates and times are in.
+    pub timezone: String,
+    pub temperature_unit: String,
+    /// Today, then tomorrow.
+    pub days: Vec<DayForecast>,
+    pub fetched_at: String,
+    /// Set when Open-Meteo couldn't be reached and this is the last
+    /// forecast fetched.
+    pub stale: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RemoteDaily {
+    time: Vec<String>,
+    weather_code: Vec<Option<i64>>,
+    temperature_2m_max: Vec<Option<f64>>,
+    temperature_2m_min: Vec<Option<f64>>,
+    precipitation_probability_max: Vec<Option<i64>>,
+    precipitation_sum: Vec<Option<f64>>,
+    wind_speed_10m_max: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RemoteHourly {
+    time: Vec<String>,
+    weather_code: Vec<Option<i64>>,
+    temperature_2m: Vec<Option<f64>>,
+    precipitation_probability: Vec<Option<i64>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteForecast {
+    latitude: f64,
+    longitude: f64,
+    #[serde(default)]
+    timezone: String,
+    #[serde(default)]
+    daily: RemoteDaily,
+    #[serde(default)]
+    hourly: RemoteHourly,
+}
+
+/// What a WMO weather code, as Open-Meteo reports it, means.
+fn condition(code: Option<i64>) -> &'static str {
+    match code {
+        Some(0) => "Clear",
+        Some(1) => "Mostly clear",
+        Some(2) => "Partly cloudy",
+        Some(3) => "Overcast",
+        Some(45 | 48) => "Fog",
+        Some(51 | 53 | 55) => "Drizzle",
+        Some(56 | 57) => "Freezing drizzle",
+        Some(61 | 63) => "Rain",
+        Some(65) => "Heavy rain",
+        Some(66 | 67) => "Freezing rain",
+        Some(71 | 73) => "Snow",
+        Some(75) => "Heavy snow",
+        Some(77) => "Snow grains",
+        Some(80 | 81) => "Rain showers",
+        Some(82) => "Violent rain showers",
+        Some(85 | 86) => "Snow showers",
+        Some(95) => "Thunderstorm",
+        Some(96 | 99) => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+fn round(value: f64) -> f64 {
+    let scale = 10f64.powi(COORDINATE_DECIMALS);
+    (value * scale).round() / scale
+}
+
+fn forecast_url(latitude: f64, longitude: f64, unit: &str) -> CommandResult<String> {
+    let mut url = Url::parse(FORECAST_URL).map_err(|e| e.to_string())?;
+    url.query_pairs_mut()
+        .append_pair("latitude", &round(latitude).to_string())
+        .append_pair("longitude", &round(longitude).to_string())
+        .append_pair("daily", DAILY_FIELDS)
+        .append_pair("hourly", HOURLY_FIELDS)
+        .append_pair("temperature_unit", unit)
+        .append_pair("timezone", "auto")
+        .append_pair("forecast_days", FORECAST_DAYS);
+    Ok(url.to_string())
+}
+
+/// The `index`th value of a series, which Open-Meteo leaves short or
+/// null where it has no data.
+fn at<T: Copy>(series: &[Option<T>], index: usize) -> Option<T> {
+    series.get(index).copied().flatten()
+}
+
+fn parse_forecast(body: &str, unit: &str) -> CommandResult<Forecast> {
+    let remote: RemoteForecast =
+        serde_json::from_str(body).map_err(|e| format!("Open-Meteo: {e}"))?;
+    let daily = &remote.daily;
+    let hourly = &remote.hourly;
+    let days = daily
+        .time
+        .iter()
+        .enumerate()
+        .map(|(i, date)| {
+            let hours = hourly
+                .time
+                .iter()
+                .enumerate()
+                .filter(|(_, time)| time.starts_with(date.as_str()))
+                .map(|(j, time)| {
+                    let code = at(&hourly.weather_code, j);
+                    HourForecast {
+                        time: time.clone(),
+                        condition: condition(code).to_string(),
+                        weather_code: code,
+                        temperature: at(&hourly.temperature_2m, j),
+                        precipitation_probability: at(&hourly.precipitation_probability, j),
+                    }
+                })
+                .collect();
+            let code = at(&daily.weather_code, i);
+            DayForecast {
+                date: date.clone(),
+                condition: condition(code).to_string(),
+                weather_code: code,
+                temperature_max: at(&daily.temperature_2m_max, i),
+                temperature_min: at(&daily.temperature_2m_min, i),
+                precipitation_probability: at(&daily.precipitation_probability_max, i),
+                precipitation_mm: at(&daily.precipitation_sum, i),
+                wind_speed_max_kmh: at(&daily.wind_speed_10m_max, i),
+                hours,
+            }
+        })
+        .collect();
+    Ok(Forecast {
+        latitude: remote.latitude,
+        longitude: remote.longitude,
+        timezone: remote.timezone,
+        temperature_unit: unit.to_string(),
+        days,
+        fetched_at: String::new(),
+        stale: false,
+    })
+}
+
+/// Today's and tomorrow's forecast at `latitude`, `longitude`, through the
+/// HTTP cache. Without a connection, the last forecast fetched for the
+/// place is returned, marked stale.
+pub async fn forecast(
+    db: &Db,
+    latitude: f64,
+    longitude: f64,
+    unit: &str,
+) -> CommandResult<Forecast> {
+    let url = forecast_url(latitude, longitude, unit)?;
+    let cached = db.with_conn(|conn| http::load_cached(conn, &url))?;
+    let (response, stale) = match http::get_text_cached(&url, cached.clone()).await {
+        Ok(mut response) => {
+            if response.expires_at.is_none() {
+                let fetched = parse_utc(&response.fetched_at).unwrap_or_else(|_| Utc::now());
+                response.expires_at = Some(format_utc(fetched + Duration::minutes(FRESH_MINUTES)));
+            }
+            (response, false)
+        }
+        Err(e) => {
+            tracing::warn!("fetch failed: {e}");
+            (cached.ok_or(format!("Open-Meteo: {e}"))?, true)
+        }
+    };
+    let mut forecast = parse_forecast(&response.body, unit)?;
+    forecast.fetched_at = response.fetched_at.clone();
+    forecast.stale = stale;
+    if !stale {
+        db.with_conn(|conn| http::store_cached(conn, &response))?;
+    }
+    Ok(forecast)
+}
+
+/// Today's and tomorrow's weather where the user is, in `temperature_unit`
+/// (`celsius`, the default, or `fahrenheit`).
+#[tauri::command]
+pub async fn get_weather(
+    db: State<'_, Db>,
+    temperature_unit: Option<String>,
+) -> CommandResult<Forecast> {
+    let unit = match temperature_unit.as_deref() {
+        None | Some(UNIT_CELSIUS) => UNIT_CELSIUS,
+        Some(UNIT_FAHRENHEIT) => UNIT_FAHRENHEIT,
+        Some(other) => {
+            return Err(CommandError::invalid(format!(
+                "Unknown temperature unit: {other}"
+            )))
+        }
+    };
+    let here = location::current(&db).await?;
+    forecast(&db, here.latitude, here.longitude, unit).await
+}
@@ -0,0 +1,368 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::header::ETAG;
+use reqwest::StatusCode;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use url::Url;
+
+use crate::caldav::{self, Session, TlsOptions};
+use crate::crdt::{self, ChangeSet, MergeReport, TASKS_MERGED_EVENT};
+use crate::credentials;
+use crate::data_dir;
+use crate::db::{now_utc, parse_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::history::{self, ChangeSource};
+use crate::session::write_atomic;
+use crate::sync_status::{self, SyncOutcome, Tracker};
+
+const KEYRING_ACCOUNT: &str = "webdav:folder";
+
+const CONFIG_FILE: &str = "webdav_sync.json";
+const SOURCE: &str = "webdav";
+/// Each device writes `daylight-<node>.json` and reads the others'.
+const BUNDLE_PREFIX: &str = "daylight-";
+const BUNDLE_SUFFIX: &str = ".json";
+const SCHEDULE_POLL: Duration = Duration::from_secs(60);
+
+const PROPFIND_ETAGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop><d:getetag/></d:prop>
+</d:propfind>"#;
+
+static SYNCING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebdavSyncConfig {
+    pub enabled: bool,
+    /// The shared folder, such as a Nextcloud Files folder's WebDAV URL.
+    /// It's made if it doesn't exist.
+    pub url: String,
+    pub username: String,
+    pub tls: TlsOptions,
+    /// Minutes between automatic syncs; 0 syncs only on request.
+    pub interval_minutes: u32,
+}
+
+impl Default for WebdavSyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            username: String::new(),
+            tls: TlsOptions::default(),
+            interval_minutes: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebdavSyncSettings {
+    pub config: WebdavSyncConfig,
+    pub has_password: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WebdavSyncReport {
+    /// Other devices' bundles fetched and merged because they changed.
+    pub devices_merged: usize,
+    pub created: usize,
+    pub updated: usize,
+    /// Incoming values that lost to a newer edit here.
+    pub kept_local: usize,
+    /// Whether this device's bundle was written.
+    pub uploaded: bool,
+    pub errors: Vec<String>,
+}
+
+impl SyncOutcome for WebdavSyncReport {
+    fn processed(&self) -> usize {
+        self.created + self.updated + usize::from(self.uploaded)
+    }
+
+    fn errors(&self) -> Vec<String> {
+        self.errors.clone()
+    }
+}
+
+fn config_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    Ok(data_dir::app_data_dir(app)?.join(CONFIG_FILE))
+}
+
+pub fn load_config(app: &AppHandle) -> WebdavSyncConfig {
+    let Ok(path) = config_path(app) else {
+        return WebdavSyncConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring unreadable config: {e}");
+            WebdavSyncConfig::default()
+        }),
+        Err(_) => WebdavSyncConfig::default(),
+    }
+}
+
+fn settings(app: &AppHandle) -> WebdavSyncSettings {
+    WebdavSyncSettings {
+        config: load_config(app),
+        has_password: credentials::load(KEYRING_ACCOUNT).is_some(),
+    }
+}
+
+/// The name of the bundle at `url`, if it's another device's.
+fn bundle_name<'a>(url: &'a str, own: &str) -> Option<&'a str> {
+    let name = url.trim_end_matches('/').rsplit('/').next()?;
+    (name.starts_with(BUNDLE_PREFIX) && name.ends_with(BUNDLE_SUFFIX) && name != own)
+        .then_some(name)
+}
+
+/// The etag a bundle had when it was last merged, and for this device's
+/// own, the local clock it was written at.
+fn file_state(conn: &Connection, name: &str) -> rusqlite::Result<Option<(Option<String>, i64)>> {
+    conn.query_row(
+        "SELECT etag, clock FROM webdav_sync_files WHERE name = ?1",
+        params![name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+fn save_file_state(
+    conn: &Connection,
+    name: &str,
+    etag: Option<&str>,
+    clock: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO webdav_sync_files (name, etag, clock, synced_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(name) DO UPDATE SET
+             etag = excluded.etag,
+             clock = excluded.clock,
+             synced_at = excluded.synced_at",
+        params![name, etag, clock, now_utc()],
+    )?;
+    Ok(())
+}
+
+/// The folder's bundles with their etags, making the folder first if it
+/// isn't there.
+async fn list_folder(
+    session: &Session,
+    folder: &Url,
+) -> CommandResult<Vec<(String, Option<String>)>> {
+    match session
+        .multistatus("PROPFIND", folder, "1", PROPFIND_ETAGS)
+        .await
+    {
+        Ok((_, responses)) => Ok(responses
+            .iter()
+            .map(|r| (r.href.clone(), r.text("getetag")))
+            .collect()),
+        Err(e) => {
+            let (_, response) = session.send("MKCOL", folder, &[], None).await?;
+            if response.status() == StatusCode::CREATED {
+                Ok(Vec::new())
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Merge the other devices' bundles that changed since, then write this
+/// device's own if anything changed here.
+async fn sync(app: &AppHandle, db: &Db) -> CommandResult<WebdavSyncReport> {
+    let config = load_config(app);
+    if config.url.trim().is_empty() {
+        return Err(CommandError::invalid("Choose a WebDAV folder first"));
+    }
+    let password = credentials::load(KEYRING_ACCOUNT)
+        .ok_or_else(|| CommandError::unauthorized("No saved password for the WebDAV folder"))?;
+    let session = Session::new(SOURCE, &config.username, &password, &config.tls)?;
+    let folder = caldav::collection_url(&config.url)?;
+
+    let (node, _) = db.with_conn(|conn| crdt::local_clock(conn))?;
+    let own = format!("{BUNDLE_PREFIX}{node}{BUNDLE_SUFFIX}");
+    let mut report = WebdavSyncReport::default();
+    let listing = list_folder(&session, &folder).await?;
+
+    for (url, etag) in &listing {
+        let Some(name) = bundle_name(url, &own) else {
+            continue;
+        };
+        let seen = db.with_conn(|conn| file_state(conn, name))?;
+        if etag.is_some() && seen.as_ref().is_some_and(|(seen, _)| seen == etag) {
+            continue;
+        }
+        let merged = match fetch_bundle(&session, url).await {
+            Ok(changes) => db.with_conn(|conn| {
+                let merged = history::with_source(conn, ChangeSource::Sync, |conn| {
+                    crdt::merge(conn, &changes)
+                })??;
+                save_file_state(conn, name, etag.as_deref(), changes.clock)?;
+                Ok(merged)
+            }),
+            Err(e) => Err(e),
+        };
+        match merged {
+            Ok(merged) => add_merge(&mut report, merged),
+            Err(e) => {
+                tracing::warn!("merging {name} failed: {e}");
+                report.errors.push(format!("{name}: {e}"));
+            }
+        }
+    }
+    if report.created + report.updated > 0 {
+        let _ = app.emit(
+            TASKS_MERGED_EVENT,
+            MergeReport {
+                created: report.created,
+                updated: report.updated,
+                kept_local: report.kept_local,
+                rejected: Vec::new(),
+            },
+        );
+    }
+
+    let written_at = db
+        .with_conn(|conn| file_state(conn, &own))?
+        .map(|(_, clock)| clock);
+    let listed = listing
+        .iter()
+        .any(|(url, _)| url.trim_end_matches('/').ends_with(&own));
+    let changes = db.with_conn(|conn| crdt::changes_since(conn, 0))?;
+    if !listed || written_at.is_none_or(|clock| changes.clock > clock) {
+        let body = serde_json::to_string(&changes).map_err(|e| e.to_string())?;
+        let url = folder.join(&own).map_err(|e| e.to_string())?;
+        let headers = [("Content-Type", "application/json")];
+        let (_, response) = session.send("PUT", &url, &headers, Some(&body)).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(CommandError::status(
+                status,
+                format!("PUT {}: HTTP {}", url.path(), status.as_u16()),
+            )
+            .with_provider(SOURCE));
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|e| e.to_str().ok())
+            .map(str::to_string);
+        db.with_conn(|conn| save_file_state(conn, &own, etag.as_deref(), changes.clock))?;
+        report.uploaded = true;
+    }
+    Ok(report)
+}
+
+async fn fetch_bundle(session: &Session, url: &str) -> CommandResult<ChangeSet> {
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    let (_, response) = session.send("GET", &url, &[], None).await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(CommandError::status(
+            status,
+            format!("GET {}: HTTP {}", url.path(), status.as_u16()),
+        )
+        .with_provider(SOURCE));
+    }
+    let text = response.text().await?;
+    serde_json::from_str(&text).map_err(|e| format!("Unreadable bundle: {e}").into())
+}
+
+fn add_merge(report: &mut WebdavSyncReport, merged: MergeReport) {
+    report.devices_merged += 1;
+    report.created += merged.created;
+    report.updated += merged.updated;
+    report.kept_local += merged.kept_local;
+    report.errors.extend(merged.rejected);
+}
+
+async fn run(app: &AppHandle) -> CommandResult<WebdavSyncReport> {
+    if SYNCING.swap(true, Ordering::SeqCst) {
+        return Err("A WebDAV sync is already running".into());
+    }
+    let progress = Tracker::start(app, SOURCE);
+    let result = sync(app, &app.state::<Db>().unjournaled()).await;
+    SYNCING.store(false, Ordering::SeqCst);
+    progress.finish(&result);
+    result
+}
+
+/// Whether the configured interval has passed since the last sync began.
+fn sync_due(db: &Db, config: &WebdavSyncConfig) -> bool {
+    if !config.enabled || config.interval_minutes == 0 || db.is_locked() {
+        return false;
+    }
+    let last = db
+        .with_conn(|conn| sync_status::load(conn, SOURCE))
+        .ok()
+        .and_then(|status| status.last_started_at)
+        .and_then(|at| parse_utc(&at).ok());
+    last.is_none_or(|last| {
+        Utc::now() - last >= chrono::Duration::minutes(config.interval_minutes as i64)
+    })
+}
+
+/// Sync whenever the configured interval has passed. The config is re-read
+/// on every check.
+pub fn spawn_webdav_sync_scheduler(app: &AppHandle) {
+    let handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if sync_due(&handle.state::<Db>(), &load_config(&handle)) {
+                if let Err(e) = run(&handle).await {
+                    tracing::warn!("{e}");
+                }
+            }
+            tokio::time::sleep(SCHEDULE_POLL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_webdav_sync_config(app: AppHandle) -> WebdavSyncSettings {
+    settings(&app)
+}
+
+/// Save the config. `password` replaces the saved one; an empty string
+/// forgets it and `None` keeps it.
+#[tauri::command]
+pub fn set_webdav_sync_config(
+    app: AppHandle,
+    config: WebdavSyncConfig,
+    password: Option<String>,
+) -> CommandResult<WebdavSyncSettings> {
+    let config = WebdavSyncConfig {
+        url: config.url.trim().to_string(),
+        username: config.username.trim().to_string(),
+        tls: config.tls.normalized(),
+        ..config
+    };
+    if config.enabled {
+        caldav::collection_url(&config.url)?;
+    }
+    if let Some(password) = &password {
+        credentials::save(
+            KEYRING_ACCOUNT,
+            Some(password.as_str()).filter(|p| !p.is_empty()),
+        )?;
+    }
+    let path = config_path(&app)?;
+    let body = serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())?;
+    write_atomic(&path, &body)?;
+    Ok(settings(&app))
+}
+
+/// Sync with the folder now rather than waiting for the interval.
+#[tauri::command]
+pub async fn sync_webdav_now(app: AppHandle) -> CommandResult<WebdavSyncReport> {
+    run(&app).await
+}
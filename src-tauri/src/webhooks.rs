@@ -0,0 +1,528 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+use sha2::Sha256;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+
+use crate::db::{format_utc, now_utc, Db};
+use crate::error::{CommandError, CommandResult};
+use crate::events::EntryChanged;
+use crate::schedule::TimeBlock;
+use crate::task_store;
+use crate::time_entries;
+
+pub const TASK_COMPLETED: &str = "task.completed";
+pub const TIMER_STARTED: &str = "timer.started";
+pub const DAY_PLAN_FINALIZED: &str = "day_plan.finalized";
+/// Sent by `test_webhook` only; hooks can't subscribe to it.
+const PING: &str = "ping";
+const EVENTS: &[&str] = &[TASK_COMPLETED, TIMER_STARTED, DAY_PLAN_FINALIZED];
+
+/// Header carrying `sha256=` and the hex HMAC-SHA256 of the body, keyed
+/// with the hook's secret.
+const SIGNATURE_HEADER: &str = "X-DayLight-Signature-256";
+const EVENT_HEADER: &str = "X-DayLight-Event";
+const DELIVERY_HEADER: &str = "X-DayLight-Delivery";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// How often the queue is checked for retries that came due.
+const POLL: Duration = Duration::from_secs(30);
+/// Wait before each retry; a delivery is dropped once these run out.
+const RETRY_DELAYS: &[i64] = &[60, 5 * 60, 30 * 60, 2 * 3600, 6 * 3600];
+/// Where the last error is cut, so one odd response can't bloat the row.
+const MAX_ERROR_LEN: usize = 200;
+
+/// Wakes the delivery loop when something is queued.
+static QUEUED: Notify = Notify::const_new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    /// Shown so it can be entered where the hook is received.
+    pub secret: String,
+    pub enabled: bool,
+    /// HTTP status of the latest attempt, if a response came back.
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub last_delivered_at: Option<String>,
+    /// Deliveries still waiting to go out or be retried.
+    pub pending: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewWebhook {
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WebhookPatch {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+struct Delivery {
+    id: String,
+    webhook_id: String,
+    event: String,
+    payload: String,
+    attempts: usize,
+    url: String,
+    secret: String,
+}
+
+const WEBHOOK_COLUMNS: &str = "w.id, w.url, w.events, w.secret, w.enabled, w.last_status,
+     w.last_error, w.last_delivered_at, w.created_at, w.updated_at,
+     (SELECT COUNT(*) FROM webhook_deliveries d WHERE d.webhook_id = w.id)";
+
+fn row_to_webhook(row: &Row) -> rusqlite::Result<Webhook> {
+    let events: String = row.get(2)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        events: serde_json::from_str(&events).unwrap_or_default(),
+        secret: row.get(3)?,
+        enabled: row.get(4)?,
+        last_status: row.get(5)?,
+        last_error: row.get(6)?,
+        last_delivered_at: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        pending: row.get(10)?,
+    })
+}
+
+fn list(conn: &Connection) -> rusqlite::Result<Vec<Webhook>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {WEBHOOK_COLUMNS} FROM webhooks w ORDER BY w.created_at, w.id"
+    ))?;
+    let rows = stmt.query_map([], row_to_webhook)?;
+    rows.collect()
+}
+
+fn find(conn: &Connection, id: &str) -> rusqlite::Result<Option<Webhook>> {
+    conn.query_row(
+        &format!("SELECT {WEBHOOK_COLUMNS} FROM webhooks w WHERE w.id = ?1"),
+        params![id],
+        row_to_webhook,
+    )
+    .optional()
+}
+
+fn write(conn: &Connection, hook: &Webhook) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO webhooks
+             (id, url, events, secret, enabled, last_status, last_error,
+              last_delivered_at, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            hook.id,
+            hook.url,
+            serde_json::to_string(&hook.events).unwrap_or_default(),
+            hook.secret,
+            hook.enabled,
+            hook.last_status,
+            hook.last_error,
+            hook.last_delivered_at,
+            hook.created_at,
+            hook.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+fn validate_url(url: &str) -> CommandResult<String> {
+    let url = url.trim();
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => {
+            Ok(url.to_string())
+        }
+        _ => Err(CommandError::invalid(format!("Not an http(s) URL: {url}"))),
+    }
+}
+
+fn validate_events(events: &[String]) -> CommandResult<Vec<String>> {
+    if let Some(unknown) = events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+        return Err(CommandError::invalid(format!(
+            "Unknown webhook event: {unknown}"
+        )));
+    }
+    if events.is_empty() {
+        return Err(CommandError::invalid("Choose at least one event"));
+    }
+    // Keep them in the order of EVENTS, once each.
+    Ok(EVENTS
+        .iter()
+        .filter(|e| events.iter().any(|chosen| chosen == *e))
+        .map(|e| e.to_string())
+        .collect())
+}
+
+fn new_secret() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` keyed with `secret`, the way
+/// GitHub signs its webhooks, so the same receiver code checks both.
+fn signature(secret: &str, body: &str) -> String {
+    // HMAC takes a key of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(body.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Queue `data` as an `event` payload for each enabled hook listening for
+/// it (or for `only`, whatever it listens for). Returns how many were
+/// queued.
+fn enqueue(
+    conn: &Connection,
+    event: &str,
+    data: &Json,
+    only: Option<&str>,
+) -> rusqlite::Result<usize> {
+    let hooks: Vec<Webhook> = list(conn)?
+        .into_iter()
+        .filter(|hook| match only {
+            Some(id) => hook.id == id,
+            None => hook.enabled && hook.events.iter().any(|e| e == event),
+        })
+        .collect();
+    let now = now_utc();
+    for hook in &hooks {
+        let id = uuid::Uuid::new_v4().to_string();
+        let payload = json!({
+            "id": id,
+            "event": event,
+            "occurred_at": now,
+            "data": data,
+        });
+        conn.execute(
+            "INSERT INTO webhook_deliveries
+                 (id, webhook_id, event, payload, attempts, next_attempt_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, 0, ?5, ?5)",
+            params![id, hook.id, event, payload.to_string(), now],
+        )?;
+    }
+    Ok(hooks.len())
+}
+
+/// Queue one `event` per item of `data`, built with the connection, and
+/// wake the delivery loop. Does nothing without a hook for the event.
+fn dispatch(
+    app: &AppHandle,
+    event: &str,
+    data: impl FnOnce(&Connection) -> rusqlite::Result<Vec<Json>>,
+) {
+    let db = app.state::<Db>();
+    let queued = db.with_conn(|conn| {
+        let listening: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM webhooks, json_each(webhooks.events)
+                            WHERE enabled = 1 AND json_each.value = ?1)",
+            params![event],
+            |row| row.get(0),
+        )?;
+        if !listening {
+            return Ok(0);
+        }
+        let mut queued = 0;
+        for item in data(conn)? {
+            queued += enqueue(conn, event, &item, None)?;
+        }
+        Ok(queued)
+    });
+    match queued {
+        Ok(0) => {}
+        Ok(_) => QUEUED.notify_one(),
+        Err(e) => tracing::warn!("failed to queue {event}: {e}"),
+    }
+}
+
+/// Tasks just marked done.
+pub fn tasks_completed(app: &AppHandle, ids: &[String]) {
+    if ids.is_empty() {
+        return;
+    }
+    dispatch(app, TASK_COMPLETED, |conn| {
+        let mut items = Vec::new();
+        for id in ids {
+            if let Some(task) = task_store::find_task(conn, id)? {
+                items.push(json!({ "task": task }));
+            }
+        }
+        Ok(items)
+    });
+}
+
+/// Time entries just started, with their task.
+pub fn timers_started(app: &AppHandle, entries: &[EntryChanged]) {
+    if entries.is_empty() {
+        return;
+    }
+    dispatch(app, TIMER_STARTED, |conn| {
+        let mut items = Vec::new();
+        for changed in entries {
+            let Some(entry) = time_entries::find_entry(conn, &changed.id)? else {
+                continue;
+            };
+            let task = task_store::find_task(conn, &entry.task_id)?;
+            items.push(json!({ "entry": entry, "task": task }));
+        }
+        Ok(items)
+    });
+}
+
+/// A day's blocks once the plan for it is settled, each with its task's
+/// title.
+pub fn day_plan_finalized(app: &AppHandle, day: &str, blocks: &[TimeBlock]) {
+    dispatch(app, DAY_PLAN_FINALIZED, |conn| {
+        let mut items = Vec::new();
+        for block in blocks {
+            let title = task_store::find_task(conn, &block.task_id)?.map(|t| t.title);
+            items.push(json!({ "block": block, "title": title }));
+        }
+        Ok(vec![json!({ "day": day, "blocks": items })])
+    });
+}
+
+fn due_deliveries(conn: &Connection) -> rusqlite::Result<Vec<Delivery>> {
+    let mut stmt = conn.prepare(
+        "SELECT d.id, d.webhook_id, d.event, d.payload, d.attempts, w.url, w.secret
+         FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+         WHERE d.next_attempt_at <= ?1 AND (w.enabled = 1 OR d.event = ?2)
+         ORDER BY d.created_at, d.id",
+    )?;
+    let rows = stmt.query_map(params![now_utc(), PING], |row| {
+        Ok(Delivery {
+            id: row.get(0)?,
+            webhook_id: row.get(1)?,
+            event: row.get(2)?,
+            payload: row.get(3)?,
+            attempts: row.get(4)?,
+            url: row.get(5)?,
+            secret: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// POST one delivery. Returns the response status, and an error unless
+/// it was a 2xx.
+async fn post(client: &reqwest::Client, delivery: &Delivery) -> (Option<u16>, Option<String>) {
+    let response = client
+        .post(&delivery.url)
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "DayLight-Webhooks")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, &delivery.id)
+        .header(
+            SIGNATURE_HEADER,
+            signature(&delivery.secret, &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => {
+            let status = response.status().as_u16();
+            (Some(status), Some(format!("HTTP {status}")))
+        }
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+/// Record the outcome of an attempt: a delivered (or abandoned) delivery
+/// leaves the queue, a failed one waits for its next retry.
+fn record(
+    conn: &Connection,
+    delivery: &Delivery,
+    status: Option<u16>,
+    error: Option<String>,
+) -> rusqlite::Result<()> {
+    let now = Utc::now();
+    let retry = RETRY_DELAYS
+        .get(delivery.attempts)
+        .filter(|_| error.is_some());
+    match retry {
+        Some(delay) => conn.execute(
+            "UPDATE webhook_deliveries SET attempts = attempts + 1, next_attempt_at = ?2
+             WHERE id = ?1",
+            params![
+                delivery.id,
+                format_utc(now + chrono::Duration::seconds(*delay))
+            ],
+        )?,
+        None => conn.execute(
+            "DELETE FROM webhook_deliveries WHERE id = ?1",
+            params![delivery.id],
+        )?,
+    };
+    let error = error.map(|e| {
+        let e = match retry {
+            Some(_) => e,
+            None => format!("{e} (gave up)"),
+        };
+        e.chars().take(MAX_ERROR_LEN).collect::<String>()
+    });
+    conn.execute(
+        "UPDATE webhooks SET last_status = ?2, last_error = ?3,
+             last_delivered_at = CASE WHEN ?3 IS NULL THEN ?4 ELSE last_delivered_at END
+         WHERE id = ?1",
+        params![delivery.webhook_id, status, error, format_utc(now)],
+    )?;
+    Ok(())
+}
+
+/// Post every delivery that's due, oldest first.
+async fn deliver_due(app: &AppHandle, client: &reqwest::Client) {
+    let db = app.state::<Db>();
+    let Ok(deliveries) = db.with_conn(|conn| due_deliveries(conn)) else {
+        // Locked: try again once it's open.
+        return;
+    };
+    for delivery in deliveries {
+        let (status, error) = post(client, &delivery).await;
+        if let Some(e) = &error {
+            tracing::warn!("{} to {} failed: {e}", delivery.event, delivery.url);
+        }
+        if let Err(e) = db.with_conn(|conn| record(conn, &delivery, status, error)) {
+            tracing::warn!("{e}");
+        }
+    }
+}
+
+/// Deliver queued posts as they're queued, and retries as they come due.
+pub fn spawn_webhook_sender(app: &AppHandle) {
+    let handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        loop {
+            deliver_due(&handle, &client).await;
+            let _ = tokio::time::timeout(POLL, QUEUED.notified()).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_webhooks(db: State<'_, Db>) -> CommandResult<Vec<Webhook>> {
+    db.with_conn(|conn| list(conn))
+}
+
+#[tauri::command]
+pub fn create_webhook(db: State<'_, Db>, input: NewWebhook) -> CommandResult<Webhook> {
+    let url = validate_url(&input.url)?;
+    let events = validate_events(&input.events)?;
+    let now = now_utc();
+    let hook = Webhook {
+        id: uuid::Uuid::new_v4().to_string(),
+        url,
+        events,
+        secret: new_secret(),
+        enabled: true,
+        last_status: None,
+        last_error: None,
+        last_delivered_at: None,
+        pending: 0,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    db.with_conn(|conn| write(conn, &hook))?;
+    Ok(hook)
+}
+
+#[tauri::command]
+pub fn update_webhook(
+    db: State<'_, Db>,
+    id: String,
+    patch: WebhookPatch,
+) -> CommandResult<Webhook> {
+    let url = patch.url.as_deref().map(validate_url).transpose()?;
+    let events = patch.events.as_deref().map(validate_events).transpose()?;
+    db.with_conn(|conn| {
+        let Some(mut hook) = find(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Webhook not found: {id}"
+            ))));
+        };
+        if let Some(url) = url {
+            hook.url = url;
+        }
+        if let Some(events) = events {
+            hook.events = events;
+        }
+        if let Some(enabled) = patch.enabled {
+            hook.enabled = enabled;
+        }
+        hook.updated_at = now_utc();
+        let tx = conn.transaction()?;
+        write(&tx, &hook)?;
+        if !hook.enabled {
+            // What happened while it was on isn't sent once it's back.
+            tx.execute(
+                "DELETE FROM webhook_deliveries WHERE webhook_id = ?1",
+                params![hook.id],
+            )?;
+            hook.pending = 0;
+        }
+        tx.commit()?;
+        Ok(Ok(hook))
+    })?
+}
+
+/// Delete a hook along with its undelivered posts.
+#[tauri::command]
+pub fn delete_webhook(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    let deleted =
+        db.with_conn(|conn| conn.execute("DELETE FROM webhooks WHERE id = ?1", params![id]))?;
+    if deleted == 0 {
+        return Err(CommandError::not_found(format!("Webhook not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Give a hook a new secret, for when the old one leaked.
+#[tauri::command]
+pub fn regenerate_webhook_secret(db: State<'_, Db>, id: String) -> CommandResult<Webhook> {
+    db.with_conn(|conn| {
+        let Some(mut hook) = find(conn, &id)? else {
+            return Ok(Err(CommandError::not_found(format!(
+                "Webhook not found: {id}"
+            ))));
+        };
+        hook.secret = new_secret();
+        hook.updated_at = now_utc();
+        write(conn, &hook)?;
+        Ok(Ok(hook))
+    })?
+}
+
+/// Queue a `ping` to a hook, even a disabled one, to check the receiving
+/// end. The outcome shows up on the hook like any delivery's.
+#[tauri::command]
+pub fn test_webhook(db: State<'_, Db>, id: String) -> CommandResult<()> {
+    db.with_conn(|conn| {
+        if find(conn, &id)?.is_none() {
+            return Ok(Err(CommandError::not_found(format!(
+                "Webhook not found: {id}"
+            ))));
+        }
+        enqueue(conn, PING, &json!({ "webhook_id": id }), Some(&id))?;
+        Ok(Ok(()))
+    })??;
+    QUEUED.notify_one();
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+use tauri::utils::config::WindowEffectsConfig;
+use tauri::window::{Color, Effect};
+use tauri::{AppHandle, Manager};
+
+use crate::error::{CommandError, CommandResult};
+
+/// What a window can do on this platform, so the frontend can fall back to
+/// an opaque themed background when blur or transparency is unavailable.
+#[derive(Debug, Serialize)]
+pub struct WindowEffectsSupport {
+    pub transparency: bool,
+    pub effects: Vec<Effect>,
+}
+
+fn supported_effects() -> Vec<Effect> {
+    if cfg!(target_os = "windows") {
+        vec![Effect::Mica, Effect::Acrylic, Effect::Blur, Effect::Tabbed]
+    } else if cfg!(target_os = "macos") {
+        vec![
+            Effect::HudWindow,
+            Effect::Popover,
+            Effect::Sidebar,
+            Effect::UnderWindowBackground,
+        ]
+    } else {
+        // Linux: blur is up to the compositor; tauri has no effects backend.
+        Vec::new()
+    }
+}
+
+/// Whether the window labelled `label` can show what's behind it: it must
+/// be created transparent, and macOS only allows that through its private
+/// API.
+fn transparency(app: &AppHandle, label: &str) -> bool {
+    let config = &app.config().app;
+    let transparent = config
+        .windows
+        .iter()
+        .any(|window| window.label == label && window.transparent);
+    transparent && (!cfg!(target_os = "macos") || config.macos_private_api)
+}
+
+/// Show the popup labelled `label` in front of everything, or hide it if
+/// it's already showing.
+pub fn toggle_popup(app: &AppHandle, label: &str) {
+    let Some(window) = app.get_webview_window(label) else {
+        tracing::warn!("popup window missing: {label}");
+        return;
+    };
+    if window.is_visible().unwrap_or(false) {
+        let _ = window.hide();
+    } else {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// What the window labelled `label` supports. Effects need a transparent
+/// window to show through, so there are none without transparency.
+#[tauri::command]
+pub fn get_window_effects_support(app: AppHandle, label: String) -> WindowEffectsSupport {
+    let transparency = transparency(&app, &label);
+    WindowEffectsSupport {
+        transparency,
+        effects: if transparency {
+            supported_effects()
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+/// Apply (or clear, with `effect: None`) a native material on a window
+/// configured transparent, i.e. the quick-add and mini-timer popups.
+/// `transparent` clears the webview background so the material shows
+/// through.
+#[tauri::command]
+pub fn set_window_effect(
+    app: AppHandle,
+    label: String,
+    effect: Option<Effect>,
+    transparent: bool,
+) -> CommandResult<()> {
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| CommandError::not_found(format!("Window not found: {label}")))?;
+
+    if (transparent || effect.is_some()) && !transparency(&app, &label) {
+        return Err(CommandError::invalid(format!(
+            "Window can't be transparent: {label}"
+        )));
+    }
+    if let Some(effect) = effect {
+        if !supported_effects().contains(&effect) {
+            return Err(CommandError::invalid(format!(
+                "Window effect not supported on this platform: {effect:?}"
+            )));
+        }
+    }
+
+    let background = if transparent {
+        Some(Color(0, 0, 0, 0))
+    } else {
+        None
+    };
+    window
+        .set_background_color(background)
+        .map_err(|e| format!("Failed to set window background: {e}"))?;
+
+    let config = effect.map(|effect| WindowEffectsConfig {
+        effects: vec![effect],
+        ..Default::default()
+    });
+    Ok(window
+        .set_effects(config)
+        .map_err(|e| format!("Failed to set window effects: {e}"))?)
+}
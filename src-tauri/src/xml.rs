@@ -0,0 +1,207 @@
+use crate::error::{CommandError, CommandResult};
+
+/// A parsed XML element. Names are local names: namespace prefixes are
+/// dropped, which is enough for the WebDAV responses read here.
+#[derive(Debug, Clone, Default)]
+pub struct Element {
+    pub name: String,
+    /// Attributes by local name, values decoded.
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// Character data directly inside this element, entities decoded.
+    pub text: String,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// The first element called `name` anywhere below this one.
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|c| {
+            if c.name == name {
+                Some(c)
+            } else {
+                c.find(name)
+            }
+        })
+    }
+
+    /// Text of the first descendant called `name`, trimmed.
+    pub fn find_text(&self, name: &str) -> Option<String> {
+        self.find(name)
+            .map(|e| e.text.trim().to_string())
+            .filter(|t| !t.is_empty())
+    }
+}
+
+fn local_name(tag: &str) -> String {
+    let name = tag.split_whitespace().next().unwrap_or("");
+    let name = name.trim_end_matches('/');
+    name.rsplit(':').next().unwrap_or(name).to_string()
+}
+
+/// `key="value"` pairs after the tag name. Namespace declarations are kept
+/// like any other attribute.
+fn parse_attrs(tag: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = tag
+        .trim_end_matches('/')
+        .trim_start_matches(|c: char| !c.is_whitespace());
+    while let Some((key, after)) = rest.split_once('=') {
+        let after = after.trim_start();
+        let Some(quote) = after.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(end) = after[1..].find(quote) else {
+            break;
+        };
+        attrs.push((local_name(key.trim()), decode(&after[1..1 + end])));
+        rest = &after[end + 2..];
+    }
+    attrs
+}
+
+fn decode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Escape text for use inside an element or attribute.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// End of the tag starting at `from`, skipping `>` inside quoted attributes.
+fn tag_end(text: &str, from: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text[from..].char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('>', None) => return Some(from + i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse a document into its root element. Comments, processing
+/// instructions and doctypes are skipped; CDATA is kept as text.
+pub fn parse(text: &str) -> CommandResult<Element> {
+    let mut stack: Vec<Element> = vec![Element::default()];
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find('<') {
+        let start = pos + offset;
+        if let Some(top) = stack.last_mut() {
+            top.text.push_str(&decode(&text[pos..start]));
+        }
+        let rest = &text[start..];
+        if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let end = body
+                .find("]]>")
+                .ok_or_else(|| CommandError::invalid("Unterminated CDATA section"))?;
+            if let Some(top) = stack.last_mut() {
+                top.text.push_str(&body[..end]);
+            }
+            pos = start + "<![CDATA[".len() + end + "]]>".len();
+            continue;
+        }
+        if rest.starts_with("<!--") {
+            let end = rest
+                .find("-->")
+                .ok_or_else(|| CommandError::invalid("Unterminated comment"))?;
+            pos = start + end + "-->".len();
+            continue;
+        }
+        let end = tag_end(text, start).ok_or_else(|| CommandError::invalid("Unterminated tag"))?;
+        let tag = &text[start + 1..end];
+        pos = end + 1;
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let done = stack
+                .pop()
+                .ok_or_else(|| CommandError::invalid("Unbalanced closing tag"))?;
+            if done.name != local_name(name) {
+                return Err(CommandError::invalid(format!(
+                    "Mismatched closing tag </{name}>"
+                )));
+            }
+            let parent = stack
+                .last_mut()
+                .ok_or_else(|| CommandError::invalid("Unbalanced closing tag"))?;
+            parent.children.push(done);
+            continue;
+        }
+        let element = Element {
+            name: local_name(tag),
+            attrs: parse_attrs(tag),
+            ..Element::default()
+        };
+        if tag.ends_with('/') {
+            if let Some(top) = stack.last_mut() {
+                top.children.push(element);
+            }
+        } else {
+            stack.push(element);
+        }
+    }
+    if stack.len() != 1 {
+        return Err(CommandError::invalid("Unclosed element"));
+    }
+    stack
+        .pop()
+        .and_then(|document| document.children.into_iter().next())
+        .ok_or_else(|| CommandError::invalid("Empty XML document"))
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::data_dir;
+use crate::error::{CommandError, CommandResult, ResultExt};
+use crate::session::write_atomic;
+
+const ZOOM_FILE: &str = "zoom.json";
+const MIN_ZOOM: f64 = 0.5;
+const MAX_ZOOM: f64 = 3.0;
+const ZOOM_STEP: f64 = 0.1;
+
+pub struct ZoomState {
+    pub factor: Mutex<f64>,
+}
+
+impl ZoomState {
+    pub fn new() -> Self {
+        Self {
+            factor: Mutex::new(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ZoomFile {
+    factor: f64,
+}
+
+fn zoom_path(app: &AppHandle) -> CommandResult<PathBuf> {
+    let dir = data_dir::app_config_dir(app)?;
+    fs::create_dir_all(&dir).context("Failed to create app config dir")?;
+    Ok(dir.join(ZOOM_FILE))
+}
+
+fn clamp_zoom(factor: f64) -> f64 {
+    // Round to the step so repeated Ctrl+= / Ctrl+- don't drift.
+    let rounded = (factor / ZOOM_STEP).round() * ZOOM_STEP;
+    rounded.clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+/// Set, persist, and apply the zoom factor to the main window.
+pub fn apply_zoom(app: &AppHandle, state: &ZoomState, factor: f64) -> CommandResult<f64> {
+    if !factor.is_finite() {
+        return Err(CommandError::invalid("Invalid zoom factor"));
+    }
+    let factor = clamp_zoom(factor);
+
+    if let Some(window) = app.get_webview_window("main") {
+        window
+            .set_zoom(factor)
+            .map_err(|e| format!("Failed to set zoom: {e}"))?;
+    }
+
+    *state.factor.lock().map_err(|_| "Lock poisoned")? = factor;
+
+    let body = serde_json::to_vec(&ZoomFile { factor }).map_err(|e| e.to_string())?;
+    write_atomic(&zoom_path(app)?, &body)?;
+    Ok(factor)
+}
+
+
+/// Restore the persisted zoom factor at startup.
+pub fn restore_zoom(app: &AppHandle) {
+    let Ok(path) = zoom_path(app) else {
+        return;
+    };
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    match serde_json::from_str::<ZoomFile>(&content) {
+        Ok(saved) => {
+            let state = app.state::<ZoomState>();
+            if let Err(error) = apply_zoom(app, &state, saved.factor) {
+                tracing::warn!("restore failed: {error}");
+            }
+        }
+        Err(error) => tracing::warn!("ignoring unreadable {ZOOM_FILE}: {error}"),
+    }
+}
+
+#[tauri::command]
+pub fn set_zoom(app: AppHandle, state: State<'_, ZoomState>, factor: f64) -> CommandResult<f64> {
+    apply_zoom(&app, &state, factor)
+}
+
+/// Zoom in or out by `steps` of 10%, as Ctrl+= and Ctrl+- do.
+#[tauri::command]
+pub fn step_zoom(app: AppHandle, state: State<'_, ZoomState>, steps: i32) -> CommandResult<f64> {
+    let current = *state.factor.lock().map_err(|_| "Lock poisoned")?;
+    apply_zoom(&app, &state, current + f64::from(steps) * ZOOM_STEP)
+}
+
+#[tauri::command]
+pub fn get_zoom(state: State<'_, ZoomState>) -> CommandResult<f64> {
+    Ok(*state.factor.lock().map_err(|_| "Lock poisoned")?)
+}
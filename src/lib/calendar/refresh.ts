@@ -2,6 +2,7 @@ import type { CalendarCache, CalendarEvent } from '$lib/domain/calendar';
 import type { Meta } from '$lib/domain/meta';
 import { saveCalendarCache, saveMeta } from '$lib/storage/storage';
 import { getOffsetDate } from '$lib/domain/task';
+import { errorMessage } from '$lib/platform/errors';
 import {
 	buildFetchWindow,
 	fetchCalendarEvents,
@@ -104,7 +105,7 @@ function createDefaultFetchIcs() {
 				console.warn('[ICS] Tauri fetch_url failed:', err);
 				// Don't fall back - if Tauri is available but fetch_url fails,
 				// browser fetch will also fail due to CORS
-				throw new Error(`Tauri fetch failed: ${err instanceof Error ? err.message : err}`);
+				throw new Error(`Tauri fetch failed: ${errorMessage(err)}`);
 			}
 		}
 
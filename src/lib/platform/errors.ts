@@ -0,0 +1,57 @@
+/**
+ * Errors rejected by backend commands: a `code` to branch on, a message to
+ * show, whether retrying may help and, for sync errors, the provider.
+ */
+
+export type CommandErrorCode =
+	| 'not_found'
+	| 'invalid_input'
+	| 'unauthorized'
+	| 'rate_limited'
+	| 'network'
+	| 'unavailable'
+	| 'conflict'
+	| 'storage'
+	| 'locked'
+	| 'busy'
+	| 'internal';
+
+export interface CommandError {
+	code: CommandErrorCode;
+	message: string;
+	retryable: boolean;
+	provider: string | null;
+}
+
+/** What the user can do about an error, for the button next to it. */
+export type RecoveryAction = 'reconnect' | 'retry' | 'unlock' | 'reload' | 'none';
+
+export function isCommandError(value: unknown): value is CommandError {
+	return (
+		typeof value === 'object' &&
+		value !== null &&
+		typeof (value as CommandError).code === 'string' &&
+		typeof (value as CommandError).message === 'string'
+	);
+}
+
+/** A message for any rejection: command errors, `Error`s or plain values. */
+export function errorMessage(value: unknown): string {
+	if (isCommandError(value)) return value.message;
+	if (value instanceof Error) return value.message;
+	return String(value);
+}
+
+export function recoveryAction(value: unknown): RecoveryAction {
+	if (!isCommandError(value)) return 'none';
+	switch (value.code) {
+		case 'unauthorized':
+			return 'reconnect';
+		case 'locked':
+			return 'unlock';
+		case 'conflict':
+			return 'reload';
+		default:
+			return value.retryable ? 'retry' : 'none';
+	}
+}
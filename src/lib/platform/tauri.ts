@@ -1,4 +1,5 @@
 import { isTauri } from '@tauri-apps/api/core';
+import { errorMessage } from './errors';
 
 type TauriInvoke = typeof import('@tauri-apps/api/core').invoke;
 
@@ -77,7 +78,7 @@ export async function getTauriInvoke(options: TauriRetryOptions = {}): Promise<T
 			}
 		}
 
-		throw new Error(`Tauri invoke not ready after ${attempt} attempts: ${String(lastError)}`);
+		throw new Error(`Tauri invoke not ready after ${attempt} attempts: ${errorMessage(lastError)}`);
 	})().finally(() => {
 		pendingInvoke = null;
 	});
@@ -0,0 +1,50 @@
+/**
+ * On launch, tells the user about crashes since they last looked and
+ * offers to save the report, with recent logs, to attach to a bug report.
+ */
+
+interface CrashReport {
+	id: string;
+	kind: 'panic' | 'webview';
+	created_at: string;
+	app_version: string;
+	os: string;
+	message: string;
+	location: string | null;
+	thread: string | null;
+	backtrace: string | null;
+	seen: boolean;
+}
+
+export async function offerCrashReports(): Promise<void> {
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		const { ask, save } = await import('@tauri-apps/plugin-dialog');
+
+		const reports = await invoke<CrashReport[]>('list_crash_reports', { unseenOnly: true });
+		if (reports.length === 0) return;
+
+		const latest = reports[0];
+		const what = latest.kind === 'webview' ? 'The window stopped responding' : 'DayLight crashed';
+		const more = reports.length > 1 ? ` (${reports.length} crashes since you last checked)` : '';
+		const when = new Date(latest.created_at).toLocaleString();
+		const wantsReport = await ask(
+			`${what} on ${when}${more}:\n\n${latest.message}\n\nSave a crash report to attach to a bug report?`,
+			{ title: 'DayLight crash report', kind: 'warning', okLabel: 'Save report…', cancelLabel: 'Dismiss' }
+		);
+		if (wantsReport) {
+			const path = await save({
+				defaultPath: `daylight-${latest.id}.txt`,
+				filters: [{ name: 'Text', extensions: ['txt'] }]
+			});
+			if (path) {
+				await invoke('export_crash_report', { id: latest.id, path });
+			}
+		}
+		for (const report of reports) {
+			await invoke('mark_crash_report_seen', { id: report.id });
+		}
+	} catch (err) {
+		console.error('[crash-reports] Failed to offer crash reports:', err);
+	}
+}
@@ -11,6 +11,8 @@ type RGB = [number, number, number];
 interface GtkThemeColors {
 	colors: Record<string, string>;
 	prefer_dark: boolean;
+	/** Whether prefer_dark comes from the theme schedule rather than GTK. */
+	scheduled: boolean;
 	theme_path: string | null;
 }
 
@@ -499,7 +501,8 @@ export function applyGtkTheme(data: GtkThemeColors): void {
 		if (!windowBg) return null;
 		return luminance(windowBg) < 0.4;
 	})();
-	const isDark = inferredDark ?? preferDark;
+	// A theme schedule decides over the palette's own brightness.
+	const isDark = data.scheduled ? preferDark : (inferredDark ?? preferDark);
 
 	// 1. Set data-theme for dark/light CSS branch selection in components
 	const baseTheme = isDark ? 'flexoki-dark' : 'flexoki-light';
@@ -0,0 +1,62 @@
+/**
+ * Forwards uncaught webview errors and console errors/warnings to the
+ * backend log (`log_from_frontend`), so they land in the same rotated
+ * files as backend messages.
+ */
+
+type Level = 'error' | 'warn' | 'info' | 'debug';
+
+let installed = false;
+let forwarding = false;
+
+function describe(value: unknown): string {
+	if (value instanceof Error) return value.stack ?? `${value.name}: ${value.message}`;
+	if (typeof value === 'string') return value;
+	try {
+		return JSON.stringify(value);
+	} catch {
+		return String(value);
+	}
+}
+
+async function send(level: Level, message: string): Promise<void> {
+	// A failing invoke logs to the console itself; don't loop on it.
+	if (forwarding) return;
+	forwarding = true;
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		await invoke('log_from_frontend', { level, message });
+	} catch {
+		// Tauri not available (browser dev mode)
+	} finally {
+		forwarding = false;
+	}
+}
+
+export function logToBackend(level: Level, ...parts: unknown[]): void {
+	void send(level, parts.map(describe).join(' '));
+}
+
+export function initLogSink(): void {
+	if (installed || typeof window === 'undefined') return;
+	installed = true;
+
+	window.addEventListener('error', (event) => {
+		const where = event.filename ? ` (${event.filename}:${event.lineno}:${event.colno})` : '';
+		logToBackend('error', `Uncaught: ${describe(event.error ?? event.message)}${where}`);
+	});
+	window.addEventListener('unhandledrejection', (event) => {
+		logToBackend('error', `Unhandled rejection: ${describe(event.reason)}`);
+	});
+
+	const originalError = console.error.bind(console);
+	const originalWarn = console.warn.bind(console);
+	console.error = (...args: unknown[]) => {
+		originalError(...args);
+		logToBackend('error', ...args);
+	};
+	console.warn = (...args: unknown[]) => {
+		originalWarn(...args);
+		logToBackend('warn', ...args);
+	};
+}
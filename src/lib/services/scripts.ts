@@ -0,0 +1,32 @@
+/**
+ * Shows the notifications user scripts send with `notify`. Scripts
+ * themselves run in the backend when their event happens.
+ */
+
+interface ScriptNotification {
+	script: string;
+	title: string;
+	body: string | null;
+}
+
+async function show({ script, title, body }: ScriptNotification): Promise<void> {
+	if (!('Notification' in window)) {
+		console.info(`[scripts] ${script}: ${title}${body ? ` — ${body}` : ''}`);
+		return;
+	}
+	if (Notification.permission === 'default') {
+		await Notification.requestPermission();
+	}
+	if (Notification.permission === 'granted') {
+		new Notification(title, { body: body ?? undefined, tag: `script:${script}` });
+	} else {
+		console.info(`[scripts] ${script}: ${title}${body ? ` — ${body}` : ''}`);
+	}
+}
+
+export async function listenForScriptNotifications(): Promise<() => void> {
+	const { listen } = await import('@tauri-apps/api/event');
+	return listen<ScriptNotification>('script-notification', (event) => {
+		void show(event.payload);
+	});
+}
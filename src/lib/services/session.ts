@@ -0,0 +1,55 @@
+/**
+ * Graceful restarts: the UI state worth keeping is handed to the backend,
+ * pending writes are flushed, and the relaunched app picks up where it left
+ * off.
+ */
+
+import { goto } from '$app/navigation';
+import { settleWrites } from '$lib/storage/storage';
+import { markdownStore, setSelectedDate } from '$lib/stores/markdown-store.svelte';
+
+interface RestartState {
+	route?: string;
+	selectedDate?: string;
+	scrollTop?: number;
+}
+
+function scroller(): Element | null {
+	return document.querySelector('.main-content');
+}
+
+/** Restart the app, coming back to the same view, day and scroll position. */
+export async function restartApp(): Promise<void> {
+	const { invoke } = await import('@tauri-apps/api/core');
+	const uiState: RestartState = {
+		route: `${location.pathname}${location.search}`,
+		selectedDate: markdownStore.selectedDate,
+		scrollTop: scroller()?.scrollTop ?? 0
+	};
+	await invoke('restart_app', { uiState });
+}
+
+/** Flush pending writes when the backend is about to restart, then let it go ahead. */
+export async function listenForRestart(): Promise<() => void> {
+	const [{ listen }, { invoke }] = await Promise.all([
+		import('@tauri-apps/api/event'),
+		import('@tauri-apps/api/core')
+	]);
+	return listen('daylight:before-restart', async () => {
+		await settleWrites();
+		await invoke('restart_ready');
+	});
+}
+
+/** Put back the UI state saved before the last restart, if there is one. */
+export async function restoreSession(): Promise<void> {
+	const { invoke } = await import('@tauri-apps/api/core');
+	const restored = await invoke<RestartState | null>('take_restart_state');
+	if (!restored) return;
+	if (restored.selectedDate) setSelectedDate(restored.selectedDate);
+	if (restored.route) await goto(restored.route);
+	if (restored.scrollTop) {
+		const top = restored.scrollTop;
+		requestAnimationFrame(() => scroller()?.scrollTo({ top }));
+	}
+}
@@ -0,0 +1,115 @@
+/**
+ * App settings, kept by the backend (`settings.json`) so the tray, sync and
+ * reminders can read them too. `localStorage` keeps a copy under the old
+ * keys, which the layout reads synchronously before Tauri is ready.
+ */
+
+export interface Settings {
+	version: number;
+	appearance: { theme: string };
+	data_path: string | null;
+	editor_path: string | null;
+	tray: { start_hidden: boolean; show_timer: boolean };
+	reminders: { solar: boolean };
+	privacy: { usage_stats: boolean };
+	debug: { shortcuts: boolean; gtk_theme: boolean };
+}
+
+export interface SettingsChanged {
+	keys: string[];
+	settings: Settings;
+}
+
+/** Old `localStorage` key → setting, as mapped by `import_legacy_settings`. */
+const MIRRORED: Record<string, (s: Settings) => string | boolean | null> = {
+	'daylight-theme': (s) => s.appearance.theme,
+	'daylight-data-path': (s) => s.data_path,
+	'daylight-editor-path': (s) => s.editor_path,
+	'daylight-shortcuts-debug': (s) => s.debug.shortcuts,
+	'daylight-gtk-debug': (s) => s.debug.gtk_theme
+};
+
+let unlistenChanges: (() => void) | null = null;
+
+function mirror(settings: Settings): void {
+	try {
+		for (const [key, read] of Object.entries(MIRRORED)) {
+			const value = read(settings);
+			if (value === null || value === false) {
+				localStorage.removeItem(key);
+			} else {
+				localStorage.setItem(key, value === true ? '1' : value);
+			}
+		}
+	} catch {
+		// localStorage unavailable
+	}
+}
+
+export async function getSettings(): Promise<Settings | null> {
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		return await invoke<Settings>('get_settings');
+	} catch {
+		return null;
+	}
+}
+
+/** Set one setting by dotted key, e.g. `appearance.theme`. */
+export async function setSetting(key: string, value: unknown): Promise<Settings | null> {
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		const settings = await invoke<Settings>('set_setting', { key, value });
+		mirror(settings);
+		return settings;
+	} catch (err) {
+		console.error(`[settings] Failed to save ${key}:`, err);
+		return null;
+	}
+}
+
+/** Reset one setting, or all of them without a key, to the default. */
+export async function resetSettings(key?: string): Promise<Settings | null> {
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		const settings = await invoke<Settings>('reset_settings', { key: key ?? null });
+		mirror(settings);
+		return settings;
+	} catch (err) {
+		console.error('[settings] Failed to reset settings:', err);
+		return null;
+	}
+}
+
+export async function onSettingsChanged(
+	handler: (change: SettingsChanged) => void
+): Promise<() => void> {
+	try {
+		const { listen } = await import('@tauri-apps/api/event');
+		return await listen<SettingsChanged>('settings-changed', (event) => handler(event.payload));
+	} catch {
+		return () => {};
+	}
+}
+
+/**
+ * Hand settings saved by older versions in `localStorage` to the backend,
+ * then keep the local copy in step with it. Call once Tauri is ready.
+ */
+export async function syncSettings(): Promise<Settings | null> {
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		const values: Record<string, string> = {};
+		for (const key of Object.keys(MIRRORED)) {
+			const value = localStorage.getItem(key);
+			if (value !== null) values[key] = value;
+		}
+		const settings = await invoke<Settings>('import_legacy_settings', { values });
+		mirror(settings);
+		unlistenChanges ??= await onSettingsChanged(({ settings }) => mirror(settings));
+		return settings;
+	} catch (err) {
+		console.error('[settings] Failed to sync settings:', err);
+		return null;
+	}
+}
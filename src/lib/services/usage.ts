@@ -0,0 +1,58 @@
+/**
+ * Local usage statistics, counted in the app's own database only when the
+ * user turns on `privacy.usage_stats`. Nothing here goes over the network.
+ */
+
+export interface FeatureUsage {
+	feature: string;
+	count: number;
+	total_ms: number;
+	average_ms: number | null;
+	last_used: string;
+}
+
+export interface PeriodUsage {
+	period: string;
+	count: number;
+}
+
+export interface UsageSummary {
+	enabled: boolean;
+	first_day: string | null;
+	last_day: string | null;
+	active_days: number;
+	total: number;
+	features: FeatureUsage[];
+	by_month: PeriodUsage[];
+	busiest_day: PeriodUsage | null;
+}
+
+export interface YearInReview {
+	year: number;
+	usage: UsageSummary;
+	stats: Record<string, unknown>;
+}
+
+/** Count one use of a feature, e.g. `view:calendar`. Ignored when off. */
+export async function trackUsage(feature: string, durationMs?: number): Promise<void> {
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		await invoke('record_usage', { feature, durationMs: durationMs ?? null });
+	} catch {
+		// Counting is best effort
+	}
+}
+
+export async function getYearInReview(year: number): Promise<YearInReview> {
+	const { invoke } = await import('@tauri-apps/api/core');
+	return invoke<YearInReview>('get_year_in_review', {
+		year,
+		zone: Intl.DateTimeFormat().resolvedOptions().timeZone
+	});
+}
+
+/** Delete usage counted before `before` (`YYYY-MM-DD`), or all of it. */
+export async function purgeUsage(before?: string): Promise<number> {
+	const { invoke } = await import('@tauri-apps/api/core');
+	return invoke<number>('purge_usage_stats', { before: before ?? null });
+}
@@ -0,0 +1,41 @@
+/**
+ * Native blur and transparency for the popup windows, falling back to the
+ * theme's opaque background wherever the platform or window can't do it.
+ */
+
+export interface WindowEffectsSupport {
+	transparency: boolean;
+	effects: string[];
+}
+
+/**
+ * Give the current window the first material the platform offers. Returns
+ * whether it went see-through; when it didn't, the page keeps its opaque
+ * background.
+ */
+export async function applyPopupEffect(): Promise<boolean> {
+	try {
+		const [{ invoke }, { getCurrentWindow }] = await Promise.all([
+			import('@tauri-apps/api/core'),
+			import('@tauri-apps/api/window')
+		]);
+		const label = getCurrentWindow().label;
+		const support = await invoke<WindowEffectsSupport>('get_window_effects_support', { label });
+		if (!support.transparency) return false;
+		await invoke('set_window_effect', {
+			label,
+			effect: support.effects[0] ?? null,
+			transparent: true
+		});
+		return true;
+	} catch (err) {
+		console.warn('[window-effects] Falling back to an opaque window:', err);
+		return false;
+	}
+}
+
+/** Hide the current window; popups are hidden rather than closed. */
+export async function hidePopup(): Promise<void> {
+	const { getCurrentWindow } = await import('@tauri-apps/api/window');
+	await getCurrentWindow().hide();
+}
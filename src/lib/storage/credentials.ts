@@ -0,0 +1,129 @@
+/**
+ * Keeps secrets out of meta.json, which often sits in a synced folder:
+ * the Google Calendar tokens and the secret ICS URL live in the OS keyring
+ * and are only in meta while the app runs.
+ */
+
+import type { GoogleCalendarSettings, IcsSources, Meta } from '$lib/domain/meta';
+import { hasTauriInvoke } from '$lib/platform/tauri';
+
+function patchGoogle(meta: Meta, patch: Partial<GoogleCalendarSettings>): Meta {
+	return meta.googleCalendar ? { ...meta, googleCalendar: { ...meta.googleCalendar, ...patch } } : meta;
+}
+
+function patchIcs(meta: Meta, patch: Partial<IcsSources>): Meta {
+	return meta.icsSources ? { ...meta, icsSources: { ...meta.icsSources, ...patch } } : meta;
+}
+
+/** Where meta.json keeps each secret, by keyring entry name. */
+const SECRETS = {
+	'google-calendar:client-secret': {
+		read: (meta: Meta) => meta.googleCalendar?.clientSecret ?? null,
+		write: (meta: Meta, clientSecret: string | null) => patchGoogle(meta, { clientSecret })
+	},
+	'google-calendar:access-token': {
+		read: (meta: Meta) => meta.googleCalendar?.accessToken ?? null,
+		write: (meta: Meta, accessToken: string | null) => patchGoogle(meta, { accessToken })
+	},
+	'google-calendar:refresh-token': {
+		read: (meta: Meta) => meta.googleCalendar?.refreshToken ?? null,
+		write: (meta: Meta, refreshToken: string | null) => patchGoogle(meta, { refreshToken })
+	},
+	'ics:secret-url': {
+		read: (meta: Meta) => meta.icsSources?.secretUrl ?? null,
+		write: (meta: Meta, secretUrl: string | null) => patchIcs(meta, { secretUrl })
+	}
+} as const;
+
+type SecretName = keyof typeof SECRETS;
+
+interface CredentialMigration {
+	moved: { name: string; source: string; moved_at: string }[];
+	failed: { name: string; source: string; error: string }[];
+}
+
+const NAMES = Object.keys(SECRETS) as SecretName[];
+
+/** What the keyring holds, so unchanged secrets aren't written again. */
+const stored = new Map<SecretName, string | null>();
+
+/**
+ * Move secrets still in meta.json into the keyring. Returns meta without
+ * those that moved, to write back, and their names; secrets that couldn't
+ * be moved stay.
+ */
+export async function migrateCredentials(meta: Meta): Promise<{ meta: Meta; moved: string[] }> {
+	const values: Record<string, string> = {};
+	for (const name of NAMES) {
+		const value = SECRETS[name].read(meta);
+		if (value) values[name] = value;
+	}
+	if (!hasTauriInvoke() || Object.keys(values).length === 0) return { meta, moved: [] };
+
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		const report = await invoke<CredentialMigration>('migrate_credentials', {
+			source: 'meta.json',
+			values
+		});
+		let scrubbed = meta;
+		for (const { name } of report.moved) {
+			const secret = name as SecretName;
+			stored.set(secret, values[secret]);
+			scrubbed = SECRETS[secret].write(scrubbed, null);
+		}
+		for (const failure of report.failed) {
+			console.warn(`[credentials] ${failure.name} stays in meta.json: ${failure.error}`);
+		}
+		return { meta: scrubbed, moved: report.moved.map((m) => m.name) };
+	} catch (err) {
+		console.error('[credentials] Failed to move credentials to the keyring:', err);
+		return { meta, moved: [] };
+	}
+}
+
+/** Fill meta's empty secret fields from the keyring. */
+export async function withCredentials(meta: Meta): Promise<Meta> {
+	if (!hasTauriInvoke()) return meta;
+	try {
+		const { invoke } = await import('@tauri-apps/api/core');
+		const found = await invoke<Record<string, string>>('get_credentials', { names: NAMES });
+		let filled = meta;
+		for (const name of NAMES) {
+			const value = found[name] ?? null;
+			stored.set(name, value);
+			if (value && !SECRETS[name].read(filled)) {
+				filled = SECRETS[name].write(filled, value);
+			}
+		}
+		return filled;
+	} catch (err) {
+		console.error('[credentials] Failed to read credentials from the keyring:', err);
+		return meta;
+	}
+}
+
+/**
+ * Save meta's secrets to the keyring and return meta as it should be
+ * written to disk: without them, unless the keyring refused one.
+ */
+export async function withoutCredentials(meta: Meta): Promise<Meta> {
+	if (!hasTauriInvoke()) return meta;
+	const { invoke } = await import('@tauri-apps/api/core');
+	let scrubbed = meta;
+	for (const name of NAMES) {
+		const value = SECRETS[name].read(meta) || null;
+		try {
+			// Never clear a secret the keyring may hold but wasn't read.
+			const changed = stored.has(name) ? stored.get(name) !== value : value !== null;
+			if (changed) {
+				await invoke('set_credential', { name, value });
+				stored.set(name, value);
+			}
+			scrubbed = SECRETS[name].write(scrubbed, null);
+		} catch (err) {
+			console.error(`[credentials] Failed to save ${name} to the keyring:`, err);
+		}
+	}
+	return scrubbed;
+}
@@ -35,7 +35,7 @@ import {
 	type TimeEntry,
 	type ParsedMarkdown
 } from './frontmatter';
-import { getDataPath, ensureDataDir } from './storage';
+import { getDataPath, ensureDataDir, trackWrite } from './storage';
 import { generateConflictArchiveName, SYNCTHING_CONFLICT_PATTERN, DIR_CONFLICTS } from './constants';
 
 /**
@@ -453,13 +453,15 @@ export async function generateUniqueFilename(baseTitle: string): Promise<string>
  * Atomic write for markdown files
  */
 async function atomicWriteMarkdown(filePath: string, content: string): Promise<void> {
-	const tempPath = `${filePath}.tmp`;
+	return trackWrite(async () => {
+		const tempPath = `${filePath}.tmp`;
 
-	// Write to temp file
-	await writeTextFile(tempPath, content);
+		// Write to temp file
+		await writeTextFile(tempPath, content);
 
-	// Rename temp to final (atomic on most file systems)
-	await rename(tempPath, filePath);
+		// Rename temp to final (atomic on most file systems)
+		await rename(tempPath, filePath);
+	});
 }
 
 /**
@@ -46,6 +46,7 @@ import {
 	DEFAULT_DATA_FOLDER,
 	generateConflictArchiveName
 } from './constants';
+import { migrateCredentials, withCredentials, withoutCredentials } from './credentials';
 
 /**
  * Application data structure
@@ -330,11 +331,21 @@ export async function loadAllWithErrors(): Promise<AppData & { errors: LoadError
 		} else {
 			meta = nextMeta;
 		}
+
+		// Older versions kept tokens in meta.json; move them to the keyring
+		const secured = await migrateCredentials(meta);
+		if (secured.moved.length > 0) {
+			console.info('[storage] Moved to the keyring:', secured.moved.join(', '));
+			meta = secured.meta;
+			await writeTextFile(metaPath, JSON.stringify(meta, null, 2));
+		}
 	} else {
 		meta = createMeta();
 		await writeTextFile(metaPath, JSON.stringify(meta, null, 2));
 	}
 
+	meta = await withCredentials(meta);
+
 	// Update sync state in meta
 	meta.syncState = createSyncState({
 		tasksHash: loadedState.tasks?.hash ?? null,
@@ -451,10 +462,10 @@ export async function saveAll(
 		})
 	};
 
-	// Save meta (not atomic, less critical)
+	// Save meta (not atomic, less critical), its secrets in the keyring
 	await writeTextFile(
 		await join(dataPath, FILE_META),
-		JSON.stringify(updatedMeta, null, 2)
+		JSON.stringify(await withoutCredentials(updatedMeta), null, 2)
 	);
 
 	return { success: true };
@@ -464,13 +475,37 @@ export async function saveAll(
  * Atomic write: write to temp file, then rename
  */
 async function atomicWrite(filePath: string, content: string): Promise<void> {
-	const tempPath = filePath + '.tmp';
+	return trackWrite(async () => {
+		const tempPath = filePath + '.tmp';
+
+		// Write to temp file
+		await writeTextFile(tempPath, content);
 
-	// Write to temp file
-	await writeTextFile(tempPath, content);
+		// Rename temp to final (atomic on most file systems)
+		await rename(tempPath, filePath);
+	});
+}
+
+const pendingWrites = new Set<Promise<unknown>>();
+
+/**
+ * Run a write, remembering it until it settles so a restart can wait for it
+ */
+export function trackWrite<T>(write: () => Promise<T>): Promise<T> {
+	const pending = write();
+	pendingWrites.add(pending);
+	const forget = () => pendingWrites.delete(pending);
+	pending.then(forget, forget);
+	return pending;
+}
 
-	// Rename temp to final (atomic on most file systems)
-	await rename(tempPath, filePath);
+/**
+ * Wait for every write in flight, whether it succeeds or not
+ */
+export async function settleWrites(): Promise<void> {
+	while (pendingWrites.size > 0) {
+		await Promise.allSettled([...pendingWrites]);
+	}
 }
 
 /**
@@ -545,11 +580,13 @@ export async function saveTimeLogs(
  * Save meta only
  */
 export async function saveMeta(meta: Meta): Promise<void> {
-	const dataPath = await getDataPath();
-	await writeTextFile(
-		await join(dataPath, FILE_META),
-		JSON.stringify(meta, null, 2)
-	);
+	return trackWrite(async () => {
+		const dataPath = await getDataPath();
+		await writeTextFile(
+			await join(dataPath, FILE_META),
+			JSON.stringify(await withoutCredentials(meta), null, 2)
+		);
+	});
 }
 
 /**